@@ -0,0 +1,116 @@
+//! Admin approval over a second channel: `pg-agent approve`, and the
+//! waiting side used by the agent's confirmer.
+
+use anyhow::Result;
+use postgres_agent_safety::{
+    current_user, ApprovalRecord, ApprovalStore, AuditLogger, ConfirmationLevel,
+    ConfirmationRequest, ConfirmationStatus,
+};
+use std::time::Duration;
+use tracing::error;
+
+use crate::commands::{open_audit_log, CommandContext};
+
+/// Approve or deny an operation waiting for admin approval in the shared
+/// approvals directory, as the current user; without an ID, list the
+/// operations still waiting.
+pub async fn approve_request(
+    cmd: &CommandContext,
+    id: Option<&str>,
+    deny: bool,
+    output: &str,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let store = ApprovalStore::open(config.paths.approvals_dir());
+
+    let Some(id) = id else {
+        let pending: Vec<ApprovalRecord> = store
+            .list()?
+            .into_iter()
+            .filter(|record| record.status() == ConfirmationStatus::Pending)
+            .collect();
+        if output == "json" {
+            println!("{}", serde_json::to_string_pretty(&pending)?);
+        } else if pending.is_empty() {
+            println!("No operations waiting for approval in {}.", store.dir().display());
+        } else {
+            for record in &pending {
+                let request = &record.request;
+                println!(
+                    "{}  {} by {}, expires {}",
+                    request.id,
+                    request.created_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                    record.requested_by,
+                    request.expires_at().with_timezone(&chrono::Local).format("%H:%M:%S")
+                );
+                println!("    {}", request.operation);
+            }
+        }
+        return Ok(());
+    };
+
+    let user = current_user();
+    let record = store.decide(id, !deny)?;
+    let granted = record.request.status == ConfirmationStatus::Approved;
+    open_audit_log(&config).log_confirmation(
+        &user,
+        &record.request.sql,
+        ConfirmationLevel::AdminApproval.as_str(),
+        granted,
+    );
+    if output == "json" {
+        println!("{}", serde_json::to_string_pretty(&record)?);
+    } else {
+        let decision = if granted { "Approved" } else { "Denied" };
+        println!("{} for {}: {}", decision, record.requested_by, record.request.operation);
+    }
+    Ok(())
+}
+
+/// Put an operation needing admin approval in the shared approvals
+/// directory and wait until another user approves or denies it with
+/// `pg-agent approve`, or it expires after `ttl`.
+pub(crate) async fn await_second_approval(
+    store: &ApprovalStore,
+    prompt: &str,
+    ttl: Duration,
+    audit: &AuditLogger,
+) -> bool {
+    let user = current_user();
+    let level = ConfirmationLevel::AdminApproval;
+    let request =
+        ConfirmationRequest::new(prompt.to_string(), prompt.to_string(), level).with_ttl(ttl);
+    if let Err(e) = store.submit(&request) {
+        error!("Cannot request admin approval: {}", e);
+        return false;
+    }
+    eprintln!("{}", level.prompt_message(prompt));
+    eprintln!(
+        "Waiting up to {}s for another user to run:\n  pg-agent approve {}",
+        ttl.as_secs(),
+        request.id
+    );
+    let record = match store.wait(&request.id, Duration::from_secs(1)).await {
+        Ok(record) => record,
+        Err(e) => {
+            error!("Cannot read admin approval: {}", e);
+            return false;
+        }
+    };
+    let by = record.decided_by.as_deref().unwrap_or("unknown");
+    match record.status() {
+        ConfirmationStatus::Approved => {
+            eprintln!("Approved by {}.", by);
+            true
+        }
+        ConfirmationStatus::Expired => {
+            audit.log_confirmation_expired(&user, prompt, level.as_str());
+            eprintln!("No approval within {}s.", ttl.as_secs());
+            false
+        }
+        _ => {
+            eprintln!("Denied by {}.", by);
+            false
+        }
+    }
+}
@@ -1,34 +1,159 @@
 //! Command implementations for PostgreSQL Agent CLI.
 //!
-//! Contains all the command handler functions for the CLI.
+//! Holds the query and configuration commands along with the helpers the
+//! other command modules share: loading configuration, connecting, building
+//! the agent and printing results.
 
 use anyhow::{bail, Context, Result};
 use postgres_agent_config::safety::SafetyLevel as ConfigSafetyLevel;
-use postgres_agent_config::{AppConfig, ConfigLoader, DatabaseProfile};
-use postgres_agent_core::agent::{AgentConfig, AgentResponse, PostgresAgent};
+use postgres_agent_config::safety::SelectStarAction as ConfigSelectStarAction;
+use postgres_agent_config::safety::TenantMode as ConfigTenantMode;
+use postgres_agent_config::safety::RuleAction;
+use postgres_agent_config::{
+    AdminApprovalMode, AppConfig, BudgetAction, ConfigLoader, DatabaseProfile, LlmDataPolicy,
+    PathsConfig, Persona, ResponseCacheConfig, RunCeilings, SandboxConfig, SnapshotConfig,
+    ToolsConfig,
+};
+use postgres_agent_core::agent::{
+    AgentConfig, AgentResponse, AgentStats, ExecutedSql, PostgresAgent, ResultSet, TurnRecord,
+};
+use postgres_agent_core::events::{self, AgentEvent, EventReceiver};
+use postgres_agent_core::{
+    AgentError, Example, ExampleRetriever, QueryHistory, RunRecord, SessionStore, StatsStore,
+};
 use postgres_agent_core::agent::SafetyLevel as CoreSafetyLevel;
 use postgres_agent_db::executor::QueryResult;
-use postgres_agent_db::{DbConnection, DbConnectionConfig, QueryExecutor};
+use postgres_agent_db::value::{JsonResult, JsonRows};
+use postgres_agent_db::schema::{ColumnInfo, EnumType, SchemaTable, TableSecurity};
+use postgres_agent_db::{
+    DbConnection, DbConnectionConfig, DbError, NumericOutput, QueryExecutor,
+    SandboxMode, SessionSettings, TimeZoneMode, TypeRendering,
+};
 use postgres_agent_llm::client::LlmClient;
-use postgres_agent_llm::openai::OpenAiProvider;
+use postgres_agent_llm::{
+    capabilities, AnyProvider, EmbeddingsClient,
+    EmbeddingsConfig, OllamaEmbeddings, OpenAiEmbeddings, RequestLog, ResponseCache, SystemPrompt,
+    TokenUsage,
+    ANALYST_PERSONA, DBA_PERSONA, DEVELOPER_PERSONA,
+};
+use postgres_agent_llm::conversion::OpenAiToolDefinition;
 use postgres_agent_llm::provider::ProviderConfig;
-use postgres_agent_tools::ToolContext;
+use postgres_agent_safety::{
+    current_user, parse_operations, rotated_logs, ApprovalStore, AuditConfig, AuditLogger,
+    ConfirmationLevel, ConfirmationWorkflow, PiiDetector, PolicyAction, PolicyRule,
+    SafetyLevel as ValidatorSafetyLevel, SafetyValidator, SelectStarAction, SelectStarGuard,
+    TenantMode, TenantScope,
+};
+use postgres_agent_tools::built_in::CompareTool;
+use postgres_agent_tools::{
+    create_builtin_tools, BuiltInTool, Confirmer, IdempotencyLedger, ToolContext, ToolRegistry,
+    UndoLog,
+};
+use postgres_agent_util::codec::FileCodec;
+use postgres_agent_util::encrypt::EncryptionKey;
+use postgres_agent_util::number::NumberFormat;
+use postgres_agent_util::retention::{disk_usage, format_bytes};
 use std::io::Write;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
-use tracing::error;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
 
-use postgres_agent_cli::OutputFormat;
+use postgres_agent_cli::{OutputFormat, RunLimits};
+
+use crate::script::{self, note};
+use crate::shutdown;
+use crate::approvals::await_second_approval;
 
 // ============================================================================
 // Command Handlers
 // ============================================================================
 
+/// A question for the agent, with supplementary context from files or
+/// stdin.
+#[derive(Debug, Clone, Default)]
+pub struct Question {
+    /// The question.
+    pub text: String,
+    /// Context files, as (name, contents), in order.
+    pub context: Vec<(String, String)>,
+}
+
+impl Question {
+    /// Name of stdin in arguments.
+    const STDIN: &'static str = "-";
+
+    /// A question without context.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            context: Vec::new(),
+        }
+    }
+
+    /// Read a question from `query` arguments, or from stdin when they are
+    /// `-`, with the contents of `context_files` (`-` for stdin).
+    pub fn from_args(words: &[String], context_files: &[String]) -> Result<Self> {
+        let from_stdin = words.len() == 1 && words[0] == Self::STDIN;
+        let stdin_reads = context_files.iter().filter(|f| *f == Self::STDIN).count();
+        if stdin_reads + usize::from(from_stdin) > 1 {
+            bail!("Only one of the question and context files can be read from stdin");
+        }
+
+        let text = if from_stdin {
+            read_stdin().context("Failed to read the question from stdin")?.trim().to_string()
+        } else {
+            words.join(" ")
+        };
+        if text.trim().is_empty() {
+            bail!("No question given");
+        }
+
+        let context = context_files
+            .iter()
+            .map(|path| {
+                let contents = if path == Self::STDIN {
+                    read_stdin().context("Failed to read context from stdin")?
+                } else {
+                    std::fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read context file {}", path))?
+                };
+                let name = if path == Self::STDIN { "stdin" } else { path.as_str() };
+                Ok((name.to_string(), contents))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { text, context })
+    }
+
+    /// System message giving the model the context, if there is any.
+    fn context_message(&self) -> Option<String> {
+        if self.context.is_empty() {
+            return None;
+        }
+        let mut message = String::from(
+            "The user supplied the following context for their question. \
+             Use it together with the database to answer.",
+        );
+        for (name, contents) in &self.context {
+            message.push_str(&format!("\n\n--- {} ---\n{}", name, contents.trim_end()));
+        }
+        Some(message)
+    }
+}
+
+/// Read all of stdin.
+fn read_stdin() -> std::io::Result<String> {
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+    Ok(input)
+}
+
 /// Run a single query using the agent.
 pub async fn run_query(
-    query: &str,
-    config_path: &str,
+    question: &Question,
+    cmd: &CommandContext,
     profile_name: &str,
     output_format: &str,
     safety_level: Option<&str>,
@@ -38,22 +163,52 @@ pub async fn run_query(
     let start = std::time::Instant::now();
 
     // Load configuration
-    let config = load_config(config_path).await?;
+    let config = cmd.load_config().await?;
+    apply_retention(&config);
 
     // Get database profile
     let profile = get_profile(&config, profile_name)?;
 
     // Create database connection
-    let db = create_connection(&profile).await?;
+    let db = create_connection(&config, &profile).await?;
 
     // Create LLM client
     let llm_client = create_llm_client(&config)?;
 
     // Create agent with tools
-    let mut agent = create_agent(llm_client, &db, &config, safety_level, no_confirm)?;
+    let mut agent = create_agent(
+        llm_client,
+        &db,
+        &config,
+        profile_name,
+        safety_level,
+        no_confirm,
+        &cmd.overrides,
+    )
+    .await?;
+    if let Some(context) = question.context_message() {
+        agent.context.add_system_message(&context);
+    }
+    let query = question.text.as_str();
 
-    // Run the agent
-    let response = agent.run(query).await;
+    // Enforce the daily budget before spending anything
+    let mut stats_store = open_stats_store(&config);
+    if !check_daily_budget(&config, &stats_store, no_confirm)? {
+        bail!("Query cancelled: daily LLM budget reached");
+    }
+
+    // Run the agent, cancelling it if a shutdown signal arrives
+    let Some(response) = run_until_signal(&mut agent, query).await else {
+        record_usage(&config, &mut stats_store, profile_name, query, &mut agent, None);
+        shut_down(&config, &agent, &db).await;
+        return Err(shutdown::Interrupted::new("query cancelled").into());
+    };
+    let run = response.as_ref().ok();
+    record_usage(&config, &mut stats_store, profile_name, query, &mut agent, run);
+    if cmd.overrides.timing {
+        print_timings(agent.stats());
+    }
+    db.close().await;
 
     let duration_ms = start.elapsed().as_millis();
 
@@ -61,112 +216,75 @@ pub async fn run_query(
     match response {
         Ok(agent_response) => {
             if !quiet {
-                println!("\n{}", "=".repeat(60));
+                note!("\n{}", "=".repeat(60));
             }
 
             let format = OutputFormat::from_str(output_format).unwrap_or(OutputFormat::Table);
 
             if !quiet {
-                println!("Query: {}", query);
-                println!("Duration: {}ms", duration_ms);
-                println!("Iterations: {}", agent_response.iterations);
-                if let Some(sql) = &agent_response.executed_sql {
-                    println!("SQL: {}", sql);
+                note!("Query: {}", query);
+                note!("Duration: {}ms", duration_ms);
+                note!("Iterations: {}", agent_response.iterations);
+                let stats = agent.stats();
+                let cost = format_cost(stats.cost(&config.llm));
+                if stats.usage.is_empty() {
+                    let counter = agent.token_counter().name();
+                    let tokens = format_tokens(stats.tokens());
+                    note!("Tokens: {} (estimated, {}), est. cost: {}", tokens, counter, cost);
+                } else {
+                    note!("Tokens: {}, est. cost: {}", format_tokens(stats.tokens()), cost);
+                }
+                if let Some(request_id) = &agent_response.request_id {
+                    note!("Request ID: {}", request_id);
+                }
+                for statement in &agent_response.executed_sql {
+                    note!("SQL: {}", statement);
                 }
             }
 
-            print_response(&agent_response, format);
+            let numbers = number_format(&config);
+            print_response(
+                &agent_response,
+                format,
+                numbers,
+                numeric_output(&config),
+                cmd.overrides.show_intermediate,
+            );
 
             if !quiet {
-                println!("{}", "=".repeat(60));
+                note!("{}", "=".repeat(60));
             }
 
             Ok(())
         }
         Err(e) => {
             error!("Query failed: {}", e);
-            bail!("Agent error: {}", e);
-        }
-    }
-}
-
-/// Run interactive TUI mode.
-pub async fn run_interactive(
-    config_path: &str,
-    profile_name: &str,
-    safety_level: Option<&str>,
-    no_confirm: bool,
-) -> Result<()> {
-    println!("Starting interactive mode...");
-    println!("Profile: {}", profile_name);
-    println!("(TUI mode - basic CLI REPL active)\n");
-
-    // Load configuration
-    let config = load_config(config_path).await?;
-    let profile = get_profile(&config, profile_name)?;
-    let db = create_connection(&profile).await?;
-    let llm_client = create_llm_client(&config)?;
-    let mut agent = create_agent(llm_client, &db, &config, safety_level, no_confirm)?;
-
-    println!("PostgreSQL Agent Interactive Mode");
-    println!("Type 'exit' or 'quit' to exit.\n");
-
-    let stdin = std::io::stdin();
-    let mut input = String::new();
-
-    loop {
-        print!("> ");
-        std::io::stdout().flush()?;
-
-        input.clear();
-        stdin.read_line(&mut input)?;
-
-        let input = input.trim();
-        if input.is_empty() {
-            continue;
-        }
-
-        if input.eq_ignore_ascii_case("exit") || input.eq_ignore_ascii_case("quit") {
-            println!("Goodbye!");
-            break;
-        }
-
-        if input.eq_ignore_ascii_case("\\help") || input.eq_ignore_ascii_case("\\h") {
-            print_interactive_help();
-            continue;
+            Err(anyhow::Error::new(e).context("Agent error"))
         }
-
-        match agent.run(input).await {
-            Ok(response) => {
-                println!("\n{}", response.answer);
-                if let Some(sql) = &response.executed_sql {
-                    println!("[SQL: {}]", sql);
-                }
-            }
-            Err(e) => {
-                println!("Error: {}", e);
-            }
-        }
-        println!();
     }
-
-    Ok(())
 }
 
 /// Execute SQL from files.
 pub async fn execute_files(
     files: &[String],
-    config_path: &str,
+    cmd: &CommandContext,
     profile_name: &str,
     output_format: &str,
     quiet: bool,
 ) -> Result<()> {
-    let config = load_config(config_path).await?;
+    let config = cmd.load_config().await?;
+    apply_retention(&config);
     let profile = get_profile(&config, profile_name)?;
-    let db = create_connection(&profile).await?;
-    let executor = QueryExecutor::new(db);
+    let db = create_connection(&config, &profile).await?;
+    let cancel = CancellationToken::new();
+    let executor = QueryExecutor::new(db.clone()).with_cancellation(cancel.clone());
 
     let format = OutputFormat::from_str(output_format).unwrap_or(OutputFormat::Table);
+    let history = open_query_history(&config)?;
+    let (numbers, numeric) = (number_format(&config), numeric_output(&config));
+    // JSON output is one document covering every file
+    let json = matches!(format, OutputFormat::Json);
+    let mut executed = Vec::new();
 
     for file in files {
         let path = PathBuf::from(file);
@@ -178,31 +296,91 @@ pub async fn execute_files(
             .with_context(|| format!("Failed to read file: {}", file))?;
 
         if !quiet {
-            println!("Executing: {}", file);
+            note!("Executing: {}", file);
         }
 
-        match executor.execute_query(&sql).await {
+        let Some(result) = until_signal(&cancel, executor.execute_query(&sql)).await else {
+            shutdown::drain(shutdown_deadline(&config), db.close()).await;
+            let what = format!("stopped while executing {}", file);
+            return Err(shutdown::Interrupted::new(what).into());
+        };
+
+        match result {
             Ok(result) => {
+                let recorded = history.record(&profile.name, &sql, &result);
                 if !quiet {
-                    println!("Rows: {:?}", result.row_count);
+                    note!("Rows: {:?}", result.row_count);
                     if let Some(time) = result.execution_time_ms {
-                        println!("Time: {}ms", time);
+                        note!("Time: {}ms", time);
+                    }
+                    match &recorded {
+                        Ok(id) => note!("History: #{} (pg-agent diff --against {})", id, id),
+                        Err(e) => warn!("Query not saved to history: {}", e),
                     }
                 }
-                print_query_result(&result, format);
+                if json {
+                    executed.push((file, recorded.ok(), result));
+                } else {
+                    print_query_result(&result, format, numbers, numeric);
+                }
             }
             Err(e) => {
-                bail!("Error executing {}: {}", file, e);
+                bail!("Error executing {}: {}", file, error_report(&e.into()));
             }
         }
     }
 
+    if json {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct FileJson<'a> {
+            file: &'a str,
+            history_id: Option<u64>,
+            #[serde(flatten)]
+            result: JsonResult<'a>,
+        }
+        #[derive(serde::Serialize)]
+        struct ExecJson<'a> {
+            files: Vec<FileJson<'a>>,
+        }
+        let files = executed
+            .iter()
+            .map(|(file, history_id, result)| FileJson {
+                file,
+                history_id: *history_id,
+                result: result.to_json(numeric),
+            })
+            .collect();
+        script::print_document(&ExecJson { files })?;
+    }
+
     Ok(())
 }
 
 /// List available database profiles.
-pub async fn list_profiles(config_path: &str) -> Result<()> {
-    let config = load_config(config_path).await?;
+pub async fn list_profiles(cmd: &CommandContext) -> Result<()> {
+    let config = cmd.load_config().await?;
+
+    if script::json_output() {
+        #[derive(serde::Serialize)]
+        struct ProfileJson<'a> {
+            name: &'a str,
+            url: String,
+        }
+        #[derive(serde::Serialize)]
+        struct ProfilesJson<'a> {
+            profiles: Vec<ProfileJson<'a>>,
+        }
+        let profiles = config
+            .databases
+            .iter()
+            .map(|p| ProfileJson {
+                name: &p.name,
+                url: mask_url(&p.url),
+            })
+            .collect();
+        return script::print_document(&ProfilesJson { profiles });
+    }
 
     println!("\nDatabase Profiles");
     println!("{}\n", "=".repeat(40));
@@ -223,9 +401,93 @@ pub async fn list_profiles(config_path: &str) -> Result<()> {
     Ok(())
 }
 
+/// List the models offered by the configured provider.
+pub async fn list_models(cmd: &CommandContext) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let provider = create_llm_client(&config)?;
+
+    let mut models = provider
+        .list_models()
+        .await
+        .with_context(|| format!("Failed to list models from {}", config.llm.provider))?;
+    models.sort();
+
+    println!("\nModels ({})", config.llm.provider);
+    println!("{}\n", "=".repeat(50));
+
+    if models.is_empty() {
+        println!("The provider did not report any models.");
+        return Ok(());
+    }
+
+    for model in &models {
+        let marker = if *model == config.llm.model { "*" } else { " " };
+        let tools = if !postgres_agent_llm::models::is_known(model) {
+            "tools: unknown"
+        } else if capabilities(model).supports_tools {
+            "tools: yes"
+        } else {
+            "tools: no"
+        };
+        println!("{} {:<40} {}", marker, model, tools);
+    }
+    println!("\n* current model; switch with \\model <name> in interactive mode");
+
+    Ok(())
+}
+
+/// Show effective file locations.
+///
+/// Works without a configuration file, in which case defaults are shown.
+pub fn show_paths(cmd: &CommandContext, sizes: bool) -> Result<()> {
+    let config_file = std::path::Path::new(&cmd.config_path);
+    let paths = if config_file.exists() {
+        ConfigLoader::new(config_file).load()?.paths
+    } else {
+        PathsConfig::default()
+    };
+    // Size on disk after each location when asked for
+    let size = |path: &std::path::Path| {
+        if sizes { format!(" [{}]", format_bytes(disk_usage(path))) } else { String::new() }
+    };
+    let audit_log = paths.audit_log();
+    let rotated = rotated_logs(&audit_log);
+    let audit_size = if sizes && !rotated.is_empty() {
+        let bytes = rotated.iter().map(|p| disk_usage(p)).sum();
+        format!(
+            " [{}, {} rotated in {} files]",
+            format_bytes(disk_usage(&audit_log)),
+            format_bytes(bytes),
+            rotated.len()
+        )
+    } else {
+        size(&audit_log)
+    };
+
+    let status = if config_file.exists() { "" } else { " (not found)" };
+    println!("Config file:  {}{}", config_file.display(), status);
+    println!("Data dir:     {}{}", paths.data_dir().display(), size(&paths.data_dir()));
+    println!("Cache dir:    {}{}", paths.cache_dir().display(), size(&paths.cache_dir()));
+    println!("Sessions dir: {}{}", paths.sessions_dir().display(), size(&paths.sessions_dir()));
+    println!("History file: {}{}", paths.history_file().display(), size(&paths.history_file()));
+    println!("Audit log:    {}{}", audit_log.display(), audit_size);
+    println!("Stats file:   {}{}", paths.stats_file().display(), size(&paths.stats_file()));
+    let query_log = paths.query_history_file();
+    println!("Query log:    {}{}", query_log.display(), size(&query_log));
+    let ledger = paths.idempotency_ledger();
+    println!("Idempotency:  {}{}", ledger.display(), size(&ledger));
+    println!("LLM log:      {}{}", paths.llm_log().display(), size(&paths.llm_log()));
+    let approvals = paths.approvals_dir();
+    println!("Approvals:    {}{}", approvals.display(), size(&approvals));
+    let snapshots = paths.snapshots_dir();
+    println!("Snapshots:    {}{}", snapshots.display(), size(&snapshots));
+
+    Ok(())
+}
+
 /// Show current configuration.
-pub async fn show_config(config_path: &str, _effective: bool) -> Result<()> {
-    let config = load_config(config_path).await?;
+pub async fn show_config(cmd: &CommandContext, _effective: bool) -> Result<()> {
+    let config = cmd.load_config().await?;
 
     println!("\nConfiguration");
     println!("{}\n", "=".repeat(50));
@@ -234,11 +496,26 @@ pub async fn show_config(config_path: &str, _effective: bool) -> Result<()> {
     println!("LLM:");
     println!("  Provider: {}", config.llm.provider);
     println!("  Model: {}", config.llm.model);
+    if let Some(model) = &config.llm.reasoning_model {
+        println!("  Reasoning model: {}", model);
+    }
     if let Some(ref url) = config.llm.base_url {
         println!("  Base URL: {}", url);
     }
     println!("  Temperature: {}", config.llm.temperature);
+    if let Some(seed) = config.llm.seed {
+        println!("  Seed: {}", seed);
+    }
+    if config.llm.deterministic {
+        println!("  Deterministic: yes");
+    }
     println!("  Max tokens: {}", config.llm.max_tokens);
+    if let Some(limit) = config.llm.max_tokens_per_query {
+        println!("  Max tokens per query: {}", limit);
+    }
+    if let Some(limit) = config.llm.max_cost_per_day {
+        println!("  Max cost per day: ${:.2} ({:?})", limit, config.llm.on_budget_exceeded);
+    }
     println!();
 
     // Agent Configuration
@@ -263,13 +540,13 @@ pub async fn show_config(config_path: &str, _effective: bool) -> Result<()> {
 
 /// Show database schema.
 pub async fn show_schema(
-    config_path: &str,
+    cmd: &CommandContext,
     profile_name: &str,
     table_filter: Option<&str>,
 ) -> Result<()> {
-    let config = load_config(config_path).await?;
+    let config = cmd.load_config().await?;
     let profile = get_profile(&config, profile_name)?;
-    let db = create_connection(&profile).await?;
+    let db = create_connection(&config, &profile).await?;
     let executor = QueryExecutor::new(db);
 
     let schema = executor
@@ -277,6 +554,36 @@ pub async fn show_schema(
         .await
         .context("Failed to get schema")?;
 
+    if script::json_output() {
+        // Tables in schema order rather than the maps' arbitrary order
+        #[derive(serde::Serialize)]
+        struct TableJson<'a> {
+            #[serde(flatten)]
+            table: &'a SchemaTable,
+            columns: &'a [ColumnInfo],
+            #[serde(skip_serializing_if = "Option::is_none")]
+            security: Option<&'a TableSecurity>,
+        }
+        #[derive(serde::Serialize)]
+        struct SchemaJson<'a> {
+            tables: Vec<TableJson<'a>>,
+            enums: &'a [EnumType],
+        }
+        let tables = schema
+            .tables
+            .iter()
+            .map(|table| TableJson {
+                table,
+                columns: schema.columns.get(&table.table_name).map_or(&[], Vec::as_slice),
+                security: schema.security.get(&table.table_name),
+            })
+            .collect();
+        return script::print_document(&SchemaJson {
+            tables,
+            enums: &schema.enums,
+        });
+    }
+
     println!("\nDatabase Schema");
     println!("{}\n", "=".repeat(50));
 
@@ -289,7 +596,33 @@ pub async fn show_schema(
         println!("- {}.{}", table.table_schema, table.table_name);
         if let Some(columns) = schema.columns.get(&table.table_name) {
             for col in columns {
-                println!("    {} ({})", col.column_name, col.data_type);
+                if col.enum_labels.is_empty() {
+                    println!("    {} ({})", col.column_name, col.data_type);
+                } else {
+                    let labels = col.enum_labels.join(", ");
+                    println!("    {} ({}: {})", col.column_name, col.data_type, labels);
+                }
+            }
+        }
+        if let Some(security) = schema.security.get(&table.table_name)
+            && security.rls_enabled
+        {
+            println!(
+                "    [row-level security{}{}]",
+                if security.rls_forced { ", forced" } else { "" },
+                if security.rls_applies { ", filters rows for this role" } else { "" }
+            );
+            for policy in &security.policies {
+                println!(
+                    "    policy {} ({}, {}): {}",
+                    policy.name,
+                    policy.command,
+                    policy.roles.join(", "),
+                    policy.using.as_deref().unwrap_or("true")
+                );
+            }
+            if security.denies_all_reads() {
+                println!("    no policy allows reads: queries return 0 rows for this role");
             }
         }
     }
@@ -297,89 +630,257 @@ pub async fn show_schema(
     Ok(())
 }
 
-/// Run system doctor check.
-pub async fn run_doctor(config_path: &str) -> Result<()> {
-    println!("\nPostgreSQL Agent System Check");
-    println!("{}\n", "=".repeat(50));
+// ============================================================================
+// Helper Functions
+// ============================================================================
 
-    let mut checks_passed = 0;
-    let mut checks_total = 0;
-
-    // Check configuration file
-    checks_total += 1;
-    let config_exists = PathBuf::from(config_path).exists();
-    print_check("Config file", config_exists);
-    if config_exists {
-        checks_passed += 1;
-    }
-
-    // Check configuration
-    checks_total += 1;
-    match load_config(config_path).await {
-        Ok(config) => {
-            print_check("Configuration", true);
-            checks_passed += 1;
-
-            // Check LLM configuration
-            checks_total += 1;
-            let llm_ok = !config.llm.model.is_empty() && config.llm.max_tokens > 0;
-            print_check("LLM configuration", llm_ok);
-            if llm_ok {
-                checks_passed += 1;
-            }
+/// Describe an error; database errors get the failing SQL position marked
+/// and a suggested fix for known SQLSTATEs.
+pub(crate) fn error_report(error: &anyhow::Error) -> String {
+    let mut report = error.to_string();
+    if let Some(db_error) = error.downcast_ref::<DbError>() {
+        if let Some(highlight) = db_error.highlight() {
+            report.push('\n');
+            report.push_str(&highlight);
+        }
+        if let Some(state) = db_error.sqlstate_info() {
+            report.push_str("\nSuggestion: ");
+            report.push_str(state.suggestion);
+        }
+    }
+    report
+}
 
-            // Check database configuration
-            checks_total += 1;
-            let db_ok = !config.databases.is_empty()
-                && config.databases.iter().all(|p| !p.name.is_empty());
-            print_check("Database configuration", db_ok);
-            if db_ok {
-                checks_passed += 1;
-            }
+/// Run the agent until it finishes or a shutdown signal cancels it.
+///
+/// On a signal the run is given the chance to stop its LLM request and
+/// cancel its queries on the server; `None` is returned.
+async fn run_until_signal(
+    agent: &mut PostgresAgent<AnyProvider>,
+    query: &str,
+) -> Option<Result<AgentResponse, AgentError>> {
+    let cancel = CancellationToken::new();
+    let run = agent.run(query, &cancel);
+    until_signal(&cancel, run).await
+}
+
+/// Await `work` until it finishes or a shutdown signal cancels `cancel`.
+///
+/// After a signal `work` is still awaited, so that its queries are cancelled
+/// on the server with `pg_cancel_backend`; `None` is returned.
+pub(crate) async fn until_signal<T>(
+    cancel: &CancellationToken,
+    work: impl Future<Output = T>,
+) -> Option<T> {
+    tokio::pin!(work);
+    tokio::select! {
+        result = &mut work => return Some(result),
+        () = shutdown::signal() => cancel.cancel(),
+    }
+    let _ = work.await;
+    None
+}
+
+/// Clean up after a shutdown signal or at the end of a session, within the
+/// configured deadline: flush the audit log, then close the pool.
+pub(crate) async fn shut_down(
+    config: &AppConfig,
+    agent: &PostgresAgent<AnyProvider>,
+    db: &DbConnection,
+) {
+    let cleanup = async {
+        if let Some(audit) = &agent.tool_context().audit
+            && let Err(e) = audit.flush()
+        {
+            warn!("Failed to flush the audit log: {}", e);
         }
-        Err(e) => {
-            print_check("Configuration", false);
-            println!("    Error: {}", e);
+        db.close().await;
+    };
+    shutdown::drain(shutdown_deadline(config), cleanup).await;
+}
+
+/// Command-line settings that override the configuration file.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    /// Force reproducible LLM output (`--deterministic`).
+    pub deterministic: bool,
+    /// Tenant for scoped queries (`--tenant`).
+    pub tenant: Option<String>,
+    /// Key under which side effects are recorded (`--idempotency-key`).
+    pub idempotency_key: Option<String>,
+    /// Print reasoning and tool calls live (`--verbose`).
+    pub verbose: bool,
+    /// Print the time breakdown of each run (`--timing`).
+    pub timing: bool,
+    /// Print each query result of a run after its answer
+    /// (`--show-intermediate`).
+    pub show_intermediate: bool,
+    /// Zone `timestamptz` values are shown in (`--tz`).
+    pub time_zone: Option<String>,
+    /// Number display in tables and reports (`--number-format`).
+    pub number_format: Option<String>,
+    /// Decimal places of human-formatted numbers (`--precision`).
+    pub precision: Option<usize>,
+    /// How exact numbers are written in JSON (`--numeric-output`).
+    pub numeric_output: Option<String>,
+    /// `TYPE=STYLE` renderings of types (`--render`).
+    pub type_rendering: Vec<String>,
+    /// Send the LLM result shapes only, never row values (`--metadata-only`).
+    pub metadata_only: bool,
+    /// Simulate mutations in a sandbox before confirming them (`--sandbox`).
+    pub sandbox: bool,
+    /// Save the tables of confirmed mutations for undo (`--snapshot`).
+    pub snapshot: bool,
+    /// Persona to run as (`--persona`).
+    pub persona: Option<String>,
+    /// Limits of `query` and `interactive` runs (`--timeout`,
+    /// `--max-iterations`, `--max-rows`).
+    pub run_limits: RunLimits,
+}
+
+/// What a command runs with: the configuration file and the command-line
+/// settings that override it.
+#[derive(Debug, Clone, Default)]
+pub struct CommandContext {
+    /// Path of the configuration file.
+    pub config_path: String,
+    /// Command-line overrides.
+    pub overrides: ConfigOverrides,
+}
+
+impl CommandContext {
+    /// Run commands with `config_path` and `overrides`.
+    pub fn new(config_path: impl Into<String>, overrides: ConfigOverrides) -> Self {
+        Self {
+            config_path: config_path.into(),
+            overrides,
         }
     }
 
-    println!("\n{} {}/{} checks passed", "Result:", checks_passed, checks_total);
+    /// Load the configuration file, with the overrides applied.
+    pub(crate) async fn load_config(&self) -> Result<AppConfig> {
+        let mut loader = ConfigLoader::new(&self.config_path);
+        let mut config = loader.try_load().with_context(|| {
+            format!("Failed to load configuration from '{}'", self.config_path)
+        })?;
+        apply_overrides(&mut config, self.overrides.clone())?;
+        Ok(config)
+    }
+}
 
-    if checks_passed == checks_total {
-        println!("\nSystem is ready for use!");
-    } else {
-        println!("\nSome checks failed. Review the output above.");
+/// Apply command-line overrides to a loaded configuration.
+fn apply_overrides(config: &mut AppConfig, overrides: ConfigOverrides) -> Result<()> {
+    if overrides.deterministic || config.llm.deterministic {
+        config.llm.make_deterministic();
     }
+    if overrides.metadata_only {
+        config.safety.llm_data = LlmDataPolicy::MetadataOnly;
+    }
+    if overrides.sandbox && config.safety.sandbox.is_none() {
+        config.safety.sandbox = Some(SandboxConfig::default());
+    }
+    if overrides.snapshot && config.safety.snapshots.is_none() {
+        config.safety.snapshots = Some(SnapshotConfig::default());
+    }
+    if let Some(name) = overrides.persona {
+        config.agent.persona =
+            Some(Persona::from_name(&name).with_context(|| format!("Unknown persona '{}'", name))?);
+    }
+    if let Some(persona) = config.agent.persona {
+        config.safety.safety_level = persona.safety_level();
+        for namespace in persona.namespaces() {
+            config.tools.namespaces.entry((*namespace).to_string()).or_insert(true);
+        }
+    }
+    if let Some(value) = overrides.tenant {
+        match config.safety.tenant.as_mut() {
+            Some(tenant) => tenant.value = Some(value),
+            None => warn!("--tenant ignored: no [safety.tenant] section in the configuration"),
+        }
+    }
+    let ceilings = config.safety.ceilings;
+    let limits = &overrides.run_limits;
+    if let Some(secs) = limits.timeout {
+        config.agent.run_timeout_secs =
+            RunCeilings::check("--timeout", secs, ceilings.timeout_secs)?;
+    }
+    if let Some(iterations) = limits.max_iterations {
+        config.agent.max_iterations =
+            RunCeilings::check("--max-iterations", iterations, ceilings.max_iterations)?;
+    }
+    if let Some(rows) = limits.max_rows {
+        config.agent.max_result_rows = RunCeilings::check("--max-rows", rows, ceilings.max_rows)?;
+    }
+    if let Some(zone) = overrides.time_zone {
+        config.agent.time_zone = zone;
+    }
+    config
+        .agent
+        .time_zone
+        .parse::<TimeZoneMode>()
+        .map_err(anyhow::Error::msg)
+        .context("Invalid time zone")?;
+    if let Some(format) = overrides.number_format {
+        config.agent.number_format = format;
+    }
+    if let Some(places) = overrides.precision {
+        config.agent.decimal_places = Some(places);
+    }
+    config
+        .agent
+        .number_format
+        .parse::<NumberFormat>()
+        .map_err(anyhow::Error::msg)
+        .context("Invalid number format")?;
+    if let Some(numeric) = overrides.numeric_output {
+        config.agent.numeric_output = numeric;
+    }
+    config
+        .agent
+        .numeric_output
+        .parse::<NumericOutput>()
+        .map_err(anyhow::Error::msg)
+        .context("Invalid numeric output")?;
+    for pair in &overrides.type_rendering {
+        let (type_name, style) = pair
+            .split_once('=')
+            .with_context(|| format!("Expected --render TYPE=STYLE, got '{}'", pair))?;
+        config
+            .agent
+            .type_rendering
+            .insert(type_name.trim().to_lowercase(), style.trim().to_string());
+    }
+    type_rendering(config).context("Invalid type rendering")?;
 
     Ok(())
 }
 
-/// Print interactive mode help.
-fn print_interactive_help() {
-    println!("\nAvailable commands:");
-    println!("  \\q, \\quit, exit  - Exit interactive mode");
-    println!();
-    println!("Tips:");
-    println!("  - Type natural language queries");
-    println!("  - Ask about your database schema");
-    println!("  - Request data analysis or aggregations");
-    println!();
+/// Number display for tables and reports.
+pub(crate) fn number_format(config: &AppConfig) -> NumberFormat {
+    config
+        .agent
+        .number_format
+        .parse::<NumberFormat>()
+        .unwrap_or_default()
+        .with_precision(config.agent.decimal_places)
 }
 
-// ============================================================================
-// Helper Functions
-// ============================================================================
+/// Rendering of `bytea`, `point`, geometry and `tsvector` values.
+fn type_rendering(config: &AppConfig) -> Result<TypeRendering> {
+    let mut rendering = TypeRendering::default();
+    for (type_name, style) in &config.agent.type_rendering {
+        rendering.set(type_name, style).map_err(anyhow::Error::msg)?;
+    }
+    Ok(rendering)
+}
 
-/// Load configuration from file.
-async fn load_config(config_path: &str) -> Result<AppConfig> {
-    let mut loader = ConfigLoader::new(config_path);
-    loader.try_load().with_context(|| {
-        format!("Failed to load configuration from '{}'", config_path)
-    })
+/// How `numeric` and `bigint` values are written in JSON output.
+pub(crate) fn numeric_output(config: &AppConfig) -> NumericOutput {
+    config.agent.numeric_output.parse().unwrap_or_default()
 }
 
 /// Get database profile by name.
-fn get_profile(config: &AppConfig, name: &str) -> Result<DatabaseProfile> {
+pub(crate) fn get_profile(config: &AppConfig, name: &str) -> Result<DatabaseProfile> {
     config
         .databases
         .iter()
@@ -394,13 +895,23 @@ fn parse_ssl_mode(s: &str) -> postgres_agent_db::SslMode {
     match s.to_lowercase().as_str() {
         "disable" | "disabled" => postgres_agent_db::SslMode::Disable,
         "require" | "required" => postgres_agent_db::SslMode::Require,
-        "prefer" | "preferred" | _ => postgres_agent_db::SslMode::Prefer,
+        _ => postgres_agent_db::SslMode::Prefer,
     }
 }
 
 /// Create database connection.
-async fn create_connection(profile: &DatabaseProfile) -> Result<DbConnection> {
-    let db_config = DbConnectionConfig {
+pub(crate) async fn create_connection(
+    config: &AppConfig,
+    profile: &DatabaseProfile,
+) -> Result<DbConnection> {
+    DbConnection::new(&connection_config(config, profile)).await.with_context(|| {
+        format!("Failed to connect to database '{}'", profile.name)
+    })
+}
+
+/// Build the connection settings for a profile.
+fn connection_config(config: &AppConfig, profile: &DatabaseProfile) -> DbConnectionConfig {
+    DbConnectionConfig {
         url: profile.url.clone(),
         host: None,
         port: None,
@@ -412,39 +923,217 @@ async fn create_connection(profile: &DatabaseProfile) -> Result<DbConnection> {
         connect_timeout: profile.connect_timeout,
         query_timeout: 60,
         ssl_mode: parse_ssl_mode(&profile.ssl_mode),
-    };
-
-    DbConnection::new(&db_config).await.with_context(|| {
-        format!("Failed to connect to database '{}'", profile.name)
-    })
+        session: SessionSettings {
+            statement_timeout: profile.statement_timeout.clone(),
+            idle_in_transaction_session_timeout: profile
+                .idle_in_transaction_session_timeout
+                .clone(),
+            work_mem: profile.work_mem.clone(),
+            application_name: Some(profile.effective_application_name()),
+            search_path: (!profile.search_path.is_empty()).then(|| profile.search_path.join(",")),
+        },
+        default_schema: profile.default_schema.clone(),
+        time_zone: config.agent.time_zone.parse().unwrap_or_default(),
+        numeric_output: numeric_output(config),
+        type_rendering: type_rendering(config).unwrap_or_default(),
+        max_result_bytes: config.agent.max_result_memory_mb.saturating_mul(1024 * 1024),
+    }
 }
 
 /// Create LLM client from configuration.
-fn create_llm_client(config: &AppConfig) -> Result<OpenAiProvider> {
-    let api_key = config.llm.api_key.clone().ok_or_else(|| {
-        anyhow::anyhow!("API key not configured")
-    })?;
+pub(crate) fn create_llm_client(config: &AppConfig) -> Result<AnyProvider> {
+    Ok(AnyProvider::from_config(provider_config(config)?)?)
+}
 
-    let provider_config = ProviderConfig {
+/// Provider settings of the configured LLM.
+fn provider_config(config: &AppConfig) -> Result<ProviderConfig> {
+    // An Azure AD token replaces the API key; local Ollama models need none
+    let uses_ad_token = config.llm.azure.as_ref().is_some_and(|a| a.ad_token.is_some());
+    let api_key = config.llm.api_key.clone();
+    if api_key.is_none() && !uses_ad_token && config.llm.provider != "ollama" {
+        bail!("API key not configured");
+    }
+
+    // Deterministic runs must fail rather than silently sample
+    if config.llm.deterministic && !capabilities(&config.llm.model).supports_seed {
+        bail!(
+            "Model '{}' does not support seeded sampling; deterministic mode requires one that does",
+            config.llm.model
+        );
+    }
+
+    Ok(ProviderConfig {
         provider_type: config.llm.provider.clone(),
         base_url: config.llm.base_url.clone(),
-        api_key: Some(api_key),
+        api_key,
         model: config.llm.model.clone(),
         temperature: config.llm.temperature,
         max_tokens: config.llm.max_tokens,
+        seed: config.llm.seed,
+        safety_settings: config.llm.safety_settings.clone(),
+        azure: config.llm.azure.clone(),
+        request_log: match config.llm.log_requests {
+            true => Some(Arc::new(request_log(config)?)),
+            false => None,
+        },
+        response_cache: match &config.llm.response_cache {
+            Some(settings) => Some(Arc::new(response_cache(config, settings)?)),
+            None => None,
+        },
+        // Narrowed to the registered tools when an agent is created
+        tools: None,
+    })
+}
+
+/// System prompt for a profile: its notes and schemas, the persona and the
+/// data policy; enum types are added once connected.
+fn system_prompt(config: &AppConfig, profile: &DatabaseProfile) -> SystemPrompt {
+    let mut system_prompt = SystemPrompt::standard().with_search_path(&profile.search_path);
+    if let Some(notes) = &profile.prompt {
+        system_prompt = system_prompt.with_database_notes(notes);
+    }
+    if let Some(persona) = config.agent.persona {
+        system_prompt = system_prompt.with_persona(match persona {
+            Persona::Analyst => ANALYST_PERSONA,
+            Persona::Dba => DBA_PERSONA,
+            Persona::Developer => DEVELOPER_PERSONA,
+        });
+    }
+    if config.safety.llm_data == LlmDataPolicy::MetadataOnly {
+        system_prompt = system_prompt.with_values_withheld();
+    }
+    if profile.qualify_tables {
+        system_prompt = system_prompt.with_qualified_tables();
+    }
+    system_prompt
+}
+
+/// Build the LLM request log, masking PII as well as credentials.
+fn request_log(config: &AppConfig) -> Result<RequestLog> {
+    let pii = PiiDetector::new();
+    Ok(RequestLog::new(config.paths.llm_log())
+        .with_codec(storage_codec(config)?)
+        .with_redactor(move |text| pii.redact(text)))
+}
+
+/// Build the LLM response cache from its settings.
+fn response_cache(config: &AppConfig, settings: &ResponseCacheConfig) -> Result<ResponseCache> {
+    let mut cache = ResponseCache::new(settings.capacity)
+        .with_ttl(Duration::from_secs(settings.ttl_secs));
+    if settings.persist {
+        cache = cache.with_dir(config.paths.llm_cache_dir()).with_codec(storage_codec(config)?);
+    }
+    Ok(cache)
+}
+
+/// Build the `SELECT *` guard from the configuration.
+fn select_star_guard(config: &AppConfig) -> Option<SelectStarGuard> {
+    let settings = &config.safety.select_star;
+    let action = match settings.action {
+        ConfigSelectStarAction::Off => return None,
+        ConfigSelectStarAction::Warn => SelectStarAction::Warn,
+        ConfigSelectStarAction::Reject => SelectStarAction::Reject,
     };
+    Some(SelectStarGuard::new(settings.max_columns, settings.max_rows).with_action(action))
+}
+
+/// Build the policy rules from the configuration.
+fn policy_rules(config: &AppConfig) -> Result<Vec<PolicyRule>> {
+    let mut rules = Vec::new();
+    for rule in &config.safety.rules {
+        let invalid = |reason: String| anyhow::anyhow!("Policy rule '{}': {}", rule.name, reason);
+        let action = match rule.action {
+            RuleAction::Allow => PolicyAction::Allow,
+            RuleAction::Confirm => PolicyAction::Confirm,
+            RuleAction::Deny => PolicyAction::Deny,
+        };
+        let mut operations = Vec::new();
+        for name in &rule.operations {
+            operations.extend(parse_operations(name).map_err(invalid)?);
+        }
+        let mut days = Vec::new();
+        for day in &rule.days {
+            days.push(day.parse().map_err(|_| {
+                invalid(format!("Invalid day '{}' (expected e.g. mon or sat)", day))
+            })?);
+        }
+
+        let mut policy_rule = PolicyRule::new(&rule.name, action)
+            .on_operations(operations)
+            .on_tables(rule.tables.clone())
+            .on_days(days)
+            .for_roles(rule.roles.clone());
+        if let Some(rows) = rule.min_rows {
+            policy_rule = policy_rule.with_min_rows(rows);
+        }
+        if let Some(hours) = &rule.hours {
+            policy_rule = policy_rule.during(hours.parse().map_err(invalid)?);
+        }
+        if let Some(message) = &rule.message {
+            policy_rule = policy_rule.with_message(message);
+        }
+        rules.push(policy_rule);
+    }
+    Ok(rules)
+}
 
-    Ok(OpenAiProvider::new(provider_config))
+/// Build the safety validator, with the configured policy rules.
+pub(crate) fn safety_validator(config: &AppConfig) -> Result<SafetyValidator> {
+    Ok(SafetyValidator::new().with_rules(policy_rules(config)?))
+}
+
+/// Map core safety level to the validator's safety level.
+pub(crate) fn validator_level(level: CoreSafetyLevel) -> ValidatorSafetyLevel {
+    match level {
+        CoreSafetyLevel::ReadOnly => ValidatorSafetyLevel::ReadOnly,
+        CoreSafetyLevel::Balanced => ValidatorSafetyLevel::Balanced,
+        CoreSafetyLevel::Permissive => ValidatorSafetyLevel::Permissive,
+    }
+}
+
+/// Build tenant scoping from the configuration.
+///
+/// Without configured tables, every table with the tenant column is scoped.
+async fn tenant_scope(config: &AppConfig, db: &DbConnection) -> Result<Option<TenantScope>> {
+    let Some(tenant) = &config.safety.tenant else {
+        return Ok(None);
+    };
+    let tables = if tenant.tables.is_empty() {
+        QueryExecutor::new(db.clone())
+            .tables_with_column(&tenant.column)
+            .await
+            .context("Failed to find tenant-scoped tables")?
+    } else {
+        tenant.tables.clone()
+    };
+    let mode = match tenant.mode {
+        ConfigTenantMode::Reject => TenantMode::Reject,
+        ConfigTenantMode::Augment => TenantMode::Augment,
+    };
+
+    let scope = TenantScope::new(&tenant.column, tables).with_mode(mode);
+    Ok(Some(match &tenant.value {
+        Some(value) => scope.with_value(value),
+        None => {
+            warn!(
+                "No tenant selected (--tenant); queries on tables scoped by {} will be rejected",
+                tenant.column
+            );
+            scope
+        }
+    }))
 }
 
 /// Create agent with tools.
-fn create_agent<C: LlmClient>(
-    llm_client: C,
-    _db: &DbConnection,
+pub(crate) async fn create_agent(
+    mut llm_client: AnyProvider,
+    db: &DbConnection,
     config: &AppConfig,
+    profile_name: &str,
     safety_level: Option<&str>,
     no_confirm: bool,
-) -> Result<PostgresAgent<C>> {
+    overrides: &ConfigOverrides,
+) -> Result<PostgresAgent<AnyProvider>> {
     // Determine safety level
     let safety = match safety_level {
         Some(s) => parse_safety_level(s),
@@ -452,26 +1141,484 @@ fn create_agent<C: LlmClient>(
     };
 
     // Create tool context with timeout
-    let tool_context = ToolContext::with_timeout(Duration::from_secs(30));
+    let audit = Arc::new(open_audit_log(config));
+    let confirmer = if no_confirm {
+        Confirmer::approve_all()
+    } else {
+        let ttl = Duration::from_secs(config.safety.confirmation_ttl_secs);
+        let audit = Arc::clone(&audit);
+        let approvals = (config.safety.admin_approval == AdminApprovalMode::SecondChannel)
+            .then(|| ApprovalStore::open(config.paths.approvals_dir()));
+        Confirmer::new_async(move |prompt, level| {
+            let prompt = prompt.to_string();
+            let approvals = approvals.clone();
+            let audit = Arc::clone(&audit);
+            async move {
+                match approvals {
+                    Some(store) if level == ConfirmationLevel::AdminApproval => {
+                        await_second_approval(&store, &prompt, ttl, &audit).await
+                    }
+                    _ => confirm_action(&prompt, level, ttl, Some(audit)),
+                }
+            }
+        })
+    };
+    let mut tool_context = ToolContext::with_timeout(Duration::from_secs(30))
+        .with_confirmer(confirmer)
+        .with_audit(audit)
+        .with_safety_level(validator_level(safety));
+    if let Some(tenant) = tenant_scope(config, db).await? {
+        tool_context = tool_context.with_tenant(tenant);
+    }
+    if let Some(guard) = select_star_guard(config) {
+        tool_context = tool_context.with_select_star_guard(guard);
+    }
+    if !config.safety.rules.is_empty() {
+        tool_context = tool_context.with_policy(Arc::new(safety_validator(config)?));
+    }
+    if let Some(mode) = sandbox_mode(config) {
+        tool_context = tool_context.with_sandbox(mode);
+    }
+    if let Some(log) = undo_log(config, profile_name)? {
+        tool_context = tool_context.with_undo_log(log);
+    }
+    if let Some(key) = overrides.idempotency_key.clone() {
+        let ledger = IdempotencyLedger::open(config.paths.idempotency_ledger());
+        tool_context = tool_context.with_idempotency_key(key, Arc::new(ledger));
+    }
 
     // Create agent config - use default values for missing fields
     let agent_config = AgentConfig {
         max_iterations: config.agent.max_iterations,
         require_confirmation: !no_confirm,
         safety_level: safety,
-        timeout_seconds: 30,
+        timeout_seconds: config.agent.iteration_timeout_secs,
+        total_timeout_seconds: config.agent.run_timeout_secs,
         verbose_reasoning: false,
+        max_tokens_per_query: config.llm.max_tokens_per_query.map(u64::from),
+        deterministic: config.llm.deterministic,
+        tokenizer_fallback: config.llm.tokenizer_fallback,
+        max_result_rows: config.agent.max_result_rows,
+        audit_reasoning: config.agent.audit_reasoning,
+        llm_data: config.safety.llm_data,
+        local_only_columns: config.safety.local_only_columns.clone(),
+        fast_path: config.agent.fast_path,
     };
 
+    // Profile notes and schemas extend the system prompt, for the reasoning
+    // model too
+    let profile = get_profile(config, profile_name)?;
+    let mut system_prompt = system_prompt(config, &profile);
+    if profile.qualify_tables {
+        tool_context = tool_context.with_qualified_tables();
+    }
+    match QueryExecutor::new(db.clone()).list_enums().await {
+        Ok(enums) => {
+            system_prompt = system_prompt
+                .with_enum_types(enums.into_iter().map(|e| (e.name, e.labels)).collect());
+        }
+        Err(e) => warn!("Enum types left out of the system prompt: {}", e),
+    }
+    llm_client.set_system_prompt(system_prompt);
+
+    // Only the tools the persona and namespace settings leave are offered
+    let mut tools = ToolRegistry::default();
+    register_tools(&mut tools, db, config)?;
+    llm_client.set_tools(provider_tools(&tools));
+
+    // Intermediate turns go to the cheaper model when one is configured
+    let reasoning_client = config.llm.reasoning_model.as_ref().map(|model| {
+        let mut client = llm_client.clone();
+        client.set_model(model.as_str());
+        client
+    });
+
     // Create agent
-    let mut agent = PostgresAgent::with_config(Box::new(llm_client), agent_config);
+    let mut agent =
+        PostgresAgent::with_shared(Arc::new(llm_client), Arc::new(tools), agent_config);
     agent.set_tool_context(tool_context);
+    if let Some(client) = reasoning_client {
+        agent.set_reasoning_client(client);
+    }
+    if let Some(retriever) = example_retriever(config, profile_name) {
+        agent.set_example_retriever(retriever);
+    }
+    if overrides.verbose {
+        let (sender, receiver) = events::channel();
+        agent.set_event_sender(sender);
+        tokio::spawn(print_events(receiver));
+    }
 
     Ok(agent)
 }
 
+/// Print run progress to stderr as it happens, keeping stdout for results.
+async fn print_events(mut events: EventReceiver) {
+    while let Some(event) = events.recv().await {
+        print_event(&event);
+    }
+}
+
+/// Print one progress event to stderr; answer text is left to the caller.
+fn print_event(event: &AgentEvent) {
+    match event {
+        AgentEvent::AnswerDelta { .. } => {}
+        AgentEvent::Reasoning { iteration, thought } => {
+            eprintln!("[{}] Thinking: {}", iteration, thought);
+        }
+        AgentEvent::ToolCall { iteration, name } => {
+            eprintln!("[{}] Running {}", iteration, name);
+        }
+        AgentEvent::Confirmation { prompt, status, .. } => {
+            eprintln!("Confirmation {}: {}", status.as_str(), prompt);
+        }
+    }
+}
+
+/// Run the agent like [`run_until_signal`], writing the answer to stdout as
+/// it is streamed. Returns the result and the text streamed for the answer.
+pub(crate) async fn run_streaming(
+    agent: &mut PostgresAgent<AnyProvider>,
+    query: &str,
+    events: &mut EventReceiver,
+    verbose: bool,
+) -> (Option<Result<AgentResponse, AgentError>>, String) {
+    let mut streamed = String::new();
+    let mut show = |event: AgentEvent| {
+        if let AgentEvent::AnswerDelta { delta, .. } = &event {
+            if streamed.is_empty() {
+                println!();
+            }
+            print!("{}", delta);
+            let _ = std::io::stdout().flush();
+            streamed.push_str(delta);
+            return;
+        }
+        // Text streamed before a tool call was not the answer
+        if !streamed.is_empty() {
+            println!();
+            streamed.clear();
+        }
+        if verbose {
+            print_event(&event);
+        }
+    };
+
+    let run = run_until_signal(agent, query);
+    tokio::pin!(run);
+    let result = loop {
+        tokio::select! {
+            result = &mut run => break result,
+            Some(event) = events.recv() => show(event),
+        }
+    };
+    while let Ok(event) = events.try_recv() {
+        show(event);
+    }
+    (result, streamed)
+}
+
+/// Build the few-shot example retriever from past runs of a profile.
+fn example_retriever(config: &AppConfig, profile_name: &str) -> Option<ExampleRetriever> {
+    let settings = config.llm.examples.as_ref()?;
+    let mut embeddings = EmbeddingsConfig::new(settings.embedding_model.as_str());
+    embeddings.base_url = config.llm.base_url.clone();
+    embeddings.api_key = config.llm.api_key.clone();
+    let embedder: Arc<dyn EmbeddingsClient> = if config.llm.provider == "ollama" {
+        Arc::new(OllamaEmbeddings::new(embeddings))
+    } else {
+        Arc::new(OpenAiEmbeddings::new(embeddings))
+    };
+
+    let examples = Example::from_runs(open_stats_store(config).runs(), profile_name);
+    Some(
+        ExampleRetriever::new(embedder, examples)
+            .with_k(settings.k)
+            .with_min_similarity(settings.min_similarity),
+    )
+}
+
+/// Register the built-in tools and apply namespace settings.
+fn register_tools(registry: &mut ToolRegistry, db: &DbConnection, config: &AppConfig) -> Result<()> {
+    for tool in create_builtin_tools(db.clone()) {
+        registry.register(tool)?;
+    }
+    if config.databases.len() > 1 {
+        let profiles = config
+            .databases
+            .iter()
+            .map(|p| (p.name.clone(), connection_config(config, p)))
+            .collect();
+        registry.register(BuiltInTool::Compare(CompareTool::new(db.clone(), profiles)))?;
+    }
+    if let Some(persona) = config.agent.persona {
+        registry.retain(|name| persona.tools().contains(&name));
+    }
+    let namespaces = ToolsConfig::DISABLED_BY_DEFAULT
+        .iter()
+        .copied()
+        .chain(config.tools.namespaces.keys().map(String::as_str));
+    for namespace in namespaces {
+        registry.set_namespace_enabled(namespace, config.tools.is_namespace_enabled(namespace));
+    }
+    Ok(())
+}
+
+/// Definitions of the enabled tools as offered to the model: under their
+/// bare names, which the registry resolves, and in a stable order.
+fn provider_tools(registry: &ToolRegistry) -> Vec<OpenAiToolDefinition> {
+    let mut definitions: Vec<OpenAiToolDefinition> = registry
+        .get_definitions()
+        .into_iter()
+        .map(|definition| {
+            let name = definition.name.rsplit('.').next().unwrap_or(&definition.name);
+            OpenAiToolDefinition::function(
+                name.to_string(),
+                definition.description,
+                definition.parameters,
+            )
+        })
+        .collect();
+    definitions.sort_by(|a, b| a.function.name.cmp(&b.function.name));
+    definitions
+}
+
+/// Deadline for cleanup after a shutdown signal.
+fn shutdown_deadline(config: &AppConfig) -> Duration {
+    Duration::from_secs(config.agent.shutdown_timeout_secs)
+}
+
+/// Open the persistent stats store, falling back to memory if it is unreadable.
+pub(crate) fn open_stats_store(config: &AppConfig) -> StatsStore {
+    let path = config.paths.stats_file();
+    StatsStore::open(&path).unwrap_or_else(|e| {
+        warn!("Usage stats unavailable, budget tracking is per-process: {}", e);
+        StatsStore::in_memory()
+    })
+}
+
+/// Check the daily spend budget.
+///
+/// Returns `false` if the run should not proceed.
+pub(crate) fn check_daily_budget(
+    config: &AppConfig,
+    store: &StatsStore,
+    no_confirm: bool,
+) -> Result<bool> {
+    let Some(limit) = config.llm.max_cost_per_day else {
+        return Ok(true);
+    };
+
+    let Err(e) = store.check_daily_budget(limit) else {
+        return Ok(true);
+    };
+
+    // Without prompts there is nobody to ask, so the budget is a hard stop
+    if config.llm.on_budget_exceeded == BudgetAction::Abort || no_confirm {
+        eprintln!("{}", e.user_message());
+        return Ok(false);
+    }
+
+    eprint!("{}. Continue anyway? [y/N] ", e.user_message());
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Ask the user on the terminal to approve a tool action.
+///
+/// Admin approval requires typing `APPROVE`; other levels take yes/no.
+/// Anything else declines. An answer given after `ttl` approves nothing:
+/// the expiry goes to `audit` and the user may ask again.
+pub(crate) fn confirm_action(
+    prompt: &str,
+    level: ConfirmationLevel,
+    ttl: Duration,
+    audit: Option<Arc<AuditLogger>>,
+) -> bool {
+    let mut workflow = ConfirmationWorkflow::new().with_ttl(ttl);
+    if let Some(audit) = audit {
+        workflow = workflow.with_audit(audit, current_user());
+    }
+    if workflow.request(prompt, prompt, level).is_none() {
+        return read_confirmation(prompt, level);
+    }
+    loop {
+        if !read_confirmation(prompt, level) {
+            workflow.cancel();
+            return false;
+        }
+        if workflow.approve() {
+            return true;
+        }
+        eprintln!("The request expired after {}s; nothing was approved.", ttl.as_secs());
+        if !read_confirmation("Ask again?", ConfirmationLevel::Simple) {
+            return false;
+        }
+        workflow.refresh();
+    }
+}
+
+/// Sandbox mutations are simulated in, if configured.
+pub(crate) fn sandbox_mode(config: &AppConfig) -> Option<SandboxMode> {
+    let sandbox = config.safety.sandbox.as_ref()?;
+    Some(match &sandbox.template {
+        Some(template) => SandboxMode::Template(template.clone()),
+        None => SandboxMode::Sample(sandbox.sample_rows),
+    })
+}
+
+/// Snapshots of confirmed mutations, registered in the query history, if
+/// configured.
+fn undo_log(config: &AppConfig, profile_name: &str) -> Result<Option<UndoLog>> {
+    let Some(snapshots) = &config.safety.snapshots else {
+        return Ok(None);
+    };
+    let history = open_query_history(config)?;
+    let profile = profile_name.to_string();
+    let record = move |sql: &str, result: &QueryResult, snapshot| {
+        history
+            .record_undo(&profile, sql, result, snapshot)
+            .map_err(|e| e.to_string())
+    };
+    Ok(Some(UndoLog::new(config.paths.snapshots_dir(), snapshots.max_rows, record)))
+}
+
+/// Read one answer to a confirmation prompt, asked on stderr so that stdout
+/// holds only results.
+fn read_confirmation(prompt: &str, level: ConfirmationLevel) -> bool {
+    if level == ConfirmationLevel::AdminApproval {
+        eprint!("{}\nType APPROVE to proceed: ", level.prompt_message(prompt));
+    } else {
+        eprint!("{} [y/N] ", prompt);
+    }
+    let mut answer = String::new();
+    if std::io::stderr().flush().is_err() || std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    if level == ConfirmationLevel::AdminApproval {
+        answer.trim() == "APPROVE"
+    } else {
+        matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+    }
+}
+
+/// Open the audit log, creating its directory if needed.
+pub(crate) fn open_audit_log(config: &AppConfig) -> AuditLogger {
+    let path = config.paths.audit_log();
+    if let Some(parent) = path.parent()
+        && let Err(e) = std::fs::create_dir_all(parent)
+    {
+        warn!("Cannot create audit log directory {}: {}", parent.display(), e);
+    }
+    let storage = &config.storage;
+    AuditLogger::new(
+        AuditConfig::with_path(path)
+            .with_rotation(storage.audit_rotate_bytes(), storage.compression()),
+    )
+}
+
+/// Encoding of saved sessions, the query history and the LLM request log.
+///
+/// `PG_AGENT_STORAGE_KEY` overrides the configured key source; the key is
+/// loaded once per process.
+fn storage_codec(config: &AppConfig) -> Result<FileCodec> {
+    static KEY: OnceLock<EncryptionKey> = OnceLock::new();
+
+    let codec = FileCodec::new(config.storage.compression());
+    if !config.storage.encrypt {
+        return Ok(codec);
+    }
+    let key = match KEY.get() {
+        Some(key) => key.clone(),
+        None => {
+            let key = match std::env::var("PG_AGENT_STORAGE_KEY") {
+                Ok(text) => EncryptionKey::from_base64(&text)?,
+                Err(_) => EncryptionKey::load_or_create(&config.storage.key_source())
+                    .context("Failed to load the storage encryption key")?,
+            };
+            KEY.get_or_init(|| key).clone()
+        }
+    };
+    Ok(codec.with_key(key))
+}
+
+/// Open the saved sessions store.
+pub(crate) fn open_sessions(config: &AppConfig) -> Result<SessionStore> {
+    Ok(SessionStore::open(config.paths.sessions_dir()).with_codec(storage_codec(config)?))
+}
+
+/// Open the query history.
+pub(crate) fn open_query_history(config: &AppConfig) -> Result<QueryHistory> {
+    Ok(QueryHistory::open(config.paths.query_history_file()).with_codec(storage_codec(config)?))
+}
+
+/// Remove the sessions, query history entries and rotated audit logs that
+/// fall outside the configured retention policy.
+pub(crate) fn apply_retention(config: &AppConfig) {
+    let policy = config.storage.retention();
+    if policy.is_unlimited() {
+        return;
+    }
+    let results = [
+        (
+            "sessions",
+            open_sessions(config).and_then(|store| Ok(store.prune(&policy)?)),
+        ),
+        (
+            "query history entries",
+            open_query_history(config).and_then(|history| Ok(history.prune(&policy)?)),
+        ),
+        (
+            "rotated audit logs",
+            policy.prune(&rotated_logs(&config.paths.audit_log())).map_err(Into::into),
+        ),
+    ];
+    for (what, result) in results {
+        match result {
+            Ok(0) => {}
+            Ok(removed) => debug!("Removed {} {} past the retention policy", removed, what),
+            Err(e) => warn!("Failed to clean up {}: {}", what, e),
+        }
+    }
+}
+
+/// Record the usage of an agent run in the stats store.
+pub(crate) fn record_usage(
+    config: &AppConfig,
+    store: &mut StatsStore,
+    profile: &str,
+    question: &str,
+    agent: &mut PostgresAgent<AnyProvider>,
+    response: Option<&AgentResponse>,
+) {
+    let model = agent.llm_client().provider_info().model;
+    let stats = agent.stats();
+    let (tokens, cost) = (stats.tokens(), stats.cost(&config.llm));
+    store.record_query(tokens, cost);
+    store.record_tools(&stats.tools);
+    store.record_run(RunRecord {
+        timestamp: chrono::Utc::now(),
+        profile: profile.to_string(),
+        model,
+        tokens,
+        cost,
+        duration_ms: stats.duration_ms,
+        iterations: stats.iterations,
+        tool_calls: stats.tool_calls,
+        success: response.is_some_and(|r| r.success),
+        question: question.to_string(),
+        sql: response.and_then(AgentResponse::last_sql).map(str::to_string),
+        feedback: None,
+    });
+    if let Err(e) = store.save() {
+        warn!("Failed to save usage stats: {}", e);
+    }
+}
+
 /// Parse safety level string to core SafetyLevel enum.
-fn parse_safety_level(s: &str) -> CoreSafetyLevel {
+pub(crate) fn parse_safety_level(s: &str) -> CoreSafetyLevel {
     match s.to_lowercase().as_str() {
         "read_only" | "readonly" => CoreSafetyLevel::ReadOnly,
         "balanced" => CoreSafetyLevel::Balanced,
@@ -481,7 +1628,7 @@ fn parse_safety_level(s: &str) -> CoreSafetyLevel {
 }
 
 /// Map config safety level to core safety level.
-fn map_safety_level(s: ConfigSafetyLevel) -> CoreSafetyLevel {
+pub(crate) fn map_safety_level(s: ConfigSafetyLevel) -> CoreSafetyLevel {
     match s {
         ConfigSafetyLevel::ReadOnly => CoreSafetyLevel::ReadOnly,
         ConfigSafetyLevel::Balanced => CoreSafetyLevel::Balanced,
@@ -489,18 +1636,111 @@ fn map_safety_level(s: ConfigSafetyLevel) -> CoreSafetyLevel {
     }
 }
 
-/// Print agent response based on format.
-fn print_response(response: &AgentResponse, format: OutputFormat) {
+/// Print where the time of the last run went, for `--timing`.
+///
+/// Goes to stderr so that JSON output stays parseable.
+pub(crate) fn print_timings(stats: &AgentStats) {
+    let format_ms = |ms: Option<u64>| ms.map_or_else(|| "-".to_string(), |ms| format!("{}ms", ms));
+    eprintln!("{:>4} {:>9}  {:<28} {:>9}", "ITER", "LLM", "TOOL", "TOOL TIME");
+    for timing in &stats.timings {
+        eprintln!(
+            "{:>4} {:>9}  {:<28} {:>9}",
+            timing.iteration,
+            format_ms(Some(timing.llm_ms)),
+            timing.tool.as_deref().unwrap_or("-"),
+            format_ms(timing.tool_ms),
+        );
+    }
+    let (llm_ms, tool_ms) = (stats.llm_ms(), stats.tool_ms());
+    let (llm, tools) = (format_ms(Some(llm_ms)), format_ms(Some(tool_ms)));
+    eprintln!("{:>4} {:>9}  {:<28} {:>9}", "", llm, "", tools);
+    let total = (llm_ms + tool_ms).max(1) as f64;
+    eprintln!(
+        "LLM {:.0}%, tools {:.0}% of {}ms total run time",
+        llm_ms as f64 * 100.0 / total,
+        tool_ms as f64 * 100.0 / total,
+        stats.duration_ms
+    );
+}
+
+/// Token count in thousands past 1000, e.g. `2.3k`.
+fn format_tokens(tokens: u64) -> String {
+    if tokens < 1000 {
+        return tokens.to_string();
+    }
+    format!("{:.1}k", tokens as f64 / 1000.0)
+}
+
+/// Cost in USD, e.g. `$0.004`; costs under a tenth of a cent show as
+/// `<$0.001`.
+fn format_cost(cost: f64) -> String {
+    if cost > 0.0 && cost < 0.001 {
+        return "<$0.001".to_string();
+    }
+    format!("${:.3}", cost)
+}
+
+/// Print agent response based on format, followed by its query results
+/// with `--show-intermediate`; JSON output always includes them.
+fn print_response(
+    response: &AgentResponse,
+    format: OutputFormat,
+    numbers: NumberFormat,
+    numeric: NumericOutput,
+    show_intermediate: bool,
+) {
     match format {
         OutputFormat::Json => {
-            let json = serde_json::json!({
-                "answer": response.answer,
-                "success": response.success,
-                "iterations": response.iterations,
-                "executed_sql": response.executed_sql,
-                "error": response.error,
-            });
-            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
+            // Serialized directly: a `serde_json::Value` would turn exact
+            // numbers into doubles
+            #[derive(serde::Serialize)]
+            struct ResultSetJson<'a> {
+                label: String,
+                tool: &'a str,
+                sql: Option<&'a str>,
+                #[serde(flatten)]
+                result: JsonResult<'a>,
+            }
+            #[derive(serde::Serialize)]
+            struct ResponseJson<'a> {
+                answer: &'a str,
+                success: bool,
+                iterations: u32,
+                executed_sql: &'a [ExecutedSql],
+                results: Vec<ResultSetJson<'a>>,
+                error: Option<&'a str>,
+                request_id: Option<&'a str>,
+                trace: &'a [TurnRecord],
+                usage: TokenUsage,
+            }
+            let results = response
+                .results
+                .iter()
+                .map(|set| ResultSetJson {
+                    label: set.label(),
+                    tool: &set.tool,
+                    sql: set.sql.as_deref(),
+                    result: set.result.to_json(numeric),
+                })
+                .collect();
+            let json = ResponseJson {
+                answer: &response.answer,
+                success: response.success,
+                iterations: response.iterations,
+                executed_sql: &response.executed_sql,
+                results,
+                error: response.error.as_deref(),
+                request_id: response.request_id.as_deref(),
+                trace: &response.trace,
+                usage: response.usage,
+            };
+            // Written as rows are read, since some may come from disk
+            let mut out = std::io::stdout().lock();
+            match serde_json::to_writer_pretty(&mut out, &json) {
+                Ok(()) => println!(),
+                Err(e) => eprintln!("\nError: {}", e),
+            }
+            return;
         }
         OutputFormat::Table | OutputFormat::Raw => {
             println!("{}", response.answer);
@@ -511,18 +1751,62 @@ fn print_response(response: &AgentResponse, format: OutputFormat) {
             println!("\"{}\"", response.answer.replace('"', "\"\""));
         }
     }
+    if show_intermediate {
+        print_result_sets(&response.results, format, numbers, numeric);
+    }
+}
+
+/// Print the query results of a run, each under a label naming its SQL.
+///
+/// CSV labels are `#` comment lines between the blocks; raw output uses
+/// tables, since its result form only reports the row count.
+pub(crate) fn print_result_sets(
+    results: &[ResultSet],
+    format: OutputFormat,
+    numbers: NumberFormat,
+    numeric: NumericOutput,
+) {
+    let format = match format {
+        OutputFormat::Raw => OutputFormat::Table,
+        format => format,
+    };
+    for (i, set) in results.iter().enumerate() {
+        let label = format!("Result {} of {}: {}", i + 1, results.len(), set.label());
+        match format {
+            OutputFormat::Csv => println!("\n# {}", label),
+            _ => println!("\n-- {}", label),
+        }
+        print_query_result(&set.result, format, numbers, numeric);
+    }
 }
 
-/// Print query result based on format.
-fn print_query_result(result: &QueryResult, format: OutputFormat) {
+/// Print query result based on format; `numbers` applies to table output,
+/// while JSON and CSV stay raw, with exact numbers in JSON as `numeric` asks.
+pub(crate) fn print_query_result(
+    result: &QueryResult,
+    format: OutputFormat,
+    numbers: NumberFormat,
+    numeric: NumericOutput,
+) {
     match format {
         OutputFormat::Json => {
-            let json = serde_json::json!({
-                "columns": result.columns,
-                "rows": result.rows,
-                "row_count": result.row_count,
-                "execution_time_ms": result.execution_time_ms,
-            });
+            // Serialized directly: a `serde_json::Value` would turn exact
+            // numbers into doubles
+            #[derive(serde::Serialize)]
+            struct ResultJson<'a> {
+                columns: &'a [String],
+                rows: JsonRows<'a>,
+                row_count: usize,
+                execution_time_ms: Option<u64>,
+                time_zone: Option<&'a str>,
+            }
+            let json = ResultJson {
+                columns: &result.columns,
+                rows: result.json_rows(numeric),
+                row_count: result.row_count,
+                execution_time_ms: result.execution_time_ms,
+                time_zone: result.time_zone.as_deref(),
+            };
             println!("{}", serde_json::to_string_pretty(&json).unwrap_or_default());
         }
         OutputFormat::Table => {
@@ -531,20 +1815,29 @@ fn print_query_result(result: &QueryResult, format: OutputFormat) {
                 return;
             }
 
-            // Simple table output
-            println!("{}", result.columns.join(" | "));
-            println!("{}", "-".repeat(result.columns.iter().map(|c| c.len()).sum::<usize>()));
-
-            for row in &result.rows {
-                let row_str: Vec<String> = result
-                    .columns
-                    .iter()
-                    .map(|col| {
-                        row.get(col)
-                            .and_then(|v| v.as_str())
-                            .unwrap_or_default()
-                            .to_string()
-                    })
+            // Simple table output; timestamptz headers name their zone
+            let headers: Vec<String> = result
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(i, col)| match &result.time_zone {
+                    Some(zone) if result.is_timestamptz(i) => format!("{} ({})", col, zone),
+                    _ => col.clone(),
+                })
+                .collect();
+            println!("{}", headers.join(" | "));
+            println!("{}", "-".repeat(headers.iter().map(|c| c.len()).sum::<usize>()));
+
+            for row in result.all_rows() {
+                let row = match row {
+                    Ok(row) => row,
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                };
+                let row_str: Vec<String> = (0..result.columns.len())
+                    .map(|i| result.display_cell(&row, i, numbers))
                     .collect();
                 println!("{}", row_str.join(" | "));
             }
@@ -553,16 +1846,23 @@ fn print_query_result(result: &QueryResult, format: OutputFormat) {
         OutputFormat::Csv => {
             if !result.columns.is_empty() {
                 println!("{}", result.columns.join(","));
-                for row in &result.rows {
+                for row in result.all_rows() {
+                    let row = match row {
+                        Ok(row) => row,
+                        Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return;
+                    }
+                    };
                     let row_str: Vec<String> = result
                         .columns
                         .iter()
                         .map(|col| {
-                            let s = row.get(col).and_then(|v| v.as_str()).unwrap_or_default();
-                            if s.contains(',') || s.contains('"') {
+                            let s = cell_text(row.get(col));
+                            if s.contains(',') || s.contains('"') || s.contains('\n') {
                                 format!("\"{}\"", s.replace('"', "\"\""))
                             } else {
-                                s.to_string()
+                                s
                             }
                         })
                         .collect();
@@ -577,8 +1877,17 @@ fn print_query_result(result: &QueryResult, format: OutputFormat) {
     }
 }
 
+/// Text of a result cell: strings unquoted, null empty, other values as JSON.
+fn cell_text(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
 /// Mask URL for display.
-fn mask_url(url: &str) -> String {
+pub(crate) fn mask_url(url: &str) -> String {
     if let Some(at_pos) = url.find('@') {
         let after_at = &url[at_pos + 1..];
         return format!("***@{}", after_at);
@@ -586,8 +1895,54 @@ fn mask_url(url: &str) -> String {
     url.to_string()
 }
 
-/// Print a check result.
-fn print_check<T: std::fmt::Display>(name: &str, result: T) {
-    let status = "✓";
-    println!("[{}] {}: {}", status, name, result);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_agent_config::LlmConfig;
+
+    #[test]
+    fn test_deterministic_override() {
+        let mut config = AppConfig::default();
+        config.llm.provider = "ollama".to_string();
+        config.llm.temperature = 0.7;
+        config.llm.reasoning_model = Some("gpt-4o-mini".to_string());
+
+        // Without the flag the configured sampling is kept
+        apply_overrides(&mut config, ConfigOverrides::default()).unwrap();
+        let provider = provider_config(&config).unwrap();
+        assert!((provider.temperature - 0.7).abs() < f32::EPSILON);
+        assert_eq!(provider.seed, None);
+
+        // --deterministic wins over the configuration
+        let overrides = ConfigOverrides {
+            deterministic: true,
+            ..ConfigOverrides::default()
+        };
+        apply_overrides(&mut config, overrides).unwrap();
+        assert!(config.llm.reasoning_model.is_none());
+        let provider = provider_config(&config).unwrap();
+        assert_eq!(provider.temperature, 0.0);
+        assert_eq!(provider.seed, Some(LlmConfig::DETERMINISTIC_SEED));
+    }
+
+    #[test]
+    fn test_profile_prompt_notes() {
+        let mut config = AppConfig::default();
+        config.agent.persona = Some(Persona::Analyst);
+        let plain = DatabaseProfile::new("plain", "postgres://localhost/plain");
+        let mut billing = DatabaseProfile::new("billing", "postgres://localhost/billing");
+        billing.prompt = Some("Amounts are in cents; never join to audit_log.".to_string());
+        let mut crm = DatabaseProfile::new("crm", "postgres://localhost/crm");
+        crm.prompt = Some("Accounts are companies, contacts are people.".to_string());
+
+        // Each profile's notes are merged with the rest of the prompt
+        let prompt = system_prompt(&config, &billing).full();
+        assert!(prompt.contains("## Persona"));
+        let notes = "## Database Notes\n\nAmounts are in cents; never join to audit_log.";
+        assert!(prompt.ends_with(notes));
+        let prompt = system_prompt(&config, &crm).full();
+        assert!(prompt.contains("Accounts are companies"));
+        assert!(!prompt.contains("Amounts are in cents"));
+        assert!(!system_prompt(&config, &plain).full().contains("## Database Notes"));
+    }
 }
@@ -0,0 +1,147 @@
+//! Result comparisons across profiles: `pg-agent compare` and `pg-agent diff`.
+
+use anyhow::{Context, Result};
+use postgres_agent_db::QueryExecutor;
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_tools::built_in::{compare_results, ROW_COUNTS_SQL, ResultDiff};
+
+use crate::commands::{create_connection, get_profile, open_query_history, CommandContext};
+
+/// Compare query results between two profiles.
+pub async fn compare_profiles(
+    cmd: &CommandContext,
+    left: &str,
+    right: &str,
+    sql: Option<&str>,
+    keys: &[String],
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let find = |name: &str| {
+        config
+            .databases
+            .iter()
+            .find(|p| p.name == name)
+            .cloned()
+            .with_context(|| format!("Database profile '{}' not found", name))
+    };
+    let (left_profile, right_profile) = (find(left)?, find(right)?);
+    let (left_db, right_db) = tokio::try_join!(
+        create_connection(&config, &left_profile),
+        create_connection(&config, &right_profile)
+    )?;
+
+    let sql = sql.unwrap_or(ROW_COUNTS_SQL);
+    let (left_executor, right_executor) = (QueryExecutor::new(left_db), QueryExecutor::new(right_db));
+    let (left_result, right_result) = tokio::try_join!(
+        left_executor.execute_query(sql),
+        right_executor.execute_query(sql),
+    )
+    .context("Comparison query failed")?;
+    let diff = compare_results(&left_result, &right_result, keys);
+
+    println!("\nComparing {} with {} (key: {})", left, right, diff.key_columns.join(", "));
+    print_result_diff(&diff, left, right);
+
+    Ok(())
+}
+
+/// Re-run a previous query and diff the result against the saved one.
+///
+/// `against` is a query history ID or a JSON file holding saved rows, either
+/// as an array of objects (as written by `export_result`) or as an object
+/// with `rows` (a history entry or query result).
+pub async fn diff_against(
+    cmd: &CommandContext,
+    profile_name: &str,
+    against: &str,
+    sql: Option<&str>,
+    keys: &[String],
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let history = open_query_history(&config)?;
+
+    let (saved_sql, before, label) = if let Ok(id) = against.trim_start_matches('#').parse::<u64>() {
+        let entry = history
+            .get(id)?
+            .with_context(|| format!("No query #{} in {}", id, history.path().display()))?;
+        (Some(entry.sql), entry.result, format!("#{} ({})", id, entry.timestamp.format("%Y-%m-%d %H:%M")))
+    } else {
+        let content = std::fs::read_to_string(against)
+            .with_context(|| format!("Failed to read {}", against))?;
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("{} is not a JSON result file", against))?;
+        let saved_sql = value.get("sql").and_then(|v| v.as_str()).map(str::to_string);
+        let rows = value.get("result").and_then(|r| r.get("rows")).or(value.get("rows")).unwrap_or(&value);
+        let rows: Vec<serde_json::Map<String, serde_json::Value>> = serde_json::from_value(rows.clone())
+            .with_context(|| format!("{} does not hold an array of rows", against))?;
+        let result = QueryResult {
+            columns: rows.first().map(|r| r.keys().cloned().collect()).unwrap_or_default(),
+            row_count: rows.len(),
+            rows,
+            ..QueryResult::default()
+        };
+        (saved_sql, result, against.to_string())
+    };
+
+    let sql = sql
+        .map(str::to_string)
+        .or(saved_sql)
+        .context("No SQL stored with the saved result; pass --sql")?;
+    let profile = get_profile(&config, profile_name)?;
+    let db = create_connection(&config, &profile).await?;
+    let after = QueryExecutor::new(db)
+        .execute_query(&sql)
+        .await
+        .context("Query failed")?;
+    let id = history.record(&profile.name, &sql, &after)?;
+
+    let diff = compare_results(&before, &after, keys);
+    println!("\nComparing {} with now, saved as #{} (key: {})", label, id, diff.key_columns.join(", "));
+    if before.truncated {
+        println!("Note: the saved result was truncated; rows past the limit show as added.");
+    }
+    print_result_diff(&diff, "before", "now");
+
+    Ok(())
+}
+
+/// Print a result diff; `left` and `right` name the two sides.
+fn print_result_diff(diff: &ResultDiff, left: &str, right: &str) {
+    println!("{}\n", "=".repeat(50));
+    println!("Matching rows:      {}", diff.matching);
+    println!("Changed rows:       {}", diff.changed.len());
+    println!("Only in {}: {}", left, diff.only_left.len());
+    println!("Only in {}: {}", right, diff.only_right.len());
+
+    let key_of = |row: &serde_json::Map<String, serde_json::Value>| {
+        diff.key_columns
+            .iter()
+            .map(|c| row.get(c).map(|v| v.to_string()).unwrap_or_default())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if !diff.changed.is_empty() {
+        println!("\nChanged:");
+        for change in &diff.changed {
+            let key = key_of(&change.left);
+            println!("  {}", key);
+            for (column, value) in &change.left {
+                let other = change.right.get(column).cloned().unwrap_or_default();
+                if *value != other {
+                    println!("    {}: {} -> {}", column, value, other);
+                }
+            }
+        }
+    }
+    for (side, rows) in [(left, &diff.only_left), (right, &diff.only_right)] {
+        if !rows.is_empty() {
+            println!("\nOnly in {}:", side);
+            for row in rows {
+                println!("  {}", key_of(row));
+            }
+        }
+    }
+    if diff.is_identical() {
+        println!("\nResults are identical.");
+    }
+}
@@ -0,0 +1,253 @@
+//! System health checks: `pg-agent doctor`.
+
+use anyhow::Result;
+use postgres_agent_config::AppConfig;
+use postgres_agent_config::safety::SafetyLevel as ConfigSafetyLevel;
+use postgres_agent_db::QueryExecutor;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::commands::{create_connection, create_llm_client, get_profile, CommandContext};
+use crate::script::{self, note};
+
+/// Run system doctor check.
+pub async fn run_doctor(cmd: &CommandContext, profile_name: &str, skip_llm: bool) -> Result<()> {
+    note!("\nPostgreSQL Agent System Check");
+    note!("{}\n", "=".repeat(50));
+
+    let mut report = DoctorReport::default();
+
+    let mut checks_passed = 0;
+    let mut checks_total = 0;
+
+    // Check configuration file
+    checks_total += 1;
+    let config_exists = PathBuf::from(&cmd.config_path).exists();
+    report.check("Config file", config_exists);
+    if config_exists {
+        checks_passed += 1;
+    }
+
+    // Check configuration
+    checks_total += 1;
+    match cmd.load_config().await {
+        Ok(config) => {
+            report.check("Configuration", true);
+            checks_passed += 1;
+
+            // Check LLM configuration
+            checks_total += 1;
+            let llm_ok = !config.llm.model.is_empty() && config.llm.max_tokens > 0;
+            report.check("LLM configuration", llm_ok);
+            if llm_ok {
+                checks_passed += 1;
+            }
+
+            // Check LLM connectivity and model availability
+            if skip_llm {
+                report.skip("LLM connectivity");
+            } else {
+                checks_total += 2;
+                let (reachable, model_ok) = check_llm(&config, &mut report).await;
+                if reachable {
+                    checks_passed += 1;
+                }
+                if model_ok {
+                    checks_passed += 1;
+                }
+            }
+
+            // Check database configuration
+            checks_total += 1;
+            let db_ok = !config.databases.is_empty()
+                && config.databases.iter().all(|p| !p.name.is_empty());
+            report.check("Database configuration", db_ok);
+            if db_ok {
+                checks_passed += 1;
+
+                // Check database connectivity and role privileges
+                checks_total += 2;
+                let (connected, privileges_ok) =
+                    check_database(&config, profile_name, &mut report).await;
+                if connected {
+                    checks_passed += 1;
+                }
+                if privileges_ok {
+                    checks_passed += 1;
+                }
+            }
+        }
+        Err(e) => {
+            report.check("Configuration", false);
+            report.detail(format!("Error: {}", e));
+        }
+    }
+
+    note!("\nResult: {}/{} checks passed", checks_passed, checks_total);
+
+    let ready = checks_passed == checks_total;
+    if ready {
+        note!("\nSystem is ready for use!");
+    } else {
+        note!("\nSome checks failed. Review the output above.");
+    }
+
+    if script::json_output() {
+        #[derive(serde::Serialize)]
+        struct DoctorJson<'a> {
+            ready: bool,
+            passed: u32,
+            total: u32,
+            checks: &'a [DoctorCheck],
+        }
+        script::print_document(&DoctorJson {
+            ready,
+            passed: checks_passed,
+            total: checks_total,
+            checks: &report.checks,
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Check database connectivity and audit the connected role's privileges.
+///
+/// Returns `(connected, privileges_ok)`.
+async fn check_database(
+    config: &AppConfig,
+    profile_name: &str,
+    report: &mut DoctorReport,
+) -> (bool, bool) {
+    let db = match get_profile(config, profile_name) {
+        Ok(profile) => create_connection(config, &profile).await,
+        Err(e) => Err(e),
+    };
+    let db = match db {
+        Ok(db) => db,
+        Err(e) => {
+            report.check("Database connectivity", false);
+            report.detail(format!("Error: {:#}", e));
+            return (false, false);
+        }
+    };
+    report.check("Database connectivity", true);
+
+    let privileges = match QueryExecutor::new(db).role_privileges().await {
+        Ok(privileges) => privileges,
+        Err(e) => {
+            report.check("Role privileges", false);
+            report.detail(format!("Error: {}", e));
+            return (true, false);
+        }
+    };
+
+    let allow_writes = config.safety.safety_level != ConfigSafetyLevel::ReadOnly;
+    let warnings = privileges.excess_privileges(allow_writes);
+    report.check("Role privileges", warnings.is_empty());
+    report.detail(format!(
+        "Role: {} (writes: {}, readable schemas: {})",
+        privileges.role,
+        if privileges.can_write() { "yes" } else { "no" },
+        privileges.readable_schemas.join(", ")
+    ));
+    for warning in &warnings {
+        report.detail(format!("Warning: {}", warning));
+    }
+
+    (true, warnings.is_empty())
+}
+
+/// Check that the LLM endpoint is reachable and serves the configured model.
+///
+/// Returns `(reachable, model_available)`.
+async fn check_llm(config: &AppConfig, report: &mut DoctorReport) -> (bool, bool) {
+    let provider = match create_llm_client(config) {
+        Ok(provider) => provider,
+        Err(e) => {
+            report.check("LLM connectivity", false);
+            report.detail(format!("Error: {}", e));
+            return (false, false);
+        }
+    };
+
+    let start = std::time::Instant::now();
+    let models = tokio::time::timeout(Duration::from_secs(15), provider.list_models()).await;
+    let latency_ms = start.elapsed().as_millis();
+
+    let models = match models {
+        Ok(Ok(models)) => models,
+        Ok(Err(e)) => {
+            report.check("LLM connectivity", false);
+            report.detail(format!("Error: {}", e));
+            return (false, false);
+        }
+        Err(_) => {
+            report.check("LLM connectivity", false);
+            report.detail("Error: no response within 15s".to_string());
+            return (false, false);
+        }
+    };
+
+    report.check("LLM connectivity", true);
+    report.detail(format!("Latency: {}ms", latency_ms));
+
+    // Some compatible endpoints do not implement model listing
+    let model_ok = models.is_empty() || models.contains(&config.llm.model);
+    report.check("LLM model", model_ok);
+    if !model_ok {
+        let model = &config.llm.model;
+        report.detail(format!("Model '{}' is not available for this API key", model));
+    }
+
+    (true, model_ok)
+}
+
+/// Result of one `doctor` check.
+#[derive(Debug, serde::Serialize)]
+struct DoctorCheck {
+    /// What was checked.
+    name: String,
+    /// Whether it passed; `None` when skipped.
+    passed: Option<bool>,
+    /// Notes printed under the check.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    details: Vec<String>,
+}
+
+/// Checks run by `doctor`, printed as they finish.
+#[derive(Debug, Default)]
+struct DoctorReport {
+    /// Checks in the order they ran.
+    checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    /// Record and print a check result.
+    fn check(&mut self, name: &str, passed: bool) {
+        note!("[{}] {}: {}", if passed { "✓" } else { "✗" }, name, passed);
+        self.push(name, Some(passed));
+    }
+
+    /// Record and print a skipped check.
+    fn skip(&mut self, name: &str) {
+        note!("[-] {}: skipped", name);
+        self.push(name, None);
+    }
+
+    /// Add a note to the last check.
+    fn detail(&mut self, line: String) {
+        note!("    {}", line);
+        if let Some(check) = self.checks.last_mut() {
+            check.details.push(line);
+        }
+    }
+
+    fn push(&mut self, name: &str, passed: Option<bool>) {
+        self.checks.push(DoctorCheck {
+            name: name.to_string(),
+            passed,
+            details: Vec::new(),
+        });
+    }
+}
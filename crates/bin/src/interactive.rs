@@ -0,0 +1,461 @@
+//! Interactive REPL mode: `pg-agent interactive`.
+
+use anyhow::{bail, Context, Result};
+use postgres_agent_config::{AppConfig, DatabaseProfile};
+use postgres_agent_core::agent::PostgresAgent;
+use postgres_agent_core::session::{latest_query_results, session_results, truncated_query};
+use postgres_agent_core::{events, SessionRecord, SessionTurn};
+use postgres_agent_db::{DbConnection, QueryExecutor};
+use postgres_agent_llm::client::LlmClient;
+use postgres_agent_llm::{capabilities, AnyProvider};
+use postgres_agent_tools::built_in::{ExportFormat, RESULT_PAGE_SIZE};
+use postgres_agent_util::number::NumberFormat;
+use std::io::Write;
+use std::path::PathBuf;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use postgres_agent_cli::OutputFormat;
+use crate::commands::{
+    apply_retention, check_daily_budget, create_agent, create_connection, create_llm_client,
+    error_report, get_profile, mask_url, number_format, numeric_output, open_sessions,
+    open_stats_store, print_query_result, print_result_sets, print_timings, record_usage,
+    run_streaming, shut_down, until_signal, CommandContext,
+};
+use crate::shutdown;
+use crate::stats::give_feedback;
+
+/// Run interactive TUI mode.
+///
+/// With `workspace` (or `agent.temp-workspace`) the session runs on one
+/// dedicated connection, so temp tables the agent creates survive between
+/// questions.
+pub async fn run_interactive(
+    cmd: &CommandContext,
+    profile_name: &str,
+    safety_level: Option<&str>,
+    no_confirm: bool,
+    workspace: bool,
+) -> Result<()> {
+    println!("Starting interactive mode...");
+    println!("Profile: {}", profile_name);
+    println!("(TUI mode - basic CLI REPL active)\n");
+
+    // Load configuration
+    let config = cmd.load_config().await?;
+    apply_retention(&config);
+    let workspace = workspace || config.agent.temp_workspace;
+    let mut profile_name = profile_name.to_string();
+    let profile = get_profile(&config, &profile_name)?;
+    let mut db = open_interactive_db(&config, &profile, workspace).await?;
+    let llm_client = create_llm_client(&config)?;
+    let mut agent = create_agent(
+        llm_client,
+        &db,
+        &config,
+        &profile_name,
+        safety_level,
+        no_confirm,
+        &cmd.overrides,
+    )
+    .await?;
+    let (sender, mut events) = events::channel();
+    agent.set_event_sender(sender.clone());
+    let mut stats_store = open_stats_store(&config);
+    let sessions = open_sessions(&config)?;
+    let mut session = SessionRecord::new(&profile_name, &config.llm.model);
+    let mut pager: Option<ResultPager> = None;
+    let mut stdin = shutdown::LineReader::stdin();
+
+    println!("PostgreSQL Agent Interactive Mode");
+    println!("Session: {}", session.id);
+    println!("Type 'exit' or 'quit' to exit.\n");
+
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+
+        let line = tokio::select! {
+            line = stdin.read_line() => line?,
+            () = shutdown::signal() => {
+                println!();
+                break;
+            }
+        };
+
+        // End of input
+        if line.is_empty() {
+            println!();
+            break;
+        }
+
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+
+        if input.eq_ignore_ascii_case("exit") || input.eq_ignore_ascii_case("quit") {
+            println!("Goodbye!");
+            break;
+        }
+
+        if input.eq_ignore_ascii_case("\\help") || input.eq_ignore_ascii_case("\\h") {
+            print_interactive_help();
+            continue;
+        }
+
+        if let Some(rest) = input
+            .strip_prefix("\\model")
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+        {
+            switch_model(&mut agent, rest.trim());
+            continue;
+        }
+
+        if let Some(rest) = input
+            .strip_prefix("\\connect")
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+        {
+            let name = rest.trim();
+            if name.is_empty() {
+                print_connections(&config, &profile_name).await;
+                continue;
+            }
+            let Some(profile) = config.databases.iter().find(|p| p.name == name) else {
+                println!("Unknown profile '{}'; \\connect lists them.\n", name);
+                continue;
+            };
+            if profile.name == profile_name {
+                println!("Already connected to {}.\n", name);
+                continue;
+            }
+            println!("Connecting to {}...", name);
+            // The old connection stays in use until the new agent is ready
+            let switched = async {
+                let new_db = open_interactive_db(&config, profile, workspace).await?;
+                let llm_client = agent.llm_client().clone();
+                let new_agent = create_agent(
+                    llm_client,
+                    &new_db,
+                    &config,
+                    name,
+                    safety_level,
+                    no_confirm,
+                    &cmd.overrides,
+                )
+                .await;
+                match new_agent {
+                    Ok(new_agent) => Ok((new_db, new_agent)),
+                    Err(e) => {
+                        new_db.close().await;
+                        Err(e)
+                    }
+                }
+            };
+            match switched.await {
+                Ok((new_db, new_agent)) => {
+                    std::mem::replace(&mut db, new_db).close().await;
+                    agent = new_agent;
+                    agent.set_event_sender(sender.clone());
+                    pager = None;
+                    profile_name = profile.name.clone();
+                    let model = agent.llm_client().provider_info().model.clone();
+                    session = SessionRecord::new(&profile_name, &model);
+                    println!("Connected to {} ({}).", profile_name, mask_url(&profile.url));
+                    println!("Session: {}\n", session.id);
+                }
+                Err(e) => println!("Error: {}\nStill connected to {}.\n", e, profile_name),
+            }
+            continue;
+        }
+
+        if let Some(rest) = input
+            .strip_prefix("\\feedback")
+            .filter(|rest| rest.is_empty() || rest.starts_with(' '))
+        {
+            let (rating, comment) = rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+            match give_feedback(&mut stats_store, rating, Some(comment).filter(|c| !c.is_empty())) {
+                Ok(message) => println!("{}\n", message),
+                Err(e) => println!("Error: {}\n", e),
+            }
+            continue;
+        }
+
+        if let Some(action) = PagerAction::parse(input) {
+            let Some(state) = &mut pager else {
+                println!("No truncated result to page through.\n");
+                continue;
+            };
+            let cancel = CancellationToken::new();
+            let paged = state.run(&db, action, number_format(&config), &cancel);
+            match until_signal(&cancel, paged).await {
+                Some(Ok(true)) => {}
+                Some(Ok(false)) => pager = None,
+                Some(Err(e)) => println!("Error: {}\n", error_report(&e)),
+                None => {
+                    println!("\nQuery cancelled.");
+                    break;
+                }
+            }
+            continue;
+        }
+
+        if !check_daily_budget(&config, &stats_store, no_confirm)? {
+            println!("Skipped: daily LLM budget reached.\n");
+            continue;
+        }
+
+        let mut turn = SessionTurn {
+            asked_at: chrono::Utc::now(),
+            question: input.to_string(),
+            ..SessionTurn::default()
+        };
+        let started = std::time::Instant::now();
+        let (result, streamed) =
+            run_streaming(&mut agent, input, &mut events, cmd.overrides.verbose).await;
+        let Some(result) = result else {
+            println!("\nQuery cancelled.");
+            record_usage(&config, &mut stats_store, &profile_name, input, &mut agent, None);
+            turn.duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+            turn.error = Some("Interrupted by a shutdown signal".to_string());
+            session.turns.push(turn);
+            if let Err(e) = sessions.save(&session) {
+                warn!("Failed to save session: {}", e);
+            }
+            break;
+        };
+        let run = result.as_ref().ok();
+        record_usage(&config, &mut stats_store, &profile_name, input, &mut agent, run);
+        if cmd.overrides.timing {
+            print_timings(agent.stats());
+        }
+        turn.duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        turn.results = latest_query_results(agent.context.messages());
+        pager = truncated_query(agent.context.messages())
+            .map(|(sql, cursor)| ResultPager::new(sql, cursor));
+
+        match result {
+            Ok(response) => {
+                if streamed.is_empty() {
+                    println!("\n{}", response.answer);
+                } else if streamed.trim() == response.answer.trim() {
+                    println!();
+                } else {
+                    println!("\n\n{}", response.answer);
+                }
+                for statement in &response.executed_sql {
+                    println!("[SQL: {}]", statement);
+                }
+                if cmd.overrides.show_intermediate {
+                    let numbers = number_format(&config);
+                    let numeric = numeric_output(&config);
+                    print_result_sets(&response.results, OutputFormat::Table, numbers, numeric);
+                }
+                if pager.is_some() {
+                    println!(
+                        "[Result truncated at {} rows: \\next, \\all or \\export <file>]",
+                        RESULT_PAGE_SIZE
+                    );
+                }
+                turn.iterations = response.iterations;
+                turn.sql = response.last_sql().map(str::to_string);
+                turn.results = session_results(&response.results);
+                if response.success {
+                    turn.answer = Some(response.answer);
+                } else {
+                    turn.error = response.error.or(Some(response.answer));
+                }
+            }
+            Err(e) => {
+                if !streamed.is_empty() {
+                    println!();
+                }
+                println!("Error: {}", e);
+                turn.error = Some(e.to_string());
+            }
+        }
+        println!();
+
+        session.turns.push(turn);
+        if let Err(e) = sessions.save(&session) {
+            warn!("Failed to save session: {}", e);
+        }
+    }
+
+    shut_down(&config, &agent, &db).await;
+    Ok(())
+}
+
+/// Print interactive mode help.
+fn print_interactive_help() {
+    println!("\nAvailable commands:");
+    println!("  \\q, \\quit, exit  - Exit interactive mode");
+    println!("  \\model [name]    - Show or switch the LLM model for this session");
+    println!("  \\connect [name]  - List profiles with their health, or switch to one");
+    println!("  \\feedback 👍|👎 [comment] - Rate the last answer");
+    println!("  \\next            - Show the next page of a truncated result");
+    println!("  \\all             - Show every row of a truncated result");
+    println!("  \\export <file>   - Export every row of a truncated result");
+    println!();
+    println!("Tips:");
+    println!("  - Type natural language queries");
+    println!("  - Ask about your database schema");
+    println!("  - Request data analysis or aggregations");
+    println!();
+}
+
+/// Paging action on a truncated REPL result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PagerAction {
+    /// Show the next page (`\\next`).
+    Next,
+    /// Show every remaining row (`\\all`).
+    All,
+    /// Write every row to a file (`\\export <file>`).
+    Export(PathBuf),
+}
+
+impl PagerAction {
+    /// Parse a REPL command, if it is a paging command.
+    fn parse(input: &str) -> Option<Self> {
+        let (command, rest) = input.split_once(' ').unwrap_or((input, ""));
+        match command {
+            "\\next" => Some(Self::Next),
+            "\\all" => Some(Self::All),
+            "\\export" => Some(Self::Export(PathBuf::from(rest.trim()))),
+            _ => None,
+        }
+    }
+}
+
+/// Position in a truncated result shown in the REPL.
+#[derive(Debug)]
+struct ResultPager {
+    /// SQL of the truncated query.
+    sql: String,
+    /// Continuation token of the next page.
+    cursor: String,
+    /// Rows shown so far.
+    shown: usize,
+}
+
+impl ResultPager {
+    /// Start after the first page the agent saw.
+    fn new(sql: String, cursor: String) -> Self {
+        Self {
+            sql,
+            cursor,
+            shown: RESULT_PAGE_SIZE,
+        }
+    }
+
+    /// Run `action`, returning whether rows are left to page.
+    async fn run(
+        &mut self,
+        db: &DbConnection,
+        action: PagerAction,
+        numbers: NumberFormat,
+        cancel: &CancellationToken,
+    ) -> Result<bool> {
+        let executor = QueryExecutor::new(db.clone()).with_cancellation(cancel.clone());
+        match action {
+            PagerAction::Next => {
+                let page = executor
+                    .execute_paged(&self.sql, Some(&self.cursor), RESULT_PAGE_SIZE)
+                    .await?;
+                print_query_result(&page.result, OutputFormat::Table, numbers, db.numeric_output());
+                self.shown += page.result.row_count;
+                let Some(next) = page.next else {
+                    println!("[End of result]\n");
+                    return Ok(false);
+                };
+                self.cursor = next;
+                println!("[Rows {} shown; \\next for more]\n", self.shown);
+                Ok(true)
+            }
+            PagerAction::All => {
+                let result = executor.execute_query(&self.sql).await?;
+                print_query_result(&result, OutputFormat::Table, numbers, db.numeric_output());
+                println!();
+                Ok(false)
+            }
+            PagerAction::Export(path) => {
+                if path.as_os_str().is_empty() {
+                    bail!("Usage: \\export <file.csv|file.json|file.ndjson>");
+                }
+                let format = ExportFormat::from_path(&path)
+                    .with_context(|| format!("Unknown export format for {}", path.display()))?;
+                let result = executor.execute_query(&self.sql).await?;
+                format.write_file(&result, db.numeric_output(), &path)?;
+                println!("Exported {} rows to {}.\n", result.row_count, path.display());
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Connect to a profile for interactive mode, in a temp table workspace
+/// when asked, and start introspecting its schema in the background.
+async fn open_interactive_db(
+    config: &AppConfig,
+    profile: &DatabaseProfile,
+    workspace: bool,
+) -> Result<DbConnection> {
+    let mut db = create_connection(config, profile).await?;
+    if workspace {
+        let pooled = db;
+        db = pooled
+            .workspace()
+            .await
+            .context("Failed to open the temp table workspace")?;
+        pooled.close().await;
+        println!("Temp table workspace: on (temp tables last until you exit)\n");
+    }
+    // Introspect while the user types the next question
+    let prewarm = db.schema_cache().prewarm(&db);
+    tokio::spawn(async move {
+        if let Ok(Err(e)) = prewarm.await {
+            warn!("Background schema introspection failed: {}", e);
+        }
+    });
+    Ok(db)
+}
+
+/// List the configured profiles with their health (`\\connect`).
+async fn print_connections(config: &AppConfig, current: &str) {
+    println!();
+    for profile in &config.databases {
+        let started = std::time::Instant::now();
+        let health = match create_connection(config, profile).await {
+            Ok(db) => {
+                let checked = db.health_check().await;
+                db.close().await;
+                checked.map_err(anyhow::Error::from)
+            }
+            Err(e) => Err(e),
+        };
+        let health = health.map(|()| started.elapsed().as_millis());
+        let marker = if profile.name == current { "*" } else { " " };
+        let health = match health {
+            Ok(ms) => format!("● {}ms", ms),
+            Err(e) => format!("✗ {:#}", e),
+        };
+        println!("{} {} | {} | {}", marker, profile.name, mask_url(&profile.url), health);
+    }
+    println!();
+}
+
+/// Show or switch the session model (`\\model [name]`).
+fn switch_model(agent: &mut PostgresAgent<AnyProvider>, model: &str) {
+    if model.is_empty() {
+        println!("Current model: {}\n", agent.llm_client().provider_info().model);
+        return;
+    }
+
+    agent.llm_client_mut().set_model(model);
+    println!("Switched to {}.", model);
+    if !capabilities(model).supports_tools {
+        println!("Note: {} does not support tool calling; JSON prompting will be used.", model);
+    }
+    println!();
+}
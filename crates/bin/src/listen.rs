@@ -0,0 +1,78 @@
+//! LISTEN/NOTIFY monitoring: `pg-agent listen`.
+
+use anyhow::Result;
+use postgres_agent_db::Listener;
+use postgres_agent_llm::client::LlmClient;
+use postgres_agent_llm::notification_prompt;
+use std::str::FromStr;
+use tracing::warn;
+
+use postgres_agent_cli::OutputFormat;
+use crate::commands::{create_connection, create_llm_client, get_profile, CommandContext};
+use crate::shutdown;
+
+/// Print notifications on `channels` until interrupted.
+///
+/// With `summarize`, each payload is also summarized by the LLM; a failed
+/// summary is logged and the notification printed without one.
+pub async fn listen(
+    cmd: &CommandContext,
+    profile_name: &str,
+    channels: &[String],
+    summarize: bool,
+    output_format: &str,
+    quiet: bool,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let profile = get_profile(&config, profile_name)?;
+    let db = create_connection(&config, &profile).await?;
+    let llm = if summarize { Some(create_llm_client(&config)?) } else { None };
+    let json = matches!(
+        OutputFormat::from_str(output_format).unwrap_or(OutputFormat::Table),
+        OutputFormat::Json
+    );
+
+    let mut listener = Listener::connect(&db).await?;
+    listener.listen(channels).await?;
+    if !quiet {
+        println!("Listening on {} (Ctrl-C to stop)\n", channels.join(", "));
+    }
+
+    loop {
+        let notification = tokio::select! {
+            received = listener.recv() => received?,
+            () = shutdown::signal() => break,
+        };
+        let summary = match &llm {
+            Some(llm) => llm
+                .complete(&notification_prompt(&notification.channel, &notification.payload))
+                .await
+                .map(|reply| reply.trim().to_string())
+                .map_err(|e| warn!("No summary from the LLM: {}", e))
+                .ok(),
+            None => None,
+        };
+
+        if json {
+            let mut line = serde_json::json!(notification);
+            if let Some(summary) = summary {
+                line["summary"] = serde_json::json!(summary);
+            }
+            println!("{}", line);
+        } else {
+            println!(
+                "[{}] {} (pid {}): {}",
+                notification.received_at.format("%Y-%m-%d %H:%M:%S%.3f UTC"),
+                notification.channel,
+                notification.process_id,
+                notification.payload
+            );
+            if let Some(summary) = summary {
+                println!("  => {}", summary);
+            }
+        }
+    }
+
+    db.close().await;
+    Ok(())
+}
@@ -3,43 +3,120 @@
 //! An interactive terminal-based agent for querying PostgreSQL databases
 //! using natural language, powered by LLMs.
 
+mod approvals;
 mod commands;
+mod compare;
+mod crash;
+mod doctor;
+mod interactive;
+mod listen;
+mod onboarding;
+mod optimize;
+mod policy;
+mod script;
+mod seed;
+mod sessions;
+mod shutdown;
+mod stats;
+mod undo;
+mod update;
 
-use anyhow::Result;
-use clap::Parser;
-use postgres_agent_cli::CliArgs;
-use tracing_subscriber::EnvFilter;
+use anyhow::{bail, Result};
+use postgres_agent_cli::{
+    AuditAction, CliArgs, ConfigAction, PolicyAction, SessionsAction, StatsAction,
+};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
+use postgres_agent_util::logger::{setup_logger, LogConfig};
 
-/// Configure logging based on log level.
-fn configure_logging(log_level: &str) {
-    let env_filter = EnvFilter::new(log_level);
-    tracing_subscriber::fmt()
-        .with_env_filter(env_filter)
-        .with_writer(std::io::stderr)
-        .init();
+/// Configure logging from command line arguments.
+fn configure_logging(args: &CliArgs) -> Result<()> {
+    let config = LogConfig {
+        level: args.log_level.clone(),
+        log_file: args.log_file.as_ref().map(Into::into),
+        json_format: args.log_format == "json",
+    };
+    setup_logger(&config).map_err(|e| anyhow::anyhow!("Failed to initialize logging: {}", e))
+}
+
+/// Run the first question asked during onboarding.
+async fn run_first_query(
+    args: &CliArgs,
+    cmd: &commands::CommandContext,
+    query: &str,
+) -> Result<()> {
+    commands::run_query(
+        &commands::Question::new(query),
+        cmd,
+        &args.profile,
+        &args.output,
+        args.safety_level.as_deref(),
+        args.no_confirm,
+        args.quiet,
+    )
+    .await
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     // Parse command line arguments
-    let args = CliArgs::parse();
+    let args = CliArgs::parse_with_config_discovery();
+    script::set_mode(args.output.eq_ignore_ascii_case("json"), args.quiet);
 
+    match run(&args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => script::report_error(&e),
+    }
+}
+
+/// Run the command given on the command line.
+async fn run(args: &CliArgs) -> Result<()> {
     // Configure logging
-    configure_logging(&args.log_level);
+    configure_logging(args)?;
+
+    // Restore the terminal and leave a diagnostic bundle on panic
+    crash::install(&args.config);
+
+    let overrides = commands::ConfigOverrides {
+        deterministic: args.deterministic,
+        tenant: args.tenant.clone(),
+        idempotency_key: args.idempotency_key.clone(),
+        verbose: args.verbose,
+        timing: args.timing,
+        show_intermediate: args.show_intermediate,
+        time_zone: args.tz.clone(),
+        number_format: args.number_format.clone(),
+        precision: args.precision,
+        numeric_output: args.numeric_output.clone(),
+        type_rendering: args.render.clone(),
+        metadata_only: args.metadata_only,
+        sandbox: args.sandbox,
+        snapshot: args.snapshot,
+        persona: args.persona.clone(),
+        run_limits: args.run_limits(),
+    };
+    let cmd = commands::CommandContext::new(&args.config, overrides);
 
     // Display version info if quiet mode is off
     if !args.quiet {
-        println!("PostgreSQL Agent v0.1.0");
-        println!("{}\n", "=".repeat(50));
+        script::note!("PostgreSQL Agent v0.1.0");
+        script::note!("{}\n", "=".repeat(50));
     }
 
+    // Offer the onboarding wizard instead of failing on a missing config
+    let first_run = !Path::new(&args.config).exists() && std::io::stdin().is_terminal();
+
     // Handle commands
     match &args.command {
-        Some(postgres_agent_cli::Commands::Query { query }) => {
-            let query_str = query.join(" ");
+        Some(postgres_agent_cli::Commands::Query { query, context_files, .. }) => {
+            if first_run {
+                onboarding::run_wizard(&args.config, false)?;
+            }
+            let question = commands::Question::from_args(query, context_files)?;
             commands::run_query(
-                &query_str,
-                &args.config,
+                &question,
+                &cmd,
                 &args.profile,
                 &args.output.to_string(),
                 args.safety_level.as_deref(),
@@ -48,19 +125,23 @@ async fn main() -> Result<()> {
             )
             .await?;
         }
-        Some(postgres_agent_cli::Commands::Interactive { profile }) => {
-            commands::run_interactive(
-                &args.config,
+        Some(postgres_agent_cli::Commands::Interactive { profile, workspace, .. }) => {
+            if first_run {
+                onboarding::run_wizard(&args.config, false)?;
+            }
+            interactive::run_interactive(
+                &cmd,
                 profile,
                 args.safety_level.as_deref(),
                 args.no_confirm,
+                *workspace,
             )
             .await?;
         }
         Some(postgres_agent_cli::Commands::Execute { files }) => {
             commands::execute_files(
                 files,
-                &args.config,
+                &cmd,
                 &args.profile,
                 &args.output.to_string(),
                 args.quiet,
@@ -68,20 +149,151 @@ async fn main() -> Result<()> {
             .await?;
         }
         Some(postgres_agent_cli::Commands::Profiles) => {
-            commands::list_profiles(&args.config).await?;
+            commands::list_profiles(&cmd).await?;
+        }
+        Some(postgres_agent_cli::Commands::Config { action: None }) => {
+            commands::show_config(&cmd, false).await?;
+        }
+        Some(postgres_agent_cli::Commands::Config {
+            action: Some(ConfigAction::Init { force }),
+        }) => {
+            if Path::new(&args.config).exists() && !force {
+                bail!("'{}' already exists; use --force to overwrite", args.config);
+            }
+            if let Some(query) = onboarding::run_wizard(&args.config, true)? {
+                run_first_query(args, &cmd, &query).await?;
+            }
+        }
+        Some(postgres_agent_cli::Commands::Models) => {
+            commands::list_models(&cmd).await?;
+        }
+        Some(postgres_agent_cli::Commands::Paths { sizes }) => {
+            commands::show_paths(&cmd, *sizes)?;
+        }
+        Some(postgres_agent_cli::Commands::Stats {
+            action: None,
+            since,
+        }) => {
+            stats::show_usage(&cmd, since.as_deref()).await?;
+        }
+        Some(postgres_agent_cli::Commands::Stats {
+            action: Some(StatsAction::Tools),
+            ..
+        }) => {
+            stats::show_tool_stats(&cmd).await?;
+        }
+        Some(postgres_agent_cli::Commands::Audit {
+            action: AuditAction::Top { since, limit },
+        }) => {
+            stats::show_audit_top(&cmd, since.as_deref(), *limit).await?;
+        }
+        Some(postgres_agent_cli::Commands::Policy { action }) => {
+            let (input, explain) = match action {
+                PolicyAction::Test { input } => (input, false),
+                PolicyAction::Explain { input } => (input, true),
+            };
+            policy::check_policy(
+                &cmd,
+                &args.profile,
+                args.safety_level.as_deref(),
+                input,
+                explain,
+                &args.output,
+            )
+            .await?;
+        }
+        Some(postgres_agent_cli::Commands::Approve { id, deny }) => {
+            approvals::approve_request(&cmd, id.as_deref(), *deny, &args.output).await?;
+        }
+        Some(postgres_agent_cli::Commands::Undo { id, script }) => {
+            undo::undo_mutation(&cmd, *id, *script, args.no_confirm).await?;
+        }
+        Some(postgres_agent_cli::Commands::Feedback { rating, comment }) => {
+            stats::record_feedback(&cmd, rating, comment.as_deref()).await?;
+        }
+        Some(postgres_agent_cli::Commands::Sessions {
+            action: None | Some(SessionsAction::List),
+        }) => {
+            sessions::list_sessions(&cmd).await?;
+        }
+        Some(postgres_agent_cli::Commands::Sessions {
+            action: Some(SessionsAction::Show { id, summary }),
+        }) => {
+            sessions::show_session(&cmd, id, *summary).await?;
+        }
+        Some(postgres_agent_cli::Commands::Sessions {
+            action: Some(SessionsAction::Export { id, format, output, max_rows }),
+        }) => {
+            sessions::export_session(&cmd, id, format, output.as_deref(), *max_rows)
+                .await?;
         }
-        Some(postgres_agent_cli::Commands::Config) => {
-            commands::show_config(&args.config, false).await?;
+        Some(postgres_agent_cli::Commands::Compare {
+            left,
+            right,
+            sql,
+            keys,
+        }) => {
+            compare::compare_profiles(&cmd, left, right, sql.as_deref(), keys).await?;
+        }
+        Some(postgres_agent_cli::Commands::Diff { against, sql, keys }) => {
+            compare::diff_against(&cmd, &args.profile, against, sql.as_deref(), keys)
+                .await?;
+        }
+        Some(postgres_agent_cli::Commands::Seed { table, rows, no_llm }) => {
+            seed::seed_table(
+                &cmd,
+                &args.profile,
+                table,
+                *rows,
+                args.safety_level.as_deref(),
+                args.no_confirm,
+                !no_llm,
+            )
+            .await?;
+        }
+        Some(postgres_agent_cli::Commands::ExplainSql { query }) => {
+            optimize::explain_sql(&cmd, &args.profile, query, &args.output.to_string())
+                .await?;
+        }
+        Some(postgres_agent_cli::Commands::Optimize { query }) => {
+            optimize::optimize(
+                &cmd,
+                &args.profile,
+                query,
+                &args.output.to_string(),
+                args.safety_level.as_deref(),
+                args.no_confirm,
+            )
+            .await?;
         }
         Some(postgres_agent_cli::Commands::Schema { table }) => {
-            commands::show_schema(&args.config, &args.profile, table.as_deref()).await?;
+            commands::show_schema(&cmd, &args.profile, table.as_deref()).await?;
+        }
+        Some(postgres_agent_cli::Commands::Listen { channels, summarize }) => {
+            listen::listen(
+                &cmd,
+                &args.profile,
+                channels,
+                *summarize,
+                &args.output.to_string(),
+                args.quiet,
+            )
+            .await?;
         }
-        Some(postgres_agent_cli::Commands::Doctor) => {
-            commands::run_doctor(&args.config).await?;
+        Some(postgres_agent_cli::Commands::Doctor { skip_llm }) => {
+            doctor::run_doctor(&cmd, &args.profile, *skip_llm).await?;
         }
         Some(postgres_agent_cli::Commands::Version) => {
             println!("PostgreSQL Agent v0.1.0");
         }
+        Some(postgres_agent_cli::Commands::SelfUpdate { check }) => {
+            update::self_update(*check).await?;
+        }
+        None if first_run => {
+            if let Some(query) = onboarding::run_wizard(&args.config, true)? {
+                run_first_query(args, &cmd, &query).await?;
+            }
+        }
         None => {
             // Show help
             println!("PostgreSQL Agent v0.1.0");
@@ -94,13 +306,27 @@ async fn main() -> Result<()> {
             println!("  exec <files>      Execute SQL files");
             println!("  profiles         List available database profiles");
             println!("  config           Show current configuration");
+            println!("  models           List available LLM models");
+            println!("  paths            Show file locations");
+            println!("  compare <a> <b>  Compare query results between profiles");
+            println!("  seed <table>     Insert generated test data");
             println!("  schema           Show database schema");
+            println!("  listen <channel> Print LISTEN/NOTIFY notifications");
             println!("  doctor          Run system health checks");
             println!("  version         Show version information");
+            println!("  self-update     Update to the latest release");
             println!();
             println!("Run 'pg-agent --help' for more information.");
         }
     }
 
+    let checks_updates = !matches!(
+        args.command,
+        Some(postgres_agent_cli::Commands::SelfUpdate { .. })
+    );
+    if checks_updates && !args.quiet && !args.no_update_check {
+        update::notify_if_outdated().await;
+    }
+
     Ok(())
 }
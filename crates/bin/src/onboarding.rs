@@ -0,0 +1,192 @@
+//! First-run onboarding wizard.
+//!
+//! Detects a local PostgreSQL server and common environment variables,
+//! proposes a configuration, and writes it to disk.
+
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+/// Environment variable conventionally holding the OpenAI API key.
+const OPENAI_KEY_VAR: &str = "OPENAI_API_KEY";
+
+/// Answers collected by the wizard.
+#[derive(Debug, Clone)]
+pub struct WizardAnswers {
+    /// Database connection URL.
+    pub database_url: String,
+    /// API key, or an `env://` reference to one.
+    pub api_key: String,
+    /// LLM model identifier.
+    pub model: String,
+    /// Safety level (read-only, balanced, permissive).
+    pub safety_level: String,
+}
+
+/// Run the onboarding wizard and write the resulting configuration.
+///
+/// When `ask_first_query` is set, the user is offered a first question to
+/// run, which is returned.
+pub fn run_wizard(config_path: &str, ask_first_query: bool) -> Result<Option<String>> {
+    println!("Welcome to PostgreSQL Agent!");
+    println!("No configuration found at '{}'. Let's create one.\n", config_path);
+
+    let database_url = prompt("Database URL", &detect_database_url())?;
+    if database_url.is_empty() {
+        bail!("A database URL is required");
+    }
+
+    let api_key = if std::env::var(OPENAI_KEY_VAR).is_ok() {
+        println!("Found {} in the environment; the config will reference it.", OPENAI_KEY_VAR);
+        format!("env://{}", OPENAI_KEY_VAR)
+    } else {
+        prompt("OpenAI API key", "")?
+    };
+
+    let model = prompt("Model", "gpt-4o")?;
+    let safety_level = prompt("Safety level (read-only, balanced, permissive)", "read-only")?;
+
+    let answers = WizardAnswers {
+        database_url,
+        api_key,
+        model,
+        safety_level,
+    };
+    write_config(Path::new(config_path), &render_config(&answers))?;
+    println!("\nWrote configuration to '{}'.", config_path);
+
+    if !ask_first_query {
+        return Ok(None);
+    }
+
+    let query = prompt("Ask your first question (leave empty to skip)", "")?;
+    Ok(Some(query).filter(|q| !q.is_empty()))
+}
+
+/// Propose a database URL from `DATABASE_URL` or a local server.
+fn detect_database_url() -> String {
+    if let Ok(url) = std::env::var("DATABASE_URL") {
+        println!("Found DATABASE_URL in the environment.");
+        return url;
+    }
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], 5432));
+    if TcpStream::connect_timeout(&addr, Duration::from_millis(500)).is_ok() {
+        println!("Found a PostgreSQL server on localhost:5432.");
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "postgres".to_string());
+        return format!("postgresql://{}@localhost:5432/postgres", user);
+    }
+
+    String::new()
+}
+
+/// Render the configuration file contents.
+#[must_use]
+pub fn render_config(answers: &WizardAnswers) -> String {
+    format!(
+        r#"# PostgreSQL Agent Configuration
+# Generated by `pg-agent config init`
+
+[llm]
+provider = "openai"
+model = {model}
+api-key = {api_key}
+
+[[databases]]
+name = "default"
+url = {url}
+
+[safety]
+safety-level = {safety_level}
+"#,
+        model = toml_string(&answers.model),
+        api_key = toml_string(&answers.api_key),
+        url = toml_string(&answers.database_url),
+        safety_level = toml_string(&answers.safety_level),
+    )
+}
+
+/// Quote a value as a TOML basic string.
+fn toml_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Write the configuration, readable only by the owner where supported.
+///
+/// The file is created with its final permissions, so the API key is never
+/// readable by others, and an existing file is never overwritten.
+fn write_config(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt as _;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Prompt for a value on stdin, returning the default on empty input.
+fn prompt(question: &str, default: &str) -> Result<String> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    std::io::stdout().flush()?;
+
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+
+    Ok(if input.is_empty() { default } else { input }.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_agent_config::safety::SafetyLevel;
+    use postgres_agent_config::ConfigLoader;
+
+    #[test]
+    fn test_rendered_config_loads() {
+        let answers = WizardAnswers {
+            database_url: "postgresql://me@localhost:5432/postgres".to_string(),
+            api_key: "sk-\"quoted\"".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            safety_level: "balanced".to_string(),
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_config(&path, &render_config(&answers)).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        // An existing configuration is left alone
+        assert!(write_config(&path, "").is_err());
+
+        let config = ConfigLoader::new(&path).load().unwrap();
+        assert_eq!(config.llm.model, "gpt-4o-mini");
+        assert_eq!(
+            config.llm.api_key.as_ref().map(|k| k.expose().as_str()),
+            Some("sk-\"quoted\"")
+        );
+        assert_eq!(config.databases[0].url, answers.database_url);
+        assert_eq!(config.safety.safety_level, SafetyLevel::Balanced);
+    }
+}
@@ -0,0 +1,293 @@
+//! Query explanation and tuning: `pg-agent explain-sql` and
+//! `pg-agent optimize`.
+
+use anyhow::{bail, Context, Result};
+use postgres_agent_core::agent::SafetyLevel as CoreSafetyLevel;
+use postgres_agent_db::{tuning, DbConnection};
+use postgres_agent_llm::client::LlmClient;
+use postgres_agent_llm::{explain_sql_prompt, generate_typed, optimize_prompt};
+use postgres_agent_safety::{tables_in_query, ConfirmationLevel};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::commands::{
+    confirm_action, create_connection, create_llm_client, get_profile, map_safety_level,
+    parse_safety_level, CommandContext,
+};
+
+/// Explain an existing query in plain English.
+///
+/// `query` is a SQL file or the statement itself. The definitions of the
+/// tables it refers to are sent along so that the explanation names real
+/// columns; without a database connection the query is explained alone.
+pub async fn explain_sql(
+    cmd: &CommandContext,
+    profile_name: &str,
+    query: &str,
+    output_format: &str,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let sql = read_query(query)?;
+    if sql.trim().is_empty() {
+        bail!("Nothing to explain: the query is empty");
+    }
+    let llm = create_llm_client(&config)?;
+
+    let profile = get_profile(&config, profile_name)?;
+    let tables = match create_connection(&config, &profile).await {
+        Ok(db) => {
+            let tables = referenced_tables(&db, &sql).await;
+            db.close().await;
+            tables
+        }
+        Err(e) => {
+            warn!("Explaining without table definitions: {}", e);
+            Vec::new()
+        }
+    };
+
+    let explanation = llm.complete(&explain_sql_prompt(&sql, &tables)).await?;
+    let explanation = explanation.trim();
+    if output_format == "json" {
+        let tables: Vec<&str> = tables.iter().map(|(table, _)| table.as_str()).collect();
+        let output = serde_json::json!({
+            "sql": sql.trim(),
+            "tables": tables,
+            "explanation": explanation,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        println!("{}", explanation);
+    }
+    Ok(())
+}
+
+/// Propose a faster rewrite of a query and indexes for it.
+///
+/// The model sees the current plan, and the statistics and indexes of the
+/// tables the query reads. Plans are estimates from `EXPLAIN`; the query is
+/// never run. Proposed indexes are only built, to plan the query with them
+/// in a transaction that is rolled back, after confirmation; they are
+/// printed for the user to create.
+pub async fn optimize(
+    cmd: &CommandContext,
+    profile_name: &str,
+    query: &str,
+    output_format: &str,
+    safety_level: Option<&str>,
+    no_confirm: bool,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let sql = read_query(query)?;
+    let sql = sql.trim();
+    if sql.is_empty() {
+        bail!("Nothing to optimize: the query is empty");
+    }
+    let llm = create_llm_client(&config)?;
+    let profile = get_profile(&config, profile_name)?;
+    let db = create_connection(&config, &profile).await?;
+    let json = output_format == "json";
+
+    let plan = tuning::explain(&db, sql).await.context("Failed to plan the query")?;
+    let mut stats = Vec::new();
+    for (table, _) in referenced_tables(&db, sql).await {
+        match tuning::table_stats(&db, &table).await {
+            Ok(Some(table_stats)) => stats.push(table_stats),
+            Ok(None) => {}
+            Err(e) => warn!("No statistics for {}: {}", table, e),
+        }
+    }
+
+    let tables: Vec<(String, String, Vec<String>)> = stats
+        .iter()
+        .map(|s| (s.table.clone(), s.summary(), s.indexes.clone()))
+        .collect();
+    let prompt = optimize_prompt(sql, &plan.outline(), &tables);
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "explanation": { "type": "string" },
+            "rewrittenSql": { "type": "string" },
+            "indexes": { "type": "array", "items": { "type": "string" } }
+        },
+        "required": ["explanation", "rewrittenSql", "indexes"]
+    });
+    let proposal: Optimization = generate_typed(&llm, &prompt, &schema).await?;
+
+    // Rewrites are only planned, like the original
+    let rewrite = proposal
+        .rewritten_sql
+        .as_deref()
+        .map(str::trim)
+        .filter(|rewrite| !rewrite.is_empty() && *rewrite != sql);
+    let rewrite_plan = match rewrite {
+        Some(rewrite) => Some(tuning::explain(&db, rewrite).await),
+        None => None,
+    };
+
+    let mut indexes = Vec::new();
+    for ddl in &proposal.indexes {
+        if tuning::trial_index(ddl).is_some() {
+            indexes.push(ddl.trim().to_string());
+        } else {
+            warn!("Ignoring a suggestion that is not a CREATE INDEX: {}", ddl);
+        }
+    }
+
+    // The trial is rolled back, so it needs less than creating the indexes:
+    // any level that allows changes, and a confirmation
+    let level = match safety_level {
+        Some(s) => parse_safety_level(s),
+        None => map_safety_level(config.safety.safety_level),
+    };
+    let refused = matches!(level, CoreSafetyLevel::ReadOnly)
+        .then_some("the read-only safety level allows no DDL, even rolled back");
+    let best_sql = match &rewrite_plan {
+        Some(Ok(_)) => rewrite.unwrap_or(sql),
+        _ => sql,
+    };
+    let index_plan = if indexes.is_empty() || refused.is_some() {
+        None
+    } else if no_confirm
+        || (!json
+            && confirm_action(
+                &format!(
+                    "Build the {} suggested indexes in a transaction that is rolled back, to \
+                     compare plans? Writes to the tables wait while they build.",
+                    indexes.len()
+                ),
+                ConfirmationLevel::Simple,
+                Duration::from_secs(config.safety.confirmation_ttl_secs),
+                None,
+            ))
+    {
+        Some(tuning::explain_with_indexes(&db, &indexes, best_sql).await)
+    } else {
+        None
+    };
+    db.close().await;
+
+    let cost = plan.total_cost();
+    if json {
+        let rewrite_json = rewrite.map(|rewrite| match &rewrite_plan {
+            Some(Ok(rewrite_plan)) => {
+                serde_json::json!({ "sql": rewrite, "cost": rewrite_plan.total_cost() })
+            }
+            Some(Err(e)) => serde_json::json!({ "sql": rewrite, "error": e.to_string() }),
+            None => serde_json::json!({ "sql": rewrite }),
+        });
+        let with_indexes = match &index_plan {
+            Some(Ok(index_plan)) => serde_json::json!({ "cost": index_plan.total_cost() }),
+            Some(Err(e)) => serde_json::json!({ "error": e.to_string() }),
+            None => serde_json::Value::Null,
+        };
+        let output = serde_json::json!({
+            "sql": sql,
+            "cost": cost,
+            "plan": plan,
+            "tables": stats,
+            "explanation": proposal.explanation.trim(),
+            "rewrite": rewrite_json,
+            "indexes": indexes,
+            "withIndexes": with_indexes,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    println!("Current plan (cost {:.2}):\n{}\n", cost, plan.outline());
+    println!("{}", proposal.explanation.trim());
+    if let Some(rewrite) = rewrite {
+        println!("\nRewritten query:\n\n```sql\n{}\n```", rewrite);
+        match &rewrite_plan {
+            Some(Ok(rewrite_plan)) => {
+                println!("{}", cost_change(cost, rewrite_plan.total_cost()));
+                println!("{}", rewrite_plan.outline());
+            }
+            Some(Err(e)) => println!("The rewritten query could not be planned: {}", e),
+            None => {}
+        }
+    }
+    if !indexes.is_empty() {
+        println!("\nSuggested indexes (not created):");
+        for ddl in &indexes {
+            println!("  {};", ddl.trim_end_matches(';'));
+        }
+        match (&index_plan, &refused) {
+            (_, Some(reason)) => println!("Not tried: {}", reason),
+            (Some(Ok(index_plan)), _) => {
+                println!("With the indexes, {}", cost_change(cost, index_plan.total_cost()));
+                println!("{}", index_plan.outline());
+            }
+            (Some(Err(e)), _) => println!("The indexes could not be tried: {}", e),
+            (None, _) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Proposal of the model for `optimize`.
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Optimization {
+    /// Why the current plan is slow and how the proposals help.
+    explanation: String,
+    /// Cheaper query returning the same rows; empty for none.
+    #[serde(default)]
+    rewritten_sql: Option<String>,
+    /// `CREATE INDEX` statements.
+    #[serde(default)]
+    indexes: Vec<String>,
+}
+
+/// A plan cost compared with the original, e.g. `cost 8.29 (-59%)`.
+fn cost_change(before: f64, after: f64) -> String {
+    if before > 0.0 {
+        format!("cost {:.2} ({:+.0}%)", after, (after - before) / before * 100.0)
+    } else {
+        format!("cost {:.2}", after)
+    }
+}
+
+/// The SQL of a file, or `query` itself if it is not a file.
+fn read_query(query: &str) -> Result<String> {
+    if PathBuf::from(query).is_file() {
+        std::fs::read_to_string(query).with_context(|| format!("Failed to read file: {}", query))
+    } else {
+        Ok(query.to_string())
+    }
+}
+
+/// Tables of the database that `sql` refers to, with their columns.
+async fn referenced_tables(db: &DbConnection, sql: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let schema = match db.cached_schema().await {
+        Ok(schema) => schema,
+        Err(e) => {
+            warn!("Explaining without table definitions: {}", e);
+            return Vec::new();
+        }
+    };
+    let qualified: Vec<String> = schema
+        .tables
+        .iter()
+        .map(|t| format!("{}.{}", t.table_schema, t.table_name))
+        .collect();
+    tables_in_query(sql, &qualified)
+        .into_iter()
+        .map(|table| {
+            let (_, name) = table.split_once('.').unwrap_or(("", table));
+            let columns = schema
+                .columns
+                .get(name)
+                .map(|columns| {
+                    columns
+                        .iter()
+                        .map(|c| (c.column_name.clone(), c.data_type.clone()))
+                        .collect()
+                })
+                .unwrap_or_default();
+            (table.to_string(), columns)
+        })
+        .collect()
+}
@@ -0,0 +1,113 @@
+//! Checks of SQL against the safety policy: `pg-agent policy`.
+
+use anyhow::{Context, Result};
+use postgres_agent_db::QueryExecutor;
+use postgres_agent_safety::{SafetyContext, ValidationDetailKind};
+use tracing::warn;
+
+use postgres_agent_cli::PolicyInput;
+use crate::commands::{
+    create_connection, get_profile, map_safety_level, parse_safety_level, safety_validator,
+    validator_level, CommandContext,
+};
+
+/// Show the safety policy's decision on a statement without running it;
+/// with `explain`, every check, pattern, rule and condition it went
+/// through.
+///
+/// The planner's row estimate and the connected role are looked up only
+/// when a rule needs them and `--rows` or `--role` is not given; without a
+/// database they stay unknown.
+pub async fn check_policy(
+    cmd: &CommandContext,
+    profile_name: &str,
+    safety_level: Option<&str>,
+    input: &PolicyInput,
+    explain: bool,
+    output: &str,
+) -> Result<()> {
+    let sql = input.sql.as_str();
+    let config = cmd.load_config().await?;
+    let validator = safety_validator(&config)?;
+    let level = match safety_level {
+        Some(s) => parse_safety_level(s),
+        None => map_safety_level(config.safety.safety_level),
+    };
+    let mut ctx = SafetyContext::with_level(validator_level(level));
+    if let Some(at) = &input.at {
+        let today = chrono::Local::now().date_naive();
+        let now = chrono::NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M")
+            .or_else(|_| chrono::NaiveTime::parse_from_str(at, "%H:%M").map(|t| today.and_time(t)))
+            .with_context(|| format!("Invalid --at '{}' (expected YYYY-MM-DD HH:MM)", at))?;
+        ctx = ctx.at(now);
+    }
+    ctx.role = input.role.clone();
+    ctx.estimated_rows = input.rows;
+
+    let policy = validator.policy();
+    let lookup_role = ctx.role.is_none() && policy.needs_role();
+    let lookup_rows = ctx.estimated_rows.is_none() && policy.needs_row_estimate();
+    if lookup_role || lookup_rows {
+        let profile = get_profile(&config, profile_name)?;
+        match create_connection(&config, &profile).await {
+            Ok(db) => {
+                let executor = QueryExecutor::new(db);
+                if lookup_role {
+                    ctx.role = executor.current_role().await.ok();
+                }
+                if lookup_rows {
+                    match executor.estimate_rows(sql).await {
+                        Ok(rows) => ctx.estimated_rows = rows,
+                        Err(e) => warn!("Could not estimate rows: {}", e),
+                    }
+                }
+            }
+            Err(e) => warn!("{:#}; role and row estimate are unknown", e),
+        }
+    }
+
+    let explanation = validator.explain(sql, &ctx);
+    if output == "json" {
+        let json = if explain {
+            serde_json::to_string_pretty(&explanation)?
+        } else {
+            serde_json::to_string_pretty(&explanation.result)?
+        };
+        println!("{}", json);
+        return Ok(());
+    }
+
+    let unknown = || "unknown".to_string();
+    println!("Safety level:   {:?}", ctx.level);
+    println!("Role:           {}", ctx.role.clone().unwrap_or_else(unknown));
+    println!(
+        "Estimated rows: {}",
+        ctx.estimated_rows.map_or_else(unknown, |rows| rows.to_string())
+    );
+    let now = ctx.now.unwrap_or_else(|| chrono::Local::now().naive_local());
+    println!("Time:           {}", now.format("%a %Y-%m-%d %H:%M"));
+    println!();
+    if explain {
+        println!("{}", explanation);
+        return Ok(());
+    }
+
+    let result = &explanation.result;
+    println!("Operation: {}", result.operation_type.label());
+    match &result.rule {
+        Some(rule) => println!("Decision:  {} (rule '{}')", explanation.decision(), rule),
+        None => println!("Decision:  {}", explanation.decision()),
+    }
+    if let Some(error) = &result.error {
+        println!("  {}", error);
+    }
+    for detail in &result.details {
+        if matches!(detail.kind, ValidationDetailKind::PolicyRule) && result.error.is_none() {
+            println!("  {}", detail.message);
+        }
+    }
+    for warning in &result.warnings {
+        println!("  warning: {}", warning);
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Machine-readable output for scripts.
+//!
+//! With `--output json`, a command writes exactly one JSON document to
+//! stdout: its result, or an error object such as
+//! `{"error": {"kind": "database", "message": "...", "exitCode": 3}}`.
+//! Banners, progress and other text meant for people go to stderr instead,
+//! and `--quiet` drops them. Failed commands exit with the code of their
+//! [`ErrorKind`] in every output format.
+
+use std::fmt;
+use std::io::Write;
+use std::process::ExitCode;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use postgres_agent_config::ConfigError;
+use postgres_agent_core::AgentError;
+use postgres_agent_db::DbError;
+use postgres_agent_llm::LlmError;
+use serde::Serialize;
+
+use crate::shutdown::Interrupted;
+
+/// How command output is written.
+#[derive(Debug, Clone, Copy, Default)]
+struct OutputMode {
+    /// Results are a JSON document (`--output json`).
+    json: bool,
+    /// Text for people is dropped (`--quiet`).
+    quiet: bool,
+}
+
+/// Output mode of this process.
+static MODE: OnceLock<OutputMode> = OnceLock::new();
+
+/// Set the output mode; only the first call has an effect.
+pub fn set_mode(json: bool, quiet: bool) {
+    let _ = MODE.set(OutputMode { json, quiet });
+}
+
+/// Whether results are written as a JSON document.
+pub fn json_output() -> bool {
+    MODE.get().is_some_and(|m| m.json)
+}
+
+/// Print a line meant for people: to stdout, or with JSON output to stderr,
+/// or nowhere with `--quiet` JSON output. Use the [`note!`] macro.
+pub fn print_note(args: fmt::Arguments<'_>) {
+    let mode = MODE.get().copied().unwrap_or_default();
+    match (mode.json, mode.quiet) {
+        (false, _) => println!("{}", args),
+        (true, false) => eprintln!("{}", args),
+        (true, true) => {}
+    }
+}
+
+/// Print a line meant for people, formatted like `println!`; see [`print_note`].
+macro_rules! note {
+    ($($arg:tt)*) => {
+        $crate::script::print_note(format_args!($($arg)*))
+    };
+}
+pub(crate) use note;
+
+/// Write the JSON document of a command to stdout.
+pub fn print_document<T: Serialize>(document: &T) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, document)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// What made a command fail; sets its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Anything not covered below.
+    General,
+    /// Missing or invalid configuration.
+    Config,
+    /// Connection or query failure.
+    Database,
+    /// LLM provider failure.
+    Llm,
+    /// Blocked by a safety rule.
+    Safety,
+    /// Stopped by a shutdown signal.
+    Interrupted,
+}
+
+impl ErrorKind {
+    /// Kind of the first error in the chain that has one.
+    pub fn of(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|cause| {
+                if cause.is::<Interrupted>() {
+                    Some(Self::Interrupted)
+                } else if cause.is::<ConfigError>() {
+                    Some(Self::Config)
+                } else if cause.is::<DbError>() {
+                    Some(Self::Database)
+                } else if cause.is::<LlmError>() {
+                    Some(Self::Llm)
+                } else {
+                    cause.downcast_ref::<AgentError>().and_then(Self::of_agent_error)
+                }
+            })
+            .unwrap_or(Self::General)
+    }
+
+    /// Kind of an agent error, if it maps to one.
+    fn of_agent_error(error: &AgentError) -> Option<Self> {
+        match error {
+            AgentError::ConfigurationError { .. } => Some(Self::Config),
+            AgentError::DatabaseError { .. } => Some(Self::Database),
+            AgentError::LlmError { .. } | AgentError::ContextTooLarge { .. } => Some(Self::Llm),
+            AgentError::SafetyViolation { .. } => Some(Self::Safety),
+            AgentError::Cancelled => Some(Self::Interrupted),
+            _ => None,
+        }
+    }
+
+    /// Process exit code.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::General => 1,
+            Self::Config => 2,
+            Self::Database => 3,
+            Self::Llm => 4,
+            Self::Safety => 5,
+            // As shells report a process ended by SIGINT
+            Self::Interrupted => 130,
+        }
+    }
+}
+
+/// Report a failed command, as an error object on stdout with JSON output,
+/// and return its exit code.
+pub fn report_error(error: &anyhow::Error) -> ExitCode {
+    let kind = ErrorKind::of(error);
+    if !json_output() {
+        eprintln!("Error: {:?}", error);
+        return ExitCode::from(kind.exit_code());
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ErrorJson {
+        kind: ErrorKind,
+        message: String,
+        exit_code: u8,
+    }
+    #[derive(Serialize)]
+    struct ErrorDocument {
+        error: ErrorJson,
+    }
+    let document = ErrorDocument {
+        error: ErrorJson {
+            kind,
+            message: format!("{:#}", error),
+            exit_code: kind.exit_code(),
+        },
+    };
+    note!("Error: {:#}", error);
+    if let Err(e) = print_document(&document) {
+        eprintln!("Error: {}", e);
+    }
+    ExitCode::from(kind.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        let error = anyhow::Error::new(DbError::ConnectionFailed).context("Failed to connect");
+        assert_eq!(ErrorKind::of(&error), ErrorKind::Database);
+        assert_eq!(ErrorKind::of(&error).exit_code(), 3);
+
+        let error = anyhow::Error::new(AgentError::safety_violation("DROP")).context("Agent error");
+        assert_eq!(ErrorKind::of(&error), ErrorKind::Safety);
+        let error = anyhow::Error::new(Interrupted::new("query cancelled"));
+        assert_eq!(ErrorKind::of(&error).exit_code(), 130);
+        assert_eq!(ErrorKind::of(&anyhow::anyhow!("File not found")), ErrorKind::General);
+    }
+}
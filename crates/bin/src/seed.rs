@@ -0,0 +1,859 @@
+//! Test data generation for `pg-agent seed`.
+//!
+//! Values are chosen from the column type and name (emails for `email`,
+//! cities for `city`, ... made up with the `fake` crate), from sampled
+//! parent keys for foreign keys, or from example values suggested by the
+//! LLM for other text columns. Columns under a primary key, unique
+//! constraint or unique index get values that are distinct from each other.
+
+use anyhow::{bail, Context, Result};
+use chrono::{TimeDelta, Utc};
+use fake::faker::address::en::{CityName, CountryName};
+use fake::faker::lorem::en::Sentence;
+use fake::faker::name::en::{FirstName, LastName};
+use fake::faker::phone_number::en::PhoneNumber;
+use fake::Fake;
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_db::{ColumnInfo, QueryExecutor, Sandbox, Snapshot};
+use postgres_agent_llm::client::LlmClient;
+use postgres_agent_llm::seed_hints_prompt;
+use postgres_agent_safety::{ConfirmationLevel, SafetyContext};
+use postgres_agent_tools::IdempotencyLedger;
+use postgres_agent_util::ident::split_qualified_name;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::commands::{
+    confirm_action, create_connection, create_llm_client, get_profile, map_safety_level,
+    open_query_history, parse_safety_level, safety_validator, sandbox_mode, validator_level,
+    CommandContext,
+};
+
+/// Rows per INSERT statement.
+const BATCH_SIZE: usize = 100;
+
+/// How far back generated dates and timestamps reach.
+const MAX_AGE_DAYS: i64 = 3 * 365;
+
+/// Rows generated in a row before giving up on distinct unique values.
+const MAX_ATTEMPTS: usize = 100;
+
+const STATUSES: &[&str] = &["active", "inactive", "pending"];
+
+/// Where a column's values come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSource {
+    /// Generated from the column type and name.
+    Generated,
+    /// Picked from a fixed set, e.g. sampled parent keys or LLM hints.
+    Choices(Vec<String>),
+}
+
+/// A column to fill.
+#[derive(Debug, Clone)]
+pub struct SeedColumn {
+    /// Column name.
+    pub name: String,
+    /// `information_schema` data type.
+    pub data_type: String,
+    /// Whether NULL is allowed.
+    pub nullable: bool,
+    /// Maximum character length.
+    pub max_length: Option<i64>,
+    /// Numeric precision and scale.
+    pub numeric: Option<(i64, i64)>,
+    /// Value source.
+    pub source: ValueSource,
+    /// Whether the column alone is unique, so every row needs its own value.
+    pub unique: bool,
+}
+
+impl SeedColumn {
+    /// Whether values come from type/name heuristics that only produce
+    /// placeholder text, so an LLM hint would help.
+    #[must_use]
+    pub fn wants_hint(&self) -> bool {
+        self.source == ValueSource::Generated
+            && !self.unique
+            && is_text(&self.data_type)
+            && name_kind(&self.name) == NameKind::Other
+    }
+}
+
+/// Build the seed columns for a table.
+///
+/// Columns with defaults and those listed in `skip` (identity and
+/// generated columns) are left to the database. `unique` holds the column
+/// sets of the table's unique constraints.
+#[must_use]
+pub fn plan_columns(
+    columns: &[ColumnInfo],
+    skip: &[String],
+    unique: &[Vec<String>],
+) -> Vec<SeedColumn> {
+    columns
+        .iter()
+        .filter(|c| c.column_default.is_none() && !skip.contains(&c.column_name))
+        .map(|c| SeedColumn {
+            name: c.column_name.clone(),
+            data_type: c.data_type.clone(),
+            nullable: c.is_nullable,
+            max_length: c.character_maximum_length,
+            numeric: c.numeric_precision.map(|p| (p, c.numeric_scale.unwrap_or(0))),
+            source: if c.enum_labels.is_empty() {
+                ValueSource::Generated
+            } else {
+                ValueSource::Choices(c.enum_labels.clone())
+            },
+            unique: unique.iter().any(|set| *set == [c.column_name.clone()]),
+        })
+        .collect()
+}
+
+/// Parse an LLM reply mapping column names to example values.
+///
+/// Code fences around the JSON are tolerated.
+#[must_use]
+pub fn parse_hints(reply: &str) -> HashMap<String, Vec<String>> {
+    let start = reply.find('{');
+    let end = reply.rfind('}');
+    let (Some(start), Some(end)) = (start, end) else {
+        return HashMap::new();
+    };
+    serde_json::from_str::<HashMap<String, Vec<serde_json::Value>>>(&reply[start..=end])
+        .map(|hints| {
+            hints
+                .into_iter()
+                .map(|(column, values)| {
+                    let values = values
+                        .into_iter()
+                        .map(|v| match v {
+                            serde_json::Value::String(s) => s,
+                            other => other.to_string(),
+                        })
+                        .collect();
+                    (column, values)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Generate `count` rows; `None` is SQL NULL.
+///
+/// No two rows agree on every column of a set in `unique`, unless one of
+/// them is NULL there. Sets with a column left to the database, such as a
+/// serial key, are for the database to keep unique.
+///
+/// # Errors
+/// Returns an error if a NOT NULL column has a type that cannot be
+/// generated, or if not enough distinct values can be found for a unique
+/// column or set.
+pub fn generate_rows(
+    columns: &[SeedColumn],
+    unique: &[Vec<String>],
+    count: usize,
+    rng: &mut impl Rng,
+) -> Result<Vec<Vec<Option<String>>>> {
+    let pools = unique_pools(columns, count, rng)?;
+    let sets: Vec<(&Vec<String>, Vec<usize>)> = unique
+        .iter()
+        .filter_map(|set| {
+            let indexes = set
+                .iter()
+                .map(|name| columns.iter().position(|c| c.name == *name))
+                .collect::<Option<Vec<_>>>()?;
+            Some((set, indexes))
+        })
+        .collect();
+    let mut seen = vec![HashSet::new(); sets.len()];
+    // Distinct values are numbered from a random start, so that another
+    // run does not repeat them
+    let start = rng.gen_range(1..1_000_000_000u64);
+
+    let mut rows = Vec::with_capacity(count);
+    for index in 0..count {
+        let serial = start + index as u64;
+        let mut attempts = 0;
+        let row = loop {
+            let row = generate_row(columns, &pools, index, serial, rng)?;
+            let keys: Vec<Option<Vec<String>>> = sets
+                .iter()
+                .map(|(_, indexes)| indexes.iter().map(|&i| row[i].clone()).collect())
+                .collect();
+            let fresh = keys
+                .iter()
+                .zip(&seen)
+                .all(|(key, seen)| key.as_ref().is_none_or(|key| !seen.contains(key)));
+            if fresh {
+                for (key, seen) in keys.into_iter().zip(&mut seen) {
+                    seen.extend(key);
+                }
+                break row;
+            }
+            attempts += 1;
+            if attempts == MAX_ATTEMPTS {
+                let (set, _) = sets
+                    .iter()
+                    .zip(keys.iter().zip(&seen))
+                    .find(|(_, (key, seen))| key.as_ref().is_some_and(|key| seen.contains(key)))
+                    .map_or(&sets[0], |(set, _)| set);
+                bail!(
+                    "Cannot generate more than {} rows with distinct ({})",
+                    rows.len(),
+                    set.join(", ")
+                );
+            }
+        };
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Names of a generated person, shared by the columns of a row so that
+/// e.g. name and email agree.
+struct Person {
+    /// First name.
+    first: String,
+    /// Last name.
+    last: String,
+    /// Number telling apart people of the same name.
+    tag: u32,
+}
+
+/// Generate one row.
+fn generate_row(
+    columns: &[SeedColumn],
+    pools: &[Option<Vec<String>>],
+    index: usize,
+    serial: u64,
+    rng: &mut impl Rng,
+) -> Result<Vec<Option<String>>> {
+    let person = Person {
+        first: FirstName().fake_with_rng(rng),
+        last: LastName().fake_with_rng(rng),
+        tag: rng.gen_range(0..1_000_000),
+    };
+    columns
+        .iter()
+        .zip(pools)
+        .map(|(column, pool)| match pool {
+            Some(pool) => Ok(pool.get(index).cloned()),
+            None => value_for(column, &person, column.unique.then_some(serial), rng),
+        })
+        .collect()
+}
+
+/// The choices of each unique column picking from a fixed set, shuffled,
+/// so that row `i` takes the `i`th one.
+///
+/// # Errors
+/// Returns an error if a NOT NULL column has fewer choices than rows.
+fn unique_pools(
+    columns: &[SeedColumn],
+    count: usize,
+    rng: &mut impl Rng,
+) -> Result<Vec<Option<Vec<String>>>> {
+    columns
+        .iter()
+        .map(|column| {
+            let ValueSource::Choices(choices) = &column.source else {
+                return Ok(None);
+            };
+            if !column.unique {
+                return Ok(None);
+            }
+            let mut pool: Vec<String> =
+                choices.iter().collect::<BTreeSet<_>>().into_iter().cloned().collect();
+            if pool.len() < count && !column.nullable {
+                bail!(
+                    "'{}' is unique and has only {} values to choose from; insert at most {} rows",
+                    column.name,
+                    pool.len(),
+                    pool.len()
+                );
+            }
+            pool.shuffle(rng);
+            Ok(Some(pool))
+        })
+        .collect()
+}
+
+/// Render INSERT statements for generated rows.
+#[must_use]
+pub fn insert_statements(
+    table: &str,
+    columns: &[SeedColumn],
+    rows: &[Vec<Option<String>>],
+) -> Vec<String> {
+    let column_list = columns
+        .iter()
+        .map(|c| quote_ident(&c.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    rows.chunks(BATCH_SIZE)
+        .map(|batch| {
+            let values = batch
+                .iter()
+                .map(|row| {
+                    let literals = row
+                        .iter()
+                        .map(|v| v.as_deref().map_or_else(|| "NULL".to_string(), quote_literal))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("({})", literals)
+                })
+                .collect::<Vec<_>>()
+                .join(",\n    ");
+            format!("INSERT INTO {} ({}) VALUES\n    {}", table, column_list, values)
+        })
+        .collect()
+}
+
+/// Quote an SQL identifier.
+#[must_use]
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quote an SQL string literal; PostgreSQL casts it to the column type.
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// What a column name suggests about its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NameKind {
+    Email,
+    FirstName,
+    LastName,
+    FullName,
+    Username,
+    Phone,
+    City,
+    Country,
+    Url,
+    Status,
+    Text,
+    Other,
+}
+
+/// Classify a column by name.
+fn name_kind(name: &str) -> NameKind {
+    let name = name.to_lowercase();
+    let has = |needle: &str| name.contains(needle);
+    if has("email") {
+        NameKind::Email
+    } else if has("first_name") || has("firstname") || has("given_name") {
+        NameKind::FirstName
+    } else if has("last_name") || has("lastname") || has("surname") || has("family_name") {
+        NameKind::LastName
+    } else if has("username") || has("login") || has("handle") {
+        NameKind::Username
+    } else if name == "name" || has("full_name") || has("fullname") || has("customer_name") {
+        NameKind::FullName
+    } else if has("phone") || has("mobile") {
+        NameKind::Phone
+    } else if has("city") {
+        NameKind::City
+    } else if has("country") {
+        NameKind::Country
+    } else if has("url") || has("website") || has("homepage") {
+        NameKind::Url
+    } else if has("status") || has("state") {
+        NameKind::Status
+    } else if has("description") || has("comment") || has("note") || has("body") || has("bio") {
+        NameKind::Text
+    } else {
+        NameKind::Other
+    }
+}
+
+/// Whether a type holds free text.
+fn is_text(data_type: &str) -> bool {
+    matches!(data_type, "text" | "character varying" | "character")
+}
+
+/// Produce one value for a column.
+///
+/// With a `serial`, the column is unique and the value is made distinct
+/// with it.
+fn value_for(
+    column: &SeedColumn,
+    person: &Person,
+    serial: Option<u64>,
+    rng: &mut impl Rng,
+) -> Result<Option<String>> {
+    if let ValueSource::Choices(choices) = &column.source {
+        return Ok(choices.choose(rng).cloned());
+    }
+
+    let value = match (column.data_type.as_str(), serial) {
+        ("smallint", Some(serial)) => (serial % 32_000).to_string(),
+        ("integer" | "bigint", Some(serial)) => serial.to_string(),
+        ("smallint", None) => rng.gen_range(0..1_000).to_string(),
+        ("integer" | "bigint", None) => match column.name.to_lowercase().as_str() {
+            n if n.contains("age") => rng.gen_range(18..90).to_string(),
+            n if n.contains("year") => rng.gen_range(1990..2026).to_string(),
+            n if n.contains("quantity") || n.contains("count") => rng.gen_range(1..20).to_string(),
+            _ => rng.gen_range(1..100_000).to_string(),
+        },
+        ("numeric" | "real" | "double precision" | "money", _) => {
+            let (precision, scale) = column.numeric.unwrap_or((10, 2));
+            let max = 10f64.powi(i32::try_from((precision - scale).clamp(1, 4)).unwrap_or(4));
+            let value: f64 = rng.gen_range(0.0..max);
+            format!("{:.*}", usize::try_from(scale.clamp(0, 4)).unwrap_or(2), value)
+        }
+        ("boolean", _) => rng.gen_bool(0.5).to_string(),
+        ("uuid", _) => uuid::Uuid::new_v4().to_string(),
+        ("date", _) => (Utc::now() - TimeDelta::days(rng.gen_range(0..MAX_AGE_DAYS)))
+            .format("%Y-%m-%d")
+            .to_string(),
+        (t, _) if t.starts_with("timestamp") => {
+            (Utc::now() - TimeDelta::seconds(rng.gen_range(0..MAX_AGE_DAYS * 86_400)))
+                .format("%Y-%m-%d %H:%M:%S%:z")
+                .to_string()
+        }
+        (t, _) if t.starts_with("time") => format!(
+            "{:02}:{:02}:{:02}",
+            rng.gen_range(0..24),
+            rng.gen_range(0..60),
+            rng.gen_range(0..60)
+        ),
+        ("json" | "jsonb", _) => "{}".to_string(),
+        ("inet", _) => format!(
+            "10.{}.{}.{}",
+            rng.gen_range(0..256),
+            rng.gen_range(0..256),
+            rng.gen_range(1..255)
+        ),
+        (t, _) if is_text(t) => text_for(column, person, serial, rng),
+        (other, _) => {
+            if column.nullable {
+                return Ok(None);
+            }
+            bail!("Cannot generate values of type {} for column {}", other, column.name);
+        }
+    };
+    Ok(Some(value))
+}
+
+/// Produce the text of a column, cut to its maximum length.
+fn text_for(
+    column: &SeedColumn,
+    person: &Person,
+    serial: Option<u64>,
+    rng: &mut impl Rng,
+) -> String {
+    let Person { first, last, tag } = person;
+    let login = |separator: &str| {
+        format!("{}{}{}", first, separator, last)
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '.')
+            .collect::<String>()
+            .to_lowercase()
+    };
+    // A unique value ends with its serial, which cutting to length keeps
+    let suffix = serial.map(|serial| format!("-{}", serial)).unwrap_or_default();
+    let (text, suffix) = match name_kind(&column.name) {
+        NameKind::Email => {
+            let local = login(".");
+            let domain = match serial {
+                Some(serial) => format!("{}@example.com", serial),
+                None => format!("{}@example.com", tag),
+            };
+            (local, domain)
+        }
+        NameKind::FirstName => (first.clone(), suffix),
+        NameKind::LastName => (last.clone(), suffix),
+        NameKind::FullName => (format!("{} {}", first, last), suffix),
+        NameKind::Username => match serial {
+            Some(serial) => (login(""), serial.to_string()),
+            None => (login(""), tag.to_string()),
+        },
+        NameKind::Phone => (PhoneNumber().fake_with_rng(rng), suffix),
+        NameKind::City => (CityName().fake_with_rng(rng), suffix),
+        NameKind::Country => (CountryName().fake_with_rng(rng), suffix),
+        NameKind::Url => (
+            "https://example.com/".to_string(),
+            serial.unwrap_or(u64::from(*tag)).to_string(),
+        ),
+        NameKind::Status => ((*STATUSES.choose(rng).unwrap_or(&"active")).to_string(), suffix),
+        NameKind::Text => (Sentence(4..8).fake_with_rng(rng), suffix),
+        NameKind::Other => (
+            format!("{}_", column.name),
+            serial.unwrap_or(u64::from(*tag)).to_string(),
+        ),
+    };
+    let Some(max) = column.max_length.and_then(|m| usize::try_from(m).ok()) else {
+        return text + &suffix;
+    };
+    if serial.is_none() {
+        return (text + &suffix).chars().take(max).collect();
+    }
+    let kept = max.saturating_sub(suffix.chars().count());
+    text.chars().take(kept).chain(suffix.chars()).take(max).collect()
+}
+
+/// Generate test data for a table and insert it after confirmation.
+pub async fn seed_table(
+    cmd: &CommandContext,
+    profile_name: &str,
+    table: &str,
+    rows: usize,
+    safety_level: Option<&str>,
+    no_confirm: bool,
+    use_llm: bool,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let profile = get_profile(&config, profile_name)?;
+    let db = create_connection(&config, &profile).await?;
+    let executor = QueryExecutor::new(db.clone());
+
+    // Unqualified names resolve like describe_table: the profile's default
+    // schema, then the search_path
+    let (qualifier, table) = split_qualified_name(table);
+    let schema = match qualifier.or_else(|| db.default_schema().map(str::to_string)) {
+        Some(schema) => schema,
+        None => executor
+            .current_schema()
+            .await
+            .context("Failed to resolve the schema")?
+            .context("No schema on the search_path; qualify the table name")?,
+    };
+    let table = table.as_str();
+    let columns = executor
+        .describe_table(Some(&schema), table)
+        .await
+        .context("Failed to describe table")?;
+    if columns.is_empty() {
+        bail!("Table '{}.{}' not found", schema, table);
+    }
+    let skip = executor.generated_columns(&schema, table).await?;
+    let unique = executor.unique_columns(&schema, table).await?;
+    let mut plan = plan_columns(&columns, &skip, &unique);
+    if plan.is_empty() {
+        bail!("Every column of '{}' is filled by the database", table);
+    }
+    let qualified = format!("{}.{}", quote_ident(&schema), quote_ident(table));
+
+    // Foreign keys draw from existing parent rows
+    for fk in executor.foreign_keys(&schema, table).await? {
+        let Some(column) = plan.iter_mut().find(|c| c.name == fk.column) else {
+            continue;
+        };
+        let key = quote_ident(&fk.referenced_column);
+        // A unique reference needs a parent per row, not yet referenced
+        let (unused, limit) = if column.unique {
+            let unused = format!(
+                " WHERE NOT EXISTS (SELECT 1 FROM {} AS child WHERE child.{} = parent.{})",
+                qualified,
+                quote_ident(&fk.column),
+                key
+            );
+            (unused, rows.max(100))
+        } else {
+            (String::new(), 100)
+        };
+        let sql = format!(
+            "SELECT DISTINCT to_jsonb(parent.{}::text) AS value FROM {}.{} AS parent{} LIMIT {}",
+            key,
+            quote_ident(&fk.referenced_schema),
+            quote_ident(&fk.referenced_table),
+            unused,
+            limit
+        );
+        let values: Vec<String> = executor
+            .execute_query(&sql)
+            .await?
+            .rows
+            .iter()
+            .filter_map(|row| row.get("value")?.as_str().map(str::to_string))
+            .collect();
+        if values.is_empty() && !column.nullable {
+            bail!(
+                "'{}' references {}.{}, which is empty; seed it first",
+                fk.column,
+                fk.referenced_schema,
+                fk.referenced_table
+            );
+        }
+        column.source = ValueSource::Choices(values);
+    }
+
+    let hinted: Vec<(String, String)> = plan
+        .iter()
+        .filter(|c| c.wants_hint())
+        .map(|c| (c.name.clone(), c.data_type.clone()))
+        .collect();
+    if use_llm && !hinted.is_empty() {
+        let hints = match create_llm_client(&config) {
+            Ok(llm) => llm
+                .complete(&seed_hints_prompt(table, &hinted))
+                .await
+                .map(|reply| parse_hints(&reply))
+                .unwrap_or_else(|e| {
+                    warn!("No example values from the LLM: {}", e);
+                    HashMap::new()
+                }),
+            Err(e) => {
+                warn!("No example values from the LLM: {}", e);
+                HashMap::new()
+            }
+        };
+        for column in &mut plan {
+            if let Some(values) = hints.get(&column.name).filter(|v| !v.is_empty()) {
+                column.source = ValueSource::Choices(values.clone());
+            }
+        }
+    }
+
+    // --deterministic also makes the generated data reproducible
+    let mut rng = match config.llm.seed {
+        Some(seed) if config.llm.deterministic => StdRng::seed_from_u64(seed),
+        _ => StdRng::from_entropy(),
+    };
+    // Under an idempotency key, a seed that already ran is not repeated
+    let idempotency = cmd
+        .overrides
+        .idempotency_key
+        .clone()
+        .map(|key| (key, IdempotencyLedger::open(config.paths.idempotency_ledger())));
+    let seed_args = serde_json::json!({ "table": qualified, "rows": rows });
+    if let Some((key, ledger)) = &idempotency
+        && let Some(result) = ledger.get(key, "seed", &seed_args)?
+    {
+        println!("Already seeded under idempotency key {}: {}", key, result);
+        return Ok(());
+    }
+
+    let generated = generate_rows(&plan, &unique, rows, &mut rng)?;
+    let statements = insert_statements(&qualified, &plan, &generated);
+    let Some(first) = statements.first() else {
+        println!("Nothing to insert.");
+        return Ok(());
+    };
+
+    let level = match safety_level {
+        Some(s) => parse_safety_level(s),
+        None => map_safety_level(config.safety.safety_level),
+    };
+    let validator = safety_validator(&config)?;
+    let mut ctx = SafetyContext::with_level(validator_level(level))
+        .with_estimated_rows(i64::try_from(rows).unwrap_or(i64::MAX));
+    if validator.policy().needs_role() {
+        ctx = ctx.with_role(executor.current_role().await.context("Failed to look up role")?);
+    }
+    let validation = validator.validate(first, &ctx);
+    if !validation.is_allowed {
+        bail!(
+            "{}",
+            validation.error.unwrap_or_else(|| "Insert not allowed".to_string())
+        );
+    }
+
+    let preview: String = first.lines().take(4).collect::<Vec<_>>().join("\n");
+    println!("{}\n    ...", preview);
+    let prompt = format!("Insert {} rows into {}?", rows, qualified);
+    let confirmation_ttl = Duration::from_secs(config.safety.confirmation_ttl_secs);
+    if validation.requires_confirmation
+        && !no_confirm
+        && let Some(mode) = sandbox_mode(&config)
+    {
+        let tables = [format!("{}.{}", schema, table)];
+        match Sandbox::new(db.clone(), mode).simulate(&statements, &tables).await {
+            Ok(report) => println!("{}", report),
+            Err(e) => println!("Sandbox run unavailable: {}", e),
+        }
+    }
+    if validation.requires_confirmation
+        && !no_confirm
+        && !confirm_action(&prompt, ConfirmationLevel::Simple, confirmation_ttl, None)
+    {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let snapshot = match &config.safety.snapshots {
+        Some(snapshots) if validation.requires_confirmation => {
+            let tables = [format!("{}.{}", schema, table)];
+            let dir = config.paths.snapshots_dir();
+            match Snapshot::take(&db, &tables, &dir, snapshots.max_rows).await {
+                Ok(snapshot) => Some(snapshot),
+                Err(e) => {
+                    warn!("No snapshot taken, cannot undo: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let inserted = match executor.execute_in_transaction(&statements).await {
+        Ok(inserted) => inserted,
+        Err(e) => {
+            if let Some(snapshot) = snapshot
+                && let Err(e) = snapshot.discard()
+            {
+                warn!("Failed to remove snapshot {}: {}", snapshot.dir.display(), e);
+            }
+            return Err(e).context("Failed to insert rows");
+        }
+    };
+    println!("Inserted {} rows into {}.", inserted, qualified);
+    if let Some((key, ledger)) = &idempotency {
+        let result = serde_json::json!({ "inserted": inserted });
+        if let Err(e) = ledger.record(key, "seed", &seed_args, &result) {
+            warn!("Could not record the seed for idempotency key {}: {}", key, e);
+        }
+    }
+    if let Some(snapshot) = snapshot {
+        let result = QueryResult {
+            row_count: usize::try_from(inserted).unwrap_or(usize::MAX),
+            ..QueryResult::default()
+        };
+        let id = open_query_history(&config)?.record_undo(
+            &profile.name,
+            &statements.join(";\n"),
+            &result,
+            snapshot,
+        )?;
+        println!("Undo: pg-agent undo {}", id);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn column(name: &str, data_type: &str) -> ColumnInfo {
+        ColumnInfo {
+            column_name: name.to_string(),
+            data_type: data_type.to_string(),
+            is_nullable: false,
+            ..ColumnInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_generates_typed_rows() {
+        let mut columns = vec![
+            column("id", "integer"),
+            column("email", "character varying"),
+            column("signed_up", "date"),
+        ];
+        columns[0].column_default = Some("nextval('users_id_seq')".to_string());
+        columns[1].character_maximum_length = Some(12);
+
+        let plan = plan_columns(&columns, &[], &[]);
+        assert_eq!(plan.len(), 2);
+
+        let rows = generate_rows(&plan, &[], 3, &mut StdRng::seed_from_u64(7)).unwrap();
+        assert_eq!(rows.len(), 3);
+        let email = rows[0][0].as_deref().unwrap();
+        assert!(email.chars().count() <= 12 && email.contains('.'));
+        assert_eq!(rows[0][1].as_deref().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_insert_statements_quote_values() {
+        let plan = plan_columns(&[column("note", "text"), column("ref", "text")], &[], &[]);
+        let rows = vec![vec![Some("it's".to_string()), None]];
+
+        assert_eq!(
+            insert_statements("\"public\".\"t\"", &plan, &rows),
+            vec!["INSERT INTO \"public\".\"t\" (\"note\", \"ref\") VALUES\n    ('it''s', NULL)"]
+        );
+    }
+
+    #[test]
+    fn test_parse_hints() {
+        let hints = parse_hints("```json\n{\"sku\": [\"A-1\", 2]}\n```");
+        assert_eq!(hints["sku"], vec!["A-1", "2"]);
+        assert!(parse_hints("no json here").is_empty());
+    }
+
+    #[test]
+    fn test_wants_hint() {
+        let plan = plan_columns(&[column("sku", "text"), column("email", "text")], &[], &[]);
+        assert!(plan[0].wants_hint());
+        assert!(!plan[1].wants_hint());
+    }
+
+    #[test]
+    fn test_enum_columns_use_labels() {
+        let labels = vec!["pending".to_string(), "shipped".to_string()];
+        let mut status = column("status", "order_status");
+        status.enum_labels = labels.clone();
+        let plan = plan_columns(&[status], &[], &[]);
+        assert_eq!(plan[0].source, ValueSource::Choices(labels.clone()));
+
+        let rows = generate_rows(&plan, &[], 5, &mut StdRng::seed_from_u64(3)).unwrap();
+        assert!(rows.iter().all(|r| labels.contains(r[0].as_ref().unwrap())));
+    }
+
+    #[test]
+    fn test_unique_values_are_distinct() {
+        let mut columns = vec![
+            column("email", "character varying"),
+            column("country", "text"),
+            column("rank", "smallint"),
+            column("nickname", "text"),
+        ];
+        columns[0].character_maximum_length = Some(40);
+        let sets = [&["email"][..], &["country", "rank"], &["id"], &["nickname"]];
+        let unique: Vec<Vec<String>> = sets
+            .iter()
+            .map(|set| set.iter().map(|c| c.to_string()).collect())
+            .collect();
+        let plan = plan_columns(&columns, &[], &unique);
+        assert!(plan[0].unique && !plan[1].unique && !plan[2].unique);
+        assert!(!plan[3].wants_hint());
+
+        let rows = generate_rows(&plan, &unique, 500, &mut StdRng::seed_from_u64(11)).unwrap();
+        let distinct = |columns: &[usize]| {
+            rows.iter()
+                .map(|row| columns.iter().map(|&i| row[i].clone()).collect::<Vec<_>>())
+                .collect::<HashSet<_>>()
+                .len()
+        };
+        assert_eq!(distinct(&[0]), 500);
+        assert_eq!(distinct(&[1, 2]), 500);
+        assert_eq!(distinct(&[3]), 500);
+        assert!(rows.iter().all(|row| row[0].as_ref().unwrap().ends_with("@example.com")));
+    }
+
+    #[test]
+    fn test_unique_choices_run_out() {
+        let keys: Vec<String> = ["1", "2", "3", "3"].iter().map(|k| k.to_string()).collect();
+        let unique = vec![vec!["user_id".to_string()]];
+        let mut plan = plan_columns(&[column("user_id", "integer")], &[], &unique);
+        plan[0].source = ValueSource::Choices(keys);
+
+        // Each parent key is used once, as a one-to-one relation needs
+        let rows = generate_rows(&plan, &unique, 3, &mut StdRng::seed_from_u64(5)).unwrap();
+        let used: HashSet<_> = rows.iter().map(|row| row[0].clone().unwrap()).collect();
+        assert_eq!(used.len(), 3);
+        let err = generate_rows(&plan, &unique, 4, &mut StdRng::seed_from_u64(5)).unwrap_err();
+        assert!(err.to_string().contains("only 3 values"), "{}", err);
+
+        // A unique pair of columns with few values each runs out too
+        let pair = vec![vec!["a".to_string(), "b".to_string()]];
+        let mut plan = plan_columns(&[column("a", "text"), column("b", "text")], &[], &pair);
+        for column in &mut plan {
+            column.source = ValueSource::Choices(vec!["x".to_string(), "y".to_string()]);
+        }
+        assert!(generate_rows(&plan, &pair, 4, &mut StdRng::seed_from_u64(5)).is_ok());
+        let err = generate_rows(&plan, &pair, 5, &mut StdRng::seed_from_u64(5)).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot generate more than 4 rows with distinct (a, b)");
+    }
+}
@@ -0,0 +1,102 @@
+//! Saved sessions: `pg-agent sessions`.
+
+use anyhow::{Context, Result};
+use postgres_agent_core::ReportFormat;
+use postgres_agent_core::session::render_report;
+use postgres_agent_llm::client::LlmClient;
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::commands::{create_llm_client, number_format, open_sessions, CommandContext};
+
+/// List saved interactive sessions.
+pub async fn list_sessions(cmd: &CommandContext) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let store = open_sessions(&config)?;
+    let ids = store.ids()?;
+
+    if ids.is_empty() {
+        println!("No saved sessions in {}", store.dir().display());
+        return Ok(());
+    }
+
+    println!("{:<24} {:<16} {:>9}  FIRST QUESTION", "ID", "PROFILE", "QUESTIONS");
+    for id in ids {
+        match store.load(&id) {
+            Ok(session) => {
+                let first = session.turns.first().map_or("", |t| t.question.as_str());
+                println!(
+                    "{:<24} {:<16} {:>9}  {}",
+                    session.id,
+                    session.profile,
+                    session.turns.len(),
+                    first.chars().take(60).collect::<String>()
+                );
+            }
+            Err(e) => warn!("Skipping session {}: {}", id, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Show a saved session, turn by turn or as a summary written by the LLM.
+pub async fn show_session(cmd: &CommandContext, id: &str, summary: bool) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let session = open_sessions(&config)?.load(id)?;
+
+    println!(
+        "Session {} ({}, {}, started {})",
+        session.id,
+        session.profile,
+        session.model,
+        session.started_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if summary {
+        let llm_client = create_llm_client(&config)?;
+        let digest = llm_client
+            .summarize(&session.prompt_messages())
+            .await
+            .context("Failed to summarize session")?;
+        println!("\n{}", digest);
+        return Ok(());
+    }
+
+    for (i, turn) in session.turns.iter().enumerate() {
+        println!("\n[{}] {}", i + 1, turn.question);
+        if let Some(sql) = &turn.sql {
+            println!("    {}", sql);
+        }
+        match (&turn.answer, &turn.error) {
+            (Some(answer), _) => println!("{}", answer),
+            (None, Some(error)) => println!("Failed: {}", error),
+            (None, None) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Export a saved session as a Markdown or HTML report.
+pub async fn export_session(
+    cmd: &CommandContext,
+    id: &str,
+    format: &str,
+    output: Option<&str>,
+    max_rows: usize,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let format = ReportFormat::from_str(format).map_err(anyhow::Error::msg)?;
+    let session = open_sessions(&config)?.load(id)?;
+    let report = render_report(&session, format, max_rows, number_format(&config));
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, report)
+                .with_context(|| format!("Failed to write report: {}", path))?;
+            println!("Exported session {} to {}", session.id, path);
+        }
+        None => print!("{}", report),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+//! Graceful shutdown on SIGINT/SIGTERM.
+//!
+//! Long-running commands race their work against [`signal`]. When a signal
+//! wins, the in-flight future (LLM request or database query) is dropped,
+//! which cancels it, and cleanup runs under a deadline via [`drain`].
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Wait for a shutdown signal: Ctrl-C everywhere, plus SIGTERM on Unix.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(e) => {
+                warn!("Cannot listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+
+    info!("Shutdown signal received");
+}
+
+/// Error of a command stopped by a shutdown signal.
+#[derive(Debug)]
+pub struct Interrupted {
+    /// What was stopped.
+    what: String,
+}
+
+impl Interrupted {
+    /// Error for stopping `what`.
+    pub fn new(what: impl Into<String>) -> Self {
+        Self { what: what.into() }
+    }
+}
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrupted: {}", self.what)
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Run cleanup, giving up once the deadline passes.
+pub async fn drain(deadline: Duration, cleanup: impl Future<Output = ()>) {
+    if tokio::time::timeout(deadline, cleanup).await.is_err() {
+        warn!(
+            "Shutdown did not finish within {}s; exiting without full cleanup",
+            deadline.as_secs()
+        );
+    }
+}
+
+/// Reads lines from stdin on one background thread without blocking the
+/// runtime.
+///
+/// A line is only read when asked for, so that confirmation prompts reading
+/// stdin directly are not robbed of their input. Reading is cancel-safe: a
+/// line that arrives after its caller gave up is returned by the next call.
+/// The thread is detached so that a pending read (after a shutdown signal)
+/// does not keep the process alive.
+pub struct LineReader {
+    /// Asks the thread for the next line.
+    requests: std::sync::mpsc::Sender<()>,
+    /// Lines read, or the error that stopped a read.
+    lines: mpsc::UnboundedReceiver<std::io::Result<String>>,
+    /// Whether a line was asked for and not yet received.
+    pending: bool,
+}
+
+impl LineReader {
+    /// Read lines from stdin.
+    pub fn stdin() -> Self {
+        Self::new(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        })
+    }
+
+    /// Read lines with `read` on a new thread.
+    pub fn new(mut read: impl FnMut() -> std::io::Result<String> + Send + 'static) -> Self {
+        let (requests, asked) = std::sync::mpsc::channel::<()>();
+        let (tx, lines) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            while asked.recv().is_ok() {
+                if tx.send(read()).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            lines,
+            pending: false,
+        }
+    }
+
+    /// Read the next line; empty at end of input.
+    ///
+    /// # Errors
+    /// Returns an error if the read fails or the reader thread has stopped.
+    pub async fn read_line(&mut self) -> std::io::Result<String> {
+        let stopped = || std::io::Error::other("stdin reader stopped");
+        if !self.pending {
+            self.requests.send(()).map_err(|_| stopped())?;
+            self.pending = true;
+        }
+        let line = self.lines.recv().await.ok_or_else(stopped)?;
+        self.pending = false;
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, Write};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_drain_deadline() {
+        // Cleanup that outlives the deadline is abandoned
+        let started = Instant::now();
+        drain(Duration::from_millis(50), tokio::time::sleep(Duration::from_secs(30))).await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Cleanup within the deadline runs to completion
+        let done = AtomicBool::new(false);
+        drain(Duration::from_secs(5), async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            done.store(true, Ordering::SeqCst);
+        })
+        .await;
+        assert!(done.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_line_reader() {
+        let (input, mut output) = std::io::pipe().unwrap();
+        let mut input = std::io::BufReader::new(input);
+        let mut reader = LineReader::new(move || {
+            let mut line = String::new();
+            input.read_line(&mut line).map(|_| line)
+        });
+
+        // Nothing typed yet: the read is abandoned, as on a shutdown signal
+        let abandoned = tokio::time::timeout(Duration::from_millis(50), reader.read_line()).await;
+        assert!(abandoned.is_err());
+
+        // The line typed next goes to the next read, in order
+        output.write_all(b"first\nsecond\n").unwrap();
+        assert_eq!(reader.read_line().await.unwrap(), "first\n");
+        assert_eq!(reader.read_line().await.unwrap(), "second\n");
+
+        drop(output);
+        assert_eq!(reader.read_line().await.unwrap(), "");
+    }
+}
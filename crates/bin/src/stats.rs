@@ -0,0 +1,188 @@
+//! Usage reports and feedback: `pg-agent stats`, `pg-agent audit top` and
+//! `pg-agent feedback`.
+
+use anyhow::{bail, Context, Result};
+use postgres_agent_core::stats::parse_window;
+use postgres_agent_core::{Feedback, Rating, StatsStore};
+use postgres_agent_safety::{read_audit_log, ActivityByShape, AuditEvent};
+use std::str::FromStr;
+
+use crate::commands::{open_query_history, open_stats_store, CommandContext};
+
+/// Show today's LLM usage.
+pub async fn show_usage(cmd: &CommandContext, since: Option<&str>) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let store = open_stats_store(&config);
+    let today = store.today();
+
+    println!("Today (UTC):");
+    println!("  Queries: {}", today.queries);
+    println!("  Tokens:  {}", today.tokens);
+    println!("  Cost:    ${:.4}", today.cost);
+    if let Some(limit) = config.llm.max_cost_per_day {
+        println!("  Budget:  ${:.2}", limit);
+    }
+
+    let cutoff = since
+        .map(|window| {
+            parse_window(window)
+                .map(|window| chrono::Utc::now() - window)
+                .with_context(|| format!("Invalid --since '{}' (expected e.g. 24h, 7d, 2w)", window))
+        })
+        .transpose()?;
+    let summaries = store.summarize_runs(cutoff);
+
+    println!();
+    match since {
+        Some(window) => println!("Runs in the last {}:", window),
+        None => println!("All recorded runs:"),
+    }
+    if summaries.is_empty() {
+        println!("  No runs recorded.");
+        return Ok(());
+    }
+
+    println!(
+        "  {:<16} {:<24} {:>6} {:>8} {:>10} {:>10} {:>9} {:>6}",
+        "PROFILE", "MODEL", "RUNS", "SUCCESS", "TOKENS", "COST", "AVG TIME", "TOOLS"
+    );
+    for ((profile, model), summary) in &summaries {
+        println!(
+            "  {:<16} {:<24} {:>6} {:>7.1}% {:>10} {:>10} {:>7}ms {:>6}",
+            profile,
+            model,
+            summary.runs,
+            summary.success_rate() * 100.0,
+            summary.tokens,
+            format!("${:.4}", summary.cost),
+            summary.avg_duration_ms(),
+            summary.tool_calls,
+        );
+    }
+
+    Ok(())
+}
+
+/// Show the query shapes run most often, counting audited queries and
+/// query history entries by fingerprint.
+pub async fn show_audit_top(cmd: &CommandContext, since: Option<&str>, limit: usize) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let cutoff = since
+        .map(|window| {
+            parse_window(window)
+                .map(|window| chrono::Utc::now() - window)
+                .with_context(|| format!("Invalid --since '{}' (expected e.g. 24h, 7d, 2w)", window))
+        })
+        .transpose()?;
+    let in_window = |at: &chrono::DateTime<chrono::Utc>| cutoff.is_none_or(|cutoff| *at >= cutoff);
+
+    let mut activity = ActivityByShape::new();
+    // Agent queries are audited; `pg-agent exec` runs are in the history
+    for record in read_audit_log(&config.paths.audit_log()) {
+        if let Some(AuditEvent::Query {
+            timestamp,
+            query,
+            success,
+            duration_ms,
+            ..
+        }) = record.event()
+            && in_window(&timestamp)
+        {
+            activity.add(&query, timestamp, success, Some(duration_ms));
+        }
+    }
+    for entry in open_query_history(&config)?.entries()? {
+        if in_window(&entry.timestamp) {
+            activity.add(&entry.sql, entry.timestamp, true, entry.result.execution_time_ms);
+        }
+    }
+
+    match since {
+        Some(window) => println!("Query shapes run in the last {}:", window),
+        None => println!("Query shapes run:"),
+    }
+    if activity.is_empty() {
+        println!("  No queries recorded.");
+        return Ok(());
+    }
+    println!(
+        "  {:<16} {:>6} {:>7} {:>9} {:<16}  QUERY",
+        "FINGERPRINT", "RUNS", "FAILED", "AVG TIME", "LAST RUN"
+    );
+    for shape in activity.top(limit) {
+        let average = shape.average_ms().map_or_else(|| "-".to_string(), |ms| format!("{}ms", ms));
+        println!(
+            "  {:<16} {:>6} {:>7} {:>9} {:<16}  {}",
+            shape.fingerprint,
+            shape.runs,
+            shape.failures,
+            average,
+            shape.last_run.format("%Y-%m-%d %H:%M"),
+            shape.shape.chars().take(60).collect::<String>(),
+        );
+    }
+    if activity.len() > limit {
+        println!("  ...and {} more shapes", activity.len() - limit);
+    }
+
+    Ok(())
+}
+
+/// Show per-tool call statistics.
+pub async fn show_tool_stats(cmd: &CommandContext) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let store = open_stats_store(&config);
+    let tools = store.tool_usage();
+
+    if tools.is_empty() {
+        println!("No tool calls recorded yet.");
+        return Ok(());
+    }
+
+    let format_ms = |ms: Option<u64>| ms.map_or_else(|| "-".to_string(), |ms| format!("{}ms", ms));
+    println!(
+        "{:<28} {:>7} {:>7} {:>9} {:>9} {:>9}",
+        "TOOL", "CALLS", "ERRORS", "P50", "P95", "P99"
+    );
+    for (name, usage) in tools {
+        println!(
+            "{:<28} {:>7} {:>6.1}% {:>9} {:>9} {:>9}",
+            name,
+            usage.calls,
+            usage.error_rate() * 100.0,
+            format_ms(usage.latency_percentile(50.0)),
+            format_ms(usage.latency_percentile(95.0)),
+            format_ms(usage.latency_percentile(99.0)),
+        );
+    }
+
+    Ok(())
+}
+
+/// Rate the last agent run (`feedback 👍|👎 [--comment ...]`).
+pub async fn record_feedback(
+    cmd: &CommandContext,
+    rating: &str,
+    comment: Option<&str>,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let mut store = open_stats_store(&config);
+    println!("{}", give_feedback(&mut store, rating, comment)?);
+    Ok(())
+}
+
+/// Tag the last run in the stats store with a rating.
+pub(crate) fn give_feedback(
+    store: &mut StatsStore,
+    rating: &str,
+    comment: Option<&str>,
+) -> Result<String> {
+    let rating = Rating::from_str(rating).map_err(anyhow::Error::msg)?;
+    let feedback = Feedback::new(rating, comment.map(str::to_string));
+    let Some(run) = store.tag_last_run(feedback) else {
+        bail!("No runs recorded yet");
+    };
+    let message = format!("{} recorded for: {}", rating.emoji(), run.question);
+    store.save()?;
+    Ok(message)
+}
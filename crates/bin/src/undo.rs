@@ -0,0 +1,67 @@
+//! Undo of confirmed mutations from their snapshots: `pg-agent undo`.
+
+use anyhow::{bail, Context, Result};
+use postgres_agent_safety::ConfirmationLevel;
+use std::time::Duration;
+
+use crate::commands::{
+    confirm_action, create_connection, get_profile, open_audit_log, open_query_history,
+    CommandContext,
+};
+
+/// Restore the tables a mutation changed from the snapshot taken before it,
+/// or print a psql script that does.
+pub async fn undo_mutation(
+    cmd: &CommandContext,
+    id: u64,
+    script: bool,
+    no_confirm: bool,
+) -> Result<()> {
+    let config = cmd.load_config().await?;
+    let history = open_query_history(&config)?;
+    let entry = history
+        .get(id)?
+        .with_context(|| format!("No query #{} in {}", id, history.path().display()))?;
+    let Some(snapshot) = entry.undo else {
+        bail!("Query #{} has no snapshot to undo", id);
+    };
+    if script {
+        print!("{}", snapshot.script());
+        return Ok(());
+    }
+
+    println!("Query #{} on {} at {}:", id, entry.profile, entry.timestamp.to_rfc3339());
+    println!("{}", entry.sql.lines().take(4).collect::<Vec<_>>().join("\n"));
+    println!("Snapshot taken {}:", snapshot.taken_at.to_rfc3339());
+    for table in &snapshot.tables {
+        println!("  {}: {} rows", table.table, table.rows);
+    }
+    let prompt = format!(
+        "Replace every row of {} table(s), including changes made since, with the snapshot?",
+        snapshot.tables.len()
+    );
+    let ttl = Duration::from_secs(config.safety.confirmation_ttl_secs);
+    if !no_confirm && !confirm_action(&prompt, ConfirmationLevel::Simple, ttl, None) {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let profile = get_profile(&config, &entry.profile)?;
+    let db = create_connection(&config, &profile).await?;
+    let start = std::time::Instant::now();
+    let restored = snapshot.restore(&db).await;
+    let db_config = db.config();
+    open_audit_log(&config).log_query(
+        db_config.username.as_deref().unwrap_or("unknown"),
+        db_config.database.as_deref().unwrap_or("unknown"),
+        &snapshot.script(),
+        restored.is_ok(),
+        u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        restored.as_ref().ok().and_then(|rows| i64::try_from(*rows).ok()),
+        None,
+    );
+    db.close().await;
+    let restored = restored.context("Failed to restore the snapshot")?;
+    println!("Restored {} rows into {} table(s).", restored, snapshot.tables.len());
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! Version checks and self-update.
+//!
+//! Releases are published on GitHub with one binary per target and a
+//! matching minisign signature, e.g. `pg-agent-x86_64-linux` and
+//! `pg-agent-x86_64-linux.minisig`.
+//!
+//! The signature is checked against [`RELEASE_PUBLIC_KEY`], which is built
+//! into the binary, so a tampered release or compromised GitHub account
+//! cannot push an update without the signing key. A binary that fails
+//! verification is never installed.
+
+use anyhow::{bail, Context, Result};
+use minisign_verify::{PublicKey, Signature};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// GitHub repository publishing releases.
+const REPO: &str = "forfd8960/postgres-agent";
+
+/// Minisign public key that release binaries are signed with.
+const RELEASE_PUBLIC_KEY: &str = "RWQ/ZcdoA5yIauvUqoJEjB8n8qW1XDUPVzQbW//IlIehkpoNHo771J0S";
+
+/// Version of the running binary.
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Minimum interval between startup version checks.
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A published release.
+#[derive(Debug, Deserialize)]
+struct Release {
+    /// Release tag, e.g. `v0.2.0`.
+    tag_name: String,
+    /// Downloadable files.
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+/// A file attached to a release.
+#[derive(Debug, Deserialize)]
+struct Asset {
+    /// File name.
+    name: String,
+    /// Download URL.
+    browser_download_url: String,
+}
+
+impl Release {
+    /// Version without the leading `v`.
+    fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    /// Find an asset by name.
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// Download the latest release for this platform and replace the running binary.
+pub async fn self_update(check_only: bool) -> Result<()> {
+    let release = fetch_latest_release().await?;
+
+    if !is_newer(release.version(), CURRENT_VERSION) {
+        println!("pg-agent v{} is up to date.", CURRENT_VERSION);
+        return Ok(());
+    }
+
+    println!("New version available: v{} (current: v{})", release.version(), CURRENT_VERSION);
+    if check_only {
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let binary = release
+        .asset(&name)
+        .with_context(|| format!("Release has no binary for this platform ({})", name))?;
+    let signature = release
+        .asset(&format!("{}.minisig", name))
+        .with_context(|| format!("Release has no signature for {}", name))?;
+
+    let client = http_client()?;
+    println!("Downloading {}...", binary.name);
+    let bytes = client
+        .get(&binary.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let signature = client
+        .get(&signature.browser_download_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    verify_signature(&bytes, &signature, &name, RELEASE_PUBLIC_KEY)
+        .context("Refusing to install the update")?;
+
+    let current = std::env::current_exe().context("Cannot locate the running binary")?;
+    replace_binary(&current, &bytes)?;
+    println!("Updated to v{}.", release.version());
+
+    Ok(())
+}
+
+/// Print a notice if a newer release exists, at most once per day.
+///
+/// Failures are ignored: the notice must never get in the way of a command.
+pub async fn notify_if_outdated() {
+    let stamp = postgres_agent_config::paths::cache_dir().join("last-update-check");
+    let checked_recently = std::fs::metadata(&stamp)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age < CHECK_INTERVAL);
+    if checked_recently {
+        return;
+    }
+
+    let release = tokio::time::timeout(Duration::from_secs(2), fetch_latest_release()).await;
+    if let Some(parent) = stamp.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    let _ = std::fs::write(&stamp, CURRENT_VERSION);
+
+    if let Ok(Ok(release)) = release
+        && is_newer(release.version(), CURRENT_VERSION)
+    {
+        eprintln!(
+            "A new version of pg-agent is available: v{} (run 'pg-agent self-update')",
+            release.version()
+        );
+    }
+}
+
+/// Build an HTTP client for the GitHub API.
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(format!("pg-agent/{}", CURRENT_VERSION))
+        .build()
+        .context("Failed to create HTTP client")
+}
+
+/// Fetch the latest release metadata.
+async fn fetch_latest_release() -> Result<Release> {
+    let url = format!("https://api.github.com/repos/{}/releases/latest", REPO);
+    http_client()?
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Invalid release metadata")
+}
+
+/// Release asset name for the current platform.
+fn asset_name() -> String {
+    let name = format!("pg-agent-{}-{}", std::env::consts::ARCH, std::env::consts::OS);
+    if cfg!(windows) { format!("{}.exe", name) } else { name }
+}
+
+/// Compare dotted numeric versions.
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse = |v: &str| -> Vec<u64> {
+        v.split(['.', '-'])
+            .map_while(|part| part.parse().ok())
+            .collect()
+    };
+    parse(candidate) > parse(current)
+}
+
+/// Check that `data` carries a valid minisign signature by `public_key`
+/// for the file `name`.
+///
+/// The file name is read from the signed trusted comment, so a signature
+/// for another platform's binary is rejected too.
+fn verify_signature(data: &[u8], signature: &str, name: &str, public_key: &str) -> Result<()> {
+    let public_key = PublicKey::from_base64(public_key).context("Invalid release public key")?;
+    let signature = Signature::decode(signature).context("Invalid release signature")?;
+    public_key
+        .verify(data, &signature, false)
+        .context("Release signature verification failed")?;
+
+    let signed_name = signature
+        .trusted_comment()
+        .split('\t')
+        .find_map(|field| field.strip_prefix("file:"));
+    if signed_name != Some(name) {
+        bail!(
+            "Release signature is for {}, not {}",
+            signed_name.unwrap_or("an unnamed file"),
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Atomically replace the binary at `path` with `bytes`.
+fn replace_binary(path: &Path, bytes: &[u8]) -> Result<()> {
+    let staged = path.with_extension("new");
+    std::fs::write(&staged, bytes)
+        .with_context(|| format!("Failed to write {}", staged.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    // A running executable cannot be overwritten on Windows, but it can be renamed
+    #[cfg(windows)]
+    {
+        let old = path.with_extension("old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(path, &old)?;
+    }
+
+    std::fs::rename(&staged, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.0"));
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(is_newer("1.0.0", "0.99.99"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.1.0-rc1", "0.1.0"));
+    }
+
+    /// Key and signature of `pg-agent` as `pg-agent-x86_64-linux`, made
+    /// with a throwaway key.
+    const TEST_PUBLIC_KEY: &str = "RWTrGFWKCkLE/thpURtg7kmFQ4bUMUzYNH5MH/tCJCGxX4Y3CIWUN/Hy";
+    const TEST_SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUTrGFWKCkLE/lh2fI5oHOLoDqMT/vIeZOjMUkYMkbd7VRA5BsGw4B3+9cYlkjBugS11W+OnwxmziX+Klr5VMYsuWHchd3e78gk=
+trusted comment: timestamp:1760000000\tfile:pg-agent-x86_64-linux
+vnqLDNfppeQxsIyzixOfMJGhEJ6sosPUCvQLux98csJUXQdwql7MjhwRBjEN/YrRrJzEr43SYRioXaXNlYd3BA==
+";
+
+    #[test]
+    fn test_verify_signature() {
+        let name = "pg-agent-x86_64-linux";
+        assert!(verify_signature(b"pg-agent", TEST_SIGNATURE, name, TEST_PUBLIC_KEY).is_ok());
+
+        // Tampered data, another key, another file name and garbage are all refused
+        assert!(verify_signature(b"tampered", TEST_SIGNATURE, name, TEST_PUBLIC_KEY).is_err());
+        assert!(verify_signature(b"pg-agent", TEST_SIGNATURE, name, RELEASE_PUBLIC_KEY).is_err());
+        let other = "pg-agent-aarch64-macos";
+        assert!(verify_signature(b"pg-agent", TEST_SIGNATURE, other, TEST_PUBLIC_KEY).is_err());
+        assert!(verify_signature(b"pg-agent", "", name, TEST_PUBLIC_KEY).is_err());
+    }
+}
@@ -2,7 +2,11 @@
 //!
 //! This module provides clap-based argument parsing for the PostgreSQL Agent CLI.
 
-use clap::{Parser, Subcommand};
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::path::Path;
+
+use crate::commands::find_config_files;
 
 /// PostgreSQL AI Agent - Query databases using natural language
 #[derive(Parser, Debug)]
@@ -19,6 +23,14 @@ pub struct CliArgs {
     #[arg(short, long, default_value = "info")]
     pub log_level: String,
 
+    /// Log format (text, json)
+    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    pub log_format: String,
+
+    /// Write logs to this file instead of stderr
+    #[arg(long)]
+    pub log_file: Option<String>,
+
     /// Database profile to use
     #[arg(short, long, default_value = "default")]
     pub profile: String,
@@ -27,6 +39,10 @@ pub struct CliArgs {
     #[arg(short, long)]
     pub safety_level: Option<String>,
 
+    /// Persona bundling prompt style, tools and safety level
+    #[arg(long, env = "PG_AGENT_PERSONA", value_parser = ["analyst", "dba", "developer"])]
+    pub persona: Option<String>,
+
     /// Disable confirmation prompts
     #[arg(long, default_value = "false")]
     pub no_confirm: bool,
@@ -39,10 +55,73 @@ pub struct CliArgs {
     #[arg(short, long, default_value = "false")]
     pub quiet: bool,
 
+    /// Print the model's reasoning and tool calls as the agent runs
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    /// Show how long each iteration spent in the LLM and in tools
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Also show each query result the agent used, labeled with its SQL
+    #[arg(long)]
+    pub show_intermediate: bool,
+
+    /// Zone timestamptz values are shown in: utc, session or an IANA name
+    #[arg(long, env = "PG_AGENT_TZ")]
+    pub tz: Option<String>,
+
+    /// Number display in tables and reports: human (thousands separators) or raw
+    #[arg(long, value_parser = ["human", "raw"])]
+    pub number_format: Option<String>,
+
+    /// Decimal places numbers are rounded to with --number-format human
+    #[arg(long)]
+    pub precision: Option<usize>,
+
+    /// numeric/bigint values in JSON output: number (exact literals) or string
+    #[arg(long, value_parser = ["number", "string"])]
+    pub numeric_output: Option<String>,
+
+    /// Rendering of a type as TYPE=STYLE, e.g. bytea=base64 (repeatable)
+    #[arg(long = "render", value_name = "TYPE=STYLE")]
+    pub render: Vec<String>,
+
     /// Disable TUI and use CLI mode
     #[arg(long, default_value = "false")]
     pub no_tui: bool,
 
+    /// Reproducible output for CI: temperature 0, fixed seed, single model
+    #[arg(long, env = "PG_AGENT_DETERMINISTIC")]
+    pub deterministic: bool,
+
+    /// Tenant whose rows agent queries are scoped to (see [safety.tenant])
+    #[arg(long, env = "PG_AGENT_TENANT")]
+    pub tenant: Option<String>,
+
+    /// Idempotency key: side effects already run under this key return
+    /// their recorded result instead of running again
+    #[arg(long, env = "PG_AGENT_IDEMPOTENCY_KEY")]
+    pub idempotency_key: Option<String>,
+
+    /// Send the LLM schema metadata and result shapes only, never row values
+    #[arg(long, env = "PG_AGENT_METADATA_ONLY")]
+    pub metadata_only: bool,
+
+    /// Simulate mutations in a sandbox database before confirming them
+    /// (see [safety.sandbox])
+    #[arg(long, env = "PG_AGENT_SANDBOX")]
+    pub sandbox: bool,
+
+    /// Save the tables of confirmed mutations so that `pg-agent undo` can
+    /// restore them (see [safety.snapshots])
+    #[arg(long, env = "PG_AGENT_SNAPSHOT")]
+    pub snapshot: bool,
+
+    /// Disable the startup check for new versions
+    #[arg(long, env = "PG_AGENT_NO_UPDATE_CHECK")]
+    pub no_update_check: bool,
+
     /// Subcommand to run
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -54,9 +133,15 @@ pub enum Commands {
     /// Query the database with natural language
     #[command(arg_required_else_help = true)]
     Query {
-        /// Natural language query
-        #[arg(trailing_var_arg = true)]
+        /// Natural language query, or `-` to read it from stdin
         query: Vec<String>,
+        /// File of supplementary context for the question, such as notes or
+        /// data (`-` for stdin); may be repeated
+        #[arg(long = "context-file", value_name = "FILE")]
+        context_files: Vec<String>,
+        /// Limits of this run
+        #[command(flatten)]
+        limits: RunLimits,
     },
 
     /// Start interactive REPL mode
@@ -65,6 +150,12 @@ pub enum Commands {
         /// Database profile to use
         #[arg(short, long, default_value = "default")]
         profile: String,
+        /// Keep one connection for the session so the agent can use temp tables
+        #[arg(long)]
+        workspace: bool,
+        /// Limits of each run in the session
+        #[command(flatten)]
+        limits: RunLimits,
     },
 
     /// Run a SQL file
@@ -81,7 +172,146 @@ pub enum Commands {
 
     /// Show current configuration
     #[command(name = "config")]
-    Config,
+    Config {
+        /// Configuration action (defaults to showing the configuration)
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+
+    /// List models available from the configured LLM provider
+    #[command(name = "models")]
+    Models,
+
+    /// Show effective file locations
+    #[command(name = "paths")]
+    Paths {
+        /// Also show how much disk space each location takes
+        #[arg(long)]
+        sizes: bool,
+    },
+
+    /// Show usage statistics
+    #[command(name = "stats")]
+    Stats {
+        /// Statistics to show (defaults to usage per profile and model)
+        #[command(subcommand)]
+        action: Option<StatsAction>,
+        /// Only include runs from this window, e.g. 24h, 7d or 2w
+        #[arg(long)]
+        since: Option<String>,
+    },
+
+    /// Summarize the audit log
+    #[command(name = "audit")]
+    Audit {
+        /// Audit view
+        #[command(subcommand)]
+        action: AuditAction,
+    },
+
+    /// Check SQL against the safety policy
+    #[command(name = "policy")]
+    Policy {
+        /// Policy action
+        #[command(subcommand)]
+        action: PolicyAction,
+    },
+
+    /// Approve or deny an operation waiting for admin approval; lists the
+    /// waiting operations without an ID
+    #[command(name = "approve")]
+    Approve {
+        /// Request ID shown by the waiting run
+        id: Option<String>,
+        /// Deny instead of approving
+        #[arg(long)]
+        deny: bool,
+    },
+
+    /// Restore the tables a mutation changed from the snapshot taken before
+    /// it
+    #[command(name = "undo")]
+    Undo {
+        /// Query history ID printed when the mutation ran
+        id: u64,
+        /// Print a psql script that restores the tables instead
+        #[arg(long)]
+        script: bool,
+    },
+
+    /// Compare query results between two database profiles
+    #[command(name = "compare")]
+    Compare {
+        /// Left profile
+        left: String,
+        /// Right profile
+        right: String,
+        /// Query to run on both (defaults to row counts per table)
+        #[arg(long)]
+        sql: Option<String>,
+        /// Column used to match rows (repeatable; defaults to the first column)
+        #[arg(long = "key")]
+        keys: Vec<String>,
+    },
+
+    /// Rate the last answer (👍 or 👎)
+    #[command(name = "feedback")]
+    Feedback {
+        /// Rating: 👍 (or up, +1) / 👎 (or down, -1)
+        #[arg(allow_hyphen_values = true)]
+        rating: String,
+        /// What was right or wrong about the answer
+        #[arg(long)]
+        comment: Option<String>,
+    },
+
+    /// List, show or export saved interactive sessions
+    #[command(name = "sessions")]
+    Sessions {
+        /// Session action (defaults to listing sessions)
+        #[command(subcommand)]
+        action: Option<SessionsAction>,
+    },
+
+    /// Re-run a previous query and diff the new result against the old one
+    Diff {
+        /// Query history ID, or a JSON file with saved result rows
+        #[arg(long)]
+        against: String,
+        /// Query to re-run (defaults to the SQL stored in the history entry)
+        #[arg(long)]
+        sql: Option<String>,
+        /// Column used to match rows (repeatable; defaults to the first column)
+        #[arg(long = "key")]
+        keys: Vec<String>,
+    },
+
+    /// Insert generated test data into a table
+    #[command(name = "seed", arg_required_else_help = true)]
+    Seed {
+        /// Table to fill, optionally schema-qualified (e.g. `analytics.events`)
+        table: String,
+        /// Number of rows to insert
+        #[arg(long, default_value_t = 10)]
+        rows: usize,
+        /// Do not ask the LLM for example values
+        #[arg(long)]
+        no_llm: bool,
+    },
+
+    /// Explain what an existing SQL query does, step by step
+    #[command(name = "explain-sql", arg_required_else_help = true)]
+    ExplainSql {
+        /// SQL file, or the statement itself
+        query: String,
+    },
+
+    /// Propose a faster rewrite of a query and indexes for it
+    #[command(name = "optimize", arg_required_else_help = true)]
+    Optimize {
+        /// SQL file, or the statement itself
+        query: String,
+    },
 
     /// Show schema information
     #[command(name = "schema")]
@@ -91,27 +321,203 @@ pub enum Commands {
         table: Option<String>,
     },
 
+    /// Subscribe to LISTEN/NOTIFY channels and print notifications
+    #[command(name = "listen", arg_required_else_help = true)]
+    Listen {
+        /// Channels to listen on, as written in NOTIFY (case-sensitive)
+        #[arg(required = true)]
+        channels: Vec<String>,
+        /// Ask the LLM to summarize each payload
+        #[arg(long)]
+        summarize: bool,
+    },
+
     /// Run system health checks
     #[command(name = "doctor")]
-    Doctor,
+    Doctor {
+        /// Skip the LLM connectivity check (offline use)
+        #[arg(long)]
+        skip_llm: bool,
+    },
 
     /// Show version and exit
     #[command(name = "version")]
     Version,
+
+    /// Update pg-agent to the latest release
+    ///
+    /// The download must carry a valid signature by the release key built
+    /// into pg-agent; otherwise nothing is installed.
+    #[command(name = "self-update")]
+    SelfUpdate {
+        /// Only check for a new version, do not install it
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+/// Configuration subcommands.
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Create a configuration file interactively
+    #[command(name = "init")]
+    Init {
+        /// Overwrite an existing configuration file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Saved session subcommands.
+#[derive(Subcommand, Debug)]
+pub enum SessionsAction {
+    /// List saved sessions
+    #[command(name = "list")]
+    List,
+    /// Show the questions and answers of a session
+    #[command(name = "show")]
+    Show {
+        /// Session ID (or a unique prefix)
+        id: String,
+        /// Print a short LLM-written summary instead of every turn
+        #[arg(long)]
+        summary: bool,
+    },
+    /// Export a session as a readable report
+    #[command(name = "export")]
+    Export {
+        /// Session ID (or a unique prefix)
+        id: String,
+        /// Report format (markdown, html)
+        #[arg(long, default_value = "markdown")]
+        format: String,
+        /// Write the report to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Rows shown per result table
+        #[arg(long, default_value_t = 20)]
+        max_rows: usize,
+    },
+}
+
+/// Statistics views.
+#[derive(Subcommand, Debug)]
+pub enum StatsAction {
+    /// Per-tool call counts, error rates and latency percentiles
+    #[command(name = "tools")]
+    Tools,
+}
+
+/// Safety policy actions.
+#[derive(Subcommand, Debug)]
+pub enum PolicyAction {
+    /// Show whether a statement would be allowed, confirmed or denied,
+    /// without running it
+    #[command(name = "test")]
+    Test {
+        /// Statement and evaluation settings
+        #[command(flatten)]
+        input: PolicyInput,
+    },
+    /// Show every safety check a statement goes through and which
+    /// patterns, rules and conditions matched, without running it
+    #[command(name = "explain")]
+    Explain {
+        /// Statement and evaluation settings
+        #[command(flatten)]
+        input: PolicyInput,
+    },
+}
+
+/// Per-run limits overriding the configuration, up to the ceilings in
+/// `[safety.ceilings]`.
+#[derive(Args, Debug, Clone, Default)]
+pub struct RunLimits {
+    /// Seconds a run may take before a partial answer is given
+    #[arg(long, value_name = "SECS")]
+    pub timeout: Option<u64>,
+    /// Maximum reasoning iterations of a run
+    #[arg(long, value_name = "N")]
+    pub max_iterations: Option<u32>,
+    /// Maximum result rows returned with an answer
+    #[arg(long, value_name = "N")]
+    pub max_rows: Option<usize>,
+}
+
+/// Statement checked against the safety policy.
+#[derive(Args, Debug)]
+pub struct PolicyInput {
+    /// SQL statement to check
+    pub sql: String,
+    /// Database role to check for (defaults to the connected role)
+    #[arg(long)]
+    pub role: Option<String>,
+    /// Row estimate to check with (defaults to the planner's estimate)
+    #[arg(long)]
+    pub rows: Option<i64>,
+    /// Local time to check at, e.g. "2024-06-01 23:30" (defaults to now)
+    #[arg(long)]
+    pub at: Option<String>,
+}
+
+/// Audit log views.
+#[derive(Subcommand, Debug)]
+pub enum AuditAction {
+    /// Query shapes run most often, from the audit log and query history
+    #[command(name = "top")]
+    Top {
+        /// Only include runs from this window, e.g. 24h, 7d or 2w
+        #[arg(long)]
+        since: Option<String>,
+        /// Number of query shapes shown
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
 }
 
 impl CliArgs {
+    /// Parse arguments from the process command line.
+    ///
+    /// When `--config` is not given and `config.toml` is absent from the
+    /// current directory, the first existing file from
+    /// [`find_config_files`] is used instead.
+    #[must_use]
+    pub fn parse_with_config_discovery() -> Self {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        if matches.value_source("config") == Some(ValueSource::DefaultValue)
+            && !Path::new(&args.config).exists()
+            && let Some(found) = find_config_files().into_iter().find(|p| p.exists())
+        {
+            args.config = found.display().to_string();
+        }
+
+        args
+    }
+
     /// Get the query string from arguments.
     #[must_use]
     pub fn get_query(&self) -> Option<String> {
         match &self.command {
-            Some(Commands::Query { query }) if !query.is_empty() => {
+            Some(Commands::Query { query, .. }) if !query.is_empty() => {
                 Some(query.join(" "))
             }
             _ => None,
         }
     }
 
+    /// Run limits given to `query` or `interactive`.
+    #[must_use]
+    pub fn run_limits(&self) -> RunLimits {
+        match &self.command {
+            Some(Commands::Query { limits, .. } | Commands::Interactive { limits, .. }) => {
+                limits.clone()
+            }
+            _ => RunLimits::default(),
+        }
+    }
+
     /// Get the files from arguments.
     #[must_use]
     pub fn get_files(&self) -> Option<Vec<String>> {
@@ -153,18 +559,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_query_context_files() {
+        let args = CliArgs::parse_from([
+            "pg-agent",
+            "query",
+            "summarize this",
+            "--context-file", "notes.md",
+            "--context-file", "-",
+        ]);
+
+        match &args.command {
+            Some(Commands::Query { query, context_files, .. }) => {
+                assert_eq!(query, &["summarize this"]);
+                assert_eq!(context_files, &["notes.md", "-"]);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let args = CliArgs::parse_from(["pg-agent", "query", "count users", "--timeout", "60"]);
+        assert_eq!(args.run_limits().timeout, Some(60));
+
+        let args = CliArgs::parse_from(["pg-agent", "query", "-"]);
+        assert_eq!(args.get_query(), Some("-".to_string()));
+    }
+
+    #[test]
+    fn test_sessions_export_command() {
+        let args = CliArgs::parse_from([
+            "pg-agent",
+            "sessions",
+            "export",
+            "20261016-0930",
+            "--format", "html",
+        ]);
+
+        match &args.command {
+            Some(Commands::Sessions {
+                action: Some(SessionsAction::Export { id, format, output, max_rows }),
+            }) => {
+                assert_eq!(id, "20261016-0930");
+                assert_eq!(format, "html");
+                assert!(output.is_none());
+                assert_eq!(*max_rows, 20);
+            }
+            _ => panic!("Expected sessions export command"),
+        }
+    }
+
+    #[test]
+    fn test_sessions_show_command() {
+        let args = CliArgs::parse_from(["pg-agent", "sessions", "show", "20261016", "--summary"]);
+        match &args.command {
+            Some(Commands::Sessions {
+                action: Some(SessionsAction::Show { id, summary }),
+            }) => {
+                assert_eq!(id, "20261016");
+                assert!(summary);
+            }
+            _ => panic!("Expected sessions show command"),
+        }
+    }
+
     #[test]
     fn test_interactive_command() {
         let args = CliArgs::parse_from([
             "pg-agent",
             "interactive",
             "--profile", "production",
+            "--workspace",
         ]);
 
         assert!(args.is_interactive());
         match &args.command {
-            Some(Commands::Interactive { profile }) => {
+            Some(Commands::Interactive { profile, workspace, .. }) => {
                 assert_eq!(profile, "production");
+                assert!(workspace);
             }
             _ => panic!("Expected Interactive command"),
         }
@@ -193,12 +663,120 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_explain_sql_command() {
+        let args = CliArgs::parse_from(["pg-agent", "explain-sql", "reports/legacy.sql"]);
+        match &args.command {
+            Some(Commands::ExplainSql { query }) => assert_eq!(query, "reports/legacy.sql"),
+            _ => panic!("Expected ExplainSql command"),
+        }
+        assert!(CliArgs::try_parse_from(["pg-agent", "explain-sql"]).is_err());
+    }
+
+    #[test]
+    fn test_optimize_command() {
+        let args = CliArgs::parse_from(["pg-agent", "optimize", "SELECT * FROM orders"]);
+        match &args.command {
+            Some(Commands::Optimize { query }) => assert_eq!(query, "SELECT * FROM orders"),
+            _ => panic!("Expected Optimize command"),
+        }
+        assert!(CliArgs::try_parse_from(["pg-agent", "optimize"]).is_err());
+    }
+
+    #[test]
+    fn test_listen_command() {
+        let args = CliArgs::parse_from(["pg-agent", "listen", "orders", "jobs", "--summarize"]);
+        match &args.command {
+            Some(Commands::Listen { channels, summarize }) => {
+                assert_eq!(channels, &["orders", "jobs"]);
+                assert!(summarize);
+            }
+            _ => panic!("Expected Listen command"),
+        }
+        assert!(CliArgs::try_parse_from(["pg-agent", "listen"]).is_err());
+    }
+
+    #[test]
+    fn test_approve_command() {
+        let args = CliArgs::parse_from(["pg-agent", "approve", "req-1", "--deny"]);
+        match &args.command {
+            Some(Commands::Approve { id, deny }) => {
+                assert_eq!(id.as_deref(), Some("req-1"));
+                assert!(deny);
+            }
+            _ => panic!("Expected Approve command"),
+        }
+        let args = CliArgs::parse_from(["pg-agent", "approve"]);
+        assert!(matches!(args.command, Some(Commands::Approve { id: None, deny: false })));
+    }
+
+    #[test]
+    fn test_undo_command() {
+        let args = CliArgs::parse_from(["pg-agent", "--snapshot", "undo", "7", "--script"]);
+        assert!(args.snapshot);
+        assert!(matches!(args.command, Some(Commands::Undo { id: 7, script: true })));
+    }
+
+    #[test]
+    fn test_config_init_command() {
+        let args = CliArgs::parse_from(["pg-agent", "config"]);
+        assert!(matches!(args.command, Some(Commands::Config { action: None })));
+
+        let args = CliArgs::parse_from(["pg-agent", "config", "init", "--force"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Config {
+                action: Some(ConfigAction::Init { force: true })
+            })
+        ));
+    }
+
+    #[test]
+    fn test_self_update_command() {
+        let args = CliArgs::parse_from(["pg-agent", "self-update", "--check"]);
+        assert!(matches!(args.command, Some(Commands::SelfUpdate { check: true })));
+    }
+
+    #[test]
+    fn test_doctor_skip_llm() {
+        let args = CliArgs::parse_from(["pg-agent", "doctor", "--skip-llm"]);
+        assert!(matches!(args.command, Some(Commands::Doctor { skip_llm: true })));
+    }
+
+    #[test]
+    fn test_log_format() {
+        let args = CliArgs::parse_from(["pg-agent", "--log-format", "json", "version"]);
+        assert_eq!(args.log_format, "json");
+
+        assert!(CliArgs::try_parse_from(["pg-agent", "--log-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_number_format() {
+        let args =
+            CliArgs::parse_from(["pg-agent", "--number-format", "human", "--precision", "2"]);
+        assert_eq!(args.number_format.as_deref(), Some("human"));
+        assert_eq!(args.precision, Some(2));
+
+        assert!(CliArgs::try_parse_from(["pg-agent", "--number-format", "pretty"]).is_err());
+        assert!(CliArgs::try_parse_from(["pg-agent", "--numeric-output", "float"]).is_err());
+        assert!(CliArgs::try_parse_from(["pg-agent", "--persona", "intern"]).is_err());
+        let args = CliArgs::parse_from(["pg-agent", "--persona", "dba"]);
+        assert_eq!(args.persona.as_deref(), Some("dba"));
+
+        let args = CliArgs::parse_from([
+            "pg-agent", "--render", "bytea=base64", "--render", "tsvector=lexemes",
+        ]);
+        assert_eq!(args.render, ["bytea=base64", "tsvector=lexemes"]);
+    }
+
     #[test]
     fn test_default_values() {
         let args = CliArgs::parse_from(["pg-agent"]);
 
         assert_eq!(args.config, "config.toml");
         assert_eq!(args.log_level, "info");
+        assert_eq!(args.log_format, "text");
         assert_eq!(args.profile, "default");
         assert!(!args.no_confirm);
         assert!(!args.is_interactive());
@@ -210,24 +210,9 @@ pub fn format_message(message: &Message) -> String {
     format!("[{}]: {}", role, message.content)
 }
 
-/// Find configuration file paths.
+/// Find configuration file paths, in order of precedence.
 pub fn find_config_files() -> Vec<PathBuf> {
-    let mut paths = Vec::new();
-
-    // Check current directory
-    paths.push(PathBuf::from("config.yaml"));
-
-    // Check home directory
-    if let Ok(home) = std::env::var("HOME") {
-        paths.push(PathBuf::from(home).join(".config").join("pg-agent.yaml"));
-    }
-
-    // Check XDG config directory
-    if let Ok(config) = std::env::var("XDG_CONFIG_HOME") {
-        paths.push(PathBuf::from(config).join("pg-agent.yaml"));
-    }
-
-    paths
+    postgres_agent_config::paths::config_search_paths()
 }
 
 #[cfg(test)]
@@ -239,7 +224,7 @@ mod tests {
         assert!(matches!(OutputFormat::from_str("table"), Ok(OutputFormat::Table)));
         assert!(matches!(OutputFormat::from_str("json"), Ok(OutputFormat::Json)));
         assert!(matches!(OutputFormat::from_str("csv"), Ok(OutputFormat::Csv)));
-        assert!(matches!(OutputFormat::from_str("invalid"), Err(_)));
+        assert!(OutputFormat::from_str("invalid").is_err());
     }
 
     #[test]
@@ -7,5 +7,8 @@
 pub mod args;
 pub mod commands;
 
-pub use args::{CliArgs, Commands};
+pub use args::{
+    AuditAction, CliArgs, Commands, ConfigAction, PolicyAction, PolicyInput, RunLimits,
+    SessionsAction, StatsAction,
+};
 pub use commands::{OutputFormat, QueryContext, QueryResult};
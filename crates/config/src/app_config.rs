@@ -1,8 +1,10 @@
 //! Application configuration.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use super::{DatabaseProfile, LlmConfig, SafetyConfig};
+use super::{DatabaseProfile, LlmConfig, PathsConfig, SafetyConfig, StorageConfig};
+use crate::safety::SafetyLevel;
 
 /// Application configuration.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -23,11 +25,47 @@ pub struct AppConfig {
     /// Safety and security settings.
     #[serde(default)]
     pub safety: SafetyConfig,
+
+    /// File location overrides.
+    #[serde(default)]
+    pub paths: PathsConfig,
+
+    /// Tool settings.
+    #[serde(default)]
+    pub tools: ToolsConfig,
+
+    /// Compression and cleanup of persisted files.
+    #[serde(default)]
+    pub storage: StorageConfig,
 }
 
 /// Alias for AppConfig.
 pub type Config = AppConfig;
 
+/// Tool settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ToolsConfig {
+    /// Namespaces to enable or disable, e.g. `mcp = false`. Namespaces not
+    /// listed are enabled, except those in [`Self::DISABLED_BY_DEFAULT`].
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, bool>,
+}
+
+impl ToolsConfig {
+    /// Namespaces that must be enabled explicitly.
+    pub const DISABLED_BY_DEFAULT: &'static [&'static str] = &["admin"];
+
+    /// Whether a namespace is enabled.
+    #[must_use]
+    pub fn is_namespace_enabled(&self, namespace: &str) -> bool {
+        self.namespaces
+            .get(namespace)
+            .copied()
+            .unwrap_or(!Self::DISABLED_BY_DEFAULT.contains(&namespace))
+    }
+}
+
 /// Agent behavior configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -43,6 +81,165 @@ pub struct AgentConfig {
     /// Default output format.
     #[serde(default)]
     pub default_output: String,
+
+    /// Seconds to wait for cleanup after SIGINT/SIGTERM before exiting.
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+
+    /// Seconds allowed for one reasoning iteration.
+    #[serde(default = "default_iteration_timeout_secs")]
+    pub iteration_timeout_secs: u64,
+
+    /// Seconds allowed for a whole query before a partial answer is given.
+    #[serde(default = "default_run_timeout_secs")]
+    pub run_timeout_secs: u64,
+
+    /// Query result rows returned with an answer, e.g. in JSON output.
+    #[serde(default = "default_max_result_rows")]
+    pub max_result_rows: usize,
+
+    /// Whether the model's reasoning steps are written to the audit log.
+    #[serde(default = "default_audit_reasoning")]
+    pub audit_reasoning: bool,
+
+    /// Zone `timestamptz` results are shown in: `utc`, `session` or an IANA
+    /// name such as `Europe/Berlin`.
+    #[serde(default = "default_time_zone")]
+    pub time_zone: String,
+
+    /// How numbers are shown in tables and reports: `raw` or `human`
+    /// (thousands separators). CSV and JSON output stay raw.
+    #[serde(default = "default_number_format")]
+    pub number_format: String,
+
+    /// Decimal places numbers are rounded to in `human` format.
+    #[serde(default)]
+    pub decimal_places: Option<usize>,
+
+    /// How `numeric` and `bigint` values are written in JSON output and
+    /// exports: `number` (exact literals) or `string`.
+    #[serde(default = "default_numeric_output")]
+    pub numeric_output: String,
+
+    /// Rendering of types with a choice of representation, by type name:
+    /// `bytea` (hex, base64, length), `point` and `geometry` (wkt, native)
+    /// and `tsvector` (text, lexemes).
+    #[serde(default)]
+    pub type_rendering: BTreeMap<String, String>,
+
+    /// Whether interactive sessions run on one dedicated connection on
+    /// which the agent may create temp tables for intermediate results.
+    #[serde(default)]
+    pub temp_workspace: bool,
+
+    /// Megabytes of result rows kept in memory; further rows are spilled
+    /// to a temporary file. 0 keeps every row in memory.
+    #[serde(default = "default_max_result_memory_mb")]
+    pub max_result_memory_mb: usize,
+
+    /// Persona bundling prompt style, tools and safety level.
+    #[serde(default)]
+    pub persona: Option<Persona>,
+
+    /// Whether trivial schema requests such as "list tables" or "describe
+    /// users" are answered with a direct tool call, without the LLM.
+    #[serde(default = "default_fast_path")]
+    pub fast_path: bool,
+}
+
+/// Agent persona: a prompt style, the tools offered to the model and a
+/// safety level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Persona {
+    /// Read-only business questions with charts and exports.
+    Analyst,
+    /// Server health and maintenance, with the admin tools.
+    Dba,
+    /// Schema documentation and migration scripts.
+    Developer,
+}
+
+impl Persona {
+    /// Every persona.
+    pub const ALL: [Persona; 3] = [Self::Analyst, Self::Dba, Self::Developer];
+
+    /// Name used in configuration and on the command line.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Analyst => "analyst",
+            Self::Dba => "dba",
+            Self::Developer => "developer",
+        }
+    }
+
+    /// Look up a persona by name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|persona| persona.as_str() == name)
+    }
+
+    /// Safety level the persona runs at.
+    #[must_use]
+    pub fn safety_level(self) -> SafetyLevel {
+        match self {
+            Self::Analyst => SafetyLevel::ReadOnly,
+            Self::Dba => SafetyLevel::Permissive,
+            Self::Developer => SafetyLevel::Balanced,
+        }
+    }
+
+    /// Tool namespaces the persona enables that are disabled by default;
+    /// a namespace disabled in `[tools.namespaces]` stays disabled.
+    #[must_use]
+    pub fn namespaces(self) -> &'static [&'static str] {
+        match self {
+            Self::Dba => &["admin"],
+            Self::Analyst | Self::Developer => &[],
+        }
+    }
+
+    /// Built-in tools offered to the model, by bare name.
+    #[must_use]
+    pub fn tools(self) -> &'static [&'static str] {
+        match self {
+            Self::Analyst => &[
+                "execute_query",
+                "get_schema",
+                "list_tables",
+                "describe_table",
+                "explain_query",
+                "trace_column",
+                "detect_anomalies",
+                "compare_profiles",
+                "export_result",
+                "suggest_chart",
+            ],
+            Self::Dba => &[
+                "execute_query",
+                "get_schema",
+                "list_tables",
+                "describe_table",
+                "explain_query",
+                "get_table_dependencies",
+                "trace_column",
+                "compare_profiles",
+                "kill_query",
+                "refresh_matview",
+            ],
+            Self::Developer => &[
+                "execute_query",
+                "get_schema",
+                "list_tables",
+                "describe_table",
+                "explain_query",
+                "get_table_dependencies",
+                "trace_column",
+                "export_result",
+            ],
+        }
+    }
 }
 
 fn default_max_history() -> usize {
@@ -53,12 +250,66 @@ fn default_max_iterations() -> u32 {
     10
 }
 
+fn default_shutdown_timeout_secs() -> u64 {
+    5
+}
+
+fn default_iteration_timeout_secs() -> u64 {
+    30
+}
+
+fn default_run_timeout_secs() -> u64 {
+    120
+}
+
+fn default_max_result_rows() -> usize {
+    500
+}
+
+fn default_max_result_memory_mb() -> usize {
+    256
+}
+
+fn default_audit_reasoning() -> bool {
+    true
+}
+
+fn default_fast_path() -> bool {
+    true
+}
+
+fn default_time_zone() -> String {
+    "utc".to_string()
+}
+
+fn default_number_format() -> String {
+    "raw".to_string()
+}
+
+fn default_numeric_output() -> String {
+    "number".to_string()
+}
+
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
             max_history: default_max_history(),
             max_iterations: default_max_iterations(),
             default_output: "table".to_string(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+            iteration_timeout_secs: default_iteration_timeout_secs(),
+            run_timeout_secs: default_run_timeout_secs(),
+            max_result_rows: default_max_result_rows(),
+            audit_reasoning: default_audit_reasoning(),
+            time_zone: default_time_zone(),
+            number_format: default_number_format(),
+            decimal_places: None,
+            numeric_output: default_numeric_output(),
+            type_rendering: BTreeMap::new(),
+            temp_workspace: false,
+            persona: None,
+            max_result_memory_mb: default_max_result_memory_mb(),
+            fast_path: default_fast_path(),
         }
     }
 }
@@ -1,15 +1,18 @@
 //! Database profile configuration.
 
+use postgres_agent_util::crypto::{redact_url, serialize_redacted_url};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use url::Url;
 
 /// Database profile configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct DatabaseProfile {
     /// Unique profile name.
     pub name: String,
-    /// Connection URL.
+    /// Connection URL; serialized with the password masked.
+    #[serde(serialize_with = "serialize_redacted_url")]
     pub url: String,
     /// Optional display name.
     pub display_name: Option<String>,
@@ -19,6 +22,32 @@ pub struct DatabaseProfile {
     /// Connection timeout in seconds.
     #[serde(default = "default_connect_timeout")]
     pub connect_timeout: u64,
+    /// Server-side `statement_timeout` (e.g. "30s").
+    #[serde(default)]
+    pub statement_timeout: Option<String>,
+    /// Server-side `idle_in_transaction_session_timeout` (e.g. "60s").
+    #[serde(default)]
+    pub idle_in_transaction_session_timeout: Option<String>,
+    /// Server-side `work_mem` (e.g. "64MB").
+    #[serde(default)]
+    pub work_mem: Option<String>,
+    /// Server-side `application_name` (defaults to `pg-agent/<version>/<user>`).
+    #[serde(default)]
+    pub application_name: Option<String>,
+    /// Notes added to the system prompt for this database, e.g. units or
+    /// tables to avoid.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Schema of unqualified table names in tools such as `describe_table`;
+    /// the session's `search_path` decides when unset.
+    #[serde(default)]
+    pub default_schema: Option<String>,
+    /// Server-side `search_path`, in order (e.g. `["analytics", "public"]`).
+    #[serde(default)]
+    pub search_path: Vec<String>,
+    /// Require the agent to schema-qualify every table it queries.
+    #[serde(default)]
+    pub qualify_tables: bool,
 }
 
 fn default_ssl_mode() -> String {
@@ -29,6 +58,38 @@ fn default_connect_timeout() -> u64 {
     30
 }
 
+impl fmt::Debug for DatabaseProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatabaseProfile")
+            .field("name", &self.name)
+            .field("url", &redact_url(&self.url))
+            .field("display_name", &self.display_name)
+            .field("ssl_mode", &self.ssl_mode)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("statement_timeout", &self.statement_timeout)
+            .field(
+                "idle_in_transaction_session_timeout",
+                &self.idle_in_transaction_session_timeout,
+            )
+            .field("work_mem", &self.work_mem)
+            .field("application_name", &self.application_name)
+            .field("prompt", &self.prompt)
+            .field("default_schema", &self.default_schema)
+            .field("search_path", &self.search_path)
+            .field("qualify_tables", &self.qualify_tables)
+            .finish()
+    }
+}
+
+/// Default `application_name` reported to the server: `pg-agent/<version>/<user>`.
+#[must_use]
+pub fn default_application_name() -> String {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string());
+    format!("pg-agent/{}/{}", env!("CARGO_PKG_VERSION"), user)
+}
+
 impl DatabaseProfile {
     /// Create a new database profile.
     #[allow(dead_code)]
@@ -39,14 +100,102 @@ impl DatabaseProfile {
             display_name: None,
             ssl_mode: default_ssl_mode(),
             connect_timeout: default_connect_timeout(),
+            statement_timeout: None,
+            idle_in_transaction_session_timeout: None,
+            work_mem: None,
+            application_name: None,
+            prompt: None,
+            default_schema: None,
+            search_path: Vec::new(),
+            qualify_tables: false,
         }
     }
 
+    /// Get the `application_name` to report, falling back to the default.
+    #[must_use]
+    pub fn effective_application_name(&self) -> String {
+        self.application_name
+            .clone()
+            .unwrap_or_else(default_application_name)
+    }
+
     /// Validate the profile configuration.
     #[allow(dead_code)]
     pub fn validate(&self) -> Result<(), String> {
         Url::parse(&self.url)
             .map_err(|_| "Invalid database URL".to_string())?;
+
+        // Settings are sent as startup options, which cannot contain whitespace
+        let settings = [
+            ("statement_timeout", &self.statement_timeout),
+            (
+                "idle_in_transaction_session_timeout",
+                &self.idle_in_transaction_session_timeout,
+            ),
+            ("work_mem", &self.work_mem),
+        ];
+        for (name, value) in settings {
+            if let Some(value) = value
+                && (value.is_empty() || value.contains(char::is_whitespace))
+            {
+                return Err(format!("Invalid {} value: '{}'", name, value));
+            }
+        }
+
+        if self.default_schema.as_deref().is_some_and(|s| s.trim().is_empty()) {
+            return Err("default-schema must not be empty".to_string());
+        }
+        if let Some(schema) = self
+            .search_path
+            .iter()
+            .find(|s| s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == ','))
+        {
+            return Err(format!("Invalid search-path schema: '{}'", schema));
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_session_settings() {
+        let mut profile = DatabaseProfile::new("test", "postgresql://localhost/test");
+        profile.statement_timeout = Some("30s".to_string());
+        profile.work_mem = Some("64MB".to_string());
+        assert!(profile.validate().is_ok());
+
+        profile.work_mem = Some("64 MB".to_string());
+        assert!(profile.validate().is_err());
+
+        profile.work_mem = None;
+        profile.default_schema = Some(" ".to_string());
+        assert!(profile.validate().is_err());
+
+        profile.default_schema = None;
+        profile.search_path = vec!["analytics".to_string(), "public".to_string()];
+        assert!(profile.validate().is_ok());
+        profile.search_path.push("a, b".to_string());
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_serialize_redacts_password() {
+        let profile = DatabaseProfile::new("prod", "postgresql://app:s3cret@db/prod");
+        let toml = toml::to_string(&profile).unwrap();
+        assert!(!toml.contains("s3cret"));
+        assert!(toml.contains("postgresql://app:***@db/prod"));
+    }
+
+    #[test]
+    fn test_effective_application_name() {
+        let mut profile = DatabaseProfile::new("test", "postgresql://localhost/test");
+        assert!(profile.effective_application_name().starts_with("pg-agent/"));
+
+        profile.application_name = Some("reporting".to_string());
+        assert_eq!(profile.effective_application_name(), "reporting");
+    }
+}
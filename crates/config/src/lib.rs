@@ -10,11 +10,21 @@ pub mod database;
 pub mod error;
 pub mod loader;
 pub mod llm;
+pub mod paths;
 pub mod safety;
+pub mod storage;
 
-pub use app_config::{AppConfig, Config};
+pub use app_config::{AppConfig, Config, Persona, ToolsConfig};
 pub use database::DatabaseProfile;
 pub use error::ConfigError;
 pub use loader::ConfigLoader;
-pub use llm::LlmConfig;
-pub use safety::SafetyConfig;
+pub use llm::{
+    AzureOpenAiConfig, BudgetAction, ExamplesConfig, LlmConfig, ModelPricing, ResponseCacheConfig,
+    TokenizerFallback,
+};
+pub use paths::PathsConfig;
+pub use safety::{
+    AdminApprovalMode, LlmDataPolicy, PolicyRuleConfig, RuleAction, RunCeilings, SafetyConfig,
+    SandboxConfig, SelectStarConfig, SnapshotConfig, TenantConfig, TenantMode,
+};
+pub use storage::StorageConfig;
@@ -1,13 +1,15 @@
 //! LLM provider configuration.
 
+use postgres_agent_util::crypto::Secret;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use url::Url;
 
 /// LLM provider configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct LlmConfig {
-    /// Provider type (openai, anthropic, ollama, etc.)
+    /// Provider type (openai, azure-openai, gemini, anthropic, ollama)
     #[serde(default = "default_provider")]
     pub provider: String,
 
@@ -15,12 +17,17 @@ pub struct LlmConfig {
     pub base_url: Option<Url>,
 
     /// API key (supports env:// prefix for env var lookup).
-    pub api_key: Option<String>,
+    pub api_key: Option<Secret<String>>,
 
     /// Model identifier.
     #[serde(default = "default_model")]
     pub model: String,
 
+    /// Cheaper model for intermediate reasoning and tool selection; `model`
+    /// is still used for SQL generation and final answers.
+    #[serde(default)]
+    pub reasoning_model: Option<String>,
+
     /// Temperature for sampling (0.0 to 2.0).
     #[serde(default = "default_temperature")]
     pub temperature: f32,
@@ -28,6 +35,209 @@ pub struct LlmConfig {
     /// Maximum tokens in response.
     #[serde(default = "default_max_tokens")]
     pub max_tokens: u32,
+
+    /// Sampling seed, sent to providers that support one.
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// Reproducible mode for CI and evals: temperature 0, a fixed seed and
+    /// no sampling-dependent features. Also set by `--deterministic`.
+    #[serde(default)]
+    pub deterministic: bool,
+
+    /// Maximum estimated tokens a single query may consume (unset = unlimited).
+    #[serde(default)]
+    pub max_tokens_per_query: Option<u32>,
+
+    /// Maximum estimated spend per day in USD (unset = unlimited).
+    #[serde(default)]
+    pub max_cost_per_day: Option<f64>,
+
+    /// Price per 1K tokens in USD, used to estimate spend.
+    #[serde(default)]
+    pub cost_per_1k_tokens: f64,
+
+    /// Prices by model, for models whose prompt and completion tokens are
+    /// priced apart, e.g. `[llm.pricing."gpt-4o-mini"]`. Models not listed
+    /// use `cost-per-1k-tokens`.
+    #[serde(default)]
+    pub pricing: BTreeMap<String, ModelPricing>,
+
+    /// Action to take when the daily budget is exhausted.
+    #[serde(default)]
+    pub on_budget_exceeded: BudgetAction,
+
+    /// Tokenizer for models without a known tiktoken encoding, such as
+    /// Gemini, Claude or local models.
+    #[serde(default)]
+    pub tokenizer_fallback: TokenizerFallback,
+
+    /// Provider safety filter thresholds by harm category (Gemini only),
+    /// e.g. `HARM_CATEGORY_DANGEROUS_CONTENT = "BLOCK_ONLY_HIGH"`.
+    #[serde(default)]
+    pub safety_settings: BTreeMap<String, String>,
+
+    /// Azure OpenAI settings, required when `provider = "azure-openai"`.
+    #[serde(default)]
+    pub azure: Option<AzureOpenAiConfig>,
+
+    /// Show similar past questions as examples (disabled when unset).
+    #[serde(default)]
+    pub examples: Option<ExamplesConfig>,
+
+    /// Reuse decisions for identical requests (disabled when unset).
+    #[serde(default)]
+    pub response_cache: Option<ResponseCacheConfig>,
+
+    /// Write every prompt and raw response to a debug log (`paths.llm-log`),
+    /// with secrets and PII redacted.
+    #[serde(default)]
+    pub log_requests: bool,
+}
+
+/// Azure OpenAI deployment settings.
+///
+/// The resource endpoint (e.g. `https://my-resource.openai.azure.com/`) is
+/// taken from `base-url`. Authentication uses `api-key` or, if set, an
+/// Azure AD bearer token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AzureOpenAiConfig {
+    /// Deployment name; requests are routed to this deployment, not `model`.
+    pub deployment: String,
+
+    /// REST API version.
+    #[serde(default = "default_azure_api_version")]
+    pub api_version: String,
+
+    /// Azure AD access token (supports env:// prefix), used instead of the API key.
+    #[serde(default)]
+    pub ad_token: Option<Secret<String>>,
+}
+
+/// Price of a model in USD per 1K tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ModelPricing {
+    /// Price of prompt tokens.
+    #[serde(default)]
+    pub prompt_per_1k: f64,
+
+    /// Price of completion tokens.
+    #[serde(default)]
+    pub completion_per_1k: f64,
+}
+
+/// Few-shot example retrieval from past runs.
+///
+/// Questions are embedded with the provider's embeddings endpoint
+/// (`base-url` and `api-key` are shared with the chat model).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExamplesConfig {
+    /// Embedding model.
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+
+    /// Maximum examples per question.
+    #[serde(default = "default_examples_k")]
+    pub k: usize,
+
+    /// Minimum cosine similarity for a past question to be used.
+    #[serde(default = "default_min_similarity")]
+    pub min_similarity: f32,
+}
+
+impl Default for ExamplesConfig {
+    fn default() -> Self {
+        Self {
+            embedding_model: default_embedding_model(),
+            k: default_examples_k(),
+            min_similarity: default_min_similarity(),
+        }
+    }
+}
+
+/// Reuse of LLM decisions for identical requests.
+///
+/// A request is identical when the provider, model, sampling settings and
+/// every message sent, system prompt included, are the same.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ResponseCacheConfig {
+    /// Maximum decisions kept in memory.
+    #[serde(default = "default_cache_capacity")]
+    pub capacity: usize,
+
+    /// Seconds a decision is reused for.
+    #[serde(default = "default_cache_ttl_secs")]
+    pub ttl_secs: u64,
+
+    /// Also keep decisions in `paths.cache-dir`, across sessions.
+    #[serde(default = "default_true")]
+    pub persist: bool,
+}
+
+impl Default for ResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: default_cache_capacity(),
+            ttl_secs: default_cache_ttl_secs(),
+            persist: true,
+        }
+    }
+}
+
+/// Action taken when a budget limit is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BudgetAction {
+    /// Refuse to run the query.
+    #[default]
+    Abort,
+    /// Ask the user whether to continue anyway.
+    Confirm,
+}
+
+/// Token counting for models without a known encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TokenizerFallback {
+    /// The `o200k_base` encoding of current OpenAI models.
+    #[default]
+    O200kBase,
+    /// The `cl100k_base` encoding of GPT-4 and GPT-3.5.
+    Cl100kBase,
+    /// Four characters per token.
+    Approximate,
+}
+
+fn default_azure_api_version() -> String {
+    "2024-06-01".to_string()
+}
+
+fn default_embedding_model() -> String {
+    "text-embedding-3-small".to_string()
+}
+
+fn default_examples_k() -> usize {
+    3
+}
+
+fn default_min_similarity() -> f32 {
+    0.75
+}
+
+fn default_cache_capacity() -> usize {
+    256
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_true() -> bool {
+    true
 }
 
 fn default_provider() -> String {
@@ -53,8 +263,58 @@ impl Default for LlmConfig {
             base_url: None,
             api_key: None,
             model: default_model(),
+            reasoning_model: None,
             temperature: default_temperature(),
             max_tokens: default_max_tokens(),
+            seed: None,
+            deterministic: false,
+            max_tokens_per_query: None,
+            max_cost_per_day: None,
+            cost_per_1k_tokens: 0.0,
+            pricing: BTreeMap::new(),
+            on_budget_exceeded: BudgetAction::default(),
+            tokenizer_fallback: TokenizerFallback::default(),
+            safety_settings: BTreeMap::new(),
+            azure: None,
+            examples: None,
+            response_cache: None,
+            log_requests: false,
+        }
+    }
+}
+
+impl LlmConfig {
+    /// Seed used by deterministic mode when none is configured.
+    pub const DETERMINISTIC_SEED: u64 = 42;
+
+    /// Force temperature 0 and a fixed seed, and drop the reasoning model.
+    ///
+    /// Routing between models depends on the draft model's free-form output,
+    /// so deterministic runs use the primary model only.
+    pub fn make_deterministic(&mut self) {
+        self.deterministic = true;
+        self.temperature = 0.0;
+        self.seed.get_or_insert(Self::DETERMINISTIC_SEED);
+        self.reasoning_model = None;
+    }
+
+    /// Estimate the cost in USD of the given number of tokens.
+    #[must_use]
+    pub fn estimate_cost(&self, tokens: u64) -> f64 {
+        tokens as f64 / 1000.0 * self.cost_per_1k_tokens
+    }
+
+    /// Cost in USD of the tokens a model reported, priced by `pricing` or
+    /// else by `cost-per-1k-tokens`.
+    #[must_use]
+    pub fn usage_cost(&self, model: &str, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        match self.pricing.get(model) {
+            Some(price) => {
+                (prompt_tokens as f64 * price.prompt_per_1k
+                    + completion_tokens as f64 * price.completion_per_1k)
+                    / 1000.0
+            }
+            None => self.estimate_cost(prompt_tokens + completion_tokens),
         }
     }
 }
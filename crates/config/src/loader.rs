@@ -6,6 +6,7 @@
 use std::path::{Path, PathBuf};
 
 use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use postgres_agent_util::crypto::Secret;
 
 use super::{error::ConfigError, AppConfig, DatabaseProfile, SafetyConfig};
 
@@ -40,6 +41,45 @@ impl ConfigValidator {
             });
         }
 
+        // Validate budget settings
+        if config.llm.max_tokens_per_query == Some(0) {
+            return Err(ConfigError::ValidationError {
+                message: "LLM max_tokens_per_query must be greater than 0".to_string(),
+            });
+        }
+
+        let negative_price =
+            config.llm.pricing.values().any(|p| p.prompt_per_1k < 0.0 || p.completion_per_1k < 0.0);
+        if config.llm.max_cost_per_day.is_some_and(|c| c < 0.0)
+            || config.llm.cost_per_1k_tokens < 0.0
+            || negative_price
+        {
+            return Err(ConfigError::ValidationError {
+                message: "LLM budget costs cannot be negative".to_string(),
+            });
+        }
+
+        if config.llm.response_cache.as_ref().is_some_and(|c| c.capacity == 0) {
+            return Err(ConfigError::ValidationError {
+                message: "LLM response-cache capacity must be greater than 0".to_string(),
+            });
+        }
+
+        if config.llm.provider == "azure-openai" {
+            let Some(azure) = &config.llm.azure else {
+                return Err(ConfigError::ValidationError {
+                    message: "Provider azure-openai requires an [llm.azure] section with a deployment"
+                        .to_string(),
+                });
+            };
+            if azure.deployment.trim().is_empty() || config.llm.base_url.is_none() {
+                return Err(ConfigError::ValidationError {
+                    message: "Provider azure-openai requires llm.base-url and llm.azure.deployment"
+                        .to_string(),
+                });
+            }
+        }
+
         // Validate database profiles
         for profile in &config.databases {
             if profile.name.is_empty() {
@@ -79,6 +119,25 @@ impl ConfigValidator {
     }
 }
 
+/// Resolve a single `env://VAR` secret reference in place.
+fn resolve_env_secret(secret: &mut Option<Secret<String>>, what: &str) {
+    let Some(var) = secret
+        .as_ref()
+        .and_then(|value| value.expose().strip_prefix("env://"))
+        .map(str::to_string)
+    else {
+        return;
+    };
+
+    *secret = match std::env::var(&var) {
+        Ok(value) => Some(value.into()),
+        Err(_) => {
+            tracing::warn!("{} references unset environment variable {}", what, var);
+            None
+        }
+    };
+}
+
 /// Configuration loader with file watching support.
 #[derive(Debug)]
 pub struct ConfigLoader {
@@ -120,7 +179,8 @@ impl ConfigLoader {
         let mut config: AppConfig = toml::from_str(&content)
             .map_err(|e| ConfigError::ParseError { source: e })?;
 
-        // Apply environment variable overrides
+        // Resolve env:// references, then apply environment variable overrides
+        self.resolve_env_references(&mut config);
         self.apply_env_overrides(&mut config);
 
         // Validate configuration
@@ -138,6 +198,17 @@ impl ConfigLoader {
         self.load()
     }
 
+    /// Replace `env://VAR` secret references with the variable's value.
+    ///
+    /// A missing variable leaves the secret unset so the error surfaces where
+    /// the secret is needed.
+    fn resolve_env_references(&self, config: &mut AppConfig) {
+        resolve_env_secret(&mut config.llm.api_key, "API key");
+        if let Some(azure) = &mut config.llm.azure {
+            resolve_env_secret(&mut azure.ad_token, "Azure AD token");
+        }
+    }
+
     /// Apply environment variable overrides to the configuration.
     ///
     /// Supports the following overrides:
@@ -150,7 +221,7 @@ impl ConfigLoader {
     fn apply_env_overrides(&self, config: &mut AppConfig) {
         // LLM overrides
         if let Ok(api_key) = std::env::var("PG_AGENT_LLM_API_KEY") {
-            config.llm.api_key = Some(api_key);
+            config.llm.api_key = Some(api_key.into());
         }
         if let Ok(base_url) = std::env::var("PG_AGENT_LLM_BASE_URL")
             && let Ok(url) = base_url.parse()
@@ -269,6 +340,7 @@ impl Default for ConfigLoader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{AzureOpenAiConfig, ModelPricing, ResponseCacheConfig};
     use tempfile::NamedTempFile;
 
     #[test]
@@ -289,11 +361,33 @@ mod tests {
 
         loader.apply_env_overrides(&mut config);
 
-        assert_eq!(config.llm.api_key, Some("test-key".to_string()));
+        assert_eq!(
+            config.llm.api_key.as_ref().map(|k| k.expose().as_str()),
+            Some("test-key")
+        );
 
         unsafe { std::env::remove_var("PG_AGENT_LLM_API_KEY"); }
     }
 
+    #[test]
+    fn test_resolve_env_api_key() {
+        unsafe { std::env::set_var("PG_AGENT_TEST_OPENAI_KEY", "sk-from-env"); }
+
+        let mut config = AppConfig::default();
+        config.llm.api_key = Some("env://PG_AGENT_TEST_OPENAI_KEY".into());
+        ConfigLoader::new("nonexistent.toml").resolve_env_references(&mut config);
+        assert_eq!(
+            config.llm.api_key.as_ref().map(|k| k.expose().as_str()),
+            Some("sk-from-env")
+        );
+
+        config.llm.api_key = Some("env://PG_AGENT_TEST_UNSET_KEY".into());
+        ConfigLoader::new("nonexistent.toml").resolve_env_references(&mut config);
+        assert!(config.llm.api_key.is_none());
+
+        unsafe { std::env::remove_var("PG_AGENT_TEST_OPENAI_KEY"); }
+    }
+
     #[test]
     fn test_env_override_database_url() {
         unsafe { std::env::set_var("PG_AGENT_DATABASE_URL", "postgresql://localhost/mydb"); }
@@ -357,6 +451,77 @@ max_iterations = 20
         assert!(validator.validate(&config).is_err());
     }
 
+    #[test]
+    fn test_validation_budget() {
+        let validator = ConfigValidator::default();
+
+        let mut config = AppConfig::default();
+        config.llm.max_tokens_per_query = Some(0);
+        assert!(validator.validate(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.llm.max_cost_per_day = Some(-1.0);
+        assert!(validator.validate(&config).is_err());
+
+        let mut config = AppConfig::default();
+        let price = ModelPricing { prompt_per_1k: -0.01, completion_per_1k: 0.03 };
+        config.llm.pricing.insert("gpt-4o".to_string(), price);
+        assert!(validator.validate(&config).is_err());
+
+        let mut config = AppConfig::default();
+        config.llm.max_tokens_per_query = Some(20_000);
+        config.llm.max_cost_per_day = Some(5.0);
+        assert!(validator.validate(&config).is_ok());
+    }
+
+    #[test]
+    fn test_validation_response_cache() {
+        let validator = ConfigValidator::default();
+
+        let mut config = AppConfig {
+            llm: toml::from_str("[response-cache]\nttl-secs = 60").unwrap(),
+            ..AppConfig::default()
+        };
+        let cache = config.llm.response_cache.clone().unwrap();
+        assert_eq!((cache.capacity, cache.ttl_secs, cache.persist), (256, 60, true));
+        assert!(validator.validate(&config).is_ok());
+
+        config.llm.response_cache = Some(ResponseCacheConfig { capacity: 0, ..cache });
+        assert!(validator.validate(&config).is_err());
+    }
+
+    #[test]
+    fn test_usage_cost() {
+        let mut config = AppConfig::default();
+        config.llm.cost_per_1k_tokens = 0.002;
+        let price = ModelPricing { prompt_per_1k: 0.01, completion_per_1k: 0.03 };
+        config.llm.pricing.insert("gpt-4o".to_string(), price);
+
+        let cost = config.llm.usage_cost("gpt-4o", 2000, 500);
+        assert!((cost - 0.035).abs() < 1e-9);
+        let cost = config.llm.usage_cost("gpt-4o-mini", 2000, 500);
+        assert!((cost - 0.005).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_validation_azure_openai() {
+        let validator = ConfigValidator::default();
+
+        let mut config = AppConfig::default();
+        config.llm.provider = "azure-openai".to_string();
+        assert!(validator.validate(&config).is_err());
+
+        config.llm.azure = Some(AzureOpenAiConfig {
+            deployment: "gpt-4o-prod".to_string(),
+            api_version: "2024-06-01".to_string(),
+            ad_token: None,
+        });
+        assert!(validator.validate(&config).is_err());
+
+        config.llm.base_url = Some("https://my-resource.openai.azure.com/".parse().unwrap());
+        assert!(validator.validate(&config).is_ok());
+    }
+
     #[test]
     fn test_validation_empty_profile_name() {
         let mut config = AppConfig::default();
@@ -366,6 +531,14 @@ max_iterations = 20
             display_name: None,
             ssl_mode: "prefer".to_string(),
             connect_timeout: 30,
+            statement_timeout: None,
+            idle_in_transaction_session_timeout: None,
+            work_mem: None,
+            application_name: None,
+            prompt: None,
+            default_schema: None,
+            search_path: Vec::new(),
+            qualify_tables: false,
         });
 
         let validator = ConfigValidator::default();
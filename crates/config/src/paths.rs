@@ -0,0 +1,228 @@
+//! Well-known filesystem locations.
+//!
+//! Locations follow platform conventions through [`ProjectDirs`]: XDG
+//! directories on Linux, `~/Library/Application Support` on macOS and
+//! `%APPDATA%` on Windows.
+
+use std::path::PathBuf;
+
+use directories::{BaseDirs, ProjectDirs};
+use serde::{Deserialize, Serialize};
+
+/// Name of the configuration file.
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Platform directories of the application, if a home directory is known.
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "pg-agent")
+}
+
+/// Directory for configuration files.
+#[must_use]
+pub fn config_dir() -> PathBuf {
+    project_dirs()
+        .map(|dirs| dirs.config_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Directory for persistent application data (stats, history).
+#[must_use]
+pub fn data_dir() -> PathBuf {
+    project_dirs()
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Directory for disposable cached data (schema snapshots, update checks).
+#[must_use]
+pub fn cache_dir() -> PathBuf {
+    project_dirs()
+        .map(|dirs| dirs.cache_dir().to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Directory for state that should persist but is not user data (history).
+///
+/// This is `$XDG_STATE_HOME` on Linux and the data directory elsewhere.
+#[must_use]
+pub fn state_dir() -> PathBuf {
+    project_dirs()
+        .map(|dirs| dirs.state_dir().unwrap_or_else(|| dirs.data_dir()).to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Overrides for default file locations.
+///
+/// Every field is optional; unset fields fall back to the platform defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PathsConfig {
+    /// Directory for persistent data such as usage stats.
+    #[serde(default)]
+    pub data_dir: Option<PathBuf>,
+    /// Directory for cached data.
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    /// Directory for saved sessions.
+    #[serde(default)]
+    pub sessions_dir: Option<PathBuf>,
+    /// REPL history file.
+    #[serde(default)]
+    pub history_file: Option<PathBuf>,
+    /// Audit log file.
+    #[serde(default)]
+    pub audit_log: Option<PathBuf>,
+    /// LLM request debug log, written when `llm.log-requests` is enabled.
+    #[serde(default)]
+    pub llm_log: Option<PathBuf>,
+    /// Directory of requests waiting for admin approval, shared by the
+    /// users who request and approve.
+    #[serde(default)]
+    pub approvals_dir: Option<PathBuf>,
+    /// Directory of table snapshots taken before confirmed mutations.
+    #[serde(default)]
+    pub snapshots_dir: Option<PathBuf>,
+}
+
+impl PathsConfig {
+    /// Effective data directory.
+    #[must_use]
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(data_dir)
+    }
+
+    /// Effective cache directory.
+    #[must_use]
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache_dir.clone().unwrap_or_else(cache_dir)
+    }
+
+    /// Effective sessions directory.
+    #[must_use]
+    pub fn sessions_dir(&self) -> PathBuf {
+        self.sessions_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir().join("sessions"))
+    }
+
+    /// Effective REPL history file.
+    #[must_use]
+    pub fn history_file(&self) -> PathBuf {
+        self.history_file
+            .clone()
+            .unwrap_or_else(|| state_dir().join("history"))
+    }
+
+    /// Effective audit log file.
+    #[must_use]
+    pub fn audit_log(&self) -> PathBuf {
+        self.audit_log
+            .clone()
+            .unwrap_or_else(|| state_dir().join("audit.log"))
+    }
+
+    /// Effective LLM request log file.
+    #[must_use]
+    pub fn llm_log(&self) -> PathBuf {
+        self.llm_log
+            .clone()
+            .unwrap_or_else(|| state_dir().join("llm-requests.log"))
+    }
+
+    /// Effective LLM response cache directory.
+    #[must_use]
+    pub fn llm_cache_dir(&self) -> PathBuf {
+        self.cache_dir().join("llm-responses")
+    }
+
+    /// Effective admin approvals directory.
+    #[must_use]
+    pub fn approvals_dir(&self) -> PathBuf {
+        self.approvals_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir().join("approvals"))
+    }
+
+    /// Effective snapshots directory.
+    #[must_use]
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.snapshots_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir().join("snapshots"))
+    }
+
+    /// Effective stats store file.
+    #[must_use]
+    pub fn stats_file(&self) -> PathBuf {
+        self.data_dir().join("stats.json")
+    }
+
+    /// Effective query history file.
+    #[must_use]
+    pub fn query_history_file(&self) -> PathBuf {
+        self.data_dir().join("query-history.jsonl")
+    }
+
+    /// Effective idempotency ledger file.
+    #[must_use]
+    pub fn idempotency_ledger(&self) -> PathBuf {
+        self.data_dir().join("idempotency.jsonl")
+    }
+}
+
+/// Candidate configuration files, in order of precedence.
+///
+/// The current directory comes first, then the platform configuration
+/// directory, then `~/.config/pg-agent` for Unix-style setups on macOS and
+/// Windows.
+#[must_use]
+pub fn config_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(CONFIG_FILE_NAME), config_dir().join(CONFIG_FILE_NAME)];
+
+    if let Some(base) = BaseDirs::new() {
+        let dotconfig = base.home_dir().join(".config").join("pg-agent").join(CONFIG_FILE_NAME);
+        if !paths.contains(&dotconfig) {
+            paths.push(dotconfig);
+        }
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_search_paths() {
+        let paths = config_search_paths();
+        assert_eq!(paths[0], PathBuf::from(CONFIG_FILE_NAME));
+        assert!(paths[1].ends_with(std::path::Path::new("pg-agent").join(CONFIG_FILE_NAME)));
+        assert!(paths.iter().all(|p| p.ends_with(CONFIG_FILE_NAME)));
+
+        // No duplicates when the platform config dir is ~/.config
+        let mut deduped = paths.clone();
+        deduped.dedup();
+        assert_eq!(deduped, paths);
+    }
+
+    #[test]
+    fn test_paths_config_overrides() {
+        let paths: PathsConfig = toml::from_str(
+            r#"
+data-dir = "/srv/pg-agent"
+history-file = "/tmp/history"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(paths.stats_file(), PathBuf::from("/srv/pg-agent/stats.json"));
+        assert_eq!(
+            paths.query_history_file(),
+            PathBuf::from("/srv/pg-agent/query-history.jsonl")
+        );
+        assert_eq!(paths.sessions_dir(), PathBuf::from("/srv/pg-agent/sessions"));
+        assert_eq!(paths.history_file(), PathBuf::from("/tmp/history"));
+        assert_eq!(paths.cache_dir(), cache_dir());
+    }
+}
@@ -2,8 +2,10 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::error::ConfigError;
+
 /// Safety level.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "kebab-case")]
 pub enum SafetyLevel {
     /// Maximum safety - read-only, no modifications.
@@ -34,6 +36,326 @@ pub struct SafetyConfig {
     /// Maximum query length.
     #[serde(default = "default_max_query_length")]
     pub max_query_length: usize,
+
+    /// Seconds a confirmation request stays open; later answers approve
+    /// nothing.
+    #[serde(default = "default_confirmation_ttl_secs")]
+    pub confirmation_ttl_secs: u64,
+
+    /// How operations needing admin approval are approved.
+    #[serde(default)]
+    pub admin_approval: AdminApprovalMode,
+
+    /// Simulation of mutations in a sandbox database before confirming
+    /// them.
+    #[serde(default)]
+    pub sandbox: Option<SandboxConfig>,
+
+    /// Snapshots of the tables of confirmed mutations, for undoing them.
+    #[serde(default)]
+    pub snapshots: Option<SnapshotConfig>,
+
+    /// Multi-tenant scoping of agent queries.
+    #[serde(default)]
+    pub tenant: Option<TenantConfig>,
+
+    /// Guard against `SELECT *` on wide or large tables.
+    #[serde(default)]
+    pub select_star: SelectStarConfig,
+
+    /// What the LLM sees of query results.
+    #[serde(default)]
+    pub llm_data: LlmDataPolicy,
+
+    /// Columns whose values are shown locally but masked in results sent
+    /// to the LLM, matched by name in the result.
+    #[serde(default)]
+    pub local_only_columns: Vec<String>,
+
+    /// Policy rules, evaluated in order before the rules of the safety
+    /// level; the first matching rule decides.
+    #[serde(default)]
+    pub rules: Vec<PolicyRuleConfig>,
+
+    /// Upper bounds on the run limits given on the command line.
+    #[serde(default)]
+    pub ceilings: RunCeilings,
+}
+
+/// How operations needing admin approval are approved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AdminApprovalMode {
+    /// The user running the agent types `APPROVE`.
+    #[default]
+    Prompt,
+    /// Another user approves with `pg-agent approve` through the shared
+    /// approvals directory while the run waits.
+    SecondChannel,
+}
+
+/// Sandbox settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SandboxConfig {
+    /// Database cloned with `CREATE DATABASE ... TEMPLATE`; it must have no
+    /// open connections. Without one, the referenced tables are copied with
+    /// a sample of their rows.
+    #[serde(default)]
+    pub template: Option<String>,
+
+    /// Rows copied per table when there is no template.
+    #[serde(default = "default_sandbox_sample_rows")]
+    pub sample_rows: usize,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            template: None,
+            sample_rows: default_sandbox_sample_rows(),
+        }
+    }
+}
+
+fn default_sandbox_sample_rows() -> usize {
+    1_000
+}
+
+/// Snapshot settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SnapshotConfig {
+    /// Tables with more rows are not saved, and the mutation cannot be
+    /// undone.
+    #[serde(default = "default_snapshot_max_rows")]
+    pub max_rows: u64,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            max_rows: default_snapshot_max_rows(),
+        }
+    }
+}
+
+fn default_snapshot_max_rows() -> u64 {
+    10_000
+}
+
+/// Decision of a policy rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RuleAction {
+    /// Run the operation.
+    Allow,
+    /// Ask for confirmation first.
+    Confirm,
+    /// Refuse the operation.
+    Deny,
+}
+
+/// A policy rule; conditions left out always hold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PolicyRuleConfig {
+    /// Rule name, shown when it decides.
+    pub name: String,
+
+    /// Decision when every condition holds.
+    pub action: RuleAction,
+
+    /// Operation types, e.g. `update`, `drop`, or the groups `dml`, `ddl`
+    /// and `write`.
+    #[serde(default)]
+    pub operations: Vec<String>,
+
+    /// Tables (`schema.table` or `table`) of which one must be referenced.
+    #[serde(default)]
+    pub tables: Vec<String>,
+
+    /// Minimum planner row estimate of the operation.
+    #[serde(default)]
+    pub min_rows: Option<i64>,
+
+    /// Daily window of local time, e.g. `09:00-17:00` or `22:00-06:00`.
+    #[serde(default)]
+    pub hours: Option<String>,
+
+    /// Days of the week, e.g. `["sat", "sun"]`.
+    #[serde(default)]
+    pub days: Vec<String>,
+
+    /// Database roles the rule applies to.
+    #[serde(default)]
+    pub roles: Vec<String>,
+
+    /// Reason given when the rule denies or asks for confirmation;
+    /// `{operation}` is replaced with the operation type.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// What the LLM sees of tool results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum LlmDataPolicy {
+    /// Results as returned, row values included.
+    #[default]
+    Full,
+    /// Schema metadata and result shapes only: columns, row counts and
+    /// per-column null and distinct counts, never row values.
+    MetadataOnly,
+}
+
+/// How `SELECT *` on a wide or large table is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelectStarAction {
+    /// Run the query with a warning.
+    #[default]
+    Warn,
+    /// Refuse the query and ask for specific columns.
+    Reject,
+    /// No check.
+    Off,
+}
+
+/// `SELECT *` guard settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SelectStarConfig {
+    /// Tables with more columns are flagged.
+    #[serde(default = "default_select_star_max_columns")]
+    pub max_columns: usize,
+
+    /// Tables with more estimated rows are flagged.
+    #[serde(default = "default_select_star_max_rows")]
+    pub max_rows: i64,
+
+    /// Handling of flagged queries.
+    #[serde(default)]
+    pub action: SelectStarAction,
+}
+
+impl Default for SelectStarConfig {
+    fn default() -> Self {
+        Self {
+            max_columns: default_select_star_max_columns(),
+            max_rows: default_select_star_max_rows(),
+            action: SelectStarAction::default(),
+        }
+    }
+}
+
+fn default_select_star_max_columns() -> usize {
+    20
+}
+
+fn default_select_star_max_rows() -> i64 {
+    100_000
+}
+
+/// How queries missing the tenant filter are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TenantMode {
+    /// Refuse to run the query.
+    #[default]
+    Reject,
+    /// Add the tenant filter to the query.
+    Augment,
+}
+
+/// Multi-tenant scoping settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TenantConfig {
+    /// Tenant column, e.g. `org_id`.
+    pub column: String,
+
+    /// Scoped tables (`schema.table` or `table`); when empty, every table
+    /// with the tenant column.
+    #[serde(default)]
+    pub tables: Vec<String>,
+
+    /// Handling of queries missing the `column = value` filter.
+    #[serde(default)]
+    pub mode: TenantMode,
+
+    /// Default tenant; `--tenant` overrides it.
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// Hard ceilings on the per-run limits that `--timeout`, `--max-iterations`
+/// and `--max-rows` may set; the configured limits themselves are not
+/// checked against them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RunCeilings {
+    /// Most seconds a run may be given.
+    #[serde(default = "default_ceiling_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Most reasoning iterations a run may be given.
+    #[serde(default = "default_ceiling_iterations")]
+    pub max_iterations: u32,
+
+    /// Most result rows a run may return.
+    #[serde(default = "default_ceiling_rows")]
+    pub max_rows: usize,
+}
+
+impl Default for RunCeilings {
+    fn default() -> Self {
+        Self {
+            timeout_secs: default_ceiling_timeout_secs(),
+            max_iterations: default_ceiling_iterations(),
+            max_rows: default_ceiling_rows(),
+        }
+    }
+}
+
+impl RunCeilings {
+    /// Check a per-run limit given as `flag` against its ceiling.
+    ///
+    /// # Errors
+    ///
+    /// `ConfigError::ValidationError` if the limit is 0 or above the
+    /// ceiling.
+    pub fn check<T>(flag: &str, value: T, ceiling: T) -> Result<T, ConfigError>
+    where
+        T: Copy + PartialOrd + Default + std::fmt::Display,
+    {
+        if value == T::default() {
+            return Err(ConfigError::ValidationError {
+                message: format!("{} must be greater than 0", flag),
+            });
+        }
+        if value > ceiling {
+            return Err(ConfigError::ValidationError {
+                message: format!(
+                    "{} {} is above the ceiling of {} set in [safety.ceilings]",
+                    flag, value, ceiling
+                ),
+            });
+        }
+        Ok(value)
+    }
+}
+
+fn default_ceiling_timeout_secs() -> u64 {
+    1_800
+}
+
+fn default_ceiling_iterations() -> u32 {
+    50
+}
+
+fn default_ceiling_rows() -> usize {
+    100_000
 }
 
 fn default_require_confirmation() -> bool {
@@ -48,6 +370,10 @@ fn default_max_query_length() -> usize {
     10_000
 }
 
+fn default_confirmation_ttl_secs() -> u64 {
+    300
+}
+
 impl Default for SafetyConfig {
     fn default() -> Self {
         Self {
@@ -55,6 +381,34 @@ impl Default for SafetyConfig {
             require_confirmation: default_require_confirmation(),
             show_sql_preview: default_show_sql_preview(),
             max_query_length: default_max_query_length(),
+            confirmation_ttl_secs: default_confirmation_ttl_secs(),
+            admin_approval: AdminApprovalMode::default(),
+            sandbox: None,
+            snapshots: None,
+            tenant: None,
+            select_star: SelectStarConfig::default(),
+            llm_data: LlmDataPolicy::default(),
+            local_only_columns: Vec::new(),
+            rules: Vec::new(),
+            ceilings: RunCeilings::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_ceilings() {
+        let ceilings = RunCeilings::default();
+        assert_eq!(RunCeilings::check("--max-iterations", 20, ceilings.max_iterations).unwrap(), 20);
+        assert!(RunCeilings::check("--max-iterations", 0, ceilings.max_iterations).is_err());
+
+        let error = RunCeilings::check("--timeout", 3_600, ceilings.timeout_secs).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Validation error: --timeout 3600 is above the ceiling of 1800 set in [safety.ceilings]"
+        );
+    }
+}
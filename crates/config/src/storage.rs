@@ -0,0 +1,133 @@
+//! Compression, encryption and cleanup of persisted files.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use postgres_agent_util::compress::Compression;
+use postgres_agent_util::encrypt::KeySource;
+use postgres_agent_util::retention::RetentionPolicy;
+use serde::{Deserialize, Serialize};
+
+/// Settings for saved sessions, the query history and audit logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StorageConfig {
+    /// Whether session snapshots, query history entries and rotated audit
+    /// logs are compressed with zstd. Files written before stay readable.
+    #[serde(default)]
+    pub compress: bool,
+
+    /// zstd compression level, 1 (fastest) to 19.
+    #[serde(default = "default_compression_level")]
+    pub compression_level: i32,
+
+    /// Whether session snapshots, query history entries and the LLM
+    /// request log are encrypted with AES-256-GCM.
+    #[serde(default)]
+    pub encrypt: bool,
+
+    /// Where the encryption key is kept: `keyring` for the OS keyring, or
+    /// the path of a key file. A missing key is generated on first use.
+    #[serde(default = "default_encryption_key")]
+    pub encryption_key: String,
+
+    /// Size in megabytes past which the audit log is rotated; 0 never
+    /// rotates it.
+    #[serde(default)]
+    pub audit_rotate_mb: u64,
+
+    /// Days after which sessions, history entries and rotated audit logs
+    /// are removed; 0 keeps them.
+    #[serde(default)]
+    pub max_age_days: u64,
+
+    /// Megabytes that sessions, the query history and rotated audit logs
+    /// may each take, removing the oldest first; 0 means no limit.
+    #[serde(default)]
+    pub max_size_mb: u64,
+}
+
+fn default_compression_level() -> i32 {
+    3
+}
+
+fn default_encryption_key() -> String {
+    "keyring".to_string()
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            compress: false,
+            compression_level: default_compression_level(),
+            encrypt: false,
+            encryption_key: default_encryption_key(),
+            audit_rotate_mb: 0,
+            max_age_days: 0,
+            max_size_mb: 0,
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Compression of newly written files.
+    #[must_use]
+    pub fn compression(&self) -> Compression {
+        if self.compress {
+            Compression::Zstd {
+                level: self.compression_level.clamp(1, 19),
+            }
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Where the encryption key is kept.
+    #[must_use]
+    pub fn key_source(&self) -> KeySource {
+        match self.encryption_key.trim() {
+            "keyring" => KeySource::Keyring,
+            path => KeySource::File(PathBuf::from(path)),
+        }
+    }
+
+    /// Cleanup policy for each kind of persisted data.
+    #[must_use]
+    pub fn retention(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_age: (self.max_age_days > 0)
+                .then(|| Duration::from_secs(self.max_age_days * 86_400)),
+            max_bytes: (self.max_size_mb > 0).then(|| self.max_size_mb * 1024 * 1024),
+        }
+    }
+
+    /// Size in bytes past which the audit log is rotated; 0 never rotates.
+    #[must_use]
+    pub fn audit_rotate_bytes(&self) -> u64 {
+        self.audit_rotate_mb.saturating_mul(1024 * 1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_config() {
+        let storage: StorageConfig = toml::from_str(
+            r#"
+compress = true
+max-age-days = 30
+"#,
+        )
+        .unwrap();
+        assert_eq!(storage.compression(), Compression::Zstd { level: 3 });
+        assert_eq!(storage.retention().max_age, Some(Duration::from_secs(30 * 86_400)));
+        assert_eq!(storage.retention().max_bytes, None);
+
+        let default = StorageConfig::default();
+        assert_eq!(default.compression(), Compression::None);
+        assert_eq!(default.key_source(), KeySource::Keyring);
+        assert!(default.retention().is_unlimited());
+    }
+}
@@ -1,16 +1,38 @@
 //! Agent core implementation.
 
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 pub use postgres_agent_llm::client::LlmClient;
 pub use postgres_agent_llm::error::LlmError;
+pub use postgres_agent_safety::SafetyContext;
 pub use postgres_agent_tools::registry::ToolRegistry;
 pub use postgres_agent_tools::{ToolContext, ToolError};
 
-use crate::context::{AgentContext, Message};
+use postgres_agent_config::{LlmConfig, LlmDataPolicy, TokenizerFallback};
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_llm::{DecisionChunk, TokenCounter, TokenUsage};
+use postgres_agent_safety::{ConfirmationLevel, ConfirmationStatus};
+use postgres_agent_tools::Approval;
+
+use crate::context::{AgentContext, Message, MessageRole};
+use crate::examples::{examples_prompt, Example, ExampleRetriever};
 use crate::decision::{AgentDecision, ToolCall, ToolResult};
 use crate::error::AgentError;
+use crate::events::{AgentEvent, EventSender};
+use crate::intent;
+use crate::paused::PausedRun;
+use crate::privacy;
+use crate::stats::ToolUsage;
+
+/// Messages kept verbatim when older context is summarized.
+const KEEP_RECENT_MESSAGES: usize = 4;
 
 /// Configuration for agent behavior.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,9 +50,37 @@ pub struct AgentConfig {
     /// Timeout for each iteration in seconds.
     #[serde(default = "default_timeout")]
     pub timeout_seconds: u64,
+    /// Timeout for a whole run in seconds.
+    #[serde(default = "default_total_timeout")]
+    pub total_timeout_seconds: u64,
     /// Whether to enable verbose reasoning output.
     #[serde(default)]
     pub verbose_reasoning: bool,
+    /// Maximum estimated tokens a single run may consume.
+    #[serde(default)]
+    pub max_tokens_per_query: Option<u64>,
+    /// Disable sampling-dependent features such as context compaction.
+    #[serde(default)]
+    pub deterministic: bool,
+    /// Tokenizer for models without a known encoding.
+    #[serde(default)]
+    pub tokenizer_fallback: TokenizerFallback,
+    /// Maximum query result rows kept in a response; 0 keeps none.
+    #[serde(default = "default_max_result_rows")]
+    pub max_result_rows: usize,
+    /// Whether reasoning steps are written to the audit log.
+    #[serde(default = "default_audit_reasoning")]
+    pub audit_reasoning: bool,
+    /// What the LLM sees of tool results.
+    #[serde(default)]
+    pub llm_data: LlmDataPolicy,
+    /// Columns whose values are masked in tool results sent to the LLM.
+    #[serde(default)]
+    pub local_only_columns: Vec<String>,
+    /// Answer trivial schema requests such as "list tables" with a direct
+    /// tool call instead of asking the LLM.
+    #[serde(default = "default_fast_path")]
+    pub fast_path: bool,
 }
 
 fn default_max_iterations() -> u32 {
@@ -41,6 +91,22 @@ fn default_timeout() -> u64 {
     30
 }
 
+fn default_total_timeout() -> u64 {
+    120
+}
+
+fn default_max_result_rows() -> usize {
+    500
+}
+
+fn default_audit_reasoning() -> bool {
+    true
+}
+
+fn default_fast_path() -> bool {
+    true
+}
+
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
@@ -48,7 +114,16 @@ impl Default for AgentConfig {
             require_confirmation: true,
             safety_level: SafetyLevel::Balanced,
             timeout_seconds: 30,
+            total_timeout_seconds: 120,
             verbose_reasoning: false,
+            max_tokens_per_query: None,
+            deterministic: false,
+            tokenizer_fallback: TokenizerFallback::default(),
+            max_result_rows: default_max_result_rows(),
+            audit_reasoning: default_audit_reasoning(),
+            llm_data: LlmDataPolicy::default(),
+            local_only_columns: Vec::new(),
+            fast_path: default_fast_path(),
         }
     }
 }
@@ -89,13 +164,20 @@ impl AgentConfigBuilder {
         self
     }
 
-    /// Set timeout in seconds.
+    /// Set the per-iteration timeout in seconds.
     #[must_use]
     pub fn timeout_seconds(mut self, seconds: u64) -> Self {
         self.config.timeout_seconds = seconds;
         self
     }
 
+    /// Set the timeout for a whole run in seconds.
+    #[must_use]
+    pub fn total_timeout_seconds(mut self, seconds: u64) -> Self {
+        self.config.total_timeout_seconds = seconds;
+        self
+    }
+
     /// Enable verbose reasoning.
     #[must_use]
     pub fn verbose_reasoning(mut self, verbose: bool) -> Self {
@@ -103,6 +185,63 @@ impl AgentConfigBuilder {
         self
     }
 
+    /// Set the per-query token budget.
+    #[must_use]
+    pub fn max_tokens_per_query(mut self, tokens: u64) -> Self {
+        self.config.max_tokens_per_query = Some(tokens);
+        self
+    }
+
+    /// Enable deterministic mode.
+    #[must_use]
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.config.deterministic = deterministic;
+        self
+    }
+
+    /// Set the tokenizer for models without a known encoding.
+    #[must_use]
+    pub fn tokenizer_fallback(mut self, fallback: TokenizerFallback) -> Self {
+        self.config.tokenizer_fallback = fallback;
+        self
+    }
+
+    /// Set the maximum query result rows kept in a response.
+    #[must_use]
+    pub fn max_result_rows(mut self, rows: usize) -> Self {
+        self.config.max_result_rows = rows;
+        self
+    }
+
+    /// Set whether reasoning steps are written to the audit log.
+    #[must_use]
+    pub fn audit_reasoning(mut self, audit: bool) -> Self {
+        self.config.audit_reasoning = audit;
+        self
+    }
+
+    /// Set what the LLM sees of tool results.
+    #[must_use]
+    pub fn llm_data(mut self, policy: LlmDataPolicy) -> Self {
+        self.config.llm_data = policy;
+        self
+    }
+
+    /// Set the columns whose values are masked in tool results sent to
+    /// the LLM.
+    #[must_use]
+    pub fn local_only_columns(mut self, columns: Vec<String>) -> Self {
+        self.config.local_only_columns = columns;
+        self
+    }
+
+    /// Set whether trivial schema requests skip the LLM.
+    #[must_use]
+    pub fn fast_path(mut self, enabled: bool) -> Self {
+        self.config.fast_path = enabled;
+        self
+    }
+
     /// Build the config.
     #[must_use]
     pub fn build(self) -> AgentConfig {
@@ -111,27 +250,23 @@ impl AgentConfigBuilder {
 }
 
 /// Safety levels controlling agent behavior.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum SafetyLevel {
     /// Maximum safety - read-only, no modifications.
     ReadOnly,
     /// Balanced safety - confirmations for DML/DDL.
+    #[default]
     Balanced,
     /// Permissive - faster execution with minimal checks.
     Permissive,
 }
 
-impl Default for SafetyLevel {
-    fn default() -> Self {
-        SafetyLevel::Balanced
-    }
-}
-
 /// State of the agent during execution.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum AgentState {
     /// Agent is idle, waiting for input.
+    #[default]
     Idle,
     /// Agent is thinking/reasoning.
     Thinking,
@@ -145,9 +280,100 @@ pub enum AgentState {
     Error(String),
 }
 
-impl Default for AgentState {
-    fn default() -> Self {
-        AgentState::Idle
+/// Record of a single reasoning turn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnRecord {
+    /// Iteration number, starting at 1.
+    pub iteration: u32,
+    /// Model that produced the decision.
+    pub model: String,
+    /// Decision kind (`reasoning`, `tool_call:<name>`, `final_answer`).
+    pub decision: String,
+    /// Whether the turn was re-run on the primary model after the
+    /// reasoning model proposed SQL or a final answer.
+    pub escalated: bool,
+}
+
+/// A SQL statement executed by a tool during a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutedSql {
+    /// Statement as run, after any rewriting by the tool.
+    pub sql: String,
+    /// Tool that ran it.
+    pub tool: String,
+    /// Rows returned, if the tool reported them.
+    #[serde(default)]
+    pub row_count: Option<u64>,
+    /// Tool execution time in milliseconds.
+    pub duration_ms: u64,
+    /// Whether the statement succeeded.
+    pub success: bool,
+}
+
+impl ExecutedSql {
+    /// Statement run by a tool call, if the call ran one.
+    fn from_tool_result(call: &ToolCall, result: &ToolResult) -> Option<Self> {
+        // Tools report the statement when they rewrote it, e.g. tenant scoping
+        let sql = result
+            .result
+            .get("sql")
+            .or_else(|| call.arguments.get("sql"))
+            .and_then(Value::as_str)?;
+        Some(Self {
+            sql: sql.to_string(),
+            tool: result.tool.clone(),
+            row_count: result.result.get("rowCount").and_then(Value::as_u64),
+            duration_ms: result.duration_ms,
+            success: result.success,
+        })
+    }
+}
+
+impl std::fmt::Display for ExecutedSql {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (", self.sql)?;
+        if !self.success {
+            write!(f, "failed, ")?;
+        } else if let Some(rows) = self.row_count {
+            write!(f, "{} rows, ", rows)?;
+        }
+        write!(f, "{}ms)", self.duration_ms)
+    }
+}
+
+/// A query result of a run, labeled with the tool and statement that
+/// returned it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultSet {
+    /// Tool that returned it.
+    #[serde(default)]
+    pub tool: String,
+    /// Statement that produced it, if the tool ran one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+    /// The result itself.
+    #[serde(flatten)]
+    pub result: QueryResult,
+}
+
+impl ResultSet {
+    /// Longest label shown for a statement, in characters.
+    const MAX_LABEL_CHARS: usize = 80;
+
+    /// Label it: the statement on one line, shortened, or else the tool.
+    #[must_use]
+    pub fn label(&self) -> String {
+        let Some(sql) = self.sql.as_deref() else {
+            return self.tool.clone();
+        };
+        let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+        if sql.chars().count() <= Self::MAX_LABEL_CHARS {
+            return sql;
+        }
+        let shortened: String = sql.chars().take(Self::MAX_LABEL_CHARS - 3).collect();
+        format!("{}...", shortened.trim_end())
     }
 }
 
@@ -156,8 +382,11 @@ impl Default for AgentState {
 pub struct AgentResponse {
     /// The final answer to the user.
     pub answer: String,
-    /// SQL that was executed (if any).
-    pub executed_sql: Option<String>,
+    /// SQL statements executed during the run, in order.
+    pub executed_sql: Vec<ExecutedSql>,
+    /// Query results of the run, in order, capped at `max_result_rows` rows
+    /// in total.
+    pub results: Vec<ResultSet>,
     /// Number of iterations taken.
     pub iterations: u32,
     /// Whether the query was successful.
@@ -166,6 +395,12 @@ pub struct AgentResponse {
     pub error: Option<String>,
     /// Final agent state.
     pub state: AgentState,
+    /// Request ID used to correlate logs for this run.
+    pub request_id: Option<String>,
+    /// Per-turn trace, including the model used for each turn.
+    pub trace: Vec<TurnRecord>,
+    /// Tokens the LLM providers reported for the run.
+    pub usage: TokenUsage,
 }
 
 impl AgentResponse {
@@ -174,11 +409,15 @@ impl AgentResponse {
     pub fn success(answer: String, iterations: u32) -> Self {
         Self {
             answer,
-            executed_sql: None,
+            executed_sql: Vec::new(),
+            results: Vec::new(),
             iterations,
             success: true,
             error: None,
             state: AgentState::Completed,
+            request_id: None,
+            trace: Vec::new(),
+            usage: TokenUsage::default(),
         }
     }
 
@@ -188,30 +427,44 @@ impl AgentResponse {
         let error_msg = message.clone();
         Self {
             answer: String::new(),
-            executed_sql: None,
+            executed_sql: Vec::new(),
+            results: Vec::new(),
             iterations,
             success: false,
             error: Some(message),
             state: AgentState::Error(error_msg),
+            request_id: None,
+            trace: Vec::new(),
+            usage: TokenUsage::default(),
         }
     }
 
     /// Create a response with executed SQL.
     #[must_use]
-    pub fn with_sql(answer: String, sql: String, iterations: u32) -> Self {
+    pub fn with_sql(answer: String, executed_sql: Vec<ExecutedSql>, iterations: u32) -> Self {
         Self {
             answer,
-            executed_sql: Some(sql),
+            executed_sql,
+            results: Vec::new(),
             iterations,
             success: true,
             error: None,
             state: AgentState::Completed,
+            request_id: None,
+            trace: Vec::new(),
+            usage: TokenUsage::default(),
         }
     }
+
+    /// Last statement that ran successfully.
+    #[must_use]
+    pub fn last_sql(&self) -> Option<&str> {
+        self.executed_sql.iter().rev().find(|s| s.success).map(|s| s.sql.as_str())
+    }
 }
 
 /// Statistics about agent execution.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct AgentStats {
     /// Total iterations.
     pub iterations: u32,
@@ -219,19 +472,142 @@ pub struct AgentStats {
     pub tool_calls: u32,
     /// Total reasoning tokens.
     pub reasoning_tokens: u32,
+    /// Tokens sent to and received from the LLM, counted with the model's
+    /// tokenizer.
+    pub estimated_tokens: u64,
+    /// Tokens in the most recent prompt.
+    pub context_tokens: u64,
     /// Execution duration in milliseconds.
     pub duration_ms: u64,
+    /// Calls, errors and latencies per tool.
+    pub tools: BTreeMap<String, ToolUsage>,
+    /// Time spent in each iteration.
+    #[serde(default)]
+    pub timings: Vec<IterationTiming>,
+    /// Tokens reported by the LLM providers, per call.
+    #[serde(default)]
+    pub usage: Vec<LlmUsage>,
+}
+
+impl AgentStats {
+    /// Total milliseconds spent waiting for the LLM.
+    #[must_use]
+    pub fn llm_ms(&self) -> u64 {
+        self.timings.iter().map(|t| t.llm_ms).sum()
+    }
+
+    /// Total milliseconds spent running tools.
+    #[must_use]
+    pub fn tool_ms(&self) -> u64 {
+        self.timings.iter().filter_map(|t| t.tool_ms).sum()
+    }
+
+    /// Tokens reported by the LLM providers in total.
+    #[must_use]
+    pub fn token_usage(&self) -> TokenUsage {
+        let mut total = TokenUsage::default();
+        for usage in &self.usage {
+            total += usage.tokens();
+        }
+        total
+    }
+
+    /// Estimated cost in USD: reported tokens priced per model, or the
+    /// estimated tokens when no provider reported any.
+    #[must_use]
+    pub fn cost(&self, llm: &LlmConfig) -> f64 {
+        if self.usage.is_empty() {
+            return llm.estimate_cost(self.estimated_tokens);
+        }
+        self.usage
+            .iter()
+            .map(|u| llm.usage_cost(&u.model, u.prompt_tokens, u.completion_tokens))
+            .sum()
+    }
+
+    /// Tokens used: as reported by the providers, or as estimated when no
+    /// provider reported any.
+    #[must_use]
+    pub fn tokens(&self) -> u64 {
+        if self.usage.is_empty() { self.estimated_tokens } else { self.token_usage().total() }
+    }
+
+    /// Record the tool run in an iteration.
+    fn record_tool_time(&mut self, iteration: u32, tool: &str, ms: u64) {
+        // A resumed call runs without asking the LLM again
+        if self.timings.last().is_none_or(|t| t.iteration != iteration) {
+            self.timings.push(IterationTiming {
+                iteration,
+                ..IterationTiming::default()
+            });
+        }
+        if let Some(timing) = self.timings.last_mut() {
+            timing.tool = Some(tool.to_string());
+            timing.tool_ms = Some(timing.tool_ms.unwrap_or(0) + ms);
+        }
+    }
+}
+
+/// Where the time of one iteration went.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IterationTiming {
+    /// Iteration number, starting at 1.
+    pub iteration: u32,
+    /// Milliseconds waiting for the LLM decision.
+    pub llm_ms: u64,
+    /// Tool the iteration ran, if any.
+    #[serde(default)]
+    pub tool: Option<String>,
+    /// Milliseconds running the tool.
+    #[serde(default)]
+    pub tool_ms: Option<u64>,
+}
+
+/// Tokens reported for one LLM call.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmUsage {
+    /// Iteration of the call, starting at 1.
+    pub iteration: u32,
+    /// Model called.
+    pub model: String,
+    /// Tokens of the prompt.
+    pub prompt_tokens: u64,
+    /// Tokens generated.
+    pub completion_tokens: u64,
+}
+
+impl LlmUsage {
+    /// Usage of a call, taken from its decision, if the provider reported any.
+    fn take(iteration: u32, model: &str, decision: &mut Value) -> Option<Self> {
+        let tokens = TokenUsage::take(decision)?;
+        Some(Self {
+            iteration,
+            model: model.to_string(),
+            prompt_tokens: tokens.prompt_tokens,
+            completion_tokens: tokens.completion_tokens,
+        })
+    }
+
+    /// Prompt and completion tokens.
+    #[must_use]
+    pub fn tokens(&self) -> TokenUsage {
+        TokenUsage::new(self.prompt_tokens, self.completion_tokens)
+    }
 }
 
 /// The core agent that implements the ReAct reasoning loop.
 #[derive(Debug)]
 pub struct PostgresAgent<Client: LlmClient> {
     /// LLM client for generating decisions.
-    llm_client: Box<Client>,
+    llm_client: Arc<Client>,
+    /// Optional cheaper client for intermediate reasoning and tool selection.
+    reasoning_client: Option<Arc<Client>>,
     /// Context manager for conversation state.
     pub context: AgentContext,
     /// Tool registry for executing tools.
-    tools: ToolRegistry,
+    tools: Arc<ToolRegistry>,
     /// Agent configuration.
     pub config: AgentConfig,
     /// Current execution state.
@@ -240,48 +616,59 @@ pub struct PostgresAgent<Client: LlmClient> {
     stats: AgentStats,
     /// Tool execution context.
     tool_context: ToolContext,
+    /// Request ID of the current or last run.
+    request_id: Option<String>,
+    /// Retriever of similar past questions, shown as examples.
+    examples: Option<ExampleRetriever>,
+    /// Examples prompt for the current run.
+    examples_prompt: Option<String>,
+    /// State of the last run, if it paused for confirmation.
+    paused: Option<PausedRun>,
+    /// Subscriber to run progress.
+    events: Option<EventSender>,
 }
 
 impl<Client: LlmClient> PostgresAgent<Client> {
     /// Create a new agent with default configuration.
     #[must_use]
     pub fn new(llm_client: Box<Client>) -> Self {
-        Self {
-            llm_client,
-            context: AgentContext::new(),
-            tools: ToolRegistry::default(),
-            config: AgentConfig::default(),
-            state: AgentState::Idle,
-            stats: AgentStats::default(),
-            tool_context: ToolContext::default(),
-        }
+        Self::with_shared(Arc::from(llm_client), Arc::default(), AgentConfig::default())
     }
 
     /// Create a new agent with custom configuration.
     #[must_use]
     pub fn with_config(llm_client: Box<Client>, config: AgentConfig) -> Self {
-        Self {
-            llm_client,
-            context: AgentContext::new(),
-            tools: ToolRegistry::default(),
-            config,
-            state: AgentState::Idle,
-            stats: AgentStats::default(),
-            tool_context: ToolContext::default(),
-        }
+        Self::with_shared(Arc::from(llm_client), Arc::default(), config)
     }
 
     /// Create a new agent with tools registry.
     #[must_use]
     pub fn with_tools(llm_client: Box<Client>, tools: ToolRegistry) -> Self {
+        Self::with_shared(Arc::from(llm_client), Arc::new(tools), AgentConfig::default())
+    }
+
+    /// Create an agent on an LLM client and tool registry that other agents
+    /// may share; conversation context and run state are its own.
+    #[must_use]
+    pub fn with_shared(
+        llm_client: Arc<Client>,
+        tools: Arc<ToolRegistry>,
+        config: AgentConfig,
+    ) -> Self {
         Self {
             llm_client,
+            reasoning_client: None,
             context: AgentContext::new(),
             tools,
-            config: AgentConfig::default(),
+            config,
             state: AgentState::Idle,
             stats: AgentStats::default(),
             tool_context: ToolContext::default(),
+            request_id: None,
+            examples: None,
+            examples_prompt: None,
+            paused: None,
+            events: None,
         }
     }
 
@@ -303,9 +690,38 @@ impl<Client: LlmClient> PostgresAgent<Client> {
         &self.tools
     }
 
-    /// Get mutable reference to the tool registry.
-    pub fn tools_mut(&mut self) -> &mut ToolRegistry {
-        &mut self.tools
+    /// Get mutable reference to the tool registry; `None` while it is
+    /// shared with other agents.
+    pub fn tools_mut(&mut self) -> Option<&mut ToolRegistry> {
+        Arc::get_mut(&mut self.tools)
+    }
+
+    /// Get the request ID of the current or last run.
+    #[must_use]
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// Build a safety context for the current run.
+    #[must_use]
+    pub fn safety_context(&self) -> SafetyContext {
+        let level = match self.config.safety_level {
+            SafetyLevel::ReadOnly => postgres_agent_safety::SafetyLevel::ReadOnly,
+            SafetyLevel::Balanced => postgres_agent_safety::SafetyLevel::Balanced,
+            SafetyLevel::Permissive => postgres_agent_safety::SafetyLevel::Permissive,
+        };
+        SafetyContext {
+            level,
+            read_only: self.config.safety_level == SafetyLevel::ReadOnly,
+            user_id: None,
+            request_id: self.request_id.clone(),
+            server_version: None,
+            rls_tables: Vec::new(),
+            tenant: None,
+            role: None,
+            estimated_rows: None,
+            now: None,
+        }
     }
 
     /// Set the tool context for tool executions.
@@ -313,23 +729,235 @@ impl<Client: LlmClient> PostgresAgent<Client> {
         self.tool_context = context;
     }
 
+    /// Tool context of tool executions.
+    #[must_use]
+    pub fn tool_context(&self) -> &ToolContext {
+        &self.tool_context
+    }
+
+    /// Send run progress, such as reasoning steps, to a subscriber.
+    pub fn set_event_sender(&mut self, sender: EventSender) {
+        self.events = Some(sender);
+    }
+
+    /// Send an event to the subscriber, if any is still listening.
+    fn emit(&self, event: AgentEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Show similar past questions and their SQL as examples in each run.
+    ///
+    /// Successful runs are added as examples for later ones.
+    pub fn set_example_retriever(&mut self, retriever: ExampleRetriever) {
+        self.examples = Some(retriever);
+    }
+
     /// Run the agent on a user query.
     ///
+    /// Cancelling `cancel` stops the run between iterations, abandons an
+    /// in-flight LLM request and cancels running queries on the server.
+    ///
     /// # Errors
     ///
-    /// Returns an error if the LLM call fails or tool execution fails.
-    pub async fn run(&mut self, query: &str) -> Result<AgentResponse, AgentError> {
+    /// Returns an error if the LLM call fails or tool execution fails, and
+    /// `AgentError::Cancelled` if the run is cancelled.
+    pub async fn run(
+        &mut self,
+        query: &str,
+        cancel: &CancellationToken,
+    ) -> Result<AgentResponse, AgentError> {
         self.state = AgentState::Thinking;
         self.stats = AgentStats::default();
+        self.paused = None;
+        let started = std::time::Instant::now();
+
+        // Every run gets its own request ID, shared with tools and logs
+        let request_id = uuid::Uuid::new_v4().to_string();
+        self.request_id = Some(request_id.clone());
+        self.tool_context.request_id = Some(request_id.clone());
+        self.tool_context.cancel = cancel.clone();
+        self.context.set_token_counter(self.token_counter());
+
+        match self.fast_path(query).await {
+            Ok(Some(response)) => return self.finish(query, started, Ok(response)),
+            Ok(None) => {}
+            Err(e) => return self.finish(query, started, Err(e)),
+        }
+
+        self.compact_context().await;
+        self.examples_prompt = self.retrieve_examples(query).await;
 
         // Add user message to context
         self.context.add_user_message(query);
 
         // ReAct loop
-        let result = self.react_loop(query).await;
+        let span = tracing::info_span!("agent_run", request_id = %request_id);
+        let result = self
+            .react_loop(query, cancel, RunProgress::default(), None)
+            .instrument(span)
+            .await;
+        self.finish(query, started, result)
+    }
+
+    /// Answer a trivial request with one tool call, without the LLM.
+    ///
+    /// Returns `None` when the request is not trivial, the tool is not
+    /// registered, or the tool result does not make a complete answer; the
+    /// run then goes to the model as usual.
+    async fn fast_path(&mut self, query: &str) -> Result<Option<AgentResponse>, AgentError> {
+        if !self.config.fast_path {
+            return Ok(None);
+        }
+        let Some(intent) = intent::classify(query) else {
+            return Ok(None);
+        };
+        let (name, arguments) = intent.tool_call();
+        if !self.tools.contains(name) {
+            return Ok(None);
+        }
+        let call = ToolCall::new(name, arguments, "fast-path").map_err(|e| {
+            AgentError::SerializationError {
+                message: e.to_string(),
+            }
+        })?;
+
+        self.state = AgentState::ExecutingTool;
+        self.emit(AgentEvent::ToolCall {
+            iteration: 1,
+            name: call.name.clone(),
+        });
+        let result = match self.execute_tool(&call).await {
+            Ok(result) if result.success => result,
+            Err(AgentError::Cancelled) => return Err(AgentError::Cancelled),
+            Ok(_) | Err(_) => {
+                tracing::debug!("Fast path for {:?} failed, asking the model", intent);
+                return Ok(None);
+            }
+        };
+        let Some(answer) = intent.answer(&result.result) else {
+            return Ok(None);
+        };
+        tracing::debug!("Answered {:?} without the model", intent);
+
+        self.stats.iterations += 1;
+        self.stats.tool_calls += 1;
+        self.stats.record_tool_time(1, &call.name, result.duration_ms);
+        self.context.add_user_message(query);
+        self.add_tool_message(&call.name, &result.result);
+        self.context.add_assistant_message(&answer);
+
+        Ok(Some(AgentResponse {
+            answer,
+            executed_sql: ExecutedSql::from_tool_result(&call, &result).into_iter().collect(),
+            results: Vec::new(),
+            iterations: 1,
+            success: true,
+            error: None,
+            state: AgentState::Completed,
+            request_id: self.request_id.clone(),
+            trace: vec![TurnRecord {
+                iteration: 1,
+                model: "fast-path".to_string(),
+                decision: format!("tool_call:{}", call.name),
+                escalated: false,
+            }],
+            usage: TokenUsage::default(),
+        }))
+    }
+
+    /// Resume a run paused for confirmation, with the decision on its
+    /// pending action.
+    ///
+    /// The pending tool call runs again, now with the decision, and the run
+    /// continues from there. The run timeout starts over, so time spent
+    /// waiting for approval is not counted.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`run`](Self::run).
+    pub async fn resume(
+        &mut self,
+        run: PausedRun,
+        approved: bool,
+        cancel: &CancellationToken,
+    ) -> Result<AgentResponse, AgentError> {
+        self.state = AgentState::ExecutingTool;
+        self.stats = run.stats;
+        let started = std::time::Instant::now();
+
+        self.request_id = Some(run.request_id.clone());
+        self.tool_context.request_id = Some(run.request_id.clone());
+        self.tool_context.cancel = cancel.clone();
+        self.emit(AgentEvent::Confirmation {
+            request_id: run.request_id.clone(),
+            prompt: run.prompt.clone(),
+            level: run.level,
+            status: if approved {
+                ConfirmationStatus::Approved
+            } else {
+                ConfirmationStatus::Denied
+            },
+        });
+        self.tool_context.approval = Some(Approval {
+            prompt: run.prompt,
+            approved,
+        });
+        self.context = run.context;
+        self.context.set_token_counter(self.token_counter());
+        self.examples_prompt = run.examples_prompt;
+
+        let progress = RunProgress {
+            iterations: run.iterations,
+            executed_sql: run.executed_sql,
+            results: run.results,
+            trace: run.trace,
+            awaiting: None,
+        };
+        let span = tracing::info_span!("agent_run", request_id = %run.request_id);
+        let result = self
+            .react_loop(&run.query, cancel, progress, Some(run.pending_call))
+            .instrument(span)
+            .await;
+        self.tool_context.approval = None;
+        self.finish(&run.query, started, result)
+    }
+
+    /// Take the state of a run that paused for confirmation.
+    ///
+    /// Runs pause instead of asking when the tool context defers
+    /// confirmation; save the state and pass it to [`resume`](Self::resume).
+    pub fn take_paused_run(&mut self) -> Option<PausedRun> {
+        self.paused.take()
+    }
+
+    /// Record the outcome of a run.
+    fn finish(
+        &mut self,
+        query: &str,
+        started: std::time::Instant,
+        mut result: Result<AgentResponse, AgentError>,
+    ) -> Result<AgentResponse, AgentError> {
+        let elapsed = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        self.stats.duration_ms = self.stats.duration_ms.saturating_add(elapsed);
+        if let Ok(response) = &mut result {
+            response.usage = self.stats.token_usage();
+        }
+
+        // Learn from successful runs
+        if let (Some(examples), Ok(response)) = (&mut self.examples, &result)
+            && response.success
+            && let Some(sql) = response.last_sql()
+        {
+            examples.add(Example::new(query, sql.to_string()));
+        }
 
         // Set final state
         self.state = match &result {
+            Ok(response) if response.state == AgentState::AwaitingConfirmation => {
+                AgentState::AwaitingConfirmation
+            }
             Ok(_) => AgentState::Completed,
             Err(e) => AgentState::Error(e.to_string()),
         };
@@ -337,87 +965,425 @@ impl<Client: LlmClient> PostgresAgent<Client> {
         result
     }
 
-    /// Run a single reasoning iteration.
-    async fn react_loop(&mut self, _initial_query: &str) -> Result<AgentResponse, AgentError> {
-        let mut iterations = 0u32;
-        let mut final_answer = String::new();
-        let mut executed_sql = None;
+    /// Build the examples prompt for a query.
+    ///
+    /// Failures are logged and the run continues without examples.
+    async fn retrieve_examples(&mut self, query: &str) -> Option<String> {
+        let examples = self.examples.as_mut()?;
+        match examples.retrieve(query).await {
+            Ok(found) if !found.is_empty() => {
+                tracing::debug!("Using {} similar past questions as examples", found.len());
+                let found: Vec<Example> = found.into_iter().map(|(example, _)| example).collect();
+                Some(examples_prompt(&found))
+            }
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!("Example retrieval failed: {}", e);
+                None
+            }
+        }
+    }
 
-        while iterations < self.config.max_iterations {
-            iterations += 1;
-            self.stats.iterations += 1;
-            self.state = AgentState::Thinking;
+    /// Summarize older turns once the context nears its token limit.
+    ///
+    /// Failures are logged and left to the regular pruning. Skipped in
+    /// deterministic mode, where a generated summary would feed later prompts.
+    async fn compact_context(&mut self) {
+        if self.config.deterministic
+            || !self.context.needs_compaction()
+            || self.context.len() <= KEEP_RECENT_MESSAGES
+        {
+            return;
+        }
 
-            // Serialize context to JSON for LLM
-            let context_json = serde_json::to_value(&self.context)
-                .map_err(|e| AgentError::SerializationError {
-                    message: e.to_string(),
-                })?;
+        let split = self.context.len() - KEEP_RECENT_MESSAGES;
+        let older: Vec<_> = self.context.messages()[..split]
+            .iter()
+            .map(Message::to_prompt_message)
+            .collect();
 
-            // Get LLM decision
-            let decision_value = self
-                .llm_client
-                .generate_decision(&context_json)
-                .await
-                .map_err(|e| AgentError::LlmError {
-                    message: e.to_string(),
-                })?;
-
-            // Parse decision
-            let decision = parse_decision(&decision_value)
-                .map_err(|e| AgentError::InvalidToolCall { details: e })?;
-
-            // Process decision
-            match decision {
-                AgentDecision::Reasoning { thought } => {
-                    // Add reasoning as assistant message
-                    self.context.add_assistant_message(&thought);
-
-                    if self.config.verbose_reasoning {
-                        tracing::info!("Thought: {}", thought);
+        match self.llm_client.summarize(&older).await {
+            Ok(summary) => {
+                tracing::debug!("Compacted {} messages into a summary", split);
+                self.context.compact(KEEP_RECENT_MESSAGES, &summary);
+            }
+            Err(e) => tracing::warn!("Context compaction failed: {}", e),
+        }
+    }
+
+    /// Run reasoning iterations until a final answer, starting with
+    /// `pending_call` when resuming.
+    ///
+    /// Each iteration and the run as a whole are bounded by the configured
+    /// timeouts; on timeout the findings so far are returned as a partial
+    /// answer. An action waiting for deferred approval pauses the run.
+    async fn react_loop(
+        &mut self,
+        query: &str,
+        cancel: &CancellationToken,
+        mut progress: RunProgress,
+        mut pending_call: Option<ToolCall>,
+    ) -> Result<AgentResponse, AgentError> {
+        let per_iteration = Duration::from_secs(self.config.timeout_seconds);
+        let total = Duration::from_secs(self.config.total_timeout_seconds);
+        let deadline = Instant::now() + total;
+
+        while pending_call.is_some() || progress.iterations < self.config.max_iterations {
+            if cancel.is_cancelled() {
+                return Err(AgentError::Cancelled);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let (budget, seconds) = if remaining < per_iteration {
+                (remaining, self.config.total_timeout_seconds)
+            } else {
+                (per_iteration, self.config.timeout_seconds)
+            };
+
+            let step = if budget.is_zero() {
+                None
+            } else {
+                let work = async {
+                    match pending_call.take() {
+                        Some(call) => self.run_tool_call(call, &mut progress).await.map(|()| None),
+                        None => self.iterate(&mut progress).await,
                     }
+                };
+                tokio::time::timeout(budget, work).await.ok()
+            };
+            match step {
+                Some(Ok(Some(answer))) => {
+                    return Ok(AgentResponse {
+                        answer,
+                        executed_sql: progress.executed_sql,
+                        results: progress.results,
+                        iterations: progress.iterations,
+                        success: true,
+                        error: None,
+                        state: AgentState::Completed,
+                        request_id: self.request_id.clone(),
+                        trace: progress.trace,
+                        usage: TokenUsage::default(),
+                    });
+                }
+                Some(Ok(None)) => {}
+                Some(Err(AgentError::ConfirmationRequired { prompt, level })) => {
+                    return self.pause(query, prompt, level, progress);
                 }
+                Some(Err(e)) => return Err(e),
+                None => {
+                    tracing::warn!("Run timed out after {}s", seconds);
+                    let error = AgentError::timeout(seconds).to_string();
+                    return Ok(AgentResponse {
+                        answer: self.partial_answer(seconds),
+                        executed_sql: progress.executed_sql,
+                        results: progress.results,
+                        iterations: progress.iterations,
+                        success: false,
+                        error: Some(error.clone()),
+                        state: AgentState::Error(error),
+                        request_id: self.request_id.clone(),
+                        trace: progress.trace,
+                        usage: TokenUsage::default(),
+                    });
+                }
+            }
+        }
+
+        Err(AgentError::MaxIterationsExceeded {
+            iterations: self.config.max_iterations,
+        })
+    }
 
-                AgentDecision::ToolCall(call) => {
-                    self.state = AgentState::ExecutingTool;
+    /// Run a single reasoning iteration, returning the final answer if any.
+    async fn iterate(&mut self, progress: &mut RunProgress) -> Result<Option<String>, AgentError> {
+        progress.iterations += 1;
+        self.stats.iterations += 1;
+        self.state = AgentState::Thinking;
 
-                    // Execute tool
-                    let tool_result = self.execute_tool(&call).await?;
+        // Serialize context to JSON for LLM
+        let mut context_json = serde_json::to_value(&self.context)
+            .map_err(|e| AgentError::SerializationError {
+                message: e.to_string(),
+            })?;
+        if let Some(prompt) = &self.examples_prompt {
+            insert_before_last_user(&mut context_json, Message::system(prompt.as_str()));
+        }
 
-                    // Add tool result to context
-                    self.context.add_tool_message(&tool_result.result.to_string(), &call.name);
+        // Refuse prompts the model cannot take, then enforce the per-query
+        // token budget before spending more
+        let counter = self.context.token_counter();
+        let prompt_tokens = count_json_tokens(counter, &context_json);
+        self.stats.context_tokens = prompt_tokens;
+        let model = self.llm_client.provider_info().model;
+        let window = postgres_agent_llm::capabilities(&model).context_window;
+        if postgres_agent_llm::models::is_known(&model) && prompt_tokens > u64::from(window) {
+            return Err(AgentError::ContextTooLarge {
+                size: usize::try_from(prompt_tokens).unwrap_or(usize::MAX),
+                limit: window as usize,
+            });
+        }
+        if let Some(limit) = self.config.max_tokens_per_query {
+            let projected = self.stats.estimated_tokens + prompt_tokens;
+            if projected > limit {
+                return Err(AgentError::TokenBudgetExceeded {
+                    used: projected,
+                    limit,
+                });
+            }
+        }
 
-                    if let Some(sql) = extract_sql(&tool_result.result) {
-                        executed_sql = Some(sql);
-                    }
+        // Get LLM decision, routed between reasoning and primary models;
+        // cancelling drops the request. Tools handle cancellation themselves
+        // so that running queries are cancelled on the server.
+        let cancel = self.tool_context.cancel.clone();
+        let llm_started = Instant::now();
+        let routed = tokio::select! {
+            routed = self.decide(&context_json, progress.iterations) => routed?,
+            () = cancel.cancelled() => return Err(AgentError::Cancelled),
+        };
+        self.stats.timings.push(IterationTiming {
+            iteration: progress.iterations,
+            llm_ms: u64::try_from(llm_started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            ..IterationTiming::default()
+        });
+        let calls = if routed.escalated { 2 } else { 1 };
+        self.stats.estimated_tokens +=
+            prompt_tokens * calls + count_json_tokens(counter, &routed.value);
+        self.stats.usage.extend(routed.usage);
+        progress.trace.push(TurnRecord {
+            iteration: progress.iterations,
+            model: routed.model,
+            decision: describe_decision(&routed.value),
+            escalated: routed.escalated,
+        });
+        let decision_value = routed.value;
 
-                    self.stats.tool_calls += 1;
-                }
+        // Parse decision
+        let decision = AgentDecision::from_value(&decision_value)
+            .map_err(|e| AgentError::InvalidToolCall { details: e.to_string() })?;
 
-                AgentDecision::FinalAnswer(answer) => {
-                    final_answer = answer.clone();
-                    self.context.add_assistant_message(&answer);
-                    break;
+        // Process decision
+        match decision {
+            AgentDecision::Reasoning { thought } => {
+                // Add reasoning as assistant message
+                self.context.add_assistant_message(&thought);
+
+                if self.config.verbose_reasoning {
+                    tracing::info!("Thought: {}", thought);
+                }
+                if self.config.audit_reasoning
+                    && let Some(audit) = &self.tool_context.audit
+                {
+                    audit.log_reasoning(progress.iterations, &thought, self.request_id.as_deref());
                 }
+                self.emit(AgentEvent::Reasoning {
+                    iteration: progress.iterations,
+                    thought,
+                });
+            }
+
+            AgentDecision::ToolCall(call) => self.run_tool_call(call, progress).await?,
+
+            AgentDecision::FinalAnswer { answer } => {
+                self.context.add_assistant_message(&answer);
+                return Ok(Some(answer));
             }
         }
+        Ok(None)
+    }
 
-        if final_answer.is_empty() {
-            return Err(AgentError::MaxIterationsExceeded {
-                iterations: self.config.max_iterations,
-            });
+    /// Execute a tool call and add its result to the context.
+    async fn run_tool_call(
+        &mut self,
+        call: ToolCall,
+        progress: &mut RunProgress,
+    ) -> Result<(), AgentError> {
+        self.state = AgentState::ExecutingTool;
+        self.emit(AgentEvent::ToolCall {
+            iteration: progress.iterations,
+            name: call.name.clone(),
+        });
+
+        // Execute tool
+        let tool_result = match self.execute_tool(&call).await {
+            Ok(result) => result,
+            Err(e @ AgentError::ConfirmationRequired { .. }) => {
+                progress.awaiting = Some(call);
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // Add tool result to context; the full result stays in the response
+        self.add_tool_message(&call.name, &tool_result.result);
+        self.stats.record_tool_time(progress.iterations, &call.name, tool_result.duration_ms);
+
+        let executed = ExecutedSql::from_tool_result(&call, &tool_result);
+        if tool_result.success
+            && let Ok(result) = QueryResult::deserialize(&tool_result.result)
+        {
+            let result = ResultSet {
+                tool: tool_result.tool.clone(),
+                sql: executed.as_ref().map(|e| e.sql.clone()),
+                result,
+            };
+            progress.add_result(result, self.config.max_result_rows);
         }
+        progress.executed_sql.extend(executed);
 
+        self.stats.tool_calls += 1;
+        Ok(())
+    }
+
+    /// Add a tool result to the context, as much of it as the LLM may see.
+    fn add_tool_message(&mut self, tool: &str, result: &Value) {
+        let mut content = match self.config.llm_data {
+            LlmDataPolicy::Full => result.clone(),
+            LlmDataPolicy::MetadataOnly => privacy::withhold_values(tool, result),
+        };
+        privacy::mask_columns(tool, &mut content, &self.config.local_only_columns);
+        self.context.add_tool_message(&content.to_string(), tool);
+    }
+
+    /// Save the state of a run waiting for approval and respond that it is
+    /// waiting.
+    fn pause(
+        &mut self,
+        query: &str,
+        prompt: String,
+        level: ConfirmationLevel,
+        mut progress: RunProgress,
+    ) -> Result<AgentResponse, AgentError> {
+        let pending_call = progress.awaiting.take().ok_or_else(|| AgentError::InvalidState {
+            state: "paused without a pending tool call".to_string(),
+        })?;
+        let request_id = self.request_id.clone().unwrap_or_default();
+        tracing::info!("Run {} paused for confirmation: {}", request_id, prompt);
+        self.emit(AgentEvent::Confirmation {
+            request_id: request_id.clone(),
+            prompt: prompt.clone(),
+            level,
+            status: ConfirmationStatus::Pending,
+        });
+        self.paused = Some(PausedRun {
+            request_id,
+            query: query.to_string(),
+            paused_at: chrono::Utc::now(),
+            prompt: prompt.clone(),
+            level,
+            pending_call,
+            context: self.context.clone(),
+            examples_prompt: self.examples_prompt.clone(),
+            iterations: progress.iterations,
+            executed_sql: progress.executed_sql.clone(),
+            results: progress.results.clone(),
+            trace: progress.trace.clone(),
+            stats: self.stats.clone(),
+        });
         Ok(AgentResponse {
-            answer: final_answer,
-            executed_sql,
-            iterations,
-            success: true,
+            answer: format!("Waiting for approval: {}", prompt),
+            executed_sql: progress.executed_sql,
+            results: progress.results,
+            iterations: progress.iterations,
+            success: false,
             error: None,
-            state: AgentState::Completed,
+            state: AgentState::AwaitingConfirmation,
+            request_id: self.request_id.clone(),
+            trace: progress.trace,
+            usage: TokenUsage::default(),
         })
     }
 
+    /// Answer summarizing the reasoning of the current run, for runs that
+    /// timed out.
+    fn partial_answer(&self, seconds: u64) -> String {
+        let messages = self.context.messages();
+        let start = messages
+            .iter()
+            .rposition(|m| m.role == MessageRole::User)
+            .map_or(0, |i| i + 1);
+        let findings: Vec<&str> = messages[start..]
+            .iter()
+            .filter(|m| m.role == MessageRole::Assistant)
+            .map(|m| m.content.as_str())
+            .collect();
+        if findings.is_empty() {
+            return format!("Timed out after {}s before reaching an answer.", seconds);
+        }
+        format!(
+            "Timed out after {}s before reaching a final answer. Findings so far:\n- {}",
+            seconds,
+            findings.join("\n- ")
+        )
+    }
+
+    /// Get the next decision, escalating to the primary model when needed.
+    ///
+    /// With a subscriber, the primary model's decision is streamed so that a
+    /// final answer is sent as it is written.
+    async fn decide(
+        &self,
+        context_json: &Value,
+        iteration: u32,
+    ) -> Result<RoutedDecision, AgentError> {
+        let to_agent_error = |e: LlmError| AgentError::LlmError {
+            message: e.to_string(),
+        };
+
+        let mut usage = Vec::new();
+        if let Some(reasoning) = &self.reasoning_client {
+            let mut draft = reasoning
+                .generate_decision(context_json)
+                .await
+                .map_err(to_agent_error)?;
+            let model = reasoning.provider_info().model;
+            usage.extend(LlmUsage::take(iteration, &model, &mut draft));
+            if !needs_primary_model(&draft) {
+                return Ok(RoutedDecision {
+                    value: draft,
+                    model,
+                    escalated: false,
+                    usage,
+                });
+            }
+            tracing::debug!("Escalating {} to the primary model", describe_decision(&draft));
+        }
+
+        let mut value = if self.events.is_some() {
+            self.stream_decision(context_json, iteration).await
+        } else {
+            self.llm_client.generate_decision(context_json).await
+        }
+        .map_err(to_agent_error)?;
+        let model = self.llm_client.provider_info().model;
+        usage.extend(LlmUsage::take(iteration, &model, &mut value));
+        Ok(RoutedDecision {
+            value,
+            model,
+            escalated: self.reasoning_client.is_some(),
+            usage,
+        })
+    }
+
+    /// Get a decision from the primary model, sending answer text to the
+    /// subscriber as it arrives.
+    async fn stream_decision(
+        &self,
+        context_json: &Value,
+        iteration: u32,
+    ) -> Result<Value, LlmError> {
+        let mut chunks = self.llm_client.generate_decision_stream(context_json).await?;
+        while let Some(chunk) = chunks.next().await {
+            match chunk? {
+                DecisionChunk::AnswerDelta(delta) => {
+                    self.emit(AgentEvent::AnswerDelta { iteration, delta });
+                }
+                DecisionChunk::Decision(decision) => return Ok(decision),
+            }
+        }
+        Err(LlmError::NoResponse)
+    }
+
     /// Execute a tool call.
     async fn execute_tool(&mut self, call: &ToolCall) -> Result<ToolResult, AgentError> {
         let start = std::time::Instant::now();
@@ -425,13 +1391,48 @@ impl<Client: LlmClient> PostgresAgent<Client> {
         let result = self
             .tools
             .execute(&call.name, &call.arguments, &self.tool_context)
-            .await
-            .map_err(|e| AgentError::ToolExecutionFailed {
-                tool_name: call.name.clone(),
-                reason: e.to_string(),
-            })?;
+            .await;
+        if let Err(ToolError::ConfirmationRequired { prompt, level }) = result {
+            return Err(AgentError::ConfirmationRequired { prompt, level });
+        }
 
         let duration_ms = start.elapsed().as_millis() as u64;
+        // Bare and qualified names of one tool share an entry
+        let tool_name = self.tools.qualified_name(&call.name).unwrap_or_else(|| call.name.clone());
+        self.stats.tools.entry(tool_name).or_default().record(duration_ms, result.is_ok());
+
+        let result = match result {
+            Ok(result) => result,
+            // Errors reported by the server go back to the model to fix the query
+            Err(ToolError::Database { source }) if source.sqlstate().is_some() => {
+                let message = source.to_string();
+                let mut result = database_error_result(&source);
+                let detail = source.pg_error().and_then(|e| e.detail.as_deref()).unwrap_or("");
+                let local_only = &self.config.local_only_columns;
+                if self.config.llm_data == LlmDataPolicy::MetadataOnly
+                    || local_only.iter().any(|c| detail.contains(c.as_str()))
+                {
+                    result["error"] = serde_json::json!(privacy::error_without_detail(&source));
+                }
+                return Ok(ToolResult {
+                    call_id: call.call_id.clone(),
+                    tool: call.name.clone(),
+                    result,
+                    success: false,
+                    error: Some(message),
+                    duration_ms,
+                });
+            }
+            Err(_) if self.tool_context.cancel.is_cancelled() => {
+                return Err(AgentError::Cancelled);
+            }
+            Err(e) => {
+                return Err(AgentError::ToolExecutionFailed {
+                    tool_name: call.name.clone(),
+                    reason: e.to_string(),
+                });
+            }
+        };
 
         Ok(ToolResult {
             call_id: call.call_id.clone(),
@@ -450,11 +1451,46 @@ impl<Client: LlmClient> PostgresAgent<Client> {
         self.stats = AgentStats::default();
     }
 
+    /// Tokenizer of the primary model.
+    #[must_use]
+    pub fn token_counter(&self) -> TokenCounter {
+        let model = self.llm_client.provider_info().model;
+        TokenCounter::for_model(&model, self.config.tokenizer_fallback)
+    }
+
     /// Set the database schema in context.
+    ///
+    /// Warns if the schema alone exceeds the model's context window.
     pub fn set_schema(&mut self, schema: String) {
+        let model = self.llm_client.provider_info().model;
+        let caps = postgres_agent_llm::capabilities(&model);
+        let tokens = self.token_counter().count(&schema);
+        if !caps.fits(tokens) {
+            tracing::warn!(
+                "Schema is {} tokens but {} has a {}-token context window; \
+                 filter the schema or use a larger model",
+                tokens,
+                model,
+                caps.context_window
+            );
+        }
         self.context.set_database_schema(schema);
     }
 
+    /// Route intermediate turns to a cheaper reasoning model.
+    ///
+    /// Turns where the reasoning model proposes running SQL or gives a final
+    /// answer are re-run on the primary client.
+    pub fn set_reasoning_client(&mut self, client: impl Into<Arc<Client>>) {
+        self.reasoning_client = Some(client.into());
+    }
+
+    /// Get the LLM client.
+    #[must_use]
+    pub fn llm_client(&self) -> &Client {
+        &self.llm_client
+    }
+
     /// Get provider info from the LLM client.
     #[must_use]
     pub fn provider_info(&self) -> String {
@@ -462,62 +1498,120 @@ impl<Client: LlmClient> PostgresAgent<Client> {
     }
 }
 
-/// Parse a decision from JSON value.
-fn parse_decision(value: &Value) -> Result<AgentDecision, String> {
-    let decision_type = value
-        .get("type")
-        .and_then(|v| v.as_str())
-        .ok_or("Missing 'type' field")?;
-
-    match decision_type {
-        "reasoning" => {
-            let thought = value
-                .get("thought")
-                .and_then(|v| v.as_str())
-                .ok_or("Missing 'thought' field")?
-                .to_string();
-            Ok(AgentDecision::Reasoning { thought })
-        }
-        "tool_call" => {
-            let name = value
-                .get("name")
-                .and_then(|v| v.as_str())
-                .ok_or("Missing 'name' field")?
-                .to_string();
-            let arguments = value
-                .get("arguments")
-                .cloned()
-                .unwrap_or_else(|| Value::Object(serde_json::Map::new()));
-            let call_id = value
-                .get("call_id")
-                .and_then(|v| v.as_str())
-                .unwrap_or("default")
-                .to_string();
-            Ok(AgentDecision::ToolCall(ToolCall {
-                name,
-                arguments,
-                call_id,
-            }))
-        }
-        "final_answer" => {
-            let answer = value
-                .get("answer")
-                .and_then(|v| v.as_str())
-                .ok_or("Missing 'answer' field")?
-                .to_string();
-            Ok(AgentDecision::FinalAnswer(answer))
-        }
-        _ => Err(format!("Unknown decision type: {}", decision_type)),
-    }
-}
-
-/// Extract SQL from a tool result if present.
-fn extract_sql(result: &serde_json::Value) -> Option<String> {
-    result
-        .as_object()
-        .and_then(|obj| obj.get("sql"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string())
+impl<Client: LlmClient + Clone> PostgresAgent<Client> {
+    /// Get mutable access to the LLM client, e.g. to switch models.
+    ///
+    /// A client shared with other agents is copied first, so the change
+    /// applies to this agent only.
+    pub fn llm_client_mut(&mut self) -> &mut Client {
+        Arc::make_mut(&mut self.llm_client)
+    }
+}
+
+/// Insert a message into serialized context ahead of the latest user message.
+fn insert_before_last_user(context_json: &mut Value, message: Message) {
+    let Some(messages) = context_json.get_mut("messages").and_then(Value::as_array_mut) else {
+        return;
+    };
+    let (Ok(message), Ok(user)) =
+        (serde_json::to_value(message), serde_json::to_value(MessageRole::User))
+    else {
+        return;
+    };
+    let position = messages
+        .iter()
+        .rposition(|m| m.get("role") == Some(&user))
+        .unwrap_or(messages.len());
+    messages.insert(position, message);
+}
+
+/// State accumulated over the iterations of one run.
+#[derive(Debug, Default)]
+struct RunProgress {
+    /// Iterations started.
+    iterations: u32,
+    /// SQL executed by tools.
+    executed_sql: Vec<ExecutedSql>,
+    /// Query results returned by tools.
+    results: Vec<ResultSet>,
+    /// Decision made in each iteration.
+    trace: Vec<TurnRecord>,
+    /// Tool call waiting for approval.
+    awaiting: Option<ToolCall>,
+}
+
+impl RunProgress {
+    /// Keep a query result, truncating it to the rows left under `max_rows`;
+    /// results beyond the cap are dropped.
+    fn add_result(&mut self, mut set: ResultSet, max_rows: usize) {
+        let kept: usize = self.results.iter().map(|r| r.result.rows.len()).sum();
+        let room = max_rows.saturating_sub(kept);
+        if room == 0 {
+            return;
+        }
+        let result = &mut set.result;
+        if result.rows.len() > room {
+            result.rows.truncate(room);
+            result.truncated = true;
+        }
+        self.results.push(set);
+    }
+}
+
+/// A decision together with the model that produced it.
+struct RoutedDecision {
+    /// Raw decision JSON.
+    value: Value,
+    /// Model that produced the decision.
+    model: String,
+    /// Whether the primary model was used after a reasoning-model draft.
+    escalated: bool,
+    /// Tokens reported for the calls made.
+    usage: Vec<LlmUsage>,
+}
+
+/// Whether a draft decision must come from the primary model: SQL
+/// generation (`execute_query`) and final answers.
+fn needs_primary_model(decision: &Value) -> bool {
+    match decision.get("type").and_then(Value::as_str) {
+        Some("final_answer") => true,
+        // Tool names may be namespace-qualified, e.g. `db.execute_query`
+        Some("tool_call") => decision
+            .get("name")
+            .and_then(Value::as_str)
+            .is_some_and(|name| name.rsplit('.').next() == Some("execute_query")),
+        _ => false,
+    }
+}
+
+/// Short description of a decision for the trace.
+fn describe_decision(decision: &Value) -> String {
+    let kind = decision.get("type").and_then(Value::as_str).unwrap_or("unknown");
+    match decision.get("name").and_then(Value::as_str) {
+        Some(name) if kind == "tool_call" => format!("tool_call:{}", name),
+        _ => kind.to_string(),
+    }
+}
+
+/// Count the tokens of a JSON payload.
+fn count_json_tokens(counter: TokenCounter, value: &Value) -> u64 {
+    counter.count(&value.to_string()) as u64
+}
+
+/// Tool output describing a failed query, with a suggested fix if known.
+fn database_error_result(error: &postgres_agent_db::DbError) -> serde_json::Value {
+    let mut output = serde_json::json!({
+        "error": error.to_string(),
+        "sqlstate": error.sqlstate(),
+    });
+    if let Some(highlight) = error.highlight() {
+        output["position"] = serde_json::json!(highlight);
+    }
+    if let Some(state) = error.sqlstate_info() {
+        output["condition"] = serde_json::json!(state.name);
+        output["suggestion"] = serde_json::json!(state.suggestion);
+    }
+    output
 }
 
 #[cfg(test)]
@@ -527,8 +1621,16 @@ mod tests {
     use postgres_agent_llm::provider::ProviderInfo;
 
     // Mock LLM client for testing
-    #[derive(Debug, Default)]
-    struct MockLlmClient;
+    #[derive(Debug)]
+    struct MockLlmClient {
+        model: &'static str,
+    }
+
+    impl Default for MockLlmClient {
+        fn default() -> Self {
+            Self { model: "mock" }
+        }
+    }
 
     #[async_trait::async_trait]
     impl LlmClient for MockLlmClient {
@@ -536,40 +1638,210 @@ mod tests {
             Ok("Mock completion".to_string())
         }
 
-        async fn generate_decision(&self, _context_json: &Value) -> Result<Value, LlmError> {
-            Ok(serde_json::json!({
+        async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+            // The slow model stalls after its first thought
+            if self.model == "slow" {
+                if context_json.to_string().contains("Found the orders table") {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                return Ok(serde_json::json!({
+                    "type": "reasoning",
+                    "thought": "Found the orders table"
+                }));
+            }
+            let mut answer = serde_json::json!({
                 "type": "final_answer",
                 "answer": "Mock response"
-            }))
+            });
+            if self.model == "metered" {
+                TokenUsage::new(300u32, 20u32).attach(&mut answer);
+            }
+            // A cache hit reports that it used nothing
+            if self.model == "cached" {
+                TokenUsage::default().attach(&mut answer);
+            }
+            Ok(answer)
         }
 
-        async fn generate_structured<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+        async fn generate_decision_stream(
             &self,
-            _prompt: &str,
-            _schema: &T,
-        ) -> Result<T, LlmError> {
-            unimplemented!()
+            context_json: &Value,
+        ) -> Result<postgres_agent_llm::DecisionStream, LlmError> {
+            let decision = self.generate_decision(context_json).await?;
+            let deltas = ["Mock ", "response"].map(|d| Ok(DecisionChunk::AnswerDelta(d.into())));
+            let chunks = deltas.into_iter().chain([Ok(DecisionChunk::Decision(decision))]);
+            Ok(futures::stream::iter(chunks).boxed())
         }
 
         fn provider_info(&self) -> ProviderInfo {
             ProviderInfo {
                 provider: "Mock".to_string(),
-                model: "mock".to_string(),
+                model: self.model.to_string(),
             }
         }
     }
 
     #[tokio::test]
     async fn test_agent_run() {
-        let client = Box::new(MockLlmClient);
+        let client = Box::new(MockLlmClient::default());
         let mut agent = PostgresAgent::new(client);
 
-        let result = agent.run("Test query").await;
+        let result = agent.run("Test query", &CancellationToken::new()).await;
         assert!(result.is_ok());
 
         let response = result.unwrap();
         assert!(response.success);
         assert_eq!(response.answer, "Mock response");
+        assert!(response.request_id.is_some());
+        assert_eq!(response.request_id.as_deref(), agent.request_id());
+        assert_eq!(agent.safety_context().request_id.as_deref(), agent.request_id());
+        assert_eq!(agent.stats().timings.len(), 1);
+        assert!(agent.stats().timings[0].tool.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_agent_streams_answer() {
+        let mut agent = PostgresAgent::new(Box::new(MockLlmClient::default()));
+        let (sender, mut receiver) = crate::events::channel();
+        agent.set_event_sender(sender);
+
+        let response = agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        let mut streamed = String::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let AgentEvent::AnswerDelta { iteration, delta } = event {
+                assert_eq!(iteration, 1);
+                streamed.push_str(&delta);
+            }
+        }
+        assert_eq!(streamed, response.answer);
+    }
+
+    #[test]
+    fn test_record_tool_time() {
+        let mut stats = AgentStats::default();
+        stats.timings.push(IterationTiming {
+            iteration: 1,
+            llm_ms: 800,
+            ..IterationTiming::default()
+        });
+        stats.record_tool_time(1, "execute_query", 40);
+        // A resumed call has no LLM time of its own
+        stats.record_tool_time(2, "export_results", 15);
+
+        assert_eq!(stats.timings.len(), 2);
+        assert_eq!(stats.timings[0].tool.as_deref(), Some("execute_query"));
+        assert_eq!(stats.timings[1].llm_ms, 0);
+        assert_eq!((stats.llm_ms(), stats.tool_ms()), (800, 55));
+    }
+
+    #[tokio::test]
+    async fn test_agent_token_budget() {
+        let config = AgentConfigBuilder::new().max_tokens_per_query(1).build();
+        let mut agent = PostgresAgent::with_config(Box::new(MockLlmClient::default()), config);
+
+        let result = agent.run("Test query", &CancellationToken::new()).await;
+        assert!(matches!(result, Err(AgentError::TokenBudgetExceeded { limit: 1, .. })));
+
+        let mut agent = PostgresAgent::new(Box::new(MockLlmClient::default()));
+        agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        assert!(agent.stats().estimated_tokens > 0);
+    }
+
+    #[tokio::test]
+    async fn test_agent_token_usage() {
+        let mut agent = PostgresAgent::new(Box::new(MockLlmClient { model: "metered" }));
+        let response = agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        assert_eq!(response.usage, TokenUsage::new(300u32, 20u32));
+        let stats = agent.stats();
+        assert_eq!(stats.usage.len(), 1);
+        assert_eq!((stats.usage[0].iteration, stats.usage[0].model.as_str()), (1, "metered"));
+        assert_eq!(stats.tokens(), 320);
+
+        let mut llm = LlmConfig::default();
+        let price = postgres_agent_config::ModelPricing {
+            prompt_per_1k: 0.01,
+            completion_per_1k: 0.05,
+        };
+        llm.pricing.insert("metered".to_string(), price);
+        assert!((stats.cost(&llm) - 0.004).abs() < 1e-9);
+
+        // Without reported usage, the estimate is used
+        let mut agent = PostgresAgent::new(Box::new(MockLlmClient::default()));
+        let response = agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        assert!(response.usage.is_empty());
+        assert_eq!(agent.stats().tokens(), agent.stats().estimated_tokens);
+
+        // A cached decision is free, rather than charged at the estimate
+        let mut agent = PostgresAgent::new(Box::new(MockLlmClient { model: "cached" }));
+        agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        let stats = agent.stats();
+        assert_eq!(stats.usage.len(), 1);
+        assert_eq!(stats.tokens(), 0);
+        assert!(stats.estimated_tokens > 0);
+        llm.cost_per_1k_tokens = 0.01;
+        assert!(stats.cost(&llm).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_agent_timeouts() {
+        let config = AgentConfigBuilder::new().timeout_seconds(1).build();
+        let client = Box::new(MockLlmClient { model: "slow" });
+        let mut agent = PostgresAgent::with_config(client, config);
+
+        let response = agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        assert!(!response.success);
+        assert_eq!(response.iterations, 2);
+        assert!(response.answer.contains("Findings so far:\n- Found the orders table"));
+        assert_eq!(response.error.as_deref(), Some("Operation timed out after 1s"));
+
+        let config = AgentConfigBuilder::new().total_timeout_seconds(0).build();
+        let mut agent = PostgresAgent::with_config(Box::new(MockLlmClient::default()), config);
+        let response = agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        assert_eq!(response.answer, "Timed out after 0s before reaching an answer.");
+    }
+
+    #[tokio::test]
+    async fn test_agent_cancelled() {
+        let mut agent = PostgresAgent::new(Box::new(MockLlmClient::default()));
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let result = agent.run("Test query", &cancel).await;
+        assert!(matches!(result, Err(AgentError::Cancelled)));
+        assert_eq!(agent.stats().iterations, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dual_model_routing() {
+        let mut agent = PostgresAgent::new(Box::new(MockLlmClient { model: "strong" }));
+        agent.set_reasoning_client(Box::new(MockLlmClient { model: "cheap" }));
+
+        // The cheap model's final answer is re-run on the primary model
+        let response = agent.run("Test query", &CancellationToken::new()).await.unwrap();
+        assert_eq!(
+            response.trace,
+            vec![TurnRecord {
+                iteration: 1,
+                model: "strong".to_string(),
+                decision: "final_answer".to_string(),
+                escalated: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_needs_primary_model() {
+        let sql = serde_json::json!({"type": "tool_call", "name": "execute_query"});
+        let schema = serde_json::json!({"type": "tool_call", "name": "list_tables"});
+        let thought = serde_json::json!({"type": "reasoning", "thought": "..."});
+
+        let qualified = serde_json::json!({"type": "tool_call", "name": "db.execute_query"});
+
+        assert!(needs_primary_model(&sql));
+        assert!(needs_primary_model(&qualified));
+        assert!(!needs_primary_model(&schema));
+        assert!(!needs_primary_model(&thought));
+        assert_eq!(describe_decision(&schema), "tool_call:list_tables");
     }
 
     #[test]
@@ -592,16 +1864,100 @@ mod tests {
         assert_eq!(response.error, Some("test error".to_string()));
     }
 
+    #[test]
+    fn test_executed_sql_from_tool_result() {
+        let call = ToolCall {
+            name: "execute_query".to_string(),
+            arguments: serde_json::json!({"sql": "SELECT * FROM orders"}),
+            call_id: "call-1".to_string(),
+        };
+        let mut result = ToolResult {
+            call_id: "call-1".to_string(),
+            tool: "execute_query".to_string(),
+            result: serde_json::json!({"rows": [], "rowCount": 3}),
+            success: true,
+            error: None,
+            duration_ms: 12,
+        };
+        let statement = ExecutedSql::from_tool_result(&call, &result).unwrap();
+        assert_eq!(statement.sql, "SELECT * FROM orders");
+        assert_eq!(statement.to_string(), "SELECT * FROM orders (3 rows, 12ms)");
+
+        // The statement reported by the tool wins over the requested one
+        result.result["sql"] = serde_json::json!("SELECT * FROM orders WHERE tenant_id = 7");
+        let statement = ExecutedSql::from_tool_result(&call, &result).unwrap();
+        assert!(statement.sql.ends_with("tenant_id = 7"));
+
+        let list = ToolCall {
+            name: "list_tables".to_string(),
+            arguments: serde_json::json!({}),
+            call_id: "call-2".to_string(),
+        };
+        result.result = serde_json::json!({"tables": []});
+        assert!(ExecutedSql::from_tool_result(&list, &result).is_none());
+    }
+
+    #[test]
+    fn test_results_row_cap() {
+        let result = |rows: usize| ResultSet {
+            tool: "execute_query".to_string(),
+            sql: None,
+            result: QueryResult {
+                columns: vec!["id".to_string()],
+                rows: (0..rows)
+                    .map(|i| {
+                        serde_json::Map::from_iter([("id".to_string(), serde_json::json!(i))])
+                    })
+                    .collect(),
+                row_count: rows,
+                ..QueryResult::default()
+            },
+        };
+        let mut progress = RunProgress::default();
+        progress.add_result(result(3), 5);
+        progress.add_result(result(4), 5);
+        progress.add_result(result(1), 5);
+
+        assert_eq!(progress.results.len(), 2);
+        assert_eq!(progress.results[1].result.rows.len(), 2);
+        assert!(progress.results[1].result.truncated);
+        assert!(!progress.results[0].result.truncated);
+    }
+
+    #[test]
+    fn test_result_set_label() {
+        let mut set = ResultSet {
+            tool: "list_tables".to_string(),
+            ..ResultSet::default()
+        };
+        assert_eq!(set.label(), "list_tables");
+        set.sql = Some("SELECT id\n  FROM orders".to_string());
+        assert_eq!(set.label(), "SELECT id FROM orders");
+        set.sql = Some(format!("SELECT {} FROM t", "x, ".repeat(40)));
+        assert_eq!(set.label().chars().count(), 80);
+        assert!(set.label().ends_with(", x..."));
+
+        // Labels survive a round trip next to the flattened result
+        set.result.columns = vec!["a".to_string()];
+        let json = serde_json::to_value(&set).unwrap();
+        assert_eq!(json["columns"], serde_json::json!(["a"]));
+        let back: ResultSet = serde_json::from_value(json).unwrap();
+        assert_eq!(back.sql, set.sql);
+        let old = r#"{"columns":["a"],"rows":[],"rowCount":0,"truncated":false}"#;
+        let old: ResultSet = serde_json::from_str(old).unwrap();
+        assert!(old.tool.is_empty() && old.sql.is_none());
+    }
+
     #[test]
     fn test_parse_decision() {
         let json = serde_json::json!({
             "type": "final_answer",
             "answer": "Test answer"
         });
-        let decision = parse_decision(&json);
+        let decision = AgentDecision::from_value(&json);
         assert!(decision.is_ok());
-        if let Ok(AgentDecision::FinalAnswer(ans)) = decision {
-            assert_eq!(ans, "Test answer");
+        if let Ok(AgentDecision::FinalAnswer { answer }) = decision {
+            assert_eq!(answer, "Test answer");
         }
     }
 }
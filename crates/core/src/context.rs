@@ -1,6 +1,7 @@
 //! Agent context and conversation management.
 
 use chrono::{DateTime, Utc};
+use postgres_agent_llm::{PromptMessage, TokenCounter};
 use serde::{Deserialize, Serialize};
 
 /// A single message in the conversation.
@@ -71,13 +72,33 @@ impl Message {
             tool_name: None,
         }
     }
+
+    /// Convert to an LLM prompt message.
+    #[must_use]
+    pub fn to_prompt_message(&self) -> PromptMessage {
+        let content = self.content.clone();
+        match self.role {
+            MessageRole::User => PromptMessage::User { content },
+            MessageRole::Assistant => PromptMessage::Assistant {
+                content,
+                tool_calls: Vec::new(),
+            },
+            MessageRole::Tool => PromptMessage::Tool {
+                tool_call_id: String::new(),
+                name: self.tool_name.clone().unwrap_or_default(),
+                content,
+            },
+            MessageRole::System => PromptMessage::System { content },
+        }
+    }
 }
 
 /// Role of a message in the conversation.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum MessageRole {
     /// User input.
+    #[default]
     User,
     /// Assistant response.
     Assistant,
@@ -87,18 +108,12 @@ pub enum MessageRole {
     System,
 }
 
-impl Default for MessageRole {
-    fn default() -> Self {
-        MessageRole::User
-    }
-}
-
 /// Statistics about the conversation context.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct ContextStats {
     /// Total message count.
     pub message_count: usize,
-    /// Total token estimate.
+    /// Total tokens, counted with the model's tokenizer.
     pub token_estimate: usize,
     /// User message count.
     pub user_message_count: usize,
@@ -119,6 +134,9 @@ pub struct AgentContext {
     max_tokens: usize,
     /// Current database schema (cached).
     database_schema: Option<String>,
+    /// Tokenizer of the model the context is sent to.
+    #[serde(skip)]
+    token_counter: TokenCounter,
 }
 
 impl Default for AgentContext {
@@ -128,6 +146,7 @@ impl Default for AgentContext {
             max_messages: 50,
             max_tokens: 8000,
             database_schema: None,
+            token_counter: TokenCounter::default(),
         }
     }
 }
@@ -245,11 +264,25 @@ impl AgentContext {
         }
     }
 
-    /// Estimate token count (rough approximation: 4 chars per token).
+    /// Count the tokens of all messages.
     #[must_use]
     pub fn estimate_tokens(&self) -> usize {
-        let total_chars: usize = self.messages.iter().map(|m| m.content.len()).sum();
-        total_chars / 4
+        self.messages
+            .iter()
+            .map(|m| self.token_counter.count(&m.content))
+            .sum()
+    }
+
+    /// Set the tokenizer used to count tokens, pruning to the token limit.
+    pub fn set_token_counter(&mut self, counter: TokenCounter) {
+        self.token_counter = counter;
+        self.prune();
+    }
+
+    /// Get the tokenizer used to count tokens.
+    #[must_use]
+    pub fn token_counter(&self) -> TokenCounter {
+        self.token_counter
     }
 
     /// Check if context is within token limits.
@@ -258,6 +291,28 @@ impl AgentContext {
         self.estimate_tokens() <= self.max_tokens
     }
 
+    /// Whether the context has grown past three quarters of its token limit.
+    ///
+    /// Compacting at this point keeps older turns as a summary instead of
+    /// letting pruning drop them outright.
+    #[must_use]
+    pub fn needs_compaction(&self) -> bool {
+        self.estimate_tokens() > self.max_tokens / 4 * 3
+    }
+
+    /// Replace all but the `keep_recent` newest messages with a summary.
+    pub fn compact(&mut self, keep_recent: usize, summary: &str) {
+        let split = self.messages.len().saturating_sub(keep_recent);
+        if split == 0 {
+            return;
+        }
+        self.messages.drain(..split);
+        self.messages.insert(
+            0,
+            Message::system(format!("Summary of the earlier conversation:\n{}", summary)),
+        );
+    }
+
     /// Set the cached database schema.
     pub fn set_database_schema(&mut self, schema: String) {
         self.database_schema = Some(schema);
@@ -306,10 +361,22 @@ impl AgentContext {
             self.messages.drain(..remove_count);
         }
 
-        // Prune by token estimate
-        while self.estimate_tokens() > self.max_tokens && !self.messages.is_empty() {
-            self.messages.remove(0);
-        }
+        // Prune by token count, counting each message once
+        let counts: Vec<usize> = self
+            .messages
+            .iter()
+            .map(|m| self.token_counter.count(&m.content))
+            .collect();
+        let mut tokens: usize = counts.iter().sum();
+        let remove_count = counts
+            .iter()
+            .take_while(|&&count| {
+                let over = tokens > self.max_tokens;
+                tokens -= count;
+                over
+            })
+            .count();
+        self.messages.drain(..remove_count);
     }
 }
 
@@ -343,6 +410,19 @@ mod tests {
         assert_eq!(ctx.messages()[0].content, "2");
     }
 
+    #[test]
+    fn test_context_compaction() {
+        let mut ctx = AgentContext::with_token_limit(16);
+        ctx.add_user_message("How many orders were placed last week?");
+        ctx.add_assistant_message("There were 42 orders.");
+        assert!(ctx.needs_compaction());
+
+        ctx.compact(1, "User asked about last week's orders.");
+        assert_eq!(ctx.len(), 2);
+        assert_eq!(ctx.messages()[0].role, MessageRole::System);
+        assert_eq!(ctx.messages()[1].content, "There were 42 orders.");
+    }
+
     #[test]
     fn test_context_stats() {
         let mut ctx = AgentContext::new();
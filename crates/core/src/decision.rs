@@ -2,35 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 
-/// A decision made by the agent after reasoning.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type")]
-pub enum AgentDecision {
-    /// Continue reasoning, no tool call needed.
-    #[serde(rename = "reasoning")]
-    Reasoning {
-        /// The reasoning trace.
-        thought: String,
-    },
-    /// Execute a tool call.
-    #[serde(rename = "tool_call")]
-    ToolCall(ToolCall),
-    /// Provide final answer to user.
-    #[serde(rename = "final_answer")]
-    FinalAnswer(String),
-}
-
-/// A tool call made by the agent.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ToolCall {
-    /// Tool name.
-    pub name: String,
-    /// Tool arguments (JSON).
-    pub arguments: serde_json::Value,
-    /// Call ID for tracking.
-    pub call_id: String,
-}
+pub use postgres_agent_llm::decision::{AgentDecision, ToolCall};
 
 /// Result of a tool execution.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,5 +1,6 @@
 //! Core agent errors.
 
+use postgres_agent_safety::ConfirmationLevel;
 use thiserror::Error;
 
 /// Errors that can occur during agent execution.
@@ -7,55 +8,136 @@ use thiserror::Error;
 pub enum AgentError {
     /// Maximum iterations exceeded for query.
     #[error("Maximum iterations ({iterations}) exceeded for query")]
-    MaxIterationsExceeded { iterations: u32 },
+    MaxIterationsExceeded {
+        /// Iteration budget that was exhausted.
+        iterations: u32,
+    },
 
     /// Invalid tool call.
     #[error("Invalid tool call: {details}")]
-    InvalidToolCall { details: String },
+    InvalidToolCall {
+        /// Why the call was invalid.
+        details: String,
+    },
 
     /// Tool execution failed.
     #[error("Tool execution failed: {tool_name} - {reason}")]
-    ToolExecutionFailed { tool_name: String, reason: String },
+    ToolExecutionFailed {
+        /// Name of the failing tool.
+        tool_name: String,
+        /// Failure reason.
+        reason: String,
+    },
 
     /// Context exceeds model limit.
     #[error("Context exceeds model limit: {size} tokens (limit: {limit})")]
-    ContextTooLarge { size: usize, limit: usize },
+    ContextTooLarge {
+        /// Current context size in tokens.
+        size: usize,
+        /// Model limit in tokens.
+        limit: usize,
+    },
 
     /// LLM API error.
     #[error("LLM error: {message}")]
-    LlmError { message: String },
+    LlmError {
+        /// Error details.
+        message: String,
+    },
 
     /// Database error.
     #[error("Database error: {message}")]
-    DatabaseError { message: String },
+    DatabaseError {
+        /// Error details.
+        message: String,
+    },
 
     /// Safety violation.
     #[error("Safety violation: {reason}")]
-    SafetyViolation { reason: String },
+    SafetyViolation {
+        /// Why the operation was blocked.
+        reason: String,
+    },
 
     /// Configuration error.
     #[error("Configuration error: {message}")]
-    ConfigurationError { message: String },
+    ConfigurationError {
+        /// Error details.
+        message: String,
+    },
 
     /// Tool not found.
     #[error("Tool not found: {name}")]
-    ToolNotFound { name: String },
+    ToolNotFound {
+        /// Requested tool name.
+        name: String,
+    },
 
     /// Timeout error.
     #[error("Operation timed out after {seconds}s")]
-    Timeout { seconds: u64 },
+    Timeout {
+        /// Elapsed timeout in seconds.
+        seconds: u64,
+    },
+
+    /// The run was cancelled by the caller.
+    #[error("Run cancelled")]
+    Cancelled,
+
+    /// The run paused until an action is approved.
+    #[error("Waiting for confirmation: {prompt}")]
+    ConfirmationRequired {
+        /// Action to approve.
+        prompt: String,
+        /// Required confirmation level.
+        level: ConfirmationLevel,
+    },
 
     /// Invalid state for operation.
     #[error("Invalid agent state: {state}")]
-    InvalidState { state: String },
+    InvalidState {
+        /// Description of the offending state.
+        state: String,
+    },
 
     /// Conversation history error.
     #[error("History error: {message}")]
-    HistoryError { message: String },
+    HistoryError {
+        /// Error details.
+        message: String,
+    },
 
     /// Serialization error.
     #[error("Serialization error: {message}")]
-    SerializationError { message: String },
+    SerializationError {
+        /// Error details.
+        message: String,
+    },
+
+    /// Per-query token budget exceeded.
+    #[error("Token budget exceeded: {used} tokens (limit: {limit})")]
+    TokenBudgetExceeded {
+        /// Estimated tokens the run would consume.
+        used: u64,
+        /// Configured per-query limit.
+        limit: u64,
+    },
+
+    /// Daily spend budget exceeded.
+    #[error("Daily budget exceeded: ${spent:.4} spent (limit: ${limit:.2})")]
+    DailyBudgetExceeded {
+        /// Estimated spend so far today in USD.
+        spent: f64,
+        /// Configured daily limit in USD.
+        limit: f64,
+    },
+
+    /// Stats store error.
+    #[error("Stats error: {message}")]
+    StatsError {
+        /// Error details.
+        message: String,
+    },
 }
 
 impl AgentError {
@@ -98,12 +180,12 @@ impl AgentError {
     /// Check if this is a retryable error.
     #[must_use]
     pub fn is_retryable(&self) -> bool {
-        match self {
-            AgentError::LlmError { .. } => true,
-            AgentError::Timeout { .. } => true,
-            AgentError::DatabaseError { .. } => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            AgentError::LlmError { .. }
+                | AgentError::Timeout { .. }
+                | AgentError::DatabaseError { .. }
+        )
     }
 
     /// Get a user-friendly error message.
@@ -140,6 +222,10 @@ impl AgentError {
             AgentError::Timeout { seconds } => {
                 format!("Operation timed out after {} seconds", seconds)
             }
+            AgentError::Cancelled => "Query cancelled.".to_string(),
+            AgentError::ConfirmationRequired { prompt, .. } => {
+                format!("Waiting for approval: {}", prompt)
+            }
             AgentError::InvalidState { state } => {
                 format!("Invalid agent state: {}", state)
             }
@@ -149,6 +235,15 @@ impl AgentError {
             AgentError::SerializationError { message } => {
                 format!("Serialization error: {}", message)
             }
+            AgentError::TokenBudgetExceeded { used, limit } => {
+                format!("Query stopped: it would use ~{} tokens (budget: {})", used, limit)
+            }
+            AgentError::DailyBudgetExceeded { spent, limit } => {
+                format!("Daily LLM budget reached (${:.4} of ${:.2})", spent, limit)
+            }
+            AgentError::StatsError { message } => {
+                format!("Stats error: {}", message)
+            }
         }
     }
 }
@@ -0,0 +1,64 @@
+//! Events emitted while the agent runs.
+//!
+//! Front ends subscribe with [`PostgresAgent::set_event_sender`] to show
+//! progress live, e.g. the model's reasoning before the final answer and the
+//! answer itself as it is written.
+//!
+//! [`PostgresAgent::set_event_sender`]: crate::agent::PostgresAgent::set_event_sender
+
+use postgres_agent_safety::{ConfirmationLevel, ConfirmationStatus};
+use serde::{Deserialize, Serialize};
+
+/// Sending half of an agent's event stream.
+pub type EventSender = tokio::sync::mpsc::UnboundedSender<AgentEvent>;
+
+/// Receiving half of an agent's event stream.
+pub type EventReceiver = tokio::sync::mpsc::UnboundedReceiver<AgentEvent>;
+
+/// Progress of an agent run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// The model reasoned without acting.
+    Reasoning {
+        /// Iteration of the run, starting at 1.
+        iteration: u32,
+        /// The reasoning trace.
+        thought: String,
+    },
+    /// More text of the answer, as the model writes it.
+    ///
+    /// Text the model writes before calling a tool arrives this way too; a
+    /// `ToolCall` event of the same iteration follows it then.
+    AnswerDelta {
+        /// Iteration of the run, starting at 1.
+        iteration: u32,
+        /// The text.
+        delta: String,
+    },
+    /// A tool is about to run.
+    ToolCall {
+        /// Iteration of the run, starting at 1.
+        iteration: u32,
+        /// Tool name.
+        name: String,
+    },
+    /// An action's confirmation changed status: the run paused for it
+    /// (`pending`) or resumed with the decision.
+    Confirmation {
+        /// Request ID of the run the action belongs to.
+        request_id: String,
+        /// The action.
+        prompt: String,
+        /// Required confirmation level.
+        level: ConfirmationLevel,
+        /// Status of the confirmation.
+        status: ConfirmationStatus,
+    },
+}
+
+/// Create an event stream.
+#[must_use]
+pub fn channel() -> (EventSender, EventReceiver) {
+    tokio::sync::mpsc::unbounded_channel()
+}
@@ -0,0 +1,295 @@
+//! Few-shot example retrieval.
+//!
+//! Past questions that produced SQL are embedded and the most similar ones
+//! are shown to the model as worked examples for a new question.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use postgres_agent_llm::EmbeddingsClient;
+
+use crate::error::AgentError;
+use crate::stats::{Rating, RunRecord};
+
+/// A past question and the SQL that answered it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    /// Question asked.
+    pub question: String,
+    /// SQL that answered it.
+    pub sql: String,
+}
+
+impl Example {
+    /// Create an example.
+    #[must_use]
+    pub fn new(question: impl Into<String>, sql: impl Into<String>) -> Self {
+        Self {
+            question: question.into(),
+            sql: sql.into(),
+        }
+    }
+
+    /// Examples from logged runs of a profile, best first.
+    ///
+    /// Successful runs with SQL qualify unless rated 👎; runs rated 👍 come
+    /// first, then the most recent. Each question is kept once.
+    #[must_use]
+    pub fn from_runs<'a>(
+        runs: impl IntoIterator<Item = &'a RunRecord>,
+        profile: &str,
+    ) -> Vec<Self> {
+        let mut candidates: Vec<&RunRecord> = runs
+            .into_iter()
+            .filter(|run| run.profile == profile && run.success && !run.question.is_empty())
+            .filter(|run| run.feedback.as_ref().is_none_or(|f| f.rating == Rating::Up))
+            .collect();
+        candidates.reverse();
+        candidates.sort_by_key(|run| run.feedback.is_none());
+
+        let mut seen = HashSet::new();
+        candidates
+            .into_iter()
+            .filter_map(|run| Some(Self::new(run.question.clone(), run.sql.clone()?)))
+            .filter(|example| seen.insert(example.question.trim().to_lowercase()))
+            .collect()
+    }
+}
+
+/// Cosine similarity of two vectors; 0 when either is empty or zero.
+#[must_use]
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 { 0.0 } else { dot / denominator }
+}
+
+/// Render examples as a system prompt section.
+#[must_use]
+pub fn examples_prompt(examples: &[Example]) -> String {
+    let mut prompt = String::from(
+        "## Similar Past Questions\n\nThese questions were answered correctly before. \
+         Reuse their tables and joins where they fit; check the schema if unsure.\n",
+    );
+    for example in examples {
+        let _ = write!(
+            prompt,
+            "\nQuestion: {}\nSQL:\n```sql\n{}\n```\n",
+            example.question.trim(),
+            example.sql.trim()
+        );
+    }
+    prompt
+}
+
+/// Picks the past examples most similar to a question.
+pub struct ExampleRetriever {
+    /// Client embedding questions.
+    embedder: Arc<dyn EmbeddingsClient>,
+    /// Candidate examples.
+    examples: Vec<Example>,
+    /// Embeddings of `examples`, computed on first use.
+    vectors: Vec<Option<Vec<f32>>>,
+    /// Maximum examples returned.
+    k: usize,
+    /// Minimum similarity for an example to be returned.
+    min_similarity: f32,
+}
+
+impl fmt::Debug for ExampleRetriever {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExampleRetriever")
+            .field("model", &self.embedder.model())
+            .field("examples", &self.examples.len())
+            .field("k", &self.k)
+            .field("min_similarity", &self.min_similarity)
+            .finish()
+    }
+}
+
+impl ExampleRetriever {
+    /// Create a retriever returning up to 3 examples with similarity ≥ 0.75.
+    #[must_use]
+    pub fn new(embedder: Arc<dyn EmbeddingsClient>, examples: Vec<Example>) -> Self {
+        let vectors = vec![None; examples.len()];
+        Self {
+            embedder,
+            examples,
+            vectors,
+            k: 3,
+            min_similarity: 0.75,
+        }
+    }
+
+    /// Set the maximum number of examples returned.
+    #[must_use]
+    pub fn with_k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Set the minimum similarity for an example to be returned.
+    #[must_use]
+    pub fn with_min_similarity(mut self, min_similarity: f32) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+
+    /// Number of candidate examples.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    /// Whether there are no candidate examples.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    /// Add an example, replacing any earlier one for the same question.
+    pub fn add(&mut self, example: Example) {
+        let key = example.question.trim().to_lowercase();
+        if let Some(i) = self
+            .examples
+            .iter()
+            .position(|e| e.question.trim().to_lowercase() == key)
+        {
+            if self.examples[i].sql != example.sql {
+                self.examples[i] = example;
+            }
+            return;
+        }
+        self.examples.push(example);
+        self.vectors.push(None);
+    }
+
+    /// The most similar examples to `question`, best first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the embeddings request fails.
+    pub async fn retrieve(&mut self, question: &str) -> Result<Vec<(Example, f32)>, AgentError> {
+        if self.examples.is_empty() || self.k == 0 {
+            return Ok(Vec::new());
+        }
+
+        // Embed the question together with any examples not yet embedded
+        let missing: Vec<usize> = (0..self.examples.len())
+            .filter(|&i| self.vectors[i].is_none())
+            .collect();
+        let mut inputs = vec![question.to_string()];
+        inputs.extend(missing.iter().map(|&i| self.examples[i].question.clone()));
+        let mut vectors = self
+            .embedder
+            .embed(&inputs)
+            .await
+            .map_err(|e| AgentError::llm_error(format!("Embedding examples failed: {}", e)))?
+            .into_iter();
+        let query = vectors.next().unwrap_or_default();
+        for (i, vector) in missing.into_iter().zip(vectors) {
+            self.vectors[i] = Some(vector);
+        }
+
+        let mut scored: Vec<(Example, f32)> = self
+            .examples
+            .iter()
+            .zip(&self.vectors)
+            .filter_map(|(example, vector)| {
+                let score = cosine_similarity(&query, vector.as_deref()?);
+                (score >= self.min_similarity).then(|| (example.clone(), score))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(self.k);
+        Ok(scored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::Feedback;
+    use async_trait::async_trait;
+    use postgres_agent_llm::LlmError;
+
+    /// Embeds text as word counts over a tiny vocabulary.
+    struct BagOfWords;
+
+    #[async_trait]
+    impl EmbeddingsClient for BagOfWords {
+        async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+            let vocabulary = ["orders", "customers", "revenue", "month", "count"];
+            Ok(inputs
+                .iter()
+                .map(|input| {
+                    let input = input.to_lowercase();
+                    vocabulary.iter().map(|w| input.matches(w).count() as f32).collect()
+                })
+                .collect())
+        }
+
+        fn model(&self) -> &str {
+            "bag-of-words"
+        }
+
+        fn dimensions(&self) -> Option<usize> {
+            Some(5)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_most_similar() {
+        let examples = vec![
+            Example::new("Count customers", "SELECT count(*) FROM customers"),
+            Example::new(
+                "Revenue per month",
+                "SELECT date_trunc('month', at), sum(total) FROM orders GROUP BY 1",
+            ),
+            Example::new("Count orders", "SELECT count(*) FROM orders"),
+        ];
+        let mut retriever = ExampleRetriever::new(Arc::new(BagOfWords), examples)
+            .with_k(2)
+            .with_min_similarity(0.5);
+
+        let found = retriever.retrieve("How many orders? count them").await.unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0.question, "Count orders");
+
+        retriever.add(Example::new("Orders revenue by month", "SELECT 1"));
+        assert_eq!(retriever.len(), 4);
+        let found = retriever.retrieve("monthly revenue").await.unwrap();
+        assert_eq!(found[0].0.question, "Revenue per month");
+        assert!(examples_prompt(&[found[0].0.clone()]).contains("sum(total)"));
+    }
+
+    #[test]
+    fn test_examples_from_runs() {
+        let run = |question: &str, rating: Option<Rating>| RunRecord {
+            profile: "default".to_string(),
+            question: question.to_string(),
+            sql: Some(format!("-- {}", question)),
+            success: true,
+            feedback: rating.map(|r| Feedback::new(r, None)),
+            ..RunRecord::default()
+        };
+        let runs = vec![
+            run("liked", Some(Rating::Up)),
+            run("disliked", Some(Rating::Down)),
+            run("plain", None),
+            run("Plain", None),
+        ];
+
+        let examples = Example::from_runs(&runs, "default");
+        let questions: Vec<&str> = examples.iter().map(|e| e.question.as_str()).collect();
+        assert_eq!(questions, vec!["liked", "Plain"]);
+        assert!(Example::from_runs(&runs, "other").is_empty());
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,276 @@
+//! Persistent query history.
+//!
+//! Executed queries and a snapshot of their results are appended to a JSON
+//! Lines file so a later run of the same query can be diffed against them.
+//! With compression or encryption each entry is appended as a frame of its
+//! own. Mutations can carry a snapshot of the tables they changed, which
+//! `pg-agent undo <id>` restores.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_db::Snapshot;
+use postgres_agent_util::codec::FileCodec;
+use postgres_agent_util::retention::RetentionPolicy;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::error::AgentError;
+
+/// Rows kept per result snapshot; larger results are truncated.
+pub const MAX_HISTORY_ROWS: usize = 10_000;
+
+/// A recorded query with its result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// Sequential entry ID, starting at 1.
+    pub id: u64,
+    /// When the query ran.
+    pub timestamp: DateTime<Utc>,
+    /// Database profile the query ran on.
+    pub profile: String,
+    /// SQL that was executed.
+    pub sql: String,
+    /// Result snapshot.
+    pub result: QueryResult,
+    /// Tables as they were before the query, to undo it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undo: Option<Snapshot>,
+}
+
+/// Append-only store of executed queries.
+#[derive(Debug, Clone)]
+pub struct QueryHistory {
+    /// Backing JSON Lines file.
+    path: PathBuf,
+    /// Encoding of new entries.
+    codec: FileCodec,
+}
+
+impl QueryHistory {
+    /// Open the history at the given path; the file is created on first write.
+    #[must_use]
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            codec: FileCodec::default(),
+        }
+    }
+
+    /// Write new entries, and read encrypted ones, with `codec`.
+    #[must_use]
+    pub fn with_codec(mut self, codec: FileCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Get the backing file path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read all entries, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>, AgentError> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = self.codec.read_to_string(&self.path).map_err(|e| {
+            AgentError::HistoryError {
+                message: format!("Failed to read {}: {}", self.path.display(), e),
+            }
+        })?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line).map_err(|e| AgentError::HistoryError {
+                    message: format!("Failed to parse {}: {}", self.path.display(), e),
+                })
+            })
+            .collect()
+    }
+
+    /// Look up an entry by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history cannot be read.
+    pub fn get(&self, id: u64) -> Result<Option<HistoryEntry>, AgentError> {
+        Ok(self.entries()?.into_iter().find(|e| e.id == id))
+    }
+
+    /// Record a query and its result, returning the new entry's ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history cannot be read or written.
+    pub fn record(&self, profile: &str, sql: &str, result: &QueryResult) -> Result<u64, AgentError> {
+        self.append(profile, sql, result, None)
+    }
+
+    /// Record a mutation with the snapshot that undoes it, returning the
+    /// new entry's ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history cannot be read or written.
+    pub fn record_undo(
+        &self,
+        profile: &str,
+        sql: &str,
+        result: &QueryResult,
+        undo: Snapshot,
+    ) -> Result<u64, AgentError> {
+        self.append(profile, sql, result, Some(undo))
+    }
+
+    fn append(
+        &self,
+        profile: &str,
+        sql: &str,
+        result: &QueryResult,
+        undo: Option<Snapshot>,
+    ) -> Result<u64, AgentError> {
+        let id = self.entries()?.last().map_or(1, |e| e.id + 1);
+
+        let mut result = result.clone();
+        if result.rows.len() > MAX_HISTORY_ROWS {
+            result.rows.truncate(MAX_HISTORY_ROWS);
+            result.truncated = true;
+        }
+        let entry = HistoryEntry {
+            id,
+            timestamp: Utc::now(),
+            profile: profile.to_string(),
+            sql: sql.to_string(),
+            result,
+            undo,
+        };
+        let line = serde_json::to_string(&entry).map_err(|e| AgentError::SerializationError {
+            message: e.to_string(),
+        })?;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| AgentError::HistoryError {
+                message: format!("Failed to create {}: {}", parent.display(), e),
+            })?;
+        }
+        let write_error = |e: std::io::Error| AgentError::HistoryError {
+            message: format!("Failed to write {}: {}", self.path.display(), e),
+        };
+        self.codec
+            .append(&self.path, format!("{}\n", line).as_bytes())
+            .map_err(write_error)?;
+
+        Ok(id)
+    }
+
+    /// Remove the entries outside `policy`, oldest first, returning how
+    /// many were removed, with their snapshot files. Sizes are those of the
+    /// uncompressed entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history cannot be read or rewritten.
+    pub fn prune(&self, policy: &RetentionPolicy) -> Result<usize, AgentError> {
+        if policy.is_unlimited() {
+            return Ok(0);
+        }
+        let entries = self.entries()?;
+        let lines = entries
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| AgentError::SerializationError {
+                message: e.to_string(),
+            })?;
+        let items: Vec<(SystemTime, u64)> = entries
+            .iter()
+            .zip(&lines)
+            .map(|(entry, line)| (SystemTime::from(entry.timestamp), line.len() as u64 + 1))
+            .collect();
+        let keep = policy.keep(&items);
+        if keep.len() == entries.len() {
+            return Ok(0);
+        }
+
+        let content: String = keep.iter().map(|&i| format!("{}\n", lines[i])).collect();
+        self.codec
+            .write(&self.path, content.as_bytes())
+            .map_err(|e| AgentError::HistoryError {
+                message: format!("Failed to write {}: {}", self.path.display(), e),
+            })?;
+        let removed = entries
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !keep.contains(i))
+            .filter_map(|(_, entry)| entry.undo.as_ref());
+        for snapshot in removed {
+            if let Err(e) = snapshot.discard() {
+                warn!("Failed to remove snapshot {}: {}", snapshot.dir.display(), e);
+            }
+        }
+        Ok(entries.len() - keep.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_agent_util::compress::Compression;
+
+    #[test]
+    fn test_record_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = QueryHistory::open(dir.path().join("query-history.jsonl"));
+        assert!(history.entries().unwrap().is_empty());
+
+        let result = QueryResult {
+            columns: vec!["id".to_string()],
+            rows: vec![serde_json::json!({"id": 1}).as_object().cloned().unwrap()],
+            row_count: 1,
+            ..QueryResult::default()
+        };
+        assert_eq!(history.record("default", "SELECT 1 AS id", &result).unwrap(), 1);
+        assert_eq!(history.record("default", "SELECT 2 AS id", &result).unwrap(), 2);
+
+        let entry = history.get(2).unwrap().unwrap();
+        assert_eq!(entry.sql, "SELECT 2 AS id");
+        assert_eq!(entry.result.rows.len(), 1);
+        assert!(history.get(3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_compressed_history_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("query-history.jsonl");
+        let result = QueryResult::default();
+        QueryHistory::open(&path).record("default", "SELECT 1", &result).unwrap();
+
+        // Compressed entries follow the plain one in the same file
+        let codec = FileCodec::new(Compression::Zstd { level: 3 });
+        let history = QueryHistory::open(&path).with_codec(codec);
+        history.record("default", "SELECT 2", &result).unwrap();
+        history.record("default", "SELECT 3", &result).unwrap();
+        assert_eq!(history.entries().unwrap().len(), 3);
+
+        // Room for two entries
+        let size = serde_json::to_string(&history.get(1).unwrap().unwrap()).unwrap().len();
+        let policy = RetentionPolicy {
+            max_age: None,
+            max_bytes: Some(2 * size as u64 + 10),
+        };
+        assert_eq!(history.prune(&policy).unwrap(), 1);
+        let sql: Vec<String> = history.entries().unwrap().into_iter().map(|e| e.sql).collect();
+        assert_eq!(sql, ["SELECT 2", "SELECT 3"]);
+        assert_eq!(history.record("default", "SELECT 4", &result).unwrap(), 4);
+    }
+}
@@ -0,0 +1,224 @@
+//! Fast path for trivial requests.
+//!
+//! Requests such as "list tables", "describe users" or "show schema of
+//! orders" map to a single schema tool call. [`classify`] recognizes them so
+//! that the agent can answer from the tool result without an LLM round trip;
+//! anything else, including requests that only look similar, goes to the
+//! full agent.
+
+use serde_json::Value;
+
+use postgres_agent_db::schema::{ColumnInfo, DatabaseSchema};
+
+/// Words dropped before matching, e.g. "please show me all the tables".
+const FILLER_WORDS: &[&str] = &["please", "can", "could", "you", "me", "the", "all", "table"];
+
+/// A request answered by one tool call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Intent {
+    /// List the tables of a schema, or of the default schema.
+    ListTables {
+        /// Schema name.
+        schema: Option<String>,
+    },
+    /// Columns of one table, optionally schema-qualified.
+    DescribeTable {
+        /// Table name.
+        table: String,
+    },
+    /// Tables and columns of the database, or of one table.
+    ShowSchema {
+        /// Table name.
+        table: Option<String>,
+    },
+}
+
+impl Intent {
+    /// Tool name and arguments answering the request.
+    #[must_use]
+    pub fn tool_call(&self) -> (&'static str, Value) {
+        match self {
+            Self::ListTables { schema } => ("list_tables", serde_json::json!({ "schema": schema })),
+            Self::DescribeTable { table } => {
+                ("describe_table", serde_json::json!({ "tableName": table }))
+            }
+            Self::ShowSchema { table } => {
+                ("get_schema", serde_json::json!({ "tableFilter": table }))
+            }
+        }
+    }
+
+    /// Answer from the tool result; `None` if the result is empty or not as
+    /// expected, e.g. for an unknown table, which the model can handle
+    /// better.
+    #[must_use]
+    pub fn answer(&self, result: &Value) -> Option<String> {
+        match self {
+            Self::ListTables { schema } => {
+                let tables: Vec<String> =
+                    serde_json::from_value(result.get("tables")?.clone()).ok()?;
+                if tables.is_empty() {
+                    return None;
+                }
+                let count = match tables.len() {
+                    1 => "1 table".to_string(),
+                    n => format!("{} tables", n),
+                };
+                let heading = match schema {
+                    Some(schema) => format!("{} in schema {}:", count, schema),
+                    None => format!("{}:", count),
+                };
+                Some(format!("{}\n- {}", heading, tables.join("\n- ")))
+            }
+            Self::DescribeTable { .. } => {
+                let columns: Vec<ColumnInfo> =
+                    serde_json::from_value(result.get("columns")?.clone()).ok()?;
+                if columns.is_empty() {
+                    return None;
+                }
+                let name = result.get("sqlName").and_then(Value::as_str)?;
+                Some(describe_columns(name, &columns))
+            }
+            Self::ShowSchema { table } => {
+                let schema: DatabaseSchema = serde_json::from_value(result.clone()).ok()?;
+                // The tool filters by prefix; prefer the table that was asked for
+                let exact: Vec<_> = schema
+                    .tables
+                    .iter()
+                    .filter(|t| table.as_ref().is_some_and(|table| t.table_name == *table))
+                    .collect();
+                let tables = if exact.is_empty() { schema.tables.iter().collect() } else { exact };
+                if tables.is_empty() {
+                    return None;
+                }
+                let sections: Vec<String> = tables
+                    .iter()
+                    .map(|t| {
+                        let name = format!("{}.{}", t.table_schema, t.table_name);
+                        let columns =
+                            schema.columns.get(&t.table_name).map_or(&[][..], Vec::as_slice);
+                        describe_columns(&name, columns)
+                    })
+                    .collect();
+                Some(sections.join("\n\n"))
+            }
+        }
+    }
+}
+
+/// Recognize a trivial request; `None` for anything that needs the model.
+#[must_use]
+pub fn classify(request: &str) -> Option<Intent> {
+    let request = request.trim().trim_end_matches(['.', '?', '!']).to_lowercase();
+    let words: Vec<&str> = request
+        .split_whitespace()
+        .filter(|word| !FILLER_WORDS.contains(word))
+        .collect();
+
+    match words.as_slice() {
+        ["list" | "show", "tables"] => Some(Intent::ListTables { schema: None }),
+        ["list" | "show", "tables", "in", "schema", schema]
+        | ["list" | "show", "tables", "in", schema] => Some(Intent::ListTables {
+            schema: Some(identifier(schema).filter(|s| !s.contains('.'))?),
+        }),
+        ["describe" | "desc", table] | ["show", "columns", "of" | "in" | "from", table] => {
+            Some(Intent::DescribeTable {
+                table: identifier(table)?,
+            })
+        }
+        ["show", "schema"] => Some(Intent::ShowSchema { table: None }),
+        ["show", "schema", "of" | "for", table] | ["schema", "of" | "for", table] => {
+            Some(Intent::ShowSchema {
+                table: Some(identifier(table).filter(|t| !t.contains('.'))?),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// An unquoted, optionally schema-qualified identifier.
+fn identifier(word: &str) -> Option<String> {
+    let word = word.trim_matches(['"', '\'', '`']);
+    let parts: Vec<&str> = word.split('.').collect();
+    let valid = parts.len() <= 2
+        && parts.iter().all(|part| {
+            part.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+                && part.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        });
+    valid.then(|| word.to_string())
+}
+
+/// A table name followed by one line per column.
+fn describe_columns(name: &str, columns: &[ColumnInfo]) -> String {
+    let mut text = format!("Columns of {}:", name);
+    for column in columns {
+        text.push_str(&format!("\n- {} {}", column.column_name, column.data_type));
+        if !column.is_nullable {
+            text.push_str(" NOT NULL");
+        }
+        if let Some(default) = &column.column_default {
+            text.push_str(&format!(" DEFAULT {}", default));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("List tables"), Some(Intent::ListTables { schema: None }));
+        assert_eq!(
+            classify("please show me all the tables in schema Analytics?"),
+            Some(Intent::ListTables {
+                schema: Some("analytics".to_string())
+            })
+        );
+        assert_eq!(
+            classify("describe public.users"),
+            Some(Intent::DescribeTable {
+                table: "public.users".to_string()
+            })
+        );
+        assert_eq!(
+            classify("Show schema of orders."),
+            Some(Intent::ShowSchema {
+                table: Some("orders".to_string())
+            })
+        );
+        assert_eq!(classify("list tables with more than a million rows"), None);
+        assert_eq!(classify("describe the users who signed up today"), None);
+        assert_eq!(classify("describe users; drop table users"), None);
+    }
+
+    #[test]
+    fn test_answer() {
+        let intent = Intent::DescribeTable {
+            table: "users".to_string(),
+        };
+        let result = serde_json::json!({
+            "sqlName": "public.users",
+            "columns": [
+                { "columnName": "id", "dataType": "integer", "isNullable": false,
+                  "columnDefault": "nextval('users_id_seq'::regclass)" },
+                { "columnName": "email", "dataType": "text", "isNullable": true }
+            ]
+        });
+        assert_eq!(
+            intent.answer(&result).as_deref(),
+            Some(
+                "Columns of public.users:\n\
+                 - id integer NOT NULL DEFAULT nextval('users_id_seq'::regclass)\n\
+                 - email text"
+            )
+        );
+        // Unknown tables go to the model
+        assert_eq!(intent.answer(&serde_json::json!({ "columns": [] })), None);
+
+        let intent = Intent::ListTables { schema: None };
+        let result = serde_json::json!({ "tables": ["orders", "users"] });
+        assert_eq!(intent.answer(&result).as_deref(), Some("2 tables:\n- orders\n- users"));
+    }
+}
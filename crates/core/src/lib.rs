@@ -6,8 +6,25 @@ pub mod agent;
 pub mod context;
 pub mod decision;
 pub mod error;
+pub mod events;
+pub mod examples;
+pub mod history;
+pub mod intent;
+pub mod paused;
+pub mod privacy;
+pub mod service;
+pub mod session;
+pub mod stats;
 
-pub use agent::PostgresAgent;
+pub use agent::{ExecutedSql, LlmUsage, PostgresAgent, ResultSet};
 pub use context::AgentContext;
 pub use decision::AgentDecision;
 pub use error::AgentError;
+pub use events::{AgentEvent, EventReceiver, EventSender};
+pub use examples::{Example, ExampleRetriever};
+pub use history::{HistoryEntry, QueryHistory};
+pub use intent::Intent;
+pub use paused::{PausedRun, PausedRunStore};
+pub use service::{AgentService, Session};
+pub use session::{ReportFormat, SessionRecord, SessionStore, SessionTurn};
+pub use stats::{Feedback, Rating, RunRecord, RunSummary, StatsStore, ToolUsage};
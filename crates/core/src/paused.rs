@@ -0,0 +1,173 @@
+//! Runs paused for confirmation.
+//!
+//! With deferred confirmation a run stops at the first action that needs
+//! approval. Its full state is saved so that the decision can arrive minutes
+//! later, possibly in another process, and the run resumes where it stopped.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use postgres_agent_safety::ConfirmationLevel;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{AgentStats, ExecutedSql, ResultSet, TurnRecord};
+use crate::context::AgentContext;
+use crate::decision::ToolCall;
+use crate::error::AgentError;
+
+/// State of a run waiting for approval.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PausedRun {
+    /// Request ID of the run.
+    pub request_id: String,
+    /// The user's question.
+    pub query: String,
+    /// When the run paused.
+    pub paused_at: DateTime<Utc>,
+    /// Action waiting for approval.
+    pub prompt: String,
+    /// Required confirmation level.
+    pub level: ConfirmationLevel,
+    /// Tool call that asked for approval; it runs again on resume.
+    pub pending_call: ToolCall,
+    /// Conversation up to the pending call.
+    pub context: AgentContext,
+    /// Examples prompt of the run.
+    #[serde(default)]
+    pub examples_prompt: Option<String>,
+    /// Iterations started.
+    pub iterations: u32,
+    /// SQL executed by tools so far.
+    #[serde(default)]
+    pub executed_sql: Vec<ExecutedSql>,
+    /// Query results returned by tools so far.
+    #[serde(default)]
+    pub results: Vec<ResultSet>,
+    /// Decision made in each iteration.
+    #[serde(default)]
+    pub trace: Vec<TurnRecord>,
+    /// Statistics of the run so far.
+    #[serde(default)]
+    pub stats: AgentStats,
+}
+
+/// Directory of paused runs, one `<request-id>.json` file each.
+#[derive(Debug, Clone)]
+pub struct PausedRunStore {
+    /// Directory holding the runs.
+    dir: PathBuf,
+}
+
+impl PausedRunStore {
+    /// Open the store in the given directory; it is created on first save.
+    #[must_use]
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Get the store directory.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Save a paused run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, run: &PausedRun) -> Result<(), AgentError> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to create {}: {}", self.dir.display(), e),
+        })?;
+        let path = self.path(&run.request_id)?;
+        let content =
+            serde_json::to_string_pretty(run).map_err(|e| AgentError::SerializationError {
+                message: e.to_string(),
+            })?;
+        std::fs::write(&path, content).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to write {}: {}", path.display(), e),
+        })
+    }
+
+    /// Take a paused run out of the store so that it resumes only once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request ID is invalid or has no paused run,
+    /// or if the file cannot be read or removed.
+    pub fn take(&self, request_id: &str) -> Result<PausedRun, AgentError> {
+        let path = self.path(request_id)?;
+        let content = std::fs::read_to_string(&path).map_err(|e| AgentError::HistoryError {
+            message: format!("No paused run '{}' in {}: {}", request_id, self.dir.display(), e),
+        })?;
+        let run = serde_json::from_str(&content).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to parse {}: {}", path.display(), e),
+        })?;
+        std::fs::remove_file(&path).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to remove {}: {}", path.display(), e),
+        })?;
+        Ok(run)
+    }
+
+    /// File of a paused run; request IDs may come from another process and
+    /// must not name a path outside the store.
+    fn path(&self, request_id: &str) -> Result<PathBuf, AgentError> {
+        let valid = !request_id.is_empty()
+            && request_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(AgentError::HistoryError {
+                message: format!("Invalid request ID '{}'", request_id),
+            });
+        }
+        Ok(self.dir.join(format!("{}.json", request_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = PausedRunStore::open(dir.path());
+        let mut context = AgentContext::new();
+        context.add_user_message("Export the orders");
+        let run = PausedRun {
+            request_id: "req-1".to_string(),
+            query: "Export the orders".to_string(),
+            paused_at: Utc::now(),
+            prompt: "Write 3 rows as Csv to orders.csv?".to_string(),
+            level: ConfirmationLevel::Simple,
+            pending_call: ToolCall {
+                name: "export_results".to_string(),
+                arguments: serde_json::json!({"path": "orders.csv"}),
+                call_id: "call-1".to_string(),
+            },
+            context,
+            examples_prompt: None,
+            iterations: 2,
+            executed_sql: vec![ExecutedSql {
+                sql: "SELECT * FROM orders".to_string(),
+                tool: "execute_query".to_string(),
+                row_count: Some(3),
+                duration_ms: 12,
+                success: true,
+            }],
+            results: Vec::new(),
+            trace: Vec::new(),
+            stats: AgentStats::default(),
+        };
+        store.save(&run).unwrap();
+
+        let loaded = store.take("req-1").unwrap();
+        assert_eq!(loaded.prompt, run.prompt);
+        assert_eq!(loaded.pending_call.arguments, run.pending_call.arguments);
+        assert_eq!(loaded.context.len(), 1);
+        assert_eq!(loaded.iterations, 2);
+        assert!(store.take("req-1").is_err());
+        assert!(store.take("../req-1").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Keeping row values out of the LLM context.
+//!
+//! In metadata-only mode the model sees the shape of each tool result:
+//! columns, row counts and per-column null and distinct counts. Local-only
+//! columns are masked in any mode. The user still gets the full rows
+//! locally.
+
+use std::collections::HashSet;
+
+use postgres_agent_db::{DbError, PgErrorInfo};
+use serde_json::{json, Map, Value};
+
+/// The form of a tool result added to the LLM context in metadata-only
+/// mode.
+#[must_use]
+pub fn withhold_values(tool: &str, result: &Value) -> Value {
+    let mut result = result.clone();
+    // Tools may be called by their qualified name, e.g. `db.suggest_chart`
+    match tool.rsplit('.').next().unwrap_or(tool) {
+        "suggest_chart" => {
+            if let Some(output) = result.as_object_mut() {
+                output.remove("ascii");
+            }
+            if let Some(data) = result.pointer_mut("/vegaLite/data").and_then(Value::as_object_mut)
+            {
+                let rows = data.remove("values").and_then(|v| v.as_array().map(Vec::len));
+                data.insert("rowCount".to_string(), json!(rows.unwrap_or(0)));
+                data.insert("rowsWithheld".to_string(), json!(true));
+            }
+        }
+        "detect_anomalies" => {
+            let anomalies = result.get_mut("anomalies").and_then(Value::as_array_mut);
+            for anomaly in anomalies.into_iter().flatten().filter_map(Value::as_object_mut) {
+                anomaly.remove("bucket");
+                anomaly.remove("value");
+            }
+        }
+        _ => summarize_rows(&mut result),
+    }
+    result
+}
+
+/// Replace the rows of every `columns`/`rows` result in `value` with
+/// per-column counts.
+fn summarize_rows(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let columns = map.get("columns").and_then(Value::as_array).map(|columns| {
+                columns.iter().filter_map(Value::as_str).map(str::to_string).collect::<Vec<_>>()
+            });
+            if let Some(columns) = columns
+                && let Some(Value::Array(rows)) = map.remove("rows")
+            {
+                let stats: Vec<Value> =
+                    columns.iter().map(|column| column_stats(column, &rows)).collect();
+                map.insert("columnStats".to_string(), Value::Array(stats));
+                map.insert("rowsWithheld".to_string(), json!(true));
+                // Keyset cursors hold the sort key of the last row
+                map.remove("nextCursor");
+            }
+            map.values_mut().for_each(summarize_rows);
+        }
+        Value::Array(items) => items.iter_mut().for_each(summarize_rows),
+        _ => {}
+    }
+}
+
+/// Text local-only values are replaced with.
+pub const MASKED_VALUE: &str = "[local-only]";
+
+/// Whether `column` is one of the local-only `columns`; names compare
+/// case-insensitively.
+#[must_use]
+pub fn is_local_only(column: &str, columns: &[String]) -> bool {
+    columns.iter().any(|c| c.eq_ignore_ascii_case(column))
+}
+
+/// Mask the values of local-only `columns` in the rows of a tool result.
+///
+/// Columns are matched by their name in the result, so an alias such as
+/// `email AS contact` is not masked.
+pub fn mask_columns(tool: &str, result: &mut Value, columns: &[String]) {
+    if columns.is_empty() {
+        return;
+    }
+    if tool.rsplit('.').next().unwrap_or(tool) == "suggest_chart" {
+        let axes = ["x", "y"].map(|axis| result.get(axis).and_then(Value::as_str));
+        if axes.into_iter().flatten().any(|axis| is_local_only(axis, columns))
+            && let Some(output) = result.as_object_mut()
+        {
+            output.remove("ascii");
+        }
+    }
+    mask_rows(result, columns);
+}
+
+/// Mask local-only values in every `rows` or `values` array of row objects
+/// in `value`.
+fn mask_rows(value: &mut Value, columns: &[String]) {
+    match value {
+        Value::Object(map) => {
+            let mut masked = false;
+            for key in ["rows", "values"] {
+                let rows = map.get_mut(key).and_then(Value::as_array_mut);
+                for row in rows.into_iter().flatten().filter_map(Value::as_object_mut) {
+                    for (column, cell) in row.iter_mut() {
+                        if !cell.is_null() && is_local_only(column, columns) {
+                            *cell = json!(MASKED_VALUE);
+                            masked = true;
+                        }
+                    }
+                }
+            }
+            if masked {
+                // The cursor may hold a masked sort key
+                map.remove("nextCursor");
+            }
+            map.values_mut().for_each(|v| mask_rows(v, columns));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| mask_rows(v, columns)),
+        _ => {}
+    }
+}
+
+/// Null count, distinct count and JSON types of one column.
+fn column_stats(column: &str, rows: &[Value]) -> Value {
+    let mut nulls = 0;
+    let mut distinct = HashSet::new();
+    let mut types = Vec::new();
+    for value in rows.iter().filter_map(|row| row.get(column)) {
+        if value.is_null() {
+            nulls += 1;
+            continue;
+        }
+        let kind = json_type(value);
+        if !types.contains(&kind) {
+            types.push(kind);
+        }
+        distinct.insert(value.to_string());
+    }
+    let mut stats = Map::new();
+    stats.insert("name".to_string(), json!(column));
+    stats.insert("types".to_string(), json!(types));
+    stats.insert("nulls".to_string(), json!(nulls));
+    stats.insert("distinct".to_string(), json!(distinct.len()));
+    Value::Object(stats)
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// A database error message without its DETAIL, which often quotes row
+/// values, e.g. the conflicting key of a unique violation.
+#[must_use]
+pub fn error_without_detail(error: &DbError) -> String {
+    match error.pg_error() {
+        Some(info) if info.detail.is_some() => format!(
+            "Query failed: {}",
+            PgErrorInfo {
+                detail: None,
+                ..info.clone()
+            }
+        ),
+        _ => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_withhold_query_rows() {
+        let result = json!({
+            "columns": ["email", "plan"],
+            "rows": [
+                { "email": "ann@example.com", "plan": "pro" },
+                { "email": "bob@example.com", "plan": "pro" },
+                { "email": null, "plan": "free" }
+            ],
+            "rowCount": 3,
+            "truncated": true,
+            "nextCursor": "7b226f6666736574223a337d"
+        });
+        let withheld = withhold_values("db.execute_query", &result);
+        assert!(!withheld.to_string().contains("example.com"));
+        assert_eq!(withheld["rowCount"], 3);
+        assert_eq!(withheld["rowsWithheld"], true);
+        assert!(withheld.get("nextCursor").is_none());
+        assert_eq!(
+            withheld["columnStats"],
+            json!([
+                { "name": "email", "types": ["string"], "nulls": 1, "distinct": 2 },
+                { "name": "plan", "types": ["string"], "nulls": 0, "distinct": 2 }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_withhold_chart_and_anomaly_values() {
+        let chart = json!({
+            "x": "week",
+            "y": "revenue",
+            "vegaLite": { "data": { "values": [{ "week": "2024-01-01", "revenue": 1234 }] } },
+            "ascii": "2024-01-01 | #### 1234"
+        });
+        let withheld = withhold_values("suggest_chart", &chart);
+        assert!(!withheld.to_string().contains("1234"));
+        assert_eq!(withheld["vegaLite"]["data"]["rowCount"], 1);
+
+        let anomalies = json!({
+            "buckets": 8,
+            "anomalies": [{ "bucket": "2024-01-08", "value": 60.0, "score": 3.1 }]
+        });
+        let withheld = withhold_values("detect_anomalies", &anomalies);
+        assert_eq!(withheld["anomalies"], json!([{ "score": 3.1 }]));
+    }
+
+    #[test]
+    fn test_mask_local_only_columns() {
+        let mut result = json!({
+            "columns": ["id", "SSN", "note"],
+            "rows": [
+                { "id": 1, "SSN": "078-05-1120", "note": "vip" },
+                { "id": 2, "SSN": null, "note": null }
+            ],
+            "nextCursor": "7b7d"
+        });
+        mask_columns("execute_query", &mut result, &["ssn".to_string()]);
+        assert_eq!(
+            result["rows"],
+            json!([
+                { "id": 1, "SSN": "[local-only]", "note": "vip" },
+                { "id": 2, "SSN": null, "note": null }
+            ])
+        );
+        assert!(result.get("nextCursor").is_none());
+
+        let mut chart = json!({
+            "x": "email",
+            "y": "orders",
+            "vegaLite": { "data": { "values": [{ "email": "ann@example.com", "orders": 3 }] } },
+            "ascii": "ann@example.com | ### 3"
+        });
+        mask_columns("db.suggest_chart", &mut chart, &["email".to_string()]);
+        assert!(!chart.to_string().contains("ann@example.com"));
+        assert_eq!(chart["vegaLite"]["data"]["values"][0]["orders"], 3);
+    }
+}
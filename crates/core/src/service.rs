@@ -0,0 +1,196 @@
+//! Multi-session agent service.
+//!
+//! An [`AgentService`] holds what sessions share: the LLM clients, the tool
+//! registry with its database pool, the agent configuration and the tool
+//! context settings. Each session is an agent of its own with a separate
+//! conversation, run state, statistics and last query result, so sessions
+//! can run concurrently.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use postgres_agent_llm::LlmClient;
+use postgres_agent_tools::{ToolContext, ToolRegistry};
+use tokio_util::sync::CancellationToken;
+
+use crate::agent::{AgentConfig, AgentResponse, PostgresAgent};
+use crate::error::AgentError;
+
+/// A session's agent; runs on one session are serialized.
+pub type Session<Client> = Arc<tokio::sync::Mutex<PostgresAgent<Client>>>;
+
+/// Serves many concurrent sessions on shared resources.
+#[derive(Debug)]
+pub struct AgentService<Client: LlmClient> {
+    /// LLM client shared by all sessions.
+    llm_client: Arc<Client>,
+    /// Cheaper client for intermediate reasoning, if configured.
+    reasoning_client: Option<Arc<Client>>,
+    /// Tool registry shared by all sessions.
+    tools: Arc<ToolRegistry>,
+    /// Configuration of every session's agent.
+    config: AgentConfig,
+    /// Tool context settings copied into each session.
+    tool_context: ToolContext,
+    /// Open sessions by ID.
+    sessions: Mutex<HashMap<String, Session<Client>>>,
+}
+
+impl<Client: LlmClient> AgentService<Client> {
+    /// Create a service without sessions.
+    #[must_use]
+    pub fn new(llm_client: Client, tools: ToolRegistry, config: AgentConfig) -> Self {
+        Self {
+            llm_client: Arc::new(llm_client),
+            reasoning_client: None,
+            tools: Arc::new(tools),
+            config,
+            tool_context: ToolContext::default(),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Route intermediate turns of every session to a cheaper model.
+    #[must_use]
+    pub fn with_reasoning_client(mut self, client: Client) -> Self {
+        self.reasoning_client = Some(Arc::new(client));
+        self
+    }
+
+    /// Set the tool context settings, such as the confirmer and audit log,
+    /// of new sessions.
+    #[must_use]
+    pub fn with_tool_context(mut self, tool_context: ToolContext) -> Self {
+        self.tool_context = tool_context;
+        self
+    }
+
+    /// Open a new session, returning its ID.
+    pub fn create_session(&self) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut agent = PostgresAgent::with_shared(
+            self.llm_client.clone(),
+            self.tools.clone(),
+            self.config.clone(),
+        );
+        agent.set_tool_context(self.tool_context.for_session());
+        if let Some(client) = &self.reasoning_client {
+            agent.set_reasoning_client(client.clone());
+        }
+        self.lock_sessions()
+            .insert(id.clone(), Arc::new(tokio::sync::Mutex::new(agent)));
+        id
+    }
+
+    /// Get an open session.
+    #[must_use]
+    pub fn session(&self, id: &str) -> Option<Session<Client>> {
+        self.lock_sessions().get(id).cloned()
+    }
+
+    /// Close a session; returns `false` if it was not open.
+    ///
+    /// A run in progress on the session finishes first.
+    pub fn close_session(&self, id: &str) -> bool {
+        self.lock_sessions().remove(id).is_some()
+    }
+
+    /// Number of open sessions.
+    #[must_use]
+    pub fn session_count(&self) -> usize {
+        self.lock_sessions().len()
+    }
+
+    /// Run a query in a session, waiting for any run already in progress on
+    /// the same session.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AgentError::InvalidState` if the session is not open, and
+    /// otherwise the errors of [`PostgresAgent::run`].
+    pub async fn run(
+        &self,
+        session_id: &str,
+        query: &str,
+        cancel: &CancellationToken,
+    ) -> Result<AgentResponse, AgentError> {
+        let session = self.session(session_id).ok_or_else(|| AgentError::InvalidState {
+            state: format!("no session '{}'", session_id),
+        })?;
+        let mut agent = session.lock().await;
+        agent.run(query, cancel).await
+    }
+
+    /// Lock the session table.
+    fn lock_sessions(&self) -> std::sync::MutexGuard<'_, HashMap<String, Session<Client>>> {
+        self.sessions.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_agent_llm::provider::ProviderInfo;
+    use postgres_agent_llm::LlmError;
+    use serde_json::Value;
+
+    /// Answers every question with the last user message.
+    #[derive(Debug)]
+    struct EchoClient;
+
+    #[async_trait::async_trait]
+    impl LlmClient for EchoClient {
+        async fn complete(&self, _prompt: &str) -> Result<String, LlmError> {
+            Ok(String::new())
+        }
+
+        async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+            let question = context_json["messages"]
+                .as_array()
+                .and_then(|messages| messages.last())
+                .and_then(|message| message["content"].as_str())
+                .unwrap_or_default()
+                .to_string();
+            tokio::task::yield_now().await;
+            Ok(serde_json::json!({"type": "final_answer", "answer": question}))
+        }
+
+        fn provider_info(&self) -> ProviderInfo {
+            ProviderInfo {
+                provider: "Mock".to_string(),
+                model: "echo".to_string(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_sessions() {
+        let service = Arc::new(AgentService::new(
+            EchoClient,
+            ToolRegistry::default(),
+            AgentConfig::default(),
+        ));
+        let first = service.create_session();
+        let second = service.create_session();
+        assert_eq!(service.session_count(), 2);
+
+        let run = |id: String, query: &'static str| {
+            let service = service.clone();
+            tokio::spawn(async move {
+                service.run(&id, query, &CancellationToken::new()).await
+            })
+        };
+        let (a, b) = tokio::join!(run(first.clone(), "orders?"), run(second.clone(), "users?"));
+        assert_eq!(a.unwrap().unwrap().answer, "orders?");
+        assert_eq!(b.unwrap().unwrap().answer, "users?");
+
+        // Each session keeps its own conversation
+        let session = service.session(&first).unwrap();
+        let messages = session.lock().await.context.messages().to_vec();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "orders?");
+
+        assert!(service.close_session(&second));
+        assert!(service.run(&second, "again", &CancellationToken::new()).await.is_err());
+    }
+}
@@ -0,0 +1,653 @@
+//! Saved interactive sessions.
+//!
+//! Each interactive session is stored as a JSON file holding its questions,
+//! generated SQL, truncated result tables, timings and answers, and can be
+//! exported as a Markdown or HTML report. Files may be zstd compressed and
+//! encrypted; they keep their `.json` name either way.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_llm::PromptMessage;
+use postgres_agent_util::codec::FileCodec;
+use postgres_agent_util::number::NumberFormat;
+use postgres_agent_util::retention::RetentionPolicy;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::ResultSet;
+use crate::context::{Message, MessageRole};
+use crate::error::AgentError;
+
+/// Rows kept per result table in a saved session.
+pub const MAX_SESSION_ROWS: usize = 200;
+
+/// One question and its outcome.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTurn {
+    /// When the question was asked.
+    pub asked_at: DateTime<Utc>,
+    /// The user's question.
+    pub question: String,
+    /// Final answer, if the run succeeded.
+    #[serde(default)]
+    pub answer: Option<String>,
+    /// Error message, if the run failed.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Executed SQL.
+    #[serde(default)]
+    pub sql: Option<String>,
+    /// Query results, in order, truncated to [`MAX_SESSION_ROWS`].
+    #[serde(default)]
+    pub results: Vec<ResultSet>,
+    /// Wall-clock duration of the run in milliseconds.
+    pub duration_ms: u64,
+    /// Reasoning iterations.
+    pub iterations: u32,
+}
+
+/// A saved session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRecord {
+    /// Session ID, also the file name.
+    pub id: String,
+    /// Database profile.
+    pub profile: String,
+    /// LLM model.
+    pub model: String,
+    /// When the session started.
+    pub started_at: DateTime<Utc>,
+    /// Questions asked, in order.
+    #[serde(default)]
+    pub turns: Vec<SessionTurn>,
+}
+
+impl SessionRecord {
+    /// Start a new session.
+    #[must_use]
+    pub fn new(profile: impl Into<String>, model: impl Into<String>) -> Self {
+        let started_at = Utc::now();
+        let suffix = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            id: format!("{}-{}", started_at.format("%Y%m%d-%H%M%S"), &suffix[..4]),
+            profile: profile.into(),
+            model: model.into(),
+            started_at,
+            turns: Vec::new(),
+        }
+    }
+
+    /// The conversation of the session: each question, then its SQL and
+    /// answer, or the error it ended with.
+    #[must_use]
+    pub fn prompt_messages(&self) -> Vec<PromptMessage> {
+        let mut messages = Vec::with_capacity(self.turns.len() * 2);
+        for turn in &self.turns {
+            messages.push(PromptMessage::User {
+                content: turn.question.clone(),
+            });
+            let outcome = match (&turn.answer, &turn.error) {
+                (Some(answer), _) => answer.clone(),
+                (None, Some(error)) => format!("Failed: {}", error),
+                (None, None) => continue,
+            };
+            let content = match &turn.sql {
+                Some(sql) => format!("SQL: {}\n{}", sql, outcome),
+                None => outcome,
+            };
+            messages.push(PromptMessage::Assistant {
+                content,
+                tool_calls: Vec::new(),
+            });
+        }
+        messages
+    }
+}
+
+/// Messages with the outputs of `execute_query` since the last user message.
+fn latest_query_outputs(messages: &[Message]) -> impl Iterator<Item = &Message> {
+    let start = messages
+        .iter()
+        .rposition(|m| m.role == MessageRole::User)
+        .map_or(0, |i| i + 1);
+    messages[start..]
+        .iter()
+        .filter(|m| {
+            m.tool_name
+                .as_deref()
+                .is_some_and(|name| name.rsplit('.').next() == Some("execute_query"))
+        })
+}
+
+/// SQL and next-page cursor of the last truncated query since the last
+/// user message.
+#[must_use]
+pub fn truncated_query(messages: &[Message]) -> Option<(String, String)> {
+    latest_query_outputs(messages)
+        .filter_map(|m| serde_json::from_str::<serde_json::Value>(&m.content).ok())
+        .filter_map(|output| {
+            let sql = output["sql"].as_str()?;
+            let cursor = output["nextCursor"].as_str()?;
+            Some((sql.to_string(), cursor.to_string()))
+        })
+        .last()
+}
+
+/// Query results returned by `execute_query` since the last user message.
+#[must_use]
+pub fn latest_query_results(messages: &[Message]) -> Vec<ResultSet> {
+    let results: Vec<ResultSet> = latest_query_outputs(messages)
+        .filter_map(|m| {
+            let output: serde_json::Value = serde_json::from_str(&m.content).ok()?;
+            Some(ResultSet {
+                tool: m.tool_name.clone().unwrap_or_default(),
+                sql: output["sql"].as_str().map(str::to_string),
+                result: QueryResult::deserialize(output).ok()?,
+            })
+        })
+        .collect();
+    session_results(&results)
+}
+
+/// Query results of a run as kept in a session, truncated to
+/// [`MAX_SESSION_ROWS`].
+#[must_use]
+pub fn session_results(results: &[ResultSet]) -> Vec<ResultSet> {
+    results
+        .iter()
+        .map(|set| {
+            let mut set = set.clone();
+            if set.result.rows.len() > MAX_SESSION_ROWS {
+                set.result.rows.truncate(MAX_SESSION_ROWS);
+                set.result.truncated = true;
+            }
+            set
+        })
+        .collect()
+}
+
+/// Directory of saved sessions.
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    /// Directory holding `<id>.json` files.
+    dir: PathBuf,
+    /// Encoding of saved sessions.
+    codec: FileCodec,
+}
+
+impl SessionStore {
+    /// Open the store in the given directory; it is created on first save.
+    #[must_use]
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            codec: FileCodec::default(),
+        }
+    }
+
+    /// Save sessions, and read encrypted ones, with `codec`.
+    #[must_use]
+    pub fn with_codec(mut self, codec: FileCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Get the store directory.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write a session, replacing any earlier version.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self, session: &SessionRecord) -> Result<(), AgentError> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to create {}: {}", self.dir.display(), e),
+        })?;
+        let path = self.dir.join(format!("{}.json", session.id));
+        let content =
+            serde_json::to_string_pretty(session).map_err(|e| AgentError::SerializationError {
+                message: e.to_string(),
+            })?;
+        self.codec
+            .write(&path, content.as_bytes())
+            .map_err(|e| AgentError::HistoryError {
+                message: format!("Failed to write {}: {}", path.display(), e),
+            })
+    }
+
+    /// Load a session by ID or unique ID prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no session or several sessions match, or if the
+    /// file cannot be read.
+    pub fn load(&self, id: &str) -> Result<SessionRecord, AgentError> {
+        let matches: Vec<String> = self
+            .ids()?
+            .into_iter()
+            .filter(|s| s.starts_with(id))
+            .collect();
+        let id = match matches.as_slice() {
+            [single] => single,
+            [] => {
+                return Err(AgentError::HistoryError {
+                    message: format!("No session '{}' in {}", id, self.dir.display()),
+                });
+            }
+            _ if matches.iter().any(|m| m == id) => id,
+            _ => {
+                return Err(AgentError::HistoryError {
+                    message: format!("Session '{}' is ambiguous: {}", id, matches.join(", ")),
+                });
+            }
+        };
+
+        let path = self.dir.join(format!("{}.json", id));
+        let content = self.codec.read_to_string(&path).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to read {}: {}", path.display(), e),
+        })?;
+        serde_json::from_str(&content).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to parse {}: {}", path.display(), e),
+        })
+    }
+
+    /// IDs of all saved sessions, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory exists but cannot be read.
+    pub fn ids(&self) -> Result<Vec<String>, AgentError> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to read {}: {}", self.dir.display(), e),
+        })?;
+        let mut ids: Vec<String> = entries
+            .filter_map(Result::ok)
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Delete the sessions outside `policy`, oldest first, returning how
+    /// many were deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be read or a file cannot be
+    /// deleted.
+    pub fn prune(&self, policy: &RetentionPolicy) -> Result<usize, AgentError> {
+        let files: Vec<PathBuf> = self
+            .ids()?
+            .iter()
+            .map(|id| self.dir.join(format!("{}.json", id)))
+            .collect();
+        policy.prune(&files).map_err(|e| AgentError::HistoryError {
+            message: format!("Failed to clean up {}: {}", self.dir.display(), e),
+        })
+    }
+}
+
+/// Session report format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// GitHub-flavored Markdown.
+    Markdown,
+    /// Standalone HTML page.
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            other => Err(format!(
+                "Unknown report format '{}' (expected markdown or html)",
+                other
+            )),
+        }
+    }
+}
+
+/// Render a session as a report, showing at most `max_rows` rows per table
+/// and numbers in the `numbers` format.
+#[must_use]
+pub fn render_report(
+    session: &SessionRecord,
+    format: ReportFormat,
+    max_rows: usize,
+    numbers: NumberFormat,
+) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(session, max_rows, numbers),
+        ReportFormat::Html => render_html(session, max_rows, numbers),
+    }
+}
+
+/// Describe how many rows a truncated table shows.
+fn truncation_note(result: &QueryResult, shown: usize) -> Option<String> {
+    let total = result.row_count.max(result.rows.len());
+    (shown < total || result.truncated).then(|| {
+        format!(
+            "Showing {} of {}{} rows.",
+            shown,
+            total,
+            if result.truncated { "+" } else { "" }
+        )
+    })
+}
+
+/// Render a turn's metadata line.
+fn turn_meta(turn: &SessionTurn) -> String {
+    format!(
+        "{} · {} ms · {} iteration{}",
+        turn.asked_at.format("%H:%M:%S"),
+        turn.duration_ms,
+        turn.iterations,
+        if turn.iterations == 1 { "" } else { "s" }
+    )
+}
+
+fn render_markdown(session: &SessionRecord, max_rows: usize, numbers: NumberFormat) -> String {
+    let escape = |s: &str| s.replace('|', "\\|").replace(['\n', '\r'], " ");
+    let mut out = String::new();
+    let _ = writeln!(out, "# Session {}\n", session.id);
+    let _ = writeln!(out, "- Profile: {}", session.profile);
+    let _ = writeln!(out, "- Model: {}", session.model);
+    let _ = writeln!(
+        out,
+        "- Started: {}",
+        session.started_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    let _ = writeln!(out, "- Questions: {}", session.turns.len());
+
+    for (i, turn) in session.turns.iter().enumerate() {
+        let _ = writeln!(out, "\n## {}. {}\n", i + 1, escape(&turn.question));
+        let _ = writeln!(out, "_{}_", turn_meta(turn));
+        if let Some(sql) = &turn.sql {
+            let _ = writeln!(out, "\n```sql\n{}\n```", sql.trim());
+        }
+        for (n, set) in turn.results.iter().enumerate() {
+            let result = &set.result;
+            if result.columns.is_empty() {
+                continue;
+            }
+            let label = set.label();
+            if !label.is_empty() {
+                let _ = writeln!(out, "\n**Result {}:** `{}`", n + 1, label.replace('`', "'"));
+            }
+            let _ = writeln!(
+                out,
+                "\n| {} |",
+                result
+                    .columns
+                    .iter()
+                    .map(|c| escape(c))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            );
+            let _ = writeln!(out, "|{}", "---|".repeat(result.columns.len()));
+            let shown = result.rows.len().min(max_rows);
+            for row in &result.rows[..shown] {
+                let cells: Vec<String> = (0..result.columns.len())
+                    .map(|i| escape(&result.display_cell(row, i, numbers)))
+                    .collect();
+                let _ = writeln!(out, "| {} |", cells.join(" | "));
+            }
+            if let Some(note) = truncation_note(result, shown) {
+                let _ = writeln!(out, "\n_{}_", note);
+            }
+        }
+        if let Some(answer) = &turn.answer {
+            let _ = writeln!(out, "\n**Answer:** {}", answer.trim());
+        }
+        if let Some(error) = &turn.error {
+            let _ = writeln!(out, "\n**Error:** {}", error.trim());
+        }
+    }
+    out
+}
+
+/// Escape text for HTML.
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(session: &SessionRecord, max_rows: usize, numbers: NumberFormat) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+    );
+    let _ = writeln!(out, "<title>Session {}</title>", html_escape(&session.id));
+    let _ = writeln!(
+        out,
+        "<style>body{{font-family:sans-serif;max-width:60em;margin:2em auto}}\
+         table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:.2em .5em}}\
+         pre{{background:#f4f4f4;padding:.5em}}.meta{{color:#666}}</style>\n</head>\n<body>"
+    );
+    let _ = writeln!(out, "<h1>Session {}</h1>", html_escape(&session.id));
+    let _ = writeln!(
+        out,
+        "<p class=\"meta\">Profile {} · Model {} · Started {} · {} questions</p>",
+        html_escape(&session.profile),
+        html_escape(&session.model),
+        session.started_at.format("%Y-%m-%d %H:%M UTC"),
+        session.turns.len()
+    );
+
+    for (i, turn) in session.turns.iter().enumerate() {
+        let _ = writeln!(out, "<h2>{}. {}</h2>", i + 1, html_escape(&turn.question));
+        let _ = writeln!(out, "<p class=\"meta\">{}</p>", turn_meta(turn));
+        if let Some(sql) = &turn.sql {
+            let _ = writeln!(out, "<pre><code>{}</code></pre>", html_escape(sql.trim()));
+        }
+        for (n, set) in turn.results.iter().enumerate() {
+            let result = &set.result;
+            if result.columns.is_empty() {
+                continue;
+            }
+            let label = set.label();
+            if !label.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "<p class=\"meta\">Result {}: <code>{}</code></p>",
+                    n + 1,
+                    html_escape(&label)
+                );
+            }
+            let _ = write!(out, "<table>\n<tr>");
+            for column in &result.columns {
+                let _ = write!(out, "<th>{}</th>", html_escape(column));
+            }
+            let _ = writeln!(out, "</tr>");
+            let shown = result.rows.len().min(max_rows);
+            for row in &result.rows[..shown] {
+                let _ = write!(out, "<tr>");
+                for i in 0..result.columns.len() {
+                    let cell = result.display_cell(row, i, numbers);
+                    let _ = write!(out, "<td>{}</td>", html_escape(&cell));
+                }
+                let _ = writeln!(out, "</tr>");
+            }
+            let _ = writeln!(out, "</table>");
+            if let Some(note) = truncation_note(result, shown) {
+                let _ = writeln!(out, "<p class=\"meta\">{}</p>", note);
+            }
+        }
+        if let Some(answer) = &turn.answer {
+            let _ = writeln!(
+                out,
+                "<p><strong>Answer:</strong> {}</p>",
+                html_escape(answer.trim())
+            );
+        }
+        if let Some(error) = &turn.error {
+            let _ = writeln!(
+                out,
+                "<p><strong>Error:</strong> {}</p>",
+                html_escape(error.trim())
+            );
+        }
+    }
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_agent_util::compress::Compression;
+    use postgres_agent_util::encrypt::EncryptionKey;
+
+    fn session() -> SessionRecord {
+        let mut session = SessionRecord::new("default", "gpt-4o");
+        session.turns.push(SessionTurn {
+            asked_at: Utc::now(),
+            question: "Top customers?".to_string(),
+            answer: Some("Ada <3 leads.".to_string()),
+            sql: Some("SELECT name, total FROM customers".to_string()),
+            results: vec![ResultSet {
+                tool: "execute_query".to_string(),
+                sql: Some("SELECT name, total FROM customers".to_string()),
+                result: QueryResult {
+                    columns: vec!["name".to_string(), "total".to_string()],
+                    rows: (0..3)
+                        .map(|i| {
+                            serde_json::json!({"name": format!("c|{}", i), "total": i * 1000})
+                                .as_object()
+                                .cloned()
+                                .unwrap()
+                        })
+                        .collect(),
+                    row_count: 3,
+                    ..QueryResult::default()
+                },
+            }],
+            duration_ms: 42,
+            iterations: 2,
+            ..SessionTurn::default()
+        });
+        session
+    }
+
+    #[test]
+    fn test_prompt_messages() {
+        let mut session = session();
+        session.turns.push(SessionTurn {
+            question: "And last month?".to_string(),
+            error: Some("Run timed out".to_string()),
+            ..SessionTurn::default()
+        });
+
+        let messages = session.prompt_messages();
+        assert_eq!(messages.len(), 4);
+        assert!(matches!(
+            &messages[0],
+            PromptMessage::User { content } if content == "Top customers?"
+        ));
+        assert!(matches!(
+            &messages[1],
+            PromptMessage::Assistant { content, .. }
+                if content == "SQL: SELECT name, total FROM customers\nAda <3 leads."
+        ));
+        assert!(matches!(
+            &messages[3],
+            PromptMessage::Assistant { content, .. } if content == "Failed: Run timed out"
+        ));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let report = render_report(&session(), ReportFormat::Markdown, 2, NumberFormat::Raw);
+        assert!(report.contains("## 1. Top customers?"));
+        assert!(report.contains("```sql\nSELECT name, total FROM customers\n```"));
+        assert!(report.contains("**Result 1:** `SELECT name, total FROM customers`"));
+        assert!(report.contains("| c\\|1 | 1000 |"));
+        assert!(!report.contains("c\\|2"));
+        assert!(report.contains("_Showing 2 of 3 rows._"));
+        assert!(report.contains("**Answer:** Ada <3 leads."));
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let human = NumberFormat::Human { precision: None };
+        let report = render_report(&session(), ReportFormat::Html, 10, human);
+        assert!(report.contains("<td>c|2</td><td>2,000</td>"));
+        assert!(report.contains("Result 1: <code>SELECT name, total FROM customers</code>"));
+        assert!(report.contains("Ada &lt;3 leads."));
+        assert_eq!("HTML".parse::<ReportFormat>(), Ok(ReportFormat::Html));
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SessionStore::open(dir.path());
+        let session = session();
+        store.save(&session).unwrap();
+
+        let loaded = store.load(&session.id[..8]).unwrap();
+        assert_eq!(loaded.turns.len(), 1);
+        assert_eq!(store.ids().unwrap(), vec![session.id.clone()]);
+        assert!(store.load("missing").is_err());
+
+        let codec = FileCodec::new(Compression::Zstd { level: 3 })
+            .with_key(EncryptionKey::generate());
+        let encrypted = SessionStore::open(dir.path()).with_codec(codec);
+        encrypted.save(&session).unwrap();
+        assert_eq!(encrypted.load(&session.id).unwrap().turns.len(), 1);
+        assert!(store.load(&session.id).is_err());
+        assert!(std::fs::read_to_string(dir.path().join(format!("{}.json", session.id))).is_err());
+    }
+
+    #[test]
+    fn test_latest_query_results() {
+        let messages = vec![
+            Message::user("first"),
+            Message::tool(
+                r#"{"columns":["a"],"rows":[{"a":1}],"rowCount":1,"truncated":false}"#,
+                "execute_query",
+            ),
+            Message::user("second"),
+            Message::tool(r#"{"tables":[]}"#, "get_schema"),
+            Message::tool(
+                r#"{"columns":["b"],"rows":[{"b":2}],"rowCount":1,"truncated":false}"#,
+                "db.execute_query",
+            ),
+        ];
+        let results = latest_query_results(&messages);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].result.columns, vec!["b"]);
+        assert_eq!(results[0].tool, "db.execute_query");
+        assert_eq!(truncated_query(&messages), None);
+
+        let mut messages = messages;
+        messages.push(Message::tool(
+            r#"{"columns":["c"],"rows":[],"rowCount":0,"truncated":true,
+                "sql":"SELECT c","nextCursor":"7b7d"}"#,
+            "execute_query",
+        ));
+        let expected = ("SELECT c".to_string(), "7b7d".to_string());
+        assert_eq!(truncated_query(&messages), Some(expected));
+    }
+}
@@ -0,0 +1,546 @@
+//! Persistent usage statistics.
+//!
+//! Tracks per-day token usage and estimated spend in a small JSON file so
+//! budgets can be enforced across process restarts, along with per-tool call
+//! counts, error rates and latencies, and a log of recent runs.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AgentError;
+
+/// Usage accumulated over a single day.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyUsage {
+    /// Number of agent runs.
+    pub queries: u64,
+    /// Estimated tokens consumed.
+    pub tokens: u64,
+    /// Estimated spend in USD.
+    pub cost: f64,
+}
+
+/// Latency samples kept per tool; older samples are discarded.
+const MAX_LATENCY_SAMPLES: usize = 500;
+
+/// Call statistics for a single tool.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolUsage {
+    /// Number of calls.
+    pub calls: u64,
+    /// Number of failed calls.
+    pub errors: u64,
+    /// Most recent call latencies in milliseconds, oldest first.
+    #[serde(default)]
+    pub latencies_ms: Vec<u64>,
+}
+
+impl ToolUsage {
+    /// Record one call.
+    pub fn record(&mut self, duration_ms: u64, success: bool) {
+        self.calls += 1;
+        if !success {
+            self.errors += 1;
+        }
+        self.push_latency(duration_ms);
+    }
+
+    /// Merge another tool's statistics into this one.
+    pub fn merge(&mut self, other: &Self) {
+        self.calls += other.calls;
+        self.errors += other.errors;
+        for &latency in &other.latencies_ms {
+            self.push_latency(latency);
+        }
+    }
+
+    /// Fraction of calls that failed.
+    #[must_use]
+    pub fn error_rate(&self) -> f64 {
+        if self.calls == 0 {
+            return 0.0;
+        }
+        self.errors as f64 / self.calls as f64
+    }
+
+    /// Latency percentile (0-100) over the recorded samples, by nearest rank.
+    #[must_use]
+    pub fn latency_percentile(&self, percentile: f64) -> Option<u64> {
+        if self.latencies_ms.is_empty() {
+            return None;
+        }
+        let mut sorted = self.latencies_ms.clone();
+        sorted.sort_unstable();
+        let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.clamp(1, sorted.len()) - 1])
+    }
+
+    /// Append a latency sample, dropping the oldest beyond the cap.
+    fn push_latency(&mut self, duration_ms: u64) {
+        if self.latencies_ms.len() == MAX_LATENCY_SAMPLES {
+            self.latencies_ms.remove(0);
+        }
+        self.latencies_ms.push(duration_ms);
+    }
+}
+
+/// Runs kept in the log; older runs are discarded.
+const MAX_RUNS: usize = 10_000;
+
+/// User rating of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rating {
+    /// The answer was right.
+    Up,
+    /// The answer was wrong or unhelpful.
+    Down,
+}
+
+impl Rating {
+    /// Emoji shown for the rating.
+    #[must_use]
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Self::Up => "👍",
+            Self::Down => "👎",
+        }
+    }
+}
+
+impl FromStr for Rating {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "👍" | "+1" | "up" | "good" | "yes" => Ok(Self::Up),
+            "👎" | "-1" | "down" | "bad" | "no" => Ok(Self::Down),
+            other => Err(format!("Unknown rating '{}' (expected 👍 or 👎)", other)),
+        }
+    }
+}
+
+/// User feedback on a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Feedback {
+    /// Rating.
+    pub rating: Rating,
+    /// Optional free-form comment.
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// When the feedback was given.
+    pub given_at: DateTime<Utc>,
+}
+
+impl Feedback {
+    /// Create feedback given now.
+    #[must_use]
+    pub fn new(rating: Rating, comment: Option<String>) -> Self {
+        Self {
+            rating,
+            comment,
+            given_at: Utc::now(),
+        }
+    }
+}
+
+/// Outcome of a single agent run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunRecord {
+    /// When the run finished.
+    pub timestamp: DateTime<Utc>,
+    /// Database profile.
+    pub profile: String,
+    /// LLM model.
+    pub model: String,
+    /// Estimated tokens consumed.
+    pub tokens: u64,
+    /// Estimated spend in USD.
+    pub cost: f64,
+    /// Wall-clock duration in milliseconds.
+    pub duration_ms: u64,
+    /// Reasoning iterations.
+    pub iterations: u32,
+    /// Tool calls made.
+    pub tool_calls: u32,
+    /// Whether the run produced an answer.
+    pub success: bool,
+    /// Question asked.
+    #[serde(default)]
+    pub question: String,
+    /// Executed SQL.
+    #[serde(default)]
+    pub sql: Option<String>,
+    /// User feedback on the answer.
+    #[serde(default)]
+    pub feedback: Option<Feedback>,
+}
+
+/// Runs aggregated per profile and model.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    /// Number of runs.
+    pub runs: u64,
+    /// Number of successful runs.
+    pub successes: u64,
+    /// Estimated tokens consumed.
+    pub tokens: u64,
+    /// Estimated spend in USD.
+    pub cost: f64,
+    /// Total duration in milliseconds.
+    pub duration_ms: u64,
+    /// Total reasoning iterations.
+    pub iterations: u64,
+    /// Total tool calls.
+    pub tool_calls: u64,
+}
+
+impl RunSummary {
+    /// Add a run to the summary.
+    pub fn add(&mut self, run: &RunRecord) {
+        self.runs += 1;
+        if run.success {
+            self.successes += 1;
+        }
+        self.tokens += run.tokens;
+        self.cost += run.cost;
+        self.duration_ms += run.duration_ms;
+        self.iterations += u64::from(run.iterations);
+        self.tool_calls += u64::from(run.tool_calls);
+    }
+
+    /// Fraction of runs that succeeded.
+    #[must_use]
+    pub fn success_rate(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.successes as f64 / self.runs as f64
+    }
+
+    /// Mean run duration in milliseconds.
+    #[must_use]
+    pub fn avg_duration_ms(&self) -> u64 {
+        self.duration_ms.checked_div(self.runs).unwrap_or(0)
+    }
+}
+
+/// Parse a look-back window such as `30m`, `24h`, `7d` or `2w`.
+#[must_use]
+pub fn parse_window(window: &str) -> Option<Duration> {
+    let window = window.trim();
+    let unit = window.chars().last()?;
+    let amount: i64 = window[..window.len() - unit.len_utf8()].trim().parse().ok()?;
+    match unit.to_ascii_lowercase() {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+/// On-disk representation of the stats store.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatsData {
+    /// Usage keyed by UTC date.
+    #[serde(default)]
+    days: BTreeMap<NaiveDate, DailyUsage>,
+    /// Tool statistics keyed by tool name.
+    #[serde(default)]
+    tools: BTreeMap<String, ToolUsage>,
+    /// Recent runs, oldest first.
+    #[serde(default)]
+    runs: Vec<RunRecord>,
+}
+
+/// Persistent store for usage statistics.
+#[derive(Debug, Clone, Default)]
+pub struct StatsStore {
+    /// Backing file, `None` for an in-memory store.
+    path: Option<PathBuf>,
+    /// Loaded statistics.
+    data: StatsData,
+}
+
+impl StatsStore {
+    /// Open the store at the given path, starting empty if the file is missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, AgentError> {
+        let path = path.into();
+        let data = if path.exists() {
+            let content = std::fs::read_to_string(&path).map_err(|e| AgentError::StatsError {
+                message: format!("Failed to read {}: {}", path.display(), e),
+            })?;
+            serde_json::from_str(&content).map_err(|e| AgentError::StatsError {
+                message: format!("Failed to parse {}: {}", path.display(), e),
+            })?
+        } else {
+            StatsData::default()
+        };
+
+        Ok(Self {
+            path: Some(path),
+            data,
+        })
+    }
+
+    /// Create a store that is never written to disk.
+    #[must_use]
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Get the backing file path.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Get usage for a given day.
+    #[must_use]
+    pub fn usage_on(&self, date: NaiveDate) -> DailyUsage {
+        self.data.days.get(&date).cloned().unwrap_or_default()
+    }
+
+    /// Get usage for the current UTC day.
+    #[must_use]
+    pub fn today(&self) -> DailyUsage {
+        self.usage_on(Utc::now().date_naive())
+    }
+
+    /// Record a completed run against the current UTC day.
+    pub fn record_query(&mut self, tokens: u64, cost: f64) {
+        let usage = self.data.days.entry(Utc::now().date_naive()).or_default();
+        usage.queries += 1;
+        usage.tokens += tokens;
+        usage.cost += cost;
+    }
+
+    /// Merge a run's tool statistics into the store.
+    pub fn record_tools(&mut self, tools: &BTreeMap<String, ToolUsage>) {
+        for (name, usage) in tools {
+            self.data.tools.entry(name.clone()).or_default().merge(usage);
+        }
+    }
+
+    /// Append a run to the log, dropping the oldest beyond the cap.
+    pub fn record_run(&mut self, run: RunRecord) {
+        if self.data.runs.len() >= MAX_RUNS {
+            let excess = self.data.runs.len() + 1 - MAX_RUNS;
+            self.data.runs.drain(..excess);
+        }
+        self.data.runs.push(run);
+    }
+
+    /// Get logged runs, oldest first.
+    #[must_use]
+    pub fn runs(&self) -> &[RunRecord] {
+        &self.data.runs
+    }
+
+    /// Attach feedback to the most recent run, returning that run.
+    pub fn tag_last_run(&mut self, feedback: Feedback) -> Option<&RunRecord> {
+        let run = self.data.runs.last_mut()?;
+        run.feedback = Some(feedback);
+        Some(run)
+    }
+
+    /// Runs that were given the rating, oldest first.
+    pub fn rated_runs(&self, rating: Rating) -> impl Iterator<Item = &RunRecord> {
+        self.data
+            .runs
+            .iter()
+            .filter(move |run| run.feedback.as_ref().is_some_and(|f| f.rating == rating))
+    }
+
+    /// Summarize runs since a point in time per `(profile, model)`.
+    #[must_use]
+    pub fn summarize_runs(
+        &self,
+        since: Option<DateTime<Utc>>,
+    ) -> BTreeMap<(String, String), RunSummary> {
+        let mut summaries: BTreeMap<(String, String), RunSummary> = BTreeMap::new();
+        for run in &self.data.runs {
+            if since.is_some_and(|since| run.timestamp < since) {
+                continue;
+            }
+            summaries
+                .entry((run.profile.clone(), run.model.clone()))
+                .or_default()
+                .add(run);
+        }
+        summaries
+    }
+
+    /// Get accumulated statistics for every tool.
+    #[must_use]
+    pub fn tool_usage(&self) -> &BTreeMap<String, ToolUsage> {
+        &self.data.tools
+    }
+
+    /// Check that today's spend is still below the daily limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AgentError::DailyBudgetExceeded`] once the limit is reached.
+    pub fn check_daily_budget(&self, limit: f64) -> Result<(), AgentError> {
+        let spent = self.today().cost;
+        if spent >= limit {
+            return Err(AgentError::DailyBudgetExceeded { spent, limit });
+        }
+        Ok(())
+    }
+
+    /// Write the store to disk. No-op for in-memory stores.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn save(&self) -> Result<(), AgentError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| AgentError::StatsError {
+                message: format!("Failed to create {}: {}", parent.display(), e),
+            })?;
+        }
+
+        let content = serde_json::to_string_pretty(&self.data).map_err(|e| {
+            AgentError::SerializationError {
+                message: e.to_string(),
+            }
+        })?;
+        std::fs::write(path, content).map_err(|e| AgentError::StatsError {
+            message: format!("Failed to write {}: {}", path.display(), e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_check_budget() {
+        let mut store = StatsStore::in_memory();
+        assert!(store.check_daily_budget(1.0).is_ok());
+
+        store.record_query(1500, 0.75);
+        store.record_query(500, 0.25);
+
+        let today = store.today();
+        assert_eq!(today.queries, 2);
+        assert_eq!(today.tokens, 2000);
+        assert!(matches!(
+            store.check_daily_budget(1.0),
+            Err(AgentError::DailyBudgetExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tool_usage() {
+        let mut usage = ToolUsage::default();
+        for latency in [10, 20, 30, 40] {
+            usage.record(latency, true);
+        }
+        usage.record(500, false);
+
+        assert_eq!(usage.calls, 5);
+        assert!((usage.error_rate() - 0.2).abs() < f64::EPSILON);
+        assert_eq!(usage.latency_percentile(50.0), Some(30));
+        assert_eq!(usage.latency_percentile(95.0), Some(500));
+
+        let mut store = StatsStore::in_memory();
+        let run = BTreeMap::from([("db.get_schema".to_string(), usage)]);
+        store.record_tools(&run);
+        store.record_tools(&run);
+        assert_eq!(store.tool_usage()["db.get_schema"].calls, 10);
+    }
+
+    #[test]
+    fn test_summarize_runs() {
+        let run = |profile: &str, model: &str, hours_ago: i64, success: bool| RunRecord {
+            timestamp: Utc::now() - Duration::hours(hours_ago),
+            profile: profile.to_string(),
+            model: model.to_string(),
+            tokens: 1000,
+            cost: 0.5,
+            duration_ms: 200,
+            iterations: 2,
+            tool_calls: 1,
+            success,
+            ..RunRecord::default()
+        };
+        let mut store = StatsStore::in_memory();
+        store.record_run(run("default", "gpt-4o", 1, true));
+        store.record_run(run("default", "gpt-4o", 2, false));
+        store.record_run(run("default", "gpt-4o-mini", 3, true));
+        store.record_run(run("prod", "gpt-4o", 24 * 10, true));
+
+        let all = store.summarize_runs(None);
+        assert_eq!(all.len(), 3);
+
+        let week = store.summarize_runs(Some(Utc::now() - parse_window("7d").unwrap()));
+        assert_eq!(week.len(), 2);
+        let summary = &week[&("default".to_string(), "gpt-4o".to_string())];
+        assert_eq!(summary.runs, 2);
+        assert_eq!(summary.tokens, 2000);
+        assert!((summary.success_rate() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(summary.avg_duration_ms(), 200);
+
+        assert_eq!(parse_window("24h"), Some(Duration::hours(24)));
+        assert_eq!(parse_window("2w"), Some(Duration::weeks(2)));
+        assert_eq!(parse_window("7x"), None);
+        assert_eq!(parse_window("d"), None);
+    }
+
+    #[test]
+    fn test_feedback() {
+        let mut store = StatsStore::in_memory();
+        assert!(store.tag_last_run(Feedback::new(Rating::Up, None)).is_none());
+
+        for question in ["first", "second"] {
+            store.record_run(RunRecord {
+                question: question.to_string(),
+                ..RunRecord::default()
+            });
+        }
+        let comment = Some("wrong join".to_string());
+        let tagged = store.tag_last_run(Feedback::new(Rating::Down, comment)).unwrap();
+        assert_eq!(tagged.question, "second");
+
+        assert_eq!(store.rated_runs(Rating::Down).count(), 1);
+        assert_eq!(store.rated_runs(Rating::Up).count(), 0);
+        assert_eq!("👍".parse::<Rating>(), Ok(Rating::Up));
+        assert_eq!("-1".parse::<Rating>(), Ok(Rating::Down));
+        assert!("meh".parse::<Rating>().is_err());
+    }
+
+    #[test]
+    fn test_persistence_roundtrip() {
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let path = dir.path().join("nested").join("stats.json");
+
+        let mut store = StatsStore::open(&path).expect("Failed to open store");
+        store.record_query(100, 0.01);
+        store.save().expect("Failed to save store");
+
+        let reopened = StatsStore::open(&path).expect("Failed to reopen store");
+        assert_eq!(reopened.today(), store.today());
+    }
+}
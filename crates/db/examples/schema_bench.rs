@@ -0,0 +1,112 @@
+//! Benchmark of schema introspection on a wide schema.
+//!
+//! Creates a scratch schema with many tables, then compares the old
+//! per-table column lookup with [`QueryExecutor::get_schema`], which fetches
+//! all columns in one query. The scratch schema is dropped afterwards.
+//!
+//! ```text
+//! DATABASE_URL=postgres://localhost/postgres \
+//!     cargo run --release -p postgres-agent-db --example schema_bench -- 500
+//! ```
+
+use std::time::{Duration, Instant};
+
+use postgres_agent_db::{DbConnection, DbError, QueryExecutor};
+
+/// Scratch schema holding the benchmark tables.
+const SCHEMA: &str = "agent_schema_bench";
+/// Prefix of the benchmark table names.
+const PREFIX: &str = "bench_table_";
+/// Timed runs of each strategy.
+const RUNS: usize = 5;
+
+#[tokio::main]
+async fn main() -> Result<(), DbError> {
+    let Ok(url) = std::env::var("DATABASE_URL") else {
+        eprintln!("Set DATABASE_URL to a database where a scratch schema can be created");
+        std::process::exit(2);
+    };
+    let tables: usize = std::env::args()
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(300);
+
+    let db = DbConnection::from_url(&url).await?;
+    setup(&db, tables).await?;
+    let outcome = compare(&db, tables).await;
+    sqlx::query(&format!("DROP SCHEMA {} CASCADE", SCHEMA))
+        .execute(db.pool())
+        .await?;
+    outcome
+}
+
+/// Create the scratch schema with `tables` tables of ten columns each.
+async fn setup(db: &DbConnection, tables: usize) -> Result<(), DbError> {
+    let columns = (0..10)
+        .map(|i| format!("c{} integer", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut ddl = format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0};", SCHEMA);
+    for i in 0..tables {
+        ddl.push_str(&format!("CREATE TABLE {}.{}{} ({});", SCHEMA, PREFIX, i, columns));
+    }
+    sqlx::raw_sql(&ddl).execute(db.pool()).await?;
+    Ok(())
+}
+
+/// Time both strategies and print the medians.
+async fn compare(db: &DbConnection, tables: usize) -> Result<(), DbError> {
+    let mut per_table = Vec::new();
+    let mut batched = Vec::new();
+    for _ in 0..RUNS {
+        let started = Instant::now();
+        per_table_columns(db).await?;
+        per_table.push(started.elapsed());
+
+        let started = Instant::now();
+        let schema = QueryExecutor::new(db.clone()).get_schema(Some(PREFIX)).await?;
+        batched.push(started.elapsed());
+        assert_eq!(schema.columns.len(), tables);
+    }
+
+    let (per_table, batched) = (median(per_table), median(batched));
+    println!("{} tables, median of {} runs", tables, RUNS);
+    println!("  per-table column queries: {:>8.1?}", per_table);
+    println!("  batched get_schema:       {:>8.1?}", batched);
+    println!(
+        "  speedup:                  {:>8.1}x",
+        per_table.as_secs_f64() / batched.as_secs_f64()
+    );
+    Ok(())
+}
+
+/// The former strategy: one columns query per table.
+async fn per_table_columns(db: &DbConnection) -> Result<(), DbError> {
+    let names: Vec<(String,)> = sqlx::query_as(
+        "SELECT table_name::text FROM information_schema.tables \
+         WHERE table_schema = $1 AND table_name LIKE $2 || '%'",
+    )
+    .bind(SCHEMA)
+    .bind(PREFIX)
+    .fetch_all(db.pool())
+    .await?;
+    for (name,) in names {
+        sqlx::query(
+            "SELECT column_name, data_type, is_nullable, column_default, \
+             character_maximum_length, numeric_precision, numeric_scale \
+             FROM information_schema.columns \
+             WHERE table_schema = $1 AND table_name = $2 ORDER BY ordinal_position",
+        )
+        .bind(SCHEMA)
+        .bind(&name)
+        .fetch_all(db.pool())
+        .await?;
+    }
+    Ok(())
+}
+
+/// Median of the timings.
+fn median(mut timings: Vec<Duration>) -> Duration {
+    timings.sort();
+    timings[timings.len() / 2]
+}
@@ -3,17 +3,26 @@
 //! This module provides the [`DbConnection`] wrapper around sqlx's PgPool,
 //! handling connection pooling, lifecycle management, and configuration.
 
+use chrono_tz::Tz;
+use postgres_agent_util::crypto::{redact_url, serialize_redacted_url, Secret};
 use serde::{Deserialize, Serialize};
-use sqlx::{postgres::PgConnectOptions, PgPool};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use std::fmt;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::debug;
 
+use crate::rendering::TypeRendering;
+use crate::{DatabaseSchema, NumericOutput, SchemaCache, TimeZoneMode};
+
 /// Database connection configuration.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DbConnectionConfig {
-    /// Database connection URL or connection string.
-    #[serde(default = "default_url")]
+    /// Database connection URL or connection string; serialized with the
+    /// password masked.
+    #[serde(default = "default_url", serialize_with = "serialize_redacted_url")]
     pub url: String,
     /// Database host (alternative to url).
     #[serde(default)]
@@ -24,9 +33,9 @@ pub struct DbConnectionConfig {
     /// Database username.
     #[serde(default)]
     pub username: Option<String>,
-    /// Database password (as plain string for serialization).
+    /// Database password.
     #[serde(default)]
-    pub password: Option<String>,
+    pub password: Option<Secret<String>>,
     /// Database name.
     #[serde(default)]
     pub database: Option<String>,
@@ -45,6 +54,25 @@ pub struct DbConnectionConfig {
     /// Query execution timeout in seconds.
     #[serde(default = "default_query_timeout")]
     pub query_timeout: u64,
+    /// Session settings applied to every pooled connection.
+    #[serde(default)]
+    pub session: SessionSettings,
+    /// Schema of unqualified table names; `current_schema()` when unset.
+    #[serde(default)]
+    pub default_schema: Option<String>,
+    /// Zone `timestamptz` results are rendered in.
+    #[serde(default)]
+    pub time_zone: TimeZoneMode,
+    /// How `numeric` and `bigint` values are written in JSON exports.
+    #[serde(default)]
+    pub numeric_output: NumericOutput,
+    /// How `bytea`, `point`, geometry and `tsvector` values are shown.
+    #[serde(default)]
+    pub type_rendering: TypeRendering,
+    /// Bytes of result rows buffered in memory before the rest spill to
+    /// disk; 0 means no limit.
+    #[serde(default = "default_max_result_bytes")]
+    pub max_result_bytes: usize,
 }
 
 fn default_url() -> String {
@@ -71,6 +99,10 @@ fn default_query_timeout() -> u64 {
     60
 }
 
+fn default_max_result_bytes() -> usize {
+    256 * 1024 * 1024
+}
+
 impl Default for DbConnectionConfig {
     fn default() -> Self {
         Self {
@@ -85,10 +117,98 @@ impl Default for DbConnectionConfig {
             min_idle_connections: default_min_idle_connections(),
             connect_timeout: default_connect_timeout(),
             query_timeout: default_query_timeout(),
+            session: SessionSettings::default(),
+            default_schema: None,
+            time_zone: TimeZoneMode::default(),
+            numeric_output: NumericOutput::default(),
+            type_rendering: TypeRendering::default(),
+            max_result_bytes: default_max_result_bytes(),
         }
     }
 }
 
+impl fmt::Debug for DbConnectionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbConnectionConfig")
+            .field("url", &redact_url(&self.url))
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password)
+            .field("database", &self.database)
+            .field("ssl_mode", &self.ssl_mode)
+            .field("max_connections", &self.max_connections)
+            .field("min_idle_connections", &self.min_idle_connections)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("query_timeout", &self.query_timeout)
+            .field("session", &self.session)
+            .field("default_schema", &self.default_schema)
+            .field("time_zone", &self.time_zone)
+            .field("numeric_output", &self.numeric_output)
+            .field("type_rendering", &self.type_rendering)
+            .field("max_result_bytes", &self.max_result_bytes)
+            .finish()
+    }
+}
+
+/// Server-side session settings (GUCs) applied when a connection is established.
+///
+/// Values use PostgreSQL syntax, e.g. `"30s"` or `"64MB"`, and bound runaway
+/// queries on the server regardless of client-side timeouts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSettings {
+    /// `statement_timeout` for each statement.
+    #[serde(default)]
+    pub statement_timeout: Option<String>,
+    /// `idle_in_transaction_session_timeout` for open transactions.
+    #[serde(default)]
+    pub idle_in_transaction_session_timeout: Option<String>,
+    /// `work_mem` for sorts and hashes.
+    #[serde(default)]
+    pub work_mem: Option<String>,
+    /// `application_name` reported in `pg_stat_activity`.
+    #[serde(default)]
+    pub application_name: Option<String>,
+    /// `search_path`, as a comma-separated list of schemas.
+    #[serde(default)]
+    pub search_path: Option<String>,
+}
+
+impl SessionSettings {
+    /// Get the configured GUCs as name/value pairs, excluding `application_name`.
+    #[must_use]
+    pub fn gucs(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("statement_timeout", &self.statement_timeout),
+            (
+                "idle_in_transaction_session_timeout",
+                &self.idle_in_transaction_session_timeout,
+            ),
+            ("work_mem", &self.work_mem),
+            ("search_path", &self.search_path),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.as_deref().map(|v| (name, v)))
+        .collect()
+    }
+
+    /// Apply the settings to sqlx connect options.
+    #[must_use]
+    pub fn apply(&self, mut options: PgConnectOptions) -> PgConnectOptions {
+        if let Some(name) = &self.application_name {
+            options = options.application_name(name);
+        }
+
+        let gucs = self.gucs();
+        if !gucs.is_empty() {
+            options = options.options(gucs);
+        }
+
+        options
+    }
+}
+
 /// SSL mode for database connections.
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -126,10 +246,10 @@ impl DbConnectionConfig {
             &self.database,
         ) {
             let options: PgConnectOptions = self.url.parse().map_err(|_| {
-                debug!("Failed to parse connection URL: {}", self.url);
+                debug!("Failed to parse connection URL: {}", redact_url(&self.url));
                 crate::DbError::ConnectionFailed
             })?;
-            return Ok(options);
+            return Ok(self.session.apply(options));
         }
 
         // Build from individual components
@@ -148,7 +268,7 @@ impl DbConnectionConfig {
         }
 
         if let Some(password) = &self.password {
-            options = options.password(password);
+            options = options.password(password.expose());
         }
 
         if let Some(database) = &self.database {
@@ -157,7 +277,7 @@ impl DbConnectionConfig {
 
         options = options.ssl_mode(self.ssl_mode.into());
 
-        Ok(options)
+        Ok(self.session.apply(options))
     }
 }
 
@@ -171,6 +291,12 @@ pub struct DbConnection {
     config: DbConnectionConfig,
     /// SQLx connection pool.
     pool: PgPool,
+    /// Introspected schema, shared by clones of the connection.
+    schema_cache: SchemaCache,
+    /// `TimeZone` setting of the sessions, looked up on first use.
+    session_zone: Arc<tokio::sync::OnceCell<Tz>>,
+    /// Whether the pool holds a single session kept for temp tables.
+    workspace: bool,
 }
 
 impl DbConnection {
@@ -200,9 +326,94 @@ impl DbConnection {
         Ok(Self {
             config: config.clone(),
             pool,
+            schema_cache: SchemaCache::new(),
+            session_zone: Arc::default(),
+            workspace: false,
+        })
+    }
+
+    /// Create a connection without connecting; the first query opens one
+    /// and fails if the database is unreachable.
+    ///
+    /// # Errors
+    /// Returns `DbError::ConnectionFailed` if the connection string is invalid.
+    pub fn lazy(config: &DbConnectionConfig) -> Result<Self, crate::DbError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(Duration::from_secs(config.connect_timeout))
+            .connect_lazy_with(config.to_connect_options()?);
+
+        Ok(Self {
+            config: config.clone(),
+            pool,
+            schema_cache: SchemaCache::new(),
+            session_zone: Arc::default(),
+            workspace: false,
         })
     }
 
+    /// Open a workspace: a pool of one connection with the same settings.
+    ///
+    /// All queries on the workspace run in the same server session, so temp
+    /// tables created by one query are visible to the next. They last until
+    /// the workspace is closed or its connection is lost, e.g. when a
+    /// running query is cancelled.
+    ///
+    /// # Errors
+    /// Returns `DbError::ConnectionFailed` if the connection cannot be opened.
+    pub async fn workspace(&self) -> Result<Self, crate::DbError> {
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(self.config.to_connect_options()?)
+            .await
+            .map_err(|e| {
+                debug!("Failed to open workspace connection: {}", e);
+                crate::DbError::ConnectionFailed
+            })?;
+
+        Ok(Self {
+            config: self.config.clone(),
+            pool,
+            schema_cache: self.schema_cache.clone(),
+            session_zone: Arc::clone(&self.session_zone),
+            workspace: true,
+        })
+    }
+
+    /// Connect to another database on the same server with the same
+    /// settings, through a pool of one connection.
+    ///
+    /// # Errors
+    /// Returns `DbError::ConnectionFailed` if the connection cannot be opened.
+    pub async fn connect_to(&self, database: &str) -> Result<Self, crate::DbError> {
+        let options = self.config.to_connect_options()?.database(database);
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await
+            .map_err(|e| {
+                debug!("Failed to connect to database {}: {}", database, e);
+                crate::DbError::ConnectionFailed
+            })?;
+
+        Ok(Self {
+            config: self.config.clone(),
+            pool,
+            schema_cache: SchemaCache::new(),
+            session_zone: Arc::default(),
+            workspace: false,
+        })
+    }
+
+    /// Whether this is a workspace from [`workspace`](Self::workspace).
+    #[must_use]
+    pub fn is_workspace(&self) -> bool {
+        self.workspace
+    }
+
     /// Create a new connection from a connection URL string.
     ///
     /// Convenience method for simple connection scenarios.
@@ -226,12 +437,77 @@ impl DbConnection {
         &self.pool
     }
 
+    /// Get the schema cache of the database.
+    #[must_use]
+    pub fn schema_cache(&self) -> &SchemaCache {
+        &self.schema_cache
+    }
+
+    /// Get the schema, from the cache once loaded.
+    ///
+    /// # Errors
+    /// Returns an error if introspection fails.
+    pub async fn cached_schema(&self) -> Result<Arc<DatabaseSchema>, crate::DbError> {
+        self.schema_cache.get(self).await
+    }
+
     /// Get the connection configuration.
     #[must_use]
     pub fn config(&self) -> &DbConnectionConfig {
         &self.config
     }
 
+    /// Zone in which `timestamptz` results are rendered.
+    ///
+    /// In session mode the server's `TimeZone` setting is looked up once;
+    /// a setting that is not an IANA zone name falls back to UTC.
+    pub async fn display_zone(&self) -> Tz {
+        match self.config.time_zone {
+            TimeZoneMode::Utc => Tz::UTC,
+            TimeZoneMode::Named(zone) => zone,
+            TimeZoneMode::Session => *self
+                .session_zone
+                .get_or_init(|| async {
+                    let setting: Result<(String,), _> =
+                        sqlx::query_as("SELECT current_setting('TimeZone')")
+                            .fetch_one(&self.pool)
+                            .await;
+                    match setting.map(|(name,)| name.parse::<Tz>()) {
+                        Ok(Ok(zone)) => zone,
+                        other => {
+                            debug!("Rendering timestamps in UTC, session zone: {:?}", other);
+                            Tz::UTC
+                        }
+                    }
+                })
+                .await,
+        }
+    }
+
+    /// How `numeric` and `bigint` values are written in JSON exports.
+    #[must_use]
+    pub fn numeric_output(&self) -> NumericOutput {
+        self.config.numeric_output
+    }
+
+    /// How `bytea`, `point`, geometry and `tsvector` values are shown.
+    #[must_use]
+    pub fn type_rendering(&self) -> TypeRendering {
+        self.config.type_rendering
+    }
+
+    /// Bytes of result rows buffered in memory before spilling to disk.
+    #[must_use]
+    pub fn max_result_bytes(&self) -> usize {
+        self.config.max_result_bytes
+    }
+
+    /// Get the configured schema of unqualified table names.
+    #[must_use]
+    pub fn default_schema(&self) -> Option<&str> {
+        self.config.default_schema.as_deref()
+    }
+
     /// Check if the connection is healthy.
     ///
     /// Executes a simple query to verify connectivity.
@@ -278,6 +554,52 @@ mod tests {
         assert!(matches!(config.ssl_mode, SslMode::Prefer));
     }
 
+    #[test]
+    fn test_session_settings_gucs() {
+        let session = SessionSettings {
+            statement_timeout: Some("30s".to_string()),
+            work_mem: Some("64MB".to_string()),
+            application_name: Some("pg-agent".to_string()),
+            search_path: Some("analytics,public".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            session.gucs(),
+            vec![
+                ("statement_timeout", "30s"),
+                ("work_mem", "64MB"),
+                ("search_path", "analytics,public")
+            ]
+        );
+
+        let config = DbConnectionConfig {
+            session,
+            ..Default::default()
+        };
+        let options = config.to_connect_options().unwrap();
+        assert_eq!(
+            options.get_options(),
+            Some("-c statement_timeout=30s -c work_mem=64MB -c search_path=analytics,public")
+        );
+        assert_eq!(options.get_application_name(), Some("pg-agent"));
+    }
+
+    #[test]
+    fn test_debug_redacts_credentials() {
+        let config = DbConnectionConfig {
+            url: "postgres://admin:s3cret@db:5432/app".to_string(),
+            password: Some(Secret::new("s3cret".to_string())),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("s3cret"));
+        assert!(debug.contains("admin:***@db"));
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("s3cret"));
+        assert!(json.contains("admin:***@db"));
+    }
+
     #[test]
     fn test_ssl_mode_conversion() {
         // Test that conversion works without panicking
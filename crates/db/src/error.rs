@@ -1,28 +1,265 @@
 //! Database errors.
 
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgDatabaseError, PgErrorPosition};
 use thiserror::Error;
 
+use crate::sqlstate::{self, SqlState};
+
 /// Errors from database operations.
 #[derive(Debug, Error)]
 pub enum DbError {
+    /// Failed to establish a connection to the database.
     #[error("Failed to connect to database")]
     ConnectionFailed,
 
-    #[error("Query failed: {sql}")]
-    QueryFailed { sql: String },
+    /// Query execution failed.
+    #[error("Query failed: {}", error.as_ref().map_or_else(|| sql.clone(), ToString::to_string))]
+    QueryFailed {
+        /// The SQL that failed.
+        sql: String,
+        /// Error reported by the server, if the query reached it.
+        error: Option<Box<PgErrorInfo>>,
+    },
 
+    /// A non-SELECT query was submitted to a read-only entry point.
     #[error("Non-SELECT query not allowed in read-only mode")]
-    NonSelectQuery { sql: String },
+    NonSelectQuery {
+        /// The rejected SQL.
+        sql: String,
+    },
 
+    /// Query exceeded the configured timeout.
     #[error("Query exceeded timeout of {timeout}s")]
-    Timeout { timeout: u64 },
+    Timeout {
+        /// Timeout in seconds.
+        timeout: u64,
+    },
+
+    /// The query was cancelled by the caller.
+    #[error("Query cancelled")]
+    Cancelled,
+
+    /// The named table does not exist.
+    #[error("Table not found: {table}")]
+    TableNotFound {
+        /// Qualified table name.
+        table: String,
+    },
+
+    /// The named column does not exist.
+    #[error("Column not found: {column}")]
+    ColumnNotFound {
+        /// Qualified column name.
+        column: String,
+    },
+
+    /// A page cursor could not be decoded.
+    #[error("Invalid page cursor: {cursor}")]
+    InvalidCursor {
+        /// The rejected cursor.
+        cursor: String,
+    },
 
+    /// Schema introspection failed.
     #[error("Schema introspection failed")]
     SchemaIntrospectionFailed,
 
+    /// Result rows could not be written to or read from disk.
+    #[error("Failed to spill result rows to disk: {reason}")]
+    Spill {
+        /// Underlying I/O or encoding error.
+        reason: String,
+    },
+
+    /// Table rows could not be saved or restored.
+    #[error("Snapshot failed: {reason}")]
+    Snapshot {
+        /// Underlying I/O error or limit exceeded.
+        reason: String,
+    },
+
+    /// A proposed index is not a single `CREATE INDEX` statement.
+    #[error("Not a CREATE INDEX statement: {sql}")]
+    NotAnIndex {
+        /// The rejected SQL.
+        sql: String,
+    },
+
+    /// Underlying sqlx error.
     #[error("Database error: {source}")]
     Database {
+        /// Source sqlx error.
         #[from]
         source: sqlx::Error,
     },
 }
+
+impl DbError {
+    /// Create a `QueryFailed` error, keeping the server's error details.
+    #[must_use]
+    pub fn query_failed(sql: impl Into<String>, source: &sqlx::Error) -> Self {
+        Self::QueryFailed {
+            sql: sql.into(),
+            error: PgErrorInfo::from_sqlx(source).map(Box::new),
+        }
+    }
+
+    /// Error details reported by the server, if any.
+    #[must_use]
+    pub fn pg_error(&self) -> Option<&PgErrorInfo> {
+        match self {
+            Self::QueryFailed { error, .. } => error.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// SQLSTATE code reported by the server, if any.
+    #[must_use]
+    pub fn sqlstate(&self) -> Option<&str> {
+        self.pg_error().map(|e| e.code.as_str())
+    }
+
+    /// Known meaning of the SQLSTATE, with a suggested fix.
+    #[must_use]
+    pub fn sqlstate_info(&self) -> Option<&'static SqlState> {
+        self.sqlstate().and_then(sqlstate::lookup)
+    }
+
+    /// The failing line of the SQL with the error position marked.
+    #[must_use]
+    pub fn highlight(&self) -> Option<String> {
+        match self {
+            Self::QueryFailed {
+                sql,
+                error: Some(error),
+            } => error.highlight(sql),
+            _ => None,
+        }
+    }
+
+    /// Report an error of a query wrapped by `prefix` against the unwrapped
+    /// `sql`, shifting the error position accordingly.
+    #[must_use]
+    pub fn unwrap_query(self, sql: &str, prefix: &str) -> Self {
+        match self {
+            Self::QueryFailed { error, .. } => Self::QueryFailed {
+                sql: sql.to_string(),
+                error: error.map(|mut error| {
+                    let shift = prefix.chars().count();
+                    error.position = error.position.and_then(|p| p.checked_sub(shift));
+                    error.position = error.position.filter(|&p| p > 0);
+                    error
+                }),
+            },
+            other => other,
+        }
+    }
+}
+
+/// Error details reported by the PostgreSQL server.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PgErrorInfo {
+    /// SQLSTATE code, e.g. `42P01`.
+    pub code: String,
+    /// Primary error message.
+    pub message: String,
+    /// Optional detail message.
+    pub detail: Option<String>,
+    /// Optional suggestion on how to fix the problem.
+    pub hint: Option<String>,
+    /// 1-based character position of the error in the SQL.
+    pub position: Option<usize>,
+}
+
+impl PgErrorInfo {
+    /// Extract the server's error details from a sqlx error.
+    #[must_use]
+    pub fn from_sqlx(error: &sqlx::Error) -> Option<Self> {
+        let pg = error.as_database_error()?.try_downcast_ref::<PgDatabaseError>()?;
+        Some(Self {
+            code: pg.code().to_string(),
+            message: pg.message().to_string(),
+            detail: pg.detail().map(str::to_string),
+            hint: pg.hint().map(str::to_string),
+            position: match pg.position() {
+                Some(PgErrorPosition::Original(position)) => Some(position),
+                _ => None,
+            },
+        })
+    }
+
+    /// The line of `sql` holding the error position, with a caret under it.
+    #[must_use]
+    pub fn highlight(&self, sql: &str) -> Option<String> {
+        let position = self.position?.checked_sub(1)?;
+        let before: String = sql.chars().take(position).collect();
+        if before.chars().count() < position {
+            return None;
+        }
+        let line_number = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or_default().chars().count();
+        let line = sql.lines().nth(line_number - 1).unwrap_or_default();
+        let label = format!("LINE {}: ", line_number);
+        Some(format!(
+            "{}{}\n{}^",
+            label,
+            line,
+            " ".repeat(label.len() + column)
+        ))
+    }
+}
+
+impl fmt::Display for PgErrorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (SQLSTATE {})", self.message, self.code)?;
+        if let Some(detail) = &self.detail {
+            write!(f, "; DETAIL: {}", detail)?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, "; HINT: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        let error = DbError::QueryFailed {
+            sql: "SELECT id\nFROM orderz o".to_string(),
+            error: Some(Box::new(PgErrorInfo {
+                code: "42P01".to_string(),
+                message: "relation \"orderz\" does not exist".to_string(),
+                position: Some(16),
+                ..PgErrorInfo::default()
+            })),
+        };
+        assert_eq!(error.sqlstate(), Some("42P01"));
+        assert_eq!(error.sqlstate_info().map(|s| s.name), Some("undefined_table"));
+        assert_eq!(
+            error.to_string(),
+            "Query failed: relation \"orderz\" does not exist (SQLSTATE 42P01)"
+        );
+        assert_eq!(
+            error.highlight().unwrap(),
+            "LINE 2: FROM orderz o\n             ^"
+        );
+
+        let wrapped = DbError::QueryFailed {
+            sql: "SELECT * FROM (SELECT idd FROM t\n) AS page".to_string(),
+            error: Some(Box::new(PgErrorInfo {
+                position: Some(23),
+                ..PgErrorInfo::default()
+            })),
+        };
+        let error = wrapped.unwrap_query("SELECT idd FROM t", "SELECT * FROM (");
+        assert_eq!(error.highlight().unwrap(), "LINE 1: SELECT idd FROM t\n               ^");
+    }
+}
@@ -3,19 +3,35 @@
 //! This module provides the [`QueryExecutor`] for executing queries
 //! and introspecting database schemas.
 
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use futures::{Stream, TryStreamExt};
+use postgres_agent_util::number::NumberFormat;
 use serde::{Deserialize, Serialize};
-use sqlx::{Column, Row, TypeInfo};
+use sqlx::postgres::PgRow;
+use sqlx::{Column, Row};
 use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, trace};
 
 use crate::{
     error::DbError,
-    schema::{ColumnInfo, DatabaseSchema, SchemaTable, TableType},
-    DbConnection,
+    paging::{keyset_order, keyset_sql, page_sql, row_key, Page, PageCursor, PAGE_PREFIX},
+    rendering::TypeRendering,
+    spill::{JsonRow, RowBuffer, SpilledRows},
+    schema::{
+        BackendActivity, ColumnInfo, DatabaseSchema, DependencyKind, EnumType, ForeignKeyRef,
+        MatviewFreshness, RlsPolicy, RolePrivileges, SchemaTable,
+        TableDependency, TableSecurity, TableSize, TableType,
+    },
+    value::{column_types, row_to_json, JsonResult, JsonRows, NumericOutput},
+    quote_qualified, DbConnection,
 };
 
 /// Result of a query execution.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QueryResult {
     /// Column names.
@@ -28,16 +44,81 @@ pub struct QueryResult {
     pub execution_time_ms: Option<u64>,
     /// Whether the result was truncated due to row limit.
     pub truncated: bool,
+    /// PostgreSQL type names of the columns.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub column_types: Vec<String>,
+    /// Zone `timestamptz` columns are rendered in, if there are any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<String>,
+    /// Rows past the memory limit, kept on disk after `rows`.
+    #[serde(skip)]
+    pub spilled: Option<SpilledRows>,
 }
 
-impl Default for QueryResult {
-    fn default() -> Self {
-        Self {
-            columns: Vec::new(),
-            rows: Vec::new(),
-            row_count: 0,
-            execution_time_ms: None,
-            truncated: false,
+impl QueryResult {
+    /// Whether the column at `index` holds `timestamptz` values.
+    #[must_use]
+    pub fn is_timestamptz(&self, index: usize) -> bool {
+        self.column_types.get(index).is_some_and(|t| t == "TIMESTAMPTZ")
+    }
+
+    /// Whether the column at `index` holds integer, float or numeric values.
+    #[must_use]
+    pub fn is_numeric(&self, index: usize) -> bool {
+        self.column_types.get(index).is_some_and(|t| {
+            matches!(t.as_str(), "INT2" | "INT4" | "INT8" | "FLOAT4" | "FLOAT8" | "NUMERIC")
+        })
+    }
+
+    /// Whether the column at `index` holds `numeric` or `bigint` values,
+    /// which JSON consumers may not parse exactly.
+    #[must_use]
+    pub fn is_exact_numeric(&self, index: usize) -> bool {
+        self.column_types.get(index).is_some_and(|t| t == "NUMERIC" || t == "INT8")
+    }
+
+    /// All rows: those in memory, then those spilled to disk.
+    ///
+    /// Rows on disk are read back one at a time; failing reads are yielded
+    /// as `DbError::Spill`.
+    pub fn all_rows(&self) -> impl Iterator<Item = Result<Cow<'_, JsonRow>, DbError>> {
+        let spilled: Box<dyn Iterator<Item = Result<Cow<'_, JsonRow>, DbError>>> =
+            match self.spilled.as_ref().map(SpilledRows::iter) {
+                None => Box::new(std::iter::empty()),
+                Some(Ok(rows)) => Box::new(rows.map(|row| row.map(Cow::Owned))),
+                Some(Err(e)) => Box::new(std::iter::once(Err(e))),
+            };
+        self.rows.iter().map(|row| Ok(Cow::Borrowed(row))).chain(spilled)
+    }
+
+    /// Rows for JSON output, with exact numbers written as `numeric` asks.
+    #[must_use]
+    pub fn json_rows(&self, numeric: NumericOutput) -> JsonRows<'_> {
+        JsonRows::new(self, numeric)
+    }
+
+    /// The whole result for JSON output; see [`QueryResult::json_rows`].
+    #[must_use]
+    pub fn to_json(&self, numeric: NumericOutput) -> JsonResult<'_> {
+        JsonResult::new(self, numeric)
+    }
+
+    /// Text of the cell in column `index` of `row` for display: strings
+    /// unquoted, null empty, numbers formatted with `numbers`.
+    #[must_use]
+    pub fn display_cell(
+        &self,
+        row: &serde_json::Map<String, serde_json::Value>,
+        index: usize,
+        numbers: NumberFormat,
+    ) -> String {
+        let value = self.columns.get(index).and_then(|column| row.get(column));
+        match value {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(serde_json::Value::Number(n)) => numbers.render(&n.to_string()),
+            Some(serde_json::Value::String(s)) if self.is_numeric(index) => numbers.render(s),
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
         }
     }
 }
@@ -50,13 +131,22 @@ impl Default for QueryResult {
 pub struct QueryExecutor {
     /// Database connection.
     db: DbConnection,
+    /// Token that cancels running queries on the server.
+    cancel: Option<CancellationToken>,
 }
 
 impl QueryExecutor {
     /// Create a new query executor.
     #[must_use]
     pub fn new(db: DbConnection) -> Self {
-        Self { db }
+        Self { db, cancel: None }
+    }
+
+    /// Cancel queries on the server when `token` is cancelled.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
     }
 
     /// Execute a SELECT query with timeout.
@@ -69,6 +159,8 @@ impl QueryExecutor {
     /// Returns `DbError::NonSelectQuery` if the query is not a SELECT.
     /// Returns `DbError::Timeout` if the query exceeds the timeout.
     /// Returns `DbError::QueryFailed` if the query execution fails.
+    /// Returns `DbError::Cancelled` if the executor's token is cancelled.
+    #[tracing::instrument(name = "db_query", skip_all)]
     pub async fn execute_query(&self, sql: &str) -> Result<QueryResult, DbError> {
         // Validate it's a SELECT query
         let normalized = sql.trim_start().to_uppercase();
@@ -81,35 +173,11 @@ impl QueryExecutor {
 
         trace!("Executing query: {}", sql);
 
-        let pool = self.db.pool();
         let timeout_duration = self.db.query_timeout();
+        let zone = self.db.display_zone().await;
+        let types = self.db.type_rendering();
 
-        let result = timeout(timeout_duration, async move {
-            // Use fetch_all for simplicity - returns all rows at once
-            let row_stream = sqlx::query(sql).fetch_all(pool).await?;
-
-            let columns: Vec<String> = if let Some(first_row) = row_stream.first() {
-                first_row.columns().iter().map(|c| c.name().to_string()).collect()
-            } else {
-                // No rows returned, try to get column info from empty query
-                Vec::new()
-            };
-
-            let row_count = row_stream.len();
-            let rows: Vec<serde_json::Map<String, serde_json::Value>> = row_stream
-                .into_iter()
-                .map(convert_row_to_json)
-                .collect();
-
-            Ok::<QueryResult, DbError>(QueryResult {
-                columns,
-                rows,
-                row_count,
-                execution_time_ms: None,
-                truncated: false,
-            })
-        })
-        .await;
+        let result = timeout(timeout_duration, self.fetch_rows(sql, zone, &types)).await;
 
         match result {
             Ok(Ok(result)) => Ok(result),
@@ -120,6 +188,74 @@ impl QueryExecutor {
         }
     }
 
+    /// Fetch all rows of `sql`, cancelling it on the server when the
+    /// executor's token is cancelled.
+    ///
+    /// Rows are converted as they arrive; beyond the connection's memory
+    /// limit they are spilled to disk.
+    async fn fetch_rows(
+        &self,
+        sql: &str,
+        zone: Tz,
+        types: &TypeRendering,
+    ) -> Result<QueryResult, DbError> {
+        let pool = self.db.pool();
+        let limit = self.db.max_result_bytes();
+        let Some(token) = &self.cancel else {
+            return collect_rows(sqlx::query(sql).fetch(pool), sql, zone, types, limit).await;
+        };
+        if token.is_cancelled() {
+            return Err(DbError::Cancelled);
+        }
+
+        let mut conn = pool.acquire().await?;
+        let (pid,): (i32,) = sqlx::query_as("SELECT pg_backend_pid()")
+            .fetch_one(&mut *conn)
+            .await?;
+        let rows = tokio::select! {
+            rows = collect_rows(sqlx::query(sql).fetch(&mut *conn), sql, zone, types, limit) => {
+                Some(rows)
+            }
+            () = token.cancelled() => None,
+        };
+        match rows {
+            Some(rows) => rows,
+            None => {
+                // Dropping the future leaves the query running on the server.
+                // Detach first so a single-connection pool can signal it.
+                let conn = conn.detach();
+                if let Err(e) = self.signal_backend(pid, false).await {
+                    debug!("Failed to cancel backend {}: {}", pid, e);
+                }
+                drop(conn);
+                Err(DbError::Cancelled)
+            }
+        }
+    }
+
+    /// Execute a statement that returns no rows, such as DDL.
+    ///
+    /// Unlike [`execute_query`](QueryExecutor::execute_query) any statement
+    /// is accepted, so callers decide what may run. Returns the number of
+    /// rows affected.
+    ///
+    /// # Errors
+    /// Returns `DbError::Timeout` if the statement exceeds the timeout.
+    /// Returns `DbError::QueryFailed` if the statement fails.
+    pub async fn execute_statement(&self, sql: &str) -> Result<u64, DbError> {
+        trace!("Executing statement: {}", sql);
+
+        let result = timeout(self.db.query_timeout(), sqlx::query(sql).execute(self.db.pool()))
+            .await
+            .map_err(|_| DbError::Timeout {
+                timeout: self.db.config().query_timeout,
+            })?;
+
+        result
+            .map(|done| done.rows_affected())
+            .map_err(|e| DbError::query_failed(sql, &e))
+    }
+
     /// Execute a SELECT query and return limited results.
     ///
     /// Similar to [`execute_query`](QueryExecutor::execute_query) but limits
@@ -150,36 +286,18 @@ impl QueryExecutor {
 
         trace!("Executing limited query: {}", sql_with_limit);
 
-        let pool = self.db.pool();
         let timeout_duration = self.db.query_timeout();
+        let zone = self.db.display_zone().await;
+        let types = self.db.type_rendering();
 
-        let result = timeout(timeout_duration, async move {
-            let row_stream = sqlx::query(&sql_with_limit).fetch_all(pool).await?;
-
-            let columns: Vec<String> = if let Some(first_row) = row_stream.first() {
-                first_row.columns().iter().map(|c| c.name().to_string()).collect()
-            } else {
-                Vec::new()
-            };
-
-            let row_count = row_stream.len();
-            let rows: Vec<serde_json::Map<String, serde_json::Value>> = row_stream
-                .into_iter()
-                .map(convert_row_to_json)
-                .collect();
-
-            Ok::<QueryResult, DbError>(QueryResult {
-                columns,
-                rows,
-                row_count,
-                execution_time_ms: None,
-                truncated: row_count >= limit,
-            })
-        })
-        .await;
+        let result =
+            timeout(timeout_duration, self.fetch_rows(&sql_with_limit, zone, &types)).await;
 
         match result {
-            Ok(Ok(result)) => Ok(result),
+            Ok(Ok(mut result)) => {
+                result.truncated = result.row_count >= limit;
+                Ok(result)
+            }
             Ok(Err(e)) => Err(e),
             Err(_) => Err(DbError::Timeout {
                 timeout: self.db.config().query_timeout,
@@ -187,10 +305,71 @@ impl QueryExecutor {
         }
     }
 
+    /// Execute one page of a SELECT query.
+    ///
+    /// Starts at `cursor`, a token returned with a previous page, or at the
+    /// first row. Queries ending in `ORDER BY` on plain columns are paged by
+    /// keyset, others by `OFFSET`. One extra row is fetched to learn whether
+    /// more follow, in which case the page carries the next token.
+    ///
+    /// # Errors
+    /// Returns `DbError::InvalidCursor` if the cursor is malformed.
+    /// Otherwise the same as [`execute_query`](QueryExecutor::execute_query).
+    pub async fn execute_paged(
+        &self,
+        sql: &str,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<Page, DbError> {
+        let normalized = sql.trim_start().to_uppercase();
+        if !normalized.starts_with("SELECT") && !normalized.starts_with("WITH ") {
+            return Err(DbError::NonSelectQuery {
+                sql: sql.to_string(),
+            });
+        }
+
+        let cursor = cursor.map(PageCursor::decode).transpose()?.unwrap_or_default();
+        let order = keyset_order(sql);
+        let keyset = order
+            .as_ref()
+            .zip(cursor.after.as_deref())
+            .and_then(|(order, after)| keyset_sql(sql, order, after, limit + 1));
+        let paged = keyset.unwrap_or_else(|| page_sql(sql, cursor.offset, limit + 1));
+        trace!("Executing page: {}", paged);
+
+        let mut result = self
+            .execute_query(&paged)
+            .await
+            .map_err(|e| e.unwrap_query(sql, PAGE_PREFIX))?;
+        if result.rows.len() <= limit {
+            return Ok(Page { result, next: None });
+        }
+
+        let extra = result.rows.pop();
+        result.row_count = result.rows.len();
+        result.truncated = true;
+        // Keyset paging would skip rows sharing the boundary key
+        let after = order.and_then(|order| {
+            let last = row_key(&order, result.rows.last()?)?;
+            let next = row_key(&order, extra.as_ref()?)?;
+            (last != next).then_some(last)
+        });
+        let next = PageCursor {
+            offset: cursor.offset + result.row_count,
+            after,
+        };
+        Ok(Page {
+            result,
+            next: Some(next.encode()),
+        })
+    }
+
     /// Introspect database schema.
     ///
     /// Retrieves information about all tables and columns in the database,
-    /// optionally filtered by table name pattern.
+    /// optionally filtered by table name pattern. Columns of all tables are
+    /// fetched in one query, so the number of round trips does not grow with
+    /// the number of tables.
     ///
     /// # Errors
     /// Returns `DbError::SchemaIntrospectionFailed` if the introspection fails.
@@ -241,62 +420,296 @@ impl QueryExecutor {
             });
         }
 
-        // Query columns for each table
-        let mut columns: Vec<(String, ColumnInfo)> = Vec::new();
-
-        for table in &tables {
-            let columns_sql = r#"
-                SELECT
-                    column_name,
-                    data_type,
-                    is_nullable,
-                    column_default,
-                    character_maximum_length,
-                    numeric_precision,
-                    numeric_scale
-                FROM information_schema.columns
-                WHERE table_schema = $1 AND table_name = $2
-                ORDER BY ordinal_position
-            "#;
-
-            let col_rows = sqlx::query(columns_sql)
-                .bind(&table.table_schema)
-                .bind(&table.table_name)
-                .fetch_all(pool)
-                .await?;
-
-            for row in col_rows {
-                columns.push((
-                    table.table_name.clone(),
-                    ColumnInfo {
-                        column_name: row.try_get(0)?,
-                        data_type: row.try_get(1)?,
-                        is_nullable: row.try_get(2)?,
-                        column_default: row.try_get(3)?,
-                        character_maximum_length: row.try_get(4)?,
-                        numeric_precision: row.try_get(5)?,
-                        numeric_scale: row.try_get(6)?,
-                    },
-                ));
-            }
-        }
+        // Query the columns of all tables at once and group them client-side
+        let columns_sql = format!(
+            "{} WHERE c.table_schema NOT IN ('pg_catalog', 'information_schema') \
+             AND ($1::text IS NULL OR c.table_name LIKE $1 || '%') \
+             ORDER BY c.table_schema, c.table_name, c.ordinal_position",
+            COLUMNS_SQL
+        );
 
-        // Group columns by table name
-        let mut column_map = std::collections::HashMap::new();
-        for (table_name, col) in columns {
-            column_map.entry(table_name).or_insert_with(Vec::new).push(col);
-        }
+        let col_rows = sqlx::query(&columns_sql)
+            .bind(table_filter)
+            .fetch_all(pool)
+            .await?;
+
+        let columns = col_rows
+            .iter()
+            .map(|row| Ok((row.try_get(0)?, column_info(row)?)))
+            .collect::<Result<Vec<_>, DbError>>()?;
+
+        let security = self.table_security(table_filter).await?;
+        let enums = self.list_enums().await?;
 
         Ok(DatabaseSchema {
             tables,
-            columns: column_map,
+            columns: group_columns(columns),
+            security,
+            enums,
         })
     }
 
+    /// List the user-defined enum types and their labels.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog query fails.
+    pub async fn list_enums(&self) -> Result<Vec<EnumType>, DbError> {
+        let sql = r#"
+            SELECT
+                format_type(t.oid, NULL),
+                n.nspname::text,
+                array_agg(e.enumlabel::text ORDER BY e.enumsortorder)
+            FROM pg_catalog.pg_type t
+            JOIN pg_catalog.pg_namespace n ON n.oid = t.typnamespace
+            JOIN pg_catalog.pg_enum e ON e.enumtypid = t.oid
+            WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+            GROUP BY t.oid, n.nspname
+            ORDER BY n.nspname, t.typname
+        "#;
+
+        let rows: Vec<(String, String, Vec<String>)> = sqlx::query_as(sql)
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| DbError::query_failed(sql, &e))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(name, schema, labels)| EnumType { name, schema, labels })
+            .collect())
+    }
+
+    /// Introspect row-level security policies and grants.
+    ///
+    /// Returns one entry per table, view or foreign table, keyed by table
+    /// name and optionally filtered by table name prefix. `rls_applies` is
+    /// evaluated for the connected role: superusers, roles with BYPASSRLS
+    /// and table owners (unless RLS is forced) are exempt.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog queries fail.
+    pub async fn table_security(
+        &self,
+        table_filter: Option<&str>,
+    ) -> Result<std::collections::HashMap<String, TableSecurity>, DbError> {
+        let pool = self.db.pool();
+
+        let tables_sql = r#"
+            SELECT
+                n.nspname,
+                c.relname,
+                pg_get_userbyid(c.relowner),
+                c.relrowsecurity,
+                c.relforcerowsecurity,
+                c.relrowsecurity
+                    AND NOT r.rolsuper
+                    AND NOT r.rolbypassrls
+                    AND (c.relforcerowsecurity OR NOT pg_has_role(c.relowner, 'USAGE'))
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            CROSS JOIN (SELECT rolsuper, rolbypassrls FROM pg_roles WHERE rolname = current_user) r
+            WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+            AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND n.nspname NOT LIKE 'pg\_%'
+            AND ($1::text IS NULL OR c.relname LIKE $1 || '%')
+        "#;
+
+        let policies_sql = r#"
+            SELECT tablename, policyname, cmd, permissive = 'PERMISSIVE', roles::text[], qual, with_check
+            FROM pg_policies
+            WHERE ($1::text IS NULL OR tablename LIKE $1 || '%')
+            ORDER BY schemaname, tablename, policyname
+        "#;
+
+        let grants_sql = r#"
+            SELECT
+                c.relname,
+                CASE WHEN a.grantee = 0 THEN 'public' ELSE pg_get_userbyid(a.grantee) END,
+                array_agg(DISTINCT a.privilege_type ORDER BY a.privilege_type)
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            CROSS JOIN LATERAL aclexplode(c.relacl) a
+            WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+            AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND n.nspname NOT LIKE 'pg\_%'
+            AND ($1::text IS NULL OR c.relname LIKE $1 || '%')
+            GROUP BY 1, 2
+        "#;
+
+        let query_failed = |sql: &'static str| {
+            move |e: sqlx::Error| {
+                debug!("Failed to read table security: {}", e);
+                DbError::query_failed(sql, &e)
+            }
+        };
+
+        let mut security = std::collections::HashMap::new();
+        for row in sqlx::query(tables_sql)
+            .bind(table_filter)
+            .fetch_all(pool)
+            .await
+            .map_err(query_failed(tables_sql))?
+        {
+            security.insert(
+                row.try_get::<String, _>(1)?,
+                TableSecurity {
+                    table_schema: row.try_get(0)?,
+                    owner: row.try_get(2)?,
+                    rls_enabled: row.try_get(3)?,
+                    rls_forced: row.try_get(4)?,
+                    rls_applies: row.try_get(5)?,
+                    ..TableSecurity::default()
+                },
+            );
+        }
+
+        for row in sqlx::query(policies_sql)
+            .bind(table_filter)
+            .fetch_all(pool)
+            .await
+            .map_err(query_failed(policies_sql))?
+        {
+            let table: String = row.try_get(0)?;
+            if let Some(entry) = security.get_mut(&table) {
+                entry.policies.push(RlsPolicy {
+                    name: row.try_get(1)?,
+                    command: row.try_get(2)?,
+                    permissive: row.try_get(3)?,
+                    roles: row.try_get(4)?,
+                    using: row.try_get(5)?,
+                    with_check: row.try_get(6)?,
+                });
+            }
+        }
+
+        for row in sqlx::query(grants_sql)
+            .bind(table_filter)
+            .fetch_all(pool)
+            .await
+            .map_err(query_failed(grants_sql))?
+        {
+            let table: String = row.try_get(0)?;
+            if let Some(entry) = security.get_mut(&table) {
+                entry.grants.insert(row.try_get(1)?, row.try_get(2)?);
+            }
+        }
+
+        Ok(security)
+    }
+
+    /// Tables that have a column with the given name, as `schema.table`.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the query fails.
+    pub async fn tables_with_column(&self, column: &str) -> Result<Vec<String>, DbError> {
+        let sql = r#"
+            SELECT c.table_schema || '.' || c.table_name
+            FROM information_schema.columns c
+            JOIN information_schema.tables t
+                ON t.table_schema = c.table_schema AND t.table_name = c.table_name
+            WHERE c.column_name = $1
+            AND t.table_type IN ('BASE TABLE', 'VIEW', 'FOREIGN')
+            AND c.table_schema NOT IN ('pg_catalog', 'information_schema')
+            ORDER BY 1
+        "#;
+
+        let rows: Vec<(String,)> = sqlx::query_as(sql)
+            .bind(column)
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to find tables with column {}: {}", column, e);
+                DbError::query_failed(sql, &e)
+            })?;
+
+        Ok(rows.into_iter().map(|(t,)| t).collect())
+    }
+
+    /// Column names and row estimates of all user tables and views.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog query fails.
+    pub async fn table_sizes(&self) -> Result<Vec<TableSize>, DbError> {
+        let sql = r#"
+            SELECT n.nspname, c.relname,
+                array_agg(a.attname::text ORDER BY a.attnum),
+                c.reltuples::bigint
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+            WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+            AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND n.nspname NOT LIKE 'pg\_%'
+            GROUP BY n.nspname, c.relname, c.reltuples
+            ORDER BY 1, 2
+        "#;
+
+        let rows: Vec<(String, String, Vec<String>, i64)> = sqlx::query_as(sql)
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to read table sizes: {}", e);
+                DbError::query_failed(sql, &e)
+            })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(table_schema, table_name, columns, estimated_rows)| TableSize {
+                table_schema,
+                table_name,
+                columns,
+                estimated_rows,
+            })
+            .collect())
+    }
+
+    /// Names of tables whose rows are filtered by row-level security for
+    /// the connected role, as `schema.table`.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog queries fail.
+    pub async fn rls_filtered_tables(&self) -> Result<Vec<String>, DbError> {
+        let mut tables: Vec<String> = self
+            .table_security(None)
+            .await?
+            .into_iter()
+            .filter(|(_, security)| security.rls_applies)
+            .map(|(name, security)| format!("{}.{}", security.table_schema, name))
+            .collect();
+        tables.sort();
+        Ok(tables)
+    }
+
+    /// The planner's estimate of the rows a statement reads or modifies,
+    /// from `EXPLAIN` without running it.
+    ///
+    /// For INSERT, UPDATE and DELETE the estimate is that of the rows fed
+    /// to the modification. Returns `None` for statements that cannot be
+    /// explained, such as DDL.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the statement is invalid.
+    pub async fn estimate_rows(&self, sql: &str) -> Result<Option<i64>, DbError> {
+        let explain_sql = format!("EXPLAIN (FORMAT JSON) {}", sql.trim().trim_end_matches(';'));
+        let plan: serde_json::Value =
+            match sqlx::query_scalar(&explain_sql).fetch_one(self.db.pool()).await {
+                Ok(plan) => plan,
+                // Utility statements have no plan
+                Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("42601") => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(DbError::query_failed(&explain_sql, &e)),
+            };
+
+        let mut node = &plan[0]["Plan"];
+        if node["Node Type"] == "ModifyTable" {
+            node = &node["Plans"][0];
+        }
+        Ok(node["Plan Rows"].as_f64().map(|rows| rows as i64))
+    }
+
     /// List all table names.
     ///
-    /// Returns a list of all table names in the database,
-    /// optionally filtered by schema.
+    /// Returns the base tables of a schema, by default `current_schema()`.
     ///
     /// # Errors
     /// Returns `DbError::QueryFailed` if the query fails.
@@ -307,23 +720,21 @@ impl QueryExecutor {
     ) -> Result<Vec<String>, DbError> {
         let pool = self.db.pool();
 
-        let schema_filter = schema.unwrap_or("public");
-
         let sql = r#"
-            SELECT table_name
+            SELECT table_name::text
             FROM information_schema.tables
-            WHERE table_schema = $1
+            WHERE table_schema = COALESCE($1::text, current_schema())
             AND table_type = 'BASE TABLE'
             ORDER BY table_name
         "#;
 
         let rows: Vec<(String,)> = sqlx::query_as(sql)
-            .bind(schema_filter)
+            .bind(schema)
             .fetch_all(pool)
             .await
             .map_err(|e| {
                 debug!("Failed to list tables: {}", e);
-                crate::DbError::QueryFailed { sql: sql.to_string() }
+                DbError::query_failed(sql, &e)
             })?;
 
         Ok(rows.into_iter().map(|(t,)| t).collect())
@@ -331,66 +742,636 @@ impl QueryExecutor {
 
     /// Describe a specific table.
     ///
-    /// Returns detailed column information for a single table.
+    /// Returns detailed column information for a single table. Without a
+    /// schema, the table is looked up in `current_schema()`, the first
+    /// existing schema on the session's `search_path`.
     ///
     /// # Errors
     /// Returns `DbError::QueryFailed` if the query fails.
     #[allow(dead_code)]
     pub async fn describe_table(
         &self,
+        schema: Option<&str>,
         table_name: &str,
     ) -> Result<Vec<ColumnInfo>, DbError> {
         let pool = self.db.pool();
 
+        let sql = format!(
+            "{} WHERE c.table_schema = COALESCE($1::text, current_schema()) \
+             AND c.table_name = $2 ORDER BY c.ordinal_position",
+            COLUMNS_SQL
+        );
+
+        let rows = sqlx::query(&sql)
+            .bind(schema)
+            .bind(table_name)
+            .fetch_all(pool)
+            .await?;
+
+        rows.iter().map(column_info).collect()
+    }
+
+    /// List objects that depend on a table.
+    ///
+    /// Covers views and materialized views, foreign keys from other tables,
+    /// triggers on the table, and functions that reference it either through
+    /// a tracked dependency or by name in their body.
+    ///
+    /// # Errors
+    /// Returns `DbError::TableNotFound` if the table does not exist.
+    /// Returns `DbError::QueryFailed` if the catalog query fails.
+    pub async fn table_dependencies(
+        &self,
+        schema: &str,
+        table_name: &str,
+    ) -> Result<Vec<TableDependency>, DbError> {
+        let pool = self.db.pool();
+
+        let exists_sql = r#"
+            SELECT count(*)
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = $1::text AND c.relname = $2::text
+        "#;
+
+        let (count,): (i64,) = sqlx::query_as(exists_sql)
+            .bind(schema)
+            .bind(table_name)
+            .fetch_one(pool)
+            .await?;
+        if count == 0 {
+            return Err(DbError::TableNotFound {
+                table: format!("{}.{}", schema, table_name),
+            });
+        }
+
+        let sql = r#"
+            WITH target AS (
+                SELECT c.oid, c.relname
+                FROM pg_class c
+                JOIN pg_namespace n ON n.oid = c.relnamespace
+                WHERE n.nspname = $1::text AND c.relname = $2::text
+            )
+            SELECT DISTINCT
+                CASE dc.relkind WHEN 'm' THEN 'materialized_view' ELSE 'view' END,
+                dn.nspname::text,
+                dc.relname::text,
+                NULL::text
+            FROM target t
+            JOIN pg_depend d ON d.refobjid = t.oid AND d.classid = 'pg_rewrite'::regclass
+            JOIN pg_rewrite r ON r.oid = d.objid
+            JOIN pg_class dc ON dc.oid = r.ev_class AND dc.oid <> t.oid
+            JOIN pg_namespace dn ON dn.oid = dc.relnamespace
+            UNION ALL
+            SELECT 'foreign_key', n.nspname::text, c.relname::text, con.conname::text
+            FROM target t
+            JOIN pg_constraint con ON con.confrelid = t.oid AND con.contype = 'f'
+            JOIN pg_class c ON c.oid = con.conrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            UNION ALL
+            SELECT 'trigger', $1::text, tg.tgname::text, tg.tgfoid::regproc::text
+            FROM target t
+            JOIN pg_trigger tg ON tg.tgrelid = t.oid AND NOT tg.tgisinternal
+            UNION ALL
+            SELECT DISTINCT
+                'function',
+                n.nspname::text,
+                p.proname::text,
+                pg_get_function_identity_arguments(p.oid)
+            FROM target t
+            CROSS JOIN pg_proc p
+            JOIN pg_namespace n ON n.oid = p.pronamespace
+            WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND (
+                EXISTS (
+                    SELECT 1 FROM pg_depend d
+                    WHERE d.classid = 'pg_proc'::regclass
+                    AND d.objid = p.oid
+                    AND d.refobjid = t.oid
+                )
+                OR position(lower(t.relname) IN lower(p.prosrc)) > 0
+            )
+            ORDER BY 1, 2, 3
+        "#;
+
+        let rows: Vec<(String, String, String, Option<String>)> = sqlx::query_as(sql)
+            .bind(schema)
+            .bind(table_name)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| {
+                debug!("Failed to read table dependencies: {}", e);
+                DbError::query_failed(sql, &e)
+            })?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(kind, schema, name, detail)| {
+                Some(TableDependency {
+                    kind: DependencyKind::from_label(&kind)?,
+                    schema,
+                    name,
+                    detail,
+                })
+            })
+            .collect())
+    }
+
+    /// List the single-column foreign keys of a table.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog query fails.
+    pub async fn foreign_keys(
+        &self,
+        schema: &str,
+        table_name: &str,
+    ) -> Result<Vec<ForeignKeyRef>, DbError> {
         let sql = r#"
             SELECT
-                column_name,
-                data_type,
-                is_nullable,
-                column_default,
-                character_maximum_length,
-                numeric_precision,
-                numeric_scale
+                a.attname::text,
+                rn.nspname::text,
+                rc.relname::text,
+                ra.attname::text
+            FROM pg_constraint con
+            JOIN pg_class c ON c.oid = con.conrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            JOIN pg_class rc ON rc.oid = con.confrelid
+            JOIN pg_namespace rn ON rn.oid = rc.relnamespace
+            JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = con.conkey[1]
+            JOIN pg_attribute ra ON ra.attrelid = con.confrelid AND ra.attnum = con.confkey[1]
+            WHERE con.contype = 'f'
+            AND cardinality(con.conkey) = 1
+            AND n.nspname = $1::text
+            AND c.relname = $2::text
+            ORDER BY 1
+        "#;
+
+        let rows: Vec<(String, String, String, String)> = sqlx::query_as(sql)
+            .bind(schema)
+            .bind(table_name)
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to read foreign keys: {}", e);
+                DbError::query_failed(sql, &e)
+            })?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(column, referenced_schema, referenced_table, referenced_column)| ForeignKeyRef {
+                column,
+                referenced_schema,
+                referenced_table,
+                referenced_column,
+            })
+            .collect())
+    }
+
+    /// List identity and generated columns of a table, which cannot be
+    /// written directly.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the query fails.
+    pub async fn generated_columns(
+        &self,
+        schema: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DbError> {
+        let sql = r#"
+            SELECT column_name::text
             FROM information_schema.columns
-            WHERE table_schema = 'public' AND table_name = $1
+            WHERE table_schema = $1::text AND table_name = $2::text
+            AND (is_identity = 'YES' OR is_generated = 'ALWAYS')
             ORDER BY ordinal_position
         "#;
 
-        let rows = sqlx::query(sql)
+        let rows: Vec<(String,)> = sqlx::query_as(sql)
+            .bind(schema)
             .bind(table_name)
-            .fetch_all(pool)
-            .await?;
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to read generated columns: {}", e);
+                DbError::query_failed(sql, &e)
+            })?;
 
-        let mut columns = Vec::new();
-        for row in rows {
-            columns.push(ColumnInfo {
-                column_name: row.try_get(0)?,
-                data_type: row.try_get(1)?,
-                is_nullable: row.try_get(2)?,
-                column_default: row.try_get(3)?,
-                character_maximum_length: row.try_get(4)?,
-                numeric_precision: row.try_get(5)?,
-                numeric_scale: row.try_get(6)?,
-            });
+        Ok(rows.into_iter().map(|(c,)| c).collect())
+    }
+
+    /// List the column sets of a table's primary key, unique constraints
+    /// and unique indexes; partial and expression indexes are left out.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog query fails.
+    pub async fn unique_columns(
+        &self,
+        schema: &str,
+        table_name: &str,
+    ) -> Result<Vec<Vec<String>>, DbError> {
+        let sql = r#"
+            SELECT array_agg(a.attname::text ORDER BY k.ord)
+            FROM pg_index i
+            JOIN pg_class c ON c.oid = i.indrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            CROSS JOIN LATERAL unnest(i.indkey) WITH ORDINALITY AS k(attnum, ord)
+            JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = k.attnum
+            WHERE i.indisunique
+            AND i.indpred IS NULL
+            AND i.indexprs IS NULL
+            AND n.nspname = $1::text
+            AND c.relname = $2::text
+            GROUP BY i.indexrelid
+            ORDER BY i.indexrelid
+        "#;
+
+        let rows: Vec<(Vec<String>,)> = sqlx::query_as(sql)
+            .bind(schema)
+            .bind(table_name)
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to read unique columns: {}", e);
+                DbError::query_failed(sql, &e)
+            })?;
+
+        Ok(rows.into_iter().map(|(columns,)| columns).collect())
+    }
+
+    /// Run write statements in a single transaction.
+    ///
+    /// Unlike [`execute_query`](QueryExecutor::execute_query) this accepts
+    /// any statement; callers are responsible for validating and confirming
+    /// them first. Either every statement is committed or none is.
+    ///
+    /// Returns the total number of affected rows.
+    ///
+    /// # Errors
+    /// Returns `DbError::Timeout` if the transaction exceeds the timeout.
+    /// Returns `DbError::Database` if a statement fails; the transaction is
+    /// rolled back.
+    pub async fn execute_in_transaction(&self, statements: &[String]) -> Result<u64, DbError> {
+        let pool = self.db.pool();
+
+        let result = timeout(self.db.query_timeout(), async move {
+            let mut tx = pool.begin().await?;
+            let mut affected = 0;
+            for statement in statements {
+                trace!("Executing statement: {}", statement);
+                affected += sqlx::query(statement).execute(&mut *tx).await?.rows_affected();
+            }
+            tx.commit().await?;
+            Ok::<u64, DbError>(affected)
+        })
+        .await;
+
+        match result {
+            Ok(result) => result,
+            Err(_) => Err(DbError::Timeout {
+                timeout: self.db.config().query_timeout,
+            }),
         }
+    }
 
-        Ok(columns)
+    /// Look up a server backend by process ID.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the query fails.
+    pub async fn backend_activity(&self, pid: i32) -> Result<Option<BackendActivity>, DbError> {
+        let sql = r#"
+            SELECT
+                pid,
+                usename::text,
+                state,
+                query,
+                EXTRACT(EPOCH FROM now() - query_start)::float8
+            FROM pg_stat_activity
+            WHERE pid = $1
+        "#;
+
+        let row = sqlx::query(sql)
+            .bind(pid)
+            .fetch_optional(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to read backend activity: {}", e);
+                DbError::query_failed(sql, &e)
+            })?;
+
+        row.map(|row| {
+            Ok(BackendActivity {
+                pid: row.try_get(0)?,
+                username: row.try_get(1)?,
+                state: row.try_get(2)?,
+                query: row.try_get(3)?,
+                running_secs: row.try_get(4)?,
+            })
+        })
+        .transpose()
+    }
+
+    /// Cancel the current query of a backend, or terminate it entirely.
+    ///
+    /// Returns whether the signal was delivered.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the call fails, e.g. for lack of
+    /// privileges.
+    pub async fn signal_backend(&self, pid: i32, terminate: bool) -> Result<bool, DbError> {
+        let sql = if terminate {
+            "SELECT pg_terminate_backend($1)"
+        } else {
+            "SELECT pg_cancel_backend($1)"
+        };
+
+        let (signalled,): (bool,) = sqlx::query_as(sql)
+            .bind(pid)
+            .fetch_one(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to signal backend {}: {}", pid, e);
+                DbError::query_failed(sql, &e)
+            })?;
+
+        Ok(signalled)
+    }
+
+    /// Refresh state of every materialized view.
+    ///
+    /// PostgreSQL does not record refresh times, so `last_refresh` is the
+    /// modification time of the view's data file, which a refresh rewrites.
+    /// It is `None` unless the role may call `pg_stat_file`.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog query fails.
+    pub async fn matview_freshness(&self) -> Result<Vec<MatviewFreshness>, DbError> {
+        let sql = r#"
+            SELECT
+                m.schemaname::text,
+                m.matviewname::text,
+                m.ispopulated,
+                CASE WHEN has_function_privilege(
+                    'pg_catalog.pg_stat_file(text, boolean)', 'EXECUTE'
+                ) THEN (pg_stat_file(pg_relation_filepath(c.oid), true)).modification
+                END
+            FROM pg_catalog.pg_matviews m
+            JOIN pg_catalog.pg_namespace n ON n.nspname = m.schemaname
+            JOIN pg_catalog.pg_class c ON c.relnamespace = n.oid AND c.relname = m.matviewname
+            ORDER BY m.schemaname, m.matviewname
+        "#;
+
+        let rows: Vec<(String, String, bool, Option<DateTime<Utc>>)> = sqlx::query_as(sql)
+            .fetch_all(self.db.pool())
+            .await
+            .map_err(|e| DbError::query_failed(sql, &e))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(schema, name, is_populated, last_refresh)| MatviewFreshness {
+                schema,
+                name,
+                is_populated,
+                last_refresh,
+            })
+            .collect())
+    }
+
+    /// Run `REFRESH MATERIALIZED VIEW` on `schema.name`.
+    ///
+    /// `concurrently` keeps the view readable during the refresh but needs
+    /// a unique index on it.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the refresh fails.
+    pub async fn refresh_matview(
+        &self,
+        schema: &str,
+        name: &str,
+        concurrently: bool,
+    ) -> Result<(), DbError> {
+        let sql = format!(
+            "REFRESH MATERIALIZED VIEW {}{}",
+            if concurrently { "CONCURRENTLY " } else { "" },
+            quote_qualified(schema, name)
+        );
+
+        sqlx::query(&sql)
+            .execute(self.db.pool())
+            .await
+            .map_err(|e| {
+                debug!("Failed to refresh {}: {}", name, e);
+                DbError::query_failed(&sql, &e)
+            })?;
+
+        Ok(())
+    }
+
+    /// Schema unqualified names resolve to (`current_schema()`): the first
+    /// existing schema on the `search_path`, if any.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the query fails.
+    pub async fn current_schema(&self) -> Result<Option<String>, DbError> {
+        let sql = "SELECT current_schema()::text";
+        sqlx::query_scalar(sql).fetch_one(self.db.pool()).await.map_err(|e| {
+            debug!("Failed to read the current schema: {}", e);
+            DbError::query_failed(sql, &e)
+        })
+    }
+
+    /// Name of the connected role (`current_user`).
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the query fails.
+    pub async fn current_role(&self) -> Result<String, DbError> {
+        let sql = "SELECT current_user::text";
+        sqlx::query_scalar(sql).fetch_one(self.db.pool()).await.map_err(|e| {
+            debug!("Failed to read the current role: {}", e);
+            DbError::query_failed(sql, &e)
+        })
+    }
+
+    /// Inspect the privileges of the connected role.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if the catalog queries fail.
+    pub async fn role_privileges(&self) -> Result<RolePrivileges, DbError> {
+        let pool = self.db.pool();
+
+        let role_sql = r#"
+            SELECT rolname, rolsuper, rolcreatedb, rolcreaterole, rolbypassrls
+            FROM pg_roles
+            WHERE rolname = current_user
+        "#;
+
+        let (role, is_superuser, can_create_db, can_create_role, bypass_rls): (
+            String,
+            bool,
+            bool,
+            bool,
+            bool,
+        ) = sqlx::query_as(role_sql)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                debug!("Failed to read role attributes: {}", e);
+                DbError::query_failed(role_sql, &e)
+            })?;
+
+        let schemas_sql = r#"
+            SELECT
+                nspname,
+                has_schema_privilege(oid, 'USAGE'),
+                has_schema_privilege(oid, 'CREATE')
+            FROM pg_namespace
+            WHERE nspname NOT LIKE 'pg\_%' AND nspname <> 'information_schema'
+            ORDER BY nspname
+        "#;
+
+        let schemas: Vec<(String, bool, bool)> = sqlx::query_as(schemas_sql)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| {
+                debug!("Failed to read schema privileges: {}", e);
+                DbError::query_failed(schemas_sql, &e)
+            })?;
+
+        let writable_sql = r#"
+            SELECT count(*)
+            FROM pg_class c
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE c.relkind IN ('r', 'p')
+            AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+            AND (
+                has_table_privilege(c.oid, 'INSERT')
+                OR has_table_privilege(c.oid, 'UPDATE')
+                OR has_table_privilege(c.oid, 'DELETE')
+            )
+        "#;
+
+        let (writable_tables,): (i64,) = sqlx::query_as(writable_sql)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| {
+                debug!("Failed to count writable tables: {}", e);
+                DbError::query_failed(writable_sql, &e)
+            })?;
+
+        Ok(RolePrivileges {
+            role,
+            is_superuser,
+            can_create_db,
+            can_create_role,
+            bypass_rls,
+            readable_schemas: schemas
+                .iter()
+                .filter(|(_, usage, _)| *usage)
+                .map(|(name, _, _)| name.clone())
+                .collect(),
+            creatable_schemas: schemas
+                .iter()
+                .filter(|(_, _, create)| *create)
+                .map(|(name, _, _)| name.clone())
+                .collect(),
+            writable_tables,
+        })
     }
 }
 
-/// Convert a sqlx row to a JSON object.
-fn convert_row_to_json(row: sqlx::postgres::PgRow) -> serde_json::Map<String, serde_json::Value> {
-    let mut map = serde_json::Map::new();
+/// Column metadata query shared by schema introspection and
+/// `describe_table`; callers append the WHERE and ORDER BY clauses.
+///
+/// User-defined types are named with `format_type`, and enum columns carry
+/// their labels.
+const COLUMNS_SQL: &str = r#"
+    SELECT
+        c.table_name::text,
+        c.column_name::text,
+        CASE WHEN c.data_type = 'USER-DEFINED' AND t.oid IS NOT NULL
+            THEN format_type(t.oid, NULL)
+            ELSE c.data_type::text
+        END,
+        c.is_nullable = 'YES',
+        c.column_default::text,
+        c.character_maximum_length::bigint,
+        c.numeric_precision::bigint,
+        c.numeric_scale::bigint,
+        (SELECT array_agg(e.enumlabel::text ORDER BY e.enumsortorder)
+         FROM pg_catalog.pg_enum e WHERE e.enumtypid = t.oid)
+    FROM information_schema.columns c
+    LEFT JOIN pg_catalog.pg_namespace tn ON tn.nspname = c.udt_schema
+    LEFT JOIN pg_catalog.pg_type t ON t.typname = c.udt_name AND t.typnamespace = tn.oid
+"#;
 
-    for (i, col) in row.columns().iter().enumerate() {
-        let value = row.try_get::<serde_json::Value, _>(i).ok().unwrap_or_else(|| {
-            let type_name = TypeInfo::name(col.type_info());
-            serde_json::Value::String(format!("<{}>", type_name))
-        });
-        map.insert(Column::name(col).to_string(), value);
+/// Read a row of [`COLUMNS_SQL`].
+fn column_info(row: &PgRow) -> Result<ColumnInfo, DbError> {
+    Ok(ColumnInfo {
+        column_name: row.try_get(1)?,
+        data_type: row.try_get(2)?,
+        is_nullable: row.try_get(3)?,
+        column_default: row.try_get(4)?,
+        character_maximum_length: row.try_get(5)?,
+        numeric_precision: row.try_get(6)?,
+        numeric_scale: row.try_get(7)?,
+        enum_labels: row.try_get::<Option<Vec<String>>, _>(8)?.unwrap_or_default(),
+    })
+}
+
+/// Group `(table, column)` pairs into the columns of each table, keeping
+/// their order.
+fn group_columns(
+    columns: impl IntoIterator<Item = (String, ColumnInfo)>,
+) -> std::collections::HashMap<String, Vec<ColumnInfo>> {
+    let mut grouped = std::collections::HashMap::<_, Vec<ColumnInfo>>::new();
+    for (table, column) in columns {
+        grouped.entry(table).or_default().push(column);
+    }
+    grouped
+}
+
+/// Convert the rows of `stream` into a result as they arrive, rendering
+/// `timestamptz` values in `zone` and other types as `types` chooses.
+async fn collect_rows(
+    mut stream: impl Stream<Item = Result<PgRow, sqlx::Error>> + Unpin,
+    sql: &str,
+    zone: Tz,
+    types: &TypeRendering,
+    limit: usize,
+) -> Result<QueryResult, DbError> {
+    let mut buffer = RowBuffer::new(limit);
+    // Column names and types come from the first row, if any
+    let mut columns = Vec::new();
+    let mut types_of_columns = Vec::new();
+    while let Some(row) = stream
+        .try_next()
+        .await
+        .map_err(|e| DbError::query_failed(sql, &e))?
+    {
+        if columns.is_empty() {
+            columns = row.columns().iter().map(|c| c.name().to_string()).collect();
+            types_of_columns = column_types(&row);
+        }
+        buffer.push(row_to_json(&row, zone, types))?;
     }
 
-    map
+    let row_count = buffer.len();
+    let (rows, spilled) = buffer.finish()?;
+    if let Some(spilled) = &spilled {
+        debug!("Spilled {} rows ({} bytes) to disk", spilled.len(), spilled.bytes());
+    }
+    let time_zone = types_of_columns
+        .iter()
+        .any(|t| t == "TIMESTAMPTZ")
+        .then(|| zone.name().to_string());
+    Ok(QueryResult {
+        columns,
+        rows,
+        row_count,
+        execution_time_ms: None,
+        truncated: false,
+        column_types: types_of_columns,
+        time_zone,
+        spilled,
+    })
 }
 
 #[cfg(test)]
@@ -404,4 +1385,65 @@ mod tests {
         assert!(result.rows.is_empty());
         assert_eq!(result.row_count, 0);
     }
+
+    #[test]
+    fn test_group_columns() {
+        let column = |name: &str| -> ColumnInfo {
+            serde_json::from_value(serde_json::json!({ "columnName": name })).unwrap()
+        };
+        let rows = [
+            ("orders", "id"),
+            ("orders", "customer_id"),
+            ("users", "id"),
+            ("orders", "total"),
+        ];
+        let grouped = group_columns(rows.map(|(table, name)| (table.to_string(), column(name))));
+
+        let names = |table: &str| -> Vec<&str> {
+            grouped[table].iter().map(|c| c.column_name.as_str()).collect()
+        };
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(names("orders"), ["id", "customer_id", "total"]);
+        assert_eq!(names("users"), ["id"]);
+        assert!(group_columns(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_display_cell() {
+        let result = QueryResult {
+            columns: vec!["total".into(), "zip".into(), "avg".into()],
+            column_types: vec!["INT8".into(), "TEXT".into(), "NUMERIC".into()],
+            ..QueryResult::default()
+        };
+        let row = serde_json::json!({"total": 1234567, "zip": "10001", "avg": "2500.125"});
+        let row = row.as_object().unwrap();
+        let human = NumberFormat::Human { precision: Some(2) };
+        assert_eq!(result.display_cell(row, 0, human), "1,234,567.00");
+        assert_eq!(result.display_cell(row, 1, human), "10001");
+        assert_eq!(result.display_cell(row, 2, human), "2,500.13");
+        assert_eq!(result.display_cell(row, 2, NumberFormat::Raw), "2500.125");
+    }
+    #[test]
+    fn test_all_rows_include_spilled() {
+        // Room for the first row only
+        let mut buffer = RowBuffer::new(100);
+        for id in 0..3 {
+            let row = serde_json::json!({ "id": id });
+            buffer.push(row.as_object().cloned().unwrap()).unwrap();
+        }
+        let (rows, spilled) = buffer.finish().unwrap();
+        let result = QueryResult {
+            columns: vec!["id".into()],
+            rows,
+            row_count: 3,
+            spilled,
+            ..QueryResult::default()
+        };
+        assert_eq!(result.rows.len(), 1);
+        let ids: Vec<serde_json::Value> =
+            result.all_rows().map(|row| row.unwrap()["id"].clone()).collect();
+        assert_eq!(ids, [0, 1, 2]);
+        let json = serde_json::to_string(&result.json_rows(NumericOutput::Number)).unwrap();
+        assert_eq!(json, r#"[{"id":0},{"id":1},{"id":2}]"#);
+    }
 }
@@ -8,9 +8,36 @@
 pub mod connection;
 pub mod error;
 pub mod executor;
+pub mod lineage;
+pub mod listen;
+pub mod paging;
+pub mod rendering;
+pub mod sandbox;
 pub mod schema;
+pub mod schema_cache;
+pub mod snapshot;
+pub mod spill;
+pub mod sqlstate;
+pub mod tuning;
+pub mod value;
 
-pub use connection::{DbConnection, DbConnectionConfig, SslMode};
-pub use error::DbError;
+pub use connection::{DbConnection, DbConnectionConfig, SessionSettings, SslMode};
+pub use error::{DbError, PgErrorInfo};
 pub use executor::QueryExecutor;
-pub use schema::{ColumnInfo, DatabaseSchema, SchemaTable, TableType};
+pub use lineage::{ColumnLineage, LineageLink};
+pub use listen::{Listener, Notification};
+pub use paging::{Page, PageCursor};
+pub use rendering::TypeRendering;
+pub use sandbox::{Sandbox, SandboxMode, SandboxReport, TableEffect};
+pub use schema::{
+    BackendActivity, ColumnInfo, DatabaseSchema, DependencyKind, EnumType, ForeignKeyRef,
+    MatviewFreshness, RlsPolicy, RolePrivileges, SchemaTable,
+    TableDependency, TableSecurity, TableSize, TableType,
+};
+pub use postgres_agent_util::ident::{quote_ident, quote_qualified, split_qualified_name};
+pub use schema_cache::SchemaCache;
+pub use snapshot::{Snapshot, TableSnapshot};
+pub use spill::SpilledRows;
+pub use sqlstate::SqlState;
+pub use tuning::{QueryPlan, TableStats};
+pub use value::{NumericOutput, TimeZoneMode};
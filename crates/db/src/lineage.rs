@@ -0,0 +1,490 @@
+//! Column lineage.
+//!
+//! [`trace_column`] reports where the data of a column comes from and what
+//! reads it. Upstream, a view column is traced through view definitions down
+//! to table columns; a table column through its default, generation
+//! expression, foreign key and the triggers that mention it. Downstream, it
+//! lists the views built on the column, directly or through other views,
+//! the foreign keys referencing it, the functions mentioning it and, with
+//! `pg_stat_statements` installed, the most frequent queries mentioning it.
+//!
+//! PostgreSQL records which columns a view reads, not which of them feed
+//! each output column, so the sources of a view column are all the columns
+//! the view reads; the view definitions are included so that the expression
+//! behind the column can be read off.
+
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::connection::DbConnection;
+use crate::error::DbError;
+use postgres_agent_util::ident::quote_qualified;
+
+/// Views are followed this many levels up and down.
+const MAX_DEPTH: i32 = 5;
+
+/// Queries from `pg_stat_statements` reported.
+const MAX_QUERIES: i64 = 10;
+
+/// The column, its relation and where stored values come from.
+const COLUMN_SQL: &str = r#"
+    SELECT
+        c.relkind::text,
+        format_type(a.atttypid, a.atttypmod),
+        CASE WHEN a.attgenerated = '' THEN coalesce(
+            pg_get_expr(ad.adbin, ad.adrelid),
+            CASE a.attidentity
+                WHEN 'a' THEN 'GENERATED ALWAYS AS IDENTITY'
+                WHEN 'd' THEN 'GENERATED BY DEFAULT AS IDENTITY'
+            END
+        ) END,
+        CASE WHEN a.attgenerated <> '' THEN pg_get_expr(ad.adbin, ad.adrelid) END,
+        (
+            SELECT rn.nspname || '.' || rc.relname || '.' || ra.attname
+            FROM pg_catalog.pg_constraint con
+            JOIN pg_catalog.pg_class rc ON rc.oid = con.confrelid
+            JOIN pg_catalog.pg_namespace rn ON rn.oid = rc.relnamespace
+            JOIN pg_catalog.pg_attribute ra ON ra.attrelid = con.confrelid
+                AND ra.attnum = con.confkey[array_position(con.conkey, a.attnum)]
+            WHERE con.conrelid = c.oid AND con.contype = 'f' AND a.attnum = ANY (con.conkey)
+            ORDER BY con.conname
+            LIMIT 1
+        )
+    FROM pg_catalog.pg_class c
+    JOIN pg_catalog.pg_attribute a ON a.attrelid = c.oid
+        AND a.attname = $2 AND a.attnum > 0 AND NOT a.attisdropped
+    LEFT JOIN pg_catalog.pg_attrdef ad ON ad.adrelid = c.oid AND ad.adnum = a.attnum
+    WHERE c.oid = to_regclass($1)
+"#;
+
+/// Triggers of a table whose function mentions the column.
+const TRIGGERS_SQL: &str = r#"
+    SELECT tg.tgname || ' (' || tg.tgfoid::regproc::text || ')'
+    FROM pg_catalog.pg_trigger tg
+    JOIN pg_catalog.pg_proc p ON p.oid = tg.tgfoid
+    WHERE tg.tgrelid = to_regclass($1) AND NOT tg.tgisinternal
+    AND position(lower($2) IN lower(p.prosrc)) > 0
+    ORDER BY 1
+"#;
+
+/// Columns read by a view.
+const VIEW_SOURCES_SQL: &str = r#"
+    SELECT DISTINCT n.nspname::text, c.relname::text, c.relkind::text, a.attname::text
+    FROM pg_catalog.pg_rewrite r
+    JOIN pg_catalog.pg_depend d ON d.classid = 'pg_catalog.pg_rewrite'::regclass
+        AND d.objid = r.oid
+        AND d.refclassid = 'pg_catalog.pg_class'::regclass
+        AND d.refobjsubid > 0
+    JOIN pg_catalog.pg_class c ON c.oid = d.refobjid
+    JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+    JOIN pg_catalog.pg_attribute a ON a.attrelid = c.oid AND a.attnum = d.refobjsubid
+    WHERE r.ev_class = to_regclass($1) AND c.oid <> r.ev_class
+    ORDER BY 1, 2, 4
+"#;
+
+/// Views reading the column, then views reading those views.
+const DEPENDENTS_SQL: &str = r#"
+    WITH RECURSIVE target AS (
+        SELECT a.attrelid, a.attnum
+        FROM pg_catalog.pg_attribute a
+        WHERE a.attrelid = to_regclass($1) AND a.attname = $2
+    ),
+    dependents (oid, via, depth) AS (
+        SELECT r.ev_class, t.attrelid, 1
+        FROM target t
+        JOIN pg_catalog.pg_depend d ON d.refobjid = t.attrelid AND d.refobjsubid = t.attnum
+            AND d.classid = 'pg_catalog.pg_rewrite'::regclass
+        JOIN pg_catalog.pg_rewrite r ON r.oid = d.objid
+        WHERE r.ev_class <> t.attrelid
+        UNION
+        SELECT r.ev_class, dep.oid, dep.depth + 1
+        FROM dependents dep
+        JOIN pg_catalog.pg_depend d ON d.refobjid = dep.oid
+            AND d.classid = 'pg_catalog.pg_rewrite'::regclass
+        JOIN pg_catalog.pg_rewrite r ON r.oid = d.objid
+        WHERE r.ev_class <> dep.oid AND dep.depth < $3
+    )
+    SELECT DISTINCT ON (dep.oid)
+        c.relkind::text,
+        n.nspname || '.' || c.relname,
+        vn.nspname || '.' || vc.relname,
+        dep.depth
+    FROM dependents dep
+    JOIN pg_catalog.pg_class c ON c.oid = dep.oid
+    JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+    JOIN pg_catalog.pg_class vc ON vc.oid = dep.via
+    JOIN pg_catalog.pg_namespace vn ON vn.oid = vc.relnamespace
+    ORDER BY dep.oid, dep.depth
+"#;
+
+/// Foreign key columns referencing the column.
+const REFERENCED_BY_SQL: &str = r#"
+    SELECT n.nspname || '.' || c.relname || '.' || a.attname || ' (' || con.conname || ')'
+    FROM pg_catalog.pg_attribute t
+    JOIN pg_catalog.pg_constraint con ON con.confrelid = t.attrelid
+        AND con.contype = 'f' AND t.attnum = ANY (con.confkey)
+    JOIN pg_catalog.pg_class c ON c.oid = con.conrelid
+    JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+    JOIN pg_catalog.pg_attribute a ON a.attrelid = con.conrelid
+        AND a.attnum = con.conkey[array_position(con.confkey, t.attnum)]
+    WHERE t.attrelid = to_regclass($1) AND t.attname = $2
+    ORDER BY 1
+"#;
+
+/// User functions whose body mentions both the relation and the column.
+const FUNCTIONS_SQL: &str = r#"
+    SELECT n.nspname || '.' || p.proname || '(' || pg_get_function_identity_arguments(p.oid) || ')'
+    FROM pg_catalog.pg_proc p
+    JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace
+    WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+    AND n.nspname NOT LIKE 'pg\_%'
+    AND position(lower($1) IN lower(p.prosrc)) > 0
+    AND position(lower($2) IN lower(p.prosrc)) > 0
+    ORDER BY 1
+"#;
+
+/// Frequent statements mentioning both the relation and the column.
+const QUERIES_SQL: &str = r#"
+    SELECT query, calls
+    FROM pg_stat_statements
+    WHERE position(lower($1) IN lower(query)) > 0
+    AND position(lower($2) IN lower(query)) > 0
+    ORDER BY calls DESC
+    LIMIT $3
+"#;
+
+/// A relation or column linked to the traced column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineageLink {
+    /// Kind of relation: `table`, `view`, `materialized_view`, ...
+    pub kind: String,
+    /// `schema.relation.column` for sources, `schema.relation` for views.
+    pub name: String,
+    /// The view reading a source, or the relation a dependent view reads.
+    pub via: String,
+    /// Views between the link and the traced column, plus one.
+    pub depth: i32,
+}
+
+/// Definition of a view on the upstream path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewDefinition {
+    /// `schema.view`.
+    pub view: String,
+    /// The view's `SELECT`.
+    pub definition: String,
+}
+
+/// A statement from `pg_stat_statements`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryUse {
+    /// Normalized statement text.
+    pub query: String,
+    /// Times it ran since statistics were reset.
+    pub calls: i64,
+}
+
+/// Where the data of a column comes from and what reads it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnLineage {
+    /// `schema.relation.column`.
+    pub column: String,
+    /// Kind of relation holding the column.
+    pub kind: String,
+    /// Column type.
+    pub data_type: String,
+    /// Default value or identity.
+    pub default: Option<String>,
+    /// Expression of a generated column.
+    pub generated: Option<String>,
+    /// Column referenced by a foreign key, as `schema.table.column`.
+    pub references: Option<String>,
+    /// Triggers on the table whose function mentions the column.
+    pub triggers: Vec<String>,
+    /// Columns read by the views the column comes from.
+    pub sources: Vec<LineageLink>,
+    /// Definitions of those views.
+    pub definitions: Vec<ViewDefinition>,
+    /// Views reading the column, directly or through other views.
+    pub dependents: Vec<LineageLink>,
+    /// Foreign key columns referencing the column, with the constraint.
+    pub referenced_by: Vec<String>,
+    /// Functions mentioning the relation and the column.
+    pub functions: Vec<String>,
+    /// Frequent statements mentioning them.
+    pub queries: Vec<QueryUse>,
+}
+
+impl ColumnLineage {
+    /// A few lines on where the column comes from and what reads it.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut lines = vec![format!("{} ({}, {})", self.column, self.data_type, self.kind)];
+        if let Some(generated) = &self.generated {
+            lines.push(format!("Generated as {}", generated));
+        }
+        if let Some(default) = &self.default {
+            lines.push(format!("Default: {}", default));
+        }
+        if let Some(references) = &self.references {
+            lines.push(format!("References {}", references));
+        }
+        if !self.triggers.is_empty() {
+            lines.push(format!("May be set by triggers: {}", self.triggers.join(", ")));
+        }
+        let direct: Vec<&str> = self
+            .sources
+            .iter()
+            .filter(|s| s.depth == 1)
+            .map(|s| s.name.as_str())
+            .collect();
+        if !direct.is_empty() {
+            lines.push(format!("Derived from: {}", direct.join(", ")));
+        }
+        let base: Vec<&str> = self
+            .sources
+            .iter()
+            .filter(|s| s.depth > 1 && !is_view(&s.kind))
+            .map(|s| s.name.as_str())
+            .collect();
+        if !base.is_empty() {
+            lines.push(format!("Ultimately from: {}", base.join(", ")));
+        }
+        if !self.dependents.is_empty() {
+            let views: Vec<&str> = self.dependents.iter().map(|d| d.name.as_str()).collect();
+            lines.push(format!("Read by views: {}", views.join(", ")));
+        }
+        if !self.referenced_by.is_empty() {
+            lines.push(format!("Referenced by: {}", self.referenced_by.join(", ")));
+        }
+        if !self.functions.is_empty() {
+            lines.push(format!("Mentioned in functions: {}", self.functions.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Trace `column` of `schema.relation`.
+///
+/// # Errors
+/// Returns `DbError::ColumnNotFound` if there is no such column, and
+/// `DbError::QueryFailed` if a catalog query fails.
+pub async fn trace_column(
+    db: &DbConnection,
+    schema: &str,
+    relation: &str,
+    column: &str,
+) -> Result<ColumnLineage, DbError> {
+    let pool = db.pool();
+    let regclass = quote_qualified(schema, relation);
+    let name = format!("{}.{}", schema, relation);
+
+    type Row = (String, String, Option<String>, Option<String>, Option<String>);
+    let row: Option<Row> = sqlx::query_as(COLUMN_SQL)
+        .bind(&regclass)
+        .bind(column)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| DbError::query_failed(COLUMN_SQL, &e))?;
+    let Some((relkind, data_type, default, generated, references)) = row else {
+        return Err(DbError::ColumnNotFound {
+            column: format!("{}.{}", name, column),
+        });
+    };
+
+    let triggers: Vec<String> = sqlx::query_scalar(TRIGGERS_SQL)
+        .bind(&regclass)
+        .bind(column)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DbError::query_failed(TRIGGERS_SQL, &e))?;
+
+    // Walk up through the views the column comes from
+    let mut sources = Vec::new();
+    let mut definitions = Vec::new();
+    let mut queue = VecDeque::new();
+    let mut visited = HashSet::new();
+    if is_view(relation_kind(&relkind)) {
+        queue.push_back((name.clone(), regclass.clone(), 1));
+        visited.insert(name.clone());
+    }
+    while let Some((view, view_regclass, depth)) = queue.pop_front() {
+        let definition: Option<String> =
+            sqlx::query_scalar("SELECT pg_get_viewdef(to_regclass($1), true)")
+                .bind(&view_regclass)
+                .fetch_one(pool)
+                .await
+                .map_err(|e| DbError::query_failed("pg_get_viewdef", &e))?;
+        if let Some(definition) = definition {
+            definitions.push(ViewDefinition {
+                view: view.clone(),
+                definition: definition.trim().to_string(),
+            });
+        }
+
+        let read: Vec<(String, String, String, String)> = sqlx::query_as(VIEW_SOURCES_SQL)
+            .bind(&view_regclass)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| DbError::query_failed(VIEW_SOURCES_SQL, &e))?;
+        for (source_schema, source_relation, source_kind, source_column) in read {
+            let kind = relation_kind(&source_kind);
+            let source = format!("{}.{}", source_schema, source_relation);
+            if is_view(kind) && depth < MAX_DEPTH && visited.insert(source.clone()) {
+                let source_regclass = quote_qualified(&source_schema, &source_relation);
+                queue.push_back((source.clone(), source_regclass, depth + 1));
+            }
+            sources.push(LineageLink {
+                kind: kind.to_string(),
+                name: format!("{}.{}", source, source_column),
+                via: view.clone(),
+                depth,
+            });
+        }
+    }
+
+    let rows: Vec<(String, String, String, i32)> = sqlx::query_as(DEPENDENTS_SQL)
+        .bind(&regclass)
+        .bind(column)
+        .bind(MAX_DEPTH)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DbError::query_failed(DEPENDENTS_SQL, &e))?;
+    let mut dependents: Vec<LineageLink> = rows
+        .into_iter()
+        .map(|(kind, name, via, depth)| LineageLink {
+            kind: relation_kind(&kind).to_string(),
+            name,
+            via,
+            depth,
+        })
+        .collect();
+    dependents.sort_by(|a, b| a.depth.cmp(&b.depth).then_with(|| a.name.cmp(&b.name)));
+
+    let referenced_by: Vec<String> = sqlx::query_scalar(REFERENCED_BY_SQL)
+        .bind(&regclass)
+        .bind(column)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DbError::query_failed(REFERENCED_BY_SQL, &e))?;
+
+    let functions: Vec<String> = sqlx::query_scalar(FUNCTIONS_SQL)
+        .bind(relation)
+        .bind(column)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DbError::query_failed(FUNCTIONS_SQL, &e))?;
+
+    Ok(ColumnLineage {
+        column: format!("{}.{}", name, column),
+        kind: relation_kind(&relkind).to_string(),
+        data_type,
+        default,
+        generated,
+        references,
+        triggers,
+        sources,
+        definitions,
+        dependents,
+        referenced_by,
+        functions,
+        queries: frequent_queries(db, relation, column).await,
+    })
+}
+
+/// Statements mentioning the relation and column, if `pg_stat_statements`
+/// is installed and readable.
+async fn frequent_queries(db: &DbConnection, relation: &str, column: &str) -> Vec<QueryUse> {
+    let installed: Option<bool> =
+        sqlx::query_scalar("SELECT to_regclass('pg_stat_statements') IS NOT NULL")
+            .fetch_one(db.pool())
+            .await
+            .ok();
+    if installed != Some(true) {
+        return Vec::new();
+    }
+
+    let rows: Result<Vec<(String, i64)>, _> = sqlx::query_as(QUERIES_SQL)
+        .bind(relation)
+        .bind(column)
+        .bind(MAX_QUERIES)
+        .fetch_all(db.pool())
+        .await;
+    match rows {
+        Ok(rows) => rows
+            .into_iter()
+            .map(|(query, calls)| QueryUse { query, calls })
+            .collect(),
+        Err(e) => {
+            debug!("Failed to read pg_stat_statements: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Label of a `pg_class.relkind`.
+fn relation_kind(relkind: &str) -> &'static str {
+    match relkind {
+        "r" => "table",
+        "p" => "partitioned_table",
+        "v" => "view",
+        "m" => "materialized_view",
+        "f" => "foreign_table",
+        _ => "other",
+    }
+}
+
+/// Whether a relation kind is defined by a query.
+fn is_view(kind: &str) -> bool {
+    matches!(kind, "view" | "materialized_view")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(kind: &str, name: &str, via: &str, depth: i32) -> LineageLink {
+        LineageLink {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            via: via.to_string(),
+            depth,
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let lineage = ColumnLineage {
+            column: "public.users.lifetime_value".to_string(),
+            kind: "view".to_string(),
+            data_type: "numeric".to_string(),
+            default: None,
+            generated: None,
+            references: None,
+            triggers: Vec::new(),
+            sources: vec![
+                link("view", "public.order_totals.total", "public.users", 1),
+                link("table", "public.users_base.id", "public.users", 1),
+                link("table", "public.orders.amount", "public.order_totals", 2),
+            ],
+            definitions: Vec::new(),
+            dependents: vec![link("materialized_view", "reporting.ltv", "public.users", 1)],
+            referenced_by: Vec::new(),
+            functions: Vec::new(),
+            queries: Vec::new(),
+        };
+        assert_eq!(
+            lineage.summary(),
+            "public.users.lifetime_value (numeric, view)\n\
+             Derived from: public.order_totals.total, public.users_base.id\n\
+             Ultimately from: public.orders.amount\n\
+             Read by views: reporting.ltv"
+        );
+    }
+}
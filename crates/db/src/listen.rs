@@ -0,0 +1,80 @@
+//! LISTEN/NOTIFY subscriptions.
+//!
+//! A [`Listener`] takes one connection from the pool for as long as it
+//! lives and uses it only for notifications, so queries on the same pool
+//! never share it.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::postgres::PgListener;
+use tracing::debug;
+
+use crate::{DbConnection, DbError};
+
+/// A notification received on a subscribed channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    /// Channel the notification was sent on.
+    pub channel: String,
+    /// Payload, empty if none was given.
+    pub payload: String,
+    /// Process ID of the notifying backend.
+    pub process_id: u32,
+    /// When the notification was received.
+    pub received_at: DateTime<Utc>,
+}
+
+/// A subscription to one or more notification channels.
+#[derive(Debug)]
+pub struct Listener {
+    /// Dedicated listening connection.
+    inner: PgListener,
+}
+
+impl Listener {
+    /// Take a connection from the pool of `db` for listening.
+    ///
+    /// # Errors
+    /// Returns `DbError::Database` if no connection can be acquired.
+    pub async fn connect(db: &DbConnection) -> Result<Self, DbError> {
+        let inner = PgListener::connect_with(db.pool()).await?;
+        Ok(Self { inner })
+    }
+
+    /// Subscribe to `channels`.
+    ///
+    /// Channel names are used exactly as given; PostgreSQL folds unquoted
+    /// names in `NOTIFY` to lower case.
+    ///
+    /// # Errors
+    /// Returns `DbError::QueryFailed` if a `LISTEN` fails.
+    pub async fn listen(&mut self, channels: &[String]) -> Result<(), DbError> {
+        for channel in channels {
+            debug!("Listening on {}", channel);
+            self.inner
+                .listen(channel)
+                .await
+                .map_err(|e| DbError::query_failed(format!("LISTEN {}", channel), &e))?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the next notification.
+    ///
+    /// A lost connection is re-established and the channels subscribed
+    /// again; notifications sent in between are lost.
+    ///
+    /// # Errors
+    /// Returns `DbError::Database` if the connection fails and cannot be
+    /// re-established.
+    pub async fn recv(&mut self) -> Result<Notification, DbError> {
+        let notification = self.inner.recv().await?;
+        Ok(Notification {
+            channel: notification.channel().to_string(),
+            payload: notification.payload().to_string(),
+            process_id: notification.process_id(),
+            received_at: Utc::now(),
+        })
+    }
+}
@@ -0,0 +1,297 @@
+//! Result pagination.
+//!
+//! Queries ending in a simple `ORDER BY` are paged by keyset: the next page
+//! starts after the sort key of the last row shown. Other queries, and pages
+//! whose boundary falls inside a run of equal keys, fall back to `OFFSET`.
+//! The position is handed out as an opaque continuation token.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::DbError;
+use crate::executor::QueryResult;
+
+/// Text wrapped around the start of a paged query.
+pub const PAGE_PREFIX: &str = "SELECT * FROM (";
+
+/// Position of the next page of a query.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageCursor {
+    /// Rows returned so far.
+    pub offset: usize,
+    /// Sort key of the last row returned, when keyset paging applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<Vec<serde_json::Value>>,
+}
+
+impl PageCursor {
+    /// Encode the cursor as a continuation token.
+    #[must_use]
+    pub fn encode(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Decode a continuation token.
+    ///
+    /// # Errors
+    /// Returns `DbError::InvalidCursor` if the token is malformed.
+    pub fn decode(token: &str) -> Result<Self, DbError> {
+        let invalid = || DbError::InvalidCursor {
+            cursor: token.to_string(),
+        };
+        if !token.len().is_multiple_of(2) || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        serde_json::from_slice(&bytes).map_err(|_| invalid())
+    }
+}
+
+/// One page of a query result.
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    /// Rows of this page; `truncated` is set when more follow.
+    pub result: QueryResult,
+    /// Token for the next page, if any.
+    pub next: Option<String>,
+}
+
+/// Sort order a query can be keyset-paged on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeysetOrder {
+    /// Output column names, in sort order.
+    pub columns: Vec<String>,
+    /// Whether the sort is descending.
+    pub descending: bool,
+}
+
+/// The keyset order of a query ending in `ORDER BY` on plain columns.
+///
+/// Returns `None` for expressions, positional references, `NULLS` clauses,
+/// mixed directions, or a trailing `LIMIT`, `OFFSET`, `FETCH` or `FOR`.
+#[must_use]
+pub fn keyset_order(sql: &str) -> Option<KeysetOrder> {
+    let sql = strip_terminator(sql);
+    let start = last_top_level_order_by(sql)?;
+    let mut columns = Vec::new();
+    let mut direction = None;
+    for item in sql[start..].split(',') {
+        let words: Vec<&str> = item.split_whitespace().collect();
+        let descending = match words.as_slice() {
+            [_] => false,
+            [_, dir] if dir.eq_ignore_ascii_case("asc") => false,
+            [_, dir] if dir.eq_ignore_ascii_case("desc") => true,
+            _ => return None,
+        };
+        if *direction.get_or_insert(descending) != descending {
+            return None;
+        }
+        columns.push(column_name(words[0])?);
+    }
+    Some(KeysetOrder {
+        columns,
+        descending: direction?,
+    })
+}
+
+/// Wrap a query so that it returns `limit` rows starting at `offset`.
+#[must_use]
+pub fn page_sql(sql: &str, offset: usize, limit: usize) -> String {
+    format!(
+        "{}{}\n) AS page LIMIT {} OFFSET {}",
+        PAGE_PREFIX,
+        strip_terminator(sql),
+        limit,
+        offset
+    )
+}
+
+/// Wrap an ordered query so that it returns `limit` rows after `after`.
+///
+/// Returns `None` if a key value cannot be written as a literal.
+#[must_use]
+pub fn keyset_sql(
+    sql: &str,
+    order: &KeysetOrder,
+    after: &[serde_json::Value],
+    limit: usize,
+) -> Option<String> {
+    if after.len() != order.columns.len() {
+        return None;
+    }
+    let literals = after.iter().map(literal).collect::<Option<Vec<_>>>()?;
+    let columns: Vec<String> = order.columns.iter().map(|c| quote_ident(c)).collect();
+    let (op, dir) = if order.descending {
+        ("<", "DESC")
+    } else {
+        (">", "ASC")
+    };
+    let sort: Vec<String> = columns.iter().map(|c| format!("{} {}", c, dir)).collect();
+    Some(format!(
+        "{}{}\n) AS page WHERE ({}) {} ({}) ORDER BY {} LIMIT {}",
+        PAGE_PREFIX,
+        strip_terminator(sql),
+        columns.join(", "),
+        op,
+        literals.join(", "),
+        sort.join(", "),
+        limit
+    ))
+}
+
+/// Sort key of `row`, if every value can be compared as a literal.
+#[must_use]
+pub fn row_key(
+    order: &KeysetOrder,
+    row: &serde_json::Map<String, serde_json::Value>,
+) -> Option<Vec<serde_json::Value>> {
+    order
+        .columns
+        .iter()
+        .map(|column| row.get(column).filter(|v| literal(v).is_some()).cloned())
+        .collect()
+}
+
+/// Drop a trailing semicolon and whitespace.
+fn strip_terminator(sql: &str) -> &str {
+    sql.trim_end().trim_end_matches(';').trim_end()
+}
+
+/// Byte offset just past the last `ORDER BY` outside parentheses and quotes.
+fn last_top_level_order_by(sql: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut found = None;
+    let bytes = sql.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match (quote, b) {
+            (Some(q), _) if b == q => quote = None,
+            (Some(_), _) => {}
+            (None, b'\'' | b'"') => quote = Some(b),
+            (None, b'(') => depth += 1,
+            (None, b')') => depth = depth.saturating_sub(1),
+            (None, _) if depth == 0 && is_keyword_at(sql, i, "ORDER") => {
+                let rest = sql[i + 5..].trim_start();
+                if is_keyword_at(rest, 0, "BY") {
+                    found = Some(sql.len() - rest.len() + 2);
+                }
+            }
+            (None, _) => {}
+        }
+    }
+    found
+}
+
+/// Whether `keyword` starts at byte `i` of `sql` as a whole word.
+fn is_keyword_at(sql: &str, i: usize, keyword: &str) -> bool {
+    let Some(word) = sql.get(i..i + keyword.len()) else {
+        return false;
+    };
+    let boundary = |c: Option<char>| c.is_none_or(|c| !c.is_alphanumeric() && c != '_');
+    word.eq_ignore_ascii_case(keyword)
+        && boundary(sql[..i].chars().next_back())
+        && boundary(sql[i + keyword.len()..].chars().next())
+}
+
+/// Output column name for an `ORDER BY` item, folding unquoted case.
+fn column_name(item: &str) -> Option<String> {
+    let name = match item.rsplit_once('.') {
+        Some((_, name)) if !item.starts_with('"') || item.ends_with('"') => name,
+        _ => item,
+    };
+    if let Some(quoted) = name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        return (!quoted.is_empty() && !quoted.contains('"')).then(|| quoted.to_string());
+    }
+    let valid = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    valid.then(|| name.to_lowercase())
+}
+
+/// Quote an identifier.
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A JSON scalar as a SQL literal; `None` for nulls, containers and
+/// placeholders of values that could not be decoded.
+fn literal(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Bool(b) => Some(b.to_string().to_uppercase()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::String(s) if !(s.starts_with('<') && s.ends_with('>')) => {
+            Some(format!("'{}'", s.replace('\'', "''")))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_keyset_order() {
+        let order =
+            keyset_order("SELECT * FROM t o ORDER BY o.created_at DESC, \"Id\" desc;").unwrap();
+        assert_eq!(order.columns, vec!["created_at", "Id"]);
+        assert!(order.descending);
+
+        let sub = "SELECT * FROM (SELECT id FROM t ORDER BY id) s";
+        assert_eq!(keyset_order(sub), None);
+        assert_eq!(keyset_order("SELECT id FROM t"), None);
+        assert_eq!(keyset_order("SELECT id FROM t ORDER BY id LIMIT 5"), None);
+        assert_eq!(keyset_order("SELECT id FROM t ORDER BY 1"), None);
+        assert_eq!(keyset_order("SELECT id FROM t ORDER BY lower(name)"), None);
+        assert_eq!(keyset_order("SELECT id FROM t ORDER BY a, b DESC"), None);
+        assert_eq!(keyset_order("SELECT id FROM t ORDER BY a NULLS LAST"), None);
+    }
+
+    #[test]
+    fn test_keyset_sql() {
+        let order = keyset_order("SELECT id, name FROM t ORDER BY name, id").unwrap();
+        let sql = keyset_sql(
+            "SELECT id, name FROM t ORDER BY name, id",
+            &order,
+            &[json!("O'Neil"), json!(7)],
+            11,
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "SELECT * FROM (SELECT id, name FROM t ORDER BY name, id\n) AS page \
+             WHERE (\"name\", \"id\") > ('O''Neil', 7) ORDER BY \"name\" ASC, \"id\" ASC LIMIT 11"
+        );
+        assert_eq!(
+            keyset_sql("SELECT 1", &order, &[json!(null), json!(1)], 11),
+            None
+        );
+        assert_eq!(
+            page_sql("SELECT id FROM t ORDER BY id; ", 20, 11),
+            "SELECT * FROM (SELECT id FROM t ORDER BY id\n) AS page LIMIT 11 OFFSET 20"
+        );
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = PageCursor {
+            offset: 200,
+            after: Some(vec![json!("2024-01-01"), json!(42)]),
+        };
+        assert_eq!(PageCursor::decode(&cursor.encode()).unwrap(), cursor);
+        assert!(PageCursor::decode("zz").is_err());
+        assert!(PageCursor::decode("abc").is_err());
+    }
+}
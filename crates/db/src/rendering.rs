@@ -0,0 +1,409 @@
+//! Rendering of binary, network, geometric and text-search values.
+//!
+//! sqlx has no decoders for these types without extra features, so they
+//! are read from PostgreSQL's binary format here. How `bytea`, `point`,
+//! PostGIS geometries and `tsvector` values are shown is chosen per type by
+//! [`TypeRendering`].
+
+use std::fmt::Write as _;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use base64::Engine as _;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// `bytea` bytes shown before a value is cut short.
+pub const BYTEA_PREVIEW_BYTES: usize = 64;
+
+/// How `bytea` values are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteaRendering {
+    /// PostgreSQL's `\x` hex notation.
+    #[default]
+    Hex,
+    /// Standard base64.
+    Base64,
+    /// Only the length, e.g. `<1024 bytes>`.
+    Length,
+}
+
+/// How `point` values and PostGIS geometries are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeometryRendering {
+    /// Well-known text, with an `SRID=` prefix when one is set.
+    #[default]
+    Wkt,
+    /// PostgreSQL's own text form: `(x,y)` for points, hex EWKB for
+    /// geometries.
+    Native,
+}
+
+/// How `tsvector` values are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TsvectorRendering {
+    /// PostgreSQL's text form, e.g. `'cat':3 'fat':2A`.
+    #[default]
+    Text,
+    /// A JSON array of the lexemes.
+    Lexemes,
+}
+
+/// Rendering of each type with a choice of representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TypeRendering {
+    /// `bytea` values.
+    pub bytea: ByteaRendering,
+    /// `point` values.
+    pub point: GeometryRendering,
+    /// PostGIS `geometry` and `geography` values.
+    pub geometry: GeometryRendering,
+    /// `tsvector` values.
+    pub tsvector: TsvectorRendering,
+}
+
+impl TypeRendering {
+    /// Set the rendering of `type_name` by style name, e.g. `bytea` to
+    /// `base64`.
+    ///
+    /// # Errors
+    /// Returns an error for an unknown type or a style it does not support.
+    pub fn set(&mut self, type_name: &str, style: &str) -> Result<(), String> {
+        fn parse<T: DeserializeOwned>(type_name: &str, style: &str) -> Result<T, String> {
+            serde_json::from_value(Value::String(style.trim().to_lowercase()))
+                .map_err(|_| format!("Unknown rendering '{}' for {}", style, type_name))
+        }
+        match type_name.trim().to_lowercase().as_str() {
+            "bytea" => self.bytea = parse(type_name, style)?,
+            "point" => self.point = parse(type_name, style)?,
+            "geometry" | "geography" => self.geometry = parse(type_name, style)?,
+            "tsvector" => self.tsvector = parse(type_name, style)?,
+            other => {
+                return Err(format!(
+                    "No rendering options for type '{}' \
+                     (expected bytea, point, geometry or tsvector)",
+                    other
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the binary value of a column of type `type_name`, given in
+    /// upper case; `None` if the type is not handled here or the value does
+    /// not decode.
+    pub(crate) fn render(&self, type_name: &str, bytes: &[u8]) -> Option<Value> {
+        match type_name {
+            "BYTEA" => Some(Value::String(render_bytea(bytes, self.bytea))),
+            "INET" | "CIDR" => inet_text(bytes).map(Value::String),
+            "POINT" => point_text(bytes, self.point).map(Value::String),
+            "GEOMETRY" | "GEOGRAPHY" => match self.geometry {
+                GeometryRendering::Wkt => ewkb_to_wkt(bytes).map(Value::String),
+                GeometryRendering::Native => Some(Value::String(hex(bytes).to_uppercase())),
+            },
+            "TSVECTOR" => tsvector(bytes, self.tsvector),
+            _ => None,
+        }
+    }
+}
+
+/// Lower-case hex digits of `bytes`.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut out, b| {
+        let _ = write!(out, "{:02x}", b);
+        out
+    })
+}
+
+/// Render `bytea`, cutting long values short with their length, since
+/// large blobs would otherwise flood tables and the model's context.
+fn render_bytea(bytes: &[u8], style: ByteaRendering) -> String {
+    let preview = &bytes[..bytes.len().min(BYTEA_PREVIEW_BYTES)];
+    let text = match style {
+        ByteaRendering::Hex => format!("\\x{}", hex(preview)),
+        ByteaRendering::Base64 => base64::engine::general_purpose::STANDARD.encode(preview),
+        ByteaRendering::Length => return format!("<{} bytes>", bytes.len()),
+    };
+    if preview.len() < bytes.len() {
+        format!("{}… ({} bytes)", text, bytes.len())
+    } else {
+        text
+    }
+}
+
+/// Text of an `inet` or `cidr`: family, prefix bits, cidr flag, address
+/// length, then the address. A host address of an `inet` has no prefix.
+fn inet_text(bytes: &[u8]) -> Option<String> {
+    let [family, bits, is_cidr, len, address @ ..] = bytes else {
+        return None;
+    };
+    let (text, max_bits) = match (family, address.len()) {
+        (2, 4) => (Ipv4Addr::from(<[u8; 4]>::try_from(address).ok()?).to_string(), 32),
+        (3, 16) => (Ipv6Addr::from(<[u8; 16]>::try_from(address).ok()?).to_string(), 128),
+        _ => return None,
+    };
+    if usize::from(*len) != address.len() {
+        return None;
+    }
+    Some(if *is_cidr == 0 && *bits == max_bits {
+        text
+    } else {
+        format!("{}/{}", text, bits)
+    })
+}
+
+/// Text of a `point`: two big-endian doubles.
+fn point_text(bytes: &[u8], style: GeometryRendering) -> Option<String> {
+    let x = f64::from_be_bytes(bytes.get(..8)?.try_into().ok()?);
+    let y = f64::from_be_bytes(bytes.get(8..16)?.try_into().ok()?);
+    Some(match style {
+        GeometryRendering::Wkt => format!("POINT({} {})", x, y),
+        GeometryRendering::Native => format!("({},{})", x, y),
+    })
+}
+
+/// Well-known text of a PostGIS geometry in extended WKB.
+fn ewkb_to_wkt(bytes: &[u8]) -> Option<String> {
+    let mut reader = WkbReader { bytes, pos: 0 };
+    let wkt = reader.geometry(true, true)?;
+    (reader.pos == bytes.len()).then_some(wkt)
+}
+
+/// Cursor over (extended) well-known binary.
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl WkbReader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.bytes.get(self.pos..self.pos + N)?.try_into().ok()?;
+        self.pos += N;
+        Some(bytes)
+    }
+
+    fn u32(&mut self, little: bool) -> Option<u32> {
+        let bytes = self.take()?;
+        Some(if little { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn f64(&mut self, little: bool) -> Option<f64> {
+        let bytes = self.take()?;
+        Some(if little { f64::from_le_bytes(bytes) } else { f64::from_be_bytes(bytes) })
+    }
+
+    /// A count of elements, bounded by the bytes left to hold them.
+    fn count(&mut self, little: bool) -> Option<usize> {
+        let count = usize::try_from(self.u32(little)?).ok()?;
+        (count <= self.bytes.len() - self.pos).then_some(count)
+    }
+
+    /// One position of `ordinates` coordinates.
+    fn position(&mut self, little: bool, ordinates: usize) -> Option<String> {
+        let coords = (0..ordinates)
+            .map(|_| self.f64(little).map(|c| c.to_string()))
+            .collect::<Option<Vec<_>>>()?;
+        Some(coords.join(" "))
+    }
+
+    /// `(x y, x y, ...)` of a counted list of positions, or `EMPTY`.
+    fn positions(&mut self, little: bool, ordinates: usize) -> Option<String> {
+        let count = self.count(little)?;
+        if count == 0 {
+            return Some("EMPTY".to_string());
+        }
+        let positions = (0..count)
+            .map(|_| self.position(little, ordinates))
+            .collect::<Option<Vec<_>>>()?;
+        Some(format!("({})", positions.join(",")))
+    }
+
+    /// A geometry with its header; `tagged` writes its type name, which
+    /// members of multi-geometries omit.
+    fn geometry(&mut self, top_level: bool, tagged: bool) -> Option<String> {
+        let little = match self.take::<1>()? {
+            [0] => false,
+            [1] => true,
+            _ => return None,
+        };
+        let header = self.u32(little)?;
+        let mut srid = String::new();
+        if header & 0x2000_0000 != 0 {
+            let id = self.u32(little)?;
+            if top_level {
+                srid = format!("SRID={};", id);
+            }
+        }
+        // EWKB flags dimensions in the high bits, ISO WKB in the thousands
+        let code = header & 0x0FFF_FFFF;
+        let z = header & 0x8000_0000 != 0 || matches!(code / 1000, 1 | 3);
+        let m = header & 0x4000_0000 != 0 || matches!(code / 1000, 2 | 3);
+        let ordinates = 2 + usize::from(z) + usize::from(m);
+
+        let (name, body) = match code % 1000 {
+            1 => {
+                let position = self.position(little, ordinates)?;
+                // An empty point has NaN coordinates
+                let body = if position.split(' ').all(|c| c == "NaN") {
+                    "EMPTY".to_string()
+                } else {
+                    format!("({})", position)
+                };
+                ("POINT", body)
+            }
+            2 => ("LINESTRING", self.positions(little, ordinates)?),
+            3 => ("POLYGON", self.members(little, |r| r.positions(little, ordinates))?),
+            kind @ 4..=7 => {
+                let name = ["MULTIPOINT", "MULTILINESTRING", "MULTIPOLYGON", "GEOMETRYCOLLECTION"]
+                    [usize::try_from(kind - 4).ok()?];
+                (name, self.members(little, |r| r.geometry(false, kind == 7))?)
+            }
+            _ => return None,
+        };
+        if !tagged {
+            return Some(body);
+        }
+        let dims = match (z, m) {
+            (true, true) => " ZM",
+            (true, false) => " Z",
+            (false, true) => " M",
+            (false, false) => "",
+        };
+        let space = if dims.is_empty() && body != "EMPTY" { "" } else { " " };
+        Some(format!("{}{}{}{}{}", srid, name, dims, space, body))
+    }
+
+    /// `(a,b,...)` of a counted list of members read by `member`, or `EMPTY`.
+    fn members(
+        &mut self,
+        little: bool,
+        mut member: impl FnMut(&mut Self) -> Option<String>,
+    ) -> Option<String> {
+        let count = self.count(little)?;
+        if count == 0 {
+            return Some("EMPTY".to_string());
+        }
+        let members = (0..count).map(|_| member(self)).collect::<Option<Vec<_>>>()?;
+        Some(format!("({})", members.join(",")))
+    }
+}
+
+/// A `tsvector`: a lexeme count, then each lexeme as a NUL-terminated
+/// string with its positions, whose top two bits are the weight.
+fn tsvector(bytes: &[u8], style: TsvectorRendering) -> Option<Value> {
+    let count = usize::try_from(i32::from_be_bytes(bytes.get(..4)?.try_into().ok()?)).ok()?;
+    let mut rest = &bytes[4..];
+    let mut lexemes = Vec::new();
+    let mut text = Vec::new();
+    for _ in 0..count {
+        let end = rest.iter().position(|&b| b == 0)?;
+        let lexeme = std::str::from_utf8(&rest[..end]).ok()?.to_string();
+        let npos = usize::from(u16::from_be_bytes(rest.get(end + 1..end + 3)?.try_into().ok()?));
+        let positions = rest.get(end + 3..end + 3 + 2 * npos)?;
+        rest = &rest[end + 3 + 2 * npos..];
+
+        let mut entry = format!("'{}'", lexeme.replace('\\', "\\\\").replace('\'', "''"));
+        for (i, pair) in positions.chunks_exact(2).enumerate() {
+            let position = u16::from_be_bytes([pair[0], pair[1]]);
+            entry.push(if i == 0 { ':' } else { ',' });
+            let _ = write!(entry, "{}", position & 0x3FFF);
+            match position >> 14 {
+                3 => entry.push('A'),
+                2 => entry.push('B'),
+                1 => entry.push('C'),
+                _ => {}
+            }
+        }
+        text.push(entry);
+        lexemes.push(Value::String(lexeme));
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+    Some(match style {
+        TsvectorRendering::Text => Value::String(text.join(" ")),
+        TsvectorRendering::Lexemes => Value::Array(lexemes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_bytea() {
+        assert_eq!(render_bytea(&[0xde, 0xad, 0xbe, 0xef], ByteaRendering::Hex), "\\xdeadbeef");
+        assert_eq!(render_bytea(b"hello", ByteaRendering::Base64), "aGVsbG8=");
+        assert_eq!(render_bytea(&[0; 100], ByteaRendering::Length), "<100 bytes>");
+        let long = render_bytea(&[0xff; 100], ByteaRendering::Hex);
+        assert!(long.starts_with("\\xffff"));
+        assert!(long.ends_with("ff… (100 bytes)"));
+        assert_eq!(long.matches("ff").count(), BYTEA_PREVIEW_BYTES);
+    }
+
+    #[test]
+    fn test_inet_text() {
+        assert_eq!(inet_text(&[2, 32, 0, 4, 10, 0, 0, 1]).unwrap(), "10.0.0.1");
+        assert_eq!(inet_text(&[2, 24, 1, 4, 10, 0, 0, 0]).unwrap(), "10.0.0.0/24");
+        let mut v6 = vec![3, 64, 0, 16, 0x20, 0x01, 0x0d, 0xb8];
+        v6.extend([0; 12]);
+        assert_eq!(inet_text(&v6).unwrap(), "2001:db8::/64");
+        assert!(inet_text(&[2, 32, 0, 4, 10]).is_none());
+    }
+
+    #[test]
+    fn test_ewkb_to_wkt() {
+        // SRID=4326;POINT(1 2), little endian
+        let mut point = vec![1, 1, 0, 0, 0x20, 0xE6, 0x10, 0, 0];
+        point.extend(1.0f64.to_le_bytes());
+        point.extend(2.0f64.to_le_bytes());
+        assert_eq!(ewkb_to_wkt(&point).unwrap(), "SRID=4326;POINT(1 2)");
+
+        // LINESTRING Z (0 0 1,1.5 1 2), big endian
+        let mut line = vec![0, 0x80, 0, 0, 2, 0, 0, 0, 2];
+        for c in [0.0f64, 0.0, 1.0, 1.5, 1.0, 2.0] {
+            line.extend(c.to_be_bytes());
+        }
+        assert_eq!(ewkb_to_wkt(&line).unwrap(), "LINESTRING Z (0 0 1,1.5 1 2)");
+
+        // MULTIPOINT((3 4)) and an empty GEOMETRYCOLLECTION
+        let mut multi = vec![1, 4, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 0];
+        multi.extend(3.0f64.to_le_bytes());
+        multi.extend(4.0f64.to_le_bytes());
+        assert_eq!(ewkb_to_wkt(&multi).unwrap(), "MULTIPOINT((3 4))");
+        assert_eq!(ewkb_to_wkt(&[1, 7, 0, 0, 0, 0, 0, 0, 0]).unwrap(), "GEOMETRYCOLLECTION EMPTY");
+        assert!(ewkb_to_wkt(&point[..12]).is_none());
+    }
+
+    #[test]
+    fn test_tsvector() {
+        // 'cat':3 'fat':2A,4
+        let mut bytes = 2i32.to_be_bytes().to_vec();
+        bytes.extend(b"cat\0\0\x01\0\x03");
+        bytes.extend(b"fat\0\0\x02\xC0\x02\0\x04");
+        assert_eq!(
+            tsvector(&bytes, TsvectorRendering::Text).unwrap(),
+            Value::from("'cat':3 'fat':2A,4")
+        );
+        assert_eq!(
+            tsvector(&bytes, TsvectorRendering::Lexemes).unwrap(),
+            serde_json::json!(["cat", "fat"])
+        );
+    }
+
+    #[test]
+    fn test_type_rendering_set() {
+        let mut rendering = TypeRendering::default();
+        rendering.set("bytea", "Base64").unwrap();
+        rendering.set("geography", "native").unwrap();
+        assert_eq!(rendering.bytea, ByteaRendering::Base64);
+        assert_eq!(rendering.geometry, GeometryRendering::Native);
+        assert_eq!(rendering.point, GeometryRendering::Wkt);
+        assert!(rendering.set("bytea", "octal").is_err());
+        assert!(rendering.set("uuid", "text").is_err());
+    }
+}
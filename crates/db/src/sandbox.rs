@@ -0,0 +1,309 @@
+//! Simulation sandboxes.
+//!
+//! A [`Sandbox`] runs proposed mutations in a throwaway database on the same
+//! server and reports their effects, so that they can be reviewed before
+//! running against the real database. The sandbox database is either a
+//! clone of a template database (`CREATE DATABASE ... TEMPLATE`), or holds
+//! the tables the mutation references with a sample of their rows. It is
+//! dropped after each simulation.
+
+use std::fmt;
+
+use chrono::Utc;
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::connection::DbConnection;
+use crate::error::DbError;
+use crate::executor::QueryExecutor;
+use postgres_agent_util::ident::{quote_ident, quote_qualified, split_qualified_name};
+
+/// Columns of a table with types that exist in an empty database; other
+/// types such as enums and domains become `text`.
+const SANDBOX_COLUMNS_SQL: &str = r#"
+    SELECT a.attname::text,
+        CASE WHEN t.typnamespace = 'pg_catalog'::regnamespace
+            THEN format_type(a.atttypid, a.atttypmod)
+            ELSE 'text'
+        END
+    FROM pg_catalog.pg_attribute a
+    JOIN pg_catalog.pg_type t ON t.oid = a.atttypid
+    WHERE a.attrelid = to_regclass($1) AND a.attnum > 0 AND NOT a.attisdropped
+    ORDER BY a.attnum
+"#;
+
+/// Where the sandbox database comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SandboxMode {
+    /// Clone this database, which must have no open connections.
+    Template(String),
+    /// Copy the referenced tables, without constraints, defaults or
+    /// triggers, with up to this many rows each.
+    Sample(usize),
+}
+
+impl fmt::Display for SandboxMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Template(database) => write!(f, "clone of {}", database),
+            Self::Sample(rows) => write!(f, "sample of up to {} rows per table", rows),
+        }
+    }
+}
+
+/// Row count of a table before and after a simulation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableEffect {
+    /// Qualified table name.
+    pub table: String,
+    /// Rows before the statements ran.
+    pub rows_before: i64,
+    /// Rows after the statements ran.
+    pub rows_after: i64,
+}
+
+/// Effects of statements run in a sandbox.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxReport {
+    /// Where the sandbox came from.
+    pub mode: String,
+    /// Rows affected by the statements that ran.
+    pub rows_affected: u64,
+    /// Row counts of the referenced tables.
+    pub tables: Vec<TableEffect>,
+    /// Error of the statement that failed, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl fmt::Display for SandboxReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            Some(error) => write!(f, "Sandbox run ({}) failed: {}", self.mode, error)?,
+            None => write!(f, "Sandbox run ({}): {} rows affected", self.mode, self.rows_affected)?,
+        }
+        for table in &self.tables {
+            write!(f, "\n  {}: {} -> {} rows", table.table, table.rows_before, table.rows_after)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs mutations in throwaway databases cloned from the connected one.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    /// Connection to the real database.
+    source: DbConnection,
+    /// Where sandbox databases come from.
+    mode: SandboxMode,
+}
+
+impl Sandbox {
+    /// Create a sandbox for the database of `source`.
+    #[must_use]
+    pub fn new(source: DbConnection, mode: SandboxMode) -> Self {
+        Self { source, mode }
+    }
+
+    /// Get the sandbox mode.
+    #[must_use]
+    pub fn mode(&self) -> &SandboxMode {
+        &self.mode
+    }
+
+    /// Run `statements` in a new sandbox database and report their effects
+    /// on `tables` (`schema.table`); the sandbox is dropped afterwards.
+    ///
+    /// Statements stop at the first failure, which is reported rather than
+    /// returned.
+    ///
+    /// # Errors
+    /// Returns an error if the sandbox cannot be created, e.g. without the
+    /// `CREATEDB` privilege or while the template database is in use.
+    pub async fn simulate(
+        &self,
+        statements: &[String],
+        tables: &[String],
+    ) -> Result<SandboxReport, DbError> {
+        let name = format!(
+            "pg_agent_sandbox_{}_{}",
+            std::process::id(),
+            Utc::now().timestamp_millis()
+        );
+        let create = match &self.mode {
+            SandboxMode::Template(template) => {
+                format!("CREATE DATABASE {} TEMPLATE {}", quote_ident(&name), quote_ident(template))
+            }
+            SandboxMode::Sample(_) => format!("CREATE DATABASE {}", quote_ident(&name)),
+        };
+        debug!("Creating sandbox: {}", create);
+        sqlx::raw_sql(&create)
+            .execute(self.source.pool())
+            .await
+            .map_err(|e| DbError::query_failed(&create, &e))?;
+
+        let report = match self.source.connect_to(&name).await {
+            Ok(sandbox) => {
+                let report = self.run(&sandbox, statements, tables).await;
+                sandbox.close().await;
+                report
+            }
+            Err(e) => Err(e),
+        };
+
+        let drop = format!("DROP DATABASE IF EXISTS {}", quote_ident(&name));
+        if let Err(e) = sqlx::raw_sql(&drop).execute(self.source.pool()).await {
+            warn!("Failed to drop sandbox database {}: {}", name, e);
+        }
+        report
+    }
+
+    /// Fill the sandbox if needed, then run the statements in it.
+    async fn run(
+        &self,
+        sandbox: &DbConnection,
+        statements: &[String],
+        tables: &[String],
+    ) -> Result<SandboxReport, DbError> {
+        if let SandboxMode::Sample(rows) = self.mode {
+            for table in tables {
+                copy_sample(&self.source, sandbox, table, rows).await?;
+            }
+        }
+
+        let mut effects = Vec::new();
+        for table in tables {
+            effects.push(TableEffect {
+                table: table.clone(),
+                rows_before: count_rows(sandbox, table).await?,
+                rows_after: 0,
+            });
+        }
+
+        let executor = QueryExecutor::new(sandbox.clone());
+        let mut rows_affected = 0;
+        let mut error = None;
+        for statement in statements {
+            match executor.execute_statement(statement).await {
+                Ok(rows) => rows_affected += rows,
+                Err(e) => {
+                    error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+
+        for effect in &mut effects {
+            effect.rows_after = count_rows(sandbox, &effect.table).await?;
+        }
+        Ok(SandboxReport {
+            mode: self.mode.to_string(),
+            rows_affected,
+            tables: effects,
+            error,
+        })
+    }
+}
+
+/// Create `table` in the sandbox with up to `rows` of its rows.
+async fn copy_sample(
+    source: &DbConnection,
+    sandbox: &DbConnection,
+    table: &str,
+    rows: usize,
+) -> Result<(), DbError> {
+    let (schema, _) = split_qualified_name(table);
+    let qualified = quoted_table(table);
+    let columns: Vec<(String, String)> = sqlx::query_as(SANDBOX_COLUMNS_SQL)
+        .bind(&qualified)
+        .fetch_all(source.pool())
+        .await
+        .map_err(|e| DbError::query_failed(SANDBOX_COLUMNS_SQL, &e))?;
+    if columns.is_empty() {
+        return Err(DbError::TableNotFound {
+            table: table.to_string(),
+        });
+    }
+
+    let select = format!(
+        "SELECT coalesce(json_agg(s), '[]')::text FROM (SELECT * FROM {} LIMIT {}) s",
+        qualified, rows
+    );
+    let sample: String = sqlx::query_scalar(&select)
+        .fetch_one(source.pool())
+        .await
+        .map_err(|e| DbError::query_failed(&select, &e))?;
+
+    let definition: Vec<String> = columns
+        .iter()
+        .map(|(name, data_type)| format!("{} {}", quote_ident(name), data_type))
+        .collect();
+    let mut create = String::new();
+    // New databases already have `public`
+    if let Some(schema) = schema.as_deref().filter(|schema| *schema != "public") {
+        create.push_str(&format!("CREATE SCHEMA IF NOT EXISTS {};\n", quote_ident(schema)));
+    }
+    create.push_str(&format!("CREATE TABLE {} ({})", qualified, definition.join(", ")));
+    sqlx::raw_sql(&create)
+        .execute(sandbox.pool())
+        .await
+        .map_err(|e| DbError::query_failed(&create, &e))?;
+
+    let insert = format!(
+        "INSERT INTO {0} SELECT * FROM json_populate_recordset(NULL::{0}, $1::json)",
+        qualified
+    );
+    sqlx::query(&insert)
+        .bind(sample)
+        .execute(sandbox.pool())
+        .await
+        .map_err(|e| DbError::query_failed(&insert, &e))?;
+    Ok(())
+}
+
+/// Quote a `schema.table` or `table` name.
+pub(crate) fn quoted_table(table: &str) -> String {
+    match split_qualified_name(table) {
+        (Some(schema), name) => quote_qualified(&schema, &name),
+        (None, name) => quote_ident(&name),
+    }
+}
+
+/// Number of rows in `table`.
+async fn count_rows(db: &DbConnection, table: &str) -> Result<i64, DbError> {
+    let sql = format!("SELECT count(*) FROM {}", quoted_table(table));
+    sqlx::query_scalar(&sql)
+        .fetch_one(db.pool())
+        .await
+        .map_err(|e| DbError::query_failed(&sql, &e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_display() {
+        let report = SandboxReport {
+            mode: SandboxMode::Sample(1000).to_string(),
+            rows_affected: 3,
+            tables: vec![TableEffect {
+                table: "public.orders".to_string(),
+                rows_before: 10,
+                rows_after: 7,
+            }],
+            error: None,
+        };
+        assert_eq!(
+            report.to_string(),
+            "Sandbox run (sample of up to 1000 rows per table): 3 rows affected\n  \
+             public.orders: 10 -> 7 rows"
+        );
+        assert_eq!(
+            SandboxMode::Template("app_template".to_string()).to_string(),
+            "clone of app_template"
+        );
+    }
+}
@@ -3,8 +3,9 @@
 //! This module provides types for representing database schema information,
 //! including tables, columns, and their metadata.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// Table information from schema.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,7 +39,8 @@ pub struct ColumnInfo {
     /// Column name.
     #[serde(default)]
     pub column_name: String,
-    /// Data type.
+    /// Data type; user-defined types such as enums by name, qualified
+    /// when not on the `search_path`.
     #[serde(default)]
     pub data_type: String,
     /// Whether nullable.
@@ -56,6 +58,9 @@ pub struct ColumnInfo {
     /// Numeric scale.
     #[serde(default)]
     pub numeric_scale: Option<i64>,
+    /// Labels of an enum type, in sort order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enum_labels: Vec<String>,
 }
 
 impl Default for ColumnInfo {
@@ -68,6 +73,7 @@ impl Default for ColumnInfo {
             character_maximum_length: None,
             numeric_precision: None,
             numeric_scale: None,
+            enum_labels: Vec::new(),
         }
     }
 }
@@ -85,6 +91,18 @@ pub enum TableType {
     ForeignTable,
 }
 
+/// A user-defined enum type.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnumType {
+    /// Type name as written in SQL, qualified when not on the `search_path`.
+    pub name: String,
+    /// Schema of the type.
+    pub schema: String,
+    /// Labels in sort order.
+    pub labels: Vec<String>,
+}
+
 /// Complete database schema.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -95,6 +113,12 @@ pub struct DatabaseSchema {
     /// Columns by table name.
     #[serde(default)]
     pub columns: HashMap<String, Vec<ColumnInfo>>,
+    /// Row-level security and grants by table name.
+    #[serde(default)]
+    pub security: HashMap<String, TableSecurity>,
+    /// Enum types of the database.
+    #[serde(default)]
+    pub enums: Vec<EnumType>,
 }
 
 impl DatabaseSchema {
@@ -115,4 +139,431 @@ impl DatabaseSchema {
     pub fn get_columns(&self, table_name: &str) -> Option<&Vec<ColumnInfo>> {
         self.columns.get(table_name)
     }
+
+    /// The part of the schema for tables whose name starts with `prefix`.
+    #[must_use]
+    pub fn filtered(&self, prefix: &str) -> Self {
+        let matches = |name: &String| name.starts_with(prefix);
+        Self {
+            tables: self
+                .tables
+                .iter()
+                .filter(|t| matches(&t.table_name))
+                .cloned()
+                .collect(),
+            columns: self
+                .columns
+                .iter()
+                .filter(|(name, _)| matches(name))
+                .map(|(name, columns)| (name.clone(), columns.clone()))
+                .collect(),
+            security: self
+                .security
+                .iter()
+                .filter(|(name, _)| matches(name))
+                .map(|(name, security)| (name.clone(), security.clone()))
+                .collect(),
+            // Only the enums used by the remaining columns
+            enums: self
+                .enums
+                .iter()
+                .filter(|e| {
+                    self.columns
+                        .iter()
+                        .filter(|(name, _)| matches(name))
+                        .flat_map(|(_, columns)| columns)
+                        .any(|c| c.data_type == e.name)
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// A row-level security policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RlsPolicy {
+    /// Policy name.
+    pub name: String,
+    /// Command the policy applies to (`ALL`, `SELECT`, ...).
+    pub command: String,
+    /// Whether the policy is permissive (OR-ed) rather than restrictive.
+    pub permissive: bool,
+    /// Roles the policy applies to.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// USING expression filtering visible rows.
+    #[serde(default)]
+    pub using: Option<String>,
+    /// WITH CHECK expression for written rows.
+    #[serde(default)]
+    pub with_check: Option<String>,
+}
+
+/// Row-level security settings and grants of a table.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableSecurity {
+    /// Table schema.
+    pub table_schema: String,
+    /// Table owner.
+    pub owner: String,
+    /// Whether row-level security is enabled.
+    pub rls_enabled: bool,
+    /// Whether row-level security also applies to the owner.
+    pub rls_forced: bool,
+    /// Whether row-level security filters rows for the connected role.
+    pub rls_applies: bool,
+    /// Policies on the table.
+    #[serde(default)]
+    pub policies: Vec<RlsPolicy>,
+    /// Granted privileges by grantee.
+    #[serde(default)]
+    pub grants: BTreeMap<String, Vec<String>>,
+}
+
+impl TableSecurity {
+    /// Whether no permissive policy allows reads, so that every SELECT by
+    /// a role subject to row-level security returns no rows.
+    #[must_use]
+    pub fn denies_all_reads(&self) -> bool {
+        self.rls_applies
+            && !self
+                .policies
+                .iter()
+                .any(|p| p.permissive && matches!(p.command.as_str(), "ALL" | "SELECT"))
+    }
+}
+
+/// Column count and planner row estimate of a table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableSize {
+    /// Table schema.
+    pub table_schema: String,
+    /// Table name.
+    pub table_name: String,
+    /// Column names in table order.
+    pub columns: Vec<String>,
+    /// `pg_class.reltuples`; negative when the table was never analyzed.
+    pub estimated_rows: i64,
+}
+
+/// Kind of object that depends on a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DependencyKind {
+    /// A view selecting from the table.
+    View,
+    /// A materialized view selecting from the table.
+    MaterializedView,
+    /// A foreign key in another table referencing the table.
+    ForeignKey,
+    /// A function whose body or signature refers to the table.
+    Function,
+    /// A trigger defined on the table.
+    Trigger,
+}
+
+impl DependencyKind {
+    /// Parse the kind label returned by the dependency query.
+    #[must_use]
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "view" => Some(Self::View),
+            "materialized_view" => Some(Self::MaterializedView),
+            "foreign_key" => Some(Self::ForeignKey),
+            "function" => Some(Self::Function),
+            "trigger" => Some(Self::Trigger),
+            _ => None,
+        }
+    }
+}
+
+/// An object that depends on a table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableDependency {
+    /// Kind of dependent object.
+    pub kind: DependencyKind,
+    /// Schema of the dependent object.
+    pub schema: String,
+    /// Name of the dependent object (the referencing table for foreign keys).
+    pub name: String,
+    /// Extra detail: constraint name, trigger function or function arguments.
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+/// A single-column foreign key of a table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeignKeyRef {
+    /// Referencing column.
+    pub column: String,
+    /// Schema of the referenced table.
+    pub referenced_schema: String,
+    /// Referenced table.
+    pub referenced_table: String,
+    /// Referenced column.
+    pub referenced_column: String,
+}
+
+/// A server backend as reported by `pg_stat_activity`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendActivity {
+    /// Backend process ID.
+    pub pid: i32,
+    /// Role the backend is connected as.
+    pub username: Option<String>,
+    /// Backend state (`active`, `idle in transaction`, ...).
+    pub state: Option<String>,
+    /// Current or most recent query.
+    pub query: Option<String>,
+    /// Seconds since the current query started.
+    pub running_secs: Option<f64>,
+}
+
+/// Refresh state of a materialized view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatviewFreshness {
+    /// Schema of the view.
+    pub schema: String,
+    /// View name.
+    pub name: String,
+    /// Whether the view has been populated (`WITH DATA` or refreshed).
+    pub is_populated: bool,
+    /// When the view data was last written, where the server exposes it.
+    pub last_refresh: Option<DateTime<Utc>>,
+}
+
+impl MatviewFreshness {
+    /// The view as `schema.name`.
+    #[must_use]
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema, self.name)
+    }
+
+    /// A note on how current the view data is, for query results.
+    #[must_use]
+    pub fn note(&self) -> String {
+        let name = self.qualified_name();
+        if !self.is_populated {
+            return format!("Materialized view {} has not been populated; refresh it first", name);
+        }
+        match self.last_refresh {
+            Some(at) => format!(
+                "{} is a materialized view; data as of the last refresh at {}",
+                name,
+                at.format("%Y-%m-%d %H:%M:%S UTC")
+            ),
+            None => format!(
+                "{} is a materialized view; data as of its last refresh (time unknown)",
+                name
+            ),
+        }
+    }
+}
+
+/// Privileges held by the connected database role.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RolePrivileges {
+    /// Role name (`current_user`).
+    pub role: String,
+    /// Whether the role is a superuser.
+    pub is_superuser: bool,
+    /// Whether the role can create databases.
+    pub can_create_db: bool,
+    /// Whether the role can create other roles.
+    pub can_create_role: bool,
+    /// Whether the role bypasses row-level security.
+    pub bypass_rls: bool,
+    /// Schemas the role can read from.
+    #[serde(default)]
+    pub readable_schemas: Vec<String>,
+    /// Schemas the role can create objects in.
+    #[serde(default)]
+    pub creatable_schemas: Vec<String>,
+    /// Number of tables the role can INSERT, UPDATE or DELETE.
+    pub writable_tables: i64,
+}
+
+impl RolePrivileges {
+    /// Whether the role can modify data or schema objects.
+    #[must_use]
+    pub fn can_write(&self) -> bool {
+        self.is_superuser || self.writable_tables > 0 || !self.creatable_schemas.is_empty()
+    }
+
+    /// Describe privileges that exceed what the agent needs.
+    ///
+    /// `allow_writes` should reflect whether the configured safety level
+    /// permits mutations at all.
+    #[must_use]
+    pub fn excess_privileges(&self, allow_writes: bool) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if self.is_superuser {
+            warnings.push(format!("Role '{}' is a superuser", self.role));
+        }
+        if self.can_create_role {
+            warnings.push("Role can create other roles".to_string());
+        }
+        if self.can_create_db {
+            warnings.push("Role can create databases".to_string());
+        }
+        if self.bypass_rls {
+            warnings.push("Role bypasses row-level security".to_string());
+        }
+        if !allow_writes && !self.is_superuser {
+            if self.writable_tables > 0 {
+                warnings.push(format!(
+                    "Read-only safety level, but role can write to {} tables",
+                    self.writable_tables
+                ));
+            }
+            if !self.creatable_schemas.is_empty() {
+                warnings.push(format!(
+                    "Read-only safety level, but role can create objects in: {}",
+                    self.creatable_schemas.join(", ")
+                ));
+            }
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denies_all_reads() {
+        let mut security = TableSecurity {
+            rls_enabled: true,
+            rls_applies: true,
+            ..Default::default()
+        };
+        assert!(security.denies_all_reads());
+
+        security.policies.push(RlsPolicy {
+            name: "tenant_isolation".to_string(),
+            command: "ALL".to_string(),
+            permissive: true,
+            roles: vec!["public".to_string()],
+            using: Some("(tenant_id = current_setting('app.tenant_id')::integer)".to_string()),
+            with_check: None,
+        });
+        assert!(!security.denies_all_reads());
+
+        security.rls_applies = false;
+        security.policies.clear();
+        assert!(!security.denies_all_reads());
+    }
+
+    #[test]
+    fn test_filtered() {
+        let table = |name: &str| SchemaTable {
+            table_name: name.to_string(),
+            table_schema: "public".to_string(),
+            table_type: TableType::BaseTable,
+        };
+        let schema = DatabaseSchema {
+            tables: vec![table("orders"), table("order_items"), table("users")],
+            columns: HashMap::from([
+                (
+                    "orders".to_string(),
+                    vec![ColumnInfo {
+                        column_name: "status".to_string(),
+                        data_type: "order_status".to_string(),
+                        enum_labels: vec!["pending".to_string(), "shipped".to_string()],
+                        ..ColumnInfo::default()
+                    }],
+                ),
+                ("users".to_string(), Vec::new()),
+            ]),
+            security: HashMap::new(),
+            enums: ["order_status", "user_role"]
+                .map(|name| EnumType {
+                    name: name.to_string(),
+                    schema: "public".to_string(),
+                    labels: Vec::new(),
+                })
+                .to_vec(),
+        };
+        let filtered = schema.filtered("order");
+        assert_eq!(filtered.tables.len(), 2);
+        assert!(filtered.get_columns("orders").is_some());
+        assert!(filtered.get_columns("users").is_none());
+        assert_eq!(filtered.enums.len(), 1);
+        assert_eq!(filtered.enums[0].name, "order_status");
+    }
+
+    #[test]
+    fn test_read_only_role_has_no_excess() {
+        let privileges = RolePrivileges {
+            role: "reader".to_string(),
+            readable_schemas: vec!["public".to_string()],
+            ..Default::default()
+        };
+        assert!(!privileges.can_write());
+        assert!(privileges.excess_privileges(false).is_empty());
+    }
+
+    #[test]
+    fn test_excess_privileges() {
+        let privileges = RolePrivileges {
+            role: "app".to_string(),
+            writable_tables: 3,
+            ..Default::default()
+        };
+        assert!(privileges.can_write());
+        assert!(privileges.excess_privileges(true).is_empty());
+        assert_eq!(privileges.excess_privileges(false).len(), 1);
+
+        let superuser = RolePrivileges {
+            role: "postgres".to_string(),
+            is_superuser: true,
+            ..Default::default()
+        };
+        assert_eq!(superuser.excess_privileges(true).len(), 1);
+    }
+
+    #[test]
+    fn test_dependency_kind_labels() {
+        assert_eq!(
+            DependencyKind::from_label("materialized_view"),
+            Some(DependencyKind::MaterializedView)
+        );
+        assert_eq!(DependencyKind::from_label("index"), None);
+        assert_eq!(
+            serde_json::to_value(DependencyKind::ForeignKey).unwrap(),
+            "foreign_key"
+        );
+    }
+
+    #[test]
+    fn test_matview_freshness_note() {
+        let mut view = MatviewFreshness {
+            schema: "reporting".to_string(),
+            name: "daily_sales".to_string(),
+            is_populated: true,
+            last_refresh: DateTime::from_timestamp(1_700_000_000, 0),
+        };
+        assert_eq!(
+            view.note(),
+            "reporting.daily_sales is a materialized view; data as of the last refresh at \
+             2023-11-14 22:13:20 UTC"
+        );
+
+        view.last_refresh = None;
+        assert!(view.note().contains("time unknown"));
+        view.is_populated = false;
+        assert!(view.note().contains("has not been populated"));
+    }
 }
@@ -0,0 +1,62 @@
+//! Shared cache of the introspected schema.
+//!
+//! Introspecting a large database takes seconds. Interactive front ends load
+//! the schema in the background at startup with [`SchemaCache::prewarm`], so
+//! the first question finds it ready; a question asked while loading waits
+//! for that load instead of starting another.
+
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::{DatabaseSchema, DbConnection, DbError, QueryExecutor};
+
+/// Schema of a database, loaded once and shared by clones.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaCache {
+    /// The schema, once loaded; locked while loading.
+    schema: Arc<Mutex<Option<Arc<DatabaseSchema>>>>,
+}
+
+impl SchemaCache {
+    /// Create an empty cache.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the schema, introspecting the database on first use.
+    ///
+    /// # Errors
+    /// Returns an error if introspection fails; the next call tries again.
+    pub async fn get(&self, db: &DbConnection) -> Result<Arc<DatabaseSchema>, DbError> {
+        let mut schema = self.schema.lock().await;
+        if let Some(schema) = schema.as_ref() {
+            return Ok(schema.clone());
+        }
+        let started = std::time::Instant::now();
+        let loaded = Arc::new(QueryExecutor::new(db.clone()).get_schema(None).await?);
+        debug!("Loaded schema of {} tables in {:?}", loaded.tables.len(), started.elapsed());
+        *schema = Some(loaded.clone());
+        Ok(loaded)
+    }
+
+    /// Load the schema in the background.
+    pub fn prewarm(&self, db: &DbConnection) -> JoinHandle<Result<Arc<DatabaseSchema>, DbError>> {
+        let (cache, db) = (self.clone(), db.clone());
+        tokio::spawn(async move { cache.get(&db).await })
+    }
+
+    /// The schema if it is loaded, without waiting.
+    #[must_use]
+    pub fn cached(&self) -> Option<Arc<DatabaseSchema>> {
+        self.schema.try_lock().ok().and_then(|schema| schema.clone())
+    }
+
+    /// Drop the cached schema, e.g. after DDL; the next use reloads it.
+    pub async fn invalidate(&self) {
+        *self.schema.lock().await = None;
+    }
+}
@@ -0,0 +1,235 @@
+//! Table snapshots for undoing mutations.
+//!
+//! A [`Snapshot`] keeps the rows of tables as `COPY` text files, taken
+//! before a confirmed mutation so that [`Snapshot::restore`] can put the
+//! tables back as they were. It is a safety net for small tables: restoring
+//! empties each table and copies the saved rows back, which also undoes any
+//! later changes, and fails for tables referenced by foreign keys.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::connection::DbConnection;
+use crate::error::DbError;
+use crate::sandbox::quoted_table;
+
+/// Rows of one table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableSnapshot {
+    /// Qualified table name.
+    pub table: String,
+    /// `COPY` text file holding the rows.
+    pub file: PathBuf,
+    /// Number of rows saved.
+    pub rows: u64,
+}
+
+/// Rows of tables at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// When the snapshot was taken.
+    pub taken_at: DateTime<Utc>,
+    /// Directory holding the files.
+    pub dir: PathBuf,
+    /// Saved tables.
+    pub tables: Vec<TableSnapshot>,
+}
+
+impl Snapshot {
+    /// Save `tables` (`schema.table`) to a new directory under `dir`, in one
+    /// repeatable-read transaction so that they are consistent.
+    ///
+    /// # Errors
+    /// Returns `DbError::Snapshot` if a table has more than `max_rows` rows
+    /// or a file cannot be written, and a query error if a table cannot be
+    /// read.
+    pub async fn take(
+        db: &DbConnection,
+        tables: &[String],
+        dir: &Path,
+        max_rows: u64,
+    ) -> Result<Self, DbError> {
+        let taken_at = Utc::now();
+        let dir = dir.join(format!(
+            "{}-{}",
+            taken_at.format("%Y%m%dT%H%M%S%.3f"),
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).map_err(snapshot_error)?;
+
+        match save_tables(db, tables, &dir, max_rows).await {
+            Ok(tables) => Ok(Self {
+                taken_at,
+                dir,
+                tables,
+            }),
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&dir);
+                Err(e)
+            }
+        }
+    }
+
+    /// Put the saved rows back, replacing the current rows of each table,
+    /// in one transaction; returns the number of rows restored.
+    ///
+    /// # Errors
+    /// Returns `DbError::Snapshot` if a file cannot be read, and a query
+    /// error if a table cannot be emptied or filled, e.g. because another
+    /// table references it.
+    pub async fn restore(&self, db: &DbConnection) -> Result<u64, DbError> {
+        let mut tx = db.pool().begin().await?;
+        let mut restored = 0;
+        for table in &self.tables {
+            let data = std::fs::read(&table.file).map_err(snapshot_error)?;
+            let quoted = quoted_table(&table.table);
+            let truncate = format!("TRUNCATE {}", quoted);
+            sqlx::query(&truncate)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| DbError::query_failed(&truncate, &e))?;
+
+            let copy = format!("COPY {} FROM STDIN", quoted);
+            let mut copy_in = tx
+                .copy_in_raw(&copy)
+                .await
+                .map_err(|e| DbError::query_failed(&copy, &e))?;
+            if let Err(e) = copy_in.send(data).await {
+                // Leave the connection usable for the rollback
+                let _ = copy_in.abort(e.to_string()).await;
+                return Err(DbError::query_failed(&copy, &e));
+            }
+            restored += copy_in
+                .finish()
+                .await
+                .map_err(|e| DbError::query_failed(&copy, &e))?;
+        }
+        tx.commit().await?;
+        Ok(restored)
+    }
+
+    /// A psql script that restores the snapshot like [`Self::restore`].
+    #[must_use]
+    pub fn script(&self) -> String {
+        let mut script = format!("-- Snapshot taken {}\nBEGIN;\n", self.taken_at.to_rfc3339());
+        for table in &self.tables {
+            let quoted = quoted_table(&table.table);
+            script.push_str(&format!(
+                "TRUNCATE {0};\n\\copy {0} FROM '{1}'\n",
+                quoted,
+                table.file.display().to_string().replace('\'', "''")
+            ));
+        }
+        script.push_str("COMMIT;\n");
+        script
+    }
+
+    /// Delete the snapshot files.
+    ///
+    /// # Errors
+    /// Returns `DbError::Snapshot` if the directory exists but cannot be
+    /// removed.
+    pub fn discard(&self) -> Result<(), DbError> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(snapshot_error(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Save each table to a file in `dir`.
+async fn save_tables(
+    db: &DbConnection,
+    tables: &[String],
+    dir: &Path,
+    max_rows: u64,
+) -> Result<Vec<TableSnapshot>, DbError> {
+    let mut tx = db.pool().begin().await?;
+    let isolation = "SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY";
+    sqlx::query(isolation)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DbError::query_failed(isolation, &e))?;
+
+    let mut saved = Vec::new();
+    for (i, table) in tables.iter().enumerate() {
+        let quoted = quoted_table(table);
+        let count = format!("SELECT count(*) FROM {}", quoted);
+        let rows: i64 = sqlx::query_scalar(&count)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| DbError::query_failed(&count, &e))?;
+        let rows = u64::try_from(rows).unwrap_or_default();
+        if rows > max_rows {
+            return Err(DbError::Snapshot {
+                reason: format!("{} has {} rows, more than {}", table, rows, max_rows),
+            });
+        }
+
+        let name: String = table
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '.' || c == '_' { c } else { '-' })
+            .collect();
+        let file = dir.join(format!("{}-{}.copy", i + 1, name));
+        let copy = format!("COPY {} TO STDOUT", quoted);
+        debug!("Saving {} to {}", table, file.display());
+        let mut out = std::fs::File::create(&file).map_err(snapshot_error)?;
+        let mut stream = tx
+            .copy_out_raw(&copy)
+            .await
+            .map_err(|e| DbError::query_failed(&copy, &e))?;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| DbError::query_failed(&copy, &e))?;
+            out.write_all(&chunk).map_err(snapshot_error)?;
+        }
+        drop(stream);
+        saved.push(TableSnapshot {
+            table: table.clone(),
+            file,
+            rows,
+        });
+    }
+    tx.commit().await?;
+
+    Ok(saved)
+}
+
+fn snapshot_error(e: std::io::Error) -> DbError {
+    DbError::Snapshot {
+        reason: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let snapshot = Snapshot {
+            taken_at: DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            dir: PathBuf::from("/tmp/snap"),
+            tables: vec![TableSnapshot {
+                table: "public.orders".to_string(),
+                file: PathBuf::from("/tmp/snap/1-public.orders.copy"),
+                rows: 3,
+            }],
+        };
+        assert_eq!(
+            snapshot.script(),
+            "-- Snapshot taken 2026-01-02T03:04:05+00:00\nBEGIN;\n\
+             TRUNCATE public.orders;\n\
+             \\copy public.orders FROM '/tmp/snap/1-public.orders.copy'\n\
+             COMMIT;\n"
+        );
+    }
+}
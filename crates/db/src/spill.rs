@@ -0,0 +1,208 @@
+//! Result rows kept on disk.
+//!
+//! Rows beyond the memory limit of a result are written to a temporary file
+//! as JSON lines and read back when the whole result is consumed, so that an
+//! accidental huge SELECT cannot exhaust the memory of the process.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::Arc;
+
+use tempfile::NamedTempFile;
+
+use crate::DbError;
+
+/// A result row as returned by the executor.
+pub type JsonRow = serde_json::Map<String, serde_json::Value>;
+
+/// Bookkeeping bytes counted per row and per value, on top of their text.
+const ROW_OVERHEAD: usize = 64;
+const VALUE_OVERHEAD: usize = 32;
+
+/// Rows of a result stored in a temporary file.
+///
+/// The file is deleted when the last clone is dropped.
+#[derive(Debug, Clone)]
+pub struct SpilledRows {
+    /// File of JSON lines, one row per line.
+    file: Arc<NamedTempFile>,
+    /// Number of rows in the file.
+    count: usize,
+    /// Size of the file in bytes.
+    bytes: u64,
+}
+
+impl SpilledRows {
+    /// Number of rows on disk.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether no rows were spilled.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Size of the spill file in bytes.
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Read the rows back in order.
+    ///
+    /// # Errors
+    /// Returns `DbError::Spill` if the file cannot be opened; the iterator
+    /// yields it for rows that cannot be read.
+    pub fn iter(&self) -> Result<impl Iterator<Item = Result<JsonRow, DbError>>, DbError> {
+        let file = File::open(self.file.path()).map_err(spill_error)?;
+        Ok(BufReader::new(file).lines().map(|line| {
+            let line = line.map_err(spill_error)?;
+            serde_json::from_str(&line).map_err(|e| DbError::Spill {
+                reason: e.to_string(),
+            })
+        }))
+    }
+}
+
+/// Rows of a result as they are fetched, in memory up to a byte limit and
+/// on disk beyond it.
+#[derive(Debug)]
+pub(crate) struct RowBuffer {
+    /// Bytes of rows kept in memory before spilling; 0 means no limit.
+    limit: usize,
+    /// Estimated bytes of the rows in memory.
+    used: usize,
+    /// Rows in memory.
+    rows: Vec<JsonRow>,
+    /// Spill file, once the limit is reached.
+    spill: Option<(BufWriter<NamedTempFile>, usize)>,
+}
+
+impl RowBuffer {
+    /// Create a buffer keeping up to `limit` bytes of rows in memory.
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: 0,
+            rows: Vec::new(),
+            spill: None,
+        }
+    }
+
+    /// Number of rows pushed so far.
+    pub(crate) fn len(&self) -> usize {
+        self.rows.len() + self.spill.as_ref().map_or(0, |(_, count)| *count)
+    }
+
+    /// Add the next row.
+    ///
+    /// # Errors
+    /// Returns `DbError::Spill` if the row cannot be written to disk.
+    pub(crate) fn push(&mut self, row: JsonRow) -> Result<(), DbError> {
+        if self.spill.is_none() {
+            let size = row_size(&row);
+            if self.limit == 0 || self.used + size <= self.limit {
+                self.used += size;
+                self.rows.push(row);
+                return Ok(());
+            }
+            tracing::debug!("Result exceeds {} bytes; spilling rows to disk", self.limit);
+            let file = NamedTempFile::with_prefix("pg-agent-rows-").map_err(spill_error)?;
+            self.spill = Some((BufWriter::new(file), 0));
+        }
+        if let Some((writer, count)) = &mut self.spill {
+            serde_json::to_writer(&mut *writer, &row).map_err(|e| DbError::Spill {
+                reason: e.to_string(),
+            })?;
+            writer.write_all(b"\n").map_err(spill_error)?;
+            *count += 1;
+        }
+        Ok(())
+    }
+
+    /// The rows in memory and those on disk, if any.
+    ///
+    /// # Errors
+    /// Returns `DbError::Spill` if the spill file cannot be flushed.
+    pub(crate) fn finish(self) -> Result<(Vec<JsonRow>, Option<SpilledRows>), DbError> {
+        let spilled = match self.spill {
+            Some((writer, count)) => {
+                let file = writer.into_inner().map_err(|e| spill_error(e.into_error()))?;
+                let bytes = file.as_file().metadata().map_err(spill_error)?.len();
+                Some(SpilledRows {
+                    file: Arc::new(file),
+                    count,
+                    bytes,
+                })
+            }
+            None => None,
+        };
+        Ok((self.rows, spilled))
+    }
+}
+
+/// Estimated memory taken by `row`.
+fn row_size(row: &JsonRow) -> usize {
+    fn value_size(value: &serde_json::Value) -> usize {
+        VALUE_OVERHEAD
+            + match value {
+                serde_json::Value::String(s) => s.len(),
+                serde_json::Value::Array(items) => items.iter().map(value_size).sum(),
+                serde_json::Value::Object(map) => {
+                    map.iter().map(|(k, v)| k.len() + value_size(v)).sum()
+                }
+                _ => 0,
+            }
+    }
+    ROW_OVERHEAD + row.iter().map(|(k, v)| k.len() + value_size(v)).sum::<usize>()
+}
+
+fn spill_error(e: std::io::Error) -> DbError {
+    DbError::Spill {
+        reason: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i64) -> JsonRow {
+        let mut row = JsonRow::new();
+        row.insert("id".to_string(), serde_json::json!(id));
+        row.insert("name".to_string(), serde_json::json!(format!("row {}", id)));
+        row
+    }
+
+    #[test]
+    fn test_spills_past_limit() {
+        let mut buffer = RowBuffer::new(row_size(&row(0)) * 3);
+        for id in 0..10 {
+            buffer.push(row(id)).unwrap();
+        }
+        assert_eq!(buffer.len(), 10);
+
+        let (rows, spilled) = buffer.finish().unwrap();
+        let spilled = spilled.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(spilled.len(), 7);
+        assert!(spilled.bytes() > 0);
+        let read: Vec<JsonRow> = spilled.iter().unwrap().map(Result::unwrap).collect();
+        assert_eq!(read.first(), Some(&row(3)));
+        assert_eq!(read.last(), Some(&row(9)));
+    }
+
+    #[test]
+    fn test_no_limit_keeps_rows_in_memory() {
+        let mut buffer = RowBuffer::new(0);
+        for id in 0..100 {
+            buffer.push(row(id)).unwrap();
+        }
+        let (rows, spilled) = buffer.finish().unwrap();
+        assert_eq!(rows.len(), 100);
+        assert!(spilled.is_none());
+    }
+}
@@ -0,0 +1,139 @@
+//! SQLSTATE knowledge.
+//!
+//! Maps common PostgreSQL error codes to their condition names and to
+//! suggestions that help a user, or the agent, fix the failing query.
+
+/// A known SQLSTATE and how to respond to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SqlState {
+    /// Five-character SQLSTATE code.
+    pub code: &'static str,
+    /// PostgreSQL condition name.
+    pub name: &'static str,
+    /// What to do about the error.
+    pub suggestion: &'static str,
+    /// Whether running the same statement again may succeed.
+    pub retryable: bool,
+}
+
+/// Known SQLSTATEs.
+const SQL_STATES: &[SqlState] = &[
+    SqlState {
+        code: "42P01",
+        name: "undefined_table",
+        suggestion: "The table does not exist. Check the schema for the correct name, \
+                     schema-qualify it, or check the search_path.",
+        retryable: false,
+    },
+    SqlState {
+        code: "42703",
+        name: "undefined_column",
+        suggestion: "The column does not exist. Describe the table to find the correct \
+                     column name, and check which table alias it belongs to.",
+        retryable: false,
+    },
+    SqlState {
+        code: "42883",
+        name: "undefined_function",
+        suggestion: "No function matches the name and argument types. Check the \
+                     spelling, or add explicit casts to the arguments.",
+        retryable: false,
+    },
+    SqlState {
+        code: "42702",
+        name: "ambiguous_column",
+        suggestion: "The column name exists in more than one joined table. Qualify it \
+                     with a table alias.",
+        retryable: false,
+    },
+    SqlState {
+        code: "42803",
+        name: "grouping_error",
+        suggestion: "Every selected column must be aggregated or listed in GROUP BY.",
+        retryable: false,
+    },
+    SqlState {
+        code: "42601",
+        name: "syntax_error",
+        suggestion: "The SQL is malformed near the marked position. Check keywords, \
+                     commas, parentheses and quoting.",
+        retryable: false,
+    },
+    SqlState {
+        code: "42804",
+        name: "datatype_mismatch",
+        suggestion: "An expression has the wrong type. Add an explicit cast or compare \
+                     values of matching types.",
+        retryable: false,
+    },
+    SqlState {
+        code: "22P02",
+        name: "invalid_text_representation",
+        suggestion: "A literal cannot be converted to the column type. Check the value \
+                     format, e.g. dates as 'YYYY-MM-DD' and numbers without quotes.",
+        retryable: false,
+    },
+    SqlState {
+        code: "22012",
+        name: "division_by_zero",
+        suggestion: "Guard the divisor with NULLIF(divisor, 0).",
+        retryable: false,
+    },
+    SqlState {
+        code: "42501",
+        name: "insufficient_privilege",
+        suggestion: "The database role lacks permission for this object. Query other \
+                     tables or ask an administrator to grant access.",
+        retryable: false,
+    },
+    SqlState {
+        code: "25006",
+        name: "read_only_sql_transaction",
+        suggestion: "The connection is read-only; only SELECT queries can run.",
+        retryable: false,
+    },
+    SqlState {
+        code: "57014",
+        name: "query_canceled",
+        suggestion: "The query was cancelled, usually by the statement timeout. Add \
+                     filters or a LIMIT, or aggregate to scan fewer rows.",
+        retryable: false,
+    },
+    SqlState {
+        code: "40P01",
+        name: "deadlock_detected",
+        suggestion: "The statement was chosen as a deadlock victim. Run it again.",
+        retryable: true,
+    },
+    SqlState {
+        code: "40001",
+        name: "serialization_failure",
+        suggestion: "A concurrent transaction conflicted with this one. Run it again.",
+        retryable: true,
+    },
+    SqlState {
+        code: "53300",
+        name: "too_many_connections",
+        suggestion: "The server has no free connections. Wait and try again.",
+        retryable: true,
+    },
+];
+
+/// Look up a SQLSTATE code.
+#[must_use]
+pub fn lookup(code: &str) -> Option<&'static SqlState> {
+    SQL_STATES.iter().find(|state| state.code == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(lookup("42P01").map(|s| s.name), Some("undefined_table"));
+        assert!(lookup("40001").is_some_and(|s| s.retryable));
+        assert!(!lookup("42501").unwrap().retryable);
+        assert!(lookup("XX000").is_none());
+    }
+}
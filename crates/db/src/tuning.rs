@@ -0,0 +1,303 @@
+//! Material for query tuning: plans, table statistics and indexes.
+//!
+//! Plans come from `EXPLAIN` without `ANALYZE`, so the statement itself
+//! never runs. [`explain_with_indexes`] builds proposed indexes in a
+//! transaction that is always rolled back; building them still locks the
+//! table against writes and takes as long as a real build, so callers
+//! should ask first.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::connection::DbConnection;
+use crate::error::DbError;
+
+/// Statistics and indexes of a table.
+const TABLE_STATS_SQL: &str = r#"
+    SELECT
+        c.reltuples::bigint,
+        s.n_dead_tup,
+        s.seq_scan,
+        s.idx_scan,
+        greatest(s.last_analyze, s.last_autoanalyze),
+        coalesce(
+            (SELECT array_agg(pg_get_indexdef(i.indexrelid) ORDER BY i.indexrelid)
+             FROM pg_catalog.pg_index i WHERE i.indrelid = c.oid),
+            '{}'
+        )
+    FROM pg_catalog.pg_class c
+    LEFT JOIN pg_catalog.pg_stat_user_tables s ON s.relid = c.oid
+    WHERE c.oid = to_regclass($1)
+"#;
+
+/// Planner statistics of a table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableStats {
+    /// Qualified table name.
+    pub table: String,
+    /// Row estimate the planner uses; negative if never analyzed.
+    pub estimated_rows: i64,
+    /// Dead rows waiting for vacuum.
+    pub dead_rows: Option<i64>,
+    /// Sequential scans since statistics were reset.
+    pub seq_scans: Option<i64>,
+    /// Index scans since statistics were reset.
+    pub index_scans: Option<i64>,
+    /// Last manual or automatic `ANALYZE`.
+    pub last_analyzed: Option<DateTime<Utc>>,
+    /// `CREATE INDEX` statements of the existing indexes.
+    pub indexes: Vec<String>,
+}
+
+impl TableStats {
+    /// A one-line summary, e.g. `~1000 rows, 3 seq scans, 0 index scans`.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut parts = vec![if self.estimated_rows < 0 {
+            "never analyzed".to_string()
+        } else {
+            format!("~{} rows", self.estimated_rows)
+        }];
+        if let Some(dead) = self.dead_rows.filter(|dead| *dead > 0) {
+            parts.push(format!("{} dead", dead));
+        }
+        if let Some(scans) = self.seq_scans {
+            parts.push(format!("{} seq scans", scans));
+        }
+        if let Some(scans) = self.index_scans {
+            parts.push(format!("{} index scans", scans));
+        }
+        if let Some(analyzed) = self.last_analyzed {
+            parts.push(format!("analyzed {}", analyzed.format("%Y-%m-%d %H:%M")));
+        }
+        parts.join(", ")
+    }
+}
+
+/// An `EXPLAIN (FORMAT JSON)` plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct QueryPlan(pub Value);
+
+impl QueryPlan {
+    /// The root plan node.
+    fn root(&self) -> &Value {
+        &self.0[0]["Plan"]
+    }
+
+    /// Estimated total cost of the statement.
+    #[must_use]
+    pub fn total_cost(&self) -> f64 {
+        self.root()["Total Cost"].as_f64().unwrap_or_default()
+    }
+
+    /// Estimated rows returned.
+    #[must_use]
+    pub fn rows(&self) -> f64 {
+        self.root()["Plan Rows"].as_f64().unwrap_or_default()
+    }
+
+    /// The plan tree as indented lines, like text `EXPLAIN` output.
+    #[must_use]
+    pub fn outline(&self) -> String {
+        let mut lines = Vec::new();
+        outline_node(self.root(), 0, &mut lines);
+        lines.join("\n")
+    }
+}
+
+/// Append a line for `node` and its children.
+fn outline_node(node: &Value, depth: usize, lines: &mut Vec<String>) {
+    let Some(node_type) = node["Node Type"].as_str() else {
+        return;
+    };
+    let mut line = format!("{}{}", "  ".repeat(depth), node_type);
+    if let Some(index) = node["Index Name"].as_str() {
+        line.push_str(&format!(" using {}", index));
+    }
+    if let Some(relation) = node["Relation Name"].as_str() {
+        match node["Schema"].as_str() {
+            Some(schema) => line.push_str(&format!(" on {}.{}", schema, relation)),
+            None => line.push_str(&format!(" on {}", relation)),
+        }
+    }
+    line.push_str(&format!(
+        "  (cost={:.2}..{:.2} rows={})",
+        node["Startup Cost"].as_f64().unwrap_or_default(),
+        node["Total Cost"].as_f64().unwrap_or_default(),
+        node["Plan Rows"].as_f64().unwrap_or_default()
+    ));
+    for key in ["Index Cond", "Filter", "Hash Cond", "Join Filter"] {
+        if let Some(condition) = node[key].as_str() {
+            line.push_str(&format!("\n{}  {}: {}", "  ".repeat(depth), key, condition));
+        }
+    }
+    lines.push(line);
+    for child in node["Plans"].as_array().into_iter().flatten() {
+        outline_node(child, depth + 1, lines);
+    }
+}
+
+/// Plan `sql` without running it.
+///
+/// # Errors
+/// Returns `DbError::QueryFailed` if the statement is invalid.
+pub async fn explain(db: &DbConnection, sql: &str) -> Result<QueryPlan, DbError> {
+    let explain_sql = explain_statement(sql);
+    let plan: Value = sqlx::query_scalar(&explain_sql)
+        .fetch_one(db.pool())
+        .await
+        .map_err(|e| DbError::query_failed(&explain_sql, &e))?;
+    Ok(QueryPlan(plan))
+}
+
+/// Plan `sql` as if `indexes` existed, building them in a transaction that
+/// is rolled back.
+///
+/// # Errors
+/// Returns `DbError::NotAnIndex` if a statement is not a plain
+/// `CREATE INDEX`, and `DbError::QueryFailed` if an index cannot be built
+/// or the statement is invalid.
+pub async fn explain_with_indexes(
+    db: &DbConnection,
+    indexes: &[String],
+    sql: &str,
+) -> Result<QueryPlan, DbError> {
+    let statements = indexes
+        .iter()
+        .map(|ddl| trial_index(ddl).ok_or_else(|| DbError::NotAnIndex { sql: ddl.clone() }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut tx = db.pool().begin().await?;
+    for statement in &statements {
+        sqlx::query(statement)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| DbError::query_failed(statement, &e))?;
+    }
+    let explain_sql = explain_statement(sql);
+    let plan: Value = sqlx::query_scalar(&explain_sql)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| DbError::query_failed(&explain_sql, &e))?;
+    tx.rollback().await?;
+    Ok(QueryPlan(plan))
+}
+
+/// Statistics and indexes of `table` (`schema.table`); `None` if there is
+/// no such table.
+///
+/// # Errors
+/// Returns `DbError::QueryFailed` if the catalog query fails.
+pub async fn table_stats(db: &DbConnection, table: &str) -> Result<Option<TableStats>, DbError> {
+    type Row = (
+        i64,
+        Option<i64>,
+        Option<i64>,
+        Option<i64>,
+        Option<DateTime<Utc>>,
+        Vec<String>,
+    );
+    let row: Option<Row> = sqlx::query_as(TABLE_STATS_SQL)
+        .bind(table)
+        .fetch_optional(db.pool())
+        .await
+        .map_err(|e| DbError::query_failed(TABLE_STATS_SQL, &e))?;
+
+    Ok(row.map(
+        |(estimated_rows, dead_rows, seq_scans, index_scans, last_analyzed, indexes)| TableStats {
+            table: table.to_string(),
+            estimated_rows,
+            dead_rows,
+            seq_scans,
+            index_scans,
+            last_analyzed,
+            indexes,
+        },
+    ))
+}
+
+/// `EXPLAIN (FORMAT JSON)` of a statement.
+fn explain_statement(sql: &str) -> String {
+    format!("EXPLAIN (FORMAT JSON) {}", sql.trim().trim_end_matches(';'))
+}
+
+/// `ddl` as a single `CREATE INDEX` that can run in a transaction, i.e.
+/// without `CONCURRENTLY`; `None` for any other statement.
+#[must_use]
+pub fn trial_index(ddl: &str) -> Option<String> {
+    let ddl = ddl.trim().trim_end_matches(';').trim();
+    let words: Vec<&str> = ddl.split_whitespace().collect();
+    let create_index = match words.as_slice() {
+        [create, index, ..] => {
+            create.eq_ignore_ascii_case("create") && index.eq_ignore_ascii_case("index")
+        }
+        _ => false,
+    };
+    let create_unique = match words.as_slice() {
+        [create, unique, index, ..] => {
+            create.eq_ignore_ascii_case("create")
+                && unique.eq_ignore_ascii_case("unique")
+                && index.eq_ignore_ascii_case("index")
+        }
+        _ => false,
+    };
+    if !(create_index || create_unique) || ddl.contains(';') {
+        return None;
+    }
+    Some(
+        words
+            .into_iter()
+            .filter(|word| !word.eq_ignore_ascii_case("concurrently"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline() {
+        let plan = QueryPlan(serde_json::json!([{
+            "Plan": {
+                "Node Type": "Aggregate",
+                "Startup Cost": 20.0,
+                "Total Cost": 20.01,
+                "Plan Rows": 1,
+                "Plans": [{
+                    "Node Type": "Seq Scan",
+                    "Relation Name": "orders",
+                    "Schema": "public",
+                    "Startup Cost": 0.0,
+                    "Total Cost": 19.5,
+                    "Plan Rows": 200,
+                    "Filter": "(status = 'open'::text)"
+                }]
+            }
+        }]));
+        assert_eq!(plan.total_cost(), 20.01);
+        assert_eq!(plan.rows(), 1.0);
+        assert_eq!(
+            plan.outline(),
+            "Aggregate  (cost=20.00..20.01 rows=1)\n\
+             \x20 Seq Scan on public.orders  (cost=0.00..19.50 rows=200)\n\
+             \x20   Filter: (status = 'open'::text)"
+        );
+    }
+
+    #[test]
+    fn test_trial_index() {
+        let ddl = "CREATE INDEX CONCURRENTLY orders_status_idx ON orders (status);";
+        assert_eq!(
+            trial_index(ddl).as_deref(),
+            Some("CREATE INDEX orders_status_idx ON orders (status)")
+        );
+        assert!(trial_index("create unique index on users (lower(email))").is_some());
+        assert_eq!(trial_index("DROP INDEX orders_status_idx"), None);
+        assert_eq!(trial_index("CREATE INDEX i ON t (a); DROP TABLE t"), None);
+    }
+}
@@ -0,0 +1,438 @@
+//! Conversion of query result values to JSON.
+//!
+//! Values are decoded by column type. `timestamptz` values are rendered in
+//! the zone chosen by [`TimeZoneMode`], so every output format shows the
+//! same instant the same way; types without a conversion are shown as
+//! `<TYPE>`; binary and geometric types are rendered by
+//! [`crate::rendering`]. `numeric` values are kept as exact decimal strings, and
+//! [`JsonRows`] writes them out as strings or as number literals according
+//! to [`NumericOutput`].
+
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use chrono_tz::Tz;
+use serde::ser::{Error as _, SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+use serde_json::Value;
+use sqlx::postgres::{PgRow, PgValueFormat};
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+
+use crate::error::DbError;
+use crate::executor::QueryResult;
+use crate::rendering::TypeRendering;
+
+/// Zone in which `timestamptz` values are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeZoneMode {
+    /// Coordinated Universal Time.
+    #[default]
+    Utc,
+    /// The `TimeZone` setting of the database session.
+    Session,
+    /// A named IANA zone, e.g. `Europe/Berlin`.
+    Named(Tz),
+}
+
+impl FromStr for TimeZoneMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "utc" => Ok(Self::Utc),
+            "session" => Ok(Self::Session),
+            _ => s.trim().parse::<Tz>().map(Self::Named).map_err(|_| {
+                format!("Unknown time zone '{}': use utc, session or an IANA name", s)
+            }),
+        }
+    }
+}
+
+impl fmt::Display for TimeZoneMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utc => write!(f, "utc"),
+            Self::Session => write!(f, "session"),
+            Self::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+impl Serialize for TimeZoneMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeZoneMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// How exact numbers (`numeric` and `bigint`) are written in JSON output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumericOutput {
+    /// As number literals with every digit, e.g. `12345678901234567890.01`.
+    #[default]
+    Number,
+    /// As strings, for consumers that parse numbers as doubles.
+    String,
+}
+
+impl FromStr for NumericOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "number" => Ok(Self::Number),
+            "string" => Ok(Self::String),
+            other => Err(format!("Unknown numeric output '{}' (expected number or string)", other)),
+        }
+    }
+}
+
+impl fmt::Display for NumericOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number => write!(f, "number"),
+            Self::String => write!(f, "string"),
+        }
+    }
+}
+
+impl Serialize for NumericOutput {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for NumericOutput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Rows of a result serialized with exact numbers written as chosen by
+/// [`NumericOutput`].
+///
+/// Number literals are written verbatim, so serialize this with
+/// `serde_json::to_string` or `to_writer`: `to_value` would parse them back
+/// into doubles.
+#[derive(Debug, Clone, Copy)]
+pub struct JsonRows<'a> {
+    result: &'a QueryResult,
+    numeric: NumericOutput,
+}
+
+impl<'a> JsonRows<'a> {
+    /// Serialize the rows of `result`.
+    #[must_use]
+    pub fn new(result: &'a QueryResult, numeric: NumericOutput) -> Self {
+        Self { result, numeric }
+    }
+
+    /// Serializers of the individual rows, e.g. for NDJSON, including rows
+    /// spilled to disk.
+    pub fn rows(self) -> impl Iterator<Item = Result<JsonRow<'a>, DbError>> {
+        self.result.all_rows().map(move |row| row.map(|row| JsonRow { rows: self, row }))
+    }
+}
+
+impl Serialize for JsonRows<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for row in self.rows() {
+            seq.serialize_element(&row.map_err(S::Error::custom)?)?;
+        }
+        seq.end()
+    }
+}
+
+/// A result serialized like [`QueryResult`], with its rows as [`JsonRows`].
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonResult<'a> {
+    columns: &'a [String],
+    rows: JsonRows<'a>,
+    row_count: usize,
+    execution_time_ms: Option<u64>,
+    truncated: bool,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    column_types: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_zone: Option<&'a str>,
+}
+
+impl<'a> JsonResult<'a> {
+    /// Serialize `result`.
+    #[must_use]
+    pub fn new(result: &'a QueryResult, numeric: NumericOutput) -> Self {
+        Self {
+            columns: &result.columns,
+            rows: JsonRows::new(result, numeric),
+            row_count: result.row_count,
+            execution_time_ms: result.execution_time_ms,
+            truncated: result.truncated,
+            column_types: &result.column_types,
+            time_zone: result.time_zone.as_deref(),
+        }
+    }
+}
+
+/// One row of [`JsonRows`].
+#[derive(Debug, Clone)]
+pub struct JsonRow<'a> {
+    rows: JsonRows<'a>,
+    row: Cow<'a, serde_json::Map<String, Value>>,
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let result = self.rows.result;
+        let mut map = serializer.serialize_map(Some(self.row.len()))?;
+        for (column, value) in self.row.iter() {
+            let exact = result
+                .columns
+                .iter()
+                .position(|c| c == column)
+                .is_some_and(|i| result.is_exact_numeric(i));
+            match (self.rows.numeric, value) {
+                // NaN and infinities are not JSON numbers and stay strings
+                (NumericOutput::Number, Value::String(s)) if exact => {
+                    match RawValue::from_string(s.clone()) {
+                        Ok(raw) => map.serialize_entry(column, &raw)?,
+                        Err(_) => map.serialize_entry(column, value)?,
+                    }
+                }
+                (NumericOutput::String, Value::Number(n)) if exact => {
+                    map.serialize_entry(column, &n.to_string())?;
+                }
+                _ => map.serialize_entry(column, value)?,
+            }
+        }
+        map.end()
+    }
+}
+
+/// Convert a row to a JSON object, rendering `timestamptz` values in `zone`
+/// and other types as `types` chooses.
+pub(crate) fn row_to_json(
+    row: &PgRow,
+    zone: Tz,
+    types: &TypeRendering,
+) -> serde_json::Map<String, Value> {
+    row.columns()
+        .iter()
+        .map(|col| (col.name().to_string(), column_to_json(row, col.ordinal(), zone, types)))
+        .collect()
+}
+
+/// Type names of the columns of a row.
+pub(crate) fn column_types(row: &PgRow) -> Vec<String> {
+    row.columns()
+        .iter()
+        .map(|col| col.type_info().name().to_string())
+        .collect()
+}
+
+/// Decode one column of a row by its type.
+fn column_to_json(row: &PgRow, index: usize, zone: Tz, types: &TypeRendering) -> Value {
+    let Ok(raw) = row.try_get_raw(index) else {
+        return Value::Null;
+    };
+    if raw.is_null() {
+        return Value::Null;
+    }
+    let type_name = row.columns()[index].type_info().name().to_string();
+    let placeholder = || Value::String(format!("<{}>", type_name));
+    // Extension types such as citext and geometry have lower-case names
+    let value = match type_name.to_ascii_uppercase().as_str() {
+        "BOOL" => row.try_get::<bool, _>(index).map(Value::from),
+        "INT2" => row.try_get::<i16, _>(index).map(Value::from),
+        "INT4" => row.try_get::<i32, _>(index).map(Value::from),
+        "INT8" => row.try_get::<i64, _>(index).map(Value::from),
+        "FLOAT4" => row.try_get::<f32, _>(index).map(|v| float_to_json(f64::from(v))),
+        "FLOAT8" => row.try_get::<f64, _>(index).map(float_to_json),
+        "TEXT" | "VARCHAR" | "BPCHAR" | "NAME" | "CHAR" | "CITEXT" | "UNKNOWN" => {
+            row.try_get::<String, _>(index).map(Value::from)
+        }
+        "JSON" | "JSONB" => row.try_get::<Value, _>(index),
+        "NUMERIC" => {
+            let text = match raw.format() {
+                PgValueFormat::Binary => raw.as_bytes().ok().and_then(numeric_text),
+                PgValueFormat::Text => raw.as_str().ok().map(str::to_string),
+            };
+            return text.map_or_else(placeholder, Value::String);
+        }
+        "TIMESTAMPTZ" => row
+            .try_get::<DateTime<Utc>, _>(index)
+            .map(|v| Value::from(render_timestamptz(v, zone))),
+        "TIMESTAMP" => row
+            .try_get::<NaiveDateTime, _>(index)
+            .map(|v| Value::from(v.format("%Y-%m-%dT%H:%M:%S%.f").to_string())),
+        "DATE" => row.try_get::<NaiveDate, _>(index).map(|v| Value::from(v.to_string())),
+        "TIME" => row.try_get::<NaiveTime, _>(index).map(|v| Value::from(v.to_string())),
+        other => {
+            let value = match raw.format() {
+                PgValueFormat::Binary => {
+                    raw.as_bytes().ok().and_then(|bytes| types.render(other, bytes))
+                }
+                PgValueFormat::Text => raw.as_str().ok().map(Value::from),
+            };
+            return value.unwrap_or_else(placeholder);
+        }
+    };
+    value.unwrap_or_else(|_| placeholder())
+}
+
+/// Render an instant in `zone` as RFC 3339 with the zone's offset.
+#[must_use]
+pub fn render_timestamptz(value: DateTime<Utc>, zone: Tz) -> String {
+    value
+        .with_timezone(&zone)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, zone == Tz::UTC)
+}
+
+/// Exact text of a `numeric` in PostgreSQL's binary format: a header of
+/// digit count, weight, sign and display scale, then base-10000 digits.
+fn numeric_text(bytes: &[u8]) -> Option<String> {
+    let word = |i: usize| bytes.get(2 * i..2 * i + 2).map(|b| [b[0], b[1]]);
+    let ndigits = usize::from(u16::from_be_bytes(word(0)?));
+    let weight = i32::from(i16::from_be_bytes(word(1)?));
+    let sign = u16::from_be_bytes(word(2)?);
+    let scale = usize::from(u16::from_be_bytes(word(3)?));
+    match sign {
+        0x0000 | 0x4000 => {}
+        0xC000 => return Some("NaN".to_string()),
+        0xD000 => return Some("Infinity".to_string()),
+        0xF000 => return Some("-Infinity".to_string()),
+        _ => return None,
+    }
+    let digits = (0..ndigits)
+        .map(|i| word(4 + i).map(u16::from_be_bytes))
+        .collect::<Option<Vec<u16>>>()?;
+    let digit = |i: i32| {
+        usize::try_from(i)
+            .ok()
+            .and_then(|i| digits.get(i))
+            .copied()
+            .unwrap_or(0)
+    };
+
+    let mut out = String::new();
+    if sign == 0x4000 {
+        out.push('-');
+    }
+    if weight < 0 {
+        out.push('0');
+    } else {
+        let _ = write!(out, "{}", digit(0));
+        for i in 1..=weight {
+            let _ = write!(out, "{:04}", digit(i));
+        }
+    }
+    if scale > 0 {
+        let mut fraction = String::new();
+        let mut i = weight + 1;
+        while fraction.len() < scale {
+            let _ = write!(fraction, "{:04}", digit(i));
+            i += 1;
+        }
+        fraction.truncate(scale);
+        out.push('.');
+        out.push_str(&fraction);
+    }
+    Some(out)
+}
+
+/// JSON has no NaN or infinities; they are kept as strings.
+fn float_to_json(value: f64) -> Value {
+    serde_json::Number::from_f64(value)
+        .map_or_else(|| Value::String(value.to_string()), Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_zone_mode_parse() {
+        assert_eq!("UTC".parse::<TimeZoneMode>(), Ok(TimeZoneMode::Utc));
+        assert_eq!("session".parse::<TimeZoneMode>(), Ok(TimeZoneMode::Session));
+        let berlin: TimeZoneMode = "Europe/Berlin".parse().unwrap();
+        assert_eq!(berlin, TimeZoneMode::Named(chrono_tz::Europe::Berlin));
+        assert_eq!(berlin.to_string(), "Europe/Berlin");
+        assert!("Mars/Olympus".parse::<TimeZoneMode>().is_err());
+    }
+
+    /// Binary `numeric` from its header and base-10000 digits.
+    fn numeric(weight: i16, sign: u16, scale: u16, digits: &[u16]) -> Vec<u8> {
+        let ndigits = u16::try_from(digits.len()).unwrap();
+        [ndigits, weight.cast_unsigned(), sign, scale]
+            .iter()
+            .chain(digits)
+            .flat_map(|w| w.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_numeric_text() {
+        let big = numeric(4, 0, 2, &[1234, 5678, 9012, 3456, 7890, 100]);
+        assert_eq!(numeric_text(&big).unwrap(), "12345678901234567890.01");
+        let small = numeric(-2, 0x4000, 8, &[1]);
+        assert_eq!(numeric_text(&small).unwrap(), "-0.00000001");
+        assert_eq!(numeric_text(&numeric(0, 0, 0, &[])).unwrap(), "0");
+        assert_eq!(numeric_text(&numeric(1, 0, 0, &[1])).unwrap(), "10000");
+        assert_eq!(numeric_text(&numeric(0, 0xC000, 0, &[])).unwrap(), "NaN");
+        assert!(numeric_text(&[0, 1]).is_none());
+    }
+
+    #[test]
+    fn test_json_rows() {
+        let result = QueryResult {
+            columns: vec!["id".into(), "amount".into(), "note".into()],
+            column_types: vec!["INT8".into(), "NUMERIC".into(), "TEXT".into()],
+            rows: vec![serde_json::json!({
+                "id": 9_007_199_254_740_993_i64,
+                "amount": "12345678901234567890.01",
+                "note": "1.5",
+            })
+            .as_object()
+            .cloned()
+            .unwrap()],
+            ..QueryResult::default()
+        };
+        let number = serde_json::to_string(&JsonRows::new(&result, NumericOutput::Number));
+        assert_eq!(
+            number.unwrap(),
+            r#"[{"amount":12345678901234567890.01,"id":9007199254740993,"note":"1.5"}]"#
+        );
+        let string = serde_json::to_string(&JsonRows::new(&result, NumericOutput::String));
+        assert_eq!(
+            string.unwrap(),
+            r#"[{"amount":"12345678901234567890.01","id":"9007199254740993","note":"1.5"}]"#
+        );
+    }
+
+    #[test]
+    fn test_render_timestamptz() {
+        let instant = DateTime::parse_from_rfc3339("2024-07-01T12:30:00Z").unwrap().to_utc();
+        assert_eq!(render_timestamptz(instant, Tz::UTC), "2024-07-01T12:30:00Z");
+        assert_eq!(
+            render_timestamptz(instant, chrono_tz::Europe::Berlin),
+            "2024-07-01T14:30:00+02:00"
+        );
+        assert_eq!(
+            render_timestamptz(instant, chrono_tz::America::New_York),
+            "2024-07-01T08:30:00-04:00"
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Anthropic Claude provider using the Messages API.
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::client::{parse_structured, LlmClient};
+use super::conversion::{
+    anthropic_stream_deltas, anthropic_text, context_to_messages, from_anthropic_response,
+    to_anthropic_messages, to_anthropic_tools, AnthropicContent, AnthropicRequest,
+    AnthropicResponse, AnthropicTool,
+};
+use super::error::LlmError;
+use super::models::capabilities;
+use super::provider::{ApiSpec, KeyAuth, ModelListing, ProviderBase, ProviderConfig, ProviderInfo};
+use super::prompt::{structured_prompt, PromptMessage, SystemPrompt};
+use super::stream::{
+    decision_stream, lines, sse_data, token_stream, DecisionStream, EventStream, TokenStream,
+};
+
+/// API version sent with every request.
+const API_VERSION: &str = "2023-06-01";
+
+/// Anthropic API details.
+const API: ApiSpec = ApiSpec {
+    default_base_url: "https://api.anthropic.com/v1/",
+    auth: KeyAuth::Header("x-api-key"),
+    headers: &[("anthropic-version", API_VERSION)],
+    models: ModelListing { path: "models", list: "data", id: "id", prefix: "" },
+};
+
+/// Tool the model is made to call for structured output.
+const STRUCTURED_TOOL: &str = "respond";
+
+/// Anthropic provider implementation.
+#[derive(Debug, Clone)]
+pub struct AnthropicProvider {
+    /// Configuration, prompt and HTTP client.
+    pub(crate) base: ProviderBase,
+}
+
+impl AnthropicProvider {
+    /// Create a new Anthropic provider.
+    #[must_use]
+    pub fn new(config: ProviderConfig) -> Self {
+        Self::with_prompt(config, SystemPrompt::default())
+    }
+
+    /// Create a new Anthropic provider with custom system prompt.
+    #[must_use]
+    pub fn with_prompt(config: ProviderConfig, prompt: SystemPrompt) -> Self {
+        Self {
+            base: ProviderBase::new(config, prompt, API),
+        }
+    }
+
+    /// Build an Anthropic request from prompt messages.
+    fn build_request(&self, messages: &[PromptMessage], with_tools: bool) -> AnthropicRequest {
+        let (messages, system) = to_anthropic_messages(messages);
+        let caps = capabilities(&self.base.config.model);
+
+        AnthropicRequest {
+            model: self.base.config.model.clone(),
+            system,
+            messages,
+            max_tokens: caps.clamp_max_tokens(self.base.config.max_tokens),
+            temperature: self.base.config.temperature,
+            tools: if with_tools && caps.supports_tools {
+                to_anthropic_tools(&self.base.config.tool_definitions())
+            } else {
+                Vec::new()
+            },
+            tool_choice: Value::Null,
+            stream: false,
+        }
+    }
+
+    /// Call the Messages API.
+    #[tracing::instrument(
+        name = "llm_request",
+        skip_all,
+        fields(provider = "anthropic", model = %self.base.config.model)
+    )]
+    async fn call_api(&self, request: &AnthropicRequest) -> Result<AnthropicResponse, LlmError> {
+        let url = self.base.endpoint("messages")?;
+        let builder = self.base.authorize(self.base.http.post(url).json(request));
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        self.base.config.log_exchange(request, &body);
+        if !status.is_success() {
+            return Err(LlmError::from_status(status));
+        }
+
+        serde_json::from_str(&body).map_err(|e| LlmError::ApiError {
+            message: format!("Invalid Anthropic response: {}", e),
+        })
+    }
+
+    /// Start a streamed Messages API call.
+    #[tracing::instrument(
+        name = "llm_stream",
+        skip_all,
+        fields(provider = "anthropic", model = %self.base.config.model)
+    )]
+    async fn call_stream(&self, request: &AnthropicRequest) -> Result<EventStream, LlmError> {
+        let url = self.base.endpoint("messages")?;
+        let builder = self.base.authorize(self.base.http.post(url).json(request));
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            self.base.config.log_exchange(request, &body);
+            return Err(LlmError::from_status(status));
+        }
+
+        Ok(sse_data(lines(response.bytes_stream())))
+    }
+
+}
+
+#[async_trait]
+impl LlmClient for AnthropicProvider {
+    async fn complete(&self, prompt: &str) -> Result<String, LlmError> {
+        let request = self.build_request(&self.base.prompt_messages(prompt), false);
+        let response = self.call_api(&request).await?;
+        let text = anthropic_text(&response);
+        if text.is_empty() {
+            return Err(LlmError::NoResponse);
+        }
+        Ok(text)
+    }
+
+    async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision(&messages, async {
+                let response = self.call_api(&self.build_request(&messages, true)).await?;
+                from_anthropic_response(&response)
+            })
+            .await
+    }
+
+    async fn complete_stream(&self, prompt: &str) -> Result<TokenStream, LlmError> {
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.stream = true;
+        let events = self.call_stream(&request).await?;
+        Ok(token_stream(events, anthropic_stream_deltas))
+    }
+
+    async fn generate_decision_stream(
+        &self,
+        context_json: &Value,
+    ) -> Result<DecisionStream, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision_stream(&messages, async {
+                let mut request = self.build_request(&messages, true);
+                request.stream = true;
+                let events = self.call_stream(&request).await?;
+                let config = self.base.config.clone();
+                let log = move |raw: &str| config.log_exchange(&request, raw);
+                Ok(decision_stream(events, anthropic_stream_deltas, log))
+            })
+            .await
+    }
+
+    async fn generate_structured(&self, prompt: &str, schema: &Value) -> Result<Value, LlmError> {
+        // Tool inputs are objects; other schemas are described in the prompt
+        if schema.get("type").and_then(Value::as_str) != Some("object") {
+            let content = self.complete(&structured_prompt(prompt, schema)).await?;
+            return parse_structured(&content);
+        }
+
+        // Forcing a call to a tool taking the schema yields a matching object
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.tools = vec![AnthropicTool {
+            name: STRUCTURED_TOOL.to_string(),
+            description: "Respond with a value matching the input schema.".to_string(),
+            input_schema: schema.clone(),
+        }];
+        request.tool_choice = serde_json::json!({ "type": "tool", "name": STRUCTURED_TOOL });
+
+        let response = self.call_api(&request).await?;
+        response
+            .content
+            .into_iter()
+            .find_map(|block| match block {
+                AnthropicContent::ToolUse { input, .. } => Some(input),
+                _ => None,
+            })
+            .ok_or(LlmError::NoResponse)
+    }
+
+    fn provider_info(&self) -> ProviderInfo {
+        self.base.info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptBuilder;
+
+    #[test]
+    fn test_build_request() {
+        let config = ProviderConfig {
+            provider_type: "anthropic".to_string(),
+            model: "claude-3-5-sonnet-latest".to_string(),
+            max_tokens: 50_000,
+            ..ProviderConfig::default()
+        };
+        let provider = AnthropicProvider::new(config);
+        assert_eq!(
+            provider.base.endpoint("messages").unwrap().as_str(),
+            "https://api.anthropic.com/v1/messages"
+        );
+
+        let messages = PromptBuilder::new().system("sys").user("hi").build();
+        let request = provider.build_request(&messages, true);
+        assert_eq!(request.system.as_deref(), Some("sys"));
+        assert_eq!(request.messages.len(), 1);
+        assert_eq!(request.max_tokens, 8_192);
+        assert!(request.tools.iter().any(|t| t.name == "execute_query"));
+
+        // Tool choice is only sent for structured output
+        let body = serde_json::to_value(&request).unwrap();
+        assert!(body.get("tool_choice").is_none());
+        assert_eq!(body["messages"][0]["content"][0]["type"], "text");
+    }
+}
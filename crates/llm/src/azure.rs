@@ -0,0 +1,268 @@
+//! Azure OpenAI provider.
+//!
+//! Uses the OpenAI wire format, but requests are routed to a named
+//! deployment under the resource endpoint and authenticated with either an
+//! `api-key` header or an Azure AD bearer token.
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::client::LlmClient;
+use super::conversion::{
+    context_to_messages, from_openai_response, openai_stream_deltas, to_openai_messages,
+    OpenAiChatRequest, OpenAiChatResponse, OpenAiMessage,
+};
+use super::error::LlmError;
+use super::models::capabilities;
+use super::provider::{ApiSpec, KeyAuth, ModelListing, ProviderBase, ProviderConfig, ProviderInfo};
+use super::prompt::{PromptMessage, SystemPrompt};
+use super::stream::{
+    decision_stream, lines, sse_data, token_stream, DecisionStream, EventStream, TokenStream,
+};
+
+/// Azure OpenAI API details; the base URL is required, so the default is
+/// never used.
+const API: ApiSpec = ApiSpec {
+    default_base_url: "https://invalid/",
+    auth: KeyAuth::Azure,
+    headers: &[],
+    models: ModelListing { path: "openai/models", list: "data", id: "id", prefix: "" },
+};
+
+/// Azure OpenAI provider implementation.
+#[derive(Debug, Clone)]
+pub struct AzureOpenAiProvider {
+    /// Configuration, prompt and HTTP client.
+    pub(crate) base: ProviderBase,
+}
+
+impl AzureOpenAiProvider {
+    /// Create a new Azure OpenAI provider.
+    ///
+    /// # Errors
+    /// Returns an error if the resource endpoint or deployment is not configured.
+    pub fn new(config: ProviderConfig) -> Result<Self, LlmError> {
+        Self::with_prompt(config, SystemPrompt::default())
+    }
+
+    /// Create a new Azure OpenAI provider with custom system prompt.
+    ///
+    /// # Errors
+    /// Returns an error if the resource endpoint or deployment is not configured.
+    pub fn with_prompt(config: ProviderConfig, prompt: SystemPrompt) -> Result<Self, LlmError> {
+        let Some(api_version) = config.azure.as_ref().map(|a| a.api_version.clone()) else {
+            return Err(LlmError::ApiError {
+                message: "Azure OpenAI requires a deployment ([llm.azure])".to_string(),
+            });
+        };
+        if config.base_url.is_none() {
+            return Err(LlmError::ApiError {
+                message: "Azure OpenAI requires the resource endpoint as llm.base-url".to_string(),
+            });
+        }
+
+        Ok(Self {
+            base: ProviderBase::new(config, prompt, API).with_query("api-version", api_version),
+        })
+    }
+
+    /// Name of the deployment requests are routed to.
+    fn deployment(&self) -> &str {
+        // The settings are checked in the constructor; a deployment is
+        // named after its model when switched
+        let config = &self.base.config;
+        config.azure.as_ref().map_or(config.model.as_str(), |a| a.deployment.as_str())
+    }
+
+    /// URL of the chat completions endpoint for the configured deployment.
+    fn chat_url(&self) -> Result<url::Url, LlmError> {
+        self.base.endpoint(&format!("openai/deployments/{}/chat/completions", self.deployment()))
+    }
+
+    /// Build a chat request from prompt messages.
+    ///
+    /// Capabilities are looked up by `model`, which should name the model
+    /// behind the deployment.
+    fn build_request(&self, messages: &[PromptMessage], with_tools: bool) -> OpenAiChatRequest {
+        let caps = capabilities(&self.base.config.model);
+
+        OpenAiChatRequest {
+            // Ignored by Azure, which routes on the deployment in the URL
+            model: self.deployment().to_string(),
+            messages: to_openai_messages(messages),
+            temperature: Some(self.base.config.temperature),
+            max_tokens: Some(caps.clamp_max_tokens(self.base.config.max_tokens)),
+            seed: self.base.config.seed,
+            tools: if with_tools && caps.supports_tools {
+                self.base.config.tool_definitions()
+            } else {
+                Vec::new()
+            },
+            response_format: if caps.supports_json_mode {
+                serde_json::json!({ "type": "json_object" })
+            } else {
+                Value::Null
+            },
+            stream: false,
+            stream_options: Value::Null,
+        }
+    }
+
+    /// Call the chat completions endpoint.
+    #[tracing::instrument(
+        name = "llm_request",
+        skip_all,
+        fields(provider = "azure-openai", deployment = %self.deployment())
+    )]
+    async fn call_api(&self, request: &OpenAiChatRequest) -> Result<OpenAiChatResponse, LlmError> {
+        let builder = self.base.authorize(self.base.http.post(self.chat_url()?).json(request));
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        self.base.config.log_exchange(request, &body);
+        if !status.is_success() {
+            return Err(LlmError::from_status(status));
+        }
+
+        serde_json::from_str(&body).map_err(|e| LlmError::ApiError {
+            message: format!("Invalid Azure OpenAI response: {}", e),
+        })
+    }
+
+    /// Start a streamed chat completion.
+    #[tracing::instrument(
+        name = "llm_stream",
+        skip_all,
+        fields(provider = "azure-openai", deployment = %self.deployment())
+    )]
+    async fn call_stream(&self, request: &OpenAiChatRequest) -> Result<EventStream, LlmError> {
+        let builder = self.base.authorize(self.base.http.post(self.chat_url()?).json(request));
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            self.base.config.log_exchange(request, &body);
+            return Err(LlmError::from_status(status));
+        }
+
+        Ok(sse_data(lines(response.bytes_stream())))
+    }
+
+}
+
+#[async_trait]
+impl LlmClient for AzureOpenAiProvider {
+    async fn complete(&self, prompt: &str) -> Result<String, LlmError> {
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.response_format = Value::Null;
+        let response = self.call_api(&request).await?;
+
+        match response.choices.first().map(|c| &c.message) {
+            Some(OpenAiMessage::Assistant {
+                content: Some(text), ..
+            }) => Ok(text.clone()),
+            _ => Err(LlmError::NoResponse),
+        }
+    }
+
+    async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision(&messages, async {
+                let response = self.call_api(&self.build_request(&messages, true)).await?;
+                from_openai_response(&response)
+            })
+            .await
+    }
+
+    async fn complete_stream(&self, prompt: &str) -> Result<TokenStream, LlmError> {
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.response_format = Value::Null;
+        request.stream = true;
+        let events = self.call_stream(&request).await?;
+        Ok(token_stream(events, openai_stream_deltas))
+    }
+
+    async fn generate_decision_stream(
+        &self,
+        context_json: &Value,
+    ) -> Result<DecisionStream, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision_stream(&messages, async {
+                let mut request = self.build_request(&messages, true);
+                request.stream = true;
+                let events = self.call_stream(&request).await?;
+                let config = self.base.config.clone();
+                let log = move |raw: &str| config.log_exchange(&request, raw);
+                Ok(decision_stream(events, openai_stream_deltas, log))
+            })
+            .await
+    }
+
+    fn provider_info(&self) -> ProviderInfo {
+        self.base.info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_agent_config::AzureOpenAiConfig;
+
+    fn azure_config() -> ProviderConfig {
+        ProviderConfig {
+            provider_type: "azure-openai".to_string(),
+            base_url: Some("https://my-resource.openai.azure.com".parse().unwrap()),
+            api_key: Some("azure-key".into()),
+            azure: Some(AzureOpenAiConfig {
+                deployment: "gpt-4o-prod".to_string(),
+                api_version: "2024-06-01".to_string(),
+                ad_token: None,
+            }),
+            ..ProviderConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_requires_deployment_and_endpoint() {
+        assert!(AzureOpenAiProvider::new(ProviderConfig::default()).is_err());
+
+        let mut config = azure_config();
+        config.base_url = None;
+        assert!(AzureOpenAiProvider::new(config).is_err());
+    }
+
+    #[test]
+    fn test_chat_url() {
+        let provider = AzureOpenAiProvider::new(azure_config()).unwrap();
+        assert_eq!(
+            provider.chat_url().unwrap().as_str(),
+            "https://my-resource.openai.azure.com/openai/deployments/gpt-4o-prod/chat/completions?api-version=2024-06-01"
+        );
+    }
+
+    #[test]
+    fn test_authorization_header() {
+        let provider = AzureOpenAiProvider::new(azure_config()).unwrap();
+        let request = provider.base.authorize(provider.base.http.get("http://localhost/"));
+        let request = request.build().unwrap();
+        assert_eq!(request.headers()["api-key"], "azure-key");
+
+        let mut config = azure_config();
+        if let Some(azure) = &mut config.azure {
+            azure.ad_token = Some("aad-token".into());
+        }
+        let provider = AzureOpenAiProvider::new(config).unwrap();
+        let request = provider.base.authorize(provider.base.http.get("http://localhost/"));
+        let request = request.build().unwrap();
+        assert_eq!(request.headers()["authorization"], "Bearer aad-token");
+        assert!(request.headers().get("api-key").is_none());
+    }
+}
@@ -0,0 +1,281 @@
+//! Cache of LLM decisions.
+//!
+//! Decisions are keyed by a SHA-256 hash of everything that shapes them:
+//! provider, model, sampling settings and the messages sent, system prompt
+//! included. Recent entries are kept in memory and the least recently used
+//! one is dropped when it is full. With a directory set, entries are also
+//! written there, one file each, so they outlive the process. Entries expire
+//! after a time to live since the database they describe keeps changing.
+
+use lru::LruCache;
+use postgres_agent_util::codec::FileCodec;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::decision::TokenUsage;
+
+/// A cached decision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    /// Unix timestamp in seconds of when the decision was made.
+    stored_at: u64,
+    /// The decision, without token usage.
+    decision: Value,
+}
+
+/// In-memory LRU cache of decisions, optionally backed by a directory.
+#[derive(Debug)]
+pub struct ResponseCache {
+    /// Entries in memory, least recently used first out.
+    memory: Mutex<LruCache<String, CacheEntry>>,
+    /// How long an entry is used for.
+    ttl: Duration,
+    /// Directory entries are persisted to, if any.
+    dir: Option<PathBuf>,
+    /// Encoding of persisted entries.
+    codec: FileCodec,
+}
+
+impl ResponseCache {
+    /// Create a memory-only cache of `capacity` entries that live an hour.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            memory: Mutex::new(LruCache::new(capacity)),
+            ttl: Duration::from_secs(3600),
+            dir: None,
+            codec: FileCodec::default(),
+        }
+    }
+
+    /// Set how long an entry is used for.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Persist entries to `dir`.
+    #[must_use]
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Write persisted entries with `codec`.
+    #[must_use]
+    pub fn with_codec(mut self, codec: FileCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Key of a request: the hex SHA-256 of its JSON form.
+    ///
+    /// # Errors
+    /// Returns an error if the request cannot be serialized.
+    pub fn key(request: &impl Serialize) -> Result<String, serde_json::Error> {
+        let json = serde_json::to_vec(request)?;
+        Ok(hex::encode(Sha256::digest(&json)))
+    }
+
+    /// Get the decision stored under `key`, unless it has expired.
+    ///
+    /// The decision reports zero token usage, so that a cache hit is
+    /// recorded as a call that cost nothing.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        let mut decision = self.lookup(key)?;
+        TokenUsage::default().attach(&mut decision);
+        Some(decision)
+    }
+
+    /// The decision stored under `key`, unless it has expired.
+    fn lookup(&self, key: &str) -> Option<Value> {
+        let now = now_secs();
+        {
+            let mut memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(entry) = memory.get(key) {
+                if self.is_fresh(entry, now) {
+                    return Some(entry.decision.clone());
+                }
+                memory.pop(key);
+            }
+        }
+
+        let path = self.entry_path(key)?;
+        let entry: CacheEntry = match self.codec.read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).ok()?,
+            Err(_) => return None,
+        };
+        if !self.is_fresh(&entry, now) {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        let decision = entry.decision.clone();
+        self.remember(key, entry);
+        Some(decision)
+    }
+
+    /// Store a decision under `key`; its token usage is left out.
+    ///
+    /// Failures to persist are logged and otherwise ignored, like a miss.
+    pub fn put(&self, key: &str, decision: &Value) {
+        let mut decision = decision.clone();
+        TokenUsage::take(&mut decision);
+        let entry = CacheEntry {
+            stored_at: now_secs(),
+            decision,
+        };
+
+        if let Some(path) = self.entry_path(key) {
+            let written = serde_json::to_vec(&entry)
+                .map_err(std::io::Error::from)
+                .and_then(|json| {
+                    if let Some(dir) = path.parent() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    self.codec.write(&path, &json)
+                });
+            if let Err(e) = written {
+                tracing::warn!("Failed to persist cached LLM response: {}", e);
+            }
+        }
+        self.remember(key, entry);
+    }
+
+    /// Drop every entry, in memory and on disk.
+    ///
+    /// # Errors
+    /// Returns an error if the cache directory cannot be removed.
+    pub fn clear(&self) -> std::io::Result<()> {
+        self.memory.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        match &self.dir {
+            Some(dir) if dir.exists() => std::fs::remove_dir_all(dir),
+            _ => Ok(()),
+        }
+    }
+
+    /// Keep an entry in memory, evicting the least recently used if full.
+    fn remember(&self, key: &str, entry: CacheEntry) {
+        self.memory.lock().unwrap_or_else(|e| e.into_inner()).put(key.to_string(), entry);
+    }
+
+    /// Whether an entry is within its time to live.
+    fn is_fresh(&self, entry: &CacheEntry, now: u64) -> bool {
+        now.saturating_sub(entry.stored_at) < self.ttl.as_secs()
+    }
+
+    /// File of a persisted entry.
+    fn entry_path(&self, key: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{}.json", key)))
+    }
+}
+
+/// Current Unix time in seconds.
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptMessage;
+    use crate::provider::ProviderConfig;
+    use crate::error::LlmError;
+    use crate::stream::DecisionChunk;
+    use futures::{stream, StreamExt};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = ResponseCache::new(2);
+        cache.put("a", &json!({ "answer": "a" }));
+        cache.put("b", &json!({ "answer": "b" }));
+        assert!(cache.get("a").is_some());
+
+        // "b" is the least recently used
+        cache.put("c", &json!({ "answer": "c" }));
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("a").unwrap()["answer"], "a");
+        assert_eq!(cache.get("c").unwrap()["answer"], "c");
+
+        let key = |request| ResponseCache::key(&("gpt-4o", request)).unwrap();
+        assert_ne!(key("hi"), key("ho"));
+    }
+
+    #[test]
+    fn test_unserializable_request_has_no_key() {
+        use std::collections::HashMap;
+
+        // JSON object keys must be strings
+        let request: HashMap<(u8, u8), &str> = HashMap::from([((1, 2), "hi")]);
+        assert!(ResponseCache::key(&request).is_err());
+    }
+
+    #[test]
+    fn test_persisted_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = ResponseCache::key(&"question").unwrap();
+        let mut decision = json!({ "answer": "42" });
+        TokenUsage::new(10u64, 5u64).attach(&mut decision);
+        ResponseCache::new(4).with_dir(dir.path()).put(&key, &decision);
+
+        // A new process reads it back, without the usage of the original call
+        let cache = ResponseCache::new(4).with_dir(dir.path());
+        let mut cached = cache.get(&key).unwrap();
+        assert_eq!(TokenUsage::take(&mut cached), Some(TokenUsage::default()));
+        assert_eq!(cached, json!({ "answer": "42" }));
+
+        let expired = ResponseCache::new(4).with_dir(dir.path()).with_ttl(Duration::ZERO);
+        assert!(expired.get(&key).is_none());
+        assert!(!dir.path().join(format!("{}.json", key)).exists());
+    }
+
+    #[tokio::test]
+    async fn test_cached_decision_stream() {
+        let config = ProviderConfig {
+            response_cache: Some(Arc::new(ResponseCache::new(4))),
+            ..ProviderConfig::default()
+        };
+        let messages = vec![PromptMessage::User {
+            content: "How many users?".to_string(),
+        }];
+        let mut decision = json!({ "answer": "3" });
+        TokenUsage::new(20u64, 2u64).attach(&mut decision);
+        let chunks = vec![
+            Ok(DecisionChunk::AnswerDelta("3".to_string())),
+            Ok(DecisionChunk::Decision(decision.clone())),
+        ];
+
+        let streamed = config
+            .cached_decision_stream(&messages, async { Ok(stream::iter(chunks).boxed()) })
+            .await
+            .unwrap();
+        assert_eq!(streamed.collect::<Vec<_>>().await.len(), 2);
+
+        // The same messages are answered from the cache, at no token cost
+        let cached = config
+            .cached_decision_stream(&messages, async { Err(LlmError::NoResponse) })
+            .await
+            .unwrap();
+        let cached: Vec<_> = cached.collect().await;
+        let mut zero = json!({ "answer": "3" });
+        TokenUsage::default().attach(&mut zero);
+        assert!(matches!(
+            cached.as_slice(),
+            [Ok(DecisionChunk::Decision(d))] if *d == zero
+        ));
+        let decision = config.cached_decision(&messages, async { Err(LlmError::NoResponse) });
+        assert_eq!(decision.await.unwrap(), zero);
+    }
+}
@@ -1,12 +1,19 @@
 //! LLM client trait.
+//!
+//! [`LlmClient`] is object-safe, so a provider chosen at runtime can be held
+//! as `Box<dyn LlmClient>` or `Arc<dyn LlmClient>`; both implement the trait.
+
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::fmt::Debug;
 
 use super::error::LlmError;
+use super::prompt::{structured_prompt, summary_prompt, PromptMessage};
 use super::provider::ProviderInfo;
+use super::stream::{single_decision, DecisionStream, TokenStream};
 
 /// Trait for LLM client implementations.
 #[async_trait]
@@ -20,13 +27,154 @@ pub trait LlmClient: Send + Sync {
         context_json: &Value,
     ) -> Result<Value, LlmError>;
 
-    /// Generate structured output with a schema.
-    async fn generate_structured<T: DeserializeOwned + Debug>(
+    /// Generate a text completion, streamed as the model writes it.
+    ///
+    /// Providers that cannot stream yield the whole completion at once.
+    async fn complete_stream(&self, prompt: &str) -> Result<TokenStream, LlmError> {
+        let text = self.complete(prompt).await?;
+        Ok(stream::once(std::future::ready(Ok(text))).boxed())
+    }
+
+    /// Generate a decision, streaming the text of a final answer before it.
+    ///
+    /// Providers that cannot stream yield the decision alone.
+    async fn generate_decision_stream(
         &self,
-        prompt: &str,
-        schema: &T,
-    ) -> Result<T, LlmError>;
+        context_json: &Value,
+    ) -> Result<DecisionStream, LlmError> {
+        Ok(single_decision(self.generate_decision(context_json).await?))
+    }
+
+    /// Generate a JSON value matching a JSON Schema.
+    ///
+    /// Use [`generate_typed`] to deserialize the value.
+    async fn generate_structured(&self, prompt: &str, schema: &Value) -> Result<Value, LlmError> {
+        let content = self.complete(&structured_prompt(prompt, schema)).await?;
+        parse_structured(&content)
+    }
+
+    /// Summarize a conversation into a short digest.
+    ///
+    /// Used to compact long contexts and to preview saved sessions.
+    async fn summarize(&self, messages: &[PromptMessage]) -> Result<String, LlmError> {
+        let summary = self.complete(&summary_prompt(messages)).await?;
+        Ok(summary.trim().to_string())
+    }
 
     /// Get provider information.
     fn provider_info(&self) -> ProviderInfo;
 }
+
+/// Generate structured output and deserialize it.
+///
+/// # Errors
+/// Returns an error if generation fails or the value does not deserialize
+/// into `T`.
+pub async fn generate_typed<T: DeserializeOwned>(
+    client: &(impl LlmClient + ?Sized),
+    prompt: &str,
+    schema: &Value,
+) -> Result<T, LlmError> {
+    let value = client.generate_structured(prompt, schema).await?;
+    serde_json::from_value(value).map_err(|e| LlmError::ApiError {
+        message: format!("Structured response does not match the schema: {}", e),
+    })
+}
+
+/// Parse a completion holding structured output.
+pub(crate) fn parse_structured(content: &str) -> Result<Value, LlmError> {
+    serde_json::from_str(content.trim()).map_err(|e| LlmError::ApiError {
+        message: format!("Failed to parse structured response: {}", e),
+    })
+}
+
+/// Implement [`LlmClient`] for a smart pointer by delegating to its target.
+macro_rules! delegate_llm_client {
+    ($pointer:ident) => {
+        #[async_trait]
+        impl<C: LlmClient + ?Sized> LlmClient for $pointer<C> {
+            async fn complete(&self, prompt: &str) -> Result<String, LlmError> {
+                (**self).complete(prompt).await
+            }
+
+            async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+                (**self).generate_decision(context_json).await
+            }
+
+            async fn complete_stream(&self, prompt: &str) -> Result<TokenStream, LlmError> {
+                (**self).complete_stream(prompt).await
+            }
+
+            async fn generate_decision_stream(
+                &self,
+                context_json: &Value,
+            ) -> Result<DecisionStream, LlmError> {
+                (**self).generate_decision_stream(context_json).await
+            }
+
+            async fn generate_structured(
+                &self,
+                prompt: &str,
+                schema: &Value,
+            ) -> Result<Value, LlmError> {
+                (**self).generate_structured(prompt, schema).await
+            }
+
+            async fn summarize(&self, messages: &[PromptMessage]) -> Result<String, LlmError> {
+                (**self).summarize(messages).await
+            }
+
+            fn provider_info(&self) -> ProviderInfo {
+                (**self).provider_info()
+            }
+        }
+    };
+}
+
+delegate_llm_client!(Box);
+delegate_llm_client!(Arc);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every prompt with a fixed completion.
+    struct FixedClient(&'static str);
+
+    #[async_trait]
+    impl LlmClient for FixedClient {
+        async fn complete(&self, _prompt: &str) -> Result<String, LlmError> {
+            Ok(self.0.to_string())
+        }
+
+        async fn generate_decision(&self, _context_json: &Value) -> Result<Value, LlmError> {
+            Err(LlmError::NoResponse)
+        }
+
+        fn provider_info(&self) -> ProviderInfo {
+            ProviderInfo {
+                provider: "fixed".to_string(),
+                model: "fixed".to_string(),
+            }
+        }
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Tables {
+        tables: Vec<String>,
+    }
+
+    #[tokio::test]
+    async fn test_dyn_client_structured() {
+        let clients: Vec<Box<dyn LlmClient>> = vec![
+            Box::new(FixedClient(r#"{"tables": ["orders", "users"]}"#)),
+            Box::new(Arc::new(FixedClient("not json"))),
+        ];
+        let schema = serde_json::json!({"type": "object"});
+
+        let tables: Tables = generate_typed(&clients[0], "List tables", &schema).await.unwrap();
+        assert_eq!(tables.tables, ["orders", "users"]);
+        assert!(generate_typed::<Tables>(&clients[1], "List tables", &schema).await.is_err());
+        assert_eq!(clients[1].provider_info().provider, "fixed");
+    }
+}
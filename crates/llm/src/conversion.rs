@@ -1,10 +1,12 @@
-//! Message conversion between internal and OpenAI formats.
+//! Message conversion between internal and provider formats.
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::decision::{AgentDecision, TokenUsage, ToolCall};
 use super::error::LlmError;
-use crate::prompt::{PromptMessage, PromptToolCall, PromptToolCallFunction};
+use super::stream::Delta;
+use crate::prompt::{PromptMessage, PromptRole, PromptToolCall, PromptToolCallFunction, SystemPrompt};
 
 /// OpenAI chat message format.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,7 +30,7 @@ pub enum OpenAiMessage {
         /// Content.
         content: Option<String>,
         /// Tool calls.
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
         tool_calls: Vec<OpenAiToolCall>,
     },
     /// Tool result.
@@ -43,7 +45,6 @@ pub enum OpenAiMessage {
 
 /// OpenAI tool call.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiToolCall {
     /// Call ID.
     pub id: String,
@@ -55,7 +56,6 @@ pub struct OpenAiToolCall {
 
 /// OpenAI function call.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiFunctionCall {
     /// Function name.
     pub name: String,
@@ -65,7 +65,6 @@ pub struct OpenAiFunctionCall {
 
 /// OpenAI chat completion request.
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiChatRequest {
     /// Model identifier.
     pub model: String,
@@ -75,17 +74,25 @@ pub struct OpenAiChatRequest {
     pub temperature: Option<f32>,
     /// Maximum tokens.
     pub max_tokens: Option<u32>,
+    /// Sampling seed for reproducible output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
     /// Tool definitions.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub tools: Vec<OpenAiToolDefinition>,
     /// Response format.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Value::is_null")]
     pub response_format: Value,
+    /// Whether to stream the response as server-sent events.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    /// Streaming options, e.g. `{"include_usage": true}`.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub stream_options: Value,
 }
 
 /// OpenAI tool definition.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiToolDefinition {
     /// Tool type.
     pub r#type: String,
@@ -93,9 +100,23 @@ pub struct OpenAiToolDefinition {
     pub function: OpenAiFunctionSpec,
 }
 
+impl OpenAiToolDefinition {
+    /// Define a function tool.
+    #[must_use]
+    pub fn function(name: String, description: String, parameters: Value) -> Self {
+        Self {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name,
+                description,
+                parameters,
+            },
+        }
+    }
+}
+
 /// OpenAI function specification.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiFunctionSpec {
     /// Function name.
     pub name: String,
@@ -107,7 +128,6 @@ pub struct OpenAiFunctionSpec {
 
 /// OpenAI chat completion response.
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiChatResponse {
     /// ID.
     pub id: String,
@@ -125,7 +145,6 @@ pub struct OpenAiChatResponse {
 
 /// Choice in the response.
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiChoice {
     /// Index.
     pub index: u32,
@@ -137,7 +156,6 @@ pub struct OpenAiChoice {
 
 /// Token usage.
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
 pub struct OpenAiUsage {
     /// Prompt tokens.
     pub prompt_tokens: u32,
@@ -147,9 +165,65 @@ pub struct OpenAiUsage {
     pub total_tokens: u32,
 }
 
+/// Chunk of a streamed chat completion.
+#[derive(Debug, Deserialize)]
+pub struct OpenAiStreamChunk {
+    /// Choices, each with the next piece of its message.
+    #[serde(default)]
+    pub choices: Vec<OpenAiStreamChoice>,
+    /// Usage, in the last chunk when the request asks for it.
+    #[serde(default)]
+    pub usage: Option<OpenAiUsage>,
+}
+
+/// Choice in a streamed chunk.
+#[derive(Debug, Deserialize)]
+pub struct OpenAiStreamChoice {
+    /// Index.
+    #[serde(default)]
+    pub index: u32,
+    /// Next piece of the message.
+    #[serde(default)]
+    pub delta: OpenAiDelta,
+}
+
+/// Next piece of a streamed message.
+#[derive(Debug, Default, Deserialize)]
+pub struct OpenAiDelta {
+    /// More content.
+    pub content: Option<String>,
+    /// Pieces of tool calls.
+    pub tool_calls: Option<Vec<OpenAiToolCallDelta>>,
+}
+
+/// Next piece of a streamed tool call.
+#[derive(Debug, Deserialize)]
+pub struct OpenAiToolCallDelta {
+    /// Position of the call in the message.
+    pub index: usize,
+    /// Call ID, sent with the first piece.
+    pub id: Option<String>,
+    /// Function name and more of the arguments.
+    pub function: Option<OpenAiFunctionDelta>,
+}
+
+/// Next piece of a streamed function call.
+#[derive(Debug, Deserialize)]
+pub struct OpenAiFunctionDelta {
+    /// Function name, sent with the first piece.
+    pub name: Option<String>,
+    /// More of the arguments as JSON text.
+    pub arguments: Option<String>,
+}
+
 /// Convert internal prompt messages to OpenAI format.
+///
+/// Tool results must answer a tool call of an earlier assistant message;
+/// results without one, such as those restored from the agent context, are
+/// sent as user text instead.
 #[must_use]
 pub fn to_openai_messages(messages: &[PromptMessage]) -> Vec<OpenAiMessage> {
+    let mut call_ids = Vec::new();
     messages
         .iter()
         .map(|m| match m {
@@ -163,30 +237,51 @@ pub fn to_openai_messages(messages: &[PromptMessage]) -> Vec<OpenAiMessage> {
                 content: Some(content.clone()),
                 tool_calls: tool_calls
                     .iter()
-                    .map(|tc| OpenAiToolCall {
-                        id: tc.id.clone(),
-                        r#type: tc.r#type.clone(),
-                        function: OpenAiFunctionCall {
-                            name: tc.function.name.clone(),
-                            arguments: tc.function.arguments.clone(),
-                        },
+                    .map(|tc| {
+                        call_ids.push(tc.id.clone());
+                        OpenAiToolCall {
+                            id: tc.id.clone(),
+                            r#type: tc.r#type.clone(),
+                            function: OpenAiFunctionCall {
+                                name: tc.function.name.clone(),
+                                arguments: tc.function.arguments.clone(),
+                            },
+                        }
                     })
                     .collect(),
             },
             PromptMessage::Tool {
                 tool_call_id,
-                name: _,
+                name,
                 content,
-            } => OpenAiMessage::Tool {
-                tool_call_id: tool_call_id.clone(),
-                content: content.clone(),
-            },
+            } => {
+                if call_ids.contains(tool_call_id) {
+                    OpenAiMessage::Tool {
+                        tool_call_id: tool_call_id.clone(),
+                        content: content.clone(),
+                    }
+                } else {
+                    OpenAiMessage::User {
+                        content: format!("Result of {}:\n{}", name, content),
+                    }
+                }
+            }
         })
         .collect()
 }
 
-/// Convert OpenAI response to internal decision format.
+/// Convert OpenAI response to internal decision format, with the token usage
+/// it reports.
 pub fn from_openai_response(response: &OpenAiChatResponse) -> Result<Value, LlmError> {
+    let mut decision = openai_decision(response)?;
+    if let Some(usage) = &response.usage {
+        TokenUsage::new(usage.prompt_tokens, usage.completion_tokens).attach(&mut decision);
+    }
+    Ok(decision)
+}
+
+/// Decision of an OpenAI response.
+fn openai_decision(response: &OpenAiChatResponse) -> Result<Value, LlmError> {
     if response.choices.is_empty() {
         return Err(LlmError::NoResponse);
     }
@@ -198,25 +293,10 @@ pub fn from_openai_response(response: &OpenAiChatResponse) -> Result<Value, LlmE
             if !tool_calls.is_empty() {
                 // Tool call
                 let tool = &tool_calls[0];
-                Ok(serde_json::json!({
-                    "type": "tool_call",
-                    "name": tool.function.name,
-                    "arguments": tool.function.arguments,
-                    "call_id": tool.id,
-                }))
+                let arguments = Value::String(tool.function.arguments.clone());
+                tool_call_decision(&tool.function.name, arguments, &tool.id)
             } else if let Some(text) = content {
-                // Final answer or reasoning
-                // Try to parse as structured decision first
-                if let Ok(decision) = serde_json::from_str::<Value>(text) {
-                    if decision.get("type").is_some() {
-                        return Ok(decision);
-                    }
-                }
-                // Fall back to final answer
-                Ok(serde_json::json!({
-                    "type": "final_answer",
-                    "answer": text
-                }))
+                Ok(text_decision(text))
             } else {
                 Err(LlmError::ApiError {
                     message: "Empty assistant message".to_string(),
@@ -229,6 +309,47 @@ pub fn from_openai_response(response: &OpenAiChatResponse) -> Result<Value, LlmE
     }
 }
 
+/// Parse an event of a streamed OpenAI chat completion into deltas.
+pub(crate) fn openai_stream_deltas(data: &str) -> Result<Vec<Delta>, LlmError> {
+    let chunk: OpenAiStreamChunk = parse_stream_event(data)?;
+    let mut deltas: Vec<Delta> = chunk
+        .usage
+        .map(|usage| Delta::Usage(TokenUsage::new(usage.prompt_tokens, usage.completion_tokens)))
+        .into_iter()
+        .collect();
+    for choice in chunk.choices.into_iter().filter(|c| c.index == 0) {
+        deltas.extend(choice.delta.content.map(Delta::Text));
+        for call in choice.delta.tool_calls.into_iter().flatten() {
+            let function = call.function.unwrap_or(OpenAiFunctionDelta {
+                name: None,
+                arguments: None,
+            });
+            deltas.push(Delta::ToolCall {
+                index: call.index,
+                id: call.id,
+                name: function.name,
+                arguments: function.arguments.unwrap_or_default(),
+            });
+        }
+    }
+    Ok(deltas)
+}
+
+/// Parse an event of a streamed response, failing on error events.
+fn parse_stream_event<T: serde::de::DeserializeOwned>(data: &str) -> Result<T, LlmError> {
+    let invalid = |e: serde_json::Error| LlmError::ApiError {
+        message: format!("Invalid stream event: {}", e),
+    };
+    let value: Value = serde_json::from_str(data).map_err(invalid)?;
+    if let Some(error) = value.get("error") {
+        let message = error.get("message").unwrap_or(error);
+        return Err(LlmError::ApiError {
+            message: message.as_str().map_or_else(|| message.to_string(), str::to_string),
+        });
+    }
+    serde_json::from_value(value).map_err(invalid)
+}
+
 /// Create tool definitions for OpenAI function calling.
 #[must_use]
 pub fn create_tool_definitions() -> Vec<OpenAiToolDefinition> {
@@ -254,11 +375,11 @@ pub fn create_tool_definitions() -> Vec<OpenAiToolDefinition> {
             r#type: "function".to_string(),
             function: OpenAiFunctionSpec {
                 name: "get_schema".to_string(),
-                description: "Get the database schema with tables and columns".to_string(),
+                description: "Get the database schema with tables, columns, row-level security policies and grants".to_string(),
                 parameters: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "filter": {
+                        "tableFilter": {
                             "type": "string",
                             "description": "Optional table name prefix filter"
                         }
@@ -286,12 +407,12 @@ pub fn create_tool_definitions() -> Vec<OpenAiToolDefinition> {
                 parameters: serde_json::json!({
                     "type": "object",
                     "properties": {
-                        "table_name": {
+                        "tableName": {
                             "type": "string",
                             "description": "The name of the table to describe"
                         }
                     },
-                    "required": ["table_name"]
+                    "required": ["tableName"]
                 }),
             },
         },
@@ -312,6 +433,225 @@ pub fn create_tool_definitions() -> Vec<OpenAiToolDefinition> {
                 }),
             },
         },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "get_table_dependencies".to_string(),
+                description: "List views, foreign keys, triggers and functions that depend on a table".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "tableName": {
+                            "type": "string",
+                            "description": "The name of the table to inspect"
+                        },
+                        "schema": {
+                            "type": "string",
+                            "description": "Schema name (defaults to 'public')"
+                        }
+                    },
+                    "required": ["tableName"]
+                }),
+            },
+        },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "trace_column".to_string(),
+                description: "Trace where a column's data comes from and which views, functions and queries depend on it".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "tableName": {
+                            "type": "string",
+                            "description": "Table or view holding the column, optionally schema-qualified"
+                        },
+                        "column": {
+                            "type": "string",
+                            "description": "Column to trace"
+                        },
+                        "schema": {
+                            "type": "string",
+                            "description": "Schema name (defaults to 'public')"
+                        }
+                    },
+                    "required": ["tableName", "column"]
+                }),
+            },
+        },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "detect_anomalies".to_string(),
+                description: "Aggregate a table into time buckets and flag unusual buckets by z-score or IQR".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "tableName": { "type": "string", "description": "Table to analyse" },
+                        "schema": { "type": "string", "description": "Schema name (defaults to 'public')" },
+                        "timeColumn": { "type": "string", "description": "Timestamp column to bucket by" },
+                        "bucket": {
+                            "type": "string",
+                            "enum": ["minute", "hour", "day", "week", "month"],
+                            "description": "Bucket width (default day)"
+                        },
+                        "aggregate": {
+                            "type": "string",
+                            "enum": ["count", "sum", "avg", "min", "max"],
+                            "description": "Aggregate per bucket (default count)"
+                        },
+                        "valueColumn": { "type": "string", "description": "Column to aggregate for sum/avg/min/max" },
+                        "since": { "type": "string", "description": "How far back to look, e.g. '30 days'" },
+                        "sql": { "type": "string", "description": "Custom SELECT returning (bucket, value) rows" },
+                        "method": {
+                            "type": "string",
+                            "enum": ["zscore", "iqr"],
+                            "description": "Detection method (default zscore)"
+                        },
+                        "threshold": { "type": "number", "description": "Z-score threshold or IQR multiplier" }
+                    }
+                }),
+            },
+        },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "compare_profiles".to_string(),
+                description: "Run the same SELECT on two database profiles and diff the results".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "left": {
+                            "type": "string",
+                            "description": "Profile for the left side (defaults to the current connection)"
+                        },
+                        "right": {
+                            "type": "string",
+                            "description": "Profile for the right side"
+                        },
+                        "sql": {
+                            "type": "string",
+                            "description": "SELECT to run on both sides (defaults to row counts per table)"
+                        },
+                        "keyColumns": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Columns used to match rows (defaults to the first column)"
+                        }
+                    },
+                    "required": ["right"]
+                }),
+            },
+        },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "kill_query".to_string(),
+                description: "Cancel a backend's running query or terminate the backend; requires admin approval".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "pid": {
+                            "type": "integer",
+                            "description": "Process ID of the backend"
+                        },
+                        "terminate": {
+                            "type": "boolean",
+                            "description": "Terminate the backend instead of cancelling its query"
+                        }
+                    },
+                    "required": ["pid"]
+                }),
+            },
+        },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "refresh_matview".to_string(),
+                description: "Refresh a materialized view so it reflects current data; requires admin approval".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "description": "Name of the materialized view"
+                        },
+                        "schema": {
+                            "type": "string",
+                            "description": "Schema name (defaults to the profile's default schema or 'public')"
+                        },
+                        "concurrently": {
+                            "type": "boolean",
+                            "description": "Refresh without blocking readers; needs a unique index on the view"
+                        }
+                    },
+                    "required": ["name"]
+                }),
+            },
+        },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "export_result".to_string(),
+                description: "Save query results to a file after the user approves the path".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "Destination file path"
+                        },
+                        "format": {
+                            "type": "string",
+                            "enum": ["csv", "json", "ndjson"],
+                            "description": "File format; inferred from the extension when omitted"
+                        },
+                        "sql": {
+                            "type": "string",
+                            "description": "SELECT query to export instead of the last result"
+                        },
+                        "overwrite": {
+                            "type": "boolean",
+                            "description": "Replace an existing file"
+                        }
+                    },
+                    "required": ["path"]
+                }),
+            },
+        },
+        OpenAiToolDefinition {
+            r#type: "function".to_string(),
+            function: OpenAiFunctionSpec {
+                name: "suggest_chart".to_string(),
+                description: "Suggest a chart for query results as a Vega-Lite spec and an ASCII chart".to_string(),
+                parameters: serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "sql": {
+                            "type": "string",
+                            "description": "SELECT query to chart instead of the last result"
+                        },
+                        "x": {
+                            "type": "string",
+                            "description": "Column for the x axis"
+                        },
+                        "y": {
+                            "type": "string",
+                            "description": "Numeric column for the y axis"
+                        },
+                        "mark": {
+                            "type": "string",
+                            "enum": ["bar", "line", "point"],
+                            "description": "Chart type; inferred when omitted"
+                        },
+                        "title": {
+                            "type": "string",
+                            "description": "Chart title"
+                        }
+                    }
+                }),
+            },
+        },
     ]
 }
 
@@ -320,72 +660,1270 @@ pub fn create_tool_definitions() -> Vec<OpenAiToolDefinition> {
 pub fn parse_tool_calls(response: &OpenAiChatResponse) -> Vec<PromptToolCall> {
     let mut calls = Vec::new();
 
-    if let Some(choice) = response.choices.first() {
-        if let OpenAiMessage::Assistant { tool_calls, .. } = &choice.message {
-            for tc in tool_calls {
-                calls.push(PromptToolCall {
-                    id: tc.id.clone(),
-                    r#type: tc.r#type.clone(),
-                    function: PromptToolCallFunction {
-                        name: tc.function.name.clone(),
-                        arguments: tc.function.arguments.clone(),
-                    },
-                });
-            }
+    if let Some(choice) = response.choices.first()
+        && let OpenAiMessage::Assistant { tool_calls, .. } = &choice.message
+    {
+        for tc in tool_calls {
+            calls.push(PromptToolCall {
+                id: tc.id.clone(),
+                r#type: tc.r#type.clone(),
+                function: PromptToolCallFunction {
+                    name: tc.function.name.clone(),
+                    arguments: tc.function.arguments.clone(),
+                },
+            });
         }
     }
 
     calls
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::prompt::{PromptBuilder, SystemPrompt};
+/// Gemini `generateContent` request.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiRequest {
+    /// Conversation turns.
+    pub contents: Vec<GeminiContent>,
+    /// System instruction, sent separately from the turns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_instruction: Option<GeminiContent>,
+    /// Tool declarations.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<GeminiTool>,
+    /// Sampling parameters.
+    pub generation_config: GeminiGenerationConfig,
+    /// Safety filter thresholds.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_settings: Vec<GeminiSafetySetting>,
+}
 
-    #[test]
-    fn test_to_openai_messages() {
-        let messages = PromptBuilder::new()
-            .system("You are a helpful assistant.")
-            .user("Hello, world!")
-            .build();
+/// A Gemini conversation turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiContent {
+    /// Role (`user` or `model`); absent for system instructions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    /// Content parts.
+    #[serde(default)]
+    pub parts: Vec<GeminiPart>,
+}
 
-        let openai_messages = to_openai_messages(&messages);
-        assert_eq!(openai_messages.len(), 2);
+/// A part of a Gemini turn. Exactly one field is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiPart {
+    /// Text content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Function call requested by the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<GeminiFunctionCall>,
+    /// Result of a function call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_response: Option<GeminiFunctionResponse>,
+}
 
-        if let OpenAiMessage::System { content } = &openai_messages[0] {
-            assert!(content.contains("helpful"));
-        }
-    }
+/// Gemini function call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionCall {
+    /// Function name.
+    pub name: String,
+    /// Arguments as a JSON object.
+    #[serde(default)]
+    pub args: Value,
+}
 
-    #[test]
-    fn test_from_openai_response() {
-        let response = OpenAiChatResponse {
-            id: "test-123".to_string(),
-            object: "chat.completion".to_string(),
-            created: 1234567890,
-            model: "gpt-4o".to_string(),
-            choices: vec![OpenAiChoice {
-                index: 0,
-                message: OpenAiMessage::Assistant {
-                    content: Some("Hello!".to_string()),
-                    tool_calls: Vec::new(),
-                },
-                finish_reason: Some("stop".to_string()),
-            }],
-            usage: None,
-        };
+/// Gemini function result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionResponse {
+    /// Function name.
+    pub name: String,
+    /// Result as a JSON object.
+    pub response: Value,
+}
 
-        let decision = from_openai_response(&response);
-        assert!(decision.is_ok());
-        let value = decision.unwrap();
-        assert_eq!(value["type"], "final_answer");
-    }
+/// Gemini tool declaration group.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiTool {
+    /// Function declarations.
+    pub function_declarations: Vec<GeminiFunctionDeclaration>,
+}
+
+/// Gemini function declaration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiFunctionDeclaration {
+    /// Function name.
+    pub name: String,
+    /// Description.
+    pub description: String,
+    /// Parameters schema (OpenAPI subset); omitted for functions without arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+}
+
+/// Gemini sampling parameters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiGenerationConfig {
+    /// Temperature.
+    pub temperature: f32,
+    /// Maximum output tokens.
+    pub max_output_tokens: u32,
+    /// Sampling seed for reproducible output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Gemini safety filter threshold for one harm category.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSafetySetting {
+    /// Harm category, e.g. `HARM_CATEGORY_DANGEROUS_CONTENT`.
+    pub category: String,
+    /// Blocking threshold, e.g. `BLOCK_ONLY_HIGH`.
+    pub threshold: String,
+}
+
+/// Gemini `generateContent` response.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiResponse {
+    /// Candidate completions.
+    #[serde(default)]
+    pub candidates: Vec<GeminiCandidate>,
+    /// Token usage.
+    pub usage_metadata: Option<GeminiUsage>,
+}
+
+/// Gemini candidate completion.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiCandidate {
+    /// Generated content; absent when blocked by safety filters.
+    pub content: Option<GeminiContent>,
+    /// Finish reason.
+    pub finish_reason: Option<String>,
+}
+
+/// Gemini token usage.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiUsage {
+    /// Prompt tokens.
+    #[serde(default)]
+    pub prompt_token_count: u32,
+    /// Generated tokens.
+    #[serde(default)]
+    pub candidates_token_count: u32,
+    /// Total tokens.
+    #[serde(default)]
+    pub total_token_count: u32,
+}
+
+impl GeminiPart {
+    /// Create a text part.
+    fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: Some(text.into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Convert internal prompt messages to Gemini turns and a system instruction.
+///
+/// Gemini has no system or tool roles: system messages are merged into the
+/// system instruction and tool results become `functionResponse` parts.
+#[must_use]
+pub fn to_gemini_contents(messages: &[PromptMessage]) -> (Vec<GeminiContent>, Option<GeminiContent>) {
+    let mut system = Vec::new();
+    let mut contents = Vec::new();
+
+    for message in messages {
+        let (role, parts) = match message {
+            PromptMessage::System { content } => {
+                system.push(GeminiPart::text(content.clone()));
+                continue;
+            }
+            PromptMessage::User { content } => ("user", vec![GeminiPart::text(content.clone())]),
+            PromptMessage::Assistant { content, tool_calls } => {
+                let mut parts = Vec::new();
+                if !content.is_empty() {
+                    parts.push(GeminiPart::text(content.clone()));
+                }
+                for tc in tool_calls {
+                    parts.push(GeminiPart {
+                        function_call: Some(GeminiFunctionCall {
+                            name: tc.function.name.clone(),
+                            args: serde_json::from_str(&tc.function.arguments)
+                                .unwrap_or_else(|_| serde_json::json!({})),
+                        }),
+                        ..GeminiPart::default()
+                    });
+                }
+                ("model", parts)
+            }
+            PromptMessage::Tool { name, content, .. } => {
+                let response = serde_json::from_str::<Value>(content)
+                    .ok()
+                    .filter(Value::is_object)
+                    .unwrap_or_else(|| serde_json::json!({ "content": content }));
+                let part = GeminiPart {
+                    function_response: Some(GeminiFunctionResponse {
+                        name: name.clone(),
+                        response,
+                    }),
+                    ..GeminiPart::default()
+                };
+                ("user", vec![part])
+            }
+        };
+
+        contents.push(GeminiContent {
+            role: Some(role.to_string()),
+            parts,
+        });
+    }
+
+    let system_instruction = (!system.is_empty()).then_some(GeminiContent {
+        role: None,
+        parts: system,
+    });
+    (contents, system_instruction)
+}
+
+/// Convert the OpenAI tool definitions to Gemini function declarations.
+#[must_use]
+pub fn to_gemini_tools(definitions: &[OpenAiToolDefinition]) -> Vec<GeminiTool> {
+    let function_declarations = definitions
+        .iter()
+        .map(|def| {
+            let mut parameters = def.function.parameters.clone();
+            // Gemini's schema subset rejects `additionalProperties` and empty objects
+            if let Some(obj) = parameters.as_object_mut() {
+                obj.remove("additionalProperties");
+            }
+            let has_properties = parameters
+                .get("properties")
+                .and_then(Value::as_object)
+                .is_some_and(|p| !p.is_empty());
+
+            GeminiFunctionDeclaration {
+                name: def.function.name.clone(),
+                description: def.function.description.clone(),
+                parameters: has_properties.then_some(parameters),
+            }
+        })
+        .collect();
+
+    vec![GeminiTool {
+        function_declarations,
+    }]
+}
+
+/// Convert a Gemini response to the internal decision format, with
+/// the token usage it reports.
+pub fn from_gemini_response(response: &GeminiResponse) -> Result<Value, LlmError> {
+    let mut decision = gemini_decision(response)?;
+    if let Some(usage) = &response.usage_metadata {
+        let usage = TokenUsage::new(usage.prompt_token_count, usage.candidates_token_count);
+        usage.attach(&mut decision);
+    }
+    Ok(decision)
+}
+
+/// Decision of a Gemini response.
+fn gemini_decision(response: &GeminiResponse) -> Result<Value, LlmError> {
+    let candidate = response.candidates.first().ok_or(LlmError::NoResponse)?;
+    let Some(content) = &candidate.content else {
+        return Err(LlmError::ApiError {
+            message: format!(
+                "Response blocked (finish reason: {})",
+                candidate.finish_reason.as_deref().unwrap_or("unknown")
+            ),
+        });
+    };
+
+    if let Some(call) = content.parts.iter().find_map(|p| p.function_call.as_ref()) {
+        return tool_call_decision(&call.name, call.args.clone(), &call.name);
+    }
+
+    let text: String = content.parts.iter().filter_map(|p| p.text.as_deref()).collect();
+    if text.is_empty() {
+        return Err(LlmError::ApiError {
+            message: "Empty model response".to_string(),
+        });
+    }
+
+    Ok(text_decision(&text))
+}
+
+/// Anthropic Messages API request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnthropicRequest {
+    /// Model identifier.
+    pub model: String,
+    /// System prompt, sent separately from the turns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    /// Conversation turns, starting with a user turn.
+    pub messages: Vec<AnthropicMessage>,
+    /// Maximum output tokens; required by the API.
+    pub max_tokens: u32,
+    /// Temperature.
+    pub temperature: f32,
+    /// Tool definitions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<AnthropicTool>,
+    /// Tool the model must call, e.g. `{"type": "tool", "name": "..."}`.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub tool_choice: Value,
+    /// Whether to stream the response as server-sent events.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+}
+
+/// An Anthropic conversation turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicMessage {
+    /// Role (`user` or `assistant`).
+    pub role: String,
+    /// Content blocks.
+    pub content: Vec<AnthropicContent>,
+}
+
+/// A content block of an Anthropic turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicContent {
+    /// Text.
+    Text {
+        /// Text content.
+        text: String,
+    },
+    /// Tool call requested by the model.
+    ToolUse {
+        /// Call ID.
+        id: String,
+        /// Tool name.
+        name: String,
+        /// Arguments as a JSON object.
+        #[serde(default)]
+        input: Value,
+    },
+    /// Result of a tool call, sent in a user turn.
+    ToolResult {
+        /// ID of the call.
+        tool_use_id: String,
+        /// Result text.
+        content: String,
+    },
+    /// Other blocks, such as extended thinking.
+    #[serde(other)]
+    Other,
+}
+
+/// Anthropic tool definition.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicTool {
+    /// Tool name.
+    pub name: String,
+    /// Description.
+    pub description: String,
+    /// Parameters as a JSON Schema object.
+    pub input_schema: Value,
+}
+
+/// Anthropic Messages API response.
+#[derive(Debug, Deserialize)]
+pub struct AnthropicResponse {
+    /// Content blocks.
+    #[serde(default)]
+    pub content: Vec<AnthropicContent>,
+    /// Why generation stopped, e.g. `end_turn`, `tool_use` or `max_tokens`.
+    pub stop_reason: Option<String>,
+    /// Token usage.
+    pub usage: Option<AnthropicUsage>,
+}
+
+/// Anthropic token usage.
+#[derive(Debug, Deserialize)]
+pub struct AnthropicUsage {
+    /// Prompt tokens.
+    #[serde(default)]
+    pub input_tokens: u32,
+    /// Generated tokens.
+    #[serde(default)]
+    pub output_tokens: u32,
+}
+
+/// Event of a streamed Anthropic response.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicStreamEvent {
+    /// A content block starts.
+    ContentBlockStart {
+        /// Position of the block.
+        index: usize,
+        /// The block, without its streamed content.
+        content_block: AnthropicContent,
+    },
+    /// More content of a block.
+    ContentBlockDelta {
+        /// Position of the block.
+        index: usize,
+        /// The content.
+        delta: AnthropicDelta,
+    },
+    /// The message starts; its usage counts the prompt.
+    MessageStart {
+        /// The message, without its content.
+        message: AnthropicStreamMessage,
+    },
+    /// The message ends; its usage counts the output so far.
+    MessageDelta {
+        /// Token usage.
+        #[serde(default)]
+        usage: Option<AnthropicUsage>,
+    },
+    /// Other events, such as `ping`.
+    #[serde(other)]
+    Other,
+}
+
+/// Message of a `message_start` event.
+#[derive(Debug, Deserialize)]
+pub struct AnthropicStreamMessage {
+    /// Token usage.
+    #[serde(default)]
+    pub usage: Option<AnthropicUsage>,
+}
+
+/// More content of a streamed Anthropic content block.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicDelta {
+    /// More text.
+    TextDelta {
+        /// The text.
+        text: String,
+    },
+    /// More of a tool call's input as JSON text.
+    InputJsonDelta {
+        /// The JSON text.
+        partial_json: String,
+    },
+    /// Other deltas, such as extended thinking.
+    #[serde(other)]
+    Other,
+}
+
+/// Convert internal prompt messages to Anthropic turns and a system prompt.
+///
+/// System messages are merged into the system prompt and tool results are
+/// sent in user turns. A tool result is only valid right after the call it
+/// answers; results whose call is not in the conversation, as in agent
+/// contexts that keep results without the calls, are sent as text instead.
+/// Consecutive turns of one role are merged, as the API expects them to
+/// alternate.
+#[must_use]
+pub fn to_anthropic_messages(
+    messages: &[PromptMessage],
+) -> (Vec<AnthropicMessage>, Option<String>) {
+    let mut system = Vec::new();
+    let mut turns: Vec<AnthropicMessage> = Vec::new();
+    let mut call_ids = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message {
+            PromptMessage::System { content } => {
+                system.push(content.clone());
+                continue;
+            }
+            PromptMessage::User { content } => {
+                ("user", vec![AnthropicContent::Text { text: content.clone() }])
+            }
+            PromptMessage::Assistant { content, tool_calls } => {
+                let mut blocks = Vec::new();
+                if !content.is_empty() {
+                    blocks.push(AnthropicContent::Text { text: content.clone() });
+                }
+                for tc in tool_calls {
+                    call_ids.push(tc.id.clone());
+                    blocks.push(AnthropicContent::ToolUse {
+                        id: tc.id.clone(),
+                        name: tc.function.name.clone(),
+                        input: serde_json::from_str(&tc.function.arguments)
+                            .unwrap_or_else(|_| serde_json::json!({})),
+                    });
+                }
+                ("assistant", blocks)
+            }
+            PromptMessage::Tool {
+                tool_call_id,
+                name,
+                content,
+            } => {
+                let block = if call_ids.contains(tool_call_id) {
+                    AnthropicContent::ToolResult {
+                        tool_use_id: tool_call_id.clone(),
+                        content: content.clone(),
+                    }
+                } else {
+                    AnthropicContent::Text {
+                        text: format!("Result of {}:\n{}", name, content),
+                    }
+                };
+                ("user", vec![block])
+            }
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+
+        match turns.last_mut() {
+            Some(last) if last.role == role => last.content.extend(blocks),
+            _ => turns.push(AnthropicMessage {
+                role: role.to_string(),
+                content: blocks,
+            }),
+        }
+    }
+
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    (turns, system)
+}
+
+/// Convert the OpenAI tool definitions to Anthropic tools.
+#[must_use]
+pub fn to_anthropic_tools(definitions: &[OpenAiToolDefinition]) -> Vec<AnthropicTool> {
+    definitions
+        .iter()
+        .map(|def| AnthropicTool {
+            name: def.function.name.clone(),
+            description: def.function.description.clone(),
+            input_schema: def.function.parameters.clone(),
+        })
+        .collect()
+}
+
+/// Convert an Anthropic response to the internal decision format, with
+/// the token usage it reports.
+pub fn from_anthropic_response(response: &AnthropicResponse) -> Result<Value, LlmError> {
+    let mut decision = anthropic_decision(response)?;
+    if let Some(usage) = &response.usage {
+        TokenUsage::new(usage.input_tokens, usage.output_tokens).attach(&mut decision);
+    }
+    Ok(decision)
+}
+
+/// Decision of an Anthropic response.
+fn anthropic_decision(response: &AnthropicResponse) -> Result<Value, LlmError> {
+    let call = response.content.iter().find_map(|block| match block {
+        AnthropicContent::ToolUse { id, name, input } => Some((id, name, input)),
+        _ => None,
+    });
+    if let Some((id, name, input)) = call {
+        return tool_call_decision(name, input.clone(), id);
+    }
+
+    let text = anthropic_text(response);
+    if text.is_empty() {
+        return Err(LlmError::ApiError {
+            message: format!(
+                "Empty model response (stop reason: {})",
+                response.stop_reason.as_deref().unwrap_or("unknown")
+            ),
+        });
+    }
+
+    Ok(text_decision(&text))
+}
+
+/// Parse an event of a streamed Anthropic response into deltas.
+pub(crate) fn anthropic_stream_deltas(data: &str) -> Result<Vec<Delta>, LlmError> {
+    let delta = match parse_stream_event(data)? {
+        AnthropicStreamEvent::ContentBlockStart {
+            index,
+            content_block: AnthropicContent::ToolUse { id, name, .. },
+        } => Delta::ToolCall {
+            index,
+            id: Some(id),
+            name: Some(name),
+            arguments: String::new(),
+        },
+        AnthropicStreamEvent::ContentBlockDelta {
+            delta: AnthropicDelta::TextDelta { text },
+            ..
+        } => Delta::Text(text),
+        AnthropicStreamEvent::ContentBlockDelta {
+            index,
+            delta: AnthropicDelta::InputJsonDelta { partial_json },
+        } => Delta::ToolCall {
+            index,
+            id: None,
+            name: None,
+            arguments: partial_json,
+        },
+        AnthropicStreamEvent::MessageStart {
+            message: AnthropicStreamMessage { usage: Some(usage) },
+        }
+        | AnthropicStreamEvent::MessageDelta { usage: Some(usage) } => {
+            Delta::Usage(TokenUsage::new(usage.input_tokens, usage.output_tokens))
+        }
+        _ => return Ok(Vec::new()),
+    };
+    Ok(vec![delta])
+}
+
+/// Text blocks of an Anthropic response, concatenated.
+#[must_use]
+pub fn anthropic_text(response: &AnthropicResponse) -> String {
+    response
+        .content
+        .iter()
+        .filter_map(|block| match block {
+            AnthropicContent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Ollama `/api/chat` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OllamaChatRequest {
+    /// Model name, optionally with a tag such as `llama3.1:8b`.
+    pub model: String,
+    /// Messages.
+    pub messages: Vec<OllamaMessage>,
+    /// Tool definitions, in the OpenAI format.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<OpenAiToolDefinition>,
+    /// `"json"` or a JSON Schema the response must match.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub format: Value,
+    /// Whether to stream the response as newline-delimited JSON.
+    pub stream: bool,
+    /// Sampling options.
+    pub options: OllamaOptions,
+}
+
+/// An Ollama chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaMessage {
+    /// Role (`system`, `user`, `assistant` or `tool`).
+    pub role: String,
+    /// Content.
+    #[serde(default)]
+    pub content: String,
+    /// Tool calls requested by the model.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<OllamaToolCall>,
+    /// Tool that produced a `tool` message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+}
+
+/// Ollama tool call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaToolCall {
+    /// Function call.
+    pub function: OllamaFunctionCall,
+}
+
+/// Ollama function call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaFunctionCall {
+    /// Function name.
+    pub name: String,
+    /// Arguments as a JSON object.
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+/// Ollama sampling options.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaOptions {
+    /// Temperature.
+    pub temperature: f32,
+    /// Maximum output tokens.
+    pub num_predict: u32,
+    /// Context window in tokens; Ollama's default silently truncates long
+    /// prompts.
+    pub num_ctx: u32,
+    /// Sampling seed for reproducible output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Ollama `/api/chat` response.
+#[derive(Debug, Deserialize)]
+pub struct OllamaChatResponse {
+    /// Generated message.
+    pub message: Option<OllamaMessage>,
+    /// Why generation stopped, e.g. `stop` or `length`.
+    #[serde(default)]
+    pub done_reason: Option<String>,
+    /// Prompt tokens.
+    #[serde(default)]
+    pub prompt_eval_count: u32,
+    /// Generated tokens.
+    #[serde(default)]
+    pub eval_count: u32,
+}
+
+/// Convert internal prompt messages to Ollama messages.
+#[must_use]
+pub fn to_ollama_messages(messages: &[PromptMessage]) -> Vec<OllamaMessage> {
+    messages
+        .iter()
+        .map(|m| {
+            let (role, content) = match m {
+                PromptMessage::System { content } => ("system", content),
+                PromptMessage::User { content } => ("user", content),
+                PromptMessage::Assistant { content, .. } => ("assistant", content),
+                PromptMessage::Tool { content, .. } => ("tool", content),
+            };
+            let tool_calls = match m {
+                PromptMessage::Assistant { tool_calls, .. } => tool_calls
+                    .iter()
+                    .map(|tc| OllamaToolCall {
+                        function: OllamaFunctionCall {
+                            name: tc.function.name.clone(),
+                            arguments: serde_json::from_str(&tc.function.arguments)
+                                .unwrap_or_else(|_| serde_json::json!({})),
+                        },
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            OllamaMessage {
+                role: role.to_string(),
+                content: content.clone(),
+                tool_calls,
+                tool_name: match m {
+                    PromptMessage::Tool { name, .. } => Some(name.clone()),
+                    _ => None,
+                },
+            }
+        })
+        .collect()
+}
+
+/// Convert an Ollama response to the internal decision format, with
+/// the token usage it reports.
+pub fn from_ollama_response(response: &OllamaChatResponse) -> Result<Value, LlmError> {
+    let mut decision = ollama_decision(response)?;
+    let usage = TokenUsage::new(response.prompt_eval_count, response.eval_count);
+    if !usage.is_empty() {
+        usage.attach(&mut decision);
+    }
+    Ok(decision)
+}
+
+/// Decision of an Ollama response.
+fn ollama_decision(response: &OllamaChatResponse) -> Result<Value, LlmError> {
+    let message = response.message.as_ref().ok_or(LlmError::NoResponse)?;
+    if let Some(call) = message.tool_calls.first() {
+        // Ollama does not assign call IDs
+        let function = &call.function;
+        return tool_call_decision(&function.name, function.arguments.clone(), &function.name);
+    }
+    if message.content.trim().is_empty() {
+        return Err(LlmError::ApiError {
+            message: format!(
+                "Empty model response (done reason: {})",
+                response.done_reason.as_deref().unwrap_or("unknown")
+            ),
+        });
+    }
+
+    Ok(text_decision(&message.content))
+}
+
+/// Decision for a native tool call of a provider.
+pub(crate) fn tool_call_decision(name: &str, arguments: Value, call_id: &str) -> Result<Value, LlmError> {
+    let call = ToolCall::new(name, arguments, call_id).map_err(|e| LlmError::ApiError {
+        message: format!("Invalid arguments for tool '{}': {}", name, e),
+    })?;
+    Ok(AgentDecision::ToolCall(call).to_value())
+}
+
+/// Decision for a text response: a JSON decision if the model wrote one,
+/// otherwise the text as the final answer.
+pub(crate) fn text_decision(text: &str) -> Value {
+    serde_json::from_str::<Value>(text)
+        .ok()
+        .and_then(|value| AgentDecision::from_value(&value).ok())
+        .unwrap_or_else(|| AgentDecision::FinalAnswer {
+            answer: text.to_string(),
+        })
+        .to_value()
+}
+
+/// Parse a line of a streamed Ollama response into deltas.
+pub(crate) fn ollama_stream_deltas(line: &str) -> Result<Vec<Delta>, LlmError> {
+    let response: OllamaChatResponse = parse_stream_event(line)?;
+    // The last line counts the tokens
+    let usage = TokenUsage::new(response.prompt_eval_count, response.eval_count);
+    let mut deltas: Vec<Delta> =
+        Some(Delta::Usage(usage)).filter(|_| !usage.is_empty()).into_iter().collect();
+    let Some(message) = response.message else {
+        return Ok(deltas);
+    };
+    if !message.content.is_empty() {
+        deltas.push(Delta::Text(message.content));
+    }
+    // Tool calls arrive whole
+    for (index, call) in message.tool_calls.into_iter().enumerate() {
+        let arguments = &call.function.arguments;
+        deltas.push(Delta::ToolCall {
+            index,
+            id: None,
+            name: Some(call.function.name),
+            arguments: if arguments.is_null() { String::new() } else { arguments.to_string() },
+        });
+    }
+    Ok(deltas)
+}
+
+/// Convert agent context JSON to prompt messages, starting with the system prompt.
+#[must_use]
+pub fn context_to_messages(context: &Value, system_prompt: &SystemPrompt) -> Vec<PromptMessage> {
+    let mut messages = Vec::new();
+
+    // Add system prompt
+    messages.push(PromptMessage::System {
+        content: system_prompt.full(),
+    });
+
+    // Convert context messages
+    if let Some(arr) = context.get("messages").and_then(|m| m.as_array()) {
+        for item in arr {
+            if let (Some(role_str), Some(content)) = (
+                item.get("role").and_then(|r| r.as_str()),
+                item.get("content").and_then(|c| c.as_str()),
+            ) {
+                // The agent context writes roles in upper case
+                let role = match role_str.to_ascii_lowercase().as_str() {
+                    "user" => PromptRole::User,
+                    "assistant" => PromptRole::Assistant,
+                    "tool" => PromptRole::Tool,
+                    "system" => PromptRole::System,
+                    _ => PromptRole::User,
+                };
+
+                match role {
+                    PromptRole::System => {
+                        messages.push(PromptMessage::System {
+                            content: content.to_string(),
+                        });
+                    }
+                    PromptRole::User => {
+                        messages.push(PromptMessage::User {
+                            content: content.to_string(),
+                        });
+                    }
+                    PromptRole::Assistant => {
+                        messages.push(PromptMessage::Assistant {
+                            content: content.to_string(),
+                            tool_calls: Vec::new(),
+                        });
+                    }
+                    PromptRole::Tool => {
+                        messages.push(PromptMessage::Tool {
+                            tool_call_id: item
+                                .get("call_id")
+                                .and_then(|c| c.as_str())
+                                .unwrap_or("default")
+                                .to_string(),
+                            name: item
+                                .get("toolName")
+                                .or_else(|| item.get("tool_name"))
+                                .and_then(|t| t.as_str())
+                                .unwrap_or("unknown")
+                                .to_string(),
+                            content: content.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptBuilder;
+
+    #[test]
+    fn test_to_openai_messages() {
+        let messages = PromptBuilder::new()
+            .system("You are a helpful assistant.")
+            .user("Hello, world!")
+            .build();
+
+        let openai_messages = to_openai_messages(&messages);
+        assert_eq!(openai_messages.len(), 2);
+
+        if let OpenAiMessage::System { content } = &openai_messages[0] {
+            assert!(content.contains("helpful"));
+        }
+
+        // A result without a matching tool call becomes user text
+        let messages = PromptBuilder::new()
+            .user("How many users?")
+            .tool_result("default", "execute_query", "[{\"count\": 3}]")
+            .build();
+        let openai_messages = to_openai_messages(&messages);
+        assert!(matches!(
+            &openai_messages[1],
+            OpenAiMessage::User { content } if content.starts_with("Result of execute_query:")
+        ));
+    }
+
+    #[test]
+    fn test_from_openai_response() {
+        let response = OpenAiChatResponse {
+            id: "test-123".to_string(),
+            object: "chat.completion".to_string(),
+            created: 1234567890,
+            model: "gpt-4o".to_string(),
+            choices: vec![OpenAiChoice {
+                index: 0,
+                message: OpenAiMessage::Assistant {
+                    content: Some("Hello!".to_string()),
+                    tool_calls: Vec::new(),
+                },
+                finish_reason: Some("stop".to_string()),
+            }],
+            usage: None,
+        };
+
+        let decision = from_openai_response(&response);
+        assert!(decision.is_ok());
+        let value = decision.unwrap();
+        assert_eq!(value["type"], "final_answer");
+
+        // Stringified arguments arrive as an object
+        let mut response = response;
+        response.choices[0].message = OpenAiMessage::Assistant {
+            content: None,
+            tool_calls: vec![OpenAiToolCall {
+                id: "call-1".to_string(),
+                r#type: "function".to_string(),
+                function: OpenAiFunctionCall {
+                    name: "describe_table".to_string(),
+                    arguments: "{\"tableName\": \"orders\"}".to_string(),
+                },
+            }],
+        };
+        let value = from_openai_response(&response).unwrap();
+        assert_eq!(value["arguments"]["tableName"], "orders");
+        assert_eq!(value["call_id"], "call-1");
+    }
 
     #[test]
     fn test_create_tool_definitions() {
         let tools = create_tool_definitions();
-        assert_eq!(tools.len(), 5);
+        assert_eq!(tools.len(), 13);
         assert!(tools.iter().any(|t| t.function.name == "execute_query"));
     }
+
+    #[test]
+    fn test_tool_definitions_match_args() {
+        use postgres_agent_tools::built_in::{
+            admin::{KillQueryToolArgs, RefreshMatviewToolArgs},
+            anomaly::AnomalyToolArgs,
+            chart::ChartToolArgs,
+            compare::CompareToolArgs,
+            export::ExportToolArgs,
+            DependenciesToolArgs, DescribeTableToolArgs, ExplainToolArgs, ListTablesToolArgs,
+            QueryToolArgs, SchemaToolArgs, TraceColumnToolArgs,
+        };
+        use serde::de::DeserializeOwned;
+
+        fn parses<T: DeserializeOwned>(args: Value) -> Result<(), String> {
+            serde_json::from_value::<T>(args).map(drop).map_err(|e| e.to_string())
+        }
+        /// A value of the type a property schema asks for.
+        fn sample(schema: &Value) -> Value {
+            if let Some(first) = schema["enum"].get(0) {
+                return first.clone();
+            }
+            match schema["type"].as_str() {
+                Some("integer") | Some("number") => serde_json::json!(1),
+                Some("boolean") => serde_json::json!(true),
+                Some("array") => serde_json::json!([sample(&schema["items"])]),
+                _ => serde_json::json!("x"),
+            }
+        }
+
+        for tool in create_tool_definitions() {
+            let parameters = &tool.function.parameters;
+            let properties = parameters["properties"].as_object().cloned().unwrap_or_default();
+            let required: Vec<&str> = parameters["required"]
+                .as_array()
+                .map(|keys| keys.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let only_required: serde_json::Map<String, Value> = required
+                .iter()
+                .map(|key| (key.to_string(), sample(&properties[*key])))
+                .collect();
+            let all: serde_json::Map<String, Value> =
+                properties.iter().map(|(key, schema)| (key.clone(), sample(schema))).collect();
+
+            for args in [Value::Object(only_required), Value::Object(all)] {
+                let parsed = match tool.function.name.as_str() {
+                    "execute_query" => parses::<QueryToolArgs>(args),
+                    "get_schema" => parses::<SchemaToolArgs>(args),
+                    "list_tables" => parses::<ListTablesToolArgs>(args),
+                    "describe_table" => parses::<DescribeTableToolArgs>(args),
+                    "explain_query" => parses::<ExplainToolArgs>(args),
+                    "get_table_dependencies" => parses::<DependenciesToolArgs>(args),
+                    "trace_column" => parses::<TraceColumnToolArgs>(args),
+                    "detect_anomalies" => parses::<AnomalyToolArgs>(args),
+                    "compare_profiles" => parses::<CompareToolArgs>(args),
+                    "kill_query" => parses::<KillQueryToolArgs>(args),
+                    "refresh_matview" => parses::<RefreshMatviewToolArgs>(args),
+                    "export_result" => parses::<ExportToolArgs>(args),
+                    "suggest_chart" => parses::<ChartToolArgs>(args),
+                    other => panic!("No arguments type for tool {}", other),
+                };
+                assert!(parsed.is_ok(), "{}: {:?}", tool.function.name, parsed);
+            }
+            // Arguments are camelCase; unknown optional keys would be ignored
+            for key in properties.keys() {
+                assert!(!key.contains('_'), "{} advertises {}", tool.function.name, key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_context_to_messages() {
+        let context = serde_json::json!({
+            "messages": [
+                {"role": "user", "content": "Hello"},
+                {"role": "assistant", "content": "Hi there!"}
+            ]
+        });
+        let prompt = SystemPrompt::default();
+        let messages = context_to_messages(&context, &prompt);
+
+        assert_eq!(messages.len(), 3); // System + User + Assistant
+
+        let context = serde_json::json!({
+            "messages": [
+                {"role": "TOOL", "content": "{}", "toolName": "list_tables"}
+            ]
+        });
+        let messages = context_to_messages(&context, &prompt);
+        assert!(matches!(&messages[1], PromptMessage::Tool { name, .. } if name == "list_tables"));
+    }
+
+    #[test]
+    fn test_to_gemini_contents() {
+        let messages = PromptBuilder::new()
+            .system("Be precise.")
+            .user("How many users?")
+            .tool_result("call-1", "execute_query", "[{\"count\": 3}]")
+            .build();
+
+        let (contents, system) = to_gemini_contents(&messages);
+        assert_eq!(system.unwrap().parts[0].text.as_deref(), Some("Be precise."));
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[0].role.as_deref(), Some("user"));
+
+        // Non-object tool output is wrapped so the response is always an object
+        let response = contents[1].parts[0].function_response.as_ref().unwrap();
+        assert_eq!(response.name, "execute_query");
+        assert!(response.response.get("content").is_some());
+    }
+
+    #[test]
+    fn test_to_gemini_tools() {
+        let tools = to_gemini_tools(&create_tool_definitions());
+        let declarations = &tools[0].function_declarations;
+        assert_eq!(declarations.len(), 13);
+
+        let list_tables = declarations.iter().find(|d| d.name == "list_tables").unwrap();
+        assert!(list_tables.parameters.is_none());
+    }
+
+    #[test]
+    fn test_from_gemini_response() {
+        let response: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{
+                "content": {
+                    "role": "model",
+                    "parts": [{"functionCall": {"name": "execute_query", "args": {"sql": "SELECT 1"}}}]
+                },
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+        let decision = from_gemini_response(&response).unwrap();
+        assert_eq!(decision["type"], "tool_call");
+        assert_eq!(decision["name"], "execute_query");
+        assert_eq!(decision["arguments"]["sql"], "SELECT 1");
+
+        let blocked: GeminiResponse = serde_json::from_value(serde_json::json!({
+            "candidates": [{"finishReason": "SAFETY"}]
+        }))
+        .unwrap();
+        assert!(from_gemini_response(&blocked).is_err());
+    }
+
+    #[test]
+    fn test_to_anthropic_messages() {
+        let messages = vec![
+            PromptMessage::System { content: "Be precise.".to_string() },
+            PromptMessage::User { content: "How many users?".to_string() },
+            PromptMessage::Assistant {
+                content: String::new(),
+                tool_calls: vec![PromptToolCall {
+                    id: "toolu_1".to_string(),
+                    r#type: "function".to_string(),
+                    function: PromptToolCallFunction {
+                        name: "execute_query".to_string(),
+                        arguments: "{\"sql\": \"SELECT count(*) FROM users\"}".to_string(),
+                    },
+                }],
+            },
+            PromptMessage::Tool {
+                tool_call_id: "toolu_1".to_string(),
+                name: "execute_query".to_string(),
+                content: "[{\"count\": 3}]".to_string(),
+            },
+            // A result without its call becomes text in the same user turn
+            PromptMessage::Tool {
+                tool_call_id: "default".to_string(),
+                name: "list_tables".to_string(),
+                content: "{\"tables\": []}".to_string(),
+            },
+        ];
+
+        let (turns, system) = to_anthropic_messages(&messages);
+        assert_eq!(system.as_deref(), Some("Be precise."));
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[1].role, "assistant");
+        assert!(matches!(
+            &turns[1].content[0],
+            AnthropicContent::ToolUse { input, .. } if input["sql"] == "SELECT count(*) FROM users"
+        ));
+        assert_eq!(turns[2].role, "user");
+        assert!(matches!(&turns[2].content[0], AnthropicContent::ToolResult { .. }));
+        assert!(matches!(&turns[2].content[1], AnthropicContent::Text { .. }));
+    }
+
+    #[test]
+    fn test_from_anthropic_response() {
+        let response: AnthropicResponse = serde_json::from_value(serde_json::json!({
+            "content": [
+                {"type": "text", "text": "Let me check."},
+                {"type": "tool_use", "id": "toolu_1", "name": "describe_table",
+                 "input": {"tableName": "orders"}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        }))
+        .unwrap();
+        let mut decision = from_anthropic_response(&response).unwrap();
+        assert_eq!(decision["type"], "tool_call");
+        assert_eq!(decision["arguments"]["tableName"], "orders");
+        assert_eq!(decision["call_id"], "toolu_1");
+        assert_eq!(TokenUsage::take(&mut decision), Some(TokenUsage::new(10u32, 5u32)));
+
+        let response: AnthropicResponse = serde_json::from_value(serde_json::json!({
+            "content": [
+                {"type": "thinking", "thinking": "..."},
+                {"type": "text", "text": "3 users."}
+            ],
+            "stop_reason": "end_turn"
+        }))
+        .unwrap();
+        assert_eq!(from_anthropic_response(&response).unwrap()["answer"], "3 users.");
+
+        let empty: AnthropicResponse =
+            serde_json::from_value(serde_json::json!({"content": [], "stop_reason": "max_tokens"}))
+                .unwrap();
+        assert!(from_anthropic_response(&empty).is_err());
+    }
+
+    #[test]
+    fn test_ollama_messages_and_response() {
+        let messages = PromptBuilder::new()
+            .system("Be precise.")
+            .user("How many users?")
+            .tool_result("call-1", "execute_query", "[{\"count\": 3}]")
+            .build();
+        let ollama = to_ollama_messages(&messages);
+        assert_eq!(ollama.len(), 3);
+        assert_eq!(ollama[2].role, "tool");
+        assert_eq!(ollama[2].tool_name.as_deref(), Some("execute_query"));
+
+        let response: OllamaChatResponse = serde_json::from_value(serde_json::json!({
+            "model": "llama3.1",
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "execute_query",
+                                             "arguments": {"sql": "SELECT 1"}}}]
+            },
+            "done": true,
+            "done_reason": "stop"
+        }))
+        .unwrap();
+        let decision = from_ollama_response(&response).unwrap();
+        assert_eq!(decision["name"], "execute_query");
+        assert_eq!(decision["arguments"]["sql"], "SELECT 1");
+
+        // Models without tools answer with the JSON decision format
+        let response: OllamaChatResponse = serde_json::from_value(serde_json::json!({
+            "message": {"role": "assistant",
+                        "content": "{\"type\": \"final_answer\", \"answer\": \"3\"}"},
+            "done": true
+        }))
+        .unwrap();
+        assert_eq!(from_ollama_response(&response).unwrap()["answer"], "3");
+    }
+
+    #[test]
+    fn test_stream_deltas() {
+        let chunk = r#"{"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0,
+            "id": "call_1", "function": {"name": "execute_query", "arguments": "{\"sq"}}]}}]}"#;
+        assert_eq!(
+            openai_stream_deltas(chunk).unwrap(),
+            [Delta::ToolCall {
+                index: 0,
+                id: Some("call_1".to_string()),
+                name: Some("execute_query".to_string()),
+                arguments: "{\"sq".to_string(),
+            }]
+        );
+        let chunk = r#"{"choices": [{"index": 0, "delta": {"content": "Hi", "tool_calls": null}}]}"#;
+        assert_eq!(openai_stream_deltas(chunk).unwrap(), [Delta::Text("Hi".to_string())]);
+        let error = r#"{"error": {"message": "Rate limit reached"}}"#;
+        assert!(openai_stream_deltas(error).unwrap_err().to_string().contains("Rate limit"));
+
+        let event = r#"{"type": "content_block_delta", "index": 1,
+            "delta": {"type": "input_json_delta", "partial_json": "{}"}}"#;
+        assert!(matches!(
+            anthropic_stream_deltas(event).unwrap().as_slice(),
+            [Delta::ToolCall { index: 1, arguments, .. }] if arguments == "{}"
+        ));
+        assert!(anthropic_stream_deltas(r#"{"type": "ping"}"#).unwrap().is_empty());
+
+        let line = r#"{"message": {"role": "assistant", "content": "3 users"}, "done": false}"#;
+        assert_eq!(ollama_stream_deltas(line).unwrap(), [Delta::Text("3 users".to_string())]);
+        assert!(ollama_stream_deltas(r#"{"error": "model not found"}"#).is_err());
+    }
 }
@@ -0,0 +1,221 @@
+//! Agent decision types.
+//!
+//! Providers convert model responses into an [`AgentDecision`], and the agent
+//! parses the same type, so both sides agree on one wire format:
+//! `{"type": "tool_call", "name": ..., "arguments": {...}, "call_id": ...}`.
+//! Providers that report token usage add it under a `usage` key, which
+//! parsing ignores; see [`TokenUsage::take`].
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+/// A decision made by the agent after reasoning.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentDecision {
+    /// Continue reasoning, no tool call needed.
+    Reasoning {
+        /// The reasoning trace.
+        thought: String,
+    },
+    /// Execute a tool call.
+    ToolCall(ToolCall),
+    /// Provide final answer to user.
+    FinalAnswer {
+        /// The answer.
+        answer: String,
+    },
+}
+
+impl AgentDecision {
+    /// Parse a decision from its JSON form.
+    ///
+    /// # Errors
+    /// Returns an error if the value is not a valid decision.
+    pub fn from_value(value: &Value) -> Result<Self, serde_json::Error> {
+        Self::deserialize(value)
+    }
+
+    /// Convert the decision to its JSON form.
+    #[must_use]
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+/// A tool call made by the agent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Tool name.
+    pub name: String,
+    /// Tool arguments as a JSON object.
+    ///
+    /// Providers that send arguments as a JSON string, such as OpenAI, are
+    /// parsed into the object; missing or null arguments become `{}`.
+    #[serde(default = "empty_arguments", deserialize_with = "deserialize_arguments")]
+    pub arguments: Value,
+    /// Call ID for tracking.
+    #[serde(default = "default_call_id", alias = "callId")]
+    pub call_id: String,
+}
+
+impl ToolCall {
+    /// Create a tool call, parsing arguments sent as stringified JSON.
+    ///
+    /// # Errors
+    /// Returns an error if string arguments are not valid JSON.
+    pub fn new(
+        name: impl Into<String>,
+        arguments: Value,
+        call_id: impl Into<String>,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            name: name.into(),
+            arguments: deserialize_arguments(arguments)?,
+            call_id: call_id.into(),
+        })
+    }
+}
+
+/// Tokens a provider reported for one response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    /// Tokens of the prompt.
+    pub prompt_tokens: u64,
+    /// Tokens generated.
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Key of the usage in the JSON form of a decision.
+    const KEY: &'static str = "usage";
+
+    /// Usage of a response.
+    #[must_use]
+    pub fn new(prompt_tokens: impl Into<u64>, completion_tokens: impl Into<u64>) -> Self {
+        Self {
+            prompt_tokens: prompt_tokens.into(),
+            completion_tokens: completion_tokens.into(),
+        }
+    }
+
+    /// Tokens in total.
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    /// Whether nothing was reported.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// Add the usage to the JSON form of a decision.
+    pub fn attach(self, decision: &mut Value) {
+        if let Some(object) = decision.as_object_mut() {
+            object.insert(Self::KEY.to_string(), serde_json::to_value(self).unwrap_or_default());
+        }
+    }
+
+    /// Remove the usage from the JSON form of a decision, if it has one.
+    pub fn take(decision: &mut Value) -> Option<Self> {
+        let usage = decision.as_object_mut()?.remove(Self::KEY)?;
+        serde_json::from_value(usage).ok()
+    }
+}
+
+impl std::ops::AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// Arguments of a call without any.
+fn empty_arguments() -> Value {
+    Value::Object(serde_json::Map::new())
+}
+
+/// Call ID of a call the model did not name.
+fn default_call_id() -> String {
+    "default".to_string()
+}
+
+/// Accept tool arguments as an object or as stringified JSON.
+fn deserialize_arguments<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(empty_arguments()),
+        Value::String(text) if text.trim().is_empty() => Ok(empty_arguments()),
+        Value::String(text) => serde_json::from_str(&text).map_err(|e| {
+            serde::de::Error::custom(format!("arguments are not valid JSON: {}", e))
+        }),
+        value => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision_round_trip() {
+        let decisions = [
+            AgentDecision::Reasoning {
+                thought: "Check the schema first".to_string(),
+            },
+            AgentDecision::ToolCall(ToolCall {
+                name: "execute_query".to_string(),
+                arguments: serde_json::json!({"sql": "SELECT 1"}),
+                call_id: "call-1".to_string(),
+            }),
+            AgentDecision::FinalAnswer {
+                answer: "There are 3 users.".to_string(),
+            },
+        ];
+        for decision in decisions {
+            let value = decision.to_value();
+            assert_eq!(AgentDecision::from_value(&value).unwrap(), decision);
+        }
+    }
+
+    #[test]
+    fn test_token_usage() {
+        let answer = AgentDecision::FinalAnswer {
+            answer: "3".to_string(),
+        };
+        let mut value = answer.to_value();
+        TokenUsage::new(120u32, 8u32).attach(&mut value);
+        assert_eq!(value["usage"]["prompt_tokens"], 120);
+        assert_eq!(AgentDecision::from_value(&value).unwrap(), answer);
+
+        assert_eq!(TokenUsage::take(&mut value), Some(TokenUsage::new(120u32, 8u32)));
+        assert_eq!(value, answer.to_value());
+        assert_eq!(TokenUsage::take(&mut value), None);
+    }
+
+    #[test]
+    fn test_tool_call_arguments() {
+        let stringified = serde_json::json!({
+            "type": "tool_call",
+            "name": "describe_table",
+            "arguments": "{\"tableName\": \"orders\"}",
+            "call_id": "call-2"
+        });
+        let AgentDecision::ToolCall(call) = AgentDecision::from_value(&stringified).unwrap() else {
+            panic!("expected a tool call");
+        };
+        assert_eq!(call.arguments, serde_json::json!({"tableName": "orders"}));
+
+        let bare = serde_json::json!({"type": "tool_call", "name": "list_tables"});
+        let AgentDecision::ToolCall(call) = AgentDecision::from_value(&bare).unwrap() else {
+            panic!("expected a tool call");
+        };
+        assert_eq!(call.arguments, serde_json::json!({}));
+        assert_eq!(call.call_id, "default");
+
+        let invalid = serde_json::json!({"type": "tool_call", "name": "x", "arguments": "{"});
+        assert!(AgentDecision::from_value(&invalid).is_err());
+        assert!(AgentDecision::from_value(&serde_json::json!({"type": "unknown"})).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Runtime provider selection.
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::anthropic::AnthropicProvider;
+use super::azure::AzureOpenAiProvider;
+use super::client::LlmClient;
+use super::conversion::OpenAiToolDefinition;
+use super::error::LlmError;
+use super::gemini::GeminiProvider;
+use super::ollama::OllamaProvider;
+use super::openai::OpenAiProvider;
+use super::prompt::SystemPrompt;
+use super::provider::{ProviderBase, ProviderConfig, ProviderInfo};
+use super::stream::{DecisionStream, TokenStream};
+
+/// An LLM provider chosen from configuration at runtime.
+#[derive(Debug, Clone)]
+pub enum AnyProvider {
+    /// OpenAI or an OpenAI-compatible endpoint.
+    OpenAi(OpenAiProvider),
+    /// Azure OpenAI deployment.
+    AzureOpenAi(AzureOpenAiProvider),
+    /// Google Gemini.
+    Gemini(GeminiProvider),
+    /// Anthropic Claude.
+    Anthropic(AnthropicProvider),
+    /// Local models served by Ollama.
+    Ollama(OllamaProvider),
+}
+
+impl AnyProvider {
+    /// Create the provider named by `config.provider_type`.
+    ///
+    /// # Errors
+    /// Returns an error for unknown provider names or incomplete provider settings.
+    pub fn from_config(config: ProviderConfig) -> Result<Self, LlmError> {
+        match config.provider_type.as_str() {
+            "openai" => Ok(Self::OpenAi(OpenAiProvider::new(config))),
+            "azure-openai" => Ok(Self::AzureOpenAi(AzureOpenAiProvider::new(config)?)),
+            "gemini" | "google" => Ok(Self::Gemini(GeminiProvider::new(config))),
+            "anthropic" | "claude" => Ok(Self::Anthropic(AnthropicProvider::new(config))),
+            "ollama" => Ok(Self::Ollama(OllamaProvider::new(config))),
+            other => Err(LlmError::ApiError {
+                message: format!("Unknown LLM provider '{}'", other),
+            }),
+        }
+    }
+
+    /// State shared by every provider.
+    fn base(&self) -> &ProviderBase {
+        match self {
+            Self::OpenAi(p) => &p.base,
+            Self::AzureOpenAi(p) => &p.base,
+            Self::Gemini(p) => &p.base,
+            Self::Anthropic(p) => &p.base,
+            Self::Ollama(p) => &p.base,
+        }
+    }
+
+    /// Mutable access to the state shared by every provider.
+    fn base_mut(&mut self) -> &mut ProviderBase {
+        match self {
+            Self::OpenAi(p) => &mut p.base,
+            Self::AzureOpenAi(p) => &mut p.base,
+            Self::Gemini(p) => &mut p.base,
+            Self::Anthropic(p) => &mut p.base,
+            Self::Ollama(p) => &mut p.base,
+        }
+    }
+
+    /// Switch the model used for subsequent requests.
+    pub fn set_model(&mut self, model: impl Into<String>) {
+        self.base_mut().set_model(model);
+    }
+
+    /// Replace the system prompt used for subsequent requests.
+    pub fn set_system_prompt(&mut self, prompt: SystemPrompt) {
+        self.base_mut().set_system_prompt(prompt);
+    }
+
+    /// Replace the tools offered to the model, e.g. with those enabled in
+    /// the tool registry.
+    pub fn set_tools(&mut self, tools: Vec<OpenAiToolDefinition>) {
+        self.base_mut().set_tools(tools);
+    }
+
+    /// List the model IDs available to the configured API key.
+    ///
+    /// # Errors
+    /// Returns an error if the endpoint is unreachable or rejects the key.
+    pub async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        self.base().list_models().await
+    }
+}
+
+#[async_trait]
+impl LlmClient for AnyProvider {
+    async fn complete(&self, prompt: &str) -> Result<String, LlmError> {
+        match self {
+            Self::OpenAi(p) => p.complete(prompt).await,
+            Self::AzureOpenAi(p) => p.complete(prompt).await,
+            Self::Gemini(p) => p.complete(prompt).await,
+            Self::Anthropic(p) => p.complete(prompt).await,
+            Self::Ollama(p) => p.complete(prompt).await,
+        }
+    }
+
+    async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+        match self {
+            Self::OpenAi(p) => p.generate_decision(context_json).await,
+            Self::AzureOpenAi(p) => p.generate_decision(context_json).await,
+            Self::Gemini(p) => p.generate_decision(context_json).await,
+            Self::Anthropic(p) => p.generate_decision(context_json).await,
+            Self::Ollama(p) => p.generate_decision(context_json).await,
+        }
+    }
+
+    async fn complete_stream(&self, prompt: &str) -> Result<TokenStream, LlmError> {
+        match self {
+            Self::OpenAi(p) => p.complete_stream(prompt).await,
+            Self::AzureOpenAi(p) => p.complete_stream(prompt).await,
+            Self::Gemini(p) => p.complete_stream(prompt).await,
+            Self::Anthropic(p) => p.complete_stream(prompt).await,
+            Self::Ollama(p) => p.complete_stream(prompt).await,
+        }
+    }
+
+    async fn generate_decision_stream(
+        &self,
+        context_json: &Value,
+    ) -> Result<DecisionStream, LlmError> {
+        match self {
+            Self::OpenAi(p) => p.generate_decision_stream(context_json).await,
+            Self::AzureOpenAi(p) => p.generate_decision_stream(context_json).await,
+            Self::Gemini(p) => p.generate_decision_stream(context_json).await,
+            Self::Anthropic(p) => p.generate_decision_stream(context_json).await,
+            Self::Ollama(p) => p.generate_decision_stream(context_json).await,
+        }
+    }
+
+    async fn generate_structured(&self, prompt: &str, schema: &Value) -> Result<Value, LlmError> {
+        match self {
+            Self::OpenAi(p) => p.generate_structured(prompt, schema).await,
+            Self::AzureOpenAi(p) => p.generate_structured(prompt, schema).await,
+            Self::Gemini(p) => p.generate_structured(prompt, schema).await,
+            Self::Anthropic(p) => p.generate_structured(prompt, schema).await,
+            Self::Ollama(p) => p.generate_structured(prompt, schema).await,
+        }
+    }
+
+    fn provider_info(&self) -> ProviderInfo {
+        match self {
+            Self::OpenAi(p) => p.provider_info(),
+            Self::AzureOpenAi(p) => p.provider_info(),
+            Self::Gemini(p) => p.provider_info(),
+            Self::Anthropic(p) => p.provider_info(),
+            Self::Ollama(p) => p.provider_info(),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! Text embedding clients.
+//!
+//! Embeddings back semantic schema search, example retrieval and pgvector
+//! integration. Inputs are sent in batches and transient failures are
+//! retried with exponential backoff.
+
+use async_trait::async_trait;
+use postgres_agent_util::crypto::Secret;
+use serde_json::Value;
+use std::time::Duration;
+use url::Url;
+
+use super::error::LlmError;
+use super::provider::resolve_endpoint;
+
+/// Default OpenAI API base URL.
+const OPENAI_BASE_URL: &str = "https://api.openai.com/v1/";
+
+/// Default Ollama server URL.
+const OLLAMA_BASE_URL: &str = "http://localhost:11434/";
+
+/// Delay before the first retry; doubled on each further attempt.
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Known embedding models and their output dimensions.
+const MODEL_DIMENSIONS: &[(&str, usize)] = &[
+    ("text-embedding-3-small", 1536),
+    ("text-embedding-3-large", 3072),
+    ("text-embedding-ada-002", 1536),
+    ("nomic-embed-text", 768),
+    ("mxbai-embed-large", 1024),
+    ("all-minilm", 384),
+];
+
+/// Trait for embedding model clients.
+#[async_trait]
+pub trait EmbeddingsClient: Send + Sync {
+    /// Embed a list of inputs, returning one vector per input in order.
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError>;
+
+    /// Embed a single input.
+    async fn embed_one(&self, input: &str) -> Result<Vec<f32>, LlmError> {
+        self.embed(&[input.to_string()])
+            .await?
+            .pop()
+            .ok_or(LlmError::NoResponse)
+    }
+
+    /// Embedding model identifier.
+    fn model(&self) -> &str;
+
+    /// Vector dimensions, if known.
+    fn dimensions(&self) -> Option<usize>;
+}
+
+/// Embedding client configuration.
+#[derive(Debug, Clone)]
+pub struct EmbeddingsConfig {
+    /// Model identifier.
+    pub model: String,
+    /// API base URL (provider default when unset).
+    pub base_url: Option<Url>,
+    /// API key.
+    pub api_key: Option<Secret<String>>,
+    /// Maximum inputs per request.
+    pub batch_size: usize,
+    /// Retries after a transient failure.
+    pub max_retries: u32,
+    /// Requested output dimensions (OpenAI `text-embedding-3-*` only).
+    pub dimensions: Option<usize>,
+}
+
+impl EmbeddingsConfig {
+    /// Create a configuration for the given model with default limits.
+    #[must_use]
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            base_url: None,
+            api_key: None,
+            batch_size: 64,
+            max_retries: 3,
+            dimensions: None,
+        }
+    }
+
+    /// Output dimensions: the requested size, else the model's known size.
+    #[must_use]
+    pub fn effective_dimensions(&self) -> Option<usize> {
+        self.dimensions.or_else(|| known_dimensions(&self.model))
+    }
+}
+
+/// Look up the output dimensions of a known embedding model.
+#[must_use]
+pub fn known_dimensions(model: &str) -> Option<usize> {
+    // Ollama models may carry a tag, e.g. `nomic-embed-text:latest`
+    let name = model.split(':').next().unwrap_or(model);
+    MODEL_DIMENSIONS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, dims)| *dims)
+}
+
+/// OpenAI embeddings client.
+#[derive(Debug, Clone)]
+pub struct OpenAiEmbeddings {
+    /// Client configuration.
+    config: EmbeddingsConfig,
+    /// HTTP client.
+    http: reqwest::Client,
+}
+
+impl OpenAiEmbeddings {
+    /// Create a new OpenAI embeddings client.
+    #[must_use]
+    pub fn new(config: EmbeddingsConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Embed one batch.
+    async fn embed_batch(&self, batch: Vec<String>) -> Result<Vec<Vec<f32>>, LlmError> {
+        let url = resolve_endpoint(self.config.base_url.as_ref(), OPENAI_BASE_URL, "embeddings")?;
+        let mut body = serde_json::json!({
+            "model": self.config.model,
+            "input": batch,
+        });
+        if let Some(dimensions) = self.config.dimensions {
+            body["dimensions"] = dimensions.into();
+        }
+
+        let mut request = self.http.post(url).json(&body);
+        if let Some(key) = &self.config.api_key {
+            request = request.bearer_auth(key.expose());
+        }
+        let response = send_json(request).await?;
+
+        // Results carry an index; sort rather than trusting response order
+        let mut data: Vec<(u64, Vec<f32>)> = response
+            .get("data")
+            .and_then(Value::as_array)
+            .ok_or(LlmError::NoResponse)?
+            .iter()
+            .map(|item| {
+                let index = item.get("index").and_then(Value::as_u64).unwrap_or_default();
+                (index, parse_vector(item.get("embedding")))
+            })
+            .collect();
+        data.sort_by_key(|(index, _)| *index);
+
+        Ok(data.into_iter().map(|(_, vector)| vector).collect())
+    }
+}
+
+#[async_trait]
+impl EmbeddingsClient for OpenAiEmbeddings {
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        embed_in_batches(&self.config, inputs, |batch| self.embed_batch(batch)).await
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.config.effective_dimensions()
+    }
+}
+
+/// Ollama embeddings client.
+#[derive(Debug, Clone)]
+pub struct OllamaEmbeddings {
+    /// Client configuration.
+    config: EmbeddingsConfig,
+    /// HTTP client.
+    http: reqwest::Client,
+}
+
+impl OllamaEmbeddings {
+    /// Create a new Ollama embeddings client.
+    #[must_use]
+    pub fn new(config: EmbeddingsConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Embed one batch.
+    async fn embed_batch(&self, batch: Vec<String>) -> Result<Vec<Vec<f32>>, LlmError> {
+        let url = resolve_endpoint(self.config.base_url.as_ref(), OLLAMA_BASE_URL, "api/embed")?;
+        let body = serde_json::json!({
+            "model": self.config.model,
+            "input": batch,
+        });
+        let response = send_json(self.http.post(url).json(&body)).await?;
+
+        Ok(response
+            .get("embeddings")
+            .and_then(Value::as_array)
+            .ok_or(LlmError::NoResponse)?
+            .iter()
+            .map(|vector| parse_vector(Some(vector)))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl EmbeddingsClient for OllamaEmbeddings {
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        embed_in_batches(&self.config, inputs, |batch| self.embed_batch(batch)).await
+    }
+
+    fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.config.effective_dimensions()
+    }
+}
+
+/// Split inputs into batches, embed each with retries, and check dimensions.
+async fn embed_in_batches<F, Fut>(
+    config: &EmbeddingsConfig,
+    inputs: &[String],
+    embed_batch: F,
+) -> Result<Vec<Vec<f32>>, LlmError>
+where
+    F: Fn(Vec<String>) -> Fut,
+    Fut: std::future::Future<Output = Result<Vec<Vec<f32>>, LlmError>>,
+{
+    let mut vectors = Vec::with_capacity(inputs.len());
+
+    for batch in inputs.chunks(config.batch_size.max(1)) {
+        let mut attempt = 0;
+        let embedded = loop {
+            match embed_batch(batch.to_vec()).await {
+                Err(e) if e.is_retryable() && attempt < config.max_retries => {
+                    let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+                    tracing::warn!("Embedding request failed ({}), retrying in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+
+        if embedded.len() != batch.len() {
+            return Err(LlmError::ApiError {
+                message: format!("Expected {} embeddings, got {}", batch.len(), embedded.len()),
+            });
+        }
+        vectors.extend(embedded);
+    }
+
+    if let Some(expected) = config.effective_dimensions()
+        && let Some(vector) = vectors.iter().find(|v| v.len() != expected)
+    {
+        return Err(LlmError::ApiError {
+            message: format!(
+                "Model {} returned {} dimensions, expected {}",
+                config.model,
+                vector.len(),
+                expected
+            ),
+        });
+    }
+
+    Ok(vectors)
+}
+
+/// Send a request and parse the JSON body, mapping HTTP failures.
+async fn send_json(request: reqwest::RequestBuilder) -> Result<Value, LlmError> {
+    let response = request.send().await.map_err(|e| LlmError::ConnectionFailed {
+        message: e.to_string(),
+    })?;
+    if !response.status().is_success() {
+        return Err(LlmError::from_status(response.status()));
+    }
+    response.json().await.map_err(|e| LlmError::ApiError {
+        message: format!("Invalid embeddings response: {}", e),
+    })
+}
+
+/// Parse a JSON array of numbers into a vector.
+fn parse_vector(value: Option<&Value>) -> Vec<f32> {
+    value
+        .and_then(Value::as_array)
+        .map(|values| values.iter().filter_map(Value::as_f64).map(|v| v as f32).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_known_dimensions() {
+        assert_eq!(known_dimensions("text-embedding-3-small"), Some(1536));
+        assert_eq!(known_dimensions("nomic-embed-text:latest"), Some(768));
+        assert_eq!(known_dimensions("unknown-model"), None);
+
+        let mut config = EmbeddingsConfig::new("text-embedding-3-large");
+        assert_eq!(config.effective_dimensions(), Some(3072));
+        config.dimensions = Some(256);
+        assert_eq!(config.effective_dimensions(), Some(256));
+    }
+
+    #[tokio::test]
+    async fn test_batching_and_retry() {
+        let mut config = EmbeddingsConfig::new("custom");
+        config.batch_size = 2;
+        config.max_retries = 1;
+
+        let calls = AtomicU32::new(0);
+        let inputs: Vec<String> = ["a", "b", "c"].iter().map(ToString::to_string).collect();
+
+        let vectors = embed_in_batches(&config, &inputs, |batch| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            let len = batch.len();
+            async move {
+                // The first request fails transiently and is retried
+                if call == 0 {
+                    return Err(LlmError::ServerError { status: 503 });
+                }
+                Ok(vec![vec![0.0, 1.0]; len])
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(vectors.len(), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_dimension_mismatch() {
+        let mut config = EmbeddingsConfig::new("custom");
+        config.dimensions = Some(3);
+        let inputs = vec!["a".to_string()];
+
+        let result = embed_in_batches(&config, &inputs, |_| async { Ok(vec![vec![0.0, 1.0]]) }).await;
+        assert!(result.is_err());
+    }
+}
@@ -5,12 +5,72 @@ use thiserror::Error;
 /// Errors from LLM operations.
 #[derive(Debug, Error)]
 pub enum LlmError {
+    /// The provider API returned an error.
     #[error("API error: {message}")]
-    ApiError { message: String },
+    ApiError {
+        /// Error details.
+        message: String,
+    },
 
+    /// The provider returned no usable response.
     #[error("No response received")]
     NoResponse,
 
+    /// The provider rejected the API key.
+    #[error("Unauthorized: check the configured API key")]
+    Unauthorized,
+
+    /// The provider could not be reached.
+    #[error("Connection failed: {message}")]
+    ConnectionFailed {
+        /// Error details.
+        message: String,
+    },
+
+    /// The provider failed with a server-side error.
+    #[error("Server error: HTTP {status}")]
+    ServerError {
+        /// HTTP status code.
+        status: u16,
+    },
+
+    /// The provider rate-limited the request.
     #[error("Rate limited: retry after {retry_after}s")]
-    RateLimited { retry_after: u64 },
+    RateLimited {
+        /// Seconds to wait before retrying.
+        retry_after: u64,
+    },
+
+    /// The provider settings cannot be used, e.g. an invalid URL.
+    #[error("Invalid configuration: {message}")]
+    InvalidConfig {
+        /// Error details.
+        message: String,
+    },
+}
+
+impl LlmError {
+    /// Map an unsuccessful HTTP status from a provider API to an error.
+    #[must_use]
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Self::Unauthorized,
+            reqwest::StatusCode::TOO_MANY_REQUESTS => Self::RateLimited { retry_after: 60 },
+            status if status.is_server_error() => Self::ServerError {
+                status: status.as_u16(),
+            },
+            status => Self::ApiError {
+                message: format!("Unexpected status {}", status),
+            },
+        }
+    }
+
+    /// Whether retrying the request may succeed.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. } | Self::ServerError { .. } | Self::ConnectionFailed { .. }
+        )
+    }
 }
@@ -0,0 +1,182 @@
+//! Google Gemini provider using the Generative Language API.
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::client::LlmClient;
+use super::conversion::{
+    context_to_messages, from_gemini_response, to_gemini_contents, to_gemini_tools,
+    GeminiGenerationConfig, GeminiRequest, GeminiResponse, GeminiSafetySetting,
+};
+use super::error::LlmError;
+use super::models::capabilities;
+use super::provider::{ApiSpec, KeyAuth, ModelListing, ProviderBase, ProviderConfig, ProviderInfo};
+use super::prompt::{PromptMessage, SystemPrompt};
+
+/// Generative Language API details; model names are returned as `models/<id>`.
+const API: ApiSpec = ApiSpec {
+    default_base_url: "https://generativelanguage.googleapis.com/v1beta/",
+    auth: KeyAuth::Header("x-goog-api-key"),
+    headers: &[],
+    models: ModelListing { path: "models", list: "models", id: "name", prefix: "models/" },
+};
+
+/// Gemini provider implementation.
+#[derive(Debug, Clone)]
+pub struct GeminiProvider {
+    /// Configuration, prompt and HTTP client.
+    pub(crate) base: ProviderBase,
+}
+
+impl GeminiProvider {
+    /// Create a new Gemini provider.
+    #[must_use]
+    pub fn new(config: ProviderConfig) -> Self {
+        Self::with_prompt(config, SystemPrompt::default())
+    }
+
+    /// Create a new Gemini provider with custom system prompt.
+    #[must_use]
+    pub fn with_prompt(config: ProviderConfig, prompt: SystemPrompt) -> Self {
+        Self {
+            base: ProviderBase::new(config, prompt, API),
+        }
+    }
+
+    /// URL of the `generateContent` endpoint for the configured model.
+    fn generate_url(&self) -> Result<url::Url, LlmError> {
+        let path = format!("models/{}:generateContent", self.base.config.model);
+        self.base.endpoint(&path)
+    }
+
+    /// Build a Gemini request from prompt messages.
+    fn build_request(&self, messages: &[PromptMessage], with_tools: bool) -> GeminiRequest {
+        let (contents, system_instruction) = to_gemini_contents(messages);
+        let caps = capabilities(&self.base.config.model);
+
+        GeminiRequest {
+            contents,
+            system_instruction,
+            tools: if with_tools && caps.supports_tools {
+                to_gemini_tools(&self.base.config.tool_definitions())
+            } else {
+                Vec::new()
+            },
+            generation_config: GeminiGenerationConfig {
+                temperature: self.base.config.temperature,
+                max_output_tokens: caps.clamp_max_tokens(self.base.config.max_tokens),
+                seed: self.base.config.seed,
+            },
+            safety_settings: self
+                .base
+                .config
+                .safety_settings
+                .iter()
+                .map(|(category, threshold)| GeminiSafetySetting {
+                    category: category.clone(),
+                    threshold: threshold.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Call the Gemini API.
+    #[tracing::instrument(
+        name = "llm_request",
+        skip_all,
+        fields(provider = "gemini", model = %self.base.config.model)
+    )]
+    async fn call_api(&self, request: &GeminiRequest) -> Result<GeminiResponse, LlmError> {
+        let mut builder = self.base.http.post(self.generate_url()?).json(request);
+        if let Some(key) = &self.base.config.api_key {
+            builder = builder.header("x-goog-api-key", key.expose());
+        }
+
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        self.base.config.log_exchange(request, &body);
+        if !status.is_success() {
+            return Err(LlmError::from_status(status));
+        }
+
+        serde_json::from_str(&body).map_err(|e| LlmError::ApiError {
+            message: format!("Invalid Gemini response: {}", e),
+        })
+    }
+}
+
+#[async_trait]
+impl LlmClient for GeminiProvider {
+    async fn complete(&self, prompt: &str) -> Result<String, LlmError> {
+        let messages = self.base.prompt_messages(prompt);
+        let response = self.call_api(&self.build_request(&messages, false)).await?;
+        let text: String = response
+            .candidates
+            .first()
+            .and_then(|c| c.content.as_ref())
+            .map(|c| c.parts.iter().filter_map(|p| p.text.as_deref()).collect())
+            .unwrap_or_default();
+
+        if text.is_empty() {
+            return Err(LlmError::NoResponse);
+        }
+        Ok(text)
+    }
+
+    async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision(&messages, async {
+                let response = self.call_api(&self.build_request(&messages, true)).await?;
+                from_gemini_response(&response)
+            })
+            .await
+    }
+
+    fn provider_info(&self) -> ProviderInfo {
+        self.base.info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptBuilder;
+
+    #[test]
+    fn test_generate_url() {
+        let config = ProviderConfig {
+            provider_type: "gemini".to_string(),
+            model: "gemini-1.5-pro".to_string(),
+            ..ProviderConfig::default()
+        };
+        let provider = GeminiProvider::new(config);
+        assert_eq!(
+            provider.generate_url().unwrap().as_str(),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-1.5-pro:generateContent"
+        );
+    }
+
+    #[test]
+    fn test_build_request_passes_safety_settings() {
+        let mut config = ProviderConfig::default();
+        config
+            .safety_settings
+            .insert("HARM_CATEGORY_DANGEROUS_CONTENT".to_string(), "BLOCK_ONLY_HIGH".to_string());
+        let provider = GeminiProvider::new(config);
+
+        let messages = PromptBuilder::new().system("sys").user("hi").build();
+        let request = provider.build_request(&messages, true);
+
+        assert_eq!(request.safety_settings.len(), 1);
+        assert_eq!(request.safety_settings[0].threshold, "BLOCK_ONLY_HIGH");
+        assert!(request.system_instruction.is_some());
+        assert_eq!(request.contents.len(), 1);
+        assert!(!request.tools.is_empty());
+    }
+}
@@ -2,16 +2,44 @@
 
 #![warn(missing_docs)]
 
+pub mod anthropic;
+pub mod azure;
+pub mod cache;
 pub mod client;
 pub mod conversion;
+pub mod decision;
+pub mod dispatch;
+pub mod embeddings;
 pub mod error;
+pub mod gemini;
+pub mod models;
+pub mod ollama;
 pub mod openai;
 pub mod provider;
 pub mod prompt;
+pub mod request_log;
+pub mod stream;
+pub mod tokenizer;
 
-pub use client::LlmClient;
+pub use anthropic::AnthropicProvider;
+pub use azure::AzureOpenAiProvider;
+pub use cache::ResponseCache;
+pub use client::{generate_typed, LlmClient};
 pub use conversion::{to_openai_messages, from_openai_response};
+pub use decision::{AgentDecision, TokenUsage, ToolCall};
+pub use dispatch::AnyProvider;
+pub use embeddings::{EmbeddingsClient, EmbeddingsConfig, OllamaEmbeddings, OpenAiEmbeddings};
 pub use error::LlmError;
+pub use gemini::GeminiProvider;
+pub use models::{capabilities, ModelCapabilities};
+pub use ollama::OllamaProvider;
 pub use openai::OpenAiProvider;
 pub use provider::{ProviderConfig, ProviderInfo};
-pub use prompt::{PromptBuilder, PromptMessage, PromptRole, SystemPrompt, ConversationHistory};
+pub use request_log::RequestLog;
+pub use stream::{DecisionChunk, DecisionStream, TokenStream};
+pub use tokenizer::TokenCounter;
+pub use prompt::{
+    explain_sql_prompt, notification_prompt, optimize_prompt, seed_hints_prompt, summary_prompt,
+    ConversationHistory, PromptBuilder, PromptMessage, PromptRole, SystemPrompt, ANALYST_PERSONA,
+    DBA_PERSONA, DEVELOPER_PERSONA,
+};
@@ -0,0 +1,135 @@
+//! Model capability registry.
+//!
+//! Providers consult this table to adapt requests to the model: tools are
+//! dropped for models without function calling (the system prompt already
+//! describes the JSON decision format), JSON mode is only requested where
+//! supported, and `max_tokens` is clamped to the model's output limit.
+//! Deterministic runs require a model that accepts a sampling seed.
+
+/// Capabilities of a model family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    /// Context window in tokens.
+    pub context_window: u32,
+    /// Maximum output tokens per response.
+    pub max_output_tokens: u32,
+    /// Whether the model supports native tool/function calling.
+    pub supports_tools: bool,
+    /// Whether the model supports a JSON response mode.
+    pub supports_json_mode: bool,
+    /// Whether the model honors temperature 0 and a sampling seed.
+    pub supports_seed: bool,
+}
+
+/// Capabilities assumed for models missing from the registry.
+pub const DEFAULT_CAPABILITIES: ModelCapabilities = ModelCapabilities {
+    context_window: 8_192,
+    max_output_tokens: 4_096,
+    supports_tools: true,
+    supports_json_mode: false,
+    supports_seed: false,
+};
+
+/// Known model families, matched by longest prefix.
+const REGISTRY: &[(&str, ModelCapabilities)] = &[
+    ("gpt-4o", caps(128_000, 16_384, true, true, true)),
+    ("gpt-4.1", caps(1_047_576, 32_768, true, true, true)),
+    ("gpt-4-turbo", caps(128_000, 4_096, true, true, true)),
+    ("gpt-4", caps(8_192, 4_096, true, false, true)),
+    ("gpt-3.5-turbo", caps(16_385, 4_096, true, true, true)),
+    // Reasoning models ignore temperature and seed
+    ("o1-mini", caps(128_000, 65_536, false, false, false)),
+    ("o1", caps(200_000, 100_000, true, true, false)),
+    ("o3-mini", caps(200_000, 100_000, true, true, false)),
+    ("gemini-1.5-pro", caps(2_097_152, 8_192, true, true, true)),
+    ("gemini-1.5-flash", caps(1_048_576, 8_192, true, true, true)),
+    ("gemini-2.0-flash", caps(1_048_576, 8_192, true, true, true)),
+    ("claude-opus-4", caps(200_000, 32_000, true, false, false)),
+    ("claude-sonnet-4", caps(200_000, 64_000, true, false, false)),
+    ("claude-3-7", caps(200_000, 64_000, true, false, false)),
+    ("claude-3-5", caps(200_000, 8_192, true, false, false)),
+    ("claude-3", caps(200_000, 4_096, true, false, false)),
+    ("llama3.1", caps(131_072, 4_096, true, true, true)),
+    ("llama3", caps(8_192, 2_048, false, true, true)),
+    ("qwen2.5", caps(32_768, 8_192, true, true, true)),
+    ("mistral", caps(32_768, 4_096, false, true, true)),
+];
+
+/// Shorthand for registry entries.
+const fn caps(
+    context_window: u32,
+    max_output_tokens: u32,
+    supports_tools: bool,
+    supports_json_mode: bool,
+    supports_seed: bool,
+) -> ModelCapabilities {
+    ModelCapabilities {
+        context_window,
+        max_output_tokens,
+        supports_tools,
+        supports_json_mode,
+        supports_seed,
+    }
+}
+
+/// Look up the capabilities of a model.
+///
+/// Matching is by longest prefix so dated snapshots such as
+/// `gpt-4o-2024-08-06` resolve to their family. Unknown models get
+/// [`DEFAULT_CAPABILITIES`].
+#[must_use]
+pub fn capabilities(model: &str) -> ModelCapabilities {
+    REGISTRY
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(DEFAULT_CAPABILITIES, |(_, caps)| *caps)
+}
+
+/// Whether a model is present in the registry.
+#[must_use]
+pub fn is_known(model: &str) -> bool {
+    REGISTRY.iter().any(|(prefix, _)| model.starts_with(prefix))
+}
+
+impl ModelCapabilities {
+    /// Clamp a requested output token limit to what the model allows.
+    #[must_use]
+    pub fn clamp_max_tokens(&self, requested: u32) -> u32 {
+        requested.min(self.max_output_tokens)
+    }
+
+    /// Whether a prompt of the given size fits in the context window.
+    #[must_use]
+    pub fn fits(&self, tokens: usize) -> bool {
+        tokens <= self.context_window as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_prefix_match() {
+        assert_eq!(capabilities("gpt-4o-2024-08-06").context_window, 128_000);
+        assert_eq!(capabilities("gpt-4-0613").context_window, 8_192);
+        assert!(!capabilities("o1-mini").supports_tools);
+        assert!(capabilities("o1-preview").supports_tools);
+        assert!(is_known("gemini-1.5-pro-002"));
+        assert!(!capabilities("o3-mini").supports_seed);
+    }
+
+    #[test]
+    fn test_unknown_model_defaults() {
+        assert!(!is_known("my-finetune"));
+        assert_eq!(capabilities("my-finetune"), DEFAULT_CAPABILITIES);
+    }
+
+    #[test]
+    fn test_clamp_max_tokens() {
+        let caps = capabilities("gpt-4-turbo");
+        assert_eq!(caps.clamp_max_tokens(100_000), 4_096);
+        assert_eq!(caps.clamp_max_tokens(1_000), 1_000);
+    }
+}
@@ -0,0 +1,235 @@
+//! Ollama provider for local models, using the Ollama HTTP API.
+//!
+//! No API key is needed; `base_url` points at the Ollama server, by default
+//! `http://localhost:11434`.
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::client::{parse_structured, LlmClient};
+use super::conversion::{
+    context_to_messages, from_ollama_response, ollama_stream_deltas, to_ollama_messages,
+    OllamaChatRequest, OllamaChatResponse, OllamaOptions,
+};
+use super::error::LlmError;
+use super::models::capabilities;
+use super::provider::{ApiSpec, KeyAuth, ModelListing, ProviderBase, ProviderConfig, ProviderInfo};
+use super::prompt::{PromptMessage, SystemPrompt};
+use super::stream::{
+    decision_stream, json_lines, lines, token_stream, DecisionStream, EventStream, TokenStream,
+};
+
+/// Ollama API details; the server is unauthenticated.
+const API: ApiSpec = ApiSpec {
+    default_base_url: "http://localhost:11434/",
+    auth: KeyAuth::None,
+    headers: &[],
+    models: ModelListing { path: "api/tags", list: "models", id: "name", prefix: "" },
+};
+
+/// Largest context window requested; local memory use grows with it.
+const MAX_CONTEXT: u32 = 32_768;
+
+/// Ollama provider implementation.
+#[derive(Debug, Clone)]
+pub struct OllamaProvider {
+    /// Configuration, prompt and HTTP client.
+    pub(crate) base: ProviderBase,
+}
+
+impl OllamaProvider {
+    /// Create a new Ollama provider.
+    #[must_use]
+    pub fn new(config: ProviderConfig) -> Self {
+        Self::with_prompt(config, SystemPrompt::default())
+    }
+
+    /// Create a new Ollama provider with custom system prompt.
+    #[must_use]
+    pub fn with_prompt(config: ProviderConfig, prompt: SystemPrompt) -> Self {
+        Self {
+            base: ProviderBase::new(config, prompt, API),
+        }
+    }
+
+    /// Build a chat request from prompt messages.
+    ///
+    /// Models without native tools are asked for JSON, the decision format
+    /// described in the system prompt.
+    fn build_request(&self, messages: &[PromptMessage], with_tools: bool) -> OllamaChatRequest {
+        let caps = capabilities(&self.base.config.model);
+        let tools = with_tools && caps.supports_tools;
+
+        OllamaChatRequest {
+            model: self.base.config.model.clone(),
+            messages: to_ollama_messages(messages),
+            tools: if tools { self.base.config.tool_definitions() } else { Vec::new() },
+            format: if with_tools && !tools && caps.supports_json_mode {
+                Value::from("json")
+            } else {
+                Value::Null
+            },
+            stream: false,
+            options: OllamaOptions {
+                temperature: self.base.config.temperature,
+                num_predict: caps.clamp_max_tokens(self.base.config.max_tokens),
+                num_ctx: caps.context_window.min(MAX_CONTEXT),
+                seed: self.base.config.seed,
+            },
+        }
+    }
+
+    /// Call the chat endpoint.
+    #[tracing::instrument(
+        name = "llm_request",
+        skip_all,
+        fields(provider = "ollama", model = %self.base.config.model)
+    )]
+    async fn call_api(&self, request: &OllamaChatRequest) -> Result<OllamaChatResponse, LlmError> {
+        let builder = self.base.http.post(self.base.endpoint("api/chat")?).json(request);
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        self.base.config.log_exchange(request, &body);
+        if !status.is_success() {
+            return Err(api_error(status, &body));
+        }
+
+        serde_json::from_str(&body).map_err(|e| LlmError::ApiError {
+            message: format!("Invalid Ollama response: {}", e),
+        })
+    }
+
+    /// Start a streamed chat, answered as one JSON object per line.
+    #[tracing::instrument(
+        name = "llm_stream",
+        skip_all,
+        fields(provider = "ollama", model = %self.base.config.model)
+    )]
+    async fn call_stream(&self, request: &OllamaChatRequest) -> Result<EventStream, LlmError> {
+        let builder = self.base.http.post(self.base.endpoint("api/chat")?).json(request);
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            self.base.config.log_exchange(request, &body);
+            return Err(api_error(status, &body));
+        }
+
+        Ok(json_lines(lines(response.bytes_stream())))
+    }
+
+}
+
+/// Map an unsuccessful response to an error.
+fn api_error(status: reqwest::StatusCode, body: &str) -> LlmError {
+    // Client errors explain themselves, e.g. a model that is not pulled
+    let message = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|b| b.get("error").and_then(Value::as_str).map(str::to_string));
+    match message {
+        Some(message) if status.is_client_error() => LlmError::ApiError { message },
+        _ => LlmError::from_status(status),
+    }
+}
+
+#[async_trait]
+impl LlmClient for OllamaProvider {
+    async fn complete(&self, prompt: &str) -> Result<String, LlmError> {
+        let request = self.build_request(&self.base.prompt_messages(prompt), false);
+        let response = self.call_api(&request).await?;
+        match response.message {
+            Some(message) if !message.content.is_empty() => Ok(message.content),
+            _ => Err(LlmError::NoResponse),
+        }
+    }
+
+    async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision(&messages, async {
+                let response = self.call_api(&self.build_request(&messages, true)).await?;
+                from_ollama_response(&response)
+            })
+            .await
+    }
+
+    async fn complete_stream(&self, prompt: &str) -> Result<TokenStream, LlmError> {
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.stream = true;
+        let events = self.call_stream(&request).await?;
+        Ok(token_stream(events, ollama_stream_deltas))
+    }
+
+    async fn generate_decision_stream(
+        &self,
+        context_json: &Value,
+    ) -> Result<DecisionStream, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision_stream(&messages, async {
+                let mut request = self.build_request(&messages, true);
+                request.stream = true;
+                let events = self.call_stream(&request).await?;
+                let config = self.base.config.clone();
+                let log = move |raw: &str| config.log_exchange(&request, raw);
+                Ok(decision_stream(events, ollama_stream_deltas, log))
+            })
+            .await
+    }
+
+    async fn generate_structured(&self, prompt: &str, schema: &Value) -> Result<Value, LlmError> {
+        // Ollama constrains the output to the schema itself
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.format = schema.clone();
+        let response = self.call_api(&request).await?;
+        let content = response.message.map(|m| m.content).unwrap_or_default();
+        parse_structured(&content)
+    }
+
+    fn provider_info(&self) -> ProviderInfo {
+        self.base.info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prompt::PromptBuilder;
+
+    #[test]
+    fn test_build_request_adapts_to_model() {
+        let provider = OllamaProvider::new(ProviderConfig {
+            provider_type: "ollama".to_string(),
+            model: "llama3.1:8b".to_string(),
+            ..ProviderConfig::default()
+        });
+        assert_eq!(
+            provider.base.endpoint("api/chat").unwrap().as_str(),
+            "http://localhost:11434/api/chat"
+        );
+
+        let messages = PromptBuilder::new().system("sys").user("hi").build();
+        let request = provider.build_request(&messages, true);
+        assert!(!request.tools.is_empty());
+        assert!(request.format.is_null());
+        assert_eq!(request.options.num_ctx, MAX_CONTEXT);
+
+        // Without native tools the decision is requested as JSON
+        let provider = OllamaProvider::new(ProviderConfig {
+            model: "llama3:8b".to_string(),
+            ..ProviderConfig::default()
+        });
+        let request = provider.build_request(&messages, true);
+        assert!(request.tools.is_empty());
+        assert_eq!(request.format, "json");
+        assert_eq!(request.options.num_ctx, 8_192);
+        assert!(provider.build_request(&messages, false).format.is_null());
+    }
+}
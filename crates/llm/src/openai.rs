@@ -1,232 +1,259 @@
-//! OpenAI provider implementation using async-openai.
+//! OpenAI provider using the Chat Completions API.
+//!
+//! Also works with OpenAI-compatible servers through `base_url`.
 
 use async_trait::async_trait;
-use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::fmt::Debug;
 
-use super::client::LlmClient;
+use super::client::{parse_structured, LlmClient};
 use super::conversion::{
-    create_tool_definitions, from_openai_response, to_openai_messages, OpenAiChatRequest,
-    OpenAiChatResponse, OpenAiMessage,
+    context_to_messages, from_openai_response, openai_stream_deltas, to_openai_messages,
+    OpenAiChatRequest, OpenAiChatResponse, OpenAiMessage,
 };
 use super::error::LlmError;
-use super::provider::{ProviderConfig, ProviderInfo};
-use super::prompt::{ConversationHistory, PromptBuilder, PromptMessage, PromptRole, SystemPrompt};
+use super::models::capabilities;
+use super::provider::{ApiSpec, KeyAuth, ModelListing, ProviderBase, ProviderConfig, ProviderInfo};
+use super::prompt::{structured_prompt, PromptMessage, SystemPrompt};
+use super::stream::{
+    decision_stream, lines, sse_data, token_stream, DecisionStream, EventStream, TokenStream,
+};
+
+/// OpenAI API details.
+const API: ApiSpec = ApiSpec {
+    default_base_url: "https://api.openai.com/v1/",
+    auth: KeyAuth::Bearer,
+    headers: &[],
+    models: ModelListing { path: "models", list: "data", id: "id", prefix: "" },
+};
 
 /// OpenAI provider implementation.
 #[derive(Debug, Clone)]
 pub struct OpenAiProvider {
-    /// Provider configuration.
-    config: ProviderConfig,
-    /// System prompt.
-    system_prompt: SystemPrompt,
-    /// Conversation history.
-    history: ConversationHistory,
-    /// Whether to use actual API.
-    use_api: bool,
+    /// Configuration, prompt and HTTP client.
+    pub(crate) base: ProviderBase,
 }
 
 impl OpenAiProvider {
     /// Create a new OpenAI provider.
-    #[allow(dead_code)]
+    #[must_use]
     pub fn new(config: ProviderConfig) -> Self {
         Self::with_prompt(config, SystemPrompt::default())
     }
 
     /// Create a new OpenAI provider with custom system prompt.
-    #[allow(dead_code)]
+    #[must_use]
     pub fn with_prompt(config: ProviderConfig, prompt: SystemPrompt) -> Self {
         Self {
-            config,
-            system_prompt: prompt,
-            history: ConversationHistory::new().with_max_messages(20),
-            use_api: false, // Set to true when API keys are configured
+            base: ProviderBase::new(config, prompt, API),
         }
     }
 
-    /// Enable/disable API usage.
-    #[allow(dead_code)]
-    pub fn set_use_api(&mut self, use_api: bool) {
-        self.use_api = use_api;
-    }
-
     /// Build an OpenAI chat request from prompt messages.
-    fn build_request(&self, messages: &[PromptMessage]) -> OpenAiChatRequest {
+    fn build_request(&self, messages: &[PromptMessage], with_tools: bool) -> OpenAiChatRequest {
         let openai_messages = to_openai_messages(messages);
+        let caps = capabilities(&self.base.config.model);
 
         OpenAiChatRequest {
-            model: self.config.model.clone(),
+            model: self.base.config.model.clone(),
             messages: openai_messages,
-            temperature: Some(self.config.temperature),
-            max_tokens: Some(self.config.max_tokens),
-            tools: create_tool_definitions(),
-            response_format: serde_json::json!({ "type": "json_object" }),
+            temperature: Some(self.base.config.temperature),
+            max_tokens: Some(caps.clamp_max_tokens(self.base.config.max_tokens)),
+            seed: self.base.config.seed,
+            // Without native tools the model follows the JSON format in the system prompt
+            tools: if with_tools && caps.supports_tools {
+                self.base.config.tool_definitions()
+            } else {
+                Vec::new()
+            },
+            response_format: if with_tools && caps.supports_json_mode {
+                serde_json::json!({ "type": "json_object" })
+            } else {
+                Value::Null
+            },
+            stream: false,
+            stream_options: Value::Null,
         }
     }
 
-    /// Call the OpenAI API (stub - enable with real API keys).
-    async fn call_api(&self, _request: &OpenAiChatRequest) -> Result<OpenAiChatResponse, LlmError> {
-        // Stub implementation - would use async-openai in production
-        // Enable by setting use_api = true and configuring API key
-        Err(LlmError::ApiError {
-            message: "API not configured. Set OPENAI_API_KEY environment variable.".to_string(),
+    /// Call the chat completions endpoint.
+    #[tracing::instrument(
+        name = "llm_request",
+        skip_all,
+        fields(provider = "openai", model = %self.base.config.model)
+    )]
+    async fn call_api(&self, request: &OpenAiChatRequest) -> Result<OpenAiChatResponse, LlmError> {
+        let url = self.base.endpoint("chat/completions")?;
+        let builder = self.base.authorize(self.base.http.post(url).json(request));
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        self.base.config.log_exchange(request, &body);
+        if !status.is_success() {
+            return Err(api_error(status, &body));
+        }
+
+        serde_json::from_str(&body).map_err(|e| LlmError::ApiError {
+            message: format!("Invalid OpenAI response: {}", e),
         })
     }
+
+    /// Start a streamed chat completion.
+    #[tracing::instrument(
+        name = "llm_stream",
+        skip_all,
+        fields(provider = "openai", model = %self.base.config.model)
+    )]
+    async fn call_stream(&self, request: &OpenAiChatRequest) -> Result<EventStream, LlmError> {
+        let url = self.base.endpoint("chat/completions")?;
+        let builder = self.base.authorize(self.base.http.post(url).json(request));
+        let response = builder.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            self.base.config.log_exchange(request, &body);
+            return Err(api_error(status, &body));
+        }
+
+        Ok(sse_data(lines(response.bytes_stream())))
+    }
+
+    /// Send a one-off prompt and return the text of the reply.
+    async fn complete_with(
+        &self,
+        prompt: &str,
+        response_format: Value,
+    ) -> Result<String, LlmError> {
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.response_format = response_format;
+        let response = self.call_api(&request).await?;
+
+        match response.choices.first().map(|c| &c.message) {
+            Some(OpenAiMessage::Assistant {
+                content: Some(text), ..
+            }) if !text.is_empty() => Ok(text.clone()),
+            _ => Err(LlmError::NoResponse),
+        }
+    }
+}
+
+/// Map an unsuccessful response to an error.
+///
+/// Request errors such as an exceeded context window keep the API's own
+/// message; authentication, rate limit and server errors map by status.
+fn api_error(status: reqwest::StatusCode, body: &str) -> LlmError {
+    let generic = LlmError::from_status(status);
+    if !matches!(generic, LlmError::ApiError { .. }) {
+        return generic;
+    }
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|b| b["error"]["message"].as_str().map(str::to_string))
+        .map_or(generic, |message| LlmError::ApiError { message })
 }
 
 #[async_trait]
 impl LlmClient for OpenAiProvider {
     async fn complete(&self, prompt: &str) -> Result<String, LlmError> {
-        if self.use_api {
-            let messages = PromptBuilder::new()
-                .with_system_prompt(self.system_prompt.clone())
-                .user(prompt)
-                .build();
-
-            let request = self.build_request(&messages);
-            let response = self.call_api(&request).await?;
-            from_openai_response(&response).map(|v| v.to_string())
-        } else {
-            // Stub response
-            Ok(format!(
-                "This is a placeholder response for: {}",
-                prompt.lines().next().unwrap_or(prompt)
-            ))
-        }
+        self.complete_with(prompt, Value::Null).await
     }
 
     async fn generate_decision(&self, context_json: &Value) -> Result<Value, LlmError> {
-        if self.use_api {
-            // Convert context JSON to prompt messages
-            let messages = convert_context_to_messages(context_json, &self.system_prompt);
-
-            // Build and send request
-            let request = self.build_request(&messages);
-            let response = self.call_api(&request).await?;
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision(&messages, async {
+                let response = self.call_api(&self.build_request(&messages, true)).await?;
+                from_openai_response(&response)
+            })
+            .await
+    }
 
-            from_openai_response(&response)
-        } else {
-            // Stub decision - check context for tool calls
-            let has_user_message = context_json
-                .get("messages")
-                .and_then(|m| m.as_array())
-                .map(|arr| {
-                    arr.iter().any(|m| {
-                        m.get("role")
-                            .and_then(|r| r.as_str())
-                            .map(|r| r == "user")
-                            .unwrap_or(false)
-                    })
-                })
-                .unwrap_or(false);
-
-            if has_user_message {
-                // Suggest using tools
-                Ok(serde_json::json!({
-                    "type": "reasoning",
-                    "thought": "The user has asked a query. I should analyze what they're asking and determine if I need to explore the schema or execute a query."
-                }))
-            } else {
-                Ok(serde_json::json!({
-                    "type": "final_answer",
-                    "answer": "I need more context to provide a useful response."
-                }))
-            }
-        }
+    async fn complete_stream(&self, prompt: &str) -> Result<TokenStream, LlmError> {
+        let mut request = self.build_request(&self.base.prompt_messages(prompt), false);
+        request.stream = true;
+        let events = self.call_stream(&request).await?;
+        Ok(token_stream(events, openai_stream_deltas))
     }
 
-    async fn generate_structured<T: DeserializeOwned + Debug>(
+    async fn generate_decision_stream(
         &self,
-        prompt: &str,
-        _schema: &T,
-    ) -> Result<T, LlmError> {
-        if self.use_api {
-            let content = self.complete(prompt).await?;
-            serde_json::from_str(&content).map_err(|e| LlmError::ApiError {
-                message: format!("Failed to parse structured response: {}", e),
+        context_json: &Value,
+    ) -> Result<DecisionStream, LlmError> {
+        let messages = context_to_messages(context_json, &self.base.system_prompt);
+        self.base.config
+            .cached_decision_stream(&messages, async {
+                let mut request = self.build_request(&messages, true);
+                request.stream = true;
+                request.stream_options = serde_json::json!({ "include_usage": true });
+                let events = self.call_stream(&request).await?;
+                let config = self.base.config.clone();
+                let log = move |raw: &str| config.log_exchange(&request, raw);
+                Ok(decision_stream(events, openai_stream_deltas, log))
             })
-        } else {
-            Err(LlmError::NoResponse)
-        }
+            .await
     }
 
-    fn provider_info(&self) -> ProviderInfo {
-        ProviderInfo {
-            provider: self.config.provider_type.clone(),
-            model: self.config.model.clone(),
-        }
+    async fn generate_structured(&self, prompt: &str, schema: &Value) -> Result<Value, LlmError> {
+        // JSON mode guarantees valid JSON; the schema itself is in the prompt
+        let response_format = if capabilities(&self.base.config.model).supports_json_mode {
+            serde_json::json!({ "type": "json_object" })
+        } else {
+            Value::Null
+        };
+        let prompt = structured_prompt(prompt, schema);
+        let content = self.complete_with(&prompt, response_format).await?;
+        parse_structured(&content)
     }
-}
 
-/// Convert context JSON to prompt messages.
-fn convert_context_to_messages(context: &Value, system_prompt: &SystemPrompt) -> Vec<PromptMessage> {
-    let mut messages = Vec::new();
-
-    // Add system prompt
-    messages.push(PromptMessage::System {
-        content: system_prompt.full(),
-    });
-
-    // Convert context messages
-    if let Some(arr) = context.get("messages").and_then(|m| m.as_array()) {
-        for item in arr {
-            if let (Some(role_str), Some(content)) = (
-                item.get("role").and_then(|r| r.as_str()),
-                item.get("content").and_then(|c| c.as_str()),
-            ) {
-                let role = match role_str {
-                    "user" => PromptRole::User,
-                    "assistant" => PromptRole::Assistant,
-                    "tool" => PromptRole::Tool,
-                    "system" => PromptRole::System,
-                    _ => PromptRole::User,
-                };
-
-                match role {
-                    PromptRole::System => {
-                        messages.push(PromptMessage::System {
-                            content: content.to_string(),
-                        });
-                    }
-                    PromptRole::User => {
-                        messages.push(PromptMessage::User {
-                            content: content.to_string(),
-                        });
-                    }
-                    PromptRole::Assistant => {
-                        messages.push(PromptMessage::Assistant {
-                            content: content.to_string(),
-                            tool_calls: Vec::new(),
-                        });
-                    }
-                    PromptRole::Tool => {
-                        messages.push(PromptMessage::Tool {
-                            tool_call_id: item
-                                .get("call_id")
-                                .and_then(|c| c.as_str())
-                                .unwrap_or("default")
-                                .to_string(),
-                            name: item
-                                .get("tool_name")
-                                .and_then(|t| t.as_str())
-                                .unwrap_or("unknown")
-                                .to_string(),
-                            content: content.to_string(),
-                        });
-                    }
-                }
-            }
-        }
+    fn provider_info(&self) -> ProviderInfo {
+        self.base.info()
     }
-
-    messages
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde_json::json;
+    use crate::conversion::OpenAiToolDefinition;
+    use crate::decision::{AgentDecision, TokenUsage};
+    use crate::prompt::PromptBuilder;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Answer chat completion requests with `status` and `body`, returning
+    /// the server and a provider pointed at it.
+    ///
+    /// The mock only matches an authorized JSON `POST` to
+    /// `/v1/chat/completions` for the configured model and expects exactly
+    /// one such request, which the server verifies when dropped.
+    async fn mock_server(status: u16, body: &str) -> (MockServer, OpenAiProvider) {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("authorization", "Bearer sk-test"))
+            .and(header("content-type", "application/json"))
+            .and(body_partial_json(serde_json::json!({ "model": "gpt-4o" })))
+            .respond_with(ResponseTemplate::new(status).set_body_string(body))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let provider = OpenAiProvider::new(ProviderConfig {
+            base_url: Some(format!("{}/v1/", server.uri()).parse().unwrap()),
+            api_key: Some("sk-test".into()),
+            ..ProviderConfig::default()
+        });
+        (server, provider)
+    }
+
+    /// Ask a provider for a decision on a one-message conversation.
+    async fn decide(provider: &OpenAiProvider) -> Result<Value, LlmError> {
+        let context = serde_json::json!({ "messages": [{ "role": "user", "content": "hi" }] });
+        provider.generate_decision(&context).await
+    }
 
     #[test]
     fn test_openai_provider_new() {
@@ -240,34 +267,159 @@ mod tests {
         let config = ProviderConfig::default();
         let custom_prompt = SystemPrompt::default();
         let provider = OpenAiProvider::with_prompt(config, custom_prompt);
-        assert!(!provider.system_prompt.full().is_empty());
+        assert!(!provider.base.system_prompt.full().is_empty());
+    }
+
+    #[test]
+    fn test_endpoint_resolution() {
+        let provider = OpenAiProvider::new(ProviderConfig::default());
+        assert_eq!(
+            provider.base.endpoint("models").unwrap().as_str(),
+            "https://api.openai.com/v1/models"
+        );
+
+        let config = ProviderConfig {
+            base_url: Some("http://localhost:8080/v1".parse().unwrap()),
+            ..ProviderConfig::default()
+        };
+        let provider = OpenAiProvider::new(config);
+        assert_eq!(
+            provider.base.endpoint("models").unwrap().as_str(),
+            "http://localhost:8080/v1/models"
+        );
     }
 
     #[test]
-    fn test_convert_context_to_messages() {
-        let context = json!({
-            "messages": [
-                {"role": "user", "content": "Hello"},
-                {"role": "assistant", "content": "Hi there!"}
-            ]
+    fn test_build_request_adapts_to_model() {
+        let messages = PromptBuilder::new().user("hi").build();
+
+        let provider = OpenAiProvider::new(ProviderConfig {
+            max_tokens: 50_000,
+            ..ProviderConfig::default()
         });
-        let prompt = SystemPrompt::default();
-        let messages = convert_context_to_messages(&context, &prompt);
+        let request = provider.build_request(&messages, true);
+        assert_eq!(request.max_tokens, Some(16_384));
+        assert!(!request.tools.is_empty());
+        assert!(!request.response_format.is_null());
 
-        assert_eq!(messages.len(), 3); // System + User + Assistant
+        let provider = OpenAiProvider::new(ProviderConfig {
+            model: "o1-mini".to_string(),
+            ..ProviderConfig::default()
+        });
+        let request = provider.build_request(&messages, true);
+        assert!(request.tools.is_empty());
+        assert!(request.response_format.is_null());
     }
 
     #[test]
-    fn test_stub_complete() {
-        let config = ProviderConfig::default();
-        let provider = OpenAiProvider::new(config);
+    fn test_deterministic_request() {
+        let provider = OpenAiProvider::new(ProviderConfig {
+            temperature: 0.0,
+            seed: Some(42),
+            ..ProviderConfig::default()
+        });
+        let request = provider.build_request(&PromptBuilder::new().user("hi").build(), true);
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["temperature"], 0.0);
+        assert_eq!(body["seed"], 42);
 
-        let result = tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(provider.complete("Test prompt"));
+        let request = provider.build_request(&PromptBuilder::new().user("hi").build(), false);
+        assert_eq!((request.temperature, request.seed), (Some(0.0), Some(42)));
+    }
 
-        assert!(result.is_ok());
-        let response = result.unwrap();
-        assert!(response.contains("Test prompt"));
+    #[test]
+    fn test_persona_and_tools_reach_request() {
+        use crate::prompt::ANALYST_PERSONA;
+
+        let mut provider = OpenAiProvider::new(ProviderConfig::default());
+        provider.base.set_system_prompt(SystemPrompt::standard().with_persona(ANALYST_PERSONA));
+        provider.base.set_tools(vec![OpenAiToolDefinition::function(
+            "list_tables".to_string(),
+            "List all tables".to_string(),
+            serde_json::json!({ "type": "object", "properties": {} }),
+        )]);
+
+        let context = serde_json::json!({ "messages": [{ "role": "user", "content": "hi" }] });
+        let messages = context_to_messages(&context, &provider.base.system_prompt);
+        let request = provider.build_request(&messages, true);
+        let names: Vec<&str> = request.tools.iter().map(|t| t.function.name.as_str()).collect();
+        assert_eq!(names, ["list_tables"]);
+        let system = serde_json::to_value(&request.messages[0]).unwrap();
+        let persona = ANALYST_PERSONA.trim().lines().next().unwrap();
+        assert!(system["content"].as_str().unwrap().contains(persona));
+    }
+
+    #[test]
+    fn test_api_error() {
+        let body = r#"{"error": {"message": "maximum context length is 128000 tokens"}}"#;
+        match api_error(reqwest::StatusCode::BAD_REQUEST, body) {
+            LlmError::ApiError { message } => assert!(message.contains("context length")),
+            other => panic!("Expected ApiError, got {:?}", other),
+        }
+        assert!(matches!(
+            api_error(reqwest::StatusCode::UNAUTHORIZED, body),
+            LlmError::Unauthorized
+        ));
+        assert!(matches!(
+            api_error(reqwest::StatusCode::NOT_FOUND, "not json"),
+            LlmError::ApiError { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_call_api_answer() {
+        let body = r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1760000000, "model": "gpt-4o", "choices": [{"index": 0, "message": {"role": "assistant", "content": "There are 3 users."}, "finish_reason": "stop"}], "usage": {"prompt_tokens": 12, "completion_tokens": 5, "total_tokens": 17}}"#;
+        let (server, provider) = mock_server(200, body).await;
+        let mut decision = decide(&provider).await.unwrap();
+        assert_eq!(TokenUsage::take(&mut decision), Some(TokenUsage::new(12u64, 5u64)));
+        assert_eq!(
+            AgentDecision::from_value(&decision).unwrap(),
+            AgentDecision::FinalAnswer {
+                answer: "There are 3 users.".to_string()
+            }
+        );
+
+        let requests = server.received_requests().await.unwrap();
+        let sent: Value = requests[0].body_json().unwrap();
+        let messages = sent["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["role"], "system");
+        let user = serde_json::json!({ "role": "user", "content": "hi" });
+        assert_eq!(messages.last(), Some(&user));
+        assert_eq!(sent["response_format"], serde_json::json!({ "type": "json_object" }));
+        assert!(!sent["tools"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_call_api_tool_call() {
+        let body = r#"{"id": "chatcmpl-2", "object": "chat.completion", "created": 1760000000, "model": "gpt-4o", "choices": [{"index": 0, "message": {"role": "assistant", "content": null, "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "describe_table", "arguments": "{\"tableName\": \"users\"}"}}]}, "finish_reason": "tool_calls"}]}"#;
+        let (_server, provider) = mock_server(200, body).await;
+        let decision = decide(&provider).await.unwrap();
+        match AgentDecision::from_value(&decision).unwrap() {
+            AgentDecision::ToolCall(call) => {
+                assert_eq!(call.name, "describe_table");
+                assert_eq!(call.arguments, serde_json::json!({ "tableName": "users" }));
+                assert_eq!(call.call_id, "call_1");
+            }
+            other => panic!("Expected a tool call, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_api_errors() {
+        let body = r#"{"error": {"message": "nope"}}"#;
+        let (_server, provider) = mock_server(401, body).await;
+        assert!(matches!(decide(&provider).await, Err(LlmError::Unauthorized)));
+        let (_server, provider) = mock_server(429, body).await;
+        assert!(matches!(decide(&provider).await, Err(LlmError::RateLimited { .. })));
+        let (_server, provider) = mock_server(503, body).await;
+        assert!(matches!(decide(&provider).await, Err(LlmError::ServerError { status: 503 })));
+
+        let (_server, provider) = mock_server(200, r#"{"choices": [{"index": 0"#).await;
+        match decide(&provider).await {
+            Err(LlmError::ApiError { message }) => {
+                assert!(message.starts_with("Invalid OpenAI response"), "{}", message);
+            }
+            other => panic!("Expected ApiError, got {:?}", other),
+        }
     }
 }
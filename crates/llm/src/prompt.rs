@@ -2,6 +2,18 @@
 
 use serde::{Deserialize, Serialize};
 
+/// Enum types listed in the system prompt; `get_schema` has the rest.
+const MAX_PROMPT_ENUMS: usize = 40;
+/// Labels listed per enum type in the system prompt.
+const MAX_PROMPT_ENUM_LABELS: usize = 30;
+
+/// Role instructions of the analyst persona.
+pub const ANALYST_PERSONA: &str = include_str!("prompts/persona_analyst.txt");
+/// Role instructions of the DBA persona.
+pub const DBA_PERSONA: &str = include_str!("prompts/persona_dba.txt");
+/// Role instructions of the developer persona.
+pub const DEVELOPER_PERSONA: &str = include_str!("prompts/persona_developer.txt");
+
 /// System prompt for the PostgreSQL Agent.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemPrompt {
@@ -13,6 +25,24 @@ pub struct SystemPrompt {
     pub safety_instructions: String,
     /// Format instructions for responses.
     pub format_instructions: String,
+    /// Role instructions of the selected persona.
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// Notes about the connected database, from its profile.
+    #[serde(default)]
+    pub database_notes: Option<String>,
+    /// Session `search_path` of the connected database, in order.
+    #[serde(default)]
+    pub search_path: Vec<String>,
+    /// Whether every table reference must be schema-qualified.
+    #[serde(default)]
+    pub qualify_tables: bool,
+    /// Enum types of the connected database as `(name, labels)`.
+    #[serde(default)]
+    pub enum_types: Vec<(String, Vec<String>)>,
+    /// Whether row values are withheld from tool results.
+    #[serde(default)]
+    pub values_withheld: bool,
 }
 
 impl Default for SystemPrompt {
@@ -30,16 +60,122 @@ impl SystemPrompt {
             tool_instructions: String::from(include_str!("prompts/tools.txt")),
             safety_instructions: String::from(include_str!("prompts/safety.txt")),
             format_instructions: String::from(include_str!("prompts/format.txt")),
+            persona: None,
+            database_notes: None,
+            search_path: Vec::new(),
+            qualify_tables: false,
+            enum_types: Vec::new(),
+            values_withheld: false,
         }
     }
 
+    /// Set the role instructions of a persona, e.g. [`ANALYST_PERSONA`].
+    #[must_use]
+    pub fn with_persona(mut self, instructions: impl Into<String>) -> Self {
+        self.persona = Some(instructions.into().trim().to_string());
+        self
+    }
+
+    /// Add notes about the connected database, e.g. units or tables to avoid.
+    #[must_use]
+    pub fn with_database_notes(mut self, notes: impl Into<String>) -> Self {
+        let notes = notes.into();
+        self.database_notes = (!notes.trim().is_empty()).then(|| notes.trim().to_string());
+        self
+    }
+
+    /// Tell the model which schemas unqualified names resolve to.
+    #[must_use]
+    pub fn with_search_path(mut self, search_path: &[String]) -> Self {
+        self.search_path = search_path.to_vec();
+        self
+    }
+
+    /// Instruct the model to schema-qualify every table reference.
+    #[must_use]
+    pub fn with_qualified_tables(mut self) -> Self {
+        self.qualify_tables = true;
+        self
+    }
+
+    /// Tell the model the labels of the database's enum types, so it
+    /// compares enum columns with valid literals.
+    #[must_use]
+    pub fn with_enum_types(mut self, enum_types: Vec<(String, Vec<String>)>) -> Self {
+        self.enum_types = enum_types;
+        self
+    }
+
+    /// Tell the model that tool results carry no row values.
+    #[must_use]
+    pub fn with_values_withheld(mut self) -> Self {
+        self.values_withheld = true;
+        self
+    }
+
     /// Get the full system prompt.
     #[must_use]
     pub fn full(&self) -> String {
-        format!(
+        let mut full = format!(
             "{}\n\n{}\n\n{}\n\n{}",
             self.base, self.tool_instructions, self.safety_instructions, self.format_instructions
-        )
+        );
+        if let Some(persona) = &self.persona {
+            full.push_str("\n\n## Persona\n\n");
+            full.push_str(persona);
+        }
+        if !self.search_path.is_empty() || self.qualify_tables {
+            full.push_str("\n\n## Schemas\n");
+        }
+        if !self.search_path.is_empty() {
+            full.push_str(&format!(
+                "\nThe search_path is {}; unqualified table names resolve to the first \
+                 schema in it that has the table.",
+                self.search_path.join(", ")
+            ));
+        }
+        if self.qualify_tables {
+            full.push_str(
+                "\nAlways schema-qualify table names (schema.table); queries with \
+                 unqualified tables are rejected.",
+            );
+        }
+        if !self.enum_types.is_empty() {
+            full.push_str(
+                "\n\n## Enum Types\n\nEnum columns only accept these labels, compared \
+                 case-sensitively:",
+            );
+            for (name, labels) in self.enum_types.iter().take(MAX_PROMPT_ENUMS) {
+                let mut quoted: Vec<String> = labels
+                    .iter()
+                    .take(MAX_PROMPT_ENUM_LABELS)
+                    .map(|l| format!("'{}'", l.replace('\'', "''")))
+                    .collect();
+                if labels.len() > MAX_PROMPT_ENUM_LABELS {
+                    quoted.push("...".to_string());
+                }
+                full.push_str(&format!("\n- {}: {}", name, quoted.join(", ")));
+            }
+            if self.enum_types.len() > MAX_PROMPT_ENUMS {
+                full.push_str(&format!(
+                    "\n- ...and {} more; get_schema lists them all",
+                    self.enum_types.len() - MAX_PROMPT_ENUMS
+                ));
+            }
+        }
+        if self.values_withheld {
+            full.push_str(
+                "\n\n## Data Privacy\n\nRow values are withheld from you: query results \
+                 only list their columns, row count and per-column null and distinct counts, \
+                 while the user sees the full rows. Answer by describing what the results \
+                 show instead of quoting values.",
+            );
+        }
+        if let Some(notes) = &self.database_notes {
+            full.push_str("\n\n## Database Notes\n\n");
+            full.push_str(notes);
+        }
+        full
     }
 
     /// Get the base system prompt only.
@@ -55,6 +191,139 @@ impl SystemPrompt {
     }
 }
 
+/// Build the prompt asking a model to summarize a conversation.
+///
+/// System messages are left out; the transcript lists one message per line
+/// prefixed with its role.
+#[must_use]
+pub fn summary_prompt(messages: &[PromptMessage]) -> String {
+    let transcript = messages
+        .iter()
+        .filter_map(|m| match m {
+            PromptMessage::System { .. } => None,
+            PromptMessage::User { content } => Some(format!("user: {}", content)),
+            PromptMessage::Assistant { content, .. } => Some(format!("assistant: {}", content)),
+            PromptMessage::Tool { name, content, .. } => Some(format!("tool {}: {}", name, content)),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("{}\n## Conversation\n\n{}", include_str!("prompts/summarize.txt"), transcript)
+}
+
+/// Build the prompt asking a model for JSON matching `schema`.
+#[must_use]
+pub fn structured_prompt(prompt: &str, schema: &serde_json::Value) -> String {
+    format!(
+        "{}\n\nRespond only with a JSON value matching this JSON Schema:\n{}",
+        prompt, schema
+    )
+}
+
+/// Build the prompt asking a model for example values of text columns.
+///
+/// `columns` holds `(name, data_type)` pairs.
+#[must_use]
+pub fn seed_hints_prompt(table: &str, columns: &[(String, String)]) -> String {
+    let columns = columns
+        .iter()
+        .map(|(name, data_type)| format!("- {} ({})", name, data_type))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!(
+        "{}\n## Table\n\n{}\n\n## Columns\n\n{}",
+        include_str!("prompts/seed_hints.txt"),
+        table,
+        columns
+    )
+}
+
+/// Build the prompt asking a model to explain a query in plain English.
+///
+/// `tables` holds the tables the query refers to, each with its
+/// `(name, data_type)` column pairs.
+#[must_use]
+pub fn explain_sql_prompt(sql: &str, tables: &[(String, Vec<(String, String)>)]) -> String {
+    let tables = if tables.is_empty() {
+        "No definitions found for the tables of this query.".to_string()
+    } else {
+        tables
+            .iter()
+            .map(|(table, columns)| {
+                let columns = columns
+                    .iter()
+                    .map(|(name, data_type)| format!("{} ({})", name, data_type))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("- {}: {}", table, columns)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    format!(
+        "{}\n## Tables\n\n{}\n\n## Query\n\n```sql\n{}\n```",
+        include_str!("prompts/explain_sql.txt"),
+        tables,
+        sql.trim()
+    )
+}
+
+/// Build the prompt asking a model to propose a faster query and indexes.
+///
+/// `plan` is the current plan in text form; `tables` holds each table the
+/// query reads with a summary of its statistics and its index definitions.
+#[must_use]
+pub fn optimize_prompt(sql: &str, plan: &str, tables: &[(String, String, Vec<String>)]) -> String {
+    let tables = if tables.is_empty() {
+        "No statistics found for the tables of this query.".to_string()
+    } else {
+        tables
+            .iter()
+            .map(|(table, stats, indexes)| {
+                let indexes = if indexes.is_empty() {
+                    "  - no indexes".to_string()
+                } else {
+                    indexes
+                        .iter()
+                        .map(|index| format!("  - {}", index))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                };
+                format!("- {}: {}\n{}", table, stats, indexes)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    format!(
+        "{}\n## Query\n\n```sql\n{}\n```\n\n## Plan\n\n```\n{}\n```\n\n## Tables\n\n{}",
+        include_str!("prompts/optimize.txt"),
+        sql.trim(),
+        plan,
+        tables
+    )
+}
+
+/// Build the prompt asking a model to summarize a `NOTIFY` payload.
+#[must_use]
+pub fn notification_prompt(channel: &str, payload: &str) -> String {
+    format!(
+        "{}
+## Channel
+
+{}
+
+## Payload
+
+{}",
+        include_str!("prompts/notification.txt"),
+        channel,
+        payload
+    )
+}
+
 /// Role for LLM messages.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -322,6 +591,121 @@ mod tests {
         let full = prompt.full();
         assert!(!full.is_empty());
         assert!(full.contains("PostgreSQL"));
+        assert!(!full.contains("## Database Notes"));
+
+        let billing = prompt.with_database_notes("Amounts are in cents.\n");
+        assert!(billing.full().ends_with("## Database Notes\n\nAmounts are in cents."));
+        assert!(!billing.full().contains("## Schemas"));
+
+        let schemas = billing
+            .with_search_path(&["analytics".to_string(), "public".to_string()])
+            .with_qualified_tables()
+            .full();
+        assert!(schemas.contains("The search_path is analytics, public;"));
+        assert!(schemas.contains("Always schema-qualify table names"));
+        assert!(!schemas.contains("## Data Privacy"));
+        assert!(SystemPrompt::standard().with_values_withheld().full().contains("## Data Privacy"));
+        assert!(!schemas.contains("## Persona"));
+        let analyst = SystemPrompt::standard().with_persona(ANALYST_PERSONA).full();
+        assert!(analyst.contains("## Persona\n\nYou are assisting a data analyst."));
+    }
+
+    #[test]
+    fn test_database_notes_merged() {
+        let notes = "  This is the billing database.\nAmounts are in cents.\n\n";
+        let full = SystemPrompt::standard()
+            .with_persona(ANALYST_PERSONA)
+            .with_search_path(&["billing".to_string()])
+            .with_database_notes(notes)
+            .full();
+
+        // The notes follow every other section, trimmed, and the rest is kept
+        assert!(full.starts_with(SystemPrompt::standard().base_only()));
+        assert!(full.contains("## Persona"));
+        assert!(full.contains("The search_path is billing;"));
+        assert!(full.ends_with(
+            "## Database Notes\n\nThis is the billing database.\nAmounts are in cents."
+        ));
+        assert_eq!(full.matches("## Database Notes").count(), 1);
+
+        // Blank notes add nothing
+        let blank = SystemPrompt::standard().with_database_notes(" \n").full();
+        assert_eq!(blank, SystemPrompt::standard().full());
+    }
+
+    #[test]
+    fn test_system_prompt_enum_types() {
+        let labels = ["pending", "shipped", "won't ship"].map(String::from).to_vec();
+        let full = SystemPrompt::standard()
+            .with_enum_types(vec![("order_status".to_string(), labels)])
+            .full();
+        assert!(full.contains(
+            "## Enum Types\n\nEnum columns only accept these labels, compared case-sensitively:\n\
+             - order_status: 'pending', 'shipped', 'won''t ship'"
+        ));
+        assert!(!SystemPrompt::standard().full().contains("## Enum Types"));
+    }
+
+    #[test]
+    fn test_summary_prompt() {
+        let messages = PromptBuilder::new()
+            .system("ignored")
+            .user("How many orders?")
+            .assistant("42")
+            .build();
+        let prompt = summary_prompt(&messages);
+
+        assert!(prompt.contains("user: How many orders?\nassistant: 42"));
+        assert!(!prompt.contains("ignored"));
+    }
+
+    #[test]
+    fn test_seed_hints_prompt() {
+        let prompt = seed_hints_prompt("products", &[("sku".to_string(), "text".to_string())]);
+
+        assert!(prompt.contains("## Table\n\nproducts"));
+        assert!(prompt.contains("- sku (text)"));
+    }
+
+    #[test]
+    fn test_explain_sql_prompt() {
+        let tables = vec![(
+            "public.orders".to_string(),
+            vec![("id".to_string(), "integer".to_string())],
+        )];
+        let prompt = explain_sql_prompt("SELECT count(*) FROM orders;\n", &tables);
+
+        assert!(prompt.contains("## Tables\n\n- public.orders: id (integer)"));
+        assert!(prompt.ends_with("```sql\nSELECT count(*) FROM orders;\n```"));
+        assert!(explain_sql_prompt("SELECT 1", &[]).contains("No definitions found"));
+    }
+
+    #[test]
+    fn test_optimize_prompt() {
+        let tables = vec![(
+            "public.orders".to_string(),
+            "~50000 rows".to_string(),
+            vec!["CREATE UNIQUE INDEX orders_pkey ON public.orders USING btree (id)".to_string()],
+        )];
+        let prompt = optimize_prompt(
+            "SELECT * FROM orders WHERE status = 'open'",
+            "Seq Scan on public.orders",
+            &tables,
+        );
+
+        assert!(prompt.contains("## Plan\n\n```\nSeq Scan on public.orders\n```"));
+        assert!(prompt.ends_with(
+            "- public.orders: ~50000 rows\n  \
+             - CREATE UNIQUE INDEX orders_pkey ON public.orders USING btree (id)"
+        ));
+    }
+
+    #[test]
+    fn test_notification_prompt() {
+        let prompt = notification_prompt("orders", "{\"id\": 7}");
+
+        assert!(prompt.contains("## Channel\n\norders"));
+        assert!(prompt.ends_with("## Payload\n\n{\"id\": 7}"));
     }
 
     #[test]
@@ -1,8 +1,28 @@
-//! Provider configuration.
+//! Provider configuration and the state shared by the providers.
+//!
+//! Each provider wraps a [`ProviderBase`], which holds the configuration,
+//! system prompt and HTTP client and implements what does not depend on the
+//! wire format: resolving endpoints, authenticating, listing models and
+//! building one-off prompts. The providers themselves only convert
+//! requests and responses.
 
+use futures::StreamExt;
+use postgres_agent_config::AzureOpenAiConfig;
+use postgres_agent_util::crypto::Secret;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
 use url::Url;
 
+use super::cache::ResponseCache;
+use super::conversion::{create_tool_definitions, OpenAiToolDefinition};
+use super::error::LlmError;
+use super::prompt::{PromptBuilder, PromptMessage, SystemPrompt};
+use super::request_log::RequestLog;
+use super::stream::{single_decision, DecisionChunk, DecisionStream};
+
 /// Provider configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,13 +32,31 @@ pub struct ProviderConfig {
     /// API base URL.
     pub base_url: Option<Url>,
     /// API key.
-    pub api_key: Option<String>,
+    pub api_key: Option<Secret<String>>,
     /// Model identifier.
     pub model: String,
     /// Temperature for sampling.
     pub temperature: f32,
     /// Maximum tokens in response.
     pub max_tokens: u32,
+    /// Sampling seed, sent to providers that support one.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Provider safety filter thresholds by category (Gemini).
+    #[serde(default)]
+    pub safety_settings: BTreeMap<String, String>,
+    /// Azure OpenAI deployment settings.
+    #[serde(default)]
+    pub azure: Option<AzureOpenAiConfig>,
+    /// Debug log for prompts and raw responses.
+    #[serde(skip)]
+    pub request_log: Option<Arc<RequestLog>>,
+    /// Cache of decisions for identical requests.
+    #[serde(skip)]
+    pub response_cache: Option<Arc<ResponseCache>>,
+    /// Tools offered to the model; the built-in set when unset.
+    #[serde(skip)]
+    pub tools: Option<Vec<OpenAiToolDefinition>>,
 }
 
 impl Default for ProviderConfig {
@@ -30,8 +68,97 @@ impl Default for ProviderConfig {
             model: "gpt-4o".to_string(),
             temperature: 0.0,
             max_tokens: 4096,
+            seed: None,
+            safety_settings: BTreeMap::new(),
+            azure: None,
+            request_log: None,
+            response_cache: None,
+            tools: None,
+        }
+    }
+}
+
+impl ProviderConfig {
+    /// Tools offered to the model.
+    pub(crate) fn tool_definitions(&self) -> Vec<OpenAiToolDefinition> {
+        self.tools.clone().unwrap_or_else(create_tool_definitions)
+    }
+
+    /// Record an exchange in the request log, if enabled.
+    pub(crate) fn log_exchange(&self, request: &impl Serialize, response: &str) {
+        if let Some(log) = &self.request_log {
+            log.record(&self.provider_type, &self.model, request, response);
+        }
+    }
+
+    /// The response cache and the key of a decision request for `messages`,
+    /// if caching is enabled and the request can be keyed.
+    fn cache_entry(&self, messages: &[PromptMessage]) -> Option<(Arc<ResponseCache>, String)> {
+        let cache = self.response_cache.as_ref()?;
+        let deployment = self.azure.as_ref().map(|a| a.deployment.as_str());
+        let key = ResponseCache::key(&(
+            &self.provider_type,
+            &self.model,
+            deployment,
+            self.temperature,
+            self.max_tokens,
+            self.seed,
+            self.tools.as_ref().map(|tools| {
+                tools.iter().map(|tool| tool.function.name.as_str()).collect::<Vec<_>>()
+            }),
+            messages,
+        ));
+        match key {
+            Ok(key) => Some((Arc::clone(cache), key)),
+            Err(e) => {
+                tracing::warn!("Not caching LLM response, the request has no key: {}", e);
+                None
+            }
         }
     }
+
+    /// Get the decision for `messages` from the response cache, or from
+    /// `generate` and cache it.
+    pub(crate) async fn cached_decision(
+        &self,
+        messages: &[PromptMessage],
+        generate: impl Future<Output = Result<Value, LlmError>>,
+    ) -> Result<Value, LlmError> {
+        let Some((cache, key)) = self.cache_entry(messages) else {
+            return generate.await;
+        };
+        if let Some(decision) = cache.get(&key) {
+            tracing::debug!("Using cached LLM response {}", key);
+            return Ok(decision);
+        }
+        let decision = generate.await?;
+        cache.put(&key, &decision);
+        Ok(decision)
+    }
+
+    /// Like [`Self::cached_decision`] for streamed decisions; a cached
+    /// decision arrives whole, and a streamed one is cached once complete.
+    pub(crate) async fn cached_decision_stream(
+        &self,
+        messages: &[PromptMessage],
+        generate: impl Future<Output = Result<DecisionStream, LlmError>>,
+    ) -> Result<DecisionStream, LlmError> {
+        let Some((cache, key)) = self.cache_entry(messages) else {
+            return generate.await;
+        };
+        if let Some(decision) = cache.get(&key) {
+            tracing::debug!("Using cached LLM response {}", key);
+            return Ok(single_decision(decision));
+        }
+        let chunks = generate.await?;
+        Ok(chunks
+            .inspect(move |chunk| {
+                if let Ok(DecisionChunk::Decision(decision)) = chunk {
+                    cache.put(&key, decision);
+                }
+            })
+            .boxed())
+    }
 }
 
 /// Provider information.
@@ -42,3 +169,251 @@ pub struct ProviderInfo {
     /// Model identifier.
     pub model: String,
 }
+
+/// How a provider sends the API key.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum KeyAuth {
+    /// `Authorization: Bearer <key>`.
+    Bearer,
+    /// The key as the value of the named header.
+    Header(&'static str),
+    /// An Azure AD bearer token if one is configured, else an `api-key` header.
+    Azure,
+    /// Requests are not authenticated.
+    None,
+}
+
+/// Where a provider lists its models.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ModelListing {
+    /// Endpoint path.
+    pub path: &'static str,
+    /// Response field holding the array of models.
+    pub list: &'static str,
+    /// Field of each model holding its ID.
+    pub id: &'static str,
+    /// Prefix stripped from each ID.
+    pub prefix: &'static str,
+}
+
+/// Fixed details of a provider's HTTP API.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ApiSpec {
+    /// Base URL used when `base_url` is not configured.
+    pub default_base_url: &'static str,
+    /// How the API key is sent.
+    pub auth: KeyAuth,
+    /// Headers sent with every request.
+    pub headers: &'static [(&'static str, &'static str)],
+    /// Model listing endpoint.
+    pub models: ModelListing,
+}
+
+/// Configuration, prompt and HTTP client of a provider.
+#[derive(Debug, Clone)]
+pub(crate) struct ProviderBase {
+    /// Provider configuration.
+    pub config: ProviderConfig,
+    /// System prompt.
+    pub system_prompt: SystemPrompt,
+    /// HTTP client.
+    pub http: reqwest::Client,
+    /// Details of the provider's API.
+    api: ApiSpec,
+    /// Query parameters appended to every endpoint.
+    query: Vec<(&'static str, String)>,
+}
+
+impl ProviderBase {
+    /// Create the shared state of a provider speaking `api`.
+    pub fn new(config: ProviderConfig, system_prompt: SystemPrompt, api: ApiSpec) -> Self {
+        Self {
+            config,
+            system_prompt,
+            http: reqwest::Client::new(),
+            api,
+            query: Vec::new(),
+        }
+    }
+
+    /// Append a query parameter to every endpoint.
+    #[must_use]
+    pub fn with_query(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.query.push((name, value.into()));
+        self
+    }
+
+    /// Switch the model used for subsequent requests.
+    ///
+    /// An Azure deployment is switched too, assuming it is named after its
+    /// model.
+    pub fn set_model(&mut self, model: impl Into<String>) {
+        let model = model.into();
+        if let Some(azure) = &mut self.config.azure {
+            azure.deployment.clone_from(&model);
+        }
+        self.config.model = model;
+    }
+
+    /// Replace the system prompt used for subsequent requests.
+    pub fn set_system_prompt(&mut self, prompt: SystemPrompt) {
+        self.system_prompt = prompt;
+    }
+
+    /// Replace the tools offered to the model.
+    pub fn set_tools(&mut self, tools: Vec<OpenAiToolDefinition>) {
+        self.config.tools = Some(tools);
+    }
+
+    /// Resolve an endpoint path against the configured base URL.
+    pub fn endpoint(&self, path: &str) -> Result<Url, LlmError> {
+        let mut url =
+            resolve_endpoint(self.config.base_url.as_ref(), self.api.default_base_url, path)?;
+        if !self.query.is_empty() {
+            url.query_pairs_mut()
+                .extend_pairs(self.query.iter().map(|(name, value)| (*name, value.as_str())));
+        }
+        Ok(url)
+    }
+
+    /// Add the authentication and fixed headers.
+    pub fn authorize(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        for (name, value) in self.api.headers {
+            request = request.header(*name, *value);
+        }
+        if let KeyAuth::Azure = self.api.auth
+            && let Some(token) = self.config.azure.as_ref().and_then(|a| a.ad_token.as_ref())
+        {
+            return request.bearer_auth(token.expose());
+        }
+        let Some(key) = &self.config.api_key else {
+            return request;
+        };
+        match self.api.auth {
+            KeyAuth::None => request,
+            KeyAuth::Bearer => request.bearer_auth(key.expose()),
+            KeyAuth::Header(name) => request.header(name, key.expose()),
+            KeyAuth::Azure => request.header("api-key", key.expose()),
+        }
+    }
+
+    /// Messages of a one-off prompt.
+    pub fn prompt_messages(&self, prompt: &str) -> Vec<PromptMessage> {
+        PromptBuilder::new()
+            .with_system_prompt(self.system_prompt.clone())
+            .user(prompt)
+            .build()
+    }
+
+    /// List the model IDs available to the configured credentials.
+    ///
+    /// # Errors
+    /// Returns an error if the endpoint is unreachable or rejects the credentials.
+    #[tracing::instrument(name = "llm_list_models", skip_all)]
+    pub async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        let listing = self.api.models;
+        let request = self.authorize(self.http.get(self.endpoint(listing.path)?));
+        let response = request.send().await.map_err(|e| LlmError::ConnectionFailed {
+            message: e.to_string(),
+        })?;
+        if !response.status().is_success() {
+            return Err(LlmError::from_status(response.status()));
+        }
+
+        let body: Value = response.json().await.map_err(|e| LlmError::ApiError {
+            message: format!("Invalid models response: {}", e),
+        })?;
+
+        Ok(body
+            .get(listing.list)
+            .and_then(|m| m.as_array())
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m.get(listing.id).and_then(|id| id.as_str()))
+                    .map(|id| id.trim_start_matches(listing.prefix).to_string())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Provider name and model.
+    pub fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            provider: self.config.provider_type.clone(),
+            model: self.config.model.clone(),
+        }
+    }
+}
+
+/// Resolve an endpoint path against a base URL, falling back to `default`.
+pub(crate) fn resolve_endpoint(
+    base_url: Option<&Url>,
+    default: &str,
+    path: &str,
+) -> Result<Url, LlmError> {
+    let invalid = |e: url::ParseError| LlmError::InvalidConfig {
+        message: format!("Invalid endpoint URL: {}", e),
+    };
+    let mut base = match base_url {
+        Some(url) => url.clone(),
+        None => Url::parse(default).map_err(invalid)?,
+    };
+    // Without a trailing slash `join` would replace the last path segment
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    base.join(path).map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_endpoint() {
+        let base: Url = "http://localhost:11434/v1".parse().unwrap();
+        let url = resolve_endpoint(Some(&base), "https://api.openai.com/v1/", "models").unwrap();
+        assert_eq!(url.as_str(), "http://localhost:11434/v1/models");
+
+        // A bad default is a configuration error, not a panic
+        assert!(matches!(
+            resolve_endpoint(None, "not a url", "models"),
+            Err(LlmError::InvalidConfig { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_models_with_headers_and_prefix() {
+        use wiremock::matchers::{header, method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        const API: ApiSpec = ApiSpec {
+            default_base_url: "https://invalid/",
+            auth: KeyAuth::Header("x-goog-api-key"),
+            headers: &[("x-version", "1")],
+            models: ModelListing { path: "models", list: "models", id: "name", prefix: "models/" },
+        };
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(query_param("api-version", "2"))
+            .and(header("x-goog-api-key", "key"))
+            .and(header("x-version", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "models": [{ "name": "models/gemini-1.5-pro" }, { "name": "models/gemini-2.0" }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = ProviderConfig {
+            base_url: Some(format!("{}/v1", server.uri()).parse().unwrap()),
+            api_key: Some("key".into()),
+            ..ProviderConfig::default()
+        };
+        let base =
+            ProviderBase::new(config, SystemPrompt::default(), API).with_query("api-version", "2");
+        assert_eq!(base.list_models().await.unwrap(), ["gemini-1.5-pro", "gemini-2.0"]);
+    }
+}
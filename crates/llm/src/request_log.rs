@@ -0,0 +1,272 @@
+//! Prompt and response debug log.
+//!
+//! When `llm.log-requests` is enabled every request body and raw response
+//! is appended to a JSON Lines file so bad generations can be diagnosed.
+//! Entries have credentials masked before they are written, plus whatever
+//! the caller's redactor removes (the CLI plugs in PII masking), and the
+//! file is rotated once it grows past a size limit. Entries may be
+//! compressed and encrypted like other persisted files.
+
+use postgres_agent_util::codec::FileCodec;
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Credential patterns, each keeping its first capture group.
+static SECRET_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    vec![
+        // Authorization headers
+        Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._~+/=-]+").unwrap(),
+        // OpenAI and Google API keys
+        Regex::new(r"()\b(?:sk-[A-Za-z0-9_-]{16,}|AIza[A-Za-z0-9_-]{30,})").unwrap(),
+        // Passwords in connection URLs
+        Regex::new(r"([a-z][a-z0-9+.-]*://[^:/\s@]+:)[^@\s]+").unwrap(),
+        // `password = ...`, `"api_key": "..."` and similar assignments
+        Regex::new(
+            r#"(?i)((?:password|passwd|pwd|api[_-]?key|secret|token)"?\s*[:=]\s*"?)[^"\s,;}]+"#
+        )
+        .unwrap(),
+    ]
+});
+
+/// Extra redaction applied after credentials are masked.
+type RedactFn = dyn Fn(&str) -> String + Send + Sync;
+
+/// Mask credentials in text.
+#[must_use]
+pub fn redact(text: &str) -> String {
+    let mut result = text.to_string();
+    for pattern in SECRET_PATTERNS.iter() {
+        result = pattern.replace_all(&result, "${1}***").into_owned();
+    }
+    result
+}
+
+/// One logged exchange.
+#[derive(Debug, Serialize)]
+struct LogEntry<'a> {
+    /// Sequence number within this process.
+    id: u64,
+    /// Unix timestamp in seconds.
+    timestamp: u64,
+    /// Provider name.
+    provider: &'a str,
+    /// Model identifier.
+    model: &'a str,
+    /// Redacted request body.
+    request: serde_json::Value,
+    /// Redacted raw response body.
+    response: String,
+}
+
+/// Rotating JSON Lines log of LLM requests and responses.
+pub struct RequestLog {
+    /// Active log file.
+    path: PathBuf,
+    /// Size at which the file is rotated.
+    max_bytes: u64,
+    /// Rotated files to keep (`<path>.1` is the newest).
+    max_files: usize,
+    /// Next entry ID.
+    next_id: AtomicU64,
+    /// Serializes writes and rotation.
+    lock: Mutex<()>,
+    /// Encoding of entries.
+    codec: FileCodec,
+    /// Applied to every string after [`redact`].
+    redactor: Option<Arc<RedactFn>>,
+}
+
+impl RequestLog {
+    /// Create a log at `path` that rotates at 10 MiB and keeps 3 old files.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 3,
+            next_id: AtomicU64::new(1),
+            lock: Mutex::new(()),
+            codec: FileCodec::default(),
+            redactor: None,
+        }
+    }
+
+    /// Write entries with `codec`.
+    #[must_use]
+    pub fn with_codec(mut self, codec: FileCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Also pass every logged string through `redactor`, e.g. a PII masker.
+    #[must_use]
+    pub fn with_redactor(
+        mut self,
+        redactor: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Set the rotation size.
+    #[must_use]
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Path of the active log file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record an exchange; `response` is the raw body or an error message.
+    ///
+    /// Write failures are logged and otherwise ignored so debugging never
+    /// breaks a request. A `tracing` event carrying the entry ID is emitted
+    /// inside the current span, linking the entry to the agent run's trace.
+    pub fn record(&self, provider: &str, model: &str, request: &impl Serialize, response: &str) {
+        let mut request = serde_json::to_value(request).unwrap_or_default();
+        self.redact_value(&mut request);
+        let entry = LogEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            provider,
+            model,
+            request,
+            response: self.redact(response),
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Failed to serialize LLM request log entry: {}", e);
+                return;
+            }
+        };
+
+        match self.append(&line) {
+            Ok(()) => tracing::debug!(log_entry = entry.id, "Logged LLM exchange"),
+            Err(e) => {
+                tracing::warn!("Failed to write LLM request log {}: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    /// Mask credentials, then apply the redactor.
+    fn redact(&self, text: &str) -> String {
+        let result = redact(text);
+        match &self.redactor {
+            Some(redactor) => redactor(&result),
+            None => result,
+        }
+    }
+
+    /// Redact every string in a JSON value, leaving its structure intact.
+    fn redact_value(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => *s = self.redact(s),
+            serde_json::Value::Array(items) => {
+                items.iter_mut().for_each(|item| self.redact_value(item));
+            }
+            serde_json::Value::Object(map) => {
+                map.values_mut().for_each(|item| self.redact_value(item));
+            }
+            _ => {}
+        }
+    }
+
+    /// Append a line, rotating first if the file is full.
+    fn append(&self, line: &str) -> std::io::Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_bytes) {
+            self.rotate()?;
+        }
+
+        self.codec.append(&self.path, format!("{}\n", line).as_bytes())
+    }
+
+    /// Shift `<path>.N` to `<path>.N+1`, dropping the oldest.
+    fn rotate(&self) -> std::io::Result<()> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+
+        for n in (1..self.max_files).rev() {
+            if rotated(n).exists() {
+                fs::rename(rotated(n), rotated(n + 1))?;
+            }
+        }
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)
+        } else {
+            fs::rename(&self.path, rotated(1))
+        }
+    }
+}
+
+impl fmt::Debug for RequestLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestLog")
+            .field("path", &self.path)
+            .field("max_bytes", &self.max_bytes)
+            .field("max_files", &self.max_files)
+            .field("codec", &self.codec)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        let text = "Authorization: Bearer abc.def; connect to postgres://app:hunter2@db/prod \
+                    with password=s3cret, key sk-proj-abcdefghijklmnop1234";
+        let redacted = redact(text);
+
+        for leaked in ["abc.def", "hunter2", "s3cret", "abcdefghijklmnop"] {
+            assert!(!redacted.contains(leaked), "{} leaked in {}", leaked, redacted);
+        }
+        assert!(redacted.contains("postgres://app:***@db/prod"));
+    }
+
+    #[test]
+    fn test_redact_value_keeps_structure() {
+        let log = RequestLog::new("llm.log").with_redactor(|text| text.replace("jane", "[NAME]"));
+        let mut value = serde_json::json!({
+            "messages": [{ "content": "{\"password\": \"s3cret\"} from jane" }],
+            "max_tokens": 1234567890,
+        });
+        log.redact_value(&mut value);
+
+        assert_eq!(value["messages"][0]["content"], "{\"password\": \"***\"} from [NAME]");
+        assert_eq!(value["max_tokens"], 1234567890);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = RequestLog::new(dir.path().join("llm.log")).with_max_bytes(1);
+
+        log.record("openai", "gpt-4o", &serde_json::json!({ "n": 1 }), "first");
+        log.record("openai", "gpt-4o", &serde_json::json!({ "n": 2 }), "second");
+
+        let current = fs::read_to_string(log.path()).unwrap();
+        let rotated = fs::read_to_string(dir.path().join("llm.log.1")).unwrap();
+        assert!(current.contains("second"));
+        assert!(rotated.contains("first"));
+    }
+}
@@ -0,0 +1,423 @@
+//! Streaming responses.
+//!
+//! Providers stream completions as server-sent events (OpenAI, Azure
+//! OpenAI, Anthropic) or newline-delimited JSON (Ollama). Each event is
+//! parsed into [`Delta`]s; text deltas make up a [`TokenStream`], and a
+//! [`DecisionAssembler`] turns them into a [`DecisionStream`] that yields
+//! the final answer as it is written, then the decision.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+use serde_json::Value;
+
+use super::conversion::{text_decision, tool_call_decision};
+use super::decision::TokenUsage;
+use super::error::LlmError;
+
+/// Text of a completion, in pieces as the model writes it.
+pub type TokenStream = BoxStream<'static, Result<String, LlmError>>;
+
+/// A decision, preceded by the text of the final answer as it is written.
+pub type DecisionStream = BoxStream<'static, Result<DecisionChunk, LlmError>>;
+
+/// An item of a [`DecisionStream`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecisionChunk {
+    /// More text of the answer.
+    ///
+    /// Text written before a tool call arrives this way too; the decision
+    /// then turns out to be the tool call.
+    AnswerDelta(String),
+    /// The decision in its JSON form; always the last item.
+    Decision(Value),
+}
+
+/// Events of a streamed response, one string each.
+pub(crate) type EventStream = BoxStream<'static, Result<String, LlmError>>;
+
+/// A piece of a streamed response.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Delta {
+    /// More text.
+    Text(String),
+    /// Part of a tool call; later parts of a call carry the same index and
+    /// more of its arguments.
+    ToolCall {
+        /// Position of the call in the response.
+        index: usize,
+        /// Call ID, sent with the first part.
+        id: Option<String>,
+        /// Tool name, sent with the first part.
+        name: Option<String>,
+        /// More of the arguments as JSON text.
+        arguments: String,
+    },
+    /// Tokens used so far; later counts replace earlier ones.
+    Usage(TokenUsage),
+}
+
+/// A tool call being streamed.
+#[derive(Debug, Default)]
+struct PartialCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Collects the deltas of a response into a decision.
+#[derive(Debug, Default)]
+pub(crate) struct DecisionAssembler {
+    /// Text so far.
+    text: String,
+    /// Length of the answer text already handed out.
+    shown: usize,
+    /// Tool calls by index.
+    calls: BTreeMap<usize, PartialCall>,
+    /// Tokens reported.
+    usage: TokenUsage,
+}
+
+impl DecisionAssembler {
+    /// Add a delta, returning any new answer text.
+    pub(crate) fn push(&mut self, delta: Delta) -> Option<String> {
+        match delta {
+            Delta::Text(text) => self.text.push_str(&text),
+            Delta::ToolCall {
+                index,
+                id,
+                name,
+                arguments,
+            } => {
+                let call = self.calls.entry(index).or_default();
+                if let Some(id) = id {
+                    call.id = id;
+                }
+                if let Some(name) = name {
+                    call.name.push_str(&name);
+                }
+                call.arguments.push_str(&arguments);
+                return None;
+            }
+            Delta::Usage(usage) => {
+                // Providers report prompt and completion tokens in separate
+                // events, or running totals
+                self.usage.prompt_tokens = self.usage.prompt_tokens.max(usage.prompt_tokens);
+                self.usage.completion_tokens =
+                    self.usage.completion_tokens.max(usage.completion_tokens);
+                return None;
+            }
+        }
+        if !self.calls.is_empty() {
+            return None;
+        }
+
+        let answer = answer_so_far(&self.text)?;
+        let delta = answer.get(self.shown..).filter(|delta| !delta.is_empty())?.to_string();
+        self.shown = answer.len();
+        Some(delta)
+    }
+
+    /// The decision of the complete response, with its token usage: its
+    /// first tool call, or else its text.
+    pub(crate) fn finish(self) -> Result<Value, LlmError> {
+        let mut decision = if let Some(call) = self.calls.into_values().next() {
+            // Providers that assign no call IDs get the tool name, as in
+            // non-streamed responses
+            let id = if call.id.is_empty() { &call.name } else { &call.id };
+            tool_call_decision(&call.name, Value::String(call.arguments), id)?
+        } else if self.text.trim().is_empty() {
+            return Err(LlmError::NoResponse);
+        } else {
+            text_decision(&self.text)
+        };
+        if !self.usage.is_empty() {
+            self.usage.attach(&mut decision);
+        }
+        Ok(decision)
+    }
+}
+
+/// The answer in the text of a response so far: the text itself, or the
+/// `answer` of a JSON final answer decision, decoded as far as it goes.
+fn answer_so_far(text: &str) -> Option<String> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    if !text.starts_with('{') {
+        return Some(text.to_string());
+    }
+
+    let key = text.find("\"answer\"")?;
+    if text[..key].contains("\"tool_call\"") || text[..key].contains("\"reasoning\"") {
+        return None;
+    }
+    let value = text[key + "\"answer\"".len()..]
+        .trim_start()
+        .strip_prefix(':')?
+        .trim_start()
+        .strip_prefix('"')?;
+    Some(decode_partial_string(value))
+}
+
+/// Decode the start of a JSON string body, up to its closing quote or the
+/// last complete character.
+fn decode_partial_string(body: &str) -> String {
+    let mut decoded = String::new();
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => {
+                let rest = chars.as_str();
+                let Some((c, len)) = decode_escape(rest) else {
+                    break;
+                };
+                decoded.push(c);
+                chars = rest[len..].chars();
+            }
+            c => decoded.push(c),
+        }
+    }
+    decoded
+}
+
+/// Decode the escape sequence at the start of `rest` (after the backslash),
+/// returning the character and the length of the sequence; `None` if it is
+/// incomplete.
+fn decode_escape(rest: &str) -> Option<(char, usize)> {
+    let c = match rest.chars().next()? {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        'b' => '\u{8}',
+        'f' => '\u{c}',
+        'u' => {
+            let unit = u16::from_str_radix(rest.get(1..5)?, 16).ok()?;
+            if !(0xD800..0xDC00).contains(&unit) {
+                return Some((char::from_u32(u32::from(unit)).unwrap_or('\u{fffd}'), 5));
+            }
+            // A surrogate pair is decoded once its second half arrives
+            let low = u16::from_str_radix(rest.get(5..11)?.strip_prefix("\\u")?, 16).ok()?;
+            let c = char::decode_utf16([unit, low]).next()?.unwrap_or('\u{fffd}');
+            return Some((c, 11));
+        }
+        other => other,
+    };
+    Some((c, c.len_utf8().max(1)))
+}
+
+/// Split a byte stream into lines, without line endings.
+pub(crate) fn lines<S, B, E>(bytes: S) -> EventStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: Display + Send + 'static,
+{
+    let state = (bytes.boxed(), Vec::new(), false);
+    stream::unfold(state, |(mut bytes, mut buffer, mut done)| async move {
+        loop {
+            if let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line[..end]).trim_end_matches('\r').to_string();
+                return Some((Ok(line), (bytes, buffer, done)));
+            }
+            if done {
+                if buffer.is_empty() {
+                    return None;
+                }
+                let line = String::from_utf8_lossy(&buffer).trim_end_matches('\r').to_string();
+                return Some((Ok(line), (bytes, Vec::new(), true)));
+            }
+            match bytes.next().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(chunk.as_ref()),
+                Some(Err(e)) => {
+                    let error = LlmError::ConnectionFailed {
+                        message: e.to_string(),
+                    };
+                    return Some((Err(error), (bytes, Vec::new(), true)));
+                }
+                None => done = true,
+            }
+        }
+    })
+    .boxed()
+}
+
+/// The data of server-sent events, up to OpenAI's `[DONE]` marker.
+pub(crate) fn sse_data(lines: EventStream) -> EventStream {
+    lines
+        .try_filter_map(|line| {
+            let data = line
+                .strip_prefix("data:")
+                .map(|data| data.strip_prefix(' ').unwrap_or(data).to_string());
+            std::future::ready(Ok(data))
+        })
+        .try_take_while(|data| std::future::ready(Ok(data != "[DONE]")))
+        .boxed()
+}
+
+/// Lines of newline-delimited JSON, skipping blank ones.
+pub(crate) fn json_lines(lines: EventStream) -> EventStream {
+    lines
+        .try_filter(|line| std::future::ready(!line.trim().is_empty()))
+        .boxed()
+}
+
+/// Parser of one streamed event into deltas.
+pub(crate) type EventParser = fn(&str) -> Result<Vec<Delta>, LlmError>;
+
+/// The text of a streamed completion.
+pub(crate) fn token_stream(events: EventStream, parse: EventParser) -> TokenStream {
+    events
+        .map(move |event| parse(&event?))
+        .map_ok(|deltas| {
+            let text: String = deltas
+                .into_iter()
+                .filter_map(|delta| match delta {
+                    Delta::Text(text) => Some(text),
+                    Delta::ToolCall { .. } | Delta::Usage(_) => None,
+                })
+                .collect();
+            stream::iter(Some(text).filter(|text| !text.is_empty()).map(Ok::<_, LlmError>))
+        })
+        .try_flatten()
+        .boxed()
+}
+
+/// A streamed decision; `log` gets the raw events once the stream ends.
+pub(crate) fn decision_stream(
+    events: EventStream,
+    parse: EventParser,
+    log: impl FnOnce(&str) + Send + 'static,
+) -> DecisionStream {
+    let state = Some((events, DecisionAssembler::default(), String::new(), log));
+    stream::unfold(state, move |state| async move {
+        let (mut events, mut assembler, mut raw, log) = state?;
+        loop {
+            let Some(event) = events.next().await else {
+                log(&raw);
+                return Some((assembler.finish().map(DecisionChunk::Decision), None));
+            };
+            let deltas = match event.and_then(|event| {
+                raw.push_str(&event);
+                raw.push('\n');
+                parse(&event)
+            }) {
+                Ok(deltas) => deltas,
+                Err(e) => {
+                    log(&raw);
+                    return Some((Err(e), None));
+                }
+            };
+            let answer: String = deltas.into_iter().filter_map(|d| assembler.push(d)).collect();
+            if !answer.is_empty() {
+                let chunk = DecisionChunk::AnswerDelta(answer);
+                return Some((Ok(chunk), Some((events, assembler, raw, log))));
+            }
+        }
+    })
+    .boxed()
+}
+
+/// A stream of one decision, for providers that do not stream.
+pub(crate) fn single_decision(decision: Value) -> DecisionStream {
+    stream::once(std::future::ready(Ok(DecisionChunk::Decision(decision)))).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Delta {
+        Delta::Text(text.to_string())
+    }
+
+    #[test]
+    fn test_answer_so_far() {
+        assert_eq!(answer_so_far("  "), None);
+        assert_eq!(answer_so_far("There are 3 ").as_deref(), Some("There are 3 "));
+        assert_eq!(answer_so_far(r#"{"type": "final_answer", "ans"#), None);
+        assert_eq!(
+            answer_so_far(r#"{"type": "final_answer", "answer": "Two\nlines, \"a\"#).as_deref(),
+            Some("Two\nlines, \"a")
+        );
+        // Incomplete escapes, like the trailing backslash above, wait for the rest
+        assert_eq!(answer_so_far(r#"{"answer": "caf\u00"#).as_deref(), Some("caf"));
+        assert_eq!(answer_so_far(r#"{"answer": "café \ud83d"#).as_deref(), Some("café "));
+        assert_eq!(answer_so_far(r#"{"answer": "😀""#).as_deref(), Some("😀"));
+        assert_eq!(answer_so_far(r#"{"answer": "done", "x": 1}"#).as_deref(), Some("done"));
+        assert_eq!(answer_so_far(r#"{"type": "tool_call", "arguments": {"answer": "x"#), None);
+    }
+
+    #[test]
+    fn test_decision_assembler() {
+        let mut assembler = DecisionAssembler::default();
+        let deltas = [r#"{"type": "final_"#, r#"answer", "answer": "Hel"#, r#"lo\n"#, r#""}"#];
+        let shown: Vec<Option<String>> = deltas.iter().map(|d| assembler.push(text(d))).collect();
+        assert_eq!(shown, [None, Some("Hel".to_string()), Some("lo\n".to_string()), None]);
+        assert_eq!(
+            assembler.finish().unwrap(),
+            serde_json::json!({"type": "final_answer", "answer": "Hello\n"})
+        );
+
+        // Tool call arguments arrive in pieces
+        let mut assembler = DecisionAssembler::default();
+        assert_eq!(assembler.push(text("Checking")), Some("Checking".to_string()));
+        let first = Delta::ToolCall {
+            index: 1,
+            id: Some("call_1".to_string()),
+            name: Some("execute_query".to_string()),
+            arguments: r#"{"sql": "SEL"#.to_string(),
+        };
+        let rest = Delta::ToolCall {
+            index: 1,
+            id: None,
+            name: None,
+            arguments: r#"ECT 1"}"#.to_string(),
+        };
+        assert_eq!(assembler.push(first), None);
+        assert_eq!(assembler.push(rest), None);
+        assert_eq!(assembler.push(text(" more")), None);
+        assert_eq!(assembler.push(Delta::Usage(TokenUsage::new(40u32, 1u32))), None);
+        assert_eq!(assembler.push(Delta::Usage(TokenUsage::new(0u32, 12u32))), None);
+        let mut decision = assembler.finish().unwrap();
+        assert_eq!(TokenUsage::take(&mut decision), Some(TokenUsage::new(40u32, 12u32)));
+        assert_eq!(decision["name"], "execute_query");
+        assert_eq!(decision["arguments"], serde_json::json!({"sql": "SELECT 1"}));
+        assert_eq!(decision["call_id"], "call_1");
+
+        assert!(matches!(DecisionAssembler::default().finish(), Err(LlmError::NoResponse)));
+    }
+
+    #[tokio::test]
+    async fn test_decision_stream() {
+        let bytes: Vec<Result<&[u8], LlmError>> = vec![
+            Ok(&b"data: The answer\n\ndata: is 4"[..]),
+            Ok(&b"2.\r\n: comment\ndata: [DONE]\ndata: ignored\n"[..]),
+        ];
+        let events = sse_data(lines(stream::iter(bytes)));
+        let parse: EventParser = |event| Ok(vec![Delta::Text(event.to_string())]);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let chunks: Vec<DecisionChunk> = decision_stream(events, parse, move |raw| {
+            sender.send(raw.to_string()).unwrap();
+        })
+        .try_collect()
+        .await
+        .unwrap();
+        assert_eq!(
+            chunks,
+            [
+                DecisionChunk::AnswerDelta("The answer".to_string()),
+                DecisionChunk::AnswerDelta("is 42.".to_string()),
+                DecisionChunk::Decision(serde_json::json!({
+                    "type": "final_answer",
+                    "answer": "The answeris 42."
+                })),
+            ]
+        );
+        assert_eq!(receiver.recv().unwrap(), "The answer\nis 42.\n");
+    }
+}
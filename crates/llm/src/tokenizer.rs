@@ -0,0 +1,114 @@
+//! Token counting.
+//!
+//! OpenAI models are counted with their tiktoken encoding. Models without a
+//! public tokenizer, such as Gemini, Claude or local models, are counted with
+//! the configured fallback, which is close enough for context budgeting.
+
+use std::fmt;
+
+use postgres_agent_config::TokenizerFallback;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+/// Characters per token of the approximate counter.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Counts tokens as a model would.
+#[derive(Clone, Copy, Default)]
+pub struct TokenCounter {
+    /// Encoding; `None` for the approximate counter.
+    bpe: Option<&'static CoreBPE>,
+    /// Encoding name.
+    name: &'static str,
+}
+
+impl TokenCounter {
+    /// Counter for `model`, using `fallback` if its encoding is unknown.
+    #[must_use]
+    pub fn for_model(model: &str, fallback: TokenizerFallback) -> Self {
+        match get_tokenizer(model) {
+            Some(Tokenizer::O200kBase) => Self::o200k_base(),
+            Some(Tokenizer::Cl100kBase) => Self::cl100k_base(),
+            _ => match fallback {
+                TokenizerFallback::O200kBase => Self::o200k_base(),
+                TokenizerFallback::Cl100kBase => Self::cl100k_base(),
+                TokenizerFallback::Approximate => Self::approximate(),
+            },
+        }
+    }
+
+    /// Counter assuming four characters per token.
+    #[must_use]
+    pub fn approximate() -> Self {
+        Self {
+            bpe: None,
+            name: "approximate",
+        }
+    }
+
+    /// Counter for the `o200k_base` encoding.
+    fn o200k_base() -> Self {
+        Self {
+            bpe: Some(tiktoken_rs::o200k_base_singleton()),
+            name: "o200k_base",
+        }
+    }
+
+    /// Counter for the `cl100k_base` encoding.
+    fn cl100k_base() -> Self {
+        Self {
+            bpe: Some(tiktoken_rs::cl100k_base_singleton()),
+            name: "cl100k_base",
+        }
+    }
+
+    /// Number of tokens in `text`.
+    #[must_use]
+    pub fn count(&self, text: &str) -> usize {
+        match self.bpe {
+            Some(bpe) => bpe.encode_ordinary(text).len(),
+            None => text.len() / CHARS_PER_TOKEN,
+        }
+    }
+
+    /// Name of the encoding.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        if self.bpe.is_none() {
+            "approximate"
+        } else {
+            self.name
+        }
+    }
+}
+
+impl fmt::Debug for TokenCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("TokenCounter").field(&self.name()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_per_model() {
+        let fallback = TokenizerFallback::Approximate;
+        assert_eq!(TokenCounter::for_model("gpt-4o-mini", fallback).name(), "o200k_base");
+        assert_eq!(TokenCounter::for_model("gpt-4-0613", fallback).name(), "cl100k_base");
+        assert_eq!(TokenCounter::for_model("gemini-1.5-pro", fallback).name(), "approximate");
+        assert_eq!(
+            TokenCounter::for_model("gemini-1.5-pro", TokenizerFallback::Cl100kBase).name(),
+            "cl100k_base"
+        );
+    }
+
+    #[test]
+    fn test_count() {
+        let counter = TokenCounter::for_model("gpt-4o", TokenizerFallback::Approximate);
+        assert_eq!(counter.count("hello world"), 2);
+        assert_eq!(counter.count(""), 0);
+        assert_eq!(TokenCounter::approximate().count("SELECT 1 FROM t"), 3);
+    }
+}
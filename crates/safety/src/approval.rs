@@ -0,0 +1,456 @@
+//! Admin approval over a second channel.
+//!
+//! An operation that needs admin approval is written to a shared directory
+//! as `<id>.json`. A different user decides on it, e.g. with
+//! `pg-agent approve <id>`, while the process that asked waits for the
+//! decision and then carries on. The user who asked cannot approve their
+//! own request, which gives two-person control over destructive operations.
+//!
+//! Users are told apart by their OS uid; user names, taken from `$USER`, are
+//! only shown to people and can be set to anything. A decision is
+//! written to `<id>.decision.json` by the user making it, and an approval
+//! only counts if that file is owned by someone other than the owner of
+//! the request, so the requester cannot approve by editing files.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::confirmation::{ConfirmationRequest, ConfirmationStatus};
+
+/// Errors of the approval store.
+#[derive(Debug, Error)]
+pub enum ApprovalError {
+    /// No request with the ID.
+    #[error("No approval request '{0}'")]
+    NotFound(String),
+
+    /// The ID could name a path outside the store.
+    #[error("Invalid approval request ID '{0}'")]
+    InvalidId(String),
+
+    /// The request was answered or expired before.
+    #[error("Approval request '{id}' is already {status}")]
+    Closed {
+        /// Request ID.
+        id: String,
+        /// Its status.
+        status: &'static str,
+    },
+
+    /// The user who asked tried to approve.
+    #[error("'{user}' requested '{id}' and cannot approve it; another user must")]
+    SelfApproval {
+        /// Request ID.
+        id: String,
+        /// The user.
+        user: String,
+    },
+
+    /// Reading or writing the store failed.
+    #[error("Approval store error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// A request file is not valid.
+    #[error("Failed to parse {path}: {message}")]
+    Parse {
+        /// The file.
+        path: PathBuf,
+        /// Parser message.
+        message: String,
+    },
+}
+
+/// A decision on a request, kept in a file of its own written by the user
+/// who made it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Decision {
+    /// The request as it was when decided.
+    request: ConfirmationRequest,
+    /// Whether it was approved.
+    approved: bool,
+    /// User who decided.
+    decided_by: String,
+    /// When.
+    decided_at: DateTime<Utc>,
+}
+
+impl Decision {
+    /// Whether the decision answers `record` and, if it approves, was made
+    /// by another user: `requester` and `decider` are the owners of the
+    /// request and decision files.
+    fn is_genuine(
+        &self,
+        record: &ApprovalRecord,
+        requester: Option<u32>,
+        decider: Option<u32>,
+    ) -> bool {
+        let same_request =
+            serde_json::to_value(&self.request).ok() == serde_json::to_value(&record.request).ok();
+        let self_approved = match (requester, decider) {
+            (Some(requester), Some(decider)) => requester == decider,
+            _ => self.decided_by == record.requested_by,
+        };
+        same_request && !(self.approved && self_approved)
+    }
+}
+
+/// A request for admin approval and its decision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalRecord {
+    /// The request; its status is the decision.
+    pub request: ConfirmationRequest,
+    /// User who asked.
+    pub requested_by: String,
+    /// User who approved or denied.
+    #[serde(default)]
+    pub decided_by: Option<String>,
+    /// When it was approved or denied.
+    #[serde(default)]
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+impl ApprovalRecord {
+    /// Status of the request; pending requests past their TTL are expired.
+    #[must_use]
+    pub fn status(&self) -> ConfirmationStatus {
+        match self.request.status {
+            ConfirmationStatus::Pending if self.request.is_expired() => {
+                ConfirmationStatus::Expired
+            }
+            status => status,
+        }
+    }
+}
+
+/// Directory of approval requests, one `<id>.json` file each.
+#[derive(Debug, Clone)]
+pub struct ApprovalStore {
+    /// Directory holding the requests.
+    dir: PathBuf,
+}
+
+impl ApprovalStore {
+    /// Open the store in the given directory; it is created on first submit.
+    #[must_use]
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Get the store directory.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write a request to the store as the current user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request ID is invalid or the file cannot be
+    /// written.
+    pub fn submit(&self, request: &ConfirmationRequest) -> Result<ApprovalRecord, ApprovalError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let record = ApprovalRecord {
+            request: request.clone(),
+            requested_by: current_user(),
+            decided_by: None,
+            decided_at: None,
+        };
+        self.write(&self.path(&request.id)?, &record)?;
+        Ok(record)
+    }
+
+    /// Get a request by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the ID is invalid or unknown, or the file cannot
+    /// be read.
+    pub fn get(&self, id: &str) -> Result<ApprovalRecord, ApprovalError> {
+        let path = self.path(id)?;
+        let mut record: ApprovalRecord =
+            read(&path)?.ok_or_else(|| ApprovalError::NotFound(id.to_string()))?;
+
+        let decision_path = self.decision_path(id)?;
+        let decision: Option<Decision> = match read(&decision_path) {
+            Ok(decision) => decision,
+            Err(e) => {
+                tracing::warn!("Ignoring approval decision: {}", e);
+                None
+            }
+        };
+        let Some(decision) = decision else {
+            return Ok(record);
+        };
+        if !decision.is_genuine(&record, owner(&path), owner(&decision_path)) {
+            tracing::warn!(
+                "Ignoring decision on approval request '{}' not made by another user",
+                id
+            );
+            return Ok(record);
+        }
+        record.request.status = if decision.approved {
+            ConfirmationStatus::Approved
+        } else {
+            ConfirmationStatus::Denied
+        };
+        record.decided_by = Some(decision.decided_by);
+        record.decided_at = Some(decision.decided_at);
+        Ok(record)
+    }
+
+    /// Every request in the store, oldest first; unreadable files are
+    /// skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory exists but cannot be read.
+    pub fn list(&self) -> Result<Vec<ApprovalRecord>, ApprovalError> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            result => result?,
+        };
+        let mut records: Vec<ApprovalRecord> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                self.get(name.strip_suffix(".json")?).ok()
+            })
+            .collect();
+        records.sort_by_key(|record| record.request.created_at);
+        Ok(records)
+    }
+
+    /// Approve or deny a pending request as the current user.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request is unknown, answered or expired, if
+    /// the current user asked for it and tries to approve it, or if the file
+    /// cannot be written.
+    pub fn decide(&self, id: &str, approve: bool) -> Result<ApprovalRecord, ApprovalError> {
+        let record = self.get(id)?;
+        let status = record.status();
+        if status != ConfirmationStatus::Pending {
+            return Err(ApprovalError::Closed {
+                id: id.to_string(),
+                status: status.as_str(),
+            });
+        }
+        let user = current_user();
+        let requester = owner(&self.path(id)?);
+        let own = match (requester, current_uid(&self.dir)) {
+            (Some(requester), Some(uid)) => requester == uid,
+            _ => record.requested_by == user,
+        };
+        if approve && own {
+            return Err(ApprovalError::SelfApproval {
+                id: id.to_string(),
+                user,
+            });
+        }
+        let decision = Decision {
+            request: record.request,
+            approved: approve,
+            decided_by: user,
+            decided_at: Utc::now(),
+        };
+        self.write(&self.decision_path(id)?, &decision)?;
+        self.get(id)
+    }
+
+    /// Wait until a request is answered or expires, checking every `poll`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request cannot be read.
+    pub async fn wait(&self, id: &str, poll: Duration) -> Result<ApprovalRecord, ApprovalError> {
+        loop {
+            let record = self.get(id)?;
+            if record.status() != ConfirmationStatus::Pending {
+                return Ok(record);
+            }
+            tokio::time::sleep(poll).await;
+        }
+    }
+
+    /// Write a file through a temporary one, so that readers never see it
+    /// half written.
+    fn write(&self, path: &Path, value: &impl Serialize) -> Result<(), ApprovalError> {
+        let content = serde_json::to_string_pretty(value).map_err(|e| ApprovalError::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// File of a request; IDs come from the command line and must not name
+    /// a path outside the store.
+    fn path(&self, id: &str) -> Result<PathBuf, ApprovalError> {
+        let valid =
+            !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ApprovalError::InvalidId(id.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    /// File of the decision on a request.
+    fn decision_path(&self, id: &str) -> Result<PathBuf, ApprovalError> {
+        Ok(self.path(id)?.with_extension("decision.json"))
+    }
+}
+
+/// Read a JSON file, or `None` if it does not exist.
+fn read<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>, ApprovalError> {
+    let content = match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        result => result?,
+    };
+    serde_json::from_str(&content).map(Some).map_err(|e| ApprovalError::Parse {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    })
+}
+
+/// Name of the user running this process, from `$USER` (`%USERNAME%` on
+/// Windows). It is for display only: the uid decides who may approve.
+#[must_use]
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Effective uid of this process, on Unix: the owner of a file it creates
+/// in `dir`.
+fn current_uid(dir: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let probe = dir.join(format!(".uid-{}.tmp", std::process::id()));
+        let uid = std::fs::File::create(&probe)
+            .and_then(|file| file.metadata())
+            .map(|metadata| metadata.uid());
+        let _ = std::fs::remove_file(&probe);
+        uid.ok()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        None
+    }
+}
+
+/// Owner of a file, on Unix.
+fn owner(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(path).ok().map(|metadata| metadata.uid())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::confirmation::ConfirmationLevel;
+
+    fn request(ttl: Duration) -> ConfirmationRequest {
+        ConfirmationRequest::new(
+            "Terminate backend 42".to_string(),
+            "SELECT pg_terminate_backend(42)".to_string(),
+            ConfirmationLevel::AdminApproval,
+        )
+        .with_ttl(ttl)
+    }
+
+    #[tokio::test]
+    async fn test_two_person_approval() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ApprovalStore::open(dir.path());
+        let request = request(Duration::from_secs(60));
+        let record = store.submit(&request).unwrap();
+        assert_eq!(record.requested_by, current_user());
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(current_uid(dir.path()), owner(&store.path(&request.id).unwrap()));
+
+        // The requester can neither approve nor forge an approval
+        let err = store.decide(&request.id, true).unwrap_err();
+        assert!(matches!(err, ApprovalError::SelfApproval { .. }));
+        let forged = Decision {
+            request: request.clone(),
+            approved: true,
+            decided_by: "bob".to_string(),
+            decided_at: Utc::now(),
+        };
+        store.write(&store.decision_path(&request.id).unwrap(), &forged).unwrap();
+        assert_eq!(store.get(&request.id).unwrap().status(), ConfirmationStatus::Pending);
+
+        let record = store.decide(&request.id, false).unwrap();
+        assert_eq!(record.decided_by, Some(current_user()));
+        let record = store.wait(&request.id, Duration::from_millis(1)).await.unwrap();
+        assert_eq!(record.status(), ConfirmationStatus::Denied);
+        let err = store.decide(&request.id, true).unwrap_err();
+        assert!(matches!(err, ApprovalError::Closed { status: "denied", .. }));
+        assert!(matches!(store.get("../x"), Err(ApprovalError::InvalidId(_))));
+        assert!(matches!(store.get("missing"), Err(ApprovalError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_genuine_decision() {
+        let asked = request(Duration::from_secs(60));
+        let record = ApprovalRecord {
+            request: asked.clone(),
+            requested_by: "alice".to_string(),
+            decided_by: None,
+            decided_at: None,
+        };
+        let decision = Decision {
+            request: asked,
+            approved: true,
+            decided_by: "bob".to_string(),
+            decided_at: Utc::now(),
+        };
+
+        assert!(decision.is_genuine(&record, Some(1000), Some(1001)));
+        // Owned by the requester, whatever name it claims
+        assert!(!decision.is_genuine(&record, Some(1000), Some(1000)));
+        assert!(Decision { approved: false, ..decision.clone() }.is_genuine(
+            &record,
+            Some(1000),
+            Some(1000)
+        ));
+        // An approval of another request does not carry over
+        let other = Decision { request: request(Duration::ZERO), ..decision };
+        assert!(!other.is_genuine(&record, Some(1000), Some(1001)));
+    }
+
+    #[tokio::test]
+    async fn test_expired_request() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ApprovalStore::open(dir.path());
+        let request = request(Duration::ZERO);
+        store.submit(&request).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+
+        let record = store.wait(&request.id, Duration::from_millis(1)).await.unwrap();
+        assert_eq!(record.status(), ConfirmationStatus::Expired);
+        let err = store.decide(&request.id, false).unwrap_err();
+        assert!(matches!(err, ApprovalError::Closed { status: "expired", .. }));
+    }
+}
@@ -2,14 +2,20 @@
 //!
 //! This module provides the [`AuditLogger`] for logging all database operations,
 //! safety violations, and schema changes for compliance and debugging.
+//!
+//! A log file that grows past its size limit is renamed with a timestamp
+//! suffix, compressed if configured, and a new file is started.
 
 use chrono::{DateTime, Utc};
+use postgres_agent_util::compress::Compression;
 use serde::{Deserialize, Serialize};
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use tracing::debug;
+use tracing::{debug, warn};
+
+use crate::fingerprint::fingerprint;
 
 /// Audit event types.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +37,9 @@ pub enum AuditEvent {
         duration_ms: u64,
         /// Number of rows affected (if available).
         rows_affected: Option<i64>,
+        /// Fingerprint of the query shape, see [`crate::fingerprint`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fingerprint: Option<String>,
     },
     /// Schema modification.
     SchemaChange {
@@ -72,6 +81,18 @@ pub enum AuditEvent {
         level: String,
         /// Whether confirmation was granted.
         granted: bool,
+        /// Whether the request expired before it was answered.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        expired: bool,
+    },
+    /// Reasoning step of an agent run.
+    Reasoning {
+        /// When the step was made.
+        timestamp: DateTime<Utc>,
+        /// Iteration of the run, starting at 1.
+        iteration: u32,
+        /// The reasoning trace.
+        thought: String,
     },
 }
 
@@ -85,6 +106,17 @@ pub struct AuditRecord {
     pub event_type: String,
     /// Event data.
     pub data: serde_json::Value,
+    /// Request ID of the agent run that produced the event.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl AuditRecord {
+    /// The logged event, if the record holds a known event.
+    #[must_use]
+    pub fn event(&self) -> Option<AuditEvent> {
+        serde_json::from_value(self.data.clone()).ok()
+    }
 }
 
 /// Audit logger configuration.
@@ -98,6 +130,8 @@ pub struct AuditConfig {
     pub max_file_size: u64,
     /// Whether to include PII in logs (should be false).
     pub include_pii: bool,
+    /// Compression of rotated log files.
+    pub compression: Compression,
 }
 
 impl AuditConfig {
@@ -109,9 +143,19 @@ impl AuditConfig {
             json_format: true,
             max_file_size: 0,
             include_pii: false,
+            compression: Compression::None,
         }
     }
 
+    /// Rotate the log once it grows past `bytes`, compressing rotated files
+    /// with `compression`.
+    #[must_use]
+    pub fn with_rotation(mut self, bytes: u64, compression: Compression) -> Self {
+        self.max_file_size = bytes;
+        self.compression = compression;
+        self
+    }
+
     /// Create a config with JSON formatting disabled (human-readable).
     #[must_use]
     pub fn human_readable(path: Option<PathBuf>) -> Self {
@@ -120,6 +164,7 @@ impl AuditConfig {
             json_format: false,
             max_file_size: 0,
             include_pii: false,
+            compression: Compression::None,
         }
     }
 }
@@ -144,6 +189,11 @@ impl Default for AuditLogger {
 impl AuditLogger {
     /// Create a new audit logger.
     pub fn new(config: AuditConfig) -> Self {
+        let size = config
+            .path
+            .as_ref()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map_or(0, |meta| meta.len());
         let file = config.path.as_ref().and_then(|path| {
             match OpenOptions::new()
                 .create(true)
@@ -164,7 +214,7 @@ impl AuditLogger {
         Self {
             config,
             file,
-            current_size: Mutex::new(0),
+            current_size: Mutex::new(size),
         }
     }
 
@@ -176,18 +226,37 @@ impl AuditLogger {
             json_format: true,
             max_file_size: 0,
             include_pii: false,
+            compression: Compression::None,
         })
     }
 
+    /// Make sure every logged event has reached the disk, as on shutdown.
+    ///
+    /// # Errors
+    /// Returns an error if the log file cannot be synced.
+    pub fn flush(&self) -> std::io::Result<()> {
+        if let Some(file_mutex) = &self.file {
+            let file = file_mutex.lock().unwrap_or_else(|e| e.into_inner());
+            file.sync_data()?;
+        }
+        std::io::stdout().flush()
+    }
+
     /// Log an audit event.
     pub fn log(&self, event: &AuditEvent) {
-        let record = self.serialize_event(event);
+        self.log_for_request(event, None);
+    }
+
+    /// Log an audit event tagged with the request ID of the originating run.
+    pub fn log_for_request(&self, event: &AuditEvent, request_id: Option<&str>) {
+        let mut record = self.serialize_event(event);
+        record.request_id = request_id.map(str::to_string);
 
         // Write to file if configured
-        if let Some(ref file_mutex) = self.file {
-            if let Ok(mut file) = file_mutex.lock() {
-                self.write_to_file(&record, &mut file);
-            }
+        if let Some(ref file_mutex) = self.file
+            && let Ok(mut file) = file_mutex.lock()
+        {
+            self.write_to_file(&record, &mut file);
         }
 
         // Also write to stdout for containerized environments
@@ -196,7 +265,9 @@ impl AuditLogger {
         }
     }
 
-    /// Log a query execution.
+    /// Log a query execution, tagged with the request ID of the run that
+    /// made it, if any.
+    #[allow(clippy::too_many_arguments)]
     pub fn log_query(
         &self,
         user: &str,
@@ -205,6 +276,7 @@ impl AuditLogger {
         success: bool,
         duration_ms: u64,
         rows_affected: Option<i64>,
+        request_id: Option<&str>,
     ) {
         let event = AuditEvent::Query {
             timestamp: Utc::now(),
@@ -214,8 +286,9 @@ impl AuditLogger {
             success,
             duration_ms,
             rows_affected,
+            fingerprint: Some(fingerprint(query)),
         };
-        self.log(&event);
+        self.log_for_request(&event, request_id);
     }
 
     /// Log a schema change.
@@ -256,6 +329,43 @@ impl AuditLogger {
         self.log(&event);
     }
 
+    /// Log the outcome of a confirmation request.
+    pub fn log_confirmation(&self, user: &str, operation: &str, level: &str, granted: bool) {
+        let event = AuditEvent::ConfirmationRequest {
+            timestamp: Utc::now(),
+            user: user.to_string(),
+            operation: self.sanitize_query(operation),
+            level: level.to_string(),
+            granted,
+            expired: false,
+        };
+        self.log(&event);
+    }
+
+    /// Log a confirmation request that expired before it was answered; it
+    /// counts as not granted.
+    pub fn log_confirmation_expired(&self, user: &str, operation: &str, level: &str) {
+        let event = AuditEvent::ConfirmationRequest {
+            timestamp: Utc::now(),
+            user: user.to_string(),
+            operation: self.sanitize_query(operation),
+            level: level.to_string(),
+            granted: false,
+            expired: true,
+        };
+        self.log(&event);
+    }
+
+    /// Log a reasoning step of the run with the given request ID.
+    pub fn log_reasoning(&self, iteration: u32, thought: &str, request_id: Option<&str>) {
+        let event = AuditEvent::Reasoning {
+            timestamp: Utc::now(),
+            iteration,
+            thought: thought.to_string(),
+        };
+        self.log_for_request(&event, request_id);
+    }
+
     /// Serialize an event to a record.
     fn serialize_event(&self, event: &AuditEvent) -> AuditRecord {
         let timestamp = match event {
@@ -263,6 +373,7 @@ impl AuditLogger {
             AuditEvent::SchemaChange { timestamp, .. } => *timestamp,
             AuditEvent::SafetyViolation { timestamp, .. } => *timestamp,
             AuditEvent::ConfirmationRequest { timestamp, .. } => *timestamp,
+            AuditEvent::Reasoning { timestamp, .. } => *timestamp,
         };
 
         let event_type = match event {
@@ -270,6 +381,7 @@ impl AuditLogger {
             AuditEvent::SchemaChange { .. } => "schema_change",
             AuditEvent::SafetyViolation { .. } => "safety_violation",
             AuditEvent::ConfirmationRequest { .. } => "confirmation_request",
+            AuditEvent::Reasoning { .. } => "reasoning",
         };
 
         let data = serde_json::to_value(event).unwrap_or_else(|_| serde_json::json!({}));
@@ -278,27 +390,70 @@ impl AuditLogger {
             timestamp,
             event_type: event_type.to_string(),
             data,
+            request_id: None,
         }
     }
 
-    /// Write a record to the file.
+    /// Write a record to the file, rotating it first if it would grow past
+    /// the size limit.
     fn write_to_file(&self, record: &AuditRecord, file: &mut File) {
-        if self.config.json_format {
-            if let Ok(line) = serde_json::to_string(record) {
-                let _ = writeln!(file, "{}", line);
-                let _ = file.flush();
+        let text = if self.config.json_format {
+            match serde_json::to_string(record) {
+                Ok(line) => format!("{}\n", line),
+                Err(_) => return,
             }
         } else {
             // Human-readable format
-            let line = format!(
-                "[{}] {}: {}\n",
+            format!(
+                "[{}] {}: {}\n\n",
                 record.timestamp,
                 record.event_type,
                 serde_json::to_string_pretty(&record.data).unwrap_or_default()
-            );
-            let _ = writeln!(file, "{}", line);
-            let _ = file.flush();
+            )
+        };
+
+        let Ok(mut size) = self.current_size.lock() else {
+            return;
+        };
+        let limit = self.config.max_file_size;
+        if limit > 0 && *size > 0 && *size + text.len() as u64 > limit {
+            match self.rotate() {
+                Ok(new_file) => {
+                    *file = new_file;
+                    *size = 0;
+                }
+                Err(e) => warn!("Failed to rotate audit log: {}", e),
+            }
         }
+        if file.write_all(text.as_bytes()).is_ok() {
+            *size += text.len() as u64;
+        }
+        let _ = file.flush();
+    }
+
+    /// Move the log file aside and open a new one in its place.
+    fn rotate(&self) -> std::io::Result<File> {
+        let Some(path) = &self.config.path else {
+            return Err(std::io::Error::other("no log file"));
+        };
+        let suffix = Utc::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+        // Several rotations within a millisecond get a counter
+        let rotated = (0..)
+            .map(|n| match n {
+                0 => PathBuf::from(format!("{}.{}", path.display(), suffix)),
+                n => PathBuf::from(format!("{}.{}-{}", path.display(), suffix, n)),
+            })
+            .find(|p| !p.exists() && !PathBuf::from(format!("{}.zst", p.display())).exists())
+            .unwrap_or_default();
+        std::fs::rename(path, &rotated)?;
+        if self.config.compression != Compression::None {
+            let data = std::fs::read(&rotated)?;
+            let compressed = PathBuf::from(format!("{}.zst", rotated.display()));
+            self.config.compression.write(&compressed, &data)?;
+            std::fs::remove_file(&rotated)?;
+        }
+        debug!("Rotated audit log {}", path.display());
+        OpenOptions::new().create(true).append(true).open(path)
     }
 
     /// Write a record to stdout.
@@ -324,7 +479,7 @@ impl AuditLogger {
 
         // Basic sanitization - remove obvious sensitive patterns
         // In production, you'd want more comprehensive sanitization
-        let sanitized = regex::Regex::new(r"(?i)(password|secret|token|api_key|auth)[\s]*=[\s]*[^\s,;]+")
+        regex::Regex::new(r"(?i)(password|secret|token|api_key|auth)[\s]*=[\s]*[^\s,;]+")
             .ok()
             .and_then(|re| {
                 if re.is_match(query) {
@@ -333,12 +488,48 @@ impl AuditLogger {
                     None
                 }
             })
-            .unwrap_or_else(|| query.to_string());
-
-        sanitized
+            .unwrap_or_else(|| query.to_string())
     }
 }
 
+/// Rotated files of the audit log at `path`, oldest first.
+#[must_use]
+pub fn rotated_logs(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let mut logs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+                .map(|e| e.path())
+                .collect()
+        })
+        .unwrap_or_default();
+    logs.sort_by_cached_key(|p| (std::fs::metadata(p).and_then(|m| m.modified()).ok(), p.clone()));
+    logs
+}
+
+/// Records of the JSON audit log at `path` and its rotated files, oldest
+/// first. Unreadable files and lines that are not JSON records are skipped.
+#[must_use]
+pub fn read_audit_log(path: &Path) -> Vec<AuditRecord> {
+    let mut files = rotated_logs(path);
+    files.push(path.to_path_buf());
+    files
+        .iter()
+        .filter_map(|file| postgres_agent_util::compress::read_to_string(file).ok())
+        .flat_map(|text| {
+            text.lines()
+                .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
 /// Create a default audit logger.
 #[must_use]
 pub fn create_default_logger() -> AuditLogger {
@@ -359,6 +550,7 @@ mod tests {
             success: true,
             duration_ms: 5,
             rows_affected: Some(1),
+            fingerprint: None,
         };
 
         let logger = AuditLogger::stdout();
@@ -368,6 +560,64 @@ mod tests {
         assert!(!serde_json::to_string(&record.data).unwrap().is_empty());
     }
 
+    #[test]
+    fn test_audit_record_request_id() {
+        let logger = AuditLogger::stdout();
+        let mut record = logger.serialize_event(&AuditEvent::SafetyViolation {
+            timestamp: Utc::now(),
+            user: "test_user".to_string(),
+            query: "DROP TABLE users".to_string(),
+            reason: "blacklisted".to_string(),
+            safety_level: "balanced".to_string(),
+        });
+        assert!(!serde_json::to_string(&record).unwrap().contains("requestId"));
+
+        record.request_id = Some("req-1".to_string());
+        assert!(serde_json::to_string(&record).unwrap().contains("\"requestId\":\"req-1\""));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let config = AuditConfig::with_path(path.clone())
+            .with_rotation(300, Compression::Zstd { level: 3 });
+        let logger = AuditLogger::new(config);
+        for i in 0..4 {
+            logger.log_confirmation("test_user", &format!("DROP TABLE t{}", i), "simple", false);
+        }
+
+        let rotated = rotated_logs(&path);
+        assert!(!rotated.is_empty());
+        assert!(rotated.iter().all(|p| p.extension().is_some_and(|e| e == "zst")));
+        let first = postgres_agent_util::compress::read_to_string(&rotated[0]).unwrap();
+        assert!(first.contains("DROP TABLE t0"));
+        assert!(std::fs::metadata(&path).unwrap().len() <= 300);
+        logger.flush().unwrap();
+        assert_eq!(read_audit_log(&path).len(), 4);
+    }
+
+    #[test]
+    fn test_query_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let logger = AuditLogger::new(AuditConfig::with_path(path.clone()));
+        logger.log_query("test_user", "test_db", "SELECT * FROM t WHERE id = 1", true, 3, None, None);
+        logger.log_query("test_user", "test_db", "select * from t where id = 2", true, 4, None, None);
+
+        let fingerprints: Vec<Option<String>> = read_audit_log(&path)
+            .iter()
+            .filter_map(AuditRecord::event)
+            .map(|event| match event {
+                AuditEvent::Query { fingerprint, .. } => fingerprint,
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fingerprints.len(), 2);
+        assert!(fingerprints[0].is_some());
+        assert_eq!(fingerprints[0], fingerprints[1]);
+    }
+
     #[test]
     fn test_query_sanitization() {
         let logger = AuditLogger::stdout();
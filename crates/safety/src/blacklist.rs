@@ -65,6 +65,17 @@ impl SqlBlacklist {
         }
         None
     }
+
+    /// Name, regular expression and whether it matches `sql`, for each
+    /// pattern in match order.
+    #[must_use]
+    pub fn check(&self, sql: &str) -> Vec<(&'static str, &str, bool)> {
+        let trimmed = sql.trim_start();
+        self.patterns
+            .iter()
+            .map(|(pattern, name)| (*name, pattern.as_str(), pattern.is_match(trimmed)))
+            .collect()
+    }
 }
 
 lazy_static! {
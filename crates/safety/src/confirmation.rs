@@ -6,8 +6,11 @@
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
+use crate::audit::AuditLogger;
+use crate::locks::{analyze_ddl, describe_warnings};
 use crate::validator::{OperationType, SafetyLevel};
 
 /// Confirmation level for operations.
@@ -32,6 +35,17 @@ impl ConfirmationLevel {
         matches!(self, Self::Simple | Self::Typed | Self::AdminApproval)
     }
 
+    /// Name of the level, as serialized.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "NONE",
+            Self::Simple => "SIMPLE",
+            Self::Typed => "TYPED",
+            Self::AdminApproval => "ADMIN_APPROVAL",
+        }
+    }
+
     /// Get the confirmation prompt message.
     #[must_use]
     pub fn prompt_message(&self, operation: &str) -> String {
@@ -49,6 +63,37 @@ impl ConfirmationLevel {
     }
 }
 
+/// How long a confirmation request stays open by default.
+pub const DEFAULT_CONFIRMATION_TTL: Duration = Duration::from_secs(300);
+
+/// State of a confirmation request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ConfirmationStatus {
+    /// Waiting for an answer.
+    #[default]
+    Pending,
+    /// The operation was approved.
+    Approved,
+    /// The operation was declined or cancelled.
+    Denied,
+    /// No answer came before the request expired; counts as denied.
+    Expired,
+}
+
+impl ConfirmationStatus {
+    /// Name of the status, as serialized.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Denied => "denied",
+            Self::Expired => "expired",
+        }
+    }
+}
+
 /// Confirmation request state.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -63,8 +108,15 @@ pub struct ConfirmationRequest {
     pub level: ConfirmationLevel,
     /// When the request was created.
     pub created_at: chrono::DateTime<chrono::Utc>,
-    /// Whether this request has expired.
-    pub expired: bool,
+    /// Seconds the request stays open.
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Whether the request was answered, and how.
+    #[serde(default)]
+    pub status: ConfirmationStatus,
+    /// Lock and rewrite warnings, with safer alternatives.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 impl Default for ConfirmationRequest {
@@ -75,53 +127,123 @@ impl Default for ConfirmationRequest {
             sql: String::new(),
             level: ConfirmationLevel::None,
             created_at: chrono::Utc::now(),
-            expired: false,
+            ttl_secs: default_ttl_secs(),
+            status: ConfirmationStatus::Pending,
+            warnings: Vec::new(),
         }
     }
 }
 
+fn default_ttl_secs() -> u64 {
+    DEFAULT_CONFIRMATION_TTL.as_secs()
+}
+
 impl ConfirmationRequest {
     /// Create a new confirmation request.
+    ///
+    /// DDL is analyzed for heavy locks and table rewrites; see
+    /// [`with_server_version`](Self::with_server_version) for
+    /// version-dependent checks.
     #[must_use]
     pub fn new(operation: String, sql: String, level: ConfirmationLevel) -> Self {
+        let warnings = describe_warnings(&analyze_ddl(&sql, None));
         Self {
             id: Uuid::new_v4().to_string(),
             operation,
             sql,
             level,
             created_at: chrono::Utc::now(),
-            expired: false,
+            ttl_secs: default_ttl_secs(),
+            status: ConfirmationStatus::Pending,
+            warnings,
+        }
+    }
+
+    /// Re-analyze the SQL for a specific server version
+    /// (`server_version_num`).
+    #[must_use]
+    pub fn with_server_version(mut self, server_version: u32) -> Self {
+        self.warnings = describe_warnings(&analyze_ddl(&self.sql, Some(server_version)));
+        self
+    }
+
+    /// Keep the request open for `ttl` instead of the default 5 minutes.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl_secs = ttl.as_secs();
+        self
+    }
+
+    /// Get the confirmation prompt, including any lock warnings.
+    #[must_use]
+    pub fn prompt(&self) -> String {
+        let mut prompt = String::new();
+        for warning in &self.warnings {
+            prompt.push_str("Warning: ");
+            prompt.push_str(warning);
+            prompt.push('\n');
         }
+        prompt.push_str(&self.level.prompt_message(&self.operation));
+        prompt
     }
 
-    /// Check if the request has expired (default 5 minutes).
+    /// When the request expires.
+    #[must_use]
+    pub fn expires_at(&self) -> chrono::DateTime<chrono::Utc> {
+        let ttl = i64::try_from(self.ttl_secs).unwrap_or(i64::MAX);
+        self.created_at + chrono::Duration::try_seconds(ttl).unwrap_or(chrono::Duration::MAX)
+    }
+
+    /// Check if the request has expired.
     #[must_use]
     pub fn is_expired(&self) -> bool {
-        let expiry = chrono::Duration::minutes(5);
-        chrono::Utc::now() - self.created_at > expiry
+        chrono::Utc::now() > self.expires_at()
+    }
+
+    /// A new pending request for the same operation, with a new ID and a
+    /// fresh expiry.
+    #[must_use]
+    pub fn refreshed(&self) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            created_at: chrono::Utc::now(),
+            status: ConfirmationStatus::Pending,
+            ..self.clone()
+        }
     }
 }
 
-/// Confirmation workflow state.
+/// Queue of confirmation requests.
+///
+/// Requests wait in the order they were made and are answered one at a
+/// time, oldest first, by ID, or as a batch. Answered requests keep their
+/// status until [`clear_answered`](Self::clear_answered). A request not
+/// answered within its TTL expires: answering it afterwards approves
+/// nothing, the expiry is audited as a denial, and the request can be asked
+/// again with [`refresh`](Self::refresh).
 #[derive(Debug)]
 pub struct ConfirmationWorkflow {
-    /// Pending confirmation request.
-    pending: Option<ConfirmationRequest>,
+    /// Requests in the order they were made, with their status.
+    requests: Vec<ConfirmationRequest>,
+    /// How long new requests stay open.
+    ttl: Duration,
+    /// Audit log for expired requests, with the user they were put to.
+    audit: Option<(Arc<AuditLogger>, String)>,
     /// Confirmation response (for testing/automation).
     auto_confirm: Arc<AtomicBool>,
     /// Typed confirmation value (for "Typed" level).
+    #[allow(dead_code)]
     typed_confirmation: Arc<AtomicBool>,
-    /// Expected typed value.
-    expected_typed_value: String,
 }
 
 impl Default for ConfirmationWorkflow {
     fn default() -> Self {
         Self {
-            pending: None,
+            requests: Vec::new(),
+            ttl: DEFAULT_CONFIRMATION_TTL,
+            audit: None,
             auto_confirm: Arc::new(AtomicBool::new(false)),
             typed_confirmation: Arc::new(AtomicBool::new(false)),
-            expected_typed_value: String::new(),
         }
     }
 }
@@ -136,12 +258,29 @@ impl ConfirmationWorkflow {
     /// Create a workflow that auto-confirms (for testing).
     #[must_use]
     pub fn with_auto_confirm() -> Self {
-        let mut workflow = Self::default();
-        workflow.auto_confirm = Arc::new(AtomicBool::new(true));
-        workflow
+        Self {
+            auto_confirm: Arc::new(AtomicBool::new(true)),
+            ..Self::default()
+        }
     }
 
-    /// Request confirmation for an operation.
+    /// Keep new requests open for `ttl`.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Record requests to `user` that expire in `audit`. Answers are left
+    /// to the caller, which knows what was approved.
+    #[must_use]
+    pub fn with_audit(mut self, audit: Arc<AuditLogger>, user: impl Into<String>) -> Self {
+        self.audit = Some((audit, user.into()));
+        self
+    }
+
+    /// Request confirmation for an operation, queued after the requests
+    /// already pending.
     pub fn request(
         &mut self,
         operation: &str,
@@ -152,110 +291,241 @@ impl ConfirmationWorkflow {
             return None;
         }
 
-        let request = ConfirmationRequest::new(operation.to_string(), sql.to_string(), level);
-        self.pending = Some(request.clone());
-        self.expected_typed_value = operation.to_uppercase();
-
+        let request = ConfirmationRequest::new(operation.to_string(), sql.to_string(), level)
+            .with_ttl(self.ttl);
+        self.requests.push(request.clone());
         Some(request)
     }
 
+    /// Ask again for the oldest pending request, or else the last one that
+    /// expired, with a new ID and a fresh expiry.
+    pub fn refresh(&mut self) -> Option<ConfirmationRequest> {
+        let id = self
+            .pending_request()
+            .or_else(|| self.expired_request())
+            .map(|request| request.id.clone())?;
+        self.refresh_request(&id)
+    }
+
+    /// Ask again for a pending or expired request, with a new ID and a
+    /// fresh expiry; it takes the place of the old request in the queue.
+    pub fn refresh_request(&mut self, id: &str) -> Option<ConfirmationRequest> {
+        let request = self.requests.iter_mut().find(|request| {
+            let open = matches!(
+                request.status,
+                ConfirmationStatus::Pending | ConfirmationStatus::Expired
+            );
+            request.id == id && open
+        })?;
+        *request = ConfirmationRequest {
+            ttl_secs: self.ttl.as_secs(),
+            ..request.refreshed()
+        };
+        Some(request.clone())
+    }
+
+    /// Deny pending requests that have expired, recording each expiry.
+    ///
+    /// Returns whether any expired.
+    pub fn expire_stale(&mut self) -> bool {
+        let mut expired = false;
+        for request in &mut self.requests {
+            if request.status != ConfirmationStatus::Pending || !request.is_expired() {
+                continue;
+            }
+            request.status = ConfirmationStatus::Expired;
+            if let Some((audit, user)) = &self.audit {
+                audit.log_confirmation_expired(user, &request.sql, request.level.as_str());
+            }
+            expired = true;
+        }
+        expired
+    }
+
     /// Check if confirmation is pending.
     #[must_use]
     pub fn is_pending(&self) -> bool {
-        self.pending.is_some() && !self.pending.as_ref().unwrap().expired
+        self.pending_requests().any(|request| !request.is_expired())
     }
 
-    /// Get the pending confirmation request.
+    /// Get the oldest pending confirmation request.
     #[must_use]
     pub fn pending_request(&self) -> Option<&ConfirmationRequest> {
-        self.pending.as_ref()
+        self.pending_requests().next()
+    }
+
+    /// Pending requests, oldest first.
+    pub fn pending_requests(&self) -> impl Iterator<Item = &ConfirmationRequest> {
+        self.requests.iter().filter(|request| request.status == ConfirmationStatus::Pending)
+    }
+
+    /// Every request in the queue with its status, oldest first.
+    #[must_use]
+    pub fn requests(&self) -> &[ConfirmationRequest] {
+        &self.requests
+    }
+
+    /// Get a request by ID.
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&ConfirmationRequest> {
+        self.requests.iter().find(|request| request.id == id)
     }
 
-    /// Get the confirmation prompt.
+    /// The last request that expired before it was answered.
+    #[must_use]
+    pub fn expired_request(&self) -> Option<&ConfirmationRequest> {
+        self.requests
+            .iter()
+            .rev()
+            .find(|request| request.status == ConfirmationStatus::Expired)
+    }
+
+    /// Get the prompt of the oldest pending request.
     #[must_use]
     pub fn get_prompt(&self) -> Option<String> {
-        self.pending.as_ref().map(|r| r.level.prompt_message(&r.operation))
+        self.pending_request().map(ConfirmationRequest::prompt)
     }
 
-    /// Confirm the operation (simple confirmation).
+    /// Confirm the oldest pending operation (simple confirmation).
+    ///
+    /// Typed requests need [`confirm_typed`](Self::confirm_typed).
     pub fn confirm(&mut self) -> bool {
-        if self.auto_confirm.load(Ordering::SeqCst) {
-            self.clear();
-            return true;
-        }
-
-        if let Some(ref mut request) = self.pending {
-            if request.level == ConfirmationLevel::Simple
-                || request.level == ConfirmationLevel::Typed
-            {
-                request.expired = true;
-                self.clear();
-                return true;
-            }
-        }
-        false
+        self.answer_oldest(|request| request.level == ConfirmationLevel::Simple)
     }
 
     /// Confirm with typed value (for Typed confirmation level).
     pub fn confirm_typed(&mut self, value: &str) -> bool {
-        if self.auto_confirm.load(Ordering::SeqCst) {
-            self.clear();
-            return true;
-        }
-
-        if let Some(ref request) = self.pending {
-            if request.level == ConfirmationLevel::Typed && value.trim() == self.expected_typed_value {
-                self.clear();
-                return true;
-            }
-        }
-        false
+        self.answer_oldest(|request| {
+            request.level == ConfirmationLevel::Typed
+                && value.trim() == request.operation.to_uppercase()
+        })
     }
 
     /// Approve as admin.
     pub fn admin_approve(&mut self) -> bool {
-        if self.auto_confirm.load(Ordering::SeqCst) {
-            self.clear();
-            return true;
-        }
+        self.answer_oldest(|request| request.level == ConfirmationLevel::AdminApproval)
+    }
 
-        if let Some(ref mut request) = self.pending {
-            if request.level == ConfirmationLevel::AdminApproval {
-                request.expired = true;
-                self.clear();
-                return true;
-            }
-        }
-        false
+    /// Approve the oldest pending request at whatever level it was
+    /// requested, for callers that collected the answer themselves.
+    pub fn approve(&mut self) -> bool {
+        self.answer_oldest(|_| true)
+    }
+
+    /// Approve a pending request by ID, at whatever level it was requested.
+    pub fn approve_request(&mut self, id: &str) -> bool {
+        self.decide(id, ConfirmationStatus::Approved)
+    }
+
+    /// Deny a pending request by ID.
+    pub fn deny_request(&mut self, id: &str) -> bool {
+        self.decide(id, ConfirmationStatus::Denied)
+    }
+
+    /// Approve every pending request that has not expired, returning their
+    /// IDs.
+    pub fn approve_all(&mut self) -> Vec<String> {
+        self.approve_only_where(|_| true)
+    }
+
+    /// Approve the pending requests with the given IDs and deny the other
+    /// pending requests, returning the IDs approved.
+    pub fn approve_only(&mut self, ids: &[&str]) -> Vec<String> {
+        self.approve_only_where(|request| ids.contains(&request.id.as_str()))
     }
 
-    /// Cancel the pending confirmation.
+    /// Cancel the oldest pending confirmation.
     pub fn cancel(&mut self) {
-        if let Some(ref mut request) = self.pending {
-            request.expired = true;
+        self.expire_stale();
+        if let Some(request) = self.oldest_pending_mut() {
+            request.status = ConfirmationStatus::Denied;
         }
-        self.clear();
     }
 
-    /// Clear the pending confirmation.
-    fn clear(&mut self) {
-        self.pending = None;
-        self.expected_typed_value.clear();
+    /// Deny every pending request.
+    pub fn deny_all(&mut self) {
+        self.approve_only(&[]);
+    }
+
+    /// Drop answered and expired requests from the queue.
+    pub fn clear_answered(&mut self) {
+        self.requests.retain(|request| request.status == ConfirmationStatus::Pending);
     }
 
-    /// Check if the current level requires typed confirmation.
+    /// Check if the oldest pending request requires typed confirmation.
     #[must_use]
     pub fn requires_typed_input(&self) -> bool {
-        self.pending
-            .as_ref()
-            .map(|r| r.level == ConfirmationLevel::Typed)
-            .unwrap_or(false)
+        self.pending_request()
+            .is_some_and(|request| request.level == ConfirmationLevel::Typed)
     }
 
-    /// Get the expected typed value (for display purposes).
+    /// Get the value the oldest pending request expects to be typed (for
+    /// display purposes).
     #[must_use]
-    pub fn expected_type_value(&self) -> &str {
-        &self.expected_typed_value
+    pub fn expected_type_value(&self) -> String {
+        self.pending_request()
+            .map(|request| request.operation.to_uppercase())
+            .unwrap_or_default()
+    }
+
+    fn oldest_pending_mut(&mut self) -> Option<&mut ConfirmationRequest> {
+        self.requests
+            .iter_mut()
+            .find(|request| request.status == ConfirmationStatus::Pending)
+    }
+
+    /// Approve the oldest pending request if it has not expired and
+    /// `accepts` it.
+    fn answer_oldest(&mut self, accepts: impl FnOnce(&ConfirmationRequest) -> bool) -> bool {
+        if self.expire_stale() {
+            return false;
+        }
+        let auto_confirm = self.auto_confirm.load(Ordering::SeqCst);
+        let Some(request) = self.oldest_pending_mut() else {
+            return false;
+        };
+        if !auto_confirm && !accepts(request) {
+            return false;
+        }
+        request.status = ConfirmationStatus::Approved;
+        true
+    }
+
+    /// Answer a pending request by ID, unless it has expired.
+    fn decide(&mut self, id: &str, status: ConfirmationStatus) -> bool {
+        self.expire_stale();
+        match self
+            .requests
+            .iter_mut()
+            .find(|request| request.id == id && request.status == ConfirmationStatus::Pending)
+        {
+            Some(request) => {
+                request.status = status;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Approve the pending requests `selected` picks and deny the others.
+    fn approve_only_where(
+        &mut self,
+        selected: impl Fn(&ConfirmationRequest) -> bool,
+    ) -> Vec<String> {
+        self.expire_stale();
+        let mut approved = Vec::new();
+        for request in &mut self.requests {
+            if request.status != ConfirmationStatus::Pending {
+                continue;
+            }
+            if selected(request) {
+                request.status = ConfirmationStatus::Approved;
+                approved.push(request.id.clone());
+            } else {
+                request.status = ConfirmationStatus::Denied;
+            }
+        }
+        approved
     }
 }
 
@@ -285,6 +555,7 @@ pub fn requires_confirmation(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audit::AuditConfig;
 
     #[test]
     fn test_confirmation_levels() {
@@ -308,6 +579,20 @@ mod tests {
         assert!(!request.is_expired());
     }
 
+    #[test]
+    fn test_request_lock_warnings() {
+        let request = ConfirmationRequest::new(
+            "CREATE INDEX".to_string(),
+            "CREATE INDEX idx_users_email ON users (email)".to_string(),
+            ConfirmationLevel::Simple,
+        );
+
+        let prompt = request.prompt();
+        assert!(prompt.starts_with("Warning: CREATE INDEX blocks writes"));
+        assert!(prompt.contains("CONCURRENTLY"));
+        assert!(prompt.ends_with("(y/n)"));
+    }
+
     #[test]
     fn test_workflow_request() {
         let mut workflow = ConfirmationWorkflow::new();
@@ -340,4 +625,74 @@ mod tests {
         workflow.cancel();
         assert!(!workflow.is_pending());
     }
+
+    #[test]
+    fn test_workflow_typed() {
+        let mut workflow = ConfirmationWorkflow::new();
+
+        workflow.request("drop", "DROP TABLE users", ConfirmationLevel::Typed);
+        assert!(!workflow.confirm());
+        assert!(!workflow.confirm_typed("drop"));
+        assert!(workflow.confirm_typed("DROP"));
+        assert!(!workflow.is_pending());
+    }
+
+    #[test]
+    fn test_workflow_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let audit = Arc::new(AuditLogger::new(AuditConfig::with_path(path.clone())));
+        let mut workflow = ConfirmationWorkflow::with_auto_confirm()
+            .with_ttl(Duration::ZERO)
+            .with_audit(audit, "alice");
+
+        let request = workflow
+            .request("DELETE", "DELETE FROM users", ConfirmationLevel::Simple)
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(!workflow.is_pending());
+        assert!(!workflow.confirm());
+        let expired = workflow.expired_request().unwrap();
+        assert_eq!(expired.id, request.id);
+        assert_eq!(expired.status, ConfirmationStatus::Expired);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.contains("\"expired\":true"));
+        assert!(log.contains("\"granted\":false"));
+
+        let mut workflow = workflow.with_ttl(DEFAULT_CONFIRMATION_TTL);
+        let refreshed = workflow.refresh().unwrap();
+        assert_ne!(refreshed.id, request.id);
+        assert_eq!(refreshed.sql, request.sql);
+        assert!(workflow.expired_request().is_none());
+        assert!(workflow.is_pending());
+        assert!(workflow.approve());
+    }
+
+    #[test]
+    fn test_workflow_queue() {
+        let mut workflow = ConfirmationWorkflow::new();
+        let mut request = |sql: &str| {
+            workflow.request("UPDATE", sql, ConfirmationLevel::Simple).unwrap().id
+        };
+        let (first, second) = (request("UPDATE a"), request("UPDATE b"));
+        let third = request("UPDATE c");
+        assert_eq!(workflow.pending_requests().count(), 3);
+        assert_eq!(workflow.pending_request().unwrap().id, first);
+
+        assert!(workflow.approve_request(&second));
+        assert!(!workflow.approve_request(&second));
+        assert_eq!(workflow.approve_only(&[third.as_str()]), vec![third.clone()]);
+        assert_eq!(workflow.get(&first).unwrap().status, ConfirmationStatus::Denied);
+        assert_eq!(workflow.get(&second).unwrap().status, ConfirmationStatus::Approved);
+        assert_eq!(workflow.get(&third).unwrap().status, ConfirmationStatus::Approved);
+        assert!(!workflow.is_pending());
+
+        workflow.clear_answered();
+        workflow.request("DELETE", "DELETE FROM a", ConfirmationLevel::Typed);
+        workflow.request("DELETE", "DELETE FROM b", ConfirmationLevel::Simple);
+        assert!(workflow.requires_typed_input());
+        assert_eq!(workflow.approve_all().len(), 2);
+        assert_eq!(workflow.requests().len(), 2);
+    }
 }
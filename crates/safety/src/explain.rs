@@ -0,0 +1,321 @@
+//! Explanations of validation decisions.
+//!
+//! [`SafetyValidator::explain`] runs SQL through the checks of
+//! [`SafetyValidator::validate`] and records each of them as a tree: the
+//! blacklist patterns, PII detection, tenant scoping, the read-only session
+//! check, every policy rule with its conditions, and the row-level security
+//! and lock warnings. Checks after the one that blocked are marked as not
+//! reached. Nothing is executed.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::locks::analyze_ddl;
+use crate::policy::PolicyAction;
+use crate::rls::rls_warnings;
+use crate::validator::{OperationType, SafetyContext, SafetyValidator, ValidationResult};
+
+/// Outcome of a check, pattern, rule or condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// The check found nothing.
+    Pass,
+    /// A pattern or condition matched.
+    Match,
+    /// A pattern, rule or condition did not match.
+    NoMatch,
+    /// The check added a warning.
+    Warn,
+    /// A rule allowed the operation.
+    Allow,
+    /// A rule requires confirmation.
+    Confirm,
+    /// The check blocked the operation.
+    Block,
+    /// An earlier check decided.
+    NotReached,
+    /// The check is not configured.
+    NotConfigured,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Match => "match",
+            Self::NoMatch => "no match",
+            Self::Warn => "WARN",
+            Self::Allow => "ALLOW",
+            Self::Confirm => "CONFIRM",
+            Self::Block => "BLOCK",
+            Self::NotReached => "not reached",
+            Self::NotConfigured => "not configured",
+        })
+    }
+}
+
+/// A node of the decision tree.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainNode {
+    /// What was checked.
+    pub label: String,
+    /// Outcome of the check.
+    pub outcome: Outcome,
+    /// Why, e.g. the warning or error it produced.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Patterns, rules or conditions checked.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ExplainNode>,
+}
+
+impl ExplainNode {
+    fn new(label: impl Into<String>, outcome: Outcome) -> Self {
+        Self {
+            label: label.into(),
+            outcome,
+            note: None,
+            children: Vec::new(),
+        }
+    }
+
+    fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    fn with_children(mut self, children: Vec<ExplainNode>) -> Self {
+        self.children = children;
+        self
+    }
+
+    fn render(&self, prefix: &str, last: bool, out: &mut String) {
+        let (branch, indent) = if last { ("└─ ", "   ") } else { ("├─ ", "│  ") };
+        out.push_str(&format!("{}{}{}: {}", prefix, branch, self.label, self.outcome));
+        if let Some(note) = &self.note {
+            out.push_str(&format!(" ({})", note));
+        }
+        out.push('\n');
+        let prefix = format!("{}{}", prefix, indent);
+        for (i, child) in self.children.iter().enumerate() {
+            child.render(&prefix, i + 1 == self.children.len(), out);
+        }
+    }
+}
+
+/// The checks of a validation and its result.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Explanation {
+    /// Classification of the statement.
+    pub operation_type: OperationType,
+    /// Each check, in the order they run.
+    pub checks: Vec<ExplainNode>,
+    /// The result [`SafetyValidator::validate`] returns.
+    pub result: ValidationResult,
+}
+
+impl Explanation {
+    /// The decision: `ALLOW`, `CONFIRM` or `DENY`.
+    #[must_use]
+    pub fn decision(&self) -> &'static str {
+        if !self.result.is_allowed {
+            "DENY"
+        } else if self.result.requires_confirmation {
+            "CONFIRM"
+        } else {
+            "ALLOW"
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = format!("Operation: {}\n", self.operation_type.label());
+        for (i, check) in self.checks.iter().enumerate() {
+            check.render("", i + 1 == self.checks.len(), &mut out);
+        }
+        out.push_str(&format!("Decision: {}", self.decision()));
+        if let Some(rule) = &self.result.rule {
+            out.push_str(&format!(" (rule '{}')", rule));
+        }
+        if let Some(error) = &self.result.error {
+            out.push_str(&format!(": {}", error));
+        }
+        f.write_str(&out)
+    }
+}
+
+impl SafetyValidator {
+    /// Explain how [`Self::validate`] decides on `sql`, check by check.
+    #[must_use]
+    pub fn explain(&self, sql: &str, ctx: &SafetyContext) -> Explanation {
+        let operation = self.classify_operation(sql);
+        let mut checks = Vec::new();
+        let mut blocked = false;
+        // Checks after a block are not reached
+        let mut push = |node: ExplainNode, checks: &mut Vec<ExplainNode>| {
+            let node = if blocked {
+                ExplainNode::new(node.label, Outcome::NotReached)
+            } else {
+                node
+            };
+            blocked |= node.outcome == Outcome::Block;
+            checks.push(node);
+        };
+
+        // The first matching pattern blocks
+        let mut matched = false;
+        let patterns = self
+            .blacklist()
+            .check(sql)
+            .into_iter()
+            .map(|(name, pattern, is_match)| {
+                let hit = is_match && !matched;
+                matched |= hit;
+                let outcome = if hit { Outcome::Match } else { Outcome::NoMatch };
+                ExplainNode::new(format!("{} /{}/", name, pattern), outcome)
+            })
+            .collect();
+        let outcome = if matched { Outcome::Block } else { Outcome::Pass };
+        push(ExplainNode::new("Blacklist", outcome).with_children(patterns), &mut checks);
+
+        let pii = if self.pii_detector().contains_pii(sql) {
+            ExplainNode::new("PII detection", Outcome::Warn).with_note("Query may contain PII")
+        } else {
+            ExplainNode::new("PII detection", Outcome::Pass)
+        };
+        push(pii, &mut checks);
+
+        let tenant = match ctx.tenant.as_ref().map(|tenant| tenant.apply(sql)) {
+            None => ExplainNode::new("Tenant scoping", Outcome::NotConfigured),
+            Some(Ok(scoped)) if scoped != sql => ExplainNode::new("Tenant scoping", Outcome::Warn)
+                .with_note("Tenant filter added to query"),
+            Some(Ok(_)) => ExplainNode::new("Tenant scoping", Outcome::Pass),
+            Some(Err(violation)) => {
+                ExplainNode::new("Tenant scoping", Outcome::Block).with_note(violation)
+            }
+        };
+        push(tenant, &mut checks);
+
+        let read_only = if !ctx.read_only {
+            ExplainNode::new("Read-only session", Outcome::NotConfigured)
+        } else if operation == OperationType::Read {
+            ExplainNode::new("Read-only session", Outcome::Pass)
+        } else {
+            ExplainNode::new("Read-only session", Outcome::Block)
+                .with_note("Mutations not allowed in read-only mode")
+        };
+        push(read_only, &mut checks);
+
+        let configured = self.policy().rules().len();
+        let mut decided = None;
+        let rules = self
+            .effective_policy(ctx.level)
+            .rules()
+            .iter()
+            .enumerate()
+            .map(|(i, rule)| {
+                let source = if i < configured { "configured" } else { "built-in" };
+                let label = format!("{} [{}, {}]", rule.name, source, rule.action.as_str());
+                if decided.is_some() {
+                    return ExplainNode::new(label, Outcome::NotReached);
+                }
+                let conditions: Vec<ExplainNode> = rule
+                    .check(sql, operation, ctx)
+                    .into_iter()
+                    .map(|check| {
+                        let outcome = if check.matched { Outcome::Match } else { Outcome::NoMatch };
+                        ExplainNode::new(check.condition, outcome)
+                    })
+                    .collect();
+                let node = if conditions.iter().all(|c| c.outcome == Outcome::Match) {
+                    let outcome = match rule.action {
+                        PolicyAction::Allow => Outcome::Allow,
+                        PolicyAction::Confirm => Outcome::Confirm,
+                        PolicyAction::Deny => Outcome::Block,
+                    };
+                    decided = Some(outcome);
+                    let node = ExplainNode::new(label, outcome);
+                    match &rule.message {
+                        Some(message) => {
+                            node.with_note(message.replace("{operation}", operation.label()))
+                        }
+                        None => node,
+                    }
+                } else {
+                    ExplainNode::new(label, Outcome::NoMatch)
+                };
+                node.with_children(conditions)
+            })
+            .collect();
+        let outcome = decided.unwrap_or(Outcome::Pass);
+        push(ExplainNode::new("Policy rules", outcome).with_children(rules), &mut checks);
+
+        let rls: Vec<ExplainNode> = rls_warnings(sql, &ctx.rls_tables)
+            .into_iter()
+            .map(|warning| ExplainNode::new(warning, Outcome::Warn))
+            .collect();
+        let outcome = if rls.is_empty() { Outcome::Pass } else { Outcome::Warn };
+        push(ExplainNode::new("Row-level security", outcome).with_children(rls), &mut checks);
+
+        let locks: Vec<ExplainNode> = if operation == OperationType::Read {
+            Vec::new()
+        } else {
+            analyze_ddl(sql, ctx.server_version)
+                .iter()
+                .map(|warning| ExplainNode::new(warning.describe(), Outcome::Warn))
+                .collect()
+        };
+        let outcome = if locks.is_empty() { Outcome::Pass } else { Outcome::Warn };
+        push(ExplainNode::new("Locks and rewrites", outcome).with_children(locks), &mut checks);
+
+        Explanation {
+            operation_type: operation,
+            checks,
+            result: self.validate(sql, ctx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{parse_operations, PolicyRule};
+    use crate::validator::SafetyLevel;
+
+    #[test]
+    fn test_explain_policy_decision() {
+        let rule = PolicyRule::new("big-updates", PolicyAction::Deny)
+            .on_operations(parse_operations("update").unwrap())
+            .with_min_rows(1_000);
+        let validator = SafetyValidator::new().with_rules(vec![rule]);
+        let ctx = SafetyContext::with_level(SafetyLevel::Balanced).with_estimated_rows(10);
+
+        let explanation = validator.explain("UPDATE users SET tier = 'pro'", &ctx);
+        assert_eq!(explanation.decision(), "CONFIRM");
+        let policy = &explanation.checks[4];
+        assert_eq!(policy.outcome, Outcome::Confirm);
+        assert_eq!(policy.children[0].outcome, Outcome::NoMatch);
+        assert_eq!(policy.children[0].children[1].label, "estimated rows (10) >= 1000");
+        assert!(policy.children.iter().any(|rule| rule.label.starts_with("balanced-dml")
+            && rule.outcome == Outcome::Confirm));
+
+        let text = explanation.to_string();
+        assert!(text.contains("├─ Blacklist: pass"));
+        assert!(text.ends_with("Decision: CONFIRM (rule 'balanced-dml')"));
+    }
+
+    #[test]
+    fn test_explain_blacklist_block() {
+        let validator = SafetyValidator::new();
+        let explanation = validator.explain("DROP TABLE users", &SafetyContext::default());
+        assert_eq!(explanation.decision(), "DENY");
+        assert_eq!(explanation.checks[0].outcome, Outcome::Block);
+        assert!(explanation.checks[0].children.iter().any(|p| p.outcome == Outcome::Match));
+        assert!(explanation.checks[1..].iter().all(|c| c.outcome == Outcome::NotReached));
+    }
+}
@@ -0,0 +1,337 @@
+//! Query fingerprints.
+//!
+//! Queries differing only in literal values, parameters, comments, case or
+//! spacing share a shape: literals become `?`, and `IN` and `VALUES` lists
+//! collapse to `(...)`. The fingerprint is a stable hash of that shape, so
+//! audit and history entries of the same query can be counted together.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// A list of placeholders after `IN`.
+    static ref IN_LIST: Regex = Regex::new(r"\bin \(\?(?:, \?)*\)").expect("valid regex");
+    /// One or more rows of placeholders after `VALUES`.
+    static ref VALUES_LIST: Regex =
+        Regex::new(r"\bvalues \(\?(?:, \?)*\)(?:, \(\?(?:, \?)*\))*").expect("valid regex");
+}
+
+/// Characters that combine into one operator token, e.g. `>=` or `->>`.
+const OPERATOR_CHARS: &str = "+-*/<>=~!@#%^&|`";
+
+/// The shape of `sql`: lower-case keywords and identifiers, literals and
+/// parameters replaced with `?`, comments removed and spacing normalized.
+#[must_use]
+pub fn normalize(sql: &str) -> String {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens: Vec<String> = Vec::new();
+    // Whether whitespace preceded each token
+    let mut spaced: Vec<bool> = Vec::new();
+    let mut space = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+        let token = if c.is_whitespace() {
+            space = true;
+            i += 1;
+            continue;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            space = true;
+            continue;
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+            space = true;
+            continue;
+        } else if c == '\'' {
+            i = skip_quoted(&chars, i, '\'');
+            "?".to_string()
+        } else if c == '"' {
+            i = skip_quoted(&chars, i, '"');
+            chars[start..i].iter().collect()
+        } else if c == '$' && next.is_some_and(|n| n.is_ascii_digit()) {
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            "?".to_string()
+        } else if c == '$' && let Some(end) = dollar_quote_end(&chars, i) {
+            i = end;
+            "?".to_string()
+        } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            i = skip_number(&chars, i);
+            "?".to_string()
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$')) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect::<String>().to_lowercase();
+            // Prefixed string literals: E'...', B'...', X'...', N'...'
+            if chars.get(i) == Some(&'\'') && matches!(word.as_str(), "e" | "b" | "x" | "n") {
+                i = skip_quoted(&chars, i, '\'');
+                "?".to_string()
+            } else {
+                word
+            }
+        } else if c == ':' && next == Some(':') {
+            i += 2;
+            "::".to_string()
+        } else if OPERATOR_CHARS.contains(c) {
+            while i < chars.len() && OPERATOR_CHARS.contains(chars[i]) {
+                i += 1;
+            }
+            chars[start..i].iter().collect()
+        } else {
+            i += 1;
+            c.to_string()
+        };
+        tokens.push(token);
+        spaced.push(space);
+        space = false;
+    }
+    while tokens.last().is_some_and(|t| t == ";") {
+        tokens.pop();
+    }
+
+    let mut shape = String::with_capacity(sql.len());
+    for (n, token) in tokens.iter().enumerate() {
+        let previous = n.checked_sub(1).map(|p| tokens[p].as_str());
+        let glued = match (previous, token.as_str()) {
+            (None, _) => true,
+            (_, "," | ")" | ";" | "." | "::" | "]") => true,
+            (Some("(" | "." | "::" | "["), _) => true,
+            // Keep `count(*)` and `in (` as written
+            (_, "(" | "[") => !spaced[n],
+            _ => false,
+        };
+        if !glued {
+            shape.push(' ');
+        }
+        shape.push_str(token);
+    }
+    let shape = IN_LIST.replace_all(&shape, "in (...)");
+    VALUES_LIST.replace_all(&shape, "values (...)").into_owned()
+}
+
+/// Fingerprint of `sql`: 16 hex digits hashing its [`normalize`]d shape.
+#[must_use]
+pub fn fingerprint(sql: &str) -> String {
+    fingerprint_shape(&normalize(sql))
+}
+
+/// Fingerprint of an already normalized shape.
+#[must_use]
+pub fn fingerprint_shape(shape: &str) -> String {
+    // FNV-1a, stable across builds and platforms
+    let hash = shape.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Position after the quoted text starting at `start`; a doubled quote
+/// character is part of the text.
+fn skip_quoted(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        // Backslash escapes of E'' strings
+        if chars[i] == '\\' && quote == '\'' {
+            i += 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Position after the dollar-quoted string starting at `start`, if there
+/// is one.
+fn dollar_quote_end(chars: &[char], start: usize) -> Option<usize> {
+    let tag_end = (start + 1..chars.len()).find(|&i| chars[i] == '$')?;
+    let tag = &chars[start..=tag_end];
+    if !tag[1..tag.len() - 1].iter().all(|c| c.is_alphanumeric() || *c == '_')
+        || tag.get(1).is_some_and(char::is_ascii_digit)
+    {
+        return None;
+    }
+    let body = tag_end + 1;
+    (body..=chars.len().saturating_sub(tag.len()))
+        .find(|&i| chars[i..i + tag.len()] == *tag)
+        .map(|i| i + tag.len())
+        .or(Some(chars.len()))
+}
+
+/// Position after the numeric literal starting at `start`.
+fn skip_number(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+        // Exponent sign, as in 1e-5
+        if matches!(chars[i], 'e' | 'E') && matches!(chars.get(i + 1), Some('+' | '-')) {
+            i += 1;
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Runs of one query shape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShapeActivity {
+    /// Fingerprint of the shape.
+    pub fingerprint: String,
+    /// The normalized query.
+    pub shape: String,
+    /// Number of runs.
+    pub runs: usize,
+    /// Runs that failed.
+    pub failures: usize,
+    /// Total duration of runs with a known duration, in milliseconds.
+    pub total_ms: u64,
+    /// Runs with a known duration.
+    pub timed_runs: usize,
+    /// Time of the latest run.
+    pub last_run: DateTime<Utc>,
+}
+
+impl ShapeActivity {
+    /// Mean duration of the timed runs, in milliseconds.
+    #[must_use]
+    pub fn average_ms(&self) -> Option<u64> {
+        (self.timed_runs > 0).then(|| self.total_ms / self.timed_runs as u64)
+    }
+}
+
+/// Query runs grouped by fingerprint.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityByShape {
+    shapes: HashMap<String, ShapeActivity>,
+}
+
+impl ActivityByShape {
+    /// Create an empty summary.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a run of `sql`.
+    pub fn add(&mut self, sql: &str, at: DateTime<Utc>, success: bool, duration_ms: Option<u64>) {
+        let shape = normalize(sql);
+        let activity = self
+            .shapes
+            .entry(fingerprint_shape(&shape))
+            .or_insert_with_key(|fingerprint| ShapeActivity {
+                fingerprint: fingerprint.clone(),
+                shape,
+                runs: 0,
+                failures: 0,
+                total_ms: 0,
+                timed_runs: 0,
+                last_run: at,
+            });
+        activity.runs += 1;
+        if !success {
+            activity.failures += 1;
+        }
+        if let Some(ms) = duration_ms {
+            activity.total_ms = activity.total_ms.saturating_add(ms);
+            activity.timed_runs += 1;
+        }
+        activity.last_run = activity.last_run.max(at);
+    }
+
+    /// Number of distinct shapes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    /// Whether no runs were counted.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    /// The `limit` shapes run most often, the most recently run first
+    /// among equals.
+    #[must_use]
+    pub fn top(&self, limit: usize) -> Vec<&ShapeActivity> {
+        let mut shapes: Vec<&ShapeActivity> = self.shapes.values().collect();
+        shapes.sort_by(|a, b| {
+            b.runs.cmp(&a.runs).then(b.last_run.cmp(&a.last_run)).then(a.shape.cmp(&b.shape))
+        });
+        shapes.truncate(limit);
+        shapes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_literals() {
+        assert_eq!(
+            normalize("SELECT *  FROM orders WHERE id = 42 AND status = 'it''s' -- note\n;"),
+            "select * from orders where id = ? and status = ?"
+        );
+        assert_eq!(
+            normalize("select count(*)from \"Events\" where at > $1::date - 1.5e-3"),
+            "select count(*) from \"Events\" where at > ?::date - ?"
+        );
+        assert_eq!(
+            normalize("SELECT $fn$ body $fn$, E'a\\'b', x1 FROM t /* hint */ LIMIT 10"),
+            "select ?, ?, x1 from t limit ?"
+        );
+    }
+
+    #[test]
+    fn test_same_shape_same_fingerprint() {
+        let a = "SELECT name FROM users WHERE id IN (1, 2, 3)";
+        let b = "select name\n  from USERS\n where id in (7)";
+        assert_eq!(normalize(a), "select name from users where id in (...)");
+        assert_eq!(fingerprint(a), fingerprint(b));
+        assert_ne!(fingerprint(a), fingerprint("SELECT email FROM users WHERE id IN (1)"));
+        assert_eq!(
+            normalize("INSERT INTO t (a, b) VALUES (1, 'x'), (2, 'y')"),
+            "insert into t (a, b) values (...)"
+        );
+        assert_eq!(fingerprint("SELECT 1").len(), 16);
+    }
+
+    #[test]
+    fn test_activity_by_shape() {
+        let now = Utc::now();
+        let earlier = now - chrono::Duration::hours(1);
+        let mut activity = ActivityByShape::new();
+        activity.add("SELECT * FROM t WHERE id = 1", earlier, true, Some(10));
+        activity.add("SELECT * FROM t WHERE id = 2", now, false, Some(30));
+        activity.add("SELECT * FROM t WHERE id = 3", now, true, None);
+        activity.add("SELECT count(*) FROM t", now, true, Some(5));
+
+        assert_eq!(activity.len(), 2);
+        let top = activity.top(10);
+        assert_eq!(top[0].shape, "select * from t where id = ?");
+        assert_eq!((top[0].runs, top[0].failures), (3, 1));
+        assert_eq!(top[0].average_ms(), Some(20));
+        assert_eq!(top[0].last_run, now);
+        assert_eq!(activity.top(1).len(), 1);
+    }
+}
@@ -3,10 +3,19 @@
 //! This crate provides comprehensive safety features for the PostgreSQL Agent:
 //! - SQL validation and classification
 //! - Safety levels (ReadOnly, Balanced, Permissive)
+//! - Policy rules deciding allow, confirm or deny per operation
+//! - Check-by-check explanations of validation decisions
 //! - Blacklist pattern matching
 //! - PII detection and redaction
 //! - Confirmation workflows for risky operations
+//! - Two-person admin approval through a shared approval store
+//! - Lock and table-rewrite analysis for DDL
+//! - Row-level security warnings
+//! - `SELECT *` guard for wide and large tables
+//! - Multi-tenant query scoping
+//! - Temp table statements for the session workspace
 //! - Audit logging for compliance
+//! - Query fingerprints for aggregating audit and history entries
 //!
 //! # Example
 //!
@@ -22,18 +31,41 @@
 
 #![warn(missing_docs)]
 
+pub mod approval;
 pub mod audit;
 pub mod blacklist;
 pub mod confirmation;
+pub mod explain;
+pub mod fingerprint;
+pub mod locks;
 pub mod pii;
+pub mod policy;
+pub mod rls;
+pub mod select_star;
+pub mod tables;
+pub mod temp_tables;
+pub mod tenant;
 pub mod validator;
 
 // Re-export types for convenience
-pub use audit::{AuditConfig, AuditEvent, AuditLogger, AuditRecord};
+pub use approval::{current_user, ApprovalError, ApprovalRecord, ApprovalStore};
+pub use audit::{read_audit_log, rotated_logs, AuditConfig, AuditEvent, AuditLogger, AuditRecord};
 pub use confirmation::{
-    ConfirmationLevel, ConfirmationRequest, ConfirmationWorkflow,
+    ConfirmationLevel, ConfirmationRequest, ConfirmationStatus, ConfirmationWorkflow,
+    DEFAULT_CONFIRMATION_TTL,
 };
+pub use explain::{ExplainNode, Explanation, Outcome};
+pub use fingerprint::{fingerprint, ActivityByShape, ShapeActivity};
+pub use locks::{analyze_ddl, describe_warnings, LockMode, LockWarning};
 pub use pii::{PiiDetector, PiiType};
+pub use policy::{
+    parse_operations, ConditionCheck, Policy, PolicyAction, PolicyDecision, PolicyRule, TimeWindow,
+};
+pub use rls::rls_warnings;
+pub use select_star::{SelectStarAction, SelectStarGuard, TableShape};
+pub use tables::{tables_in_query, unqualified_tables};
+pub use temp_tables::is_temp_table_statement;
+pub use tenant::{TenantMode, TenantScope};
 pub use validator::{
     OperationType, SafetyContext, SafetyLevel, SafetyValidator, ValidationDetail,
     ValidationDetailKind, ValidationResult,
@@ -0,0 +1,270 @@
+//! DDL lock analysis.
+//!
+//! This module flags schema changes that take heavy locks or rewrite whole
+//! tables, and suggests safer ways to make the same change. The checks are
+//! pattern based and err on the side of warning.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// First server version (`server_version_num`) where adding a column with
+/// a non-volatile default no longer rewrites the table.
+const FAST_DEFAULT_VERSION: u32 = 110_000;
+
+/// First server version with `REINDEX CONCURRENTLY`.
+const REINDEX_CONCURRENTLY_VERSION: u32 = 120_000;
+
+/// Table lock modes relevant to schema changes, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LockMode {
+    /// Allows reads and writes; blocks other schema changes.
+    ShareUpdateExclusive,
+    /// Blocks writes, allows reads.
+    Share,
+    /// Blocks writes, allows reads; self-exclusive.
+    ShareRowExclusive,
+    /// Blocks all access, including reads.
+    AccessExclusive,
+}
+
+impl LockMode {
+    /// Lock name as written in PostgreSQL documentation.
+    #[must_use]
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::ShareUpdateExclusive => "SHARE UPDATE EXCLUSIVE",
+            Self::Share => "SHARE",
+            Self::ShareRowExclusive => "SHARE ROW EXCLUSIVE",
+            Self::AccessExclusive => "ACCESS EXCLUSIVE",
+        }
+    }
+
+    /// What the lock blocks, for prompts.
+    #[must_use]
+    pub fn blocks(&self) -> &'static str {
+        match self {
+            Self::ShareUpdateExclusive => "other schema changes",
+            Self::Share | Self::ShareRowExclusive => "writes",
+            Self::AccessExclusive => "reads and writes",
+        }
+    }
+}
+
+/// A risky operation found in a DDL statement.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockWarning {
+    /// Lock taken for the duration of the operation.
+    pub lock: LockMode,
+    /// Whether the whole table is rewritten.
+    pub rewrites_table: bool,
+    /// What is risky.
+    pub message: String,
+    /// Safer alternative.
+    pub suggestion: String,
+}
+
+impl LockWarning {
+    /// Format the warning for a confirmation prompt.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        format!(
+            "{} ({} lock blocks {}{}). Safer: {}",
+            self.message,
+            self.lock.label(),
+            self.lock.blocks(),
+            if self.rewrites_table { ", rewrites the table" } else { "" },
+            self.suggestion
+        )
+    }
+}
+
+lazy_static! {
+    static ref CREATE_INDEX: Regex =
+        Regex::new(r"(?i)^\s*CREATE\s+(UNIQUE\s+)?INDEX\s+(CONCURRENTLY\b)?").unwrap();
+    static ref DROP_INDEX: Regex = Regex::new(r"(?i)^\s*DROP\s+INDEX\s+(CONCURRENTLY\b)?").unwrap();
+    static ref REINDEX: Regex =
+        Regex::new(r"(?i)^\s*REINDEX\s+(\(.*?\)\s*)?\w+\s+(CONCURRENTLY\b)?").unwrap();
+    static ref ALTER_TABLE: Regex = Regex::new(r"(?i)^\s*ALTER\s+TABLE\b").unwrap();
+    static ref ADD_COLUMN_DEFAULT: Regex =
+        Regex::new(r"(?i)\bADD\s+(COLUMN\s+)?[\w.\x22]+\s+[^,]*?\bDEFAULT\s+([^,]+)").unwrap();
+    static ref VOLATILE_DEFAULT: Regex = Regex::new(
+        r"(?i)\b(random|clock_timestamp|gen_random_uuid|uuid_generate_v\d|nextval|timeofday)\s*\("
+    )
+    .unwrap();
+    static ref ALTER_TYPE: Regex =
+        Regex::new(r"(?i)\bALTER\s+(COLUMN\s+)?[\w\x22]+\s+(SET\s+DATA\s+)?TYPE\b").unwrap();
+    static ref SET_NOT_NULL: Regex = Regex::new(r"(?i)\bSET\s+NOT\s+NULL\b").unwrap();
+    static ref ADD_CHECKED_CONSTRAINT: Regex =
+        Regex::new(r"(?i)\bADD\s+(CONSTRAINT\s+[\w\x22]+\s+)?(FOREIGN\s+KEY|CHECK)\b").unwrap();
+    static ref NOT_VALID: Regex = Regex::new(r"(?i)\bNOT\s+VALID\b").unwrap();
+    static ref ADD_INDEXED_CONSTRAINT: Regex =
+        Regex::new(r"(?i)\bADD\s+(CONSTRAINT\s+[\w\x22]+\s+)?(PRIMARY\s+KEY|UNIQUE)\b").unwrap();
+    static ref USING_INDEX: Regex = Regex::new(r"(?i)\bUSING\s+INDEX\b").unwrap();
+    static ref VACUUM_FULL: Regex = Regex::new(r"(?i)^\s*VACUUM\s+(\(.*FULL.*\)|FULL\b)").unwrap();
+    static ref CLUSTER: Regex = Regex::new(r"(?i)^\s*CLUSTER\b").unwrap();
+    static ref TRUNCATE: Regex = Regex::new(r"(?i)^\s*TRUNCATE\b").unwrap();
+}
+
+/// Analyze a statement for heavy locks and table rewrites.
+///
+/// `server_version_num` (e.g. `160002`) refines version-dependent checks;
+/// without it a modern server is assumed.
+#[must_use]
+pub fn analyze_ddl(sql: &str, server_version_num: Option<u32>) -> Vec<LockWarning> {
+    let version = server_version_num.unwrap_or(u32::MAX);
+    let mut warnings = Vec::new();
+    let mut warn = |lock, rewrites_table, message: &str, suggestion: &str| {
+        warnings.push(LockWarning {
+            lock,
+            rewrites_table,
+            message: message.to_string(),
+            suggestion: suggestion.to_string(),
+        });
+    };
+
+    if let Some(caps) = CREATE_INDEX.captures(sql)
+        && caps.get(2).is_none()
+    {
+        warn(
+            LockMode::Share,
+            false,
+            "CREATE INDEX blocks writes to the table while the index builds",
+            "CREATE INDEX CONCURRENTLY (outside a transaction block)",
+        );
+    }
+    if let Some(caps) = DROP_INDEX.captures(sql)
+        && caps.get(1).is_none()
+    {
+        warn(
+            LockMode::AccessExclusive,
+            false,
+            "DROP INDEX locks the table",
+            "DROP INDEX CONCURRENTLY",
+        );
+    }
+    if let Some(caps) = REINDEX.captures(sql)
+        && caps.get(2).is_none()
+    {
+        let suggestion = if version >= REINDEX_CONCURRENTLY_VERSION {
+            "REINDEX ... CONCURRENTLY"
+        } else {
+            "CREATE INDEX CONCURRENTLY a replacement, then drop the old index"
+        };
+        warn(LockMode::AccessExclusive, false, "REINDEX locks the table and index", suggestion);
+    }
+    if VACUUM_FULL.is_match(sql) || CLUSTER.is_match(sql) {
+        warn(
+            LockMode::AccessExclusive,
+            true,
+            "VACUUM FULL and CLUSTER rewrite the table under an exclusive lock",
+            "pg_repack, or plain VACUUM if reclaiming space for reuse is enough",
+        );
+    }
+    if TRUNCATE.is_match(sql) {
+        warn(
+            LockMode::AccessExclusive,
+            false,
+            "TRUNCATE locks the table",
+            "DELETE in batches if concurrent access must continue",
+        );
+    }
+
+    if ALTER_TABLE.is_match(sql) {
+        if let Some(caps) = ADD_COLUMN_DEFAULT.captures(sql) {
+            let default = caps.get(2).map_or("", |m| m.as_str());
+            if VOLATILE_DEFAULT.is_match(default) || version < FAST_DEFAULT_VERSION {
+                warn(
+                    LockMode::AccessExclusive,
+                    true,
+                    "Adding a column with this default rewrites every row",
+                    "add the column without a default, set the default, then backfill existing rows in batches",
+                );
+            }
+        }
+        if ALTER_TYPE.is_match(sql) {
+            warn(
+                LockMode::AccessExclusive,
+                true,
+                "Changing a column type usually rewrites the table and its indexes",
+                "add a new column, backfill it in batches, then swap columns in a short transaction",
+            );
+        }
+        if SET_NOT_NULL.is_match(sql) {
+            warn(
+                LockMode::AccessExclusive,
+                false,
+                "SET NOT NULL scans the whole table under an exclusive lock",
+                "ADD CONSTRAINT ... CHECK (col IS NOT NULL) NOT VALID, VALIDATE CONSTRAINT, then SET NOT NULL",
+            );
+        }
+        if ADD_CHECKED_CONSTRAINT.is_match(sql) && !NOT_VALID.is_match(sql) {
+            warn(
+                LockMode::ShareRowExclusive,
+                false,
+                "Adding a foreign key or check constraint validates every row while blocking writes",
+                "add it with NOT VALID, then VALIDATE CONSTRAINT separately",
+            );
+        }
+        if ADD_INDEXED_CONSTRAINT.is_match(sql) && !USING_INDEX.is_match(sql) {
+            warn(
+                LockMode::AccessExclusive,
+                false,
+                "Adding a primary key or unique constraint builds its index under an exclusive lock",
+                "CREATE UNIQUE INDEX CONCURRENTLY, then ADD CONSTRAINT ... USING INDEX",
+            );
+        }
+    }
+
+    warnings
+}
+
+/// Render warnings as lines for a confirmation prompt, with a closing
+/// reminder to bound lock waits.
+#[must_use]
+pub fn describe_warnings(warnings: &[LockWarning]) -> Vec<String> {
+    if warnings.is_empty() {
+        return Vec::new();
+    }
+    let mut lines: Vec<String> = warnings.iter().map(LockWarning::describe).collect();
+    lines.push("Run with SET lock_timeout = '5s' so a blocked lock does not queue other queries".to_string());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_index() {
+        let warnings = analyze_ddl("CREATE INDEX idx_orders_user ON orders (user_id)", None);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].lock, LockMode::Share);
+        assert!(warnings[0].suggestion.contains("CONCURRENTLY"));
+
+        assert!(analyze_ddl("CREATE INDEX CONCURRENTLY idx ON orders (user_id)", None).is_empty());
+    }
+
+    #[test]
+    fn test_add_column_default_depends_on_version() {
+        let sql = "ALTER TABLE orders ADD COLUMN region text DEFAULT 'eu'";
+        assert!(analyze_ddl(sql, Some(160_000)).is_empty());
+        assert!(analyze_ddl(sql, Some(100_000))[0].rewrites_table);
+
+        let volatile = "ALTER TABLE orders ADD COLUMN token uuid DEFAULT gen_random_uuid()";
+        assert!(analyze_ddl(volatile, Some(160_000))[0].rewrites_table);
+    }
+
+    #[test]
+    fn test_constraints() {
+        let fk = "ALTER TABLE orders ADD CONSTRAINT fk_user FOREIGN KEY (user_id) REFERENCES users (id)";
+        assert_eq!(analyze_ddl(fk, None)[0].lock, LockMode::ShareRowExclusive);
+        assert!(analyze_ddl(&format!("{} NOT VALID", fk), None).is_empty());
+
+        let lines = describe_warnings(&analyze_ddl("ALTER TABLE t ALTER COLUMN c SET NOT NULL", None));
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("blocks reads and writes"));
+    }
+}
@@ -0,0 +1,462 @@
+//! Policy rules deciding whether SQL may run.
+//!
+//! A policy is an ordered list of rules. Each rule combines conditions on
+//! the operation type, the tables referenced, the planner's row estimate,
+//! the time of day and the database role, and the first rule whose
+//! conditions all hold decides: allow, confirm or deny. Configured rules
+//! are evaluated before the built-in rules of the safety level.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Local, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::tables::tables_in_query;
+use crate::validator::{OperationType, SafetyContext, SafetyLevel};
+
+/// What a matching rule decides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyAction {
+    /// Run the operation.
+    Allow,
+    /// Run the operation once the user confirms it.
+    Confirm,
+    /// Refuse the operation.
+    Deny,
+}
+
+impl PolicyAction {
+    /// Lower-case name of the action.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Confirm => "confirm",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+/// A daily window of local time, e.g. `22:00-06:00`; it may wrap past
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    /// First minute of the window.
+    pub start: NaiveTime,
+    /// End of the window, exclusive.
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    /// Whether `time` falls in the window.
+    #[must_use]
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("Invalid time '{}' in '{}' (expected HH:MM)", time.trim(), s))
+        };
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid time window '{}' (expected HH:MM-HH:MM)", s))?;
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+/// Operation types named by `name`: a single type such as `update`, or
+/// the groups `dml`, `ddl` and `write`.
+///
+/// # Errors
+/// Returns an error naming the unknown operation.
+pub fn parse_operations(name: &str) -> Result<Vec<OperationType>, String> {
+    use OperationType::{
+        Alter, Create, Delete, Drop, Grant, Insert, Maintenance, Other, Read, Transaction,
+        Truncate, Update,
+    };
+    const DML: [OperationType; 3] = [Insert, Update, Delete];
+    const DDL: [OperationType; 4] = [Alter, Create, Drop, Truncate];
+
+    Ok(match name.trim().to_lowercase().as_str() {
+        "select" | "read" => vec![Read],
+        "insert" => vec![Insert],
+        "update" => vec![Update],
+        "delete" => vec![Delete],
+        "alter" => vec![Alter],
+        "create" => vec![Create],
+        "drop" => vec![Drop],
+        "truncate" => vec![Truncate],
+        "grant" | "revoke" => vec![Grant],
+        "maintenance" => vec![Maintenance],
+        "transaction" => vec![Transaction],
+        "other" => vec![Other],
+        "dml" => DML.to_vec(),
+        "ddl" => DDL.to_vec(),
+        "write" => DML.into_iter().chain(DDL).collect(),
+        other => {
+            return Err(format!(
+                "Unknown operation '{}' (expected e.g. select, update, dml, ddl or write)",
+                other
+            ));
+        }
+    })
+}
+
+/// Outcome of one condition of a rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionCheck {
+    /// The condition, e.g. `operation is UPDATE or DELETE`.
+    pub condition: String,
+    /// Whether it holds.
+    pub matched: bool,
+}
+
+/// A rule of a policy. Conditions left empty always hold, so a rule
+/// without conditions matches every operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyRule {
+    /// Rule name, shown when it decides.
+    pub name: String,
+    /// Decision when every condition holds.
+    pub action: PolicyAction,
+    /// Operation types the rule applies to.
+    pub operations: Vec<OperationType>,
+    /// Tables (`schema.table` or `table`) of which one must be referenced.
+    pub tables: Vec<String>,
+    /// Minimum planner row estimate; unknown estimates do not match.
+    pub min_rows: Option<i64>,
+    /// Local time of day the rule applies in.
+    pub hours: Option<TimeWindow>,
+    /// Days of the week the rule applies on.
+    pub days: Vec<Weekday>,
+    /// Database roles the rule applies to.
+    pub roles: Vec<String>,
+    /// Reason given when the rule denies or asks for confirmation;
+    /// `{operation}` is replaced with the operation type.
+    pub message: Option<String>,
+}
+
+impl PolicyRule {
+    /// Create a rule matching every operation.
+    #[must_use]
+    pub fn new(name: impl Into<String>, action: PolicyAction) -> Self {
+        Self {
+            name: name.into(),
+            action,
+            operations: Vec::new(),
+            tables: Vec::new(),
+            min_rows: None,
+            hours: None,
+            days: Vec::new(),
+            roles: Vec::new(),
+            message: None,
+        }
+    }
+
+    /// Apply to these operation types only.
+    #[must_use]
+    pub fn on_operations(mut self, operations: Vec<OperationType>) -> Self {
+        self.operations = operations;
+        self
+    }
+
+    /// Apply to operations referencing one of these tables only.
+    #[must_use]
+    pub fn on_tables(mut self, tables: Vec<String>) -> Self {
+        self.tables = tables;
+        self
+    }
+
+    /// Apply when the planner estimates at least `rows` rows.
+    #[must_use]
+    pub fn with_min_rows(mut self, rows: i64) -> Self {
+        self.min_rows = Some(rows);
+        self
+    }
+
+    /// Apply within a daily window of local time.
+    #[must_use]
+    pub fn during(mut self, hours: TimeWindow) -> Self {
+        self.hours = Some(hours);
+        self
+    }
+
+    /// Apply on these days of the week only.
+    #[must_use]
+    pub fn on_days(mut self, days: Vec<Weekday>) -> Self {
+        self.days = days;
+        self
+    }
+
+    /// Apply to these database roles only.
+    #[must_use]
+    pub fn for_roles(mut self, roles: Vec<String>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    /// Set the reason given when the rule denies or asks for confirmation.
+    #[must_use]
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Evaluate each condition of the rule.
+    #[must_use]
+    pub fn check(
+        &self,
+        sql: &str,
+        operation: OperationType,
+        ctx: &SafetyContext,
+    ) -> Vec<ConditionCheck> {
+        let mut checks = Vec::new();
+        let mut push = |condition: String, matched: bool| {
+            checks.push(ConditionCheck { condition, matched });
+        };
+        if !self.operations.is_empty() {
+            let labels: Vec<&str> = self.operations.iter().map(OperationType::label).collect();
+            push(
+                format!("operation is {}", labels.join(" or ")),
+                self.operations.contains(&operation),
+            );
+        }
+        if !self.tables.is_empty() {
+            push(
+                format!("references {}", self.tables.join(" or ")),
+                !tables_in_query(sql, &self.tables).is_empty(),
+            );
+        }
+        if let Some(min_rows) = self.min_rows {
+            let estimate = ctx.estimated_rows.map_or("unknown".to_string(), |r| r.to_string());
+            push(
+                format!("estimated rows ({}) >= {}", estimate, min_rows),
+                ctx.estimated_rows.is_some_and(|rows| rows >= min_rows),
+            );
+        }
+        let now = ctx.now.unwrap_or_else(|| Local::now().naive_local());
+        if let Some(hours) = self.hours {
+            push(
+                format!("local time ({}) within {}", now.format("%H:%M"), hours),
+                hours.contains(now.time()),
+            );
+        }
+        if !self.days.is_empty() {
+            let days: Vec<String> = self.days.iter().map(ToString::to_string).collect();
+            push(
+                format!("day ({}) is {}", now.weekday(), days.join(" or ")),
+                self.days.contains(&now.weekday()),
+            );
+        }
+        if !self.roles.is_empty() {
+            let role = ctx.role.as_deref().unwrap_or("unknown");
+            push(
+                format!("role ({}) is {}", role, self.roles.join(" or ")),
+                ctx.role.as_ref().is_some_and(|role| self.roles.contains(role)),
+            );
+        }
+        checks
+    }
+
+    /// Whether every condition of the rule holds.
+    #[must_use]
+    pub fn matches(&self, sql: &str, operation: OperationType, ctx: &SafetyContext) -> bool {
+        self.check(sql, operation, ctx).iter().all(|check| check.matched)
+    }
+}
+
+/// Decision of a policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyDecision {
+    /// What the deciding rule decides.
+    pub action: PolicyAction,
+    /// Name of the deciding rule.
+    pub rule: String,
+    /// Reason given by the rule.
+    pub message: Option<String>,
+}
+
+/// An ordered list of rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    rules: Vec<PolicyRule>,
+}
+
+impl Policy {
+    /// Create a policy from rules, evaluated in order.
+    #[must_use]
+    pub fn new(rules: Vec<PolicyRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Built-in rules of a safety level.
+    #[must_use]
+    pub fn for_level(level: SafetyLevel, allow_maintenance: bool) -> Self {
+        use OperationType::{
+            Alter, Create, Delete, Drop, Grant, Insert, Maintenance, Truncate, Update,
+        };
+        let dml = vec![Insert, Update, Delete];
+        let ddl = vec![Alter, Create, Drop, Truncate];
+        let level_rule = |kind: &str, action: PolicyAction, operations: Vec<OperationType>| {
+            let decision = match action {
+                PolicyAction::Deny => "not allowed",
+                PolicyAction::Confirm => "need confirmation",
+                PolicyAction::Allow => "allowed",
+            };
+            let name = format!("{:?}-{}", level, kind).to_lowercase();
+            PolicyRule::new(name, action).on_operations(operations).with_message(format!(
+                "{} operations ({{operation}}) {} at {:?} safety level",
+                kind, decision, level
+            ))
+        };
+
+        let mut rules = vec![PolicyRule::new("no-grant", PolicyAction::Deny)
+            .on_operations(vec![Grant])
+            .with_message("GRANT/REVOKE operations are not allowed")];
+        if !allow_maintenance {
+            rules.push(
+                PolicyRule::new("no-maintenance", PolicyAction::Deny)
+                    .on_operations(vec![Maintenance])
+                    .with_message("Maintenance operations not allowed"),
+            );
+        }
+        let (dml_action, ddl_action) = match level {
+            SafetyLevel::ReadOnly => (PolicyAction::Deny, PolicyAction::Deny),
+            SafetyLevel::Balanced => (PolicyAction::Confirm, PolicyAction::Deny),
+            SafetyLevel::Permissive => (PolicyAction::Allow, PolicyAction::Allow),
+        };
+        rules.push(level_rule("DML", dml_action, dml));
+        rules.push(level_rule("DDL", ddl_action, ddl));
+        Self { rules }
+    }
+
+    /// The rules, in evaluation order.
+    #[must_use]
+    pub fn rules(&self) -> &[PolicyRule] {
+        &self.rules
+    }
+
+    /// Whether the policy has no rules.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether a rule depends on the planner's row estimate.
+    #[must_use]
+    pub fn needs_row_estimate(&self) -> bool {
+        self.rules.iter().any(|rule| rule.min_rows.is_some())
+    }
+
+    /// Whether a rule depends on the database role.
+    #[must_use]
+    pub fn needs_role(&self) -> bool {
+        self.rules.iter().any(|rule| !rule.roles.is_empty())
+    }
+
+    /// Decision of the first matching rule, if any matches.
+    #[must_use]
+    pub fn evaluate(
+        &self,
+        sql: &str,
+        operation: OperationType,
+        ctx: &SafetyContext,
+    ) -> Option<PolicyDecision> {
+        let rule = self.rules.iter().find(|rule| rule.matches(sql, operation, ctx))?;
+        Some(PolicyDecision {
+            action: rule.action,
+            rule: rule.name.clone(),
+            message: rule
+                .message
+                .as_ref()
+                .map(|message| message.replace("{operation}", operation.label())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(hour: u32) -> SafetyContext {
+        // 2024-06-03 is a Monday
+        let now = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+        SafetyContext::with_level(SafetyLevel::Balanced).at(now)
+    }
+
+    #[test]
+    fn test_time_window() {
+        let night: TimeWindow = "22:00-06:00".parse().unwrap();
+        assert!(night.contains(NaiveTime::from_hms_opt(23, 30, 0).unwrap()));
+        assert!(night.contains(NaiveTime::from_hms_opt(5, 59, 0).unwrap()));
+        assert!(!night.contains(NaiveTime::from_hms_opt(6, 0, 0).unwrap()));
+        assert_eq!(night.to_string(), "22:00-06:00");
+        assert!("9-17".parse::<TimeWindow>().is_err());
+    }
+
+    #[test]
+    fn test_first_matching_rule_decides() {
+        let policy = Policy::new(vec![
+            PolicyRule::new("no-bulk-deletes", PolicyAction::Deny)
+                .on_operations(parse_operations("delete").unwrap())
+                .with_min_rows(10_000)
+                .with_message("{operation} of 10k+ rows must go through a migration"),
+            PolicyRule::new("payroll-business-hours", PolicyAction::Confirm)
+                .on_tables(vec!["hr.payroll".to_string()])
+                .during("09:00-17:00".parse().unwrap())
+                .on_days(vec![Weekday::Mon, Weekday::Tue]),
+            PolicyRule::new("payroll", PolicyAction::Deny)
+                .on_tables(vec!["hr.payroll".to_string()]),
+        ]);
+
+        let large = at(10).with_estimated_rows(50_000);
+        let bulk = policy.evaluate("DELETE FROM events", OperationType::Delete, &large).unwrap();
+        assert_eq!(bulk.action, PolicyAction::Deny);
+        assert_eq!(
+            bulk.message.as_deref(),
+            Some("DELETE of 10k+ rows must go through a migration")
+        );
+        assert!(policy.evaluate("DELETE FROM events", OperationType::Delete, &at(10)).is_none());
+
+        let sql = "SELECT * FROM hr.payroll";
+        let rule = |hour| policy.evaluate(sql, OperationType::Read, &at(hour)).unwrap().rule;
+        assert_eq!(rule(10), "payroll-business-hours");
+        assert_eq!(rule(20), "payroll");
+        assert!(policy.needs_row_estimate());
+        assert!(!policy.needs_role());
+    }
+
+    #[test]
+    fn test_role_condition() {
+        let rule = PolicyRule::new("analysts-read-only", PolicyAction::Deny)
+            .on_operations(parse_operations("write").unwrap())
+            .for_roles(vec!["analyst".to_string()]);
+        let analyst = at(10).with_role("analyst".to_string());
+        assert!(rule.matches("UPDATE t SET a = 1", OperationType::Update, &analyst));
+        assert!(!rule.matches("SELECT 1", OperationType::Read, &analyst));
+        assert!(!rule.matches("UPDATE t SET a = 1", OperationType::Update, &at(10)));
+        assert!(parse_operations("upsert").is_err());
+    }
+}
@@ -0,0 +1,38 @@
+//! Row-level security awareness.
+//!
+//! Warns when a query reads from tables whose rows are filtered by
+//! row-level security for the current role, so that missing or zero rows
+//! are not mistaken for missing data.
+
+use crate::tables::tables_in_query;
+
+/// Warnings for a query reading from tables filtered by row-level security.
+///
+/// `rls_tables` are given as `schema.table`.
+#[must_use]
+pub fn rls_warnings(sql: &str, rls_tables: &[String]) -> Vec<String> {
+    tables_in_query(sql, rls_tables)
+        .into_iter()
+        .map(|table| {
+            format!(
+                "Row-level security filters {} for the current role; results may be incomplete or empty",
+                table
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rls_warnings() {
+        let tables = vec!["public.orders".to_string()];
+
+        let warnings = rls_warnings("SELECT * FROM orders", &tables);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("public.orders"));
+        assert!(rls_warnings("SELECT * FROM customers", &tables).is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! `SELECT *` guard.
+//!
+//! Reading every column of a wide or large table loads the database and
+//! floods the model's context. Such queries get a warning, or are rejected
+//! so that the agent projects the columns it needs.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use postgres_agent_util::ident::quote_ident;
+use serde::{Deserialize, Serialize};
+
+use crate::tables::tables_in_query;
+
+/// Columns listed as suggestions in a guard message.
+const SUGGESTED_COLUMNS: usize = 8;
+
+lazy_static! {
+    /// A `*` or `alias.*` projection; `count(*)` and multiplication do not match.
+    static ref STAR_PROJECTION: Regex =
+        Regex::new(r"(?i)(?:\bSELECT(?:\s+DISTINCT)?|,|\.)\s*\*").expect("valid regex");
+}
+
+/// What to do with a `SELECT *` on a wide or large table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelectStarAction {
+    /// Run the query and return a warning with it.
+    #[default]
+    Warn,
+    /// Refuse the query and ask for specific columns.
+    Reject,
+}
+
+/// Width and size of a table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableShape {
+    /// Table name as `schema.table`.
+    pub name: String,
+    /// Column names in table order.
+    pub columns: Vec<String>,
+    /// Planner row estimate; negative when the table was never analyzed.
+    pub estimated_rows: i64,
+}
+
+/// Thresholds above which `SELECT *` is flagged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectStarGuard {
+    /// Tables with more columns are flagged.
+    pub max_columns: usize,
+    /// Tables with more estimated rows are flagged.
+    pub max_rows: i64,
+    /// Handling of flagged queries.
+    #[serde(default)]
+    pub action: SelectStarAction,
+}
+
+impl SelectStarGuard {
+    /// Create a guard with the given thresholds that warns.
+    #[must_use]
+    pub fn new(max_columns: usize, max_rows: i64) -> Self {
+        Self {
+            max_columns,
+            max_rows,
+            action: SelectStarAction::default(),
+        }
+    }
+
+    /// Set the handling of flagged queries.
+    #[must_use]
+    pub fn with_action(mut self, action: SelectStarAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Whether `sql` projects all columns with `*`.
+    #[must_use]
+    pub fn selects_star(sql: &str) -> bool {
+        STAR_PROJECTION.is_match(sql)
+    }
+
+    /// Messages for wide or large tables read by a `SELECT *` in `sql`.
+    #[must_use]
+    pub fn check(&self, sql: &str, tables: &[TableShape]) -> Vec<String> {
+        if !Self::selects_star(sql) {
+            return Vec::new();
+        }
+        let names: Vec<String> = tables.iter().map(|t| t.name.clone()).collect();
+        let referenced = tables_in_query(sql, &names);
+        tables
+            .iter()
+            .filter(|table| referenced.contains(&table.name.as_str()))
+            .filter(|table| {
+                table.columns.len() > self.max_columns || table.estimated_rows > self.max_rows
+            })
+            .map(|table| self.describe(table))
+            .collect()
+    }
+
+    /// Check `sql`, returning warnings for queries that may run.
+    ///
+    /// # Errors
+    /// Returns the reasons if the guard rejects the query.
+    pub fn apply(&self, sql: &str, tables: &[TableShape]) -> Result<Vec<String>, String> {
+        let messages = self.check(sql, tables);
+        if self.action == SelectStarAction::Reject && !messages.is_empty() {
+            return Err(messages.join("; "));
+        }
+        Ok(messages)
+    }
+
+    /// Describe a flagged table and suggest columns to project.
+    fn describe(&self, table: &TableShape) -> String {
+        let mut reasons = Vec::new();
+        if table.columns.len() > self.max_columns {
+            reasons.push(format!("{} columns", table.columns.len()));
+        }
+        if table.estimated_rows > self.max_rows {
+            reasons.push(format!("~{} rows", table.estimated_rows));
+        }
+        let mut suggestion = table
+            .columns
+            .iter()
+            .take(SUGGESTED_COLUMNS)
+            .map(|column| quote_ident(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if table.columns.len() > SUGGESTED_COLUMNS {
+            suggestion.push_str(", ...");
+        }
+        format!(
+            "SELECT * on {} ({}); select only the columns you need, e.g. {}",
+            table.name,
+            reasons.join(", "),
+            suggestion
+        )
+    }
+}
+
+impl Default for SelectStarGuard {
+    fn default() -> Self {
+        Self::new(20, 100_000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables() -> Vec<TableShape> {
+        vec![
+            TableShape {
+                name: "public.events".to_string(),
+                columns: (0..30).map(|i| format!("c{}", i)).collect(),
+                estimated_rows: 500,
+            },
+            TableShape {
+                name: "public.orders".to_string(),
+                columns: vec!["id".to_string(), "total".to_string()],
+                estimated_rows: 2_000_000,
+            },
+            TableShape {
+                name: "public.tags".to_string(),
+                columns: vec!["id".to_string(), "name".to_string()],
+                estimated_rows: 50,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_check() {
+        let guard = SelectStarGuard::default();
+        let tables = tables();
+
+        let messages = guard.check("SELECT * FROM events", &tables);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("30 columns"));
+        assert!(messages[0].contains("c0, c1"));
+        assert!(guard.check("SELECT o.* FROM orders o", &tables)[0].contains("~2000000 rows"));
+
+        assert!(guard.check("SELECT * FROM tags", &tables).is_empty());
+        assert!(guard.check("SELECT count(*) FROM events", &tables).is_empty());
+        assert!(guard.check("SELECT id, total * 2 FROM orders", &tables).is_empty());
+    }
+
+    #[test]
+    fn test_reject() {
+        let guard = SelectStarGuard::default().with_action(SelectStarAction::Reject);
+        let tables = tables();
+
+        assert!(guard.apply("SELECT * FROM orders", &tables).is_err());
+        assert_eq!(guard.apply("SELECT id FROM orders", &tables), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,135 @@
+//! Table references in SQL text.
+//!
+//! Lightweight, regex based matching of known table names in a query.
+//! It does not parse SQL, so names inside string literals also match.
+//!
+//! Names are matched the way PostgreSQL resolves them: an unquoted
+//! identifier folds to lower case, so a table created as `"UserEvents"` is
+//! only referenced by that quoted spelling, while `orders` is referenced by
+//! `orders`, `ORDERS` or `"orders"`.
+
+use postgres_agent_util::ident::folds_unquoted;
+use regex::Regex;
+
+/// Regex fragment matching a reference to `qualified` (`schema.table` or
+/// `table`), qualified or bare.
+#[must_use]
+pub(crate) fn table_ref_pattern(qualified: &str) -> String {
+    let (schema, table) = qualified.split_once('.').unwrap_or(("", qualified));
+    let name = ident_pattern(table);
+    if schema.is_empty() {
+        name
+    } else {
+        format!(r"(?:{}\s*\.\s*)?{}", ident_pattern(schema), name)
+    }
+}
+
+/// Regex fragment matching the identifier `name` as PostgreSQL resolves it.
+///
+/// Names that unquoted identifiers fold to match in any case, quoted or
+/// not; any other name matches only quoted and exactly.
+fn ident_pattern(name: &str) -> String {
+    if folds_unquoted(name) {
+        format!(r#"(?:(?i:{0})|(?-i:"{0}"))"#, regex::escape(name))
+    } else {
+        format!(r#"(?-i:"{}")"#, regex::escape(&name.replace('"', "\"\"")))
+    }
+}
+
+/// Number of references to `qualified` in `sql`.
+#[must_use]
+pub(crate) fn count_references(sql: &str, qualified: &str) -> usize {
+    let pattern = format!(r#"(?i)(?:^|[^\w."])({})(?:$|[^\w"])"#, table_ref_pattern(qualified));
+    Regex::new(&pattern).map_or(0, |re| re.find_iter(sql).count())
+}
+
+/// Tables among `tables` (given as `schema.table` or `table`) that `sql`
+/// refers to.
+#[must_use]
+pub fn tables_in_query<'a>(sql: &str, tables: &'a [String]) -> Vec<&'a str> {
+    tables
+        .iter()
+        .filter(|qualified| count_references(sql, qualified) > 0)
+        .map(String::as_str)
+        .collect()
+}
+
+/// Tables among `tables` (given as `schema.table`) that `sql` refers to
+/// without their schema.
+#[must_use]
+pub fn unqualified_tables<'a>(sql: &str, tables: &'a [String]) -> Vec<&'a str> {
+    // `schema . table` and `"schema"."table"` are qualified too
+    let sql = Regex::new(r#""?\s*\.\s*"?"#).map_or(sql.into(), |re| re.replace_all(sql, "."));
+    tables
+        .iter()
+        .filter(|qualified| {
+            let table = qualified.split_once('.').map_or(qualified.as_str(), |(_, t)| t);
+            count_references(&sql, table) > 0
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_in_query() {
+        let tables = vec!["public.orders".to_string(), "billing.invoices".to_string()];
+
+        assert_eq!(
+            tables_in_query("SELECT count(*) FROM orders WHERE status = 'open'", &tables),
+            vec!["public.orders"]
+        );
+        assert_eq!(
+            tables_in_query("select * from \"billing\".\"invoices\" i", &tables),
+            vec!["billing.invoices"]
+        );
+        assert!(tables_in_query("SELECT * FROM orders_archive", &tables).is_empty());
+        assert!(tables_in_query("SELECT o.orders FROM t o", &tables).is_empty());
+        assert_eq!(count_references("SELECT * FROM orders JOIN public.orders p ON true", "public.orders"), 2);
+    }
+
+    #[test]
+    fn test_mixed_case_fixture() {
+        // CREATE TABLE orders (...); CREATE TABLE "UserEvents" (...);
+        // CREATE SCHEMA "Billing"; CREATE TABLE "Billing"."Invoices" (...);
+        let tables = vec![
+            "public.orders".to_string(),
+            "public.UserEvents".to_string(),
+            "Billing.Invoices".to_string(),
+        ];
+
+        assert_eq!(tables_in_query("SELECT * FROM ORDERS", &tables), vec!["public.orders"]);
+        assert_eq!(tables_in_query("SELECT * FROM \"orders\"", &tables), vec!["public.orders"]);
+        assert!(tables_in_query("SELECT * FROM \"ORDERS\"", &tables).is_empty());
+
+        // Unquoted UserEvents folds to userevents, another table
+        assert!(tables_in_query("SELECT * FROM UserEvents", &tables).is_empty());
+        assert_eq!(
+            tables_in_query("SELECT * FROM public.\"UserEvents\" e", &tables),
+            vec!["public.UserEvents"]
+        );
+        assert_eq!(
+            tables_in_query("SELECT * FROM \"Billing\".\"Invoices\"", &tables),
+            vec!["Billing.Invoices"]
+        );
+        assert!(tables_in_query("SELECT * FROM billing.invoices", &tables).is_empty());
+
+        let sql = "SELECT * FROM \"UserEvents\" JOIN \"Billing\".\"Invoices\" i";
+        assert_eq!(unqualified_tables(sql, &tables), vec!["public.UserEvents"]);
+    }
+
+    #[test]
+    fn test_unqualified_tables() {
+        let tables = vec!["public.orders".to_string(), "billing.invoices".to_string()];
+
+        assert!(unqualified_tables("SELECT * FROM public.orders o", &tables).is_empty());
+        assert!(unqualified_tables("SELECT * FROM \"billing\" . \"invoices\"", &tables).is_empty());
+        assert_eq!(
+            unqualified_tables("SELECT * FROM orders JOIN billing.invoices i ON true", &tables),
+            vec!["public.orders"]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Temp table statements for the session workspace.
+//!
+//! A session with its own connection may keep intermediate results in temp
+//! tables. They live in the session's `pg_temp` schema and disappear with
+//! the connection, so creating and dropping them is allowed at every safety
+//! level. Like the rest of this crate the check is regex based; anything it
+//! is unsure about is rejected.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// `CREATE TEMP TABLE name ...`, with the name unqualified or in `pg_temp`.
+    static ref CREATE_TEMP: Regex = Regex::new(concat!(
+        r"(?is)^CREATE\s+(?:(?:LOCAL|GLOBAL)\s+)?TEMP(?:ORARY)?\s+TABLE\s+",
+        r#"(?:IF\s+NOT\s+EXISTS\s+)?(?:pg_temp\s*\.\s*)?(?:\w+|"[^"]+")(?:\s|\(|$)"#
+    ))
+    .expect("valid regex");
+
+    /// `DROP TABLE pg_temp.name[, pg_temp.other]`.
+    static ref DROP_TEMP: Regex = Regex::new(concat!(
+        r#"(?is)^DROP\s+TABLE\s+(?:IF\s+EXISTS\s+)?pg_temp\s*\.\s*(?:\w+|"[^"]+")"#,
+        r#"(?:\s*,\s*pg_temp\s*\.\s*(?:\w+|"[^"]+"))*(?:\s+(?:CASCADE|RESTRICT))?$"#
+    ))
+    .expect("valid regex");
+
+    /// Statements that write to other tables from inside a query.
+    static ref DATA_MODIFYING: Regex =
+        Regex::new(r"(?i)\b(?:INSERT|UPDATE|DELETE|MERGE)\b").expect("valid regex");
+}
+
+/// Whether `sql` is a single statement that only creates or drops temp
+/// tables.
+///
+/// `CREATE TEMP TABLE ... AS` queries may not contain data-modifying
+/// statements, and drops must name tables as `pg_temp.name`.
+#[must_use]
+pub fn is_temp_table_statement(sql: &str) -> bool {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+    if sql.contains(';') {
+        return false;
+    }
+    if CREATE_TEMP.is_match(sql) {
+        return !DATA_MODIFYING.is_match(sql);
+    }
+    DROP_TEMP.is_match(sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_table_statements() {
+        assert!(is_temp_table_statement(
+            "CREATE TEMP TABLE big_orders AS SELECT * FROM public.orders WHERE total > 100;"
+        ));
+        assert!(is_temp_table_statement("create temporary table if not exists t (id int)"));
+        assert!(is_temp_table_statement("CREATE TEMP TABLE pg_temp.\"Step 1\" AS SELECT 1"));
+        assert!(is_temp_table_statement("DROP TABLE IF EXISTS pg_temp.t, pg_temp.u CASCADE"));
+
+        assert!(!is_temp_table_statement("CREATE TABLE t AS SELECT 1"));
+        assert!(!is_temp_table_statement("CREATE TEMP TABLE public.t AS SELECT 1"));
+        assert!(!is_temp_table_statement("DROP TABLE t"));
+        assert!(!is_temp_table_statement("DROP TABLE pg_temp.t, public.orders"));
+        assert!(!is_temp_table_statement(
+            "CREATE TEMP TABLE t AS WITH d AS (DELETE FROM public.orders RETURNING *) \
+             SELECT * FROM d"
+        ));
+        assert!(!is_temp_table_statement("CREATE TEMP TABLE t (id int); DROP TABLE orders"));
+    }
+}
@@ -0,0 +1,609 @@
+//! Multi-tenant scoping.
+//!
+//! Queries touching tenant-scoped tables must filter on the tenant column
+//! (`column = 'value'`). Unscoped queries are rejected, or rewritten so
+//! that every scoped table is read through a filtered subquery.
+//!
+//! The filter only counts as a top-level `AND` term of the `WHERE` clause
+//! at the table's own query level, so comments, string literals and
+//! predicates that an `OR` can bypass never scope a query.
+
+use postgres_agent_util::ident::folds_unquoted;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::tables::{count_references, table_ref_pattern};
+
+/// Words that can follow a table reference but are not an alias.
+const NON_ALIAS_KEYWORDS: &[&str] = &[
+    "where", "join", "inner", "left", "right", "full", "cross", "natural", "on", "using", "group",
+    "order", "limit", "offset", "having", "union", "intersect", "except", "window", "for", "fetch",
+    "tablesample", "lateral", "returning", "set",
+];
+
+/// Words ending a `WHERE` clause.
+const CLAUSE_END_KEYWORDS: &[&str] =
+    &["group", "order", "limit", "offset", "having", "window", "fetch", "for", "returning"];
+
+/// Words separating the statements of a set operation.
+const SET_OPERATORS: &[&str] = &["union", "intersect", "except"];
+
+/// Characters of operators.
+const OPERATOR_CHARS: &str = "+-*/<>=~!@#%^&|`?";
+
+/// What to do with a query that lacks the tenant filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TenantMode {
+    /// Refuse to run the query.
+    #[default]
+    Reject,
+    /// Read scoped tables through a filtered subquery.
+    Augment,
+}
+
+/// Tenant scoping rules for a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantScope {
+    /// Tenant column, e.g. `org_id`.
+    pub column: String,
+    /// Current tenant; without one, scoped tables cannot be queried.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Scoped tables, as `schema.table` or `table`.
+    pub tables: Vec<String>,
+    /// Handling of unscoped queries.
+    #[serde(default)]
+    pub mode: TenantMode,
+}
+
+impl TenantScope {
+    /// Create a scope for a tenant column on the given tables.
+    #[must_use]
+    pub fn new(column: impl Into<String>, tables: Vec<String>) -> Self {
+        Self {
+            column: column.into(),
+            value: None,
+            tables,
+            mode: TenantMode::default(),
+        }
+    }
+
+    /// Set the current tenant.
+    #[must_use]
+    pub fn with_value(mut self, value: impl Into<String>) -> Self {
+        self.value = Some(value.into());
+        self
+    }
+
+    /// Set the handling of unscoped queries.
+    #[must_use]
+    pub fn with_mode(mut self, mode: TenantMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The required predicate, e.g. `"org_id" = 'acme'`.
+    #[must_use]
+    pub fn predicate(&self) -> Option<String> {
+        self.value.as_ref().map(|value| {
+            format!(
+                "\"{}\" = '{}'",
+                self.column.replace('"', "\"\""),
+                value.replace('\'', "''")
+            )
+        })
+    }
+
+    /// Scoped tables referenced by `sql` without the tenant filter.
+    ///
+    /// Every reference needs the filter as a top-level `AND` term of the
+    /// `WHERE` clause of its own query. Where that query reads several
+    /// relations, the column must be qualified by the table's name or alias.
+    #[must_use]
+    pub fn unscoped_tables<'a>(&'a self, sql: &str) -> Vec<&'a str> {
+        let tokens = tokenize(sql);
+        self.tables
+            .iter()
+            .filter(|table| {
+                let references = references(&tokens, table);
+                match &self.value {
+                    Some(value) => {
+                        references.iter().any(|&at| !self.is_scoped(&tokens, at, table, value))
+                    }
+                    None => !references.is_empty(),
+                }
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Check `sql` and return the statement to run.
+    ///
+    /// In [`TenantMode::Augment`], SELECTs reading scoped tables only
+    /// through `FROM` and `JOIN` are rewritten to filter each of them.
+    ///
+    /// # Errors
+    /// Returns a description of the violation if the query must not run.
+    pub fn apply(&self, sql: &str) -> Result<String, String> {
+        let unscoped = self.unscoped_tables(sql);
+        if unscoped.is_empty() {
+            return Ok(sql.to_string());
+        }
+        let Some(predicate) = self.predicate() else {
+            return Err(format!(
+                "No tenant selected; {} {} scoped by {}",
+                unscoped.join(", "),
+                if unscoped.len() == 1 { "is" } else { "are" },
+                self.column
+            ));
+        };
+        let rejection = || {
+            format!(
+                "Query reads {} without the tenant filter {}",
+                unscoped.join(", "),
+                predicate
+            )
+        };
+
+        let is_select = tokenize(sql).first().is_some_and(|(token, _)| {
+            is_keyword_token(token, "select") || is_keyword_token(token, "with")
+        });
+        if self.mode == TenantMode::Reject || !is_select {
+            return Err(rejection());
+        }
+
+        let mut augmented = sql.to_string();
+        for table in &unscoped {
+            let from_join = Regex::new(&format!(
+                r#"(?i)\b(FROM|JOIN)(\s+)({})(\s+(?:AS\s+)?("?\w+"?))?"#,
+                table_ref_pattern(table)
+            ))
+            .map_err(|e| e.to_string())?;
+            if from_join.find_iter(&augmented).count() != count_references(&augmented, table) {
+                // Referenced outside FROM/JOIN, e.g. in a comma join
+                return Err(rejection());
+            }
+            augmented = from_join
+                .replace_all(&augmented, |caps: &regex::Captures| {
+                    let bare = table.rsplit('.').next().unwrap_or(table);
+                    let (alias, rest) = match caps.get(5) {
+                        Some(alias) if !is_keyword(alias.as_str()) => {
+                            (alias.as_str().to_string(), String::new())
+                        }
+                        _ => (
+                            format!("\"{}\"", bare),
+                            caps.get(4).map_or(String::new(), |m| m.as_str().to_string()),
+                        ),
+                    };
+                    format!(
+                        "{}{}(SELECT * FROM {} WHERE {}) AS {}{}",
+                        &caps[1], &caps[2], &caps[3], predicate, alias, rest
+                    )
+                })
+                .into_owned();
+        }
+        // The rewrite is textual; only run it if it scopes every reference
+        if !self.unscoped_tables(&augmented).is_empty() {
+            return Err(rejection());
+        }
+        Ok(augmented)
+    }
+
+    /// Whether the reference to `table` at `at` is filtered on the tenant.
+    fn is_scoped(&self, tokens: &[(Token, usize)], at: usize, table: &str, value: &str) -> bool {
+        let depth = tokens[at].1;
+        let (start, end) = statement_bounds(tokens, at);
+        let at_depth = |i: &usize| tokens[*i].1 == depth;
+        let Some(where_at) =
+            (at + 1..end).filter(at_depth).find(|&i| is_keyword_token(&tokens[i].0, "where"))
+        else {
+            return false;
+        };
+        let clause_end = (where_at + 1..end)
+            .filter(at_depth)
+            .find(|&i| CLAUSE_END_KEYWORDS.iter().any(|kw| is_keyword_token(&tokens[i].0, kw)))
+            .unwrap_or(end);
+
+        let bare = table.rsplit('.').next().unwrap_or(table);
+        let mut qualifiers = vec![bare.to_string()];
+        qualifiers.extend(alias(tokens, at));
+        let unqualified = relation_count(&tokens[start..where_at], depth) == 1;
+        conjuncts(&tokens[where_at + 1..clause_end], depth)
+            .into_iter()
+            .any(|term| self.is_filter(term, value, &qualifiers, unqualified))
+    }
+
+    /// Whether `term` is `[qualifier.]column = 'value'`, either way round.
+    fn is_filter(
+        &self,
+        term: &[(Token, usize)],
+        value: &str,
+        qualifiers: &[String],
+        unqualified: bool,
+    ) -> bool {
+        let term: Vec<&Token> = term.iter().map(|(token, _)| token).collect();
+        let is_column = |column: &[&Token]| match column {
+            [name] => unqualified && is_ident(name, &self.column),
+            [qualifier, Token::Symbol(dot), name] if dot == "." => {
+                qualifiers.iter().any(|q| is_ident(qualifier, q)) && is_ident(name, &self.column)
+            }
+            _ => false,
+        };
+        let is_value = |token: &Token| match token {
+            Token::Literal(literal) => literal == value,
+            Token::Number(number) => number == value,
+            _ => false,
+        };
+        let Some(eq) = term.iter().position(|t| matches!(t, Token::Symbol(s) if s == "=")) else {
+            return false;
+        };
+        let (left, right) = (&term[..eq], &term[eq + 1..]);
+        match (left, right) {
+            (column, [value]) if is_value(value) => is_column(column),
+            ([value], column) if is_value(value) => is_column(column),
+            _ => false,
+        }
+    }
+}
+
+/// A token of SQL text; comments are dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Unquoted identifier or keyword, as written.
+    Word(String),
+    /// Quoted identifier, unescaped.
+    Quoted(String),
+    /// String constant, unescaped.
+    Literal(String),
+    /// Numeric constant.
+    Number(String),
+    /// Operator or punctuation.
+    Symbol(String),
+}
+
+/// Split `sql` into tokens, each with its parenthesis depth. Parentheses
+/// have the depth of the text around them.
+fn tokenize(sql: &str) -> Vec<(Token, usize)> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let token = if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        } else if c == '/' && next == Some('*') {
+            // Block comments nest
+            let mut level = 0;
+            while i < chars.len() {
+                match (chars[i], chars.get(i + 1)) {
+                    ('/', Some('*')) => level += 1,
+                    ('*', Some('/')) => level -= 1,
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                }
+                i += 2;
+                if level == 0 {
+                    break;
+                }
+            }
+            continue;
+        } else if c == '\'' || (matches!(c, 'e' | 'E') && next == Some('\'')) {
+            let escapes = c != '\'';
+            let start = if escapes { i + 1 } else { i };
+            let (literal, end) = read_quoted(&chars, start, '\'', escapes);
+            i = end;
+            Token::Literal(literal)
+        } else if c == '"' {
+            let (name, end) = read_quoted(&chars, i, '"', false);
+            i = end;
+            Token::Quoted(name)
+        } else if c == '$' && dollar_tag(&chars, i).is_some() {
+            let tag: Vec<char> = dollar_tag(&chars, i).unwrap_or_default();
+            let body = i + tag.len();
+            let close = (body..chars.len()).find(|&j| chars[j..].starts_with(&tag));
+            let end = close.unwrap_or(chars.len());
+            i = close.map_or(end, |j| j + tag.len());
+            Token::Literal(chars[body..end].iter().collect())
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || "_$".contains(chars[i])) {
+                i += 1;
+            }
+            Token::Word(chars[start..i].iter().collect())
+        } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            Token::Number(chars[start..i].iter().collect())
+        } else if c == '(' {
+            tokens.push((Token::Symbol("(".to_string()), depth));
+            depth += 1;
+            i += 1;
+            continue;
+        } else if c == ')' {
+            depth = depth.saturating_sub(1);
+            i += 1;
+            Token::Symbol(")".to_string())
+        } else if OPERATOR_CHARS.contains(c) {
+            // An operator ends where a comment starts
+            let start = i;
+            while i < chars.len()
+                && OPERATOR_CHARS.contains(chars[i])
+                && !(i > start && matches!(&chars[i..], ['-', '-', ..] | ['/', '*', ..]))
+            {
+                i += 1;
+            }
+            Token::Symbol(chars[start..i].iter().collect())
+        } else {
+            i += 1;
+            Token::Symbol(c.to_string())
+        };
+        tokens.push((token, depth));
+    }
+    tokens
+}
+
+/// Read a quoted string starting at `start`, where a doubled quote stands
+/// for itself; returns the unescaped text and the index after it.
+fn read_quoted(chars: &[char], start: usize, quote: char, backslash: bool) -> (String, usize) {
+    let mut text = String::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if backslash && i + 1 < chars.len() => {
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            c if c == quote && chars.get(i + 1) == Some(&quote) => {
+                text.push(quote);
+                i += 2;
+            }
+            c if c == quote => return (text, i + 1),
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+    (text, i)
+}
+
+/// The `$tag$` opening a dollar-quoted string at `start`, if there is one.
+fn dollar_tag(chars: &[char], start: usize) -> Option<Vec<char>> {
+    let mut end = start + 1;
+    while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+        end += 1;
+    }
+    let starts_with_digit = chars.get(start + 1).is_some_and(char::is_ascii_digit);
+    (chars.get(end) == Some(&'$') && !starts_with_digit).then(|| chars[start..=end].to_vec())
+}
+
+/// Whether `token` is the keyword `keyword` (lower case).
+fn is_keyword_token(token: &Token, keyword: &str) -> bool {
+    matches!(token, Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+}
+
+/// Whether `token` names the identifier `name`, as PostgreSQL resolves it.
+fn is_ident(token: &Token, name: &str) -> bool {
+    match token {
+        Token::Word(word) => folds_unquoted(name) && word.to_lowercase() == name,
+        Token::Quoted(quoted) => quoted == name,
+        _ => false,
+    }
+}
+
+/// Whether `token` is the symbol `symbol`.
+fn is_symbol(token: Option<&(Token, usize)>, symbol: &str) -> bool {
+    matches!(token, Some((Token::Symbol(s), _)) if s == symbol)
+}
+
+/// Positions of the references to `qualified` (`schema.table` or `table`).
+fn references(tokens: &[(Token, usize)], qualified: &str) -> Vec<usize> {
+    let (schema, table) = qualified.split_once('.').unwrap_or(("", qualified));
+    (0..tokens.len())
+        .filter(|&i| {
+            // `orders.total` qualifies a column, `AS orders` names a subquery
+            let is_alias = i > 0
+                && (is_keyword_token(&tokens[i - 1].0, "as") || is_symbol(tokens.get(i - 1), ")"));
+            if !is_ident(&tokens[i].0, table) || is_symbol(tokens.get(i + 1), ".") || is_alias {
+                return false;
+            }
+            if i == 0 || !is_symbol(tokens.get(i - 1), ".") {
+                return true;
+            }
+            !schema.is_empty()
+                && i >= 2
+                && is_ident(&tokens[i - 2].0, schema)
+                && (i == 2 || !is_symbol(tokens.get(i - 3), "."))
+        })
+        .collect()
+}
+
+/// Bounds of the statement around `at` at its depth, as a half-open
+/// range: statements end at `;`, set operators and the enclosing parens.
+fn statement_bounds(tokens: &[(Token, usize)], at: usize) -> (usize, usize) {
+    let depth = tokens[at].1;
+    let is_boundary = |i: usize| {
+        let (token, d) = &tokens[i];
+        *d < depth
+            || (*d == depth
+                && (is_symbol(Some(&tokens[i]), ";")
+                    || SET_OPERATORS.iter().any(|op| is_keyword_token(token, op))))
+    };
+    let start = (0..at).rev().find(|&i| is_boundary(i)).map_or(0, |i| i + 1);
+    let end = (at + 1..tokens.len()).find(|&i| is_boundary(i)).unwrap_or(tokens.len());
+    (start, end)
+}
+
+/// The alias given to the table referenced at `at`, if any.
+fn alias(tokens: &[(Token, usize)], at: usize) -> Option<String> {
+    let mut next = at + 1;
+    if tokens.get(next).is_some_and(|(token, _)| is_keyword_token(token, "as")) {
+        next += 1;
+    }
+    match tokens.get(next)? {
+        (Token::Word(word), _) if !is_keyword(word) => Some(word.to_lowercase()),
+        (Token::Quoted(name), _) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// Number of relations read by a statement, from its tokens before `WHERE`.
+fn relation_count(tokens: &[(Token, usize)], depth: usize) -> usize {
+    let mut count = 0;
+    let mut in_from = false;
+    for (token, _) in tokens.iter().filter(|(_, d)| *d == depth) {
+        if ["from", "join", "using", "update"].iter().any(|kw| is_keyword_token(token, kw)) {
+            count += 1;
+            in_from = !is_keyword_token(token, "update");
+        } else if is_keyword_token(token, "set") {
+            in_from = false;
+        } else if in_from && matches!(token, Token::Symbol(s) if s == ",") {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Terms of a condition that must all hold: its top-level `AND` operands,
+/// looking into parentheses. A top-level `OR` leaves none.
+fn conjuncts(clause: &[(Token, usize)], depth: usize) -> Vec<&[(Token, usize)]> {
+    if let Some(inner) = parenthesized(clause, depth) {
+        return conjuncts(inner, depth + 1);
+    }
+    let top = |(token, d): &(Token, usize)| *d == depth && is_keyword_token(token, "or");
+    if clause.iter().any(top) {
+        return Vec::new();
+    }
+
+    let mut terms = Vec::new();
+    let mut start = 0;
+    let mut in_between = false;
+    for (i, (token, d)) in clause.iter().enumerate() {
+        if *d != depth {
+            continue;
+        }
+        if is_keyword_token(token, "between") {
+            in_between = true;
+        } else if is_keyword_token(token, "and") {
+            if in_between {
+                in_between = false;
+            } else {
+                terms.push(&clause[start..i]);
+                start = i + 1;
+            }
+        }
+    }
+    terms.push(&clause[start..]);
+    terms
+        .into_iter()
+        .flat_map(|term| match parenthesized(term, depth) {
+            Some(inner) => conjuncts(inner, depth + 1),
+            None => vec![term],
+        })
+        .collect()
+}
+
+/// The contents of `tokens` if they are wholly in one pair of parentheses.
+fn parenthesized(tokens: &[(Token, usize)], depth: usize) -> Option<&[(Token, usize)]> {
+    if !is_symbol(tokens.first(), "(") {
+        return None;
+    }
+    let close = (1..tokens.len()).find(|&i| tokens[i].1 == depth && is_symbol(tokens.get(i), ")"))?;
+    (close == tokens.len() - 1).then(|| &tokens[1..close])
+}
+
+/// Whether a word following a table reference is a keyword, not an alias.
+fn is_keyword(word: &str) -> bool {
+    NON_ALIAS_KEYWORDS.contains(&word.to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope() -> TenantScope {
+        TenantScope::new("org_id", vec!["public.orders".to_string(), "public.invoices".to_string()])
+            .with_value("acme")
+    }
+
+    #[test]
+    fn test_reject_unscoped() {
+        let scope = scope();
+
+        assert!(scope.apply("SELECT * FROM orders WHERE org_id = 'acme'").is_ok());
+        assert!(scope.apply("SELECT * FROM customers").is_ok());
+        assert!(scope.apply("SELECT * FROM orders WHERE org_id = 'other'").is_err());
+        assert!(scope.apply("SELECT * FROM orders").unwrap_err().contains("public.orders"));
+
+        let join = "SELECT * FROM orders o JOIN invoices i ON i.order_id = o.id WHERE o.org_id = 'acme'";
+        assert_eq!(scope.unscoped_tables(join), vec!["public.invoices"]);
+        assert!(scope.apply(&format!("{} AND i.org_id = 'acme'", join)).is_ok());
+
+        let no_tenant = TenantScope::new("org_id", vec!["orders".to_string()]);
+        assert!(no_tenant.apply("SELECT * FROM orders WHERE org_id = 'acme'").is_err());
+    }
+
+    #[test]
+    fn test_filter_bypasses() {
+        let scope = scope();
+
+        for sql in [
+            "SELECT * FROM orders -- WHERE org_id = 'acme'",
+            "SELECT * FROM orders /* WHERE org_id = 'acme' */",
+            "SELECT 'WHERE org_id = ''acme''' FROM orders",
+            "SELECT * FROM orders WHERE note = $$ AND org_id = 'acme'$$",
+            "SELECT * FROM orders WHERE org_id = 'acme' OR true",
+            "SELECT * FROM orders WHERE (org_id = 'acme' OR 1 = 1) AND total > 0",
+            "SELECT * FROM orders WHERE org_id = 'acme' || ''",
+            "SELECT * FROM orders o JOIN customers c ON c.id = o.customer_id WHERE org_id = 'acme'",
+            "SELECT * FROM orders UNION SELECT * FROM orders WHERE org_id = 'acme'",
+            "SELECT * FROM customers WHERE org_id = 'acme' AND id IN (SELECT id FROM orders)",
+        ] {
+            assert_eq!(scope.unscoped_tables(sql), vec!["public.orders"], "{}", sql);
+        }
+
+        for sql in [
+            "SELECT * FROM orders WHERE (total > 0 AND org_id = 'acme') ORDER BY id",
+            "SELECT * FROM orders WHERE total BETWEEN 1 AND 9 AND 'acme' = \"org_id\"",
+            "SELECT * FROM public.orders AS o WHERE o.org_id = 'acme' -- OR true",
+            "UPDATE orders SET total = 0 WHERE org_id = 'acme'",
+        ] {
+            assert!(scope.unscoped_tables(sql).is_empty(), "{}", sql);
+        }
+
+        // Augmenting scopes the table whatever the query says
+        let augment = scope.with_mode(TenantMode::Augment);
+        let augmented = augment.apply("SELECT * FROM orders WHERE org_id = 'acme' OR true");
+        assert!(augmented.unwrap().contains("FROM (SELECT * FROM orders WHERE"));
+    }
+
+    #[test]
+    fn test_augment() {
+        let scope = scope().with_mode(TenantMode::Augment);
+
+        assert_eq!(
+            scope.apply("SELECT count(*) FROM orders o WHERE o.total > 10").unwrap(),
+            "SELECT count(*) FROM (SELECT * FROM orders WHERE \"org_id\" = 'acme') AS o WHERE o.total > 10"
+        );
+        assert_eq!(
+            scope.apply("SELECT * FROM orders WHERE total > 10").unwrap(),
+            "SELECT * FROM (SELECT * FROM orders WHERE \"org_id\" = 'acme') AS \"orders\" WHERE total > 10"
+        );
+        assert!(scope.apply("SELECT * FROM customers c, orders").is_err());
+        assert!(scope.apply("DELETE FROM orders").is_err());
+    }
+}
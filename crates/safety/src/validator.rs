@@ -3,9 +3,14 @@
 //! This module provides the [`SafetyValidator`] for validating SQL operations,
 //! classifying operation types, and enforcing safety levels.
 
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 use crate::blacklist::{default_blacklist, SqlBlacklist};
+use crate::locks::analyze_ddl;
+use crate::policy::{Policy, PolicyAction, PolicyRule};
+use crate::rls::rls_warnings;
+use crate::tenant::TenantScope;
 use crate::pii::{default_pii_detector, PiiDetector};
 
 /// Safety levels controlling agent behavior.
@@ -82,7 +87,7 @@ pub enum OperationType {
 }
 
 /// Safety context for operations.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct SafetyContext {
     /// Current safety level.
     pub level: SafetyLevel,
@@ -92,17 +97,19 @@ pub struct SafetyContext {
     pub user_id: Option<String>,
     /// Request ID for tracing.
     pub request_id: Option<String>,
-}
-
-impl Default for SafetyContext {
-    fn default() -> Self {
-        Self {
-            level: SafetyLevel::default(),
-            read_only: false,
-            user_id: None,
-            request_id: None,
-        }
-    }
+    /// Server version (`server_version_num`) for version-dependent checks.
+    pub server_version: Option<u32>,
+    /// Tables (`schema.table`) filtered by row-level security for the
+    /// current role.
+    pub rls_tables: Vec<String>,
+    /// Tenant scoping rules.
+    pub tenant: Option<TenantScope>,
+    /// Database role the operation runs as.
+    pub role: Option<String>,
+    /// Planner estimate of the rows the operation touches.
+    pub estimated_rows: Option<i64>,
+    /// Local time policy rules are evaluated at; the current time if unset.
+    pub now: Option<NaiveDateTime>,
 }
 
 impl SafetyContext {
@@ -138,6 +145,48 @@ impl SafetyContext {
         self.request_id = Some(request_id);
         self
     }
+
+    /// Set the server version (`server_version_num`, e.g. `160002`).
+    #[must_use]
+    pub fn with_server_version(mut self, server_version: u32) -> Self {
+        self.server_version = Some(server_version);
+        self
+    }
+
+    /// Set the tables filtered by row-level security for the current role.
+    #[must_use]
+    pub fn with_rls_tables(mut self, rls_tables: Vec<String>) -> Self {
+        self.rls_tables = rls_tables;
+        self
+    }
+
+    /// Set the tenant scoping rules.
+    #[must_use]
+    pub fn with_tenant(mut self, tenant: TenantScope) -> Self {
+        self.tenant = Some(tenant);
+        self
+    }
+
+    /// Set the database role the operation runs as.
+    #[must_use]
+    pub fn with_role(mut self, role: String) -> Self {
+        self.role = Some(role);
+        self
+    }
+
+    /// Set the planner estimate of the rows the operation touches.
+    #[must_use]
+    pub fn with_estimated_rows(mut self, rows: i64) -> Self {
+        self.estimated_rows = Some(rows);
+        self
+    }
+
+    /// Evaluate policy rules at this local time instead of now.
+    #[must_use]
+    pub fn at(mut self, now: NaiveDateTime) -> Self {
+        self.now = Some(now);
+        self
+    }
 }
 
 /// Result of safety validation.
@@ -159,6 +208,12 @@ pub struct ValidationResult {
     /// Details about detected issues.
     #[serde(default)]
     pub details: Vec<ValidationDetail>,
+    /// SQL to run instead, e.g. with the tenant filter added.
+    #[serde(default)]
+    pub rewritten_sql: Option<String>,
+    /// Name of the policy rule that decided, if one matched.
+    #[serde(default)]
+    pub rule: Option<String>,
 }
 
 impl Default for ValidationResult {
@@ -170,6 +225,8 @@ impl Default for ValidationResult {
             error: None,
             requires_confirmation: false,
             details: Vec::new(),
+            rewritten_sql: None,
+            rule: None,
         }
     }
 }
@@ -203,6 +260,14 @@ pub enum ValidationDetailKind {
     LargeOperation,
     /// Potential SQL injection.
     PotentialInjection,
+    /// Operation takes a heavy lock or rewrites a table.
+    HeavyLock,
+    /// Results may be filtered by row-level security.
+    RowLevelSecurity,
+    /// Tenant-scoped tables queried without the tenant filter.
+    MissingTenantFilter,
+    /// A policy rule denied the operation or asked for confirmation.
+    PolicyRule,
 }
 
 /// Safety validator for SQL operations.
@@ -216,6 +281,8 @@ pub struct SafetyValidator {
     max_rows: usize,
     /// Whether to allow maintenance operations.
     allow_maintenance: bool,
+    /// Configured policy rules, evaluated before those of the safety level.
+    policy: Policy,
 }
 
 impl Default for SafetyValidator {
@@ -233,6 +300,7 @@ impl SafetyValidator {
             pii_detector: default_pii_detector(),
             max_rows: 0,
             allow_maintenance: false,
+            policy: Policy::default(),
         }
     }
 
@@ -250,15 +318,41 @@ impl SafetyValidator {
         self
     }
 
+    /// Create a validator with policy rules, evaluated in order before the
+    /// built-in rules of the safety level.
+    #[must_use]
+    pub fn with_rules(mut self, rules: Vec<PolicyRule>) -> Self {
+        self.policy = Policy::new(rules);
+        self
+    }
+
+    /// The configured policy rules.
+    #[must_use]
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Configured rules followed by the built-in rules of `level`.
+    #[must_use]
+    pub fn effective_policy(&self, level: SafetyLevel) -> Policy {
+        let builtin = Policy::for_level(level, self.allow_maintenance);
+        Policy::new(self.policy.rules().iter().chain(builtin.rules()).cloned().collect())
+    }
+
     /// Validate a SQL query for safety.
     pub fn validate(&self, sql: &str, ctx: &SafetyContext) -> ValidationResult {
-        let mut result = ValidationResult::default();
-
         // Classify the operation type
-        result.operation_type = self.classify_operation(sql);
+        let mut result = ValidationResult {
+            operation_type: self.classify_operation(sql),
+            ..ValidationResult::default()
+        };
 
         // Check for blacklisted patterns
         if let Some(match_info) = self.blacklist.find_match(sql) {
+            tracing::debug!(
+                request_id = ?ctx.request_id,
+                "Blacklisted SQL blocked: {}", match_info
+            );
             result.is_allowed = false;
             result.error = Some(format!("Query contains prohibited operation: {}", match_info));
             result.details.push(ValidationDetail {
@@ -279,6 +373,27 @@ impl SafetyValidator {
             });
         }
 
+        // Check tenant scoping
+        if let Some(tenant) = &ctx.tenant {
+            match tenant.apply(sql) {
+                Ok(scoped) if scoped != sql => {
+                    result.warnings.push("Tenant filter added to query".to_string());
+                    result.rewritten_sql = Some(scoped);
+                }
+                Ok(_) => {}
+                Err(violation) => {
+                    result.is_allowed = false;
+                    result.details.push(ValidationDetail {
+                        kind: ValidationDetailKind::MissingTenantFilter,
+                        message: violation.clone(),
+                        position: None,
+                    });
+                    result.error = Some(violation);
+                    return result;
+                }
+            }
+        }
+
         // Check read-only mode
         if ctx.read_only && result.operation_type != OperationType::Read {
             result.is_allowed = false;
@@ -291,53 +406,50 @@ impl SafetyValidator {
             return result;
         }
 
-        // Check safety level for operation type
-        match result.operation_type {
-            OperationType::Read => {
-                // Always allowed
-            }
-            OperationType::Insert | OperationType::Update | OperationType::Delete => {
-                if !ctx.level.allows_dml() {
-                    result.is_allowed = false;
-                    result.error = Some(format!(
-                        "DML operations ({}) not allowed at {:?} safety level",
-                        result.operation_type.label(),
-                        ctx.level
-                    ));
-                    return result;
-                }
-                if ctx.level.requires_dml_confirmation() {
-                    result.requires_confirmation = true;
-                }
-            }
-            OperationType::Alter | OperationType::Create | OperationType::Drop | OperationType::Truncate => {
-                if !ctx.level.allows_ddl() {
-                    result.is_allowed = false;
-                    result.error = Some(format!(
-                        "DDL operations ({}) not allowed at {:?} safety level",
-                        result.operation_type.label(),
-                        ctx.level
-                    ));
-                    return result;
-                }
-                if ctx.level.requires_ddl_confirmation() {
-                    result.requires_confirmation = true;
-                }
-            }
-            OperationType::Grant => {
-                result.is_allowed = false;
-                result.error = Some("GRANT/REVOKE operations are not allowed".to_string());
-                return result;
+        // Apply the first matching policy rule
+        if let Some(decision) =
+            self.effective_policy(ctx.level).evaluate(sql, result.operation_type, ctx)
+        {
+            let message = decision.message.unwrap_or_else(|| {
+                format!("{} matched policy rule '{}'", result.operation_type.label(), decision.rule)
+            });
+            if decision.action != PolicyAction::Allow {
+                result.details.push(ValidationDetail {
+                    kind: ValidationDetailKind::PolicyRule,
+                    message: message.clone(),
+                    position: None,
+                });
             }
-            OperationType::Maintenance => {
-                if !self.allow_maintenance {
+            result.rule = Some(decision.rule);
+            match decision.action {
+                PolicyAction::Allow => {}
+                PolicyAction::Confirm => result.requires_confirmation = true,
+                PolicyAction::Deny => {
                     result.is_allowed = false;
-                    result.error = Some("Maintenance operations not allowed".to_string());
+                    result.error = Some(message);
                     return result;
                 }
             }
-            OperationType::Transaction | OperationType::Other => {
-                // Allow by default, may want to add more checks
+        }
+
+        for warning in rls_warnings(sql, &ctx.rls_tables) {
+            result.details.push(ValidationDetail {
+                kind: ValidationDetailKind::RowLevelSecurity,
+                message: warning.clone(),
+                position: None,
+            });
+            result.warnings.push(warning);
+        }
+
+        // Flag heavy locks and table rewrites with safer alternatives
+        if result.operation_type != OperationType::Read {
+            for warning in analyze_ddl(sql, ctx.server_version) {
+                result.warnings.push(warning.describe());
+                result.details.push(ValidationDetail {
+                    kind: ValidationDetailKind::HeavyLock,
+                    message: warning.message,
+                    position: None,
+                });
             }
         }
 
@@ -406,6 +518,12 @@ impl SafetyValidator {
         )
     }
 
+    /// Get the blacklisted SQL patterns.
+    #[must_use]
+    pub fn blacklist(&self) -> &SqlBlacklist {
+        &self.blacklist
+    }
+
     /// Get the PII detector for redaction.
     #[must_use]
     pub fn pii_detector(&self) -> &PiiDetector {
@@ -437,6 +555,7 @@ impl OperationType {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tenant::TenantMode;
 
     #[test]
     fn test_safety_level_allows() {
@@ -490,4 +609,76 @@ mod tests {
         assert!(!result.is_allowed);
         assert_eq!(result.error, Some("Query contains prohibited operation: DROP".to_string()));
     }
+
+    #[test]
+    fn test_validation_heavy_lock() {
+        let validator = SafetyValidator::new();
+        let ctx = SafetyContext::with_level(SafetyLevel::Permissive).with_server_version(100_000);
+
+        let result = validator.validate("ALTER TABLE users ADD COLUMN tier text DEFAULT 'free'", &ctx);
+        assert!(result.is_allowed);
+        assert!(result.details.iter().any(|d| matches!(d.kind, ValidationDetailKind::HeavyLock)));
+        assert!(result.warnings[0].contains("backfill"));
+    }
+
+    #[test]
+    fn test_validation_row_level_security() {
+        let validator = SafetyValidator::new();
+        let ctx = SafetyContext::read_only().with_rls_tables(vec!["public.orders".to_string()]);
+
+        let result = validator.validate("SELECT * FROM orders", &ctx);
+        assert!(result.is_allowed);
+        assert!(result.details.iter().any(|d| matches!(d.kind, ValidationDetailKind::RowLevelSecurity)));
+        assert!(validator.validate("SELECT 1", &ctx).warnings.is_empty());
+    }
+
+    #[test]
+    fn test_validation_tenant_scope() {
+        let validator = SafetyValidator::new();
+        let tenant = TenantScope::new("org_id", vec!["orders".to_string()]).with_value("42");
+        let ctx = SafetyContext::read_only().with_tenant(tenant.clone());
+
+        let result = validator.validate("SELECT * FROM orders", &ctx);
+        assert!(!result.is_allowed);
+        assert!(result.details.iter().any(|d| matches!(d.kind, ValidationDetailKind::MissingTenantFilter)));
+        assert!(validator.validate("SELECT * FROM orders WHERE org_id = 42", &ctx).is_allowed);
+
+        let ctx = SafetyContext::read_only().with_tenant(tenant.with_mode(TenantMode::Augment));
+        let result = validator.validate("SELECT * FROM orders", &ctx);
+        assert!(result.is_allowed);
+        assert!(result.rewritten_sql.unwrap().contains("\"org_id\" = '42'"));
+    }
+
+    #[test]
+    fn test_validation_policy_rules() {
+        use crate::policy::{parse_operations, PolicyAction, PolicyRule};
+
+        let validator = SafetyValidator::new().with_rules(vec![
+            PolicyRule::new("big-updates", PolicyAction::Deny)
+                .on_operations(parse_operations("update").unwrap())
+                .with_min_rows(1_000),
+            PolicyRule::new("audit-table", PolicyAction::Confirm)
+                .on_tables(vec!["audit_events".to_string()]),
+        ]);
+        let ctx = SafetyContext::with_level(SafetyLevel::Balanced);
+
+        let update = "UPDATE users SET tier = 'pro'";
+        let result = validator.validate(update, &ctx.clone().with_estimated_rows(5_000));
+        assert!(!result.is_allowed);
+        assert_eq!(result.rule.as_deref(), Some("big-updates"));
+        assert!(result.details.iter().any(|d| matches!(d.kind, ValidationDetailKind::PolicyRule)));
+
+        let result = validator.validate("SELECT * FROM audit_events", &ctx);
+        assert!(result.is_allowed && result.requires_confirmation);
+
+        // Built-in rules of the level still apply
+        let result = validator.validate(update, &ctx.clone().with_estimated_rows(10));
+        assert!(result.is_allowed && result.requires_confirmation);
+        assert_eq!(result.rule.as_deref(), Some("balanced-dml"));
+        let result = validator.validate("CREATE INDEX ON users (tier)", &ctx);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("DDL operations (CREATE) not allowed at Balanced safety level")
+        );
+    }
 }
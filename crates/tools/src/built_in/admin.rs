@@ -0,0 +1,400 @@
+//! Administrative tools.
+//!
+//! These tools live in the `admin` namespace, which is disabled unless
+//! enabled in configuration. They only run at the permissive safety level,
+//! and every action needs admin approval.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::time::Instant;
+use tracing::debug;
+
+use crate::trait_def::{Tool, ToolContext, ToolDefinition};
+use crate::{DbConnection, QueryExecutor, ToolError};
+use postgres_agent_db::{split_qualified_name, MatviewFreshness};
+use postgres_agent_safety::{ConfirmationLevel, SafetyLevel};
+
+/// Kill query tool name used in definitions and errors.
+const KILL_QUERY_TOOL: &str = "kill_query";
+
+/// Refresh tool name used in definitions and errors.
+const REFRESH_MATVIEW_TOOL: &str = "refresh_matview";
+
+/// Longest query excerpt shown in the approval prompt.
+const MAX_PROMPT_QUERY_CHARS: usize = 200;
+
+/// Arguments for the kill query tool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillQueryToolArgs {
+    /// Process ID of the backend.
+    pub pid: i32,
+    /// Terminate the whole backend instead of cancelling its query.
+    #[serde(default)]
+    pub terminate: bool,
+}
+
+/// Arguments for the refresh materialized view tool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshMatviewToolArgs {
+    /// Name of the view, optionally schema-qualified.
+    pub name: String,
+    /// Optional schema name (defaults to the profile's default schema).
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Refresh without blocking readers; needs a unique index.
+    #[serde(default)]
+    pub concurrently: bool,
+}
+
+/// Refuse to run an admin tool unless the session is at the permissive
+/// safety level.
+fn require_admin_level(ctx: &ToolContext, tool_name: &str) -> Result<(), ToolError> {
+    match ctx.safety_level {
+        Some(level) if level != SafetyLevel::Permissive => Err(ToolError::SafetyViolation {
+            reason: format!("{} needs the permissive safety level, not {:?}", tool_name, level),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Ask for admin approval of `operation`, writing the decision to the
+/// audit log.
+///
+/// # Errors
+/// Returns `ToolError::PermissionDenied` if the operation is not approved.
+async fn approve(
+    ctx: &ToolContext,
+    user: &str,
+    operation: &str,
+    tool_name: &str,
+) -> Result<(), ToolError> {
+    let approved = ctx.request_approval(operation, ConfirmationLevel::AdminApproval).await?;
+    if let Some(audit) = &ctx.audit {
+        audit.log_confirmation(user, operation, "ADMIN_APPROVAL", approved);
+    }
+    if approved {
+        Ok(())
+    } else {
+        Err(ToolError::PermissionDenied {
+            tool_name: tool_name.to_string(),
+        })
+    }
+}
+
+/// Kill query tool.
+///
+/// Wraps `pg_cancel_backend` and `pg_terminate_backend`. The target backend
+/// is shown to the user for admin approval, and both the decision and the
+/// outcome are written to the audit log.
+#[derive(Debug)]
+pub struct KillQueryTool {
+    /// Database connection.
+    db: DbConnection,
+}
+
+impl KillQueryTool {
+    /// Create a new kill query tool.
+    #[must_use]
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for KillQueryTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: KILL_QUERY_TOOL.to_string(),
+            description: "Cancel the running query of a backend (pg_cancel_backend), or terminate the backend (pg_terminate_backend). Requires admin approval. Find the pid with pg_stat_activity or pg_blocking_pids first.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "pid": {
+                        "type": "integer",
+                        "description": "Process ID of the backend"
+                    },
+                    "terminate": {
+                        "type": "boolean",
+                        "description": "Terminate the backend instead of cancelling its query"
+                    }
+                },
+                "required": ["pid"]
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: KillQueryToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: KILL_QUERY_TOOL.to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+        require_admin_level(ctx, KILL_QUERY_TOOL)?;
+
+        let executor = QueryExecutor::new(self.db.clone());
+        let backend = executor
+            .backend_activity(args.pid)
+            .await?
+            .ok_or_else(|| ToolError::ExecutionFailed {
+                reason: format!("No backend with pid {}", args.pid),
+            })?;
+
+        let action = if args.terminate { "Terminate backend" } else { "Cancel query on backend" };
+        let query: String = backend
+            .query
+            .as_deref()
+            .unwrap_or_default()
+            .chars()
+            .take(MAX_PROMPT_QUERY_CHARS)
+            .collect();
+        let operation = format!(
+            "{} {} ({}, {}): {}",
+            action,
+            backend.pid,
+            backend.username.as_deref().unwrap_or("unknown role"),
+            backend.state.as_deref().unwrap_or("unknown state"),
+            query
+        );
+
+        let config = self.db.config();
+        let user = config.username.as_deref().unwrap_or("unknown");
+        let database = config.database.as_deref().unwrap_or("unknown");
+
+        approve(ctx, user, &operation, KILL_QUERY_TOOL).await?;
+
+        debug!("{} {}", action, args.pid);
+        let start = Instant::now();
+        let result = executor.signal_backend(args.pid, args.terminate).await;
+        if let Some(audit) = &ctx.audit {
+            let function = if args.terminate { "pg_terminate_backend" } else { "pg_cancel_backend" };
+            #[allow(clippy::cast_possible_truncation)]
+            audit.log_query(
+                user,
+                database,
+                &format!("SELECT {}({})", function, args.pid),
+                matches!(result, Ok(true)),
+                start.elapsed().as_millis() as u64,
+                None,
+                ctx.request_id.as_deref(),
+            );
+        }
+        let signalled = result?;
+
+        Ok(serde_json::json!({
+            "pid": args.pid,
+            "action": if args.terminate { "terminate" } else { "cancel" },
+            "signalled": signalled
+        }))
+    }
+}
+
+/// Refresh materialized view tool.
+///
+/// Runs `REFRESH MATERIALIZED VIEW` after admin approval, showing when the
+/// view was last refreshed, and writes the decision and the refresh to the
+/// audit log.
+#[derive(Debug)]
+pub struct RefreshMatviewTool {
+    /// Database connection.
+    db: DbConnection,
+}
+
+impl RefreshMatviewTool {
+    /// Create a new refresh materialized view tool.
+    #[must_use]
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for RefreshMatviewTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: REFRESH_MATVIEW_TOOL.to_string(),
+            description: "Refresh a materialized view (REFRESH MATERIALIZED VIEW) so it reflects the current data of its source tables. Requires admin approval. Use when query results carry a stale freshness note and the user wants current data.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Name of the materialized view"
+                    },
+                    "schema": {
+                        "type": "string",
+                        "description": "Schema name (defaults to the profile's default schema or 'public')"
+                    },
+                    "concurrently": {
+                        "type": "boolean",
+                        "description": "Refresh without blocking readers; needs a unique index on the view"
+                    }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: RefreshMatviewToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: REFRESH_MATVIEW_TOOL.to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+        require_admin_level(ctx, REFRESH_MATVIEW_TOOL)?;
+        let (qualifier, name) = split_qualified_name(&args.name);
+        let schema = qualifier
+            .or(args.schema)
+            .or_else(|| self.db.default_schema().map(str::to_string))
+            .unwrap_or_else(|| "public".to_string());
+
+        let executor = QueryExecutor::new(self.db.clone());
+        let find = |views: Vec<MatviewFreshness>| {
+            views.into_iter().find(|v| v.schema == schema && v.name == name)
+        };
+        let view = find(executor.matview_freshness().await?).ok_or_else(|| {
+            ToolError::ExecutionFailed {
+                reason: format!("No materialized view {}.{}", schema, name),
+            }
+        })?;
+
+        let last = match view.last_refresh {
+            Some(at) => format!("last refreshed {}", at.format("%Y-%m-%d %H:%M:%S UTC")),
+            None if view.is_populated => "last refresh time unknown".to_string(),
+            None => "never populated".to_string(),
+        };
+        let operation = format!(
+            "Refresh materialized view {}{} ({})",
+            view.qualified_name(),
+            if args.concurrently { " concurrently" } else { "" },
+            last
+        );
+
+        let config = self.db.config();
+        let user = config.username.as_deref().unwrap_or("unknown");
+        let database = config.database.as_deref().unwrap_or("unknown");
+
+        approve(ctx, user, &operation, REFRESH_MATVIEW_TOOL).await?;
+
+        debug!("Refreshing materialized view {}", view.qualified_name());
+        let start = Instant::now();
+        let result = executor
+            .refresh_matview(&schema, &name, args.concurrently)
+            .await;
+        let elapsed_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+        if let Some(audit) = &ctx.audit {
+            audit.log_query(
+                user,
+                database,
+                &format!("REFRESH MATERIALIZED VIEW {}", view.qualified_name()),
+                result.is_ok(),
+                elapsed_ms,
+                None,
+                ctx.request_id.as_deref(),
+            );
+        }
+        result?;
+
+        let refreshed = find(executor.matview_freshness().await?);
+        Ok(serde_json::json!({
+            "view": view.qualified_name(),
+            "concurrently": args.concurrently,
+            "refreshed": true,
+            "executionTimeMs": elapsed_ms,
+            "freshness": refreshed.map(|v| v.note())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trait_def::Confirmer;
+    use postgres_agent_db::DbConnectionConfig;
+    use postgres_agent_safety::{read_audit_log, AuditConfig, AuditEvent, AuditLogger};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// A tool on a database that fails on first use.
+    fn kill_query_tool() -> KillQueryTool {
+        let config = DbConnectionConfig {
+            url: "postgres://agent@127.0.0.1:1/none".to_string(),
+            connect_timeout: 1,
+            ..DbConnectionConfig::default()
+        };
+        KillQueryTool::new(DbConnection::lazy(&config).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_kill_query_safety_level() {
+        let asked = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&asked);
+        let confirmer = Confirmer::new(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            true
+        });
+        let tool = kill_query_tool();
+        let args = serde_json::json!({ "pid": 4242 });
+
+        // Refused before the database or the user is asked
+        for level in [SafetyLevel::ReadOnly, SafetyLevel::Balanced] {
+            let ctx = ToolContext::default()
+                .with_confirmer(confirmer.clone())
+                .with_safety_level(level);
+            let err = tool.execute(&args, &ctx).await.unwrap_err();
+            assert!(matches!(err, ToolError::SafetyViolation { .. }), "{:?}", err);
+        }
+        assert_eq!(asked.load(Ordering::SeqCst), 0);
+
+        // The permissive level gets as far as looking up the backend
+        let ctx = ToolContext::default()
+            .with_confirmer(confirmer)
+            .with_safety_level(SafetyLevel::Permissive);
+        let err = tool.execute(&args, &ctx).await.unwrap_err();
+        assert!(!matches!(err, ToolError::SafetyViolation { .. }), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_kill_query_approval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&levels);
+        let ctx = ToolContext::default()
+            .with_confirmer(Confirmer::new(move |_, level| {
+                seen.lock().unwrap().push(level);
+                false
+            }))
+            .with_audit(Arc::new(AuditLogger::new(AuditConfig::with_path(path.clone()))));
+
+        // A denial stops the tool, and is asked for at the admin level
+        let operation = "Cancel query on backend 4242 (app, active): SELECT pg_sleep(60)";
+        let err = approve(&ctx, "agent", operation, KILL_QUERY_TOOL).await.unwrap_err();
+        assert!(matches!(err, ToolError::PermissionDenied { .. }));
+        assert_eq!(*levels.lock().unwrap(), [ConfirmationLevel::AdminApproval]);
+
+        // Even --no-confirm does not approve admin actions
+        let ctx = ToolContext::default().with_confirmer(Confirmer::approve_all());
+        assert!(approve(&ctx, "agent", operation, KILL_QUERY_TOOL).await.is_err());
+        let ctx = ToolContext::default().with_confirmer(Confirmer::new(|_, _| true));
+        assert!(approve(&ctx, "agent", operation, KILL_QUERY_TOOL).await.is_ok());
+
+        // The denial is in the audit log
+        let records = read_audit_log(&path);
+        assert_eq!(records.len(), 1);
+        assert!(matches!(
+            records[0].event(),
+            Some(AuditEvent::ConfirmationRequest { level, granted: false, .. })
+                if level == "ADMIN_APPROVAL"
+        ));
+    }
+}
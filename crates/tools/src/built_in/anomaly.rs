@@ -0,0 +1,429 @@
+//! Time series anomaly detection tool.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::trait_def::{Tool, ToolContext, ToolDefinition};
+use crate::{DbConnection, QueryExecutor, ToolError};
+
+/// Tool name used in definitions and errors.
+const TOOL_NAME: &str = "detect_anomalies";
+
+/// Default z-score above which a bucket is anomalous.
+const DEFAULT_Z_THRESHOLD: f64 = 3.0;
+
+/// Default IQR multiplier for the Tukey fences.
+const DEFAULT_IQR_MULTIPLIER: f64 = 1.5;
+
+/// Minimum number of buckets needed for meaningful statistics.
+const MIN_BUCKETS: usize = 4;
+
+/// Statistical method used to flag outliers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnomalyMethod {
+    /// Distance from the mean in standard deviations.
+    #[default]
+    Zscore,
+    /// Outside the Tukey fences `[Q1 - k*IQR, Q3 + k*IQR]`.
+    Iqr,
+}
+
+/// Width of the time buckets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    /// One bucket per minute.
+    Minute,
+    /// One bucket per hour.
+    Hour,
+    /// One bucket per day.
+    #[default]
+    Day,
+    /// One bucket per week.
+    Week,
+    /// One bucket per month.
+    Month,
+}
+
+impl Bucket {
+    /// `date_trunc` field name.
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Minute => "minute",
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+/// Aggregate computed per bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    /// Number of rows.
+    #[default]
+    Count,
+    /// Sum of a column.
+    Sum,
+    /// Average of a column.
+    Avg,
+    /// Minimum of a column.
+    Min,
+    /// Maximum of a column.
+    Max,
+}
+
+/// Arguments for the anomaly detection tool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnomalyToolArgs {
+    /// Table to analyse.
+    #[serde(default)]
+    pub table_name: Option<String>,
+    /// Schema of the table (defaults to 'public').
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Timestamp column to bucket by.
+    #[serde(default)]
+    pub time_column: Option<String>,
+    /// Bucket width.
+    #[serde(default)]
+    pub bucket: Bucket,
+    /// Aggregate per bucket.
+    #[serde(default)]
+    pub aggregate: Aggregate,
+    /// Column to aggregate (required for everything but count).
+    #[serde(default)]
+    pub value_column: Option<String>,
+    /// How far back to look, e.g. "30 days".
+    #[serde(default)]
+    pub since: Option<String>,
+    /// Custom query returning (bucket, value) rows instead of the generated one.
+    #[serde(default)]
+    pub sql: Option<String>,
+    /// Detection method.
+    #[serde(default)]
+    pub method: AnomalyMethod,
+    /// Z-score threshold or IQR multiplier.
+    #[serde(default)]
+    pub threshold: Option<f64>,
+}
+
+/// A bucket flagged as anomalous.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    /// Index of the bucket in the series.
+    pub index: usize,
+    /// Bucket value.
+    pub value: f64,
+    /// Z-score, or distance past the nearest fence in IQRs.
+    pub score: f64,
+}
+
+/// Flag values whose z-score exceeds `threshold`.
+#[must_use]
+pub fn zscore_anomalies(values: &[f64], threshold: f64) -> Vec<Anomaly> {
+    if values.len() < 2 {
+        return Vec::new();
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let stddev = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if stddev == 0.0 {
+        return Vec::new();
+    }
+
+    values
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &value)| {
+            let score = (value - mean) / stddev;
+            (score.abs() > threshold).then_some(Anomaly { index, value, score })
+        })
+        .collect()
+}
+
+/// Flag values outside the Tukey fences with multiplier `k`.
+#[must_use]
+pub fn iqr_anomalies(values: &[f64], k: f64) -> Vec<Anomaly> {
+    if values.len() < MIN_BUCKETS {
+        return Vec::new();
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let q1 = quantile(&sorted, 0.25);
+    let q3 = quantile(&sorted, 0.75);
+    let iqr = q3 - q1;
+    let (low, high) = (q1 - k * iqr, q3 + k * iqr);
+
+    values
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &value)| {
+            let distance = if value < low {
+                value - low
+            } else if value > high {
+                value - high
+            } else {
+                return None;
+            };
+            let score = if iqr > 0.0 { distance / iqr } else { distance };
+            Some(Anomaly { index, value, score })
+        })
+        .collect()
+}
+
+/// Linearly interpolated quantile of sorted values.
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    #[allow(clippy::cast_precision_loss)]
+    let position = q * (sorted.len() - 1) as f64;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let lower = position.floor() as usize;
+    let upper = (lower + 1).min(sorted.len() - 1);
+    #[allow(clippy::cast_precision_loss)]
+    let fraction = position - lower as f64;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * fraction
+}
+
+/// Quote an SQL identifier.
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Validate an interval such as "30 days" or "1 week".
+fn parse_since(since: &str) -> Option<String> {
+    let mut parts = since.split_whitespace();
+    let amount: u32 = parts.next()?.parse().ok()?;
+    let unit = parts.next()?.to_lowercase();
+    let unit = unit.trim_end_matches('s');
+    if parts.next().is_some()
+        || !["minute", "hour", "day", "week", "month", "year"].contains(&unit)
+    {
+        return None;
+    }
+    Some(format!("{} {}s", amount, unit))
+}
+
+/// Build the windowed aggregate query for structured arguments.
+fn build_query(args: &AnomalyToolArgs) -> Result<String, String> {
+    let table = args.table_name.as_deref().ok_or("tableName is required without sql")?;
+    let time_column = args.time_column.as_deref().ok_or("timeColumn is required without sql")?;
+    let schema = args.schema.as_deref().unwrap_or("public");
+    let since = args.since.as_deref().unwrap_or("30 days");
+    let since = parse_since(since).ok_or_else(|| format!("Invalid since: {}", since))?;
+
+    let value = match (args.aggregate, args.value_column.as_deref()) {
+        (Aggregate::Count, _) => "count(*)".to_string(),
+        (aggregate, Some(column)) => {
+            let function = match aggregate {
+                Aggregate::Sum => "sum",
+                Aggregate::Avg => "avg",
+                Aggregate::Min => "min",
+                _ => "max",
+            };
+            format!("{}({})", function, quote_ident(column))
+        }
+        (_, None) => return Err("valueColumn is required for this aggregate".to_string()),
+    };
+    let time = quote_ident(time_column);
+
+    Ok(format!(
+        "SELECT date_trunc('{bucket}', {time})::text AS bucket, to_jsonb(({value})::float8) AS value \
+         FROM {schema}.{table} \
+         WHERE {time} >= now() - interval '{since}' \
+         GROUP BY 1 ORDER BY 1",
+        bucket = args.bucket.as_str(),
+        time = time,
+        value = value,
+        schema = quote_ident(schema),
+        table = quote_ident(table),
+        since = since,
+    ))
+}
+
+/// Anomaly detection tool.
+///
+/// Aggregates a table into time buckets and flags buckets that stand out
+/// by z-score or interquartile range.
+#[derive(Debug)]
+pub struct AnomalyTool {
+    /// Database connection.
+    db: DbConnection,
+}
+
+impl AnomalyTool {
+    /// Create a new anomaly detection tool.
+    #[must_use]
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for AnomalyTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: TOOL_NAME.to_string(),
+            description: "Find unusual time buckets in a table. Aggregates rows per minute/hour/day/week/month and flags outliers by z-score or IQR. Returns the anomalous buckets with their values.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "tableName": { "type": "string", "description": "Table to analyse" },
+                    "schema": { "type": "string", "description": "Schema name (defaults to 'public')" },
+                    "timeColumn": { "type": "string", "description": "Timestamp column to bucket by" },
+                    "bucket": {
+                        "type": "string",
+                        "enum": ["minute", "hour", "day", "week", "month"],
+                        "description": "Bucket width (default day)"
+                    },
+                    "aggregate": {
+                        "type": "string",
+                        "enum": ["count", "sum", "avg", "min", "max"],
+                        "description": "Aggregate per bucket (default count)"
+                    },
+                    "valueColumn": { "type": "string", "description": "Column to aggregate for sum/avg/min/max" },
+                    "since": { "type": "string", "description": "How far back to look, e.g. '30 days' (default)" },
+                    "sql": { "type": "string", "description": "Custom SELECT returning (bucket, value) rows instead" },
+                    "method": {
+                        "type": "string",
+                        "enum": ["zscore", "iqr"],
+                        "description": "Detection method (default zscore)"
+                    },
+                    "threshold": { "type": "number", "description": "Z-score threshold (default 3) or IQR multiplier (default 1.5)" }
+                }
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: AnomalyToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: TOOL_NAME.to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+
+        let sql = match &args.sql {
+            Some(sql) => sql.clone(),
+            None => build_query(&args).map_err(|details| ToolError::InvalidArguments {
+                tool_name: TOOL_NAME.to_string(),
+                details,
+            })?,
+        };
+        let sql = ctx.scope_sql(&sql)?;
+        debug!("Detecting anomalies with: {}", sql);
+
+        let result = QueryExecutor::new(self.db.clone())
+            .with_cancellation(ctx.cancel.clone())
+            .execute_query(&sql)
+            .await?;
+        let (Some(bucket_column), Some(value_column)) =
+            (result.columns.first(), result.columns.get(1))
+        else {
+            return Err(ToolError::ExecutionFailed {
+                reason: "Query must return (bucket, value) columns".to_string(),
+            });
+        };
+
+        let mut buckets = Vec::new();
+        let mut values = Vec::new();
+        for row in &result.rows {
+            let value = match row.get(value_column) {
+                Some(serde_json::Value::Number(n)) => n.as_f64(),
+                Some(serde_json::Value::String(s)) => s.parse().ok(),
+                _ => None,
+            };
+            if let Some(value) = value {
+                buckets.push(row.get(bucket_column).cloned().unwrap_or_default());
+                values.push(value);
+            }
+        }
+        if values.len() < MIN_BUCKETS {
+            return Ok(serde_json::json!({
+                "sql": sql,
+                "buckets": values.len(),
+                "anomalies": [],
+                "note": format!("Need at least {} buckets with values to detect anomalies", MIN_BUCKETS)
+            }));
+        }
+
+        let threshold = args.threshold.unwrap_or(match args.method {
+            AnomalyMethod::Zscore => DEFAULT_Z_THRESHOLD,
+            AnomalyMethod::Iqr => DEFAULT_IQR_MULTIPLIER,
+        });
+        let anomalies = match args.method {
+            AnomalyMethod::Zscore => zscore_anomalies(&values, threshold),
+            AnomalyMethod::Iqr => iqr_anomalies(&values, threshold),
+        };
+
+        Ok(serde_json::json!({
+            "sql": sql,
+            "method": match args.method {
+                AnomalyMethod::Zscore => "zscore",
+                AnomalyMethod::Iqr => "iqr",
+            },
+            "threshold": threshold,
+            "buckets": values.len(),
+            "anomalies": anomalies
+                .iter()
+                .map(|a| serde_json::json!({
+                    "bucket": buckets[a.index],
+                    "value": a.value,
+                    "score": a.score,
+                    "direction": if a.score > 0.0 { "high" } else { "low" }
+                }))
+                .collect::<Vec<_>>()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIES: [f64; 8] = [10.0, 11.0, 9.0, 10.0, 12.0, 10.0, 11.0, 60.0];
+
+    #[test]
+    fn test_zscore_flags_spike() {
+        let anomalies = zscore_anomalies(&SERIES, 2.0);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].index, 7);
+        assert!(anomalies[0].score > 2.0);
+        assert!(zscore_anomalies(&[5.0; 6], 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_iqr_flags_spike_and_dip() {
+        let mut series = SERIES.to_vec();
+        series[0] = -40.0;
+        let anomalies = iqr_anomalies(&series, 1.5);
+        let indexes: Vec<usize> = anomalies.iter().map(|a| a.index).collect();
+        assert_eq!(indexes, vec![0, 7]);
+        assert!(anomalies[0].score < 0.0);
+    }
+
+    #[test]
+    fn test_build_query_quotes_identifiers() {
+        let args: AnomalyToolArgs = serde_json::from_value(serde_json::json!({
+            "tableName": "orders",
+            "timeColumn": "created\"at",
+            "bucket": "week",
+            "since": "8 Weeks"
+        }))
+        .unwrap();
+        let sql = build_query(&args).unwrap();
+        assert!(sql.contains("date_trunc('week', \"created\"\"at\")"));
+        assert!(sql.contains("FROM \"public\".\"orders\""));
+        assert!(sql.contains("interval '8 weeks'"));
+
+        assert_eq!(parse_since("1; DROP TABLE x"), None);
+    }
+}
@@ -0,0 +1,342 @@
+//! Chart suggestion tool.
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::trait_def::{Tool, ToolContext, ToolDefinition};
+use crate::{DbConnection, QueryExecutor, ToolError};
+use postgres_agent_db::executor::QueryResult;
+
+/// Tool name used in definitions and errors.
+const TOOL_NAME: &str = "suggest_chart";
+
+/// Maximum number of rows embedded in the Vega-Lite spec.
+const MAX_SPEC_ROWS: usize = 500;
+
+/// Maximum number of bars drawn in the ASCII chart.
+const MAX_ASCII_ROWS: usize = 30;
+
+/// Width of the longest ASCII bar.
+const ASCII_WIDTH: usize = 40;
+
+/// Chart mark type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartMark {
+    /// Bars, for categories.
+    Bar,
+    /// A line, for time series.
+    Line,
+    /// Points, for two numeric columns.
+    Point,
+}
+
+impl ChartMark {
+    /// Vega-Lite name of the mark.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Bar => "bar",
+            Self::Line => "line",
+            Self::Point => "point",
+        }
+    }
+}
+
+/// Arguments for the chart tool.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartToolArgs {
+    /// Query to chart instead of the last result.
+    #[serde(default)]
+    pub sql: Option<String>,
+    /// Column for the x axis.
+    #[serde(default)]
+    pub x: Option<String>,
+    /// Numeric column for the y axis.
+    #[serde(default)]
+    pub y: Option<String>,
+    /// Mark type (inferred when omitted).
+    #[serde(default)]
+    pub mark: Option<ChartMark>,
+    /// Chart title.
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// Chart suggestion tool.
+///
+/// Picks axes for a result set and returns a Vega-Lite spec together with
+/// an ASCII rendering that can be shown directly in the terminal.
+#[derive(Debug)]
+pub struct ChartTool {
+    /// Database connection.
+    db: DbConnection,
+}
+
+impl ChartTool {
+    /// Create a new chart tool reading the session's last query result.
+    #[must_use]
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for ChartTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: TOOL_NAME.to_string(),
+            description: "Suggest a chart for query results. Returns a Vega-Lite spec and an ASCII chart. Uses the last query result unless sql is given.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "sql": {
+                        "type": "string",
+                        "description": "Optional SELECT query to chart instead of the last result"
+                    },
+                    "x": {
+                        "type": "string",
+                        "description": "Column for the x axis"
+                    },
+                    "y": {
+                        "type": "string",
+                        "description": "Numeric column for the y axis"
+                    },
+                    "mark": {
+                        "type": "string",
+                        "enum": ["bar", "line", "point"],
+                        "description": "Chart type; inferred when omitted"
+                    },
+                    "title": {
+                        "type": "string",
+                        "description": "Chart title"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: ChartToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: TOOL_NAME.to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+
+        let result = match &args.sql {
+            Some(sql) => {
+                QueryExecutor::new(self.db.clone())
+                    .with_cancellation(ctx.cancel.clone())
+                    .execute_query(&ctx.scope_sql(sql)?)
+                    .await?
+            }
+            None => ctx
+                .last_result
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .as_ref()
+                .map(|last| last.result.clone())
+                .ok_or_else(|| ToolError::ExecutionFailed {
+                    reason: "No query result to chart yet".to_string(),
+                })?,
+        };
+
+        suggest(&result, &args)
+    }
+}
+
+/// Build the chart suggestion for a result set.
+fn suggest(result: &QueryResult, args: &ChartToolArgs) -> Result<serde_json::Value, ToolError> {
+    let invalid = |details: String| ToolError::InvalidArguments {
+        tool_name: TOOL_NAME.to_string(),
+        details,
+    };
+    for column in [&args.x, &args.y].into_iter().flatten() {
+        if !result.columns.contains(column) {
+            return Err(invalid(format!("Unknown column: {}", column)));
+        }
+    }
+
+    let y = match &args.y {
+        Some(y) => y.clone(),
+        None => result
+            .columns
+            .iter()
+            .rev()
+            .find(|c| args.x.as_ref() != Some(*c) && is_numeric_column(result, c))
+            .cloned()
+            .ok_or_else(|| invalid("No numeric column to plot".to_string()))?,
+    };
+    let x = match &args.x {
+        Some(x) => x.clone(),
+        None => result
+            .columns
+            .iter()
+            .find(|c| **c != y)
+            .cloned()
+            .ok_or_else(|| invalid("Need a second column for the x axis".to_string()))?,
+    };
+
+    let x_type = if is_temporal_column(result, &x) {
+        "temporal"
+    } else if is_numeric_column(result, &x) {
+        "quantitative"
+    } else {
+        "nominal"
+    };
+    let mark = args.mark.unwrap_or(match x_type {
+        "temporal" => ChartMark::Line,
+        "quantitative" => ChartMark::Point,
+        _ => ChartMark::Bar,
+    });
+
+    let mut spec = serde_json::json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "data": { "values": result.rows.iter().take(MAX_SPEC_ROWS).collect::<Vec<_>>() },
+        "mark": mark.as_str(),
+        "encoding": {
+            "x": { "field": x, "type": x_type },
+            "y": { "field": y, "type": "quantitative" }
+        }
+    });
+    if let Some(title) = &args.title {
+        spec["title"] = serde_json::Value::String(title.clone());
+    }
+
+    Ok(serde_json::json!({
+        "x": x,
+        "y": y,
+        "mark": mark.as_str(),
+        "vegaLite": spec,
+        "ascii": render_ascii(result, &x, &y),
+    }))
+}
+
+/// Read a cell as a number, accepting numeric strings (e.g. `numeric`).
+fn as_number(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Whether every non-null value of a column is numeric.
+fn is_numeric_column(result: &QueryResult, column: &str) -> bool {
+    let mut values = result
+        .rows
+        .iter()
+        .filter_map(|row| row.get(column))
+        .filter(|v| !v.is_null())
+        .peekable();
+    values.peek().is_some() && values.all(|v| as_number(v).is_some())
+}
+
+/// Whether every non-null value of a column looks like a date or timestamp.
+fn is_temporal_column(result: &QueryResult, column: &str) -> bool {
+    let mut values = result
+        .rows
+        .iter()
+        .filter_map(|row| row.get(column))
+        .filter(|v| !v.is_null())
+        .peekable();
+    values.peek().is_some()
+        && values.all(|v| v.as_str().is_some_and(looks_like_date))
+}
+
+/// Check for a leading `YYYY-MM-DD`.
+fn looks_like_date(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() >= 10
+        && bytes[..10].iter().enumerate().all(|(i, b)| match i {
+            4 | 7 => *b == b'-',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// Render a horizontal ASCII bar chart.
+fn render_ascii(result: &QueryResult, x: &str, y: &str) -> String {
+    let points: Vec<(String, f64)> = result
+        .rows
+        .iter()
+        .take(MAX_ASCII_ROWS)
+        .map(|row| {
+            let label = match row.get(x) {
+                None | Some(serde_json::Value::Null) => String::new(),
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(value) => value.to_string(),
+            };
+            (label, row.get(y).and_then(as_number).unwrap_or(0.0))
+        })
+        .collect();
+
+    let label_width = points.iter().map(|(l, _)| l.chars().count()).max().unwrap_or(0);
+    let max = points.iter().map(|(_, v)| *v).fold(0.0_f64, f64::max);
+
+    let mut out = String::new();
+    for (label, value) in &points {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let len = if max > 0.0 {
+            (value.max(0.0) / max * ASCII_WIDTH as f64).round() as usize
+        } else {
+            0
+        };
+        out.push_str(&format!(
+            "{:<width$} | {} {}\n",
+            label,
+            "#".repeat(len),
+            value,
+            width = label_width
+        ));
+    }
+    if result.rows.len() > MAX_ASCII_ROWS {
+        out.push_str(&format!("... {} more rows\n", result.rows.len() - MAX_ASCII_ROWS));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> QueryResult {
+        let row = |week: &str, signups: i64| {
+            serde_json::json!({ "week": week, "signups": signups })
+                .as_object()
+                .cloned()
+                .unwrap()
+        };
+        QueryResult {
+            columns: vec!["week".to_string(), "signups".to_string()],
+            rows: vec![row("2024-01-01", 10), row("2024-01-08", 20)],
+            row_count: 2,
+            ..QueryResult::default()
+        }
+    }
+
+    #[test]
+    fn test_infers_time_series() {
+        let chart = suggest(&sample(), &ChartToolArgs::default()).unwrap();
+        assert_eq!(chart["x"], "week");
+        assert_eq!(chart["y"], "signups");
+        assert_eq!(chart["mark"], "line");
+        assert_eq!(chart["vegaLite"]["encoding"]["x"]["type"], "temporal");
+
+        let ascii = chart["ascii"].as_str().unwrap();
+        assert!(ascii.contains(&format!("2024-01-08 | {} 20", "#".repeat(ASCII_WIDTH))));
+    }
+
+    #[test]
+    fn test_rejects_unknown_column() {
+        let args = ChartToolArgs {
+            y: Some("missing".to_string()),
+            ..ChartToolArgs::default()
+        };
+        assert!(suggest(&sample(), &args).is_err());
+    }
+}
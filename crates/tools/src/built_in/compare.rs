@@ -0,0 +1,302 @@
+//! Cross-profile comparison tool.
+//!
+//! Runs the same query on two database profiles and diffs the results
+//! client-side, keyed by one or more columns.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tracing::debug;
+
+use crate::trait_def::{Tool, ToolContext, ToolDefinition};
+use crate::{DbConnection, QueryExecutor, ToolError};
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_db::DbConnectionConfig;
+
+/// Tool name used in definitions and errors.
+const TOOL_NAME: &str = "compare_profiles";
+
+/// Maximum number of differing rows of each kind returned to the agent.
+const MAX_REPORTED_ROWS: usize = 50;
+
+/// Default comparison: exact row counts of every user table.
+pub const ROW_COUNTS_SQL: &str = r#"
+    SELECT
+        table_schema || '.' || table_name AS table_name,
+        (xpath('/row/count/text()', query_to_xml(
+            format('SELECT count(*) FROM %I.%I', table_schema, table_name), false, true, ''
+        )))[1]::text AS row_count
+    FROM information_schema.tables
+    WHERE table_schema NOT IN ('pg_catalog', 'information_schema')
+    AND table_type = 'BASE TABLE'
+    ORDER BY 1
+"#;
+
+/// A result row, keyed by column name.
+pub type Row = serde_json::Map<String, serde_json::Value>;
+
+/// A row present on both sides with different values.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedRow {
+    /// Key column values.
+    pub key: Vec<serde_json::Value>,
+    /// Row on the left side.
+    pub left: Row,
+    /// Row on the right side.
+    pub right: Row,
+}
+
+/// Difference between two result sets.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultDiff {
+    /// Columns used to match rows.
+    pub key_columns: Vec<String>,
+    /// Number of rows identical on both sides.
+    pub matching: usize,
+    /// Rows only present on the left side.
+    pub only_left: Vec<Row>,
+    /// Rows only present on the right side.
+    pub only_right: Vec<Row>,
+    /// Rows whose non-key values differ.
+    pub changed: Vec<ChangedRow>,
+}
+
+impl ResultDiff {
+    /// Whether both result sets hold the same rows.
+    #[must_use]
+    pub fn is_identical(&self) -> bool {
+        self.only_left.is_empty() && self.only_right.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Diff two results, matching rows on `key_columns`.
+///
+/// With no key columns the first column is used. Rows with duplicate keys
+/// are matched in order.
+#[must_use]
+pub fn compare_results(left: &QueryResult, right: &QueryResult, key_columns: &[String]) -> ResultDiff {
+    let key_columns: Vec<String> = if key_columns.is_empty() {
+        left.columns.first().or(right.columns.first()).cloned().into_iter().collect()
+    } else {
+        key_columns.to_vec()
+    };
+    let key_of = |row: &Row| -> Vec<serde_json::Value> {
+        key_columns
+            .iter()
+            .map(|c| row.get(c).cloned().unwrap_or_default())
+            .collect()
+    };
+
+    let mut right_by_key: HashMap<String, Vec<&Row>> = HashMap::new();
+    for row in &right.rows {
+        let key = serde_json::Value::Array(key_of(row)).to_string();
+        right_by_key.entry(key).or_default().push(row);
+    }
+    for rows in right_by_key.values_mut() {
+        rows.reverse();
+    }
+
+    let mut diff = ResultDiff {
+        key_columns: key_columns.clone(),
+        ..ResultDiff::default()
+    };
+    for row in &left.rows {
+        let key = key_of(row);
+        let matched = right_by_key
+            .get_mut(&serde_json::Value::Array(key.clone()).to_string())
+            .and_then(Vec::pop);
+        match matched {
+            Some(other) if other == row => diff.matching += 1,
+            Some(other) => diff.changed.push(ChangedRow {
+                key,
+                left: row.clone(),
+                right: other.clone(),
+            }),
+            None => diff.only_left.push(row.clone()),
+        }
+    }
+
+    // Keep the right side's order for its unmatched rows
+    for row in &right.rows {
+        let key = serde_json::Value::Array(key_of(row)).to_string();
+        if let Some(rows) = right_by_key.get_mut(&key)
+            && rows.last().is_some_and(|r| std::ptr::eq(*r, row))
+        {
+            rows.pop();
+            diff.only_right.push(row.clone());
+        }
+    }
+
+    diff
+}
+
+/// Arguments for the compare tool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareToolArgs {
+    /// Profile for the left side (defaults to the current connection).
+    #[serde(default)]
+    pub left: Option<String>,
+    /// Profile for the right side.
+    pub right: String,
+    /// Query to run on both sides (defaults to row counts per table).
+    #[serde(default)]
+    pub sql: Option<String>,
+    /// Columns used to match rows (defaults to the first column).
+    #[serde(default)]
+    pub key_columns: Vec<String>,
+}
+
+/// Cross-profile comparison tool.
+#[derive(Debug)]
+pub struct CompareTool {
+    /// Current database connection.
+    db: DbConnection,
+    /// Connection settings of the configured profiles, by name.
+    profiles: BTreeMap<String, DbConnectionConfig>,
+}
+
+impl CompareTool {
+    /// Create a new compare tool for the given profiles.
+    #[must_use]
+    pub fn new(db: DbConnection, profiles: BTreeMap<String, DbConnectionConfig>) -> Self {
+        Self { db, profiles }
+    }
+
+    /// Connect to a profile by name.
+    async fn connect(&self, profile: &str) -> Result<DbConnection, ToolError> {
+        let config = self.profiles.get(profile).ok_or_else(|| ToolError::InvalidArguments {
+            tool_name: TOOL_NAME.to_string(),
+            details: format!(
+                "Unknown profile '{}'; available: {}",
+                profile,
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        })?;
+        Ok(DbConnection::new(config).await?)
+    }
+}
+
+#[async_trait]
+impl Tool for CompareTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: TOOL_NAME.to_string(),
+            description: format!(
+                "Run the same SELECT on two database profiles and diff the results. Defaults to exact row counts per table. Profiles: {}.",
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "left": {
+                        "type": "string",
+                        "description": "Profile for the left side (defaults to the current connection)"
+                    },
+                    "right": {
+                        "type": "string",
+                        "description": "Profile for the right side"
+                    },
+                    "sql": {
+                        "type": "string",
+                        "description": "SELECT to run on both sides (defaults to row counts per table)"
+                    },
+                    "keyColumns": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Columns used to match rows (defaults to the first column)"
+                    }
+                },
+                "required": ["right"]
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: CompareToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: TOOL_NAME.to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+        let sql = ctx.scope_sql(args.sql.as_deref().unwrap_or(ROW_COUNTS_SQL))?;
+        let sql = sql.as_str();
+
+        let left_db = match &args.left {
+            Some(profile) => self.connect(profile).await?,
+            None => self.db.clone(),
+        };
+        let right_db = self.connect(&args.right).await?;
+        debug!("Comparing {:?} with {}", args.left, args.right);
+
+        let (left_executor, right_executor) =
+            (QueryExecutor::new(left_db), QueryExecutor::new(right_db));
+        let (left, right) = tokio::try_join!(
+            left_executor.execute_query(sql),
+            right_executor.execute_query(sql),
+        )?;
+        let mut diff = compare_results(&left, &right, &args.key_columns);
+
+        let counts = serde_json::json!({
+            "matching": diff.matching,
+            "onlyLeft": diff.only_left.len(),
+            "onlyRight": diff.only_right.len(),
+            "changed": diff.changed.len()
+        });
+        diff.only_left.truncate(MAX_REPORTED_ROWS);
+        diff.only_right.truncate(MAX_REPORTED_ROWS);
+        diff.changed.truncate(MAX_REPORTED_ROWS);
+
+        Ok(serde_json::json!({
+            "left": args.left.as_deref().unwrap_or("current"),
+            "right": args.right,
+            "identical": diff.is_identical(),
+            "counts": counts,
+            "diff": diff
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(rows: &[(&str, i64)]) -> QueryResult {
+        QueryResult {
+            columns: vec!["table_name".to_string(), "row_count".to_string()],
+            rows: rows
+                .iter()
+                .map(|(name, count)| {
+                    serde_json::json!({ "table_name": name, "row_count": count })
+                        .as_object()
+                        .cloned()
+                        .unwrap()
+                })
+                .collect(),
+            row_count: rows.len(),
+            ..QueryResult::default()
+        }
+    }
+
+    #[test]
+    fn test_compare_results() {
+        let left = result(&[("users", 10), ("orders", 5), ("audit", 1)]);
+        let right = result(&[("users", 10), ("orders", 7), ("events", 3)]);
+
+        let diff = compare_results(&left, &right, &[]);
+        assert_eq!(diff.key_columns, vec!["table_name"]);
+        assert_eq!(diff.matching, 1);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].key, vec![serde_json::json!("orders")]);
+        assert_eq!(diff.only_left[0]["table_name"], "audit");
+        assert_eq!(diff.only_right[0]["table_name"], "events");
+        assert!(!diff.is_identical());
+
+        assert!(compare_results(&left, &left, &[]).is_identical());
+    }
+}
@@ -0,0 +1,345 @@
+//! Result export tool.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+use crate::trait_def::{Tool, ToolContext, ToolDefinition};
+use crate::{DbConnection, QueryExecutor, ToolError};
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_db::spill::JsonRow;
+use postgres_agent_db::NumericOutput;
+use postgres_agent_safety::ConfirmationLevel;
+
+/// Tool name used in definitions and errors.
+const TOOL_NAME: &str = "export_result";
+
+/// A query run by the query tool and the rows it returned.
+#[derive(Debug, Clone, Default)]
+pub struct LastQuery {
+    /// SQL as executed.
+    pub sql: String,
+    /// Rows returned to the agent.
+    pub result: QueryResult,
+    /// Whether `result` holds only some of the rows.
+    pub partial: bool,
+}
+
+/// Most recent query of the query tool, kept per session in the tool context.
+pub type LastResult = Arc<Mutex<Option<LastQuery>>>;
+
+/// File format for exported results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row.
+    Csv,
+    /// A JSON array of row objects.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl ExportFormat {
+    /// Infer the format from a file extension.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+
+    /// Render a result in this format, writing exact numbers in JSON as
+    /// `numeric` asks.
+    ///
+    /// # Errors
+    /// Returns an error if JSON serialization fails.
+    pub fn render(self, result: &QueryResult, numeric: NumericOutput) -> Result<String, ToolError> {
+        let mut out = Vec::new();
+        self.write(result, numeric, &mut out)?;
+        String::from_utf8(out).map_err(|e| ToolError::ExecutionFailed {
+            reason: e.to_string(),
+        })
+    }
+
+    /// Write a result in this format to `out` row by row, including rows
+    /// spilled to disk.
+    ///
+    /// # Errors
+    /// Returns an error if a row cannot be read, serialized or written.
+    pub fn write(
+        self,
+        result: &QueryResult,
+        numeric: NumericOutput,
+        out: &mut impl Write,
+    ) -> Result<(), ToolError> {
+        match self {
+            Self::Csv => {
+                out.write_all(csv_line(result.columns.iter().map(String::as_str)).as_bytes())
+                    .map_err(write_error)?;
+                for row in result.all_rows() {
+                    let row = row?;
+                    out.write_all(csv_row(result, &row).as_bytes()).map_err(write_error)?;
+                }
+            }
+            Self::Json => {
+                let mut serializer = serde_json::Serializer::pretty(&mut *out);
+                result.json_rows(numeric).serialize(&mut serializer)?;
+            }
+            Self::Ndjson => {
+                for row in result.json_rows(numeric).rows() {
+                    serde_json::to_writer(&mut *out, &row?)?;
+                    out.write_all(b"\n").map_err(write_error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a result in this format to the file at `path`, returning its
+    /// size in bytes.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn write_file(
+        self,
+        result: &QueryResult,
+        numeric: NumericOutput,
+        path: &Path,
+    ) -> Result<u64, ToolError> {
+        let file_error = |e: std::io::Error| ToolError::ExecutionFailed {
+            reason: format!("Failed to write {}: {}", path.display(), e),
+        };
+        let mut out = BufWriter::new(File::create(path).map_err(file_error)?);
+        self.write(result, numeric, &mut out)?;
+        let file = out.into_inner().map_err(|e| file_error(e.into_error()))?;
+        Ok(file.metadata().map_err(file_error)?.len())
+    }
+}
+
+/// Arguments for the export tool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportToolArgs {
+    /// Destination file path.
+    pub path: String,
+    /// File format (inferred from the extension when omitted).
+    #[serde(default)]
+    pub format: Option<ExportFormat>,
+    /// Query to run and export instead of the last result.
+    #[serde(default)]
+    pub sql: Option<String>,
+    /// Replace the file if it already exists.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Export tool.
+///
+/// Writes the last query result, or the result of a given query, to a file.
+/// Every export must be approved through the tool context's confirmer.
+#[derive(Debug)]
+pub struct ExportTool {
+    /// Database connection.
+    db: DbConnection,
+}
+
+impl ExportTool {
+    /// Create a new export tool reading the session's last query result.
+    #[must_use]
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for ExportTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: TOOL_NAME.to_string(),
+            description: "Save query results to a file (csv, json or ndjson). Exports the last query result unless sql is given. The user must approve the destination.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Destination file path"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["csv", "json", "ndjson"],
+                        "description": "File format; inferred from the extension when omitted"
+                    },
+                    "sql": {
+                        "type": "string",
+                        "description": "Optional SELECT query to run and export instead of the last result"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace an existing file"
+                    }
+                },
+                "required": ["path"]
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: ExportToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: TOOL_NAME.to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+
+        let path = Path::new(&args.path);
+        let format = args
+            .format
+            .or_else(|| ExportFormat::from_path(path))
+            .ok_or_else(|| ToolError::InvalidArguments {
+                tool_name: TOOL_NAME.to_string(),
+                details: "Cannot infer the format from the path; pass format".to_string(),
+            })?;
+        if path.exists() && !args.overwrite {
+            return Err(ToolError::ExecutionFailed {
+                reason: format!("{} already exists", path.display()),
+            });
+        }
+
+        let executor =
+            QueryExecutor::new(self.db.clone()).with_cancellation(ctx.cancel.clone());
+        let result = match &args.sql {
+            Some(sql) => executor.execute_query(&ctx.scope_sql(sql)?).await?,
+            None => {
+                let last = ctx
+                    .last_result
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .clone()
+                    .ok_or_else(|| ToolError::ExecutionFailed {
+                        reason: "No query result to export yet".to_string(),
+                    })?;
+                // Only one page was kept; export every row
+                if last.partial {
+                    executor.execute_query(&last.sql).await?
+                } else {
+                    last.result
+                }
+            }
+        };
+
+        let prompt = format!(
+            "Write {} rows as {:?} to {}?",
+            result.row_count,
+            format,
+            path.display()
+        );
+        if !ctx.request_approval(&prompt, ConfirmationLevel::Simple).await? {
+            return Err(ToolError::PermissionDenied {
+                tool_name: TOOL_NAME.to_string(),
+            });
+        }
+
+        debug!("Exporting {} rows to {}", result.row_count, path.display());
+        let bytes = format.write_file(&result, self.db.numeric_output(), path)?;
+
+        Ok(serde_json::json!({
+            "path": path.display().to_string(),
+            "rowCount": result.row_count,
+            "bytes": bytes
+        }))
+    }
+}
+
+/// Render one row of a result as a CSV line.
+fn csv_row(result: &QueryResult, row: &JsonRow) -> String {
+    let cells: Vec<String> = result
+        .columns
+        .iter()
+        .map(|column| match row.get(column) {
+            None | Some(serde_json::Value::Null) => String::new(),
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+        })
+        .collect();
+    csv_line(cells.iter().map(String::as_str))
+}
+
+fn write_error(e: std::io::Error) -> ToolError {
+    ToolError::ExecutionFailed {
+        reason: format!("Failed to write export: {}", e),
+    }
+}
+
+/// Join fields into one CSV line, quoting where needed.
+fn csv_line<'a>(fields: impl Iterator<Item = &'a str>) -> String {
+    let fields: Vec<String> = fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    format!("{}\n", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> QueryResult {
+        let row = |id: i64, name: &str| {
+            serde_json::json!({ "id": id, "name": name })
+                .as_object()
+                .cloned()
+                .unwrap()
+        };
+        QueryResult {
+            columns: vec!["id".to_string(), "name".to_string()],
+            rows: vec![row(1, "plain"), row(2, "with, \"quotes\"")],
+            row_count: 2,
+            ..QueryResult::default()
+        }
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        assert_eq!(
+            ExportFormat::Csv.render(&sample(), NumericOutput::Number).unwrap(),
+            "id,name\n1,plain\n2,\"with, \"\"quotes\"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ExportFormat::from_path(Path::new("out.CSV")), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::from_path(Path::new("out.jsonl")), Some(ExportFormat::Ndjson));
+        assert_eq!(ExportFormat::from_path(Path::new("out")), None);
+        let ndjson = ExportFormat::Ndjson.render(&sample(), NumericOutput::Number).unwrap();
+        assert_eq!(ndjson.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_json_numeric_output() {
+        let result = QueryResult {
+            column_types: vec!["INT8".to_string(), "TEXT".to_string()],
+            ..sample()
+        };
+        let ndjson = ExportFormat::Ndjson.render(&result, NumericOutput::String).unwrap();
+        assert_eq!(ndjson.lines().next(), Some(r#"{"id":"1","name":"plain"}"#));
+        let json = ExportFormat::Json.render(&result, NumericOutput::Number).unwrap();
+        assert!(json.contains("\"id\": 1,"));
+    }
+}
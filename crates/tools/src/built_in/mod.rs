@@ -3,12 +3,37 @@
 //! This module provides the core database tools that the agent uses
 //! to interact with PostgreSQL databases.
 
+pub mod admin;
+pub mod anomaly;
+pub mod chart;
+pub mod compare;
+pub mod export;
+
 use async_trait::async_trait;
 use serde::Deserialize;
 use tracing::debug;
 
-use crate::trait_def::{Tool, ToolContext, ToolDefinition};
+use crate::trait_def::{Tool, ToolContext, ToolDefinition, UndoLog};
 use crate::{ToolError, DbConnection, QueryExecutor};
+use postgres_agent_safety::{
+    is_temp_table_statement, rls_warnings, tables_in_query, unqualified_tables,
+    ConfirmationLevel, OperationType, PolicyAction, SafetyContext, SafetyValidator,
+    SelectStarGuard, TableShape,
+};
+use postgres_agent_db::lineage;
+use postgres_agent_db::{
+    quote_ident, quote_qualified, split_qualified_name, DatabaseSchema, MatviewFreshness, Sandbox,
+    SandboxMode, Snapshot,
+};
+
+pub use admin::{KillQueryTool, RefreshMatviewTool};
+pub use anomaly::{AnomalyMethod, AnomalyTool};
+pub use chart::{ChartMark, ChartTool};
+pub use compare::{compare_results, CompareTool, ResultDiff, ROW_COUNTS_SQL};
+pub use export::{ExportFormat, ExportTool, LastQuery, LastResult};
+
+/// Rows returned to the model per query; further rows are paged.
+pub const RESULT_PAGE_SIZE: usize = 200;
 
 /// Arguments for the query execution tool.
 #[derive(Debug, Clone, Deserialize)]
@@ -16,6 +41,9 @@ use crate::{ToolError, DbConnection, QueryExecutor};
 pub struct QueryToolArgs {
     /// The SQL query to execute.
     pub sql: String,
+    /// Continuation token of a previous page of the same query.
+    #[serde(default)]
+    pub cursor: Option<String>,
 }
 
 /// Arguments for the schema introspection tool.
@@ -31,7 +59,7 @@ pub struct SchemaToolArgs {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListTablesToolArgs {
-    /// Optional schema name (defaults to 'public').
+    /// Optional schema name (defaults to the profile's default schema).
     #[serde(default)]
     pub schema: Option<String>,
 }
@@ -40,8 +68,37 @@ pub struct ListTablesToolArgs {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DescribeTableToolArgs {
-    /// Name of the table to describe.
+    /// Name of the table to describe, optionally schema-qualified.
+    pub table_name: String,
+    /// Optional schema name (defaults to the profile's default schema).
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+/// Arguments for the table dependencies tool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependenciesToolArgs {
+    /// Name of the table to inspect.
+    pub table_name: String,
+    /// Optional schema name (defaults to the profile's default schema or
+    /// 'public').
+    #[serde(default)]
+    pub schema: Option<String>,
+}
+
+/// Arguments for the column lineage tool.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceColumnToolArgs {
+    /// Table or view holding the column, optionally schema-qualified.
     pub table_name: String,
+    /// Column to trace.
+    pub column: String,
+    /// Optional schema name (defaults to the profile's default schema or
+    /// 'public').
+    #[serde(default)]
+    pub schema: Option<String>,
 }
 
 /// Arguments for the explain query tool.
@@ -68,6 +125,22 @@ pub enum BuiltInTool {
     DescribeTable(DescribeTableTool),
     /// Explain query tool.
     Explain(ExplainTool),
+    /// Result export tool.
+    Export(ExportTool),
+    /// Chart suggestion tool.
+    Chart(ChartTool),
+    /// Table dependencies tool.
+    Dependencies(DependenciesTool),
+    /// Column lineage tool.
+    TraceColumn(TraceColumnTool),
+    /// Backend cancel/terminate tool.
+    KillQuery(KillQueryTool),
+    /// Materialized view refresh tool.
+    RefreshMatview(RefreshMatviewTool),
+    /// Time series anomaly detection tool.
+    Anomalies(AnomalyTool),
+    /// Cross-profile comparison tool.
+    Compare(CompareTool),
 }
 
 impl BuiltInTool {
@@ -80,6 +153,40 @@ impl BuiltInTool {
             BuiltInTool::ListTables(_) => "list_tables",
             BuiltInTool::DescribeTable(_) => "describe_table",
             BuiltInTool::Explain(_) => "explain_query",
+            BuiltInTool::Export(_) => "export_result",
+            BuiltInTool::Chart(_) => "suggest_chart",
+            BuiltInTool::Dependencies(_) => "get_table_dependencies",
+            BuiltInTool::TraceColumn(_) => "trace_column",
+            BuiltInTool::KillQuery(_) => "kill_query",
+            BuiltInTool::RefreshMatview(_) => "refresh_matview",
+            BuiltInTool::Anomalies(_) => "detect_anomalies",
+            BuiltInTool::Compare(_) => "compare_profiles",
+        }
+    }
+
+    /// Get the tool namespace.
+    #[must_use]
+    pub fn namespace(&self) -> &'static str {
+        match self {
+            BuiltInTool::KillQuery(_) | BuiltInTool::RefreshMatview(_) => "admin",
+            _ => "db",
+        }
+    }
+
+    /// Whether a call with `args` changes anything outside the agent, so
+    /// that running it twice differs from running it once.
+    #[must_use]
+    pub fn has_side_effects(&self, args: &serde_json::Value) -> bool {
+        match self {
+            BuiltInTool::Export(_) | BuiltInTool::KillQuery(_) | BuiltInTool::RefreshMatview(_) => {
+                true
+            }
+            // Mutations run once a policy rule lets them through
+            BuiltInTool::Query(_) => args
+                .get("sql")
+                .and_then(serde_json::Value::as_str)
+                .is_some_and(|sql| SafetyValidator::new().is_mutation(sql)),
+            _ => false,
         }
     }
 }
@@ -92,28 +199,273 @@ impl BuiltInTool {
 pub struct QueryTool {
     /// Database connection.
     db: DbConnection,
+    /// Tables filtered by row-level security, looked up on first use.
+    rls_tables: std::sync::Mutex<Option<Vec<String>>>,
+    /// Table widths and sizes, looked up on the first `SELECT *`.
+    table_shapes: std::sync::Mutex<Option<Vec<TableShape>>>,
+    /// Materialized views as `schema.name`, looked up on first use.
+    matviews: std::sync::Mutex<Option<Vec<String>>>,
 }
 
 impl QueryTool {
     /// Create a new query tool.
     #[must_use]
     pub fn new(db: DbConnection) -> Self {
-        Self { db }
+        Self {
+            db,
+            rls_tables: std::sync::Mutex::new(None),
+            table_shapes: std::sync::Mutex::new(None),
+            matviews: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Tables filtered by row-level security for the connected role.
+    ///
+    /// Lookup failures are logged and treated as no tables.
+    async fn rls_tables(&self, executor: &QueryExecutor) -> Vec<String> {
+        if let Some(tables) = self
+            .rls_tables
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+        {
+            return tables.clone();
+        }
+        let tables = executor.rls_filtered_tables().await.unwrap_or_else(|e| {
+            debug!("Could not look up row-level security: {}", e);
+            Vec::new()
+        });
+        *self
+            .rls_tables
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(tables.clone());
+        tables
+    }
+
+    /// Reject `sql` if it references a table without its schema.
+    ///
+    /// Lookup failures are logged and let the query through.
+    async fn check_qualified(&self, sql: &str) -> Result<(), ToolError> {
+        let schema = match self.db.cached_schema().await {
+            Ok(schema) => schema,
+            Err(e) => {
+                debug!("Could not look up tables: {}", e);
+                return Ok(());
+            }
+        };
+        // Temp tables of a workspace are referenced without their schema
+        let tables: Vec<String> = schema
+            .tables
+            .iter()
+            .filter(|t| !t.table_schema.starts_with("pg_temp"))
+            .map(|t| format!("{}.{}", t.table_schema, t.table_name))
+            .collect();
+        let unqualified: Vec<String> = unqualified_tables(sql, &tables)
+            .into_iter()
+            .filter_map(|name| name.split_once('.'))
+            .map(|(schema, table)| quote_qualified(schema, table))
+            .collect();
+        if unqualified.is_empty() {
+            return Ok(());
+        }
+        Err(ToolError::SafetyViolation {
+            reason: format!(
+                "Table references must be schema-qualified; use {}",
+                unqualified.join(", ")
+            ),
+        })
+    }
+
+    /// Apply the configured policy rules to `sql`, asking for confirmation
+    /// when a rule requires it; returns whether it was confirmed.
+    ///
+    /// Role and row estimate lookup failures are logged and leave them
+    /// unknown.
+    async fn check_policy(
+        &self,
+        validator: &SafetyValidator,
+        executor: &QueryExecutor,
+        sql: &str,
+        ctx: &ToolContext,
+    ) -> Result<bool, ToolError> {
+        let policy = validator.policy();
+        if policy.is_empty() {
+            return Ok(false);
+        }
+        let mut safety = SafetyContext::default();
+        if policy.needs_role() {
+            match executor.current_role().await {
+                Ok(role) => safety = safety.with_role(role),
+                Err(e) => debug!("Could not look up the current role: {}", e),
+            }
+        }
+        if policy.needs_row_estimate() {
+            match executor.estimate_rows(sql).await {
+                Ok(Some(rows)) => safety = safety.with_estimated_rows(rows),
+                Ok(None) => {}
+                Err(e) => debug!("Could not estimate rows: {}", e),
+            }
+        }
+        let operation = validator.classify_operation(sql);
+        let Some(decision) = policy.evaluate(sql, operation, &safety) else {
+            return Ok(false);
+        };
+        let reason = decision.message.unwrap_or_else(|| {
+            format!("{} matched policy rule '{}'", operation.label(), decision.rule)
+        });
+        match decision.action {
+            PolicyAction::Allow => Ok(false),
+            PolicyAction::Deny => Err(ToolError::SafetyViolation { reason }),
+            PolicyAction::Confirm => {
+                let mut prompt = format!("{}\n{}\nRun this query?", reason, sql);
+                if let Some(mode) = &ctx.sandbox {
+                    prompt = match &ctx.approval {
+                        // A resumed run was shown the sandbox run before
+                        Some(approval) if approval.prompt.ends_with(&prompt) => {
+                            approval.prompt.clone()
+                        }
+                        _ => format!("{}\n{}", self.simulate(executor, mode, sql).await, prompt),
+                    };
+                }
+                if ctx.request_approval(&prompt, ConfirmationLevel::Simple).await? {
+                    Ok(true)
+                } else {
+                    Err(ToolError::PermissionDenied {
+                        tool_name: "execute_query".to_string(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Effects of `sql` run in a sandbox database, or why it could not run
+    /// there.
+    async fn simulate(&self, executor: &QueryExecutor, mode: &SandboxMode, sql: &str) -> String {
+        let names: Vec<String> =
+            self.table_shapes(executor).await.into_iter().map(|shape| shape.name).collect();
+        let tables: Vec<String> =
+            tables_in_query(sql, &names).into_iter().map(str::to_string).collect();
+        let sandbox = Sandbox::new(self.db.clone(), mode.clone());
+        match sandbox.simulate(&[sql.to_string()], &tables).await {
+            Ok(report) => report.to_string(),
+            Err(e) => format!("Sandbox run ({}) unavailable: {}", mode, e),
+        }
+    }
+
+    /// Save the tables `sql` references before running it.
+    async fn snapshot(
+        &self,
+        executor: &QueryExecutor,
+        log: &UndoLog,
+        sql: &str,
+    ) -> Result<Snapshot, String> {
+        let names: Vec<String> =
+            self.table_shapes(executor).await.into_iter().map(|shape| shape.name).collect();
+        let tables: Vec<String> =
+            tables_in_query(sql, &names).into_iter().map(str::to_string).collect();
+        if tables.is_empty() {
+            return Err("no tables found in the query".to_string());
+        }
+        Snapshot::take(&self.db, &tables, log.dir(), log.max_rows())
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Widths and sizes of all tables.
+    ///
+    /// Lookup failures are logged and treated as no tables.
+    async fn table_shapes(&self, executor: &QueryExecutor) -> Vec<TableShape> {
+        if let Some(shapes) = self
+            .table_shapes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_ref()
+        {
+            return shapes.clone();
+        }
+        let shapes: Vec<TableShape> = match executor.table_sizes().await {
+            Ok(sizes) => sizes
+                .into_iter()
+                .map(|size| TableShape {
+                    name: format!("{}.{}", size.table_schema, size.table_name),
+                    columns: size.columns,
+                    estimated_rows: size.estimated_rows,
+                })
+                .collect(),
+            Err(e) => {
+                debug!("Could not look up table sizes: {}", e);
+                Vec::new()
+            }
+        };
+        *self
+            .table_shapes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(shapes.clone());
+        shapes
+    }
+
+    /// Freshness notes for the materialized views `sql` reads from.
+    ///
+    /// Lookup failures are logged and treated as no views.
+    async fn freshness_notes(&self, executor: &QueryExecutor, sql: &str) -> Vec<String> {
+        let cached = self
+            .matviews
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone();
+        let freshness = match cached {
+            Some(names) if tables_in_query(sql, &names).is_empty() => return Vec::new(),
+            _ => match executor.matview_freshness().await {
+                Ok(freshness) => freshness,
+                Err(e) => {
+                    debug!("Could not look up materialized views: {}", e);
+                    return Vec::new();
+                }
+            },
+        };
+        let names: Vec<String> = freshness.iter().map(MatviewFreshness::qualified_name).collect();
+        let used = tables_in_query(sql, &names);
+        let notes = freshness
+            .iter()
+            .filter(|view| used.contains(&view.qualified_name().as_str()))
+            .map(MatviewFreshness::note)
+            .collect();
+        *self
+            .matviews
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(names);
+        notes
     }
 }
 
 #[async_trait]
 impl Tool for QueryTool {
     fn definition(&self) -> ToolDefinition {
+        let mut description = format!(
+            "Execute a SQL SELECT query and return results in JSON format. Only SELECT \
+             queries are allowed. At most {} rows are returned; `truncated` marks \
+             results with more rows, fetched by passing back `nextCursor`.",
+            RESULT_PAGE_SIZE
+        );
+        if self.db.is_workspace() {
+            description.push_str(
+                " This session also allows CREATE TEMP TABLE ... AS SELECT and DROP TABLE \
+                 pg_temp.name, to keep intermediate results of multi-step analyses.",
+            );
+        }
         ToolDefinition {
             name: "execute_query".to_string(),
-            description: "Execute a SQL SELECT query and return results in JSON format. Only SELECT queries are allowed.".to_string(),
+            description,
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "sql": {
                         "type": "string",
                         "description": "The SQL SELECT query to execute"
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "nextCursor of an earlier result of this query"
                     }
                 },
                 "required": ["sql"]
@@ -124,7 +476,7 @@ impl Tool for QueryTool {
     async fn execute(
         &self,
         args: &serde_json::Value,
-        _ctx: &ToolContext,
+        ctx: &ToolContext,
     ) -> Result<serde_json::Value, ToolError> {
         let args: QueryToolArgs = serde_json::from_value(args.clone())
             .map_err(|e| ToolError::InvalidArguments {
@@ -132,21 +484,140 @@ impl Tool for QueryTool {
                 details: format!("Invalid arguments: {}", e),
             })?;
 
-        debug!("Executing query: {}", args.sql);
+        let sql = ctx.scope_sql(&args.sql)?;
+        debug!("Executing query: {}", sql);
+        if ctx.qualify_tables {
+            self.check_qualified(&sql).await?;
+        }
 
-        let executor = QueryExecutor::new(self.db.clone());
-        let result = executor.execute_query(&args.sql).await?;
+        let executor =
+            QueryExecutor::new(self.db.clone()).with_cancellation(ctx.cancel.clone());
+        // Later pages of a query were checked with the first
+        let mut confirmed_mutation = false;
+        if let Some(validator) = &ctx.policy
+            && args.cursor.is_none()
+        {
+            confirmed_mutation = self.check_policy(validator, &executor, &sql, ctx).await?
+                && validator.classify_operation(&sql) != OperationType::Read;
+        }
+        if self.db.is_workspace() && is_temp_table_statement(&sql) {
+            return run_temp_statement(&executor, &sql).await;
+        }
+        let select_star_warnings = match &ctx.select_star {
+            Some(guard) if SelectStarGuard::selects_star(&sql) => guard
+                .apply(&sql, &self.table_shapes(&executor).await)
+                .map_err(|reason| ToolError::SafetyViolation { reason })?,
+            _ => Vec::new(),
+        };
+        let mut undo_warnings = Vec::new();
+        let mut snapshot = None;
+        if let Some(log) = &ctx.undo
+            && confirmed_mutation
+        {
+            match self.snapshot(&executor, log, &sql).await {
+                Ok(saved) => snapshot = Some(saved),
+                Err(e) => undo_warnings.push(format!("No snapshot taken, cannot undo: {}", e)),
+            }
+        }
+        let start = std::time::Instant::now();
+        let page = executor
+            .execute_paged(&sql, args.cursor.as_deref(), RESULT_PAGE_SIZE)
+            .await;
+        if let Some(audit) = &ctx.audit {
+            let config = self.db.config();
+            audit.log_query(
+                config.username.as_deref().unwrap_or("unknown"),
+                config.database.as_deref().unwrap_or("unknown"),
+                &sql,
+                page.is_ok(),
+                u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+                page.as_ref().ok().and_then(|p| i64::try_from(p.result.row_count).ok()),
+                ctx.request_id.as_deref(),
+            );
+        }
+        if page.is_err()
+            && let Some(snapshot) = snapshot.take()
+            && let Err(e) = snapshot.discard()
+        {
+            debug!("Failed to remove snapshot {}: {}", snapshot.dir.display(), e);
+        }
+        let page = page?;
+        let result = page.result;
+        if changes_schema(&sql) {
+            self.db.schema_cache().invalidate().await;
+        }
 
-        Ok(serde_json::json!({
+        let mut output = serde_json::json!({
             "columns": result.columns,
             "rows": result.rows,
             "rowCount": result.row_count,
             "truncated": result.truncated,
             "executionTimeMs": result.execution_time_ms
-        }))
+        });
+        if let Some(next) = page.next {
+            output["nextCursor"] = serde_json::json!(next);
+            output["sql"] = serde_json::json!(sql);
+        }
+        if let Some(log) = &ctx.undo
+            && let Some(snapshot) = snapshot
+        {
+            match log.record(&sql, &result, snapshot) {
+                Ok(id) => output["undo"] = serde_json::json!(format!("pg-agent undo {}", id)),
+                Err(e) => {
+                    undo_warnings.push(format!("Snapshot not registered, cannot undo: {}", e));
+                }
+            }
+        }
+        let mut warnings = rls_warnings(&sql, &self.rls_tables(&executor).await);
+        warnings.extend(select_star_warnings);
+        warnings.extend(undo_warnings);
+        if sql != args.sql {
+            warnings.push("Tenant filter added to query".to_string());
+            output["sql"] = serde_json::json!(sql);
+        }
+        if !warnings.is_empty() {
+            output["warnings"] = serde_json::json!(warnings);
+        }
+        let freshness = self.freshness_notes(&executor, &sql).await;
+        if !freshness.is_empty() {
+            output["freshness"] = serde_json::json!(freshness);
+        }
+        *ctx
+            .last_result
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(LastQuery {
+            partial: result.truncated || args.cursor.is_some(),
+            sql,
+            result,
+        });
+
+        Ok(output)
     }
 }
 
+/// Run a temp table statement in a workspace session.
+async fn run_temp_statement(
+    executor: &QueryExecutor,
+    sql: &str,
+) -> Result<serde_json::Value, ToolError> {
+    debug!("Running temp table statement: {}", sql);
+    let start = std::time::Instant::now();
+    let rows = executor.execute_statement(sql).await?;
+    Ok(serde_json::json!({
+        "rowCount": rows,
+        "executionTimeMs": u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        "note": "Temp tables last for this session; query them as pg_temp.name"
+    }))
+}
+
+/// Whether a statement may change the cached schema.
+fn changes_schema(sql: &str) -> bool {
+    let keyword = sql.split_whitespace().next().unwrap_or_default();
+    ["CREATE", "ALTER", "DROP"]
+        .iter()
+        .any(|ddl| keyword.eq_ignore_ascii_case(ddl))
+}
+
 /// Schema introspection tool.
 ///
 /// Retrieves the database schema including all tables and their columns.
@@ -169,7 +640,7 @@ impl Tool for SchemaTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "get_schema".to_string(),
-            description: "Get the complete database schema including all tables and their columns. Optionally filter by table name prefix.".to_string(),
+            description: "Get the complete database schema including all tables, their columns, row-level security policies and grants. Optionally filter by table name prefix.".to_string(),
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -195,12 +666,17 @@ impl Tool for SchemaTool {
 
         debug!("Getting schema with filter: {:?}", args.table_filter);
 
-        let executor = QueryExecutor::new(self.db.clone());
-        let schema = executor.get_schema(args.table_filter.as_deref()).await?;
+        let cached = self.db.cached_schema().await?;
+        let schema = match args.table_filter.as_deref() {
+            Some(prefix) => cached.filtered(prefix),
+            None => DatabaseSchema::clone(&cached),
+        };
 
         Ok(serde_json::json!({
             "tables": schema.tables,
-            "columns": schema.columns
+            "columns": schema.columns,
+            "security": schema.security,
+            "enums": schema.enums
         }))
     }
 }
@@ -227,13 +703,13 @@ impl Tool for ListTablesTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "list_tables".to_string(),
-            description: "List all table names in a database schema. Defaults to 'public' schema.".to_string(),
+            description: "List all table names in a database schema. Defaults to the profile's default schema, then the search_path.".to_string(),
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "schema": {
                         "type": "string",
-                        "description": "Schema name (defaults to 'public')"
+                        "description": "Schema name (defaults to the profile's default schema, then the search_path)"
                     }
                 }
             }),
@@ -254,7 +730,8 @@ impl Tool for ListTablesTool {
         debug!("Listing tables in schema: {:?}", args.schema);
 
         let executor = QueryExecutor::new(self.db.clone());
-        let tables = executor.list_tables(args.schema.as_deref()).await?;
+        let schema = args.schema.as_deref().or(self.db.default_schema());
+        let tables = executor.list_tables(schema).await?;
 
         Ok(serde_json::json!({
             "tables": tables
@@ -290,7 +767,11 @@ impl Tool for DescribeTableTool {
                 "properties": {
                     "tableName": {
                         "type": "string",
-                        "description": "Name of the table to describe"
+                        "description": "Name of the table to describe, optionally schema-qualified (e.g. 'analytics.events')"
+                    },
+                    "schema": {
+                        "type": "string",
+                        "description": "Schema name (defaults to the profile's default schema, then the search_path)"
                     }
                 },
                 "required": ["tableName"]
@@ -309,18 +790,181 @@ impl Tool for DescribeTableTool {
                 details: format!("Invalid arguments: {}", e),
             })?;
 
-        debug!("Describing table: {}", args.table_name);
+        let (qualifier, table_name) = split_qualified_name(&args.table_name);
+        let schema = qualifier
+            .as_deref()
+            .or(args.schema.as_deref())
+            .or(self.db.default_schema());
+
+        debug!("Describing table: {:?}.{}", schema, table_name);
 
         let executor = QueryExecutor::new(self.db.clone());
-        let columns = executor.describe_table(&args.table_name).await?;
+        let columns = executor.describe_table(schema, &table_name).await?;
+        let sql_name = match schema {
+            Some(schema) => quote_qualified(schema, &table_name),
+            None => quote_ident(&table_name),
+        };
 
         Ok(serde_json::json!({
-            "tableName": args.table_name,
+            "tableName": table_name,
+            "schema": schema,
+            "sqlName": sql_name,
             "columns": columns
         }))
     }
 }
 
+/// Table dependencies tool.
+///
+/// Lists views, foreign keys, triggers and functions that depend on a table,
+/// so the impact of DDL or DML on it can be assessed up front.
+#[derive(Debug)]
+pub struct DependenciesTool {
+    /// Database connection.
+    db: DbConnection,
+}
+
+impl DependenciesTool {
+    /// Create a new table dependencies tool.
+    #[must_use]
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for DependenciesTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "get_table_dependencies".to_string(),
+            description: "List objects that depend on a table: views, foreign keys from other tables, triggers and functions. Use before proposing DDL or DML on a table.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "tableName": {
+                        "type": "string",
+                        "description": "Name of the table to inspect"
+                    },
+                    "schema": {
+                        "type": "string",
+                        "description": "Schema name (defaults to the profile's default schema or 'public')"
+                    }
+                },
+                "required": ["tableName"]
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        _ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: DependenciesToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: "get_table_dependencies".to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+        let schema = args
+            .schema
+            .as_deref()
+            .or(self.db.default_schema())
+            .unwrap_or("public");
+
+        debug!("Listing dependencies of {}.{}", schema, args.table_name);
+
+        let executor = QueryExecutor::new(self.db.clone());
+        let dependencies = executor.table_dependencies(schema, &args.table_name).await?;
+
+        let mut output = serde_json::json!({
+            "tableName": args.table_name,
+            "schema": schema,
+            "dependencies": dependencies
+        });
+        if !dependencies.is_empty() {
+            output["warning"] = serde_json::Value::String(format!(
+                "{} objects depend on {}.{}; changing or dropping it may break them",
+                dependencies.len(),
+                schema,
+                args.table_name
+            ));
+        }
+        Ok(output)
+    }
+}
+
+/// Column lineage tool.
+///
+/// Reports where a column's data comes from, through views, defaults,
+/// foreign keys and triggers, and which views, functions and queries read
+/// it.
+#[derive(Debug)]
+pub struct TraceColumnTool {
+    /// Database connection.
+    db: DbConnection,
+}
+
+impl TraceColumnTool {
+    /// Create a new column lineage tool.
+    #[must_use]
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl Tool for TraceColumnTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "trace_column".to_string(),
+            description: "Trace the lineage of a column: where its data comes from (view definitions down to table columns, defaults, generated expressions, foreign keys, triggers) and which views, foreign keys, functions and frequent queries depend on it. Use for questions like 'where does users.lifetime_value come from?'.".to_string(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "tableName": {
+                        "type": "string",
+                        "description": "Table or view holding the column, optionally schema-qualified"
+                    },
+                    "column": {
+                        "type": "string",
+                        "description": "Column to trace"
+                    },
+                    "schema": {
+                        "type": "string",
+                        "description": "Schema name (defaults to the profile's default schema or 'public')"
+                    }
+                },
+                "required": ["tableName", "column"]
+            }),
+        }
+    }
+
+    async fn execute(
+        &self,
+        args: &serde_json::Value,
+        _ctx: &ToolContext,
+    ) -> Result<serde_json::Value, ToolError> {
+        let args: TraceColumnToolArgs = serde_json::from_value(args.clone())
+            .map_err(|e| ToolError::InvalidArguments {
+                tool_name: "trace_column".to_string(),
+                details: format!("Invalid arguments: {}", e),
+            })?;
+        let (qualifier, table_name) = split_qualified_name(&args.table_name);
+        let schema = qualifier
+            .as_deref()
+            .or(args.schema.as_deref())
+            .or(self.db.default_schema())
+            .unwrap_or("public");
+
+        debug!("Tracing {}.{}.{}", schema, table_name, args.column);
+
+        let lineage = lineage::trace_column(&self.db, schema, &table_name, &args.column).await?;
+        let mut output = serde_json::to_value(&lineage)?;
+        output["summary"] = serde_json::Value::String(lineage.summary());
+        Ok(output)
+    }
+}
+
 /// Explain query tool.
 ///
 /// Returns the query execution plan for a SQL query.
@@ -360,7 +1004,7 @@ impl Tool for ExplainTool {
     async fn execute(
         &self,
         args: &serde_json::Value,
-        _ctx: &ToolContext,
+        ctx: &ToolContext,
     ) -> Result<serde_json::Value, ToolError> {
         let args: ExplainToolArgs = serde_json::from_value(args.clone())
             .map_err(|e| ToolError::InvalidArguments {
@@ -371,7 +1015,7 @@ impl Tool for ExplainTool {
         debug!("Explaining query: {}", args.sql);
 
         // Wrap query in EXPLAIN ANALYZE
-        let explain_sql = format!("EXPLAIN (FORMAT JSON) {}", args.sql);
+        let explain_sql = format!("EXPLAIN (FORMAT JSON) {}", ctx.scope_sql(&args.sql)?);
 
         let executor = QueryExecutor::new(self.db.clone());
         let result = executor.execute_query(&explain_sql).await?;
@@ -392,6 +1036,14 @@ impl Tool for BuiltInTool {
             BuiltInTool::ListTables(tool) => tool.definition(),
             BuiltInTool::DescribeTable(tool) => tool.definition(),
             BuiltInTool::Explain(tool) => tool.definition(),
+            BuiltInTool::Export(tool) => tool.definition(),
+            BuiltInTool::Chart(tool) => tool.definition(),
+            BuiltInTool::Dependencies(tool) => tool.definition(),
+            BuiltInTool::TraceColumn(tool) => tool.definition(),
+            BuiltInTool::KillQuery(tool) => tool.definition(),
+            BuiltInTool::RefreshMatview(tool) => tool.definition(),
+            BuiltInTool::Anomalies(tool) => tool.definition(),
+            BuiltInTool::Compare(tool) => tool.definition(),
         }
     }
 
@@ -406,6 +1058,14 @@ impl Tool for BuiltInTool {
             BuiltInTool::ListTables(tool) => tool.execute(args, ctx).await,
             BuiltInTool::DescribeTable(tool) => tool.execute(args, ctx).await,
             BuiltInTool::Explain(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::Export(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::Chart(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::Dependencies(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::TraceColumn(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::KillQuery(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::RefreshMatview(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::Anomalies(tool) => tool.execute(args, ctx).await,
+            BuiltInTool::Compare(tool) => tool.execute(args, ctx).await,
         }
     }
 }
@@ -418,6 +1078,43 @@ pub fn create_builtin_tools(db: DbConnection) -> Vec<BuiltInTool> {
         BuiltInTool::Schema(SchemaTool::new(db.clone())),
         BuiltInTool::ListTables(ListTablesTool::new(db.clone())),
         BuiltInTool::DescribeTable(DescribeTableTool::new(db.clone())),
-        BuiltInTool::Explain(ExplainTool::new(db)),
+        BuiltInTool::Explain(ExplainTool::new(db.clone())),
+        BuiltInTool::Dependencies(DependenciesTool::new(db.clone())),
+        BuiltInTool::TraceColumn(TraceColumnTool::new(db.clone())),
+        BuiltInTool::KillQuery(KillQueryTool::new(db.clone())),
+        BuiltInTool::RefreshMatview(RefreshMatviewTool::new(db.clone())),
+        BuiltInTool::Anomalies(AnomalyTool::new(db.clone())),
+        BuiltInTool::Export(ExportTool::new(db.clone())),
+        BuiltInTool::Chart(ChartTool::new(db)),
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres_agent_db::DbConnectionConfig;
+    use postgres_agent_safety::{read_audit_log, AuditConfig, AuditLogger};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_query_audit_request_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let config = DbConnectionConfig {
+            url: "postgres://agent@127.0.0.1:1/none".to_string(),
+            connect_timeout: 1,
+            ..DbConnectionConfig::default()
+        };
+        let tool = QueryTool::new(DbConnection::lazy(&config).unwrap());
+        let ctx = ToolContext::with_request_id("req-7".to_string())
+            .with_audit(Arc::new(AuditLogger::new(AuditConfig::with_path(path.clone()))));
+
+        // The database is unreachable; the attempt is still audited
+        let args = serde_json::json!({ "sql": "SELECT 1" });
+        assert!(tool.execute(&args, &ctx).await.is_err());
+        let records = read_audit_log(&path);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].event_type, "query");
+        assert_eq!(records[0].request_id.as_deref(), Some("req-7"));
+    }
+}
@@ -2,6 +2,7 @@
 
 use thiserror::Error;
 use postgres_agent_db::DbError;
+use postgres_agent_safety::ConfirmationLevel;
 
 /// Errors from tool execution.
 #[derive(Debug, Error)]
@@ -13,6 +14,31 @@ pub enum ToolError {
         tool_name: String,
     },
 
+    /// A tool with the same qualified name is already registered.
+    #[error("Tool already registered: {tool_name}")]
+    DuplicateTool {
+        /// Qualified name of the conflicting tool.
+        tool_name: String,
+    },
+
+    /// A bare tool name matches tools in several namespaces.
+    #[error("Tool name {tool_name} is ambiguous; use one of: {}", candidates.join(", "))]
+    AmbiguousTool {
+        /// Bare name that was requested.
+        tool_name: String,
+        /// Qualified names of the matching tools.
+        candidates: Vec<String>,
+    },
+
+    /// The tool's namespace is disabled in configuration.
+    #[error("Tool {tool_name} is disabled (namespace '{namespace}')")]
+    Disabled {
+        /// Qualified tool name.
+        tool_name: String,
+        /// Disabled namespace.
+        namespace: String,
+    },
+
     /// Tool execution failed.
     #[error("Tool execution failed: {reason}")]
     ExecutionFailed {
@@ -31,6 +57,15 @@ pub enum ToolError {
         tool_name: String,
     },
 
+    /// The action needs an approval that is given later; the run pauses.
+    #[error("Confirmation required: {prompt}")]
+    ConfirmationRequired {
+        /// Action to approve.
+        prompt: String,
+        /// Required confirmation level.
+        level: ConfirmationLevel,
+    },
+
     /// Invalid arguments provided to tool.
     #[error("Invalid arguments for tool {tool_name}: {details}")]
     InvalidArguments {
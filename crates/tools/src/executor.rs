@@ -34,7 +34,7 @@ impl ToolExecutor {
         args: &serde_json::Value,
         ctx: &ToolContext,
     ) -> Result<serde_json::Value, ToolError> {
-        trace!("Executing tool: {}", name);
+        trace!(request_id = ?ctx.request_id, "Executing tool: {}", name);
 
         let tool = self.registry.get(name).ok_or_else(|| ToolError::NotFound {
             tool_name: name.to_string(),
@@ -46,11 +46,17 @@ impl ToolExecutor {
 
         match result {
             Ok(value) => {
-                debug!("Tool {} executed successfully in {}ms", name, duration_ms);
+                debug!(
+                    request_id = ?ctx.request_id,
+                    "Tool {} executed successfully in {}ms", name, duration_ms
+                );
                 Ok(value)
             }
             Err(e) => {
-                debug!("Tool {} failed in {}ms: {}", name, duration_ms, e);
+                debug!(
+                    request_id = ?ctx.request_id,
+                    "Tool {} failed in {}ms: {}", name, duration_ms, e
+                );
                 Err(e)
             }
         }
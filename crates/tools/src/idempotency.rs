@@ -0,0 +1,165 @@
+//! Idempotency ledger for tools with side effects.
+//!
+//! A request submitted with an idempotency key records the result of each
+//! confirmed side effect under that key. When the same request is submitted
+//! again, the recorded result is returned instead of running the action a
+//! second time. Entries are appended to a local JSON Lines file.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ToolError;
+
+/// A recorded side effect.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    /// Idempotency key of the request.
+    pub key: String,
+    /// Qualified tool name.
+    pub tool: String,
+    /// Tool arguments.
+    pub arguments: serde_json::Value,
+    /// Tool output.
+    pub result: serde_json::Value,
+    /// When the action ran.
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Append-only store of side effects by idempotency key.
+#[derive(Debug)]
+pub struct IdempotencyLedger {
+    /// Backing JSON Lines file.
+    path: PathBuf,
+    /// Recorded results, loaded on first use.
+    entries: Mutex<Option<HashMap<String, serde_json::Value>>>,
+}
+
+impl IdempotencyLedger {
+    /// Open the ledger at the given path; the file is created on first write.
+    #[must_use]
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            entries: Mutex::new(None),
+        }
+    }
+
+    /// Get the backing file path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Result recorded for a tool call under `key`.
+    ///
+    /// # Errors
+    /// Returns an error if the ledger file cannot be read or parsed.
+    pub fn get(
+        &self,
+        key: &str,
+        tool: &str,
+        arguments: &serde_json::Value,
+    ) -> Result<Option<serde_json::Value>, ToolError> {
+        let mut entries = self.entries.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if entries.is_none() {
+            *entries = Some(self.load()?);
+        }
+        Ok(entries
+            .as_ref()
+            .and_then(|entries| entries.get(&entry_id(key, tool, arguments)))
+            .cloned())
+    }
+
+    /// Record the result of a tool call under `key`.
+    ///
+    /// # Errors
+    /// Returns an error if the ledger file cannot be written.
+    pub fn record(
+        &self,
+        key: &str,
+        tool: &str,
+        arguments: &serde_json::Value,
+        result: &serde_json::Value,
+    ) -> Result<(), ToolError> {
+        let entry = LedgerEntry {
+            key: key.to_string(),
+            tool: tool.to_string(),
+            arguments: arguments.clone(),
+            result: result.clone(),
+            recorded_at: Utc::now(),
+        };
+        let write_error = |e: std::io::Error| ToolError::ExecutionFailed {
+            reason: format!("Failed to write {}: {}", self.path.display(), e),
+        };
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(write_error)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?).map_err(write_error)?;
+
+        let mut entries = self.entries.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Some(entries) = entries.as_mut() {
+            entries.insert(entry_id(key, tool, arguments), entry.result);
+        }
+        Ok(())
+    }
+
+    /// Read the recorded results from the file.
+    fn load(&self) -> Result<HashMap<String, serde_json::Value>, ToolError> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+        let content = std::fs::read_to_string(&self.path).map_err(|e| ToolError::ExecutionFailed {
+            reason: format!("Failed to read {}: {}", self.path.display(), e),
+        })?;
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let entry: LedgerEntry = serde_json::from_str(line)?;
+                Ok((entry_id(&entry.key, &entry.tool, &entry.arguments), entry.result))
+            })
+            .collect()
+    }
+}
+
+/// Lookup key of a tool call; arguments compare by value.
+fn entry_id(key: &str, tool: &str, arguments: &serde_json::Value) -> String {
+    format!("{}\u{0}{}\u{0}{}", key, tool, arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("idempotency.jsonl");
+        let args = serde_json::json!({"path": "out.csv", "format": "csv"});
+        let result = serde_json::json!({"path": "out.csv", "rowCount": 3});
+
+        let ledger = IdempotencyLedger::open(&path);
+        assert_eq!(ledger.get("req-1", "db.export_result", &args).unwrap(), None);
+        ledger.record("req-1", "db.export_result", &args, &result).unwrap();
+        assert_eq!(ledger.get("req-1", "db.export_result", &args).unwrap(), Some(result.clone()));
+
+        // A later process sees the entry; other keys and arguments do not
+        let reopened = IdempotencyLedger::open(&path);
+        let reordered = serde_json::json!({"format": "csv", "path": "out.csv"});
+        assert_eq!(reopened.get("req-1", "db.export_result", &reordered).unwrap(), Some(result));
+        assert_eq!(reopened.get("req-2", "db.export_result", &args).unwrap(), None);
+        let other = serde_json::json!({"path": "other.csv"});
+        assert_eq!(reopened.get("req-1", "db.export_result", &other).unwrap(), None);
+    }
+}
@@ -16,8 +16,8 @@
 //! async fn main() {
 //!     let mut registry = ToolRegistry::default();
 //!
-//!     // Register built-in tools
-//!     // registry.register(BuiltInTool::Query(...));
+//!     // Register built-in tools under the `db` namespace
+//!     // registry.register(BuiltInTool::Query(...))?;
 //!
 //!     let executor = ToolExecutor::new(registry);
 //! }
@@ -28,6 +28,7 @@
 pub mod built_in;
 pub mod error;
 pub mod executor;
+pub mod idempotency;
 pub mod registry;
 pub mod trait_def;
 
@@ -35,8 +36,11 @@ pub mod trait_def;
 pub use built_in::{BuiltInTool, create_builtin_tools};
 pub use error::ToolError;
 pub use executor::ToolExecutor;
+pub use idempotency::IdempotencyLedger;
 pub use registry::ToolRegistry;
-pub use trait_def::{Tool, ToolCall, ToolContext, ToolDefinition, ToolResult};
+pub use trait_def::{
+    Approval, Confirmer, Tool, ToolCall, ToolContext, ToolDefinition, ToolResult, UndoLog,
+};
 
 // Re-export database types for tools
 pub use postgres_agent_db::{DbConnection, QueryExecutor};
@@ -1,6 +1,11 @@
 //! Tool registry.
+//!
+//! Tools are registered under a qualified `namespace.name` such as
+//! `db.execute_query`. Lookups accept either the qualified name or the bare
+//! name when only one namespace provides it, and whole namespaces can be
+//! disabled from configuration.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::trait_def::{Tool, ToolDefinition};
 use crate::BuiltInTool;
@@ -9,45 +14,228 @@ use crate::ToolError;
 /// Registry of available tools for the agent.
 #[derive(Debug, Default)]
 pub struct ToolRegistry {
-    /// Registered tools by name.
+    /// Registered tools by qualified name.
     tools: HashMap<String, BuiltInTool>,
+    /// Namespaces whose tools are hidden and refused.
+    disabled_namespaces: HashSet<String>,
+}
+
+/// Build the qualified name of a tool.
+fn qualified_name(tool: &BuiltInTool) -> String {
+    format!("{}.{}", tool.namespace(), tool.name())
 }
 
 impl ToolRegistry {
     /// Register a new tool.
-    pub fn register(&mut self, tool: BuiltInTool) {
-        self.tools.insert(tool.name().to_string(), tool);
+    ///
+    /// # Errors
+    /// Returns an error if a tool with the same qualified name is already registered.
+    pub fn register(&mut self, tool: BuiltInTool) -> Result<(), ToolError> {
+        let name = qualified_name(&tool);
+        if self.tools.contains_key(&name) {
+            return Err(ToolError::DuplicateTool { tool_name: name });
+        }
+        self.tools.insert(name, tool);
+        Ok(())
+    }
+
+    /// Keep only the tools whose bare name `keep` accepts.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        self.tools.retain(|_, tool| keep(tool.name()));
+    }
+
+    /// Enable or disable every tool in a namespace.
+    pub fn set_namespace_enabled(&mut self, namespace: &str, enabled: bool) {
+        if enabled {
+            self.disabled_namespaces.remove(namespace);
+        } else {
+            self.disabled_namespaces.insert(namespace.to_string());
+        }
+    }
+
+    /// Whether a namespace is enabled.
+    pub fn is_namespace_enabled(&self, namespace: &str) -> bool {
+        !self.disabled_namespaces.contains(namespace)
+    }
+
+    /// Resolve a qualified or bare tool name.
+    ///
+    /// # Errors
+    /// Returns an error if no tool matches, a bare name matches tools in
+    /// several namespaces, or the tool's namespace is disabled.
+    pub fn resolve(&self, name: &str) -> Result<&BuiltInTool, ToolError> {
+        let tool = match self.tools.get(name) {
+            Some(tool) => tool,
+            None => {
+                let mut matches: Vec<&String> = self
+                    .tools
+                    .keys()
+                    .filter(|qualified| qualified.rsplit_once('.').is_some_and(|(_, bare)| bare == name))
+                    .collect();
+                match matches.len() {
+                    0 => {
+                        return Err(ToolError::NotFound {
+                            tool_name: name.to_string(),
+                        });
+                    }
+                    1 => &self.tools[matches[0]],
+                    _ => {
+                        matches.sort();
+                        return Err(ToolError::AmbiguousTool {
+                            tool_name: name.to_string(),
+                            candidates: matches.into_iter().cloned().collect(),
+                        });
+                    }
+                }
+            }
+        };
+
+        if !self.is_namespace_enabled(tool.namespace()) {
+            return Err(ToolError::Disabled {
+                tool_name: qualified_name(tool),
+                namespace: tool.namespace().to_string(),
+            });
+        }
+        Ok(tool)
     }
 
-    /// Get a tool by name.
+    /// Get the qualified name of the tool a name resolves to.
+    pub fn qualified_name(&self, name: &str) -> Option<String> {
+        self.resolve(name).ok().map(qualified_name)
+    }
+
+    /// Get an enabled tool by qualified or bare name.
     pub fn get(&self, name: &str) -> Option<&BuiltInTool> {
-        self.tools.get(name)
+        self.resolve(name).ok()
     }
 
-    /// Check if a tool exists.
+    /// Check if an enabled tool exists.
     pub fn contains(&self, name: &str) -> bool {
-        self.tools.contains_key(name)
+        self.resolve(name).is_ok()
     }
 
-    /// Get all tool definitions.
+    /// Get the definitions of all enabled tools, with qualified names.
     pub fn get_definitions(&self) -> Vec<ToolDefinition> {
-        self.tools.values().map(|t| t.definition()).collect()
+        self.tools
+            .iter()
+            .filter(|(_, tool)| self.is_namespace_enabled(tool.namespace()))
+            .map(|(name, tool)| {
+                let mut definition = tool.definition();
+                definition.name.clone_from(name);
+                definition
+            })
+            .collect()
     }
 
-    /// Execute a tool by name.
+    /// Execute a tool by qualified or bare name.
+    ///
+    /// With an idempotency key, a tool with side effects that already ran
+    /// with the same arguments returns its recorded result instead.
     pub async fn execute(
         &self,
         name: &str,
         args: &serde_json::Value,
         ctx: &crate::ToolContext,
     ) -> Result<serde_json::Value, ToolError> {
-        let tool = self
-            .tools
-            .get(name)
-            .ok_or_else(|| ToolError::NotFound {
-                tool_name: name.to_string(),
-            })?;
-
-        tool.execute(args, ctx).await
+        let tool = self.resolve(name)?;
+        let Some((key, ledger)) =
+            ctx.idempotency.as_ref().filter(|_| tool.has_side_effects(args))
+        else {
+            return tool.execute(args, ctx).await;
+        };
+        let qualified = qualified_name(tool);
+        if let Some(result) = ledger.get(key, &qualified, args)? {
+            tracing::info!("{} already ran for idempotency key {}", qualified, key);
+            return Ok(result);
+        }
+        let result = tool.execute(args, ctx).await?;
+        // The action ran; failing now would invite running it again
+        if let Err(e) = ledger.record(key, &qualified, args, &result) {
+            tracing::warn!("Could not record {} for idempotency key {}: {}", qualified, key, e);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::built_in::{KillQueryTool, ListTablesTool, QueryTool};
+    use crate::{IdempotencyLedger, ToolContext};
+    use postgres_agent_db::{DbConnection, DbConnectionConfig};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// A connection to a database that is not there.
+    fn db() -> DbConnection {
+        let config = DbConnectionConfig {
+            url: "postgres://agent@127.0.0.1:1/none".to_string(),
+            connect_timeout: 1,
+            ..DbConnectionConfig::default()
+        };
+        DbConnection::lazy(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_registration() {
+        let mut registry = ToolRegistry::default();
+        registry.register(BuiltInTool::ListTables(ListTablesTool::new(db()))).unwrap();
+
+        let err = registry.register(BuiltInTool::ListTables(ListTablesTool::new(db())));
+        assert!(matches!(
+            err,
+            Err(ToolError::DuplicateTool { tool_name }) if tool_name == "db.list_tables"
+        ));
+        assert_eq!(registry.get_definitions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_namespace_toggle() {
+        let mut registry = ToolRegistry::default();
+        registry.register(BuiltInTool::ListTables(ListTablesTool::new(db()))).unwrap();
+        registry.register(BuiltInTool::KillQuery(KillQueryTool::new(db()))).unwrap();
+        let names = |registry: &ToolRegistry| {
+            let mut names: Vec<String> =
+                registry.get_definitions().into_iter().map(|d| d.name).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&registry), ["admin.kill_query", "db.list_tables"]);
+
+        // A disabled namespace is neither offered nor run
+        registry.set_namespace_enabled("admin", false);
+        assert_eq!(names(&registry), ["db.list_tables"]);
+        assert!(matches!(
+            registry.resolve("kill_query"),
+            Err(ToolError::Disabled { namespace, .. }) if namespace == "admin"
+        ));
+        assert!(!registry.contains("admin.kill_query"));
+        assert!(registry.contains("list_tables"));
+
+        registry.set_namespace_enabled("admin", true);
+        assert_eq!(registry.qualified_name("kill_query").as_deref(), Some("admin.kill_query"));
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = Arc::new(IdempotencyLedger::open(dir.path().join("ledger.jsonl")));
+        let mut registry = ToolRegistry::default();
+        registry.register(BuiltInTool::Query(QueryTool::new(db()))).unwrap();
+        let ctx = ToolContext::with_timeout(Duration::from_secs(5))
+            .with_idempotency_key("run-1", Arc::clone(&ledger));
+
+        // The mutation ran before under the key, so it is not run again
+        let delete = serde_json::json!({ "sql": "DELETE FROM orders WHERE id = 1" });
+        let recorded = serde_json::json!({ "rowCount": 1 });
+        ledger.record("run-1", "db.execute_query", &delete, &recorded).unwrap();
+        assert_eq!(registry.execute("execute_query", &delete, &ctx).await.unwrap(), recorded);
+
+        // Without the key, or for reads, the tool runs and reaches the database
+        let plain = ToolContext::with_timeout(Duration::from_secs(5));
+        assert!(registry.execute("execute_query", &delete, &plain).await.is_err());
+        let select = serde_json::json!({ "sql": "SELECT count(*) FROM orders" });
+        ledger.record("run-1", "db.execute_query", &select, &recorded).unwrap();
+        assert!(registry.execute("execute_query", &select, &ctx).await.is_err());
     }
 }
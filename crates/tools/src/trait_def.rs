@@ -4,10 +4,23 @@
 //! along with supporting types for tool definitions, calls, results, and context.
 
 use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::FutureExt;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
-use crate::ToolError;
+use crate::built_in::LastResult;
+use crate::{IdempotencyLedger, ToolError};
+use postgres_agent_db::executor::QueryResult;
+use postgres_agent_db::{SandboxMode, Snapshot};
+use postgres_agent_safety::{
+    AuditLogger, ConfirmationLevel, SafetyLevel, SafetyValidator, SelectStarGuard, TenantScope,
+};
 
 /// Tool definition for LLM integration.
 ///
@@ -69,7 +82,7 @@ impl ToolCall {
         Self {
             name,
             arguments,
-            call_id: format!("call-{}", uuid::Uuid::new_v4().to_string().split_once('-').unwrap_or((&"", &"")).0),
+            call_id: format!("call-{}", uuid::Uuid::new_v4().to_string().split_once('-').unwrap_or(("", "")).0),
         }
     }
 }
@@ -123,6 +136,134 @@ impl ToolResult {
     }
 }
 
+/// Approval callback signature: prompt and required level in, a future of
+/// the approval out.
+type ConfirmFn = dyn Fn(&str, ConfirmationLevel) -> BoxFuture<'static, bool> + Send + Sync;
+
+/// Callback that asks the user to approve an action at a given level.
+///
+/// Returns `true` if the action was approved.
+#[derive(Clone)]
+pub struct Confirmer(Arc<ConfirmFn>);
+
+impl Confirmer {
+    /// Wrap a confirmation callback that answers right away.
+    #[must_use]
+    pub fn new(confirm: impl Fn(&str, ConfirmationLevel) -> bool + Send + Sync + 'static) -> Self {
+        Self::new_async(move |prompt, level| std::future::ready(confirm(prompt, level)))
+    }
+
+    /// Wrap a confirmation callback that waits for its answer, e.g. from
+    /// another user.
+    #[must_use]
+    pub fn new_async<F>(
+        confirm: impl Fn(&str, ConfirmationLevel) -> F + Send + Sync + 'static,
+    ) -> Self
+    where
+        F: Future<Output = bool> + Send + 'static,
+    {
+        Self(Arc::new(move |prompt, level| confirm(prompt, level).boxed()))
+    }
+
+    /// A confirmer for `--no-confirm`: approves everything except actions
+    /// that need admin approval, which always require a person.
+    #[must_use]
+    pub fn approve_all() -> Self {
+        Self::new(|_, level| level != ConfirmationLevel::AdminApproval)
+    }
+
+    /// Ask for approval at the given level.
+    pub async fn confirm(&self, prompt: &str, level: ConfirmationLevel) -> bool {
+        (self.0)(prompt, level).await
+    }
+}
+
+impl fmt::Debug for Confirmer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Confirmer")
+    }
+}
+
+/// Undo callback signature: SQL, result and snapshot in, undo ID out.
+type RecordUndoFn =
+    dyn Fn(&str, &QueryResult, Snapshot) -> Result<u64, String> + Send + Sync;
+
+/// Where tables are saved before confirmed mutations, and the callback
+/// that registers the snapshots so that the mutations can be undone.
+#[derive(Clone)]
+pub struct UndoLog {
+    /// Directory snapshots are written to.
+    dir: PathBuf,
+    /// Largest table, in rows, that is saved.
+    max_rows: u64,
+    /// Registers a snapshot, returning the ID that undoes it.
+    record: Arc<RecordUndoFn>,
+}
+
+impl UndoLog {
+    /// Save tables of up to `max_rows` rows to `dir`, registering each
+    /// snapshot with `record`.
+    #[must_use]
+    pub fn new(
+        dir: impl Into<PathBuf>,
+        max_rows: u64,
+        record: impl Fn(&str, &QueryResult, Snapshot) -> Result<u64, String>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self {
+            dir: dir.into(),
+            max_rows,
+            record: Arc::new(record),
+        }
+    }
+
+    /// Get the snapshot directory.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the largest table, in rows, that is saved.
+    #[must_use]
+    pub fn max_rows(&self) -> u64 {
+        self.max_rows
+    }
+
+    /// Register the snapshot taken before `sql`, returning the undo ID.
+    ///
+    /// # Errors
+    /// Returns the message of the callback's error.
+    pub fn record(
+        &self,
+        sql: &str,
+        result: &QueryResult,
+        snapshot: Snapshot,
+    ) -> Result<u64, String> {
+        (self.record)(sql, result, snapshot)
+    }
+}
+
+impl fmt::Debug for UndoLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UndoLog")
+            .field("dir", &self.dir)
+            .field("max_rows", &self.max_rows)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decision on the action a paused run is waiting for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Approval {
+    /// Prompt of the action.
+    pub prompt: String,
+    /// Whether the action was approved.
+    pub approved: bool,
+}
+
 /// Context provided during tool execution.
 ///
 /// Carries execution parameters like timeouts that apply to
@@ -133,6 +274,39 @@ pub struct ToolContext {
     pub timeout: Option<Duration>,
     /// Optional request ID for tracing.
     pub request_id: Option<String>,
+    /// Approval callback for tools with side effects; without one such
+    /// tools refuse to run.
+    pub confirmer: Option<Confirmer>,
+    /// Audit log for queries and privileged tool actions.
+    pub audit: Option<Arc<AuditLogger>>,
+    /// Tenant scoping applied to SQL run by tools.
+    pub tenant: Option<TenantScope>,
+    /// Guard against `SELECT *` on wide or large tables.
+    pub select_star: Option<SelectStarGuard>,
+    /// Validator whose policy rules apply to SQL run by tools.
+    pub policy: Option<Arc<SafetyValidator>>,
+    /// Cancels database work started by tools.
+    pub cancel: CancellationToken,
+    /// Most recent `execute_query` result of the session, read by export
+    /// and chart tools.
+    pub last_result: LastResult,
+    /// Pause the run at actions needing approval instead of asking, so that
+    /// the approval can arrive later, possibly in another process.
+    pub defer_confirmation: bool,
+    /// Decision on the action the run paused for, set when it resumes.
+    pub approval: Option<Approval>,
+    /// Idempotency key of the request and the ledger its side effects are
+    /// recorded in.
+    pub idempotency: Option<(String, Arc<IdempotencyLedger>)>,
+    /// Reject queries that reference a table without its schema.
+    pub qualify_tables: bool,
+    /// Simulate mutations in a sandbox database before asking to run them.
+    pub sandbox: Option<SandboxMode>,
+    /// Save the tables of confirmed mutations so that they can be undone.
+    pub undo: Option<UndoLog>,
+    /// Safety level of the session; admin tools only run at the permissive
+    /// level.
+    pub safety_level: Option<SafetyLevel>,
 }
 
 impl ToolContext {
@@ -142,6 +316,20 @@ impl ToolContext {
         Self {
             timeout: Some(timeout),
             request_id: None,
+            confirmer: None,
+            audit: None,
+            tenant: None,
+            select_star: None,
+            policy: None,
+            cancel: CancellationToken::new(),
+            last_result: LastResult::default(),
+            defer_confirmation: false,
+            approval: None,
+            idempotency: None,
+            qualify_tables: false,
+            sandbox: None,
+            undo: None,
+            safety_level: None,
         }
     }
 
@@ -151,6 +339,172 @@ impl ToolContext {
         Self {
             timeout: None,
             request_id: Some(request_id),
+            confirmer: None,
+            audit: None,
+            tenant: None,
+            select_star: None,
+            policy: None,
+            cancel: CancellationToken::new(),
+            last_result: LastResult::default(),
+            defer_confirmation: false,
+            approval: None,
+            idempotency: None,
+            qualify_tables: false,
+            sandbox: None,
+            undo: None,
+            safety_level: None,
+        }
+    }
+
+    /// Context for a new session: shares configuration such as the
+    /// confirmer and audit log, with its own last result and cancellation.
+    #[must_use]
+    pub fn for_session(&self) -> Self {
+        Self {
+            request_id: None,
+            cancel: CancellationToken::new(),
+            last_result: LastResult::default(),
+            approval: None,
+            ..self.clone()
+        }
+    }
+
+    /// Set the approval callback.
+    #[must_use]
+    pub fn with_confirmer(mut self, confirmer: Confirmer) -> Self {
+        self.confirmer = Some(confirmer);
+        self
+    }
+
+    /// Set the audit log.
+    #[must_use]
+    pub fn with_audit(mut self, audit: Arc<AuditLogger>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Set the tenant scoping rules.
+    #[must_use]
+    pub fn with_tenant(mut self, tenant: TenantScope) -> Self {
+        self.tenant = Some(tenant);
+        self
+    }
+
+    /// Require queries to schema-qualify every table they reference.
+    #[must_use]
+    pub fn with_qualified_tables(mut self) -> Self {
+        self.qualify_tables = true;
+        self
+    }
+
+    /// Set the `SELECT *` guard.
+    #[must_use]
+    pub fn with_select_star_guard(mut self, guard: SelectStarGuard) -> Self {
+        self.select_star = Some(guard);
+        self
+    }
+
+    /// Apply the policy rules of a validator to SQL run by tools.
+    #[must_use]
+    pub fn with_policy(mut self, validator: Arc<SafetyValidator>) -> Self {
+        self.policy = Some(validator);
+        self
+    }
+
+    /// Simulate mutations in a sandbox database and show the effects when
+    /// asking to run them.
+    #[must_use]
+    pub fn with_sandbox(mut self, mode: SandboxMode) -> Self {
+        self.sandbox = Some(mode);
+        self
+    }
+
+    /// Save the tables of confirmed mutations so that they can be undone.
+    #[must_use]
+    pub fn with_undo_log(mut self, log: UndoLog) -> Self {
+        self.undo = Some(log);
+        self
+    }
+
+    /// Set the safety level of the session.
+    #[must_use]
+    pub fn with_safety_level(mut self, level: SafetyLevel) -> Self {
+        self.safety_level = Some(level);
+        self
+    }
+
+    /// Set the token that cancels database work started by tools.
+    #[must_use]
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Record side effects under an idempotency key, replaying recorded
+    /// results instead of running them again.
+    #[must_use]
+    pub fn with_idempotency_key(
+        mut self,
+        key: impl Into<String>,
+        ledger: Arc<IdempotencyLedger>,
+    ) -> Self {
+        self.idempotency = Some((key.into(), ledger));
+        self
+    }
+
+    /// Pause the run at actions needing approval instead of asking.
+    #[must_use]
+    pub fn with_deferred_confirmation(mut self) -> Self {
+        self.defer_confirmation = true;
+        self
+    }
+
+    /// Apply tenant scoping to SQL before running it.
+    ///
+    /// # Errors
+    /// Returns `ToolError::SafetyViolation` if the query reads tenant-scoped
+    /// tables without the tenant filter and cannot be rewritten.
+    pub fn scope_sql(&self, sql: &str) -> Result<String, ToolError> {
+        match &self.tenant {
+            Some(tenant) => tenant
+                .apply(sql)
+                .map_err(|reason| ToolError::SafetyViolation { reason }),
+            None => Ok(sql.to_string()),
+        }
+    }
+
+    /// Ask the user to approve an action; `false` when nobody can be asked.
+    pub async fn confirm(&self, prompt: &str) -> bool {
+        self.confirm_at(prompt, ConfirmationLevel::Simple).await
+    }
+
+    /// Ask for approval at a specific confirmation level.
+    pub async fn confirm_at(&self, prompt: &str, level: ConfirmationLevel) -> bool {
+        match &self.confirmer {
+            Some(confirmer) => confirmer.confirm(prompt, level).await,
+            None => false,
+        }
+    }
+
+    /// Ask for approval, or pause the run if confirmation is deferred and
+    /// no decision on `prompt` has been given yet.
+    ///
+    /// # Errors
+    /// Returns `ToolError::ConfirmationRequired` to pause the run.
+    pub async fn request_approval(
+        &self,
+        prompt: &str,
+        level: ConfirmationLevel,
+    ) -> Result<bool, ToolError> {
+        if !self.defer_confirmation {
+            return Ok(self.confirm_at(prompt, level).await);
+        }
+        match &self.approval {
+            Some(approval) if approval.prompt == prompt => Ok(approval.approved),
+            _ => Err(ToolError::ConfirmationRequired {
+                prompt: prompt.to_string(),
+                level,
+            }),
         }
     }
 }
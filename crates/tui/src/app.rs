@@ -2,11 +2,16 @@
 //!
 //! Provides the terminal UI application with event handling and rendering.
 
+use postgres_agent_core::{AgentEvent, ExecutedSql, Rating};
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    components::{CommandPalette, Input, InputMode},
-    views::ChatView,
+    components::{
+        CommandPalette, ConnectionPicker, ConnectionStatus, Input, InputMode, ProfileEntry,
+        ProfileHealth, SafetyLevel, SchemaStatus, StatusInfo,
+    },
+    views::{ChatMessage, ChatView},
 };
 
 /// TUI errors.
@@ -18,7 +23,10 @@ pub enum TuiError {
 
     /// Event handling failed.
     #[error("Event handling failed: {message}")]
-    EventError { message: String },
+    EventError {
+        /// Error details.
+        message: String,
+    },
 }
 
 /// Result type for TUI operations.
@@ -47,6 +55,8 @@ pub struct PostgresAgentTui {
     input: Input,
     /// Command palette.
     command_palette: CommandPalette,
+    /// Database profile picker.
+    connection_picker: ConnectionPicker,
     /// Current state.
     state: AppState,
     /// Current view mode.
@@ -57,6 +67,35 @@ pub struct PostgresAgentTui {
     safety_level: String,
     /// Quit flag.
     should_quit: bool,
+    /// Rating of the last answer, waiting to be stored.
+    pending_feedback: Option<Rating>,
+    /// Whether the last result was truncated.
+    result_truncated: bool,
+    /// Action on the truncated result, waiting for the host.
+    pending_result_action: Option<ResultAction>,
+    /// Cancels the query being processed.
+    cancel: CancellationToken,
+    /// Tokens used by the last query.
+    tokens: Option<u64>,
+    /// Progress of background schema introspection.
+    schema_status: SchemaStatus,
+    /// Status of the database connection.
+    connection: ConnectionStatus,
+    /// Whether the host should check the health of every profile.
+    pending_health_check: bool,
+    /// Profile to switch to, waiting for the host.
+    pending_switch: Option<String>,
+}
+
+/// Actions offered on a truncated query result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultAction {
+    /// Fetch the next page.
+    NextPage,
+    /// Fetch every remaining row.
+    ShowAll,
+    /// Export every row to a file.
+    Export,
 }
 
 /// View modes.
@@ -92,11 +131,21 @@ impl PostgresAgentTui {
             chat_view: ChatView::new(),
             input: Input::with_placeholder("Ask about your database..."),
             command_palette: CommandPalette::new(),
+            connection_picker: ConnectionPicker::new(),
             state: AppState::Waiting,
             view_mode: ViewMode::Chat,
             profile: "default".to_string(),
             safety_level: "balanced".to_string(),
             should_quit: false,
+            pending_feedback: None,
+            result_truncated: false,
+            pending_result_action: None,
+            cancel: CancellationToken::new(),
+            tokens: None,
+            schema_status: SchemaStatus::default(),
+            connection: ConnectionStatus::default(),
+            pending_health_check: false,
+            pending_switch: None,
         }
     }
 
@@ -106,6 +155,7 @@ impl PostgresAgentTui {
         let mut tui = Self::new();
         tui.profile = profile.into();
         tui.safety_level = safety.into();
+        tui.connection_picker.set_current(tui.profile.as_str());
         tui
     }
 
@@ -122,6 +172,9 @@ impl PostgresAgentTui {
 
     /// Handle input character.
     pub fn handle_input(&mut self, c: char) {
+        if self.connection_picker.is_visible() {
+            return;
+        }
         if self.command_palette.is_visible() {
             let mut query = self.command_palette.search_query().to_string();
             query.push(c);
@@ -150,7 +203,17 @@ impl PostgresAgentTui {
 
     /// Handle special key.
     pub fn handle_special_key(&mut self, key: &str) {
-        match key.as_ref() {
+        if self.connection_picker.is_visible() {
+            match key {
+                "Enter" => self.switch_to_selected_profile(),
+                "Esc" => self.connection_picker.hide(),
+                "ArrowUp" | "Up" => self.connection_picker.move_up(),
+                "ArrowDown" | "Down" => self.connection_picker.move_down(),
+                _ => {}
+            }
+            return;
+        }
+        match key {
             "Enter" => {
                 if self.command_palette.is_visible() {
                     self.handle_command_palette_selection();
@@ -218,9 +281,23 @@ impl PostgresAgentTui {
             'p' if self.input.mode() == InputMode::Normal => {
                 self.command_palette.show();
             }
+            'o' if self.input.mode() == InputMode::Normal => self.show_connection_picker(),
             'q' if self.input.mode() == InputMode::Normal => {
                 self.should_quit = true;
             }
+            'u' if self.input.mode() == InputMode::Normal => self.rate_last_answer(Rating::Up),
+            'd' if self.input.mode() == InputMode::Normal => self.rate_last_answer(Rating::Down),
+            'n' if self.input.mode() == InputMode::Normal => {
+                self.request_result_action(ResultAction::NextPage);
+            }
+            'a' if self.input.mode() == InputMode::Normal => {
+                self.request_result_action(ResultAction::ShowAll);
+            }
+            'e' if self.input.mode() == InputMode::Normal => {
+                self.request_result_action(ResultAction::Export);
+            }
+            't' if self.input.mode() == InputMode::Normal => self.chat_view.toggle_reasoning(),
+            'x' => self.cancel_query(),
             'i' => self.input.set_mode(InputMode::Insert),
             _ => {}
         }
@@ -253,24 +330,206 @@ impl PostgresAgentTui {
             "query_clear" => {
                 self.input.clear();
             }
+            "feedback_up" => self.rate_last_answer(Rating::Up),
+            "feedback_down" => self.rate_last_answer(Rating::Down),
+            "results_next_page" => self.request_result_action(ResultAction::NextPage),
+            "results_show_all" => self.request_result_action(ResultAction::ShowAll),
+            "results_export" => self.request_result_action(ResultAction::Export),
+            "query_cancel" => self.cancel_query(),
+            "view_toggle_reasoning" => self.chat_view.toggle_reasoning(),
             "db_refresh" => {
                 self.chat_view.add_assistant_message("Refreshing database schema...");
             }
+            "db_switch" => self.show_connection_picker(),
             _ => {
                 self.chat_view
-                    .add_assistant_message(&format!("Selected: {}", cmd));
+                    .add_assistant_message(format!("Selected: {}", cmd));
             }
         }
     }
 
-    /// Add an assistant response to the chat.
+    /// Show the connection picker and ask the host to check the health of
+    /// every profile.
+    fn show_connection_picker(&mut self) {
+        self.command_palette.hide();
+        self.connection_picker.show();
+        self.pending_health_check = true;
+        let names: Vec<String> =
+            self.connection_picker.profiles().iter().map(|p| p.name.clone()).collect();
+        for name in names {
+            self.connection_picker.set_health(&name, ProfileHealth::Checking);
+        }
+    }
+
+    /// Ask the host to switch to the profile selected in the picker.
+    fn switch_to_selected_profile(&mut self) {
+        let Some(name) = self.connection_picker.selected().map(|p| p.name.clone()) else {
+            return;
+        };
+        self.connection_picker.hide();
+        if name == self.profile {
+            return;
+        }
+        if self.state == AppState::Processing {
+            self.chat_view.add_assistant_message("Wait for the running query to finish first.");
+            return;
+        }
+        self.chat_view.add_message(ChatMessage::system(format!("Connecting to {}...", name)));
+        self.connection = ConnectionStatus::Connecting;
+        self.pending_switch = Some(name);
+    }
+
+    /// Set the configured profiles listed by the connection picker.
+    pub fn set_profiles(&mut self, profiles: Vec<ProfileEntry>) {
+        self.connection_picker.set_profiles(profiles);
+    }
+
+    /// Record the health of a profile, as checked by the host.
+    pub fn set_profile_health(&mut self, name: &str, health: ProfileHealth) {
+        self.connection_picker.set_health(name, health);
+    }
+
+    /// Whether the picker was opened since the last call; the host then
+    /// checks every profile and reports with [`Self::set_profile_health`].
+    pub fn take_health_check(&mut self) -> bool {
+        std::mem::take(&mut self.pending_health_check)
+    }
+
+    /// Take the profile to switch to since the last call, if any.
+    ///
+    /// The host reconnects, refreshes the schema cache and resets the
+    /// safety context, then reports with [`Self::finish_profile_switch`].
+    pub fn take_profile_switch(&mut self) -> Option<String> {
+        self.pending_switch.take()
+    }
+
+    /// Record the outcome of a profile switch: the new profile and its
+    /// safety level, or why connecting failed (the old connection stays).
+    pub fn finish_profile_switch(&mut self, result: Result<(String, String), String>) {
+        match result {
+            Ok((profile, safety)) => {
+                self.chat_view
+                    .add_message(ChatMessage::system(format!("Connected to {}.", profile)));
+                self.connection_picker.set_current(profile.as_str());
+                self.profile = profile;
+                self.safety_level = safety;
+                self.connection = ConnectionStatus::Connected;
+                self.schema_status = SchemaStatus::Loading;
+                self.tokens = None;
+                self.result_truncated = false;
+                self.pending_result_action = None;
+            }
+            Err(message) => {
+                self.chat_view.add_message(ChatMessage::system(format!(
+                    "Failed to switch connection: {}",
+                    message
+                )));
+                self.connection = ConnectionStatus::Connected;
+            }
+        }
+    }
+
+    /// Record the status of the database connection.
+    pub fn set_connection_status(&mut self, status: ConnectionStatus) {
+        self.connection = status;
+    }
+
+    /// Rate the last answer; the host stores it via [`Self::take_feedback`].
+    fn rate_last_answer(&mut self, rating: Rating) {
+        self.pending_feedback = Some(rating);
+        self.chat_view
+            .add_assistant_message(format!("Feedback recorded: {}", rating.emoji()));
+    }
+
+    /// Take the rating given since the last call, if any.
+    pub fn take_feedback(&mut self) -> Option<Rating> {
+        self.pending_feedback.take()
+    }
+
+    /// Mark whether the last result was truncated, offering paging actions.
+    pub fn set_result_truncated(&mut self, truncated: bool) {
+        self.result_truncated = truncated;
+        if truncated {
+            self.chat_view.add_assistant_message(
+                "Result truncated: Ctrl+N next page, Ctrl+A show all, Ctrl+E export",
+            );
+        }
+    }
+
+    /// Ask the host to run `action` on the truncated result.
+    fn request_result_action(&mut self, action: ResultAction) {
+        if self.result_truncated {
+            self.pending_result_action = Some(action);
+        } else {
+            self.chat_view.add_assistant_message("No truncated result to page through.");
+        }
+    }
+
+    /// Take the result action requested since the last call, if any.
+    pub fn take_result_action(&mut self) -> Option<ResultAction> {
+        self.pending_result_action.take()
+    }
+
+    /// Add an assistant response to the chat, replacing the text streamed
+    /// for it.
     pub fn add_assistant_message(&mut self, content: impl Into<String>) {
-        self.chat_view.add_assistant_message(content);
+        self.chat_view.finish_answer(content);
         self.state = AppState::Waiting;
     }
 
-    /// Set processing state.
+    /// Show progress of the running query as collapsible reasoning messages,
+    /// and the answer as it is written.
+    pub fn handle_agent_event(&mut self, event: AgentEvent) {
+        // Keep the loading indicator below the progress
+        let loading = self.chat_view.messages().last().is_some_and(|m| m.is_loading);
+        self.chat_view.remove_loading();
+        let content = match event {
+            AgentEvent::AnswerDelta { delta, .. } => {
+                self.chat_view.append_answer(&delta);
+                None
+            }
+            AgentEvent::Reasoning { thought, .. } => Some(thought),
+            AgentEvent::ToolCall { name, .. } => Some(format!("Running {}", name)),
+            AgentEvent::Confirmation { prompt, status, .. } => {
+                Some(format!("Confirmation {}: {}", status.as_str(), prompt))
+            }
+        };
+        if let Some(content) = content {
+            // Text streamed before other progress was not the answer
+            self.chat_view.demote_answer();
+            self.chat_view.add_reasoning_message(content);
+        }
+        if loading {
+            self.chat_view.add_loading();
+        }
+    }
+
+    /// Show the SQL statements a run executed, one line each.
+    pub fn add_executed_sql(&mut self, statements: &[ExecutedSql]) {
+        for statement in statements {
+            self.chat_view.add_message(ChatMessage::system(format!("SQL: {}", statement)));
+        }
+    }
+
+    /// Token for the query being processed; pass it to the agent run.
+    #[must_use]
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Cancel the query being processed, if any.
+    fn cancel_query(&mut self) {
+        if self.state == AppState::Processing && !self.cancel.is_cancelled() {
+            self.cancel.cancel();
+            self.chat_view.add_assistant_message("Cancelling query...");
+        }
+    }
+
+    /// Set processing state; starting a query gets a fresh cancellation token.
     pub fn set_processing(&mut self, is_processing: bool) {
+        if is_processing {
+            self.cancel = CancellationToken::new();
+        }
         self.state = if is_processing {
             AppState::Processing
         } else {
@@ -278,8 +537,10 @@ impl PostgresAgentTui {
         };
     }
 
-    /// Set error state.
-    pub fn set_error(&mut self, _message: impl Into<String>) {
+    /// Set error state, showing the message (e.g. a highlighted SQL error).
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.chat_view.demote_answer();
+        self.chat_view.add_message(ChatMessage::system(message));
         self.state = AppState::Error;
     }
 
@@ -327,6 +588,12 @@ impl PostgresAgentTui {
         &mut self.command_palette
     }
 
+    /// Get the connection picker.
+    #[must_use]
+    pub fn connection_picker(&self) -> &ConnectionPicker {
+        &self.connection_picker
+    }
+
     /// Check if should quit.
     #[must_use]
     pub fn should_quit(&self) -> bool {
@@ -356,6 +623,31 @@ impl PostgresAgentTui {
     pub fn safety_level(&self) -> &str {
         &self.safety_level
     }
+
+    /// Record the tokens used by the last query.
+    pub fn set_tokens(&mut self, tokens: u64) {
+        self.tokens = Some(tokens);
+    }
+
+    /// Record the progress of background schema introspection.
+    pub fn set_schema_status(&mut self, status: SchemaStatus) {
+        self.schema_status = status;
+    }
+
+    /// Status bar contents.
+    #[must_use]
+    pub fn status_info(&self) -> StatusInfo {
+        let info = StatusInfo::new()
+            .with_profile(self.profile.as_str())
+            .with_connection(self.connection)
+            .with_safety(SafetyLevel::from(self.safety_level.as_str()))
+            .with_view_mode(self.view_mode.to_string())
+            .with_schema(self.schema_status.clone());
+        match self.tokens {
+            Some(tokens) => info.with_tokens(tokens),
+            None => info,
+        }
+    }
 }
 
 impl Default for PostgresAgentTui {
@@ -390,6 +682,41 @@ mod tests {
 
         tui.set_processing(false);
         assert_eq!(tui.state(), AppState::Waiting);
+
+        tui.set_processing(true);
+        let cancel = tui.cancellation_token();
+        tui.handle_control_key('x');
+        assert!(cancel.is_cancelled());
+        tui.set_processing(true);
+        assert!(!tui.cancellation_token().is_cancelled());
+    }
+
+    #[test]
+    fn test_streamed_answer_events() {
+        let mut tui = PostgresAgentTui::new();
+        tui.chat_view_mut().add_loading();
+        let delta = |delta: &str| AgentEvent::AnswerDelta {
+            iteration: 1,
+            delta: delta.to_string(),
+        };
+        tui.handle_agent_event(delta("Let me check"));
+        tui.handle_agent_event(AgentEvent::ToolCall {
+            iteration: 1,
+            name: "list_tables".to_string(),
+        });
+        tui.handle_agent_event(delta("3 "));
+        tui.handle_agent_event(delta("tables"));
+
+        let messages = tui.chat_view().messages();
+        assert_eq!(messages.len(), 4);
+        assert!(messages[0].is_reasoning && messages[1].is_reasoning);
+        assert_eq!(messages[2].content, "3 tables");
+        assert!(messages[3].is_loading);
+
+        tui.chat_view_mut().remove_loading();
+        tui.add_assistant_message("3 tables.");
+        assert_eq!(tui.chat_view().len(), 3);
+        assert_eq!(tui.chat_view().last_assistant_message(), Some("3 tables."));
     }
 
     #[test]
@@ -412,8 +739,57 @@ mod tests {
         tui.handle_command("nav_results");
         assert_eq!(tui.view_mode(), ViewMode::Results);
 
+        // Test feedback
+        tui.handle_control_key('u');
+        assert_eq!(tui.take_feedback(), Some(Rating::Up));
+        tui.handle_command("feedback_down");
+        assert_eq!(tui.take_feedback(), Some(Rating::Down));
+        assert_eq!(tui.take_feedback(), None);
+
+        // Test result paging
+        tui.handle_command("results_next_page");
+        assert_eq!(tui.take_result_action(), None);
+        tui.set_result_truncated(true);
+        tui.handle_control_key('n');
+        assert_eq!(tui.take_result_action(), Some(ResultAction::NextPage));
+        tui.handle_command("results_export");
+        assert_eq!(tui.take_result_action(), Some(ResultAction::Export));
+
         // Test quit
         tui.handle_command("app_quit");
         assert!(tui.should_quit());
     }
+
+    #[test]
+    fn test_profile_switch() {
+        let mut tui = PostgresAgentTui::with_profile("default", "balanced");
+        tui.set_profiles(vec![
+            ProfileEntry::new("default", "postgres://localhost/app"),
+            ProfileEntry::new("staging", "postgres://staging/app"),
+        ]);
+
+        tui.handle_control_key('o');
+        assert!(tui.connection_picker().is_visible());
+        assert!(tui.take_health_check());
+        assert!(!tui.take_health_check());
+
+        // Input goes to the picker while it is open
+        tui.handle_input('x');
+        assert!(tui.current_query().is_none());
+        tui.handle_special_key("Down");
+        tui.handle_special_key("Enter");
+        assert!(!tui.connection_picker().is_visible());
+        assert_eq!(tui.take_profile_switch(), Some("staging".to_string()));
+        assert_eq!(tui.status_info().connection, ConnectionStatus::Connecting);
+
+        tui.finish_profile_switch(Ok(("staging".to_string(), "read_only".to_string())));
+        assert_eq!(tui.status_info().profile, "staging");
+        assert_eq!(tui.status_info().connection, ConnectionStatus::Connected);
+        assert_eq!(tui.connection_picker().current(), "staging");
+
+        // Picking the profile in use does nothing
+        tui.handle_command("db_switch");
+        tui.handle_special_key("Enter");
+        assert_eq!(tui.take_profile_switch(), None);
+    }
 }
@@ -87,6 +87,13 @@ impl CommandPalette {
                 "Ctrl+S",
                 "Navigation",
             ),
+            Command::new(
+                "view_toggle_reasoning",
+                "Toggle Reasoning",
+                "Expand or collapse the agent's Thinking messages",
+                "Ctrl+T",
+                "Navigation",
+            ),
             // Query
             Command::new(
                 "query_execute",
@@ -102,6 +109,49 @@ impl CommandPalette {
                 "Esc",
                 "Query",
             ),
+            Command::new(
+                "query_cancel",
+                "Cancel Query",
+                "Stop the running query and its LLM and database calls",
+                "Ctrl+X",
+                "Query",
+            ),
+            Command::new(
+                "feedback_up",
+                "Good Answer",
+                "Rate the last answer 👍",
+                "Ctrl+U",
+                "Query",
+            ),
+            Command::new(
+                "feedback_down",
+                "Bad Answer",
+                "Rate the last answer 👎",
+                "Ctrl+D",
+                "Query",
+            ),
+            // Results
+            Command::new(
+                "results_next_page",
+                "Next Page",
+                "Show the next page of a truncated result",
+                "Ctrl+N",
+                "Results",
+            ),
+            Command::new(
+                "results_show_all",
+                "Show All Rows",
+                "Fetch every row of a truncated result",
+                "Ctrl+A",
+                "Results",
+            ),
+            Command::new(
+                "results_export",
+                "Export Result",
+                "Export every row of a truncated result",
+                "Ctrl+E",
+                "Results",
+            ),
             // Database
             Command::new(
                 "db_refresh",
@@ -110,6 +160,13 @@ impl CommandPalette {
                 "Ctrl+F5",
                 "Database",
             ),
+            Command::new(
+                "db_switch",
+                "Switch Connection",
+                "Connect to another database profile",
+                "Ctrl+O",
+                "Database",
+            ),
             // Application
             Command::new(
                 "app_quit",
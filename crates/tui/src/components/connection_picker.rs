@@ -0,0 +1,230 @@
+//! Connection picker for switching database profiles.
+//!
+//! Lists the configured profiles with their health, as last checked by the
+//! host, and marks the one in use.
+
+use std::fmt;
+
+/// Health of a profile's database.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ProfileHealth {
+    /// Not checked yet.
+    #[default]
+    Unknown,
+    /// Check in progress.
+    Checking,
+    /// Reachable.
+    Healthy {
+        /// Round trip of the check in milliseconds.
+        latency_ms: u64,
+    },
+    /// Not reachable.
+    Unreachable {
+        /// Why the check failed.
+        reason: String,
+    },
+}
+
+impl ProfileHealth {
+    /// One-character indicator.
+    #[must_use]
+    pub fn indicator(&self) -> &'static str {
+        match self {
+            Self::Unknown => "?",
+            Self::Checking => "…",
+            Self::Healthy { .. } => "●",
+            Self::Unreachable { .. } => "✗",
+        }
+    }
+}
+
+impl fmt::Display for ProfileHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown => write!(f, "unknown"),
+            Self::Checking => write!(f, "checking..."),
+            Self::Healthy { latency_ms } => write!(f, "{}ms", latency_ms),
+            Self::Unreachable { reason } => write!(f, "unreachable: {}", reason),
+        }
+    }
+}
+
+/// A configured database profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileEntry {
+    /// Profile name.
+    pub name: String,
+    /// Where it connects, with the password redacted.
+    pub target: String,
+    /// Last known health.
+    pub health: ProfileHealth,
+}
+
+impl ProfileEntry {
+    /// Create an entry whose health is not known yet.
+    #[must_use]
+    pub fn new(name: impl Into<String>, target: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            target: target.into(),
+            health: ProfileHealth::Unknown,
+        }
+    }
+}
+
+/// Connection picker state.
+#[derive(Debug, Default)]
+pub struct ConnectionPicker {
+    /// Configured profiles.
+    profiles: Vec<ProfileEntry>,
+    /// Profile in use.
+    current: String,
+    /// Selection index.
+    selected_index: usize,
+    /// Whether the picker is visible.
+    is_visible: bool,
+}
+
+impl ConnectionPicker {
+    /// Create an empty picker.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the listed profiles.
+    pub fn set_profiles(&mut self, profiles: Vec<ProfileEntry>) {
+        self.profiles = profiles;
+        self.selected_index = self.selected_index.min(self.profiles.len().saturating_sub(1));
+    }
+
+    /// Set the profile in use.
+    pub fn set_current(&mut self, name: impl Into<String>) {
+        self.current = name.into();
+    }
+
+    /// Record the health of a profile.
+    pub fn set_health(&mut self, name: &str, health: ProfileHealth) {
+        if let Some(entry) = self.profiles.iter_mut().find(|p| p.name == name) {
+            entry.health = health;
+        }
+    }
+
+    /// Show the picker with the profile in use selected.
+    pub fn show(&mut self) {
+        self.is_visible = true;
+        self.selected_index =
+            self.profiles.iter().position(|p| p.name == self.current).unwrap_or(0);
+    }
+
+    /// Hide the picker.
+    pub fn hide(&mut self) {
+        self.is_visible = false;
+    }
+
+    /// Check if visible.
+    #[must_use]
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    /// Move selection up.
+    pub fn move_up(&mut self) {
+        self.selected_index = self.selected_index.saturating_sub(1);
+    }
+
+    /// Move selection down.
+    pub fn move_down(&mut self) {
+        if self.selected_index < self.profiles.len().saturating_sub(1) {
+            self.selected_index += 1;
+        }
+    }
+
+    /// Get the selected profile.
+    #[must_use]
+    pub fn selected(&self) -> Option<&ProfileEntry> {
+        self.profiles.get(self.selected_index)
+    }
+
+    /// Get selected index.
+    #[must_use]
+    pub fn selected_index(&self) -> usize {
+        self.selected_index
+    }
+
+    /// Get the listed profiles.
+    #[must_use]
+    pub fn profiles(&self) -> &[ProfileEntry] {
+        &self.profiles
+    }
+
+    /// Get the profile in use.
+    #[must_use]
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+}
+
+impl fmt::Display for ConnectionPicker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Switch Connection (Enter to connect, Esc to close)")?;
+        writeln!(f, "---")?;
+
+        for (idx, profile) in self.profiles.iter().enumerate() {
+            let prefix = if idx == self.selected_index { ">" } else { " " };
+            let current = if profile.name == self.current { " (current)" } else { "" };
+            writeln!(
+                f,
+                "{} {} {}{} | {} | {}",
+                prefix,
+                profile.health.indicator(),
+                profile.name,
+                current,
+                profile.target,
+                profile.health
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> ConnectionPicker {
+        let mut picker = ConnectionPicker::new();
+        picker.set_profiles(vec![
+            ProfileEntry::new("default", "postgres://localhost/app"),
+            ProfileEntry::new("staging", "postgres://staging/app"),
+        ]);
+        picker.set_current("staging");
+        picker
+    }
+
+    #[test]
+    fn test_show_selects_current() {
+        let mut picker = picker();
+        picker.show();
+        assert!(picker.is_visible());
+        assert_eq!(picker.selected().map(|p| p.name.as_str()), Some("staging"));
+
+        picker.move_down();
+        assert_eq!(picker.selected_index(), 1);
+        picker.move_up();
+        picker.move_up();
+        assert_eq!(picker.selected_index(), 0);
+    }
+
+    #[test]
+    fn test_health_display() {
+        let mut picker = picker();
+        picker.set_health("default", ProfileHealth::Healthy { latency_ms: 3 });
+        picker.set_health("staging", ProfileHealth::Unreachable { reason: "timeout".into() });
+        picker.show();
+
+        let display = picker.to_string();
+        assert!(display.contains("  ● default | postgres://localhost/app | 3ms"));
+        assert!(display.contains("> ✗ staging (current) | postgres://staging/app | unreachable"));
+    }
+}
@@ -1,9 +1,11 @@
 //! TUI components module.
 
 pub mod command_palette;
+pub mod connection_picker;
 pub mod input;
 pub mod status_bar;
 
 pub use command_palette::{Command, CommandPalette};
+pub use connection_picker::{ConnectionPicker, ProfileEntry, ProfileHealth};
 pub use input::{Input, InputMode};
-pub use status_bar::{SafetyLevel, StatusBar, StatusInfo, ConnectionStatus};
+pub use status_bar::{SafetyLevel, SchemaStatus, StatusBar, StatusInfo, ConnectionStatus};
@@ -29,23 +29,46 @@ impl fmt::Display for ConnectionStatus {
     }
 }
 
+/// Progress of schema introspection.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum SchemaStatus {
+    /// Introspection has not started.
+    #[default]
+    NotLoaded,
+    /// Introspection is running in the background.
+    Loading,
+    /// Schema is cached.
+    Loaded {
+        /// Number of tables found.
+        tables: usize,
+    },
+    /// Introspection failed.
+    Failed,
+}
+
+impl fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotLoaded => write!(f, "Schema not loaded"),
+            Self::Loading => write!(f, "Loading schema..."),
+            Self::Loaded { tables } => write!(f, "{} tables", tables),
+            Self::Failed => write!(f, "Schema error"),
+        }
+    }
+}
+
 /// Safety level indicator.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SafetyLevel {
     /// Read-only mode.
     ReadOnly,
     /// Balanced mode (requires confirmation).
+    #[default]
     Balanced,
     /// Permissive mode (minimal checks).
     Permissive,
 }
 
-impl Default for SafetyLevel {
-    fn default() -> Self {
-        Self::Balanced
-    }
-}
-
 impl fmt::Display for SafetyLevel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -84,6 +107,10 @@ pub struct StatusInfo {
     pub view_mode: String,
     /// Agent iteration count.
     pub iterations: u32,
+    /// Tokens used by the last run.
+    pub tokens: Option<u64>,
+    /// Schema introspection progress.
+    pub schema: SchemaStatus,
 }
 
 impl StatusInfo {
@@ -134,6 +161,18 @@ impl StatusInfo {
         self.iterations = iterations;
         self
     }
+
+    /// Set the token count.
+    pub fn with_tokens(mut self, tokens: u64) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    /// Set the schema introspection progress.
+    pub fn with_schema(mut self, schema: SchemaStatus) -> Self {
+        self.schema = schema;
+        self
+    }
 }
 
 /// Status bar widget (UI-agnostic).
@@ -177,14 +216,19 @@ impl fmt::Display for StatusBar {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "[{}] | {} | {} | {}ms | {} rows | {} iter",
+            "[{}] | {} | {} | {}ms | {} rows | {} iter | {} tokens",
             self.info.profile,
             self.info.connection,
             self.info.safety,
             self.info.last_execution_time.unwrap_or(0),
             self.info.rows.unwrap_or(0),
             self.info.iterations,
-        )
+            self.info.tokens.unwrap_or(0),
+        )?;
+        if self.info.schema != SchemaStatus::NotLoaded {
+            write!(f, " | {}", self.info.schema)?;
+        }
+        Ok(())
     }
 }
 
@@ -224,11 +268,19 @@ mod tests {
     fn test_status_bar_display() {
         let info = StatusInfo::new()
             .with_profile("test")
-            .with_connection(ConnectionStatus::Connected);
+            .with_connection(ConnectionStatus::Connected)
+            .with_tokens(1234);
 
         let bar = StatusBar::with_info(info);
         let display = bar.to_string();
         assert!(display.contains("test"));
         assert!(display.contains("Connected"));
+        assert!(display.contains("1234 tokens"));
+        assert!(!display.contains("Schema"));
+
+        let bar = StatusBar::with_info(StatusInfo::new().with_schema(SchemaStatus::Loading));
+        assert!(bar.to_string().ends_with("| Loading schema..."));
+        let loaded = SchemaStatus::Loaded { tables: 42 };
+        assert_eq!(loaded.to_string(), "42 tables");
     }
 }
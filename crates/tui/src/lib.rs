@@ -6,8 +6,12 @@
 
 pub mod app;
 pub mod components;
+pub mod terminal;
 pub mod views;
 
-pub use app::{AppState, PostgresAgentTui, TuiError, TuiResult, ViewMode};
-pub use components::{Command, CommandPalette, Input, InputMode, SafetyLevel, StatusBar, StatusInfo, ConnectionStatus};
+pub use app::{AppState, PostgresAgentTui, ResultAction, TuiError, TuiResult, ViewMode};
+pub use components::{
+    Command, CommandPalette, ConnectionPicker, ConnectionStatus, Input, InputMode, ProfileEntry,
+    ProfileHealth, SafetyLevel, SchemaStatus, StatusBar, StatusInfo,
+};
 pub use views::{ChatMessage, ChatView};
@@ -0,0 +1,28 @@
+//! Terminal state management.
+//!
+//! Restores the terminal if the process exits abnormally while the TUI has
+//! it in raw mode on the alternate screen. Crossterm keeps the mode it
+//! replaced when entering raw mode and puts it back on leaving.
+
+use std::io::IsTerminal;
+
+use ratatui::crossterm::{
+    cursor, execute,
+    style::ResetColor,
+    terminal::{disable_raw_mode, LeaveAlternateScreen},
+};
+
+/// Leave raw mode and the alternate screen, show the cursor and reset
+/// colors.
+///
+/// Safe to call from a panic hook: failures are ignored.
+pub fn restore_terminal() {
+    let _ = disable_raw_mode();
+
+    // Escape sequences would corrupt piped output
+    let mut stdout = std::io::stdout();
+    if !stdout.is_terminal() {
+        return;
+    }
+    let _ = execute!(stdout, LeaveAlternateScreen, cursor::Show, ResetColor);
+}
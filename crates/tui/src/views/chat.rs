@@ -52,6 +52,17 @@ impl ChatMessage {
         }
     }
 
+    /// Create a new system message.
+    #[must_use]
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            role: MessageRole::System,
+            is_reasoning: false,
+            is_loading: false,
+        }
+    }
+
     /// Create a loading placeholder.
     #[must_use]
     pub fn loading() -> Self {
@@ -88,6 +99,10 @@ pub struct ChatView {
     scroll_offset: usize,
     /// Whether auto-scroll is enabled.
     auto_scroll: bool,
+    /// Whether reasoning messages show their content.
+    show_reasoning: bool,
+    /// Index of the answer being streamed.
+    streaming: Option<usize>,
 }
 
 impl ChatView {
@@ -127,17 +142,45 @@ impl ChatView {
 
     /// Remove the loading indicator.
     pub fn remove_loading(&mut self) {
-        if let Some(last) = self.messages.last() {
-            if last.is_loading {
-                self.messages.pop();
+        if let Some(last) = self.messages.last()
+            && last.is_loading
+        {
+            self.messages.pop();
+        }
+    }
+
+    /// Append text to the answer being streamed, starting one if needed.
+    pub fn append_answer(&mut self, delta: &str) {
+        match self.streaming.and_then(|i| self.messages.get_mut(i)) {
+            Some(answer) => answer.content.push_str(delta),
+            None => {
+                self.streaming = Some(self.messages.len());
+                self.add_assistant_message(delta);
             }
         }
     }
 
+    /// Show the final answer, in place of the streamed text if any.
+    pub fn finish_answer(&mut self, content: impl Into<String>) {
+        match self.streaming.take().and_then(|i| self.messages.get_mut(i)) {
+            Some(answer) => answer.content = content.into(),
+            None => self.add_assistant_message(content),
+        }
+    }
+
+    /// Turn the streamed text into a reasoning message, for models that
+    /// go on to call a tool or runs that fail.
+    pub fn demote_answer(&mut self) {
+        if let Some(answer) = self.streaming.take().and_then(|i| self.messages.get_mut(i)) {
+            answer.is_reasoning = true;
+        }
+    }
+
     /// Clear all messages.
     pub fn clear(&mut self) {
         self.messages.clear();
         self.scroll_offset = 0;
+        self.streaming = None;
     }
 
     /// Scroll up by one line.
@@ -203,6 +246,17 @@ impl ChatView {
         self.scroll_offset
     }
 
+    /// Expand or collapse reasoning messages.
+    pub fn toggle_reasoning(&mut self) {
+        self.show_reasoning = !self.show_reasoning;
+    }
+
+    /// Whether reasoning messages show their content.
+    #[must_use]
+    pub fn shows_reasoning(&self) -> bool {
+        self.show_reasoning
+    }
+
     /// Get the last assistant message content.
     #[must_use]
     pub fn last_assistant_message(&self) -> Option<&str> {
@@ -247,6 +301,8 @@ impl fmt::Display for ChatView {
 
             if msg.is_loading {
                 writeln!(f, "{} ...", role_prefix)?;
+            } else if msg.is_reasoning && !self.show_reasoning {
+                writeln!(f, "Thinking…")?;
             } else {
                 writeln!(f, "{}{}", role_prefix, msg.content)?;
             }
@@ -274,6 +330,36 @@ mod tests {
         assert!(thinking.is_reasoning);
     }
 
+    #[test]
+    fn test_reasoning_collapsed() {
+        let mut view = ChatView::new();
+        view.add_reasoning_message("Check the orders table");
+        assert_eq!(view.to_string(), "Thinking…\n");
+
+        view.toggle_reasoning();
+        assert_eq!(view.to_string(), "Thinking: Check the orders table\n");
+    }
+
+    #[test]
+    fn test_streamed_answer() {
+        let mut view = ChatView::new();
+        view.append_answer("Checking");
+        view.demote_answer();
+        view.append_answer("There are ");
+        view.add_loading();
+        view.append_answer("3 users");
+        assert_eq!(view.len(), 3);
+        assert!(view.messages()[0].is_reasoning);
+        assert_eq!(view.messages()[1].content, "There are 3 users");
+
+        view.remove_loading();
+        view.finish_answer("There are 3 users.");
+        assert_eq!(view.len(), 2);
+        assert_eq!(view.last_assistant_message(), Some("There are 3 users."));
+        view.finish_answer("Done");
+        assert_eq!(view.len(), 3);
+    }
+
     #[test]
     fn test_chat_view_operations() {
         let mut view = ChatView::new();
@@ -0,0 +1,151 @@
+//! Encoding of persisted files: compression, then encryption.
+//!
+//! A file may mix plain text, zstd frames and encrypted frames, so changing
+//! the storage settings never strands data written before. Reading an
+//! encrypted frame needs the key it was sealed with.
+
+use std::io;
+use std::path::Path;
+
+use crate::compress::{self, Compression};
+use crate::encrypt::{self, EncryptionKey};
+
+/// How persisted files are written and read.
+#[derive(Debug, Clone, Default)]
+pub struct FileCodec {
+    /// Compression of new data.
+    compression: Compression,
+    /// Key sealing new data and opening encrypted frames.
+    key: Option<EncryptionKey>,
+}
+
+impl FileCodec {
+    /// Write with the given compression, unencrypted.
+    #[must_use]
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            compression,
+            key: None,
+        }
+    }
+
+    /// Encrypt new data with `key`.
+    #[must_use]
+    pub fn with_key(mut self, key: EncryptionKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Whether new data is encrypted.
+    #[must_use]
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Encode `data` for writing.
+    ///
+    /// # Errors
+    /// Returns an error if compression or encryption fails.
+    pub fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let data = self.compression.encode(data)?;
+        match &self.key {
+            Some(key) => key.seal(&data),
+            None => Ok(data),
+        }
+    }
+
+    /// Replace the file at `path` with `data`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        std::fs::write(path, self.encode(data)?)
+    }
+
+    /// Append `data` to the file at `path` as a record of its own, creating
+    /// the file if needed.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn append(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        use std::io::Write as _;
+
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&self.encode(data)?)
+    }
+
+    /// Decode file content written with any settings.
+    ///
+    /// # Errors
+    /// Returns an error if a frame is corrupt, or is encrypted and cannot be
+    /// opened with this codec's key.
+    pub fn decode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(data.len());
+        let mut rest = data;
+        while !rest.is_empty() {
+            let len = if encrypt::is_frame(rest) {
+                let Some(key) = &self.key else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "data is encrypted; enable storage.encrypt to read it",
+                    ));
+                };
+                let len = encrypt::frame_len(rest).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "truncated encrypted record")
+                })?;
+                out.extend(compress::decode(&key.open(&rest[..len])?)?);
+                len
+            } else if let Some(len) = compress::frame_len(rest)? {
+                out.extend(compress::decode(&rest[..len])?);
+                len
+            } else {
+                // Plain text up to the next frame of either kind
+                let len = [compress::find_frame(rest), encrypt::find_frame(rest)]
+                    .into_iter()
+                    .flatten()
+                    .min()
+                    .unwrap_or(rest.len());
+                out.extend_from_slice(&rest[..len]);
+                len
+            };
+            rest = &rest[len..];
+        }
+        Ok(out)
+    }
+
+    /// Read a file as text.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read or decoded, or is not UTF-8.
+    pub fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.decode(&std::fs::read(path)?)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let key = EncryptionKey::generate();
+        let zstd = Compression::Zstd { level: 3 };
+
+        FileCodec::default().append(&path, b"plain\n").unwrap();
+        FileCodec::new(zstd).append(&path, b"compressed\n").unwrap();
+        let encrypted = FileCodec::new(zstd).with_key(key.clone());
+        encrypted.append(&path, b"sealed\n").unwrap();
+        FileCodec::new(Compression::None).with_key(key).append(&path, b"last\n").unwrap();
+
+        assert_eq!(
+            encrypted.read_to_string(&path).unwrap(),
+            "plain\ncompressed\nsealed\nlast\n"
+        );
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(6).any(|w| w == b"sealed"));
+        assert!(FileCodec::new(zstd).read_to_string(&path).is_err());
+    }
+}
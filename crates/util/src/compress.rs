@@ -0,0 +1,135 @@
+//! Transparent zstd compression of persisted files.
+//!
+//! Files may hold plain text, zstd frames, or both: appending a compressed
+//! record to a plain file, or a plain one to a compressed file, keeps it
+//! readable. Readers detect frames by their magic number, which cannot occur
+//! in UTF-8 text, so turning compression on or off never strands old data.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Magic number starting every zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// How files are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Plain text.
+    #[default]
+    None,
+    /// zstd frames at the given level.
+    Zstd {
+        /// Compression level, 1 (fastest) to 19.
+        level: i32,
+    },
+}
+
+impl Compression {
+    /// Encode `data` for writing.
+    ///
+    /// # Errors
+    /// Returns an error if compression fails.
+    pub fn encode(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Zstd { level } => zstd::encode_all(data, level),
+        }
+    }
+
+    /// Replace the file at `path` with `data`.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn write(self, path: &Path, data: &[u8]) -> io::Result<()> {
+        std::fs::write(path, self.encode(data)?)
+    }
+
+    /// Append `data` to the file at `path`, creating it if needed.
+    ///
+    /// Compressed data is appended as a frame of its own.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written.
+    pub fn append(self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&self.encode(data)?)
+    }
+}
+
+/// Decode file content made of plain text and zstd frames.
+///
+/// # Errors
+/// Returns an error if a frame is corrupt.
+pub fn decode(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while !rest.is_empty() {
+        if let Some(size) = frame_len(rest)? {
+            out.extend(zstd::decode_all(&rest[..size])?);
+            rest = &rest[size..];
+        } else {
+            let plain = find_frame(rest).unwrap_or(rest.len());
+            out.extend_from_slice(&rest[..plain]);
+            rest = &rest[plain..];
+        }
+    }
+    Ok(out)
+}
+
+/// Length of the zstd frame at the start of `data`, or `None` if it does
+/// not start with one.
+///
+/// # Errors
+/// Returns an error if the frame is corrupt or truncated.
+pub fn frame_len(data: &[u8]) -> io::Result<Option<usize>> {
+    if !data.starts_with(&ZSTD_MAGIC) {
+        return Ok(None);
+    }
+    zstd::zstd_safe::find_frame_compressed_size(data)
+        .map(Some)
+        .map_err(|code| {
+            io::Error::new(io::ErrorKind::InvalidData, zstd::zstd_safe::get_error_name(code))
+        })
+}
+
+/// Position of the next zstd frame in plain text, if any.
+#[must_use]
+pub fn find_frame(data: &[u8]) -> Option<usize> {
+    data.windows(ZSTD_MAGIC.len()).position(|window| window == ZSTD_MAGIC)
+}
+
+/// Read a file written with any [`Compression`] as text.
+///
+/// # Errors
+/// Returns an error if the file cannot be read or decoded, or is not UTF-8.
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    String::from_utf8(decode(&std::fs::read(path)?)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_plain_and_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.jsonl");
+        let zstd = Compression::Zstd { level: 3 };
+
+        Compression::None.append(&path, b"{\"id\":1}\n").unwrap();
+        zstd.append(&path, b"{\"id\":2}\n").unwrap();
+        zstd.append(&path, b"{\"id\":3}\n").unwrap();
+        Compression::None.append(&path, "{\"name\":\"µ\"}\n".as_bytes()).unwrap();
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            "{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n{\"name\":\"µ\"}\n"
+        );
+
+        let text = "x".repeat(10_000);
+        zstd.write(&path, text.as_bytes()).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() < 1_000);
+        assert_eq!(read_to_string(&path).unwrap(), text);
+    }
+}
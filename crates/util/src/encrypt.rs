@@ -0,0 +1,249 @@
+//! At-rest encryption of persisted files.
+//!
+//! Data is sealed with AES-256-GCM under a key kept in the OS keyring or in
+//! a key file. Every sealed record is a frame of its own, made of a magic
+//! number, its length, a random nonce and the ciphertext with its tag, so
+//! frames can be appended to a file one record at a time.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+
+use crate::crypto::Secret;
+
+/// Magic number starting every frame; 0xC1 never occurs in UTF-8 text.
+const MAGIC: [u8; 4] = [0xC1, b'P', b'G', b'E'];
+
+/// Length of the random nonce of a frame.
+const NONCE_LEN: usize = 12;
+
+/// Bytes before the ciphertext: magic, ciphertext length and nonce.
+const HEADER_LEN: usize = MAGIC.len() + 4 + NONCE_LEN;
+
+/// Keyring service and entry holding the key.
+const KEYRING_SERVICE: &str = "pg-agent";
+const KEYRING_USER: &str = "storage-key";
+
+/// Error loading or creating an encryption key.
+#[derive(Debug, thiserror::Error)]
+pub enum KeyError {
+    /// The OS keyring is unavailable or refused access.
+    #[error("OS keyring: {reason}")]
+    Keyring {
+        /// Reason reported by the keyring.
+        reason: String,
+    },
+
+    /// The key file cannot be read or written.
+    #[error("Key file {path}: {reason}")]
+    File {
+        /// Key file path.
+        path: PathBuf,
+        /// Reason for the failure.
+        reason: String,
+    },
+
+    /// The stored key is not a base64 encoded 256-bit key.
+    #[error("Invalid encryption key: {reason}")]
+    Invalid {
+        /// Reason the key was rejected.
+        reason: String,
+    },
+}
+
+/// Where the encryption key is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    /// The OS keyring: Keychain, Credential Manager or Secret Service.
+    Keyring,
+    /// A file holding the base64 encoded key, readable only by its owner.
+    File(PathBuf),
+}
+
+/// A 256-bit AES-GCM key, zeroized on drop.
+#[derive(Debug, Clone)]
+pub struct EncryptionKey(Secret<[u8; 32]>);
+
+impl EncryptionKey {
+    /// Generate a random key.
+    #[must_use]
+    pub fn generate() -> Self {
+        Self(Secret::new(Aes256Gcm::generate_key(OsRng).into()))
+    }
+
+    /// Parse a base64 encoded key.
+    ///
+    /// # Errors
+    /// Returns `KeyError::Invalid` if the text is not 32 base64 encoded bytes.
+    pub fn from_base64(text: &str) -> Result<Self, KeyError> {
+        let bytes = STANDARD.decode(text.trim()).map_err(|e| KeyError::Invalid {
+            reason: e.to_string(),
+        })?;
+        let key: [u8; 32] = bytes.try_into().map_err(|bytes: Vec<u8>| KeyError::Invalid {
+            reason: format!("expected 32 bytes, got {}", bytes.len()),
+        })?;
+        Ok(Self(Secret::new(key)))
+    }
+
+    /// The key encoded as base64.
+    #[must_use]
+    pub fn to_base64(&self) -> Secret<String> {
+        Secret::new(STANDARD.encode(self.0.expose()))
+    }
+
+    /// Load the key from `source`, creating and storing a new one on first
+    /// use.
+    ///
+    /// # Errors
+    /// Returns an error if the key cannot be read, stored or parsed.
+    pub fn load_or_create(source: &KeySource) -> Result<Self, KeyError> {
+        match source {
+            KeySource::Keyring => {
+                let keyring_error = |e: keyring::Error| KeyError::Keyring {
+                    reason: e.to_string(),
+                };
+                let entry =
+                    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(keyring_error)?;
+                match entry.get_password() {
+                    Ok(text) => Self::from_base64(&text),
+                    Err(keyring::Error::NoEntry) => {
+                        let key = Self::generate();
+                        entry.set_password(key.to_base64().expose()).map_err(keyring_error)?;
+                        Ok(key)
+                    }
+                    Err(e) => Err(keyring_error(e)),
+                }
+            }
+            KeySource::File(path) => {
+                let file_error = |e: io::Error| KeyError::File {
+                    path: path.clone(),
+                    reason: e.to_string(),
+                };
+                match std::fs::read_to_string(path) {
+                    Ok(text) => Self::from_base64(&text),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                        let key = Self::generate();
+                        write_private(path, key.to_base64().expose()).map_err(file_error)?;
+                        Ok(key)
+                    }
+                    Err(e) => Err(file_error(e)),
+                }
+            }
+        }
+    }
+
+    /// Encrypt `data` into a frame.
+    ///
+    /// # Errors
+    /// Returns an error if the data is too large for one frame.
+    pub fn seal(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, data)
+            .map_err(|_| invalid("encryption failed"))?;
+        let len = u32::try_from(ciphertext.len()).map_err(|_| invalid("record too large"))?;
+
+        let mut frame = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        frame.extend_from_slice(&MAGIC);
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(&nonce);
+        frame.extend_from_slice(&ciphertext);
+        Ok(frame)
+    }
+
+    /// Decrypt a frame made by [`Self::seal`].
+    ///
+    /// # Errors
+    /// Returns an error if the frame is malformed, was sealed with another
+    /// key or was tampered with.
+    pub fn open(&self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        let len = frame_len(frame).ok_or_else(|| invalid("truncated encrypted record"))?;
+        let nonce = Nonce::from_slice(&frame[MAGIC.len() + 4..HEADER_LEN]);
+        self.cipher()
+            .decrypt(nonce, &frame[HEADER_LEN..len])
+            .map_err(|_| invalid("cannot decrypt record: wrong key or corrupt data"))
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(self.0.expose()))
+    }
+}
+
+/// Whether `data` starts with an encrypted frame.
+#[must_use]
+pub fn is_frame(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Position of the next encrypted frame in `data`, if any.
+#[must_use]
+pub fn find_frame(data: &[u8]) -> Option<usize> {
+    data.windows(MAGIC.len()).position(|window| window == MAGIC)
+}
+
+/// Total length of the frame at the start of `data`, if it is complete.
+#[must_use]
+pub fn frame_len(data: &[u8]) -> Option<usize> {
+    if !is_frame(data) || data.len() < HEADER_LEN {
+        return None;
+    }
+    let len = u32::from_le_bytes(data[MAGIC.len()..MAGIC.len() + 4].try_into().ok()?);
+    let total = HEADER_LEN + len as usize;
+    (data.len() >= total).then_some(total)
+}
+
+/// Write `text` to a new file only its owner can read.
+fn write_private(path: &Path, text: &str) -> io::Result<()> {
+    use std::io::Write as _;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt as _;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(text.as_bytes())
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let key = EncryptionKey::generate();
+        let frame = key.seal(b"{\"question\":\"top customers\"}\n").unwrap();
+        assert!(is_frame(&frame));
+        assert_eq!(frame_len(&frame), Some(frame.len()));
+        assert_eq!(key.open(&frame).unwrap(), b"{\"question\":\"top customers\"}\n");
+
+        assert!(EncryptionKey::generate().open(&frame).is_err());
+        let mut tampered = frame.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(key.open(&tampered).is_err());
+        assert_eq!(frame_len(&frame[..frame.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = KeySource::File(dir.path().join("storage.key"));
+        let key = EncryptionKey::load_or_create(&source).unwrap();
+        let again = EncryptionKey::load_or_create(&source).unwrap();
+        assert_eq!(key.to_base64().expose(), again.to_base64().expose());
+        assert!(EncryptionKey::from_base64("c2hvcnQ=").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! SQL identifier handling.
+//!
+//! PostgreSQL folds unquoted identifiers to lower case, so a table created
+//! as `"UserEvents"` must be written quoted, exactly as introspection
+//! reports it. These helpers quote names only where needed and split
+//! schema-qualified names that may contain quoted parts.
+
+/// Reserved key words that cannot be used as unquoted identifiers.
+const RESERVED: &[&str] = &[
+    "all", "analyse", "analyze", "and", "any", "array", "as", "asc", "asymmetric",
+    "authorization", "binary", "both", "case", "cast", "check", "collate", "collation",
+    "column", "concurrently", "constraint", "create", "cross", "current_catalog",
+    "current_date", "current_role", "current_schema", "current_time", "current_timestamp",
+    "current_user", "default", "deferrable", "desc", "distinct", "do", "else", "end",
+    "except", "false", "fetch", "for", "foreign", "freeze", "from", "full", "grant",
+    "group", "having", "ilike", "in", "initially", "inner", "intersect", "into", "is",
+    "isnull", "join", "lateral", "leading", "left", "like", "limit", "localtime",
+    "localtimestamp", "natural", "not", "notnull", "null", "offset", "on", "only", "or",
+    "order", "outer", "overlaps", "placing", "primary", "references", "returning", "right",
+    "select", "session_user", "similar", "some", "symmetric", "system_user", "table",
+    "tablesample", "then", "to", "trailing", "true", "union", "unique", "user", "using",
+    "variadic", "verbose", "when", "where", "window", "with",
+];
+
+/// Whether an unquoted identifier can spell `name`: lower-case letters,
+/// digits, `_` and `$`, not starting with a digit or `$`.
+#[must_use]
+pub fn folds_unquoted(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$')
+}
+
+/// Quote `name` for use in SQL if it would not survive unquoted.
+#[must_use]
+pub fn quote_ident(name: &str) -> String {
+    if folds_unquoted(name) && !RESERVED.contains(&name) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Quote a schema-qualified name for use in SQL.
+#[must_use]
+pub fn quote_qualified(schema: &str, name: &str) -> String {
+    format!("{}.{}", quote_ident(schema), quote_ident(name))
+}
+
+/// Split a possibly schema-qualified table name such as `analytics.events`
+/// or `"Analytics"."UserEvents"` into its schema and table.
+///
+/// Quoted parts are unquoted; unquoted parts are kept as written, since
+/// tools take names exactly as introspection reports them. A name that does
+/// not parse is returned whole as the table.
+#[must_use]
+pub fn split_qualified_name(name: &str) -> (Option<String>, String) {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quoted = false;
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                part.push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(std::mem::take(&mut part)),
+            c => part.push(c),
+        }
+    }
+    parts.push(part);
+    if quoted || parts.iter().any(String::is_empty) {
+        return (None, name.to_string());
+    }
+    match <[String; 2]>::try_from(parts) {
+        Ok([schema, table]) => (Some(schema), table),
+        Err(mut parts) if parts.len() == 1 => (None, parts.remove(0)),
+        Err(_) => (None, name.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("orders"), "orders");
+        assert_eq!(quote_ident("UserEvents"), "\"UserEvents\"");
+        assert_eq!(quote_ident("order"), "\"order\"");
+        assert_eq!(quote_ident("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(quote_qualified("Billing", "invoices"), "\"Billing\".invoices");
+        assert!(!folds_unquoted("2fa"));
+    }
+
+    #[test]
+    fn test_split_qualified_name() {
+        assert_eq!(
+            split_qualified_name("analytics.events"),
+            (Some("analytics".to_string()), "events".to_string())
+        );
+        assert_eq!(split_qualified_name("events"), (None, "events".to_string()));
+        assert_eq!(split_qualified_name("\"UserEvents\""), (None, "UserEvents".to_string()));
+        assert_eq!(
+            split_qualified_name("\"Billing\".\"Odd.\"\"Name\"\"\""),
+            (Some("Billing".to_string()), "Odd.\"Name\"".to_string())
+        );
+        assert_eq!(split_qualified_name(".events"), (None, ".events".to_string()));
+        assert_eq!(split_qualified_name("a.b.c"), (None, "a.b.c".to_string()));
+    }
+}
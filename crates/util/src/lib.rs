@@ -4,6 +4,12 @@
 //! secret handling, and other helper functions.
 
 pub mod logger;
+pub mod codec;
+pub mod compress;
 pub mod crypto;
+pub mod encrypt;
+pub mod ident;
+pub mod number;
 pub mod result;
+pub mod retention;
 pub mod time;
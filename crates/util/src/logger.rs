@@ -3,24 +3,85 @@
 //! Provides structured logging using `tracing` with sensible defaults
 //! for development and production environments.
 
+use std::collections::VecDeque;
+use std::fmt::Write as _;
 use std::path::PathBuf;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock};
 
-use tracing::Level;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
 use tracing_appender::non_blocking::WorkerGuard;
-use tracing_subscriber::fmt::{self, time::UtcTime};
+use tracing_subscriber::fmt::{self, time::UtcTime, writer::BoxMakeWriter};
+use tracing_subscriber::layer::{Context, Layer};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
 
 /// Global logger guard for non-blocking appender
 static LOGGER_GUARD: OnceLock<WorkerGuard> = OnceLock::new();
 
+/// Number of recent log events kept in memory for crash reports.
+const RECENT_EVENTS_CAPACITY: usize = 200;
+
+/// Most recent log events, oldest first.
+static RECENT_EVENTS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Layer that keeps the most recent events in memory.
+struct RecentEventsLayer;
+
+impl<S: Subscriber> Layer<S> for RecentEventsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = FieldCollector::default();
+        event.record(&mut fields);
+
+        let line = format!(
+            "{} {} {}: {}",
+            chrono::Utc::now().to_rfc3339(),
+            event.metadata().level(),
+            event.metadata().target(),
+            fields.0
+        );
+
+        if let Ok(mut events) = RECENT_EVENTS.lock() {
+            if events.len() == RECENT_EVENTS_CAPACITY {
+                events.pop_front();
+            }
+            events.push_back(line);
+        }
+    }
+}
+
+/// Formats event fields as `message key=value ...`.
+#[derive(Default)]
+struct FieldCollector(String);
+
+impl Visit for FieldCollector {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Get the most recent log events, oldest first.
+#[must_use]
+pub fn recent_events() -> Vec<String> {
+    RECENT_EVENTS
+        .lock()
+        .map(|events| events.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
 /// Log file configuration
 #[derive(Debug, Clone)]
 pub struct LogConfig {
     /// Log level (trace, debug, info, warn, error)
     pub level: String,
-    /// Custom log file path (None = stdout only)
+    /// Custom log file path (None = stderr only)
     pub log_file: Option<PathBuf>,
     /// Whether to enable JSON logging for production
     pub json_format: bool,
@@ -56,7 +117,8 @@ pub fn setup_logger(config: &LogConfig) -> Result<(), Box<dyn std::error::Error>
         || std::env::var("PG_AGENT_LOG_JSON")
             .is_ok_and(|v| v.to_lowercase() == "true");
 
-    if let Some(ref log_path) = config.log_file {
+    // Logs go to stderr so they never mix with command output on stdout
+    let writer = if let Some(ref log_path) = config.log_file {
         // Ensure the parent directory exists
         if let Some(parent) = log_path.parent() {
             std::fs::create_dir_all(parent)?;
@@ -69,32 +131,38 @@ pub fn setup_logger(config: &LogConfig) -> Result<(), Box<dyn std::error::Error>
             .open(log_path)?;
 
         let (non_blocking, guard) = tracing_appender::non_blocking(file);
+        LOGGER_GUARD.set(guard).ok();
+        BoxMakeWriter::new(non_blocking)
+    } else {
+        BoxMakeWriter::new(std::io::stderr)
+    };
+
+    let layer = fmt::layer()
+        .with_writer(writer)
+        .with_timer(UtcTime::rfc_3339());
 
+    if json_format {
+        // Span fields (e.g. request_id) are emitted alongside each event
         let subscriber = tracing_subscriber::registry()
             .with(env_filter)
+            .with(RecentEventsLayer)
             .with(
-                fmt::layer()
-                    .with_writer(non_blocking)
-                    .with_timer(UtcTime::rfc_3339()),
+                layer
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(false),
             );
-
         tracing::subscriber::set_global_default(subscriber)?;
-
-        LOGGER_GUARD.set(guard).ok();
     } else {
-        // stdout-only logging
         let subscriber = tracing_subscriber::registry()
             .with(env_filter)
-            .with(
-                fmt::layer()
-                    .with_timer(UtcTime::rfc_3339()),
-            );
-
+            .with(RecentEventsLayer)
+            .with(layer);
         tracing::subscriber::set_global_default(subscriber)?;
-    };
+    }
 
     // Log startup message
-    tracing::info!(
+    tracing::debug!(
         level = %config.level,
         json = json_format,
         "Logger initialized"
@@ -167,6 +235,19 @@ mod tests {
         assert!(parse_log_level("invalid").is_err());
     }
 
+    #[test]
+    fn test_recent_events_layer() {
+        let subscriber = tracing_subscriber::registry().with(RecentEventsLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(rows = 3, "crash-report-marker");
+        });
+
+        let events = recent_events();
+        let line = events.iter().find(|e| e.contains("crash-report-marker")).unwrap();
+        assert!(line.contains("INFO"));
+        assert!(line.contains("rows=3"));
+    }
+
     #[test]
     fn test_log_config_defaults() {
         let config = LogConfig::default();
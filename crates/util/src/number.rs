@@ -0,0 +1,158 @@
+//! Display formatting of numbers in human-facing output.
+//!
+//! Numbers are formatted from their decimal text rather than through
+//! `f64`, so `numeric` values keep every digit; rounding is half away
+//! from zero.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// How numbers are shown in tables and reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberFormat {
+    /// Exactly as the database returned them.
+    #[default]
+    Raw,
+    /// With thousands separators, rounded to `precision` decimals if set.
+    Human {
+        /// Decimal places to show.
+        precision: Option<usize>,
+    },
+}
+
+impl NumberFormat {
+    /// Use `precision` decimal places in human format.
+    #[must_use]
+    pub fn with_precision(self, precision: Option<usize>) -> Self {
+        match self {
+            Self::Raw => Self::Raw,
+            Self::Human { .. } => Self::Human { precision },
+        }
+    }
+
+    /// Format the decimal text of a number; other text is returned as is.
+    #[must_use]
+    pub fn render(self, text: &str) -> String {
+        match self {
+            Self::Raw => text.to_string(),
+            Self::Human { precision } => {
+                humanize(text, precision).unwrap_or_else(|| text.to_string())
+            }
+        }
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "raw" => Ok(Self::Raw),
+            "human" => Ok(Self::Human { precision: None }),
+            other => Err(format!("Unknown number format '{}' (expected human or raw)", other)),
+        }
+    }
+}
+
+impl fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Raw => write!(f, "raw"),
+            Self::Human { .. } => write!(f, "human"),
+        }
+    }
+}
+
+/// Group the integer digits of a plain decimal and round its fraction;
+/// `None` for anything else, such as exponents, `NaN` or non-numbers.
+fn humanize(text: &str, precision: Option<usize>) -> Option<String> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if int.is_empty() || !is_digits(int) || !is_digits(frac) {
+        return None;
+    }
+
+    let (int, frac) = match precision {
+        Some(places) => round(int, frac, places),
+        None => (int.to_string(), frac.to_string()),
+    };
+    let mut out = String::new();
+    if negative && (int.bytes().chain(frac.bytes())).any(|b| b != b'0') {
+        out.push('-');
+    }
+    for (i, digit) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    if !frac.is_empty() {
+        out.push('.');
+        out.push_str(&frac);
+    }
+    Some(out)
+}
+
+/// Round `int.frac` to `places` decimals, padding with zeros if needed.
+fn round(int: &str, frac: &str, places: usize) -> (String, String) {
+    if frac.len() <= places {
+        return (int.to_string(), format!("{:0<places$}", frac));
+    }
+    let mut digits: Vec<u8> = int.bytes().chain(frac.bytes().take(places)).collect();
+    if frac.as_bytes()[places] >= b'5' {
+        let mut carry = true;
+        for digit in digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                carry = false;
+                break;
+            }
+        }
+        if carry {
+            digits.insert(0, b'1');
+        }
+    }
+    let frac = digits.split_off(digits.len() - places);
+    // Both halves are ASCII digits
+    (
+        String::from_utf8(digits).unwrap_or_default(),
+        String::from_utf8(frac).unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_human_format() {
+        let human = NumberFormat::Human { precision: None };
+        assert_eq!(human.render("1234567"), "1,234,567");
+        assert_eq!(human.render("-1234.5678"), "-1,234.5678");
+        assert_eq!(human.render("999"), "999");
+        assert_eq!(human.render("1e20"), "1e20");
+        assert_eq!(human.render("NaN"), "NaN");
+        assert_eq!(human.render("12-34"), "12-34");
+        assert_eq!(NumberFormat::Raw.render("1234567"), "1234567");
+    }
+
+    #[test]
+    fn test_precision() {
+        let two = NumberFormat::Human { precision: Some(2) };
+        assert_eq!(two.render("1234.567"), "1,234.57");
+        assert_eq!(two.render("999999.995"), "1,000,000.00");
+        assert_eq!(two.render("42"), "42.00");
+        assert_eq!(two.render("-0.001"), "0.00");
+        assert_eq!(NumberFormat::Human { precision: Some(0) }.render("2.5"), "3");
+        let parsed: NumberFormat = "human".parse().unwrap();
+        assert_eq!(parsed.with_precision(Some(2)), two);
+        assert_eq!(NumberFormat::Raw.with_precision(Some(2)), NumberFormat::Raw);
+        assert!("pretty".parse::<NumberFormat>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Cleanup and size reporting of persisted files.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Limits on how long and how much persisted data is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RetentionPolicy {
+    /// Age after which data is removed.
+    pub max_age: Option<Duration>,
+    /// Total bytes kept; the oldest data is removed first.
+    pub max_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Whether the policy removes anything at all.
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        self.max_age.is_none() && self.max_bytes.is_none()
+    }
+
+    /// Of items with a modification time and size, the indices to keep:
+    /// the newest ones within both limits.
+    #[must_use]
+    pub fn keep(&self, items: &[(SystemTime, u64)]) -> Vec<usize> {
+        let now = SystemTime::now();
+        let mut order: Vec<usize> = (0..items.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(items[i].0));
+
+        let mut total = 0u64;
+        let mut keep: Vec<usize> = order
+            .into_iter()
+            .take_while(|&i| {
+                let (modified, size) = items[i];
+                let age = now.duration_since(modified).unwrap_or_default();
+                total = total.saturating_add(size);
+                self.max_age.is_none_or(|max| age <= max)
+                    && self.max_bytes.is_none_or(|max| total <= max)
+            })
+            .collect();
+        keep.sort_unstable();
+        keep
+    }
+
+    /// Remove the files among `files` that fall outside the policy,
+    /// returning how many were removed.
+    ///
+    /// # Errors
+    /// Returns an error if a file cannot be inspected or removed.
+    pub fn prune(&self, files: &[PathBuf]) -> io::Result<usize> {
+        if self.is_unlimited() {
+            return Ok(0);
+        }
+        let items = files
+            .iter()
+            .map(|path| {
+                let meta = std::fs::metadata(path)?;
+                Ok((meta.modified()?, meta.len()))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        let keep = self.keep(&items);
+        let mut removed = 0;
+        for (i, path) in files.iter().enumerate() {
+            if keep.binary_search(&i).is_err() {
+                std::fs::remove_file(path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Bytes taken by a file, or by all files under a directory; 0 if it does
+/// not exist.
+#[must_use]
+pub fn disk_usage(path: &Path) -> u64 {
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.is_dir() => std::fs::read_dir(path)
+            .map(|entries| entries.filter_map(Result::ok).map(|e| disk_usage(&e.path())).sum())
+            .unwrap_or(0),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Format a byte count with a binary unit, e.g. `1.5 MiB`.
+#[must_use]
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keep_newest_within_limits() {
+        let now = SystemTime::now();
+        let days = |n: u64| now - Duration::from_secs(n * 86_400);
+        let items = [(days(10), 100), (days(1), 100), (days(0), 100), (days(2), 100)];
+
+        let by_age = RetentionPolicy {
+            max_age: Some(Duration::from_secs(5 * 86_400)),
+            max_bytes: None,
+        };
+        assert_eq!(by_age.keep(&items), [1, 2, 3]);
+
+        let by_size = RetentionPolicy {
+            max_age: None,
+            max_bytes: Some(250),
+        };
+        assert_eq!(by_size.keep(&items), [1, 2]);
+        assert_eq!(RetentionPolicy::default().keep(&items), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+}